@@ -1,12 +1,61 @@
 use std::io::{self, Write};
 
-use crate::cli::{Commands, KeyTypeArg, OutputFormat};
-use crate::config::Config;
-use crate::crypto::backup::{BackupManager, ExportOptions, ImportOptions};
+use crate::cli::table::{Cell, Table};
+use crate::cli::{
+    AgentCommands, ApprovalCommands, AuthCommands, AuthorizedCommands, BackupCommands, CaCommands,
+    Commands, GithubCommands, HostsCommands, ImportFormatArg, ImportFrom, KeyTypeArg,
+    KnownHostsCommands, OutputFormat, Provider, SignersCommands, SnapshotCommands, StatusFilter,
+    TeamCommands, TrashCommands,
+};
+use crate::config::{AutoBackupSettings, Config};
+use crate::crypto::backup::{
+    BackupFile, BackupManager, ExportOptions, ImportOptions, get_hostname, get_username,
+    select_keys, similar_names,
+};
+use crate::crypto::encrypt::parse_recipients;
+use crate::crypto::provenance::ProvenanceStore;
+use crate::crypto::schedule::{self, AutoBackup, BackupSchedule};
+use crate::crypto::{Lock, LockPassphrase, RemoteBackup, SourceFormat, Unlock};
+use crate::dates::{self, DateFormat};
+use crate::doctor::{self, Status};
 use crate::error::Result;
-use crate::ssh::KeyScanner;
-use crate::ssh::generate::{KeyGenOptions, KeyGenerator};
-use crate::ssh::keys::KeyType;
+use crate::forge::auth::{self, TokenSource};
+use crate::forge::{GithubClient, GitlabClient};
+use crate::ssh::agent::{self, AgentClient, AgentOrigin, LifetimeStore};
+use crate::ssh::allowed_signers::{self, ALLOWED_SIGNERS_FILE, AllowedSigners};
+use crate::ssh::approval::{self, ApprovalState, ApprovalStore};
+use crate::ssh::audit::{self, AuditReport, FindingKind, Severity};
+use crate::ssh::authorized_keys;
+use crate::ssh::ca::{self, SignRequest};
+use crate::ssh::cert;
+use crate::ssh::config::{
+    HostEntry, SshConfig, contract_path, expand_path, rewrite_identity_files,
+};
+use crate::ssh::convert::{self, ParsedKey};
+use crate::ssh::deploy::{DeployOutcome, DeployTarget, Deployment, DeploymentStore, RevokeOutcome};
+use crate::ssh::ephemeral::{self, EphemeralGrant, EphemeralStore};
+use crate::ssh::expiry::{Expiry, ExpiryPolicy, KeyAgeStore};
+use crate::ssh::generate::{GenerationPlan, KeyGenOptions, KeyGenerator};
+use crate::ssh::inventory::{self, HostGroupStore};
+use crate::ssh::keys::key_bits;
+use crate::ssh::keys::{FingerprintHash, KeyStatus, KeyType, fingerprint_with, rewrite_comments};
+use crate::ssh::known_hosts::{KnownHost, KnownHosts, host_key_name};
+use crate::ssh::perms;
+use crate::ssh::randomart;
+use crate::ssh::repos;
+use crate::ssh::scan::{Artifact, ArtifactKind};
+use crate::ssh::shred;
+use crate::ssh::snapshot::{Change, Snapshot, SnapshotStore};
+use crate::ssh::sshsig;
+use crate::ssh::team::{self, TeamRepo};
+use crate::ssh::trash::Trash;
+use crate::ssh::{KeyScanner, ScanSummary, SshKey};
+use crossterm::style::Color;
+use ssh_key::certificate::CertType;
+use ssh_key::public::KeyData;
+
+/// Exports of at least this many keys print a line per key as they go
+const EXPORT_PROGRESS_MIN_KEYS: usize = 10;
 
 pub struct CliExecutor {
     config: Config,
@@ -19,36 +68,296 @@ impl CliExecutor {
 
     pub fn execute(&self, command: Commands) -> Result<()> {
         match command {
-            Commands::List { format } => self.cmd_list(format),
+            Commands::List {
+                format,
+                status,
+                summary,
+                long,
+                no_header,
+            } => self.cmd_list(format, status, summary, long, no_header),
             Commands::Generate {
                 key_type,
                 filename,
                 comment,
                 passphrase,
                 bits,
-            } => self.cmd_generate(key_type, filename, comment, passphrase, bits),
+                dry_run,
+                allow_unsafe_dir,
+            } => {
+                self.check_key_dir(allow_unsafe_dir)?;
+                self.cmd_generate(key_type, filename, comment, passphrase, bits, dry_run)
+            }
             Commands::Export {
                 output,
+                to,
+                ssh_key,
                 passphrase,
+                recipient,
+                recipients_file,
                 keys,
+                ignore_missing,
                 public_only,
                 description,
-            } => self.cmd_export(output, passphrase, keys, public_only, description),
+                only_healthy,
+                only_problem,
+                armor,
+                plain_tar,
+                i_know_what_im_doing,
+            } => {
+                let health = match (only_healthy, only_problem) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                };
+                let opts = ExportOptions {
+                    description,
+                    include_public_only: public_only,
+                    selected_keys: (!keys.is_empty()).then_some(keys),
+                    armor,
+                };
+                let remote = to
+                    .as_deref()
+                    .map(|url| self.remote_backup(url, ssh_key))
+                    .transpose()?;
+                if let Some(ref remote) = remote {
+                    remote.check_configured()?;
+                }
+                let (output, protection) = match (plain_tar, output) {
+                    (Some(path), _) => (
+                        path,
+                        ExportProtection::Plain {
+                            confirmed: i_know_what_im_doing,
+                        },
+                    ),
+                    (None, output) => {
+                        // clap requires --output unless --plain-tar or --to is given
+                        let output = match (output, &remote) {
+                            (Some(output), _) => output,
+                            (None, Some(remote)) => self.config.export_dir.join(remote.file_name()),
+                            (None, None) => {
+                                unreachable!("--output, --to or --plain-tar is required")
+                            }
+                        };
+                        let protection = ExportProtection::Encrypted {
+                            passphrases: passphrase,
+                            recipients: parse_recipients(&recipient, &recipients_file)?,
+                        };
+                        (output, protection)
+                    }
+                };
+                self.cmd_export(output.clone(), protection, opts, health, ignore_missing)?;
+                if let Some(remote) = remote {
+                    remote.upload(&output)?;
+                    println!("Uploaded to {}", remote);
+                }
+                Ok(())
+            }
             Commands::Import {
                 file,
+                from,
+                ssh_key,
+                passphrase,
+                identity,
+                strategy,
+                keys,
+                dry_run,
+                allow_unsafe_dir,
+            } => {
+                self.check_key_dir(allow_unsafe_dir)?;
+                let opts = ImportOptions {
+                    merge_strategy: strategy.to_merge_strategy(),
+                    dry_run,
+                    selected_keys: (!keys.is_empty()).then_some(keys),
+                };
+                let input = match from {
+                    ImportFrom::Format(format) => {
+                        let file = file.ok_or_else(|| {
+                            crate::error::SkmError::Config(
+                                "--file is required unless --from is an s3:// or sftp:// URL"
+                                    .to_string(),
+                            )
+                        })?;
+                        ImportInput::File(file, format)
+                    }
+                    ImportFrom::Remote(url) => {
+                        let remote = self.remote_backup(&url, ssh_key)?;
+                        match file {
+                            // Keep a local copy only where asked to
+                            Some(file) => {
+                                if let Some(parent) = file.parent() {
+                                    std::fs::create_dir_all(parent)?;
+                                }
+                                remote.download(&file)?;
+                                eprintln!("Downloaded {} to {}", remote, file.display());
+                                ImportInput::File(file, ImportFormatArg::Skm)
+                            }
+                            None => {
+                                let content = remote.fetch()?;
+                                eprintln!("Fetched {}", remote);
+                                ImportInput::Fetched(remote, content)
+                            }
+                        }
+                    }
+                };
+                self.cmd_import(input, passphrase, identity, opts)
+            }
+            Commands::Backup { command } => self.cmd_backup(command),
+            Commands::Restore {
+                latest: _,
                 passphrase,
+                identity,
                 strategy,
+                keys,
                 dry_run,
-            } => self.cmd_import(file, passphrase, strategy, dry_run),
-            Commands::Delete { name, force } => self.cmd_delete(name, force),
-            Commands::Show { name } => self.cmd_show(name),
+                allow_unsafe_dir,
+            } => {
+                self.check_key_dir(allow_unsafe_dir)?;
+                let file = BackupManager::find_backups(&self.config.export_dir)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| {
+                        crate::error::SkmError::ImportExport(format!(
+                            "No backups in {}; `skm export` makes one",
+                            self.config.export_dir.display()
+                        ))
+                    })?;
+                eprintln!("Restoring {}", file.display());
+
+                let opts = ImportOptions {
+                    merge_strategy: strategy.to_merge_strategy(),
+                    dry_run,
+                    selected_keys: (!keys.is_empty()).then_some(keys),
+                };
+                self.cmd_import(
+                    ImportInput::File(file, crate::cli::ImportFormatArg::Skm),
+                    passphrase,
+                    identity,
+                    opts,
+                )
+            }
+            Commands::Delete {
+                name,
+                force,
+                permanent,
+                shred,
+            } => self.cmd_delete(name, force, permanent, shred),
+            Commands::Trash { command } => self.cmd_trash(command),
+            Commands::Show {
+                name,
+                authorized_line,
+                options,
+                randomart,
+            } => self.cmd_show(name, authorized_line, options, randomart),
             Commands::Copy { name, stdout, full } => self.cmd_copy(name, stdout, full),
+            Commands::Pubkey { name, write } => self.cmd_pubkey(name, write),
+            Commands::Convert {
+                key,
+                to,
+                public,
+                output,
+                passphrase,
+                unencrypted,
+            } => self.cmd_convert(key, to, public, output, passphrase, unencrypted),
+            Commands::Comment {
+                name,
+                comment,
+                regenerate_public,
+                rewrite,
+                from_pattern,
+                to,
+                dry_run,
+            } => match (name, comment) {
+                (Some(name), Some(comment)) if !rewrite => {
+                    self.cmd_comment(name, comment, regenerate_public)
+                }
+                _ => self.cmd_comment_rewrite(
+                    &from_pattern.unwrap_or_default(),
+                    &to.unwrap_or_default(),
+                    dry_run,
+                ),
+            },
+            Commands::Rename {
+                old,
+                new,
+                update_config,
+            } => self.cmd_rename(old, new, update_config),
+            Commands::Deploy {
+                name,
+                destination,
+                group,
+                port,
+                identity,
+                dry_run,
+            } => self.cmd_deploy(name, destination, group, port, identity, dry_run),
+            Commands::Ephemeral {
+                host,
+                ttl,
+                port,
+                identity,
+                cleanup,
+            } => match host {
+                Some(host) if !cleanup => self.cmd_ephemeral(host, &ttl, port, identity),
+                _ => self.cmd_ephemeral_cleanup(),
+            },
+            Commands::Agent { command } => self.cmd_agent(command),
+            Commands::Github { command } => self.cmd_github(command),
+            Commands::Authorized { command } => self.cmd_authorized(command),
+            Commands::Auth { command } => self.cmd_auth(command),
+            Commands::Hosts { command } => self.cmd_hosts(command),
+            Commands::Publish {
+                name,
+                provider,
+                title,
+            } => self.cmd_publish(name, provider, title),
+            Commands::Ca { command } => self.cmd_ca(command),
+            Commands::Sign {
+                files,
+                key,
+                namespace,
+                passphrase,
+            } => self.cmd_sign(files, key, namespace, passphrase),
+            Commands::Verify {
+                file,
+                signature,
+                identity,
+                namespace,
+                key,
+                allowed_signers,
+            } => self.cmd_verify(file, signature, identity, namespace, key, allowed_signers),
+            Commands::Signers { command, file } => self.cmd_signers(command, file),
+            Commands::Audit { format, max_age } => self.cmd_audit(format, max_age),
+            Commands::Doctor => self.cmd_doctor(),
+            Commands::FixPerms { dry_run } => self.cmd_fix_perms(dry_run),
+            Commands::Snapshot { command } => self.cmd_snapshot(command),
+            Commands::Team { command } => self.cmd_team(command),
+            Commands::Approval { command } => self.cmd_approval(command),
+            Commands::LockPassphrase { remove } => self.cmd_lock_passphrase(remove),
+            Commands::Passphrase { name, old, new } => self.cmd_passphrase(name, old, new),
         }
     }
 
-    fn cmd_list(&self, format: OutputFormat) -> Result<()> {
+    fn cmd_list(
+        &self,
+        format: OutputFormat,
+        status: Option<StatusFilter>,
+        summary: bool,
+        long: bool,
+        no_header: bool,
+    ) -> Result<()> {
         let scanner = KeyScanner::new(&self.config.ssh_dir);
-        let keys = scanner.scan()?;
+        let (mut keys, artifacts) = scanner.scan_with_artifacts()?;
+
+        if let Some(status) = status {
+            keys.retain(|key| key.status.is_problem() == (status == StatusFilter::Problem));
+        }
+        let summary = summary.then(|| ScanSummary::from_keys(&keys));
+        // Only printed from here on, so the table and JSON can share the hash
+        let hash = self.config.fingerprint_hash;
+        if hash != FingerprintHash::Sha256 {
+            for key in &mut keys {
+                key.fingerprint = key.fingerprint_as(hash);
+            }
+        }
 
         match format {
             OutputFormat::Table => {
@@ -57,22 +366,35 @@ impl CliExecutor {
                     return Ok(());
                 }
 
-                // Print header
-                println!("{:<20} {:<10} {:<20} Comment", "Name", "Type", "Status");
-                println!("{}", "-".repeat(70));
+                // An Expires column only once a maximum age is set
+                let policy = self.expiry_policy()?;
+                let expiry = policy.is_set().then_some(&policy);
+                if long {
+                    print_long_table(&keys, !no_header, &self.date_format()?, expiry);
+                    print_artifacts(&artifacts);
+                    if let Some(summary) = summary {
+                        println!("\n{}", summary);
+                    }
+                    return Ok(());
+                }
 
-                // Print keys
-                for key in keys {
-                    let status = format!("{:?}", key.status);
-                    let comment = key.comment.as_deref().unwrap_or("-");
-                    println!(
-                        "{:<20} {:<10} {:<20} {}",
-                        key.name,
-                        key.key_type.to_string(),
-                        status,
-                        comment
-                    );
+                let mut headers = vec!["Name", "Type", "Status", "Comment"];
+                if expiry.is_some() {
+                    headers.push("Expires");
                 }
+                let mut table = Table::new(&headers).show_header(!no_header);
+                for key in &keys {
+                    let mut row = vec![
+                        key.name.as_str().into(),
+                        key.key_type.to_string().into(),
+                        status_cell(key.status),
+                        key.comment.as_deref().unwrap_or("-").into(),
+                    ];
+                    row.extend(expiry.map(|policy| expiry_cell(policy, key)));
+                    table.row(row);
+                }
+                table.print();
+                print_artifacts(&artifacts);
             }
             OutputFormat::Json => {
                 let json = serde_json::to_string_pretty(&keys)?;
@@ -85,9 +407,69 @@ impl CliExecutor {
             }
         }
 
+        if let Some(summary) = summary {
+            // Keep machine-readable output parseable
+            match format {
+                OutputFormat::Table => println!("\n{}", summary),
+                OutputFormat::Json | OutputFormat::Names => eprintln!("{}", summary),
+            }
+        }
+
         Ok(())
     }
 
+    /// Maximum key ages from the `[expiry]` settings
+    fn expiry_policy(&self) -> Result<ExpiryPolicy> {
+        ExpiryPolicy::from_settings(&self.config.load_settings()?.expiry)?
+            .with_known_ages(&self.config.export_dir)
+    }
+
+    /// How dates are shown, from the `[display]` settings
+    fn date_format(&self) -> Result<DateFormat> {
+        Ok(DateFormat::new(&self.config.load_settings()?.display))
+    }
+
+    /// Refuse to write keys where other users could read or swap them,
+    /// unless told to go ahead anyway
+    fn check_key_dir(&self, allow_unsafe_dir: bool) -> Result<()> {
+        let Some(found) = perms::unsafe_dir(&self.config.ssh_dir) else {
+            return Ok(());
+        };
+        if allow_unsafe_dir {
+            eprintln!("Warning: {}; writing keys there anyway", found);
+            return Ok(());
+        }
+
+        Err(crate::error::SkmError::Config(format!(
+            "Refusing to write keys into {}: {}. Run `chmod go-w {}` or pass --allow-unsafe-dir",
+            self.config.ssh_dir.display(),
+            found,
+            found.path.display()
+        )))
+    }
+
+    /// Parse an `export --to` / `import --from` URL; `ssh_key` names the
+    /// managed key to log in to an sftp:// host with
+    fn remote_backup(&self, url: &str, ssh_key: Option<String>) -> Result<RemoteBackup> {
+        let mut remote = RemoteBackup::parse(url)?;
+        match (&mut remote, ssh_key) {
+            (RemoteBackup::Sftp(location), Some(ssh_key)) => {
+                let scanner = KeyScanner::new(&self.config.ssh_dir);
+                location.target.identity = Some(match scanner.find_key_by_name(&ssh_key)? {
+                    Some(key) => key.path,
+                    None => std::path::PathBuf::from(ssh_key),
+                });
+            }
+            (_, Some(_)) => {
+                return Err(crate::error::SkmError::Config(
+                    "--ssh-key only applies to sftp:// URLs".to_string(),
+                ));
+            }
+            (_, None) => {}
+        }
+        Ok(remote)
+    }
+
     fn cmd_generate(
         &self,
         key_type: KeyTypeArg,
@@ -95,6 +477,7 @@ impl CliExecutor {
         comment: Option<String>,
         passphrase: Option<String>,
         bits: u32,
+        dry_run: bool,
     ) -> Result<()> {
         let generator = KeyGenerator::new(&self.config.ssh_dir);
 
@@ -134,98 +517,210 @@ impl CliExecutor {
             bits,
         };
 
+        if dry_run {
+            let plan = generator.plan(&opts)?;
+            let algorithm = match plan.bits {
+                Some(bits) => format!("{} {} bits", plan.key_type, bits),
+                None => plan.key_type.to_string(),
+            };
+            println!("Would generate key: {}", filename);
+            println!(
+                "  Private:   {} (mode {:o})",
+                plan.private_path.display(),
+                GenerationPlan::PRIVATE_MODE
+            );
+            println!(
+                "  Public:    {} (mode {:o})",
+                plan.public_path.display(),
+                GenerationPlan::PUBLIC_MODE
+            );
+            println!("  Algorithm: {}", algorithm);
+            println!("  Comment:   {}", plan.comment);
+            println!("  Encrypted: {}", if plan.encrypted { "yes" } else { "no" });
+            println!();
+            println!("Equivalent ssh-keygen command:");
+            println!("  {}", plan.ssh_keygen_command());
+            return Ok(());
+        }
+
         let key = generator.generate(opts)?;
         println!("Generated key: {}", key.name);
         println!("  Private: {}", key.path.display());
         println!("  Public:  {}", key.public_path.display());
 
+        let settings = self.config.load_settings()?;
+        let requested_by = team::member_name(&get_username(), &get_hostname());
+        if approval::hold_new_key(
+            &settings.approval,
+            &self.config.export_dir,
+            &key,
+            &requested_by,
+        )?
+        .is_some()
+        {
+            println!(
+                "Waiting for approval before it can be deployed or published: skm approval approve {}",
+                key.name
+            );
+        }
+
         Ok(())
     }
 
     fn cmd_export(
         &self,
         output: std::path::PathBuf,
-        passphrase: Option<String>,
-        selected_keys: Vec<String>,
-        public_only: bool,
-        description: Option<String>,
+        protection: ExportProtection,
+        opts: ExportOptions,
+        healthy: Option<bool>,
+        ignore_missing: bool,
     ) -> Result<()> {
         let scanner = KeyScanner::new(&self.config.ssh_dir);
-        let keys = scanner.scan()?;
+        let scanned = scanner.scan()?;
+        let (selected, missing) = select_keys(&scanned, opts.selected_keys.as_deref());
+
+        // Name each unknown key with its likely intended match
+        let missing: Vec<String> = missing
+            .into_iter()
+            .map(|name| {
+                let names = scanned.iter().map(|key| key.name.as_str());
+                match similar_names(&name, names).as_slice() {
+                    [] => name,
+                    similar => format!("{} (did you mean {}?)", name, similar.join(", ")),
+                }
+            })
+            .collect();
+        if !missing.is_empty() && !ignore_missing {
+            return Err(crate::error::SkmError::KeyNotFound(format!(
+                "{}; pass --ignore-missing to export the others anyway",
+                missing.join(", ")
+            )));
+        }
+        let mut keys: Vec<SshKey> = selected.into_iter().cloned().collect();
+
+        // Audit filter: Some(true) keeps healthy keys, Some(false) problem keys
+        let mut excluded = Vec::new();
+        if let Some(healthy) = healthy {
+            let (kept, skipped): (Vec<SshKey>, Vec<SshKey>) = keys
+                .into_iter()
+                .partition(|key| audit::is_healthy(key) == healthy);
+            excluded = skipped.into_iter().map(|key| key.name).collect();
+            keys = kept;
+        }
+        let report_left_out = || {
+            if !excluded.is_empty() {
+                eprintln!(
+                    "Skipped {} key(s): {}",
+                    if healthy == Some(true) {
+                        "problem"
+                    } else {
+                        "healthy"
+                    },
+                    excluded.join(", ")
+                );
+            }
+            if !missing.is_empty() {
+                eprintln!("Not found: {}", missing.join(", "));
+            }
+        };
 
         if keys.is_empty() {
+            report_left_out();
             eprintln!("No keys to export.");
             std::process::exit(1);
         }
 
-        // Handle passphrase
-        let passphrase =
-            match passphrase.as_deref() {
-                Some("-") => read_passphrase_from_stdin("Enter encryption passphrase: ")?
-                    .ok_or_else(|| {
-                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required")
-                    })?,
-                Some(p) => p.to_string(),
-                None => read_passphrase_from_stdin("Enter encryption passphrase: ")?.ok_or_else(
-                    || std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required"),
-                )?,
-            };
-
         // Ensure parent directory exists
         if let Some(parent) = output.parent() {
             std::fs::create_dir_all(parent)?;
         }
-
         let manager = BackupManager::new(&self.config.ssh_dir);
-        let opts = ExportOptions {
-            description,
-            include_public_only: public_only,
-            selected_keys: if selected_keys.is_empty() {
-                None
-            } else {
-                Some(selected_keys)
-            },
+
+        // Handle passphrases, unless encrypting only to recipients or not at all
+        let lock = match protection {
+            ExportProtection::Plain { confirmed } => {
+                let secret = !opts.include_public_only;
+                if secret && !confirmed && !confirm_plain_export(keys.len(), &output)? {
+                    println!("Cancelled.");
+                    return Ok(());
+                }
+                let report = manager.export_plain_tar(&keys, &output, opts)?;
+                println!(
+                    "Exported {} key(s) UNENCRYPTED to {}",
+                    report.exported.len(),
+                    output.display()
+                );
+                report_left_out();
+                return Ok(());
+            }
+            ExportProtection::Encrypted {
+                passphrases,
+                recipients,
+            } => encryption_lock(passphrases, recipients)?,
         };
 
-        manager.export(&keys, &output, &passphrase, opts)?;
-        println!("Exported {} keys to {}", keys.len(), output.display());
+        let report =
+            manager.export_with_progress(&keys, &output, &lock, opts, |i, total, key| {
+                if total >= EXPORT_PROGRESS_MIN_KEYS {
+                    eprintln!("[{}/{}] {}", i + 1, total, key.name);
+                }
+            })?;
+        println!(
+            "Exported {} key(s) to {}",
+            report.exported.len(),
+            output.display()
+        );
+        report_left_out();
 
         Ok(())
     }
 
     fn cmd_import(
         &self,
-        file: std::path::PathBuf,
+        input: ImportInput,
         passphrase: Option<String>,
-        strategy: crate::cli::MergeStrategyArg,
-        dry_run: bool,
+        identities: Vec<std::path::PathBuf>,
+        opts: ImportOptions,
     ) -> Result<()> {
-        if !file.exists() {
-            eprintln!("Backup file not found: {}", file.display());
-            std::process::exit(1);
+        let manager = BackupManager::new(&self.config.ssh_dir);
+        let dry_run = opts.dry_run;
+        if !dry_run {
+            // Keys imported over themselves keep their age
+            let keys = KeyScanner::new(&self.config.ssh_dir).scan()?;
+            if let Err(e) = KeyAgeStore::new(&self.config.export_dir).remember(&keys) {
+                tracing::warn!("Failed to record key ages: {}", e);
+            }
         }
+        let (report, origin) = match input {
+            ImportInput::File(file, from) => {
+                if !file.exists() {
+                    eprintln!("Backup file not found: {}", file.display());
+                    std::process::exit(1);
+                }
 
-        // Handle passphrase
-        let passphrase =
-            match passphrase.as_deref() {
-                Some("-") => read_passphrase_from_stdin("Enter decryption passphrase: ")?
-                    .ok_or_else(|| {
-                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required")
-                    })?,
-                Some(p) => p.to_string(),
-                None => read_passphrase_from_stdin("Enter decryption passphrase: ")?.ok_or_else(
-                    || std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required"),
-                )?,
-            };
+                let format = match from.to_source_format() {
+                    Some(format) => format,
+                    None => SourceFormat::detect(&file)?,
+                };
+                let source = format.source();
 
-        let manager = BackupManager::new(&self.config.ssh_dir);
-        let opts = ImportOptions {
-            merge_strategy: strategy.to_merge_strategy(),
-            dry_run,
+                // Handle passphrase or identities; only skm backups are encrypted
+                let unlock = if source.needs_passphrase() {
+                    Some(unlock_backup(passphrase.as_deref(), &identities)?)
+                } else {
+                    eprintln!("Reading {} (not encrypted)", source.label());
+                    None
+                };
+                let report = manager.import_from(source.as_ref(), &file, unlock.as_ref(), opts)?;
+                (report, file)
+            }
+            ImportInput::Fetched(remote, content) => {
+                let unlock = unlock_backup(passphrase.as_deref(), &identities)?;
+                let report = manager.import_bytes(&content, &unlock, opts)?;
+                (report, std::path::PathBuf::from(remote.to_string()))
+            }
         };
 
-        let report = manager.import(&file, &passphrase, opts)?;
-
         if dry_run {
             println!("Dry run - would import:");
             println!("  {} keys to import", report.imported.len());
@@ -239,6 +734,13 @@ impl CliExecutor {
                 }
             }
         } else {
+            if let Err(e) = ProvenanceStore::new(&self.config.export_dir).record_import(
+                &self.config.ssh_dir,
+                &origin,
+                &report,
+            ) {
+                tracing::warn!("Failed to record where the keys came from: {}", e);
+            }
             println!("Import complete:");
             println!("  Imported: {}", report.imported.len());
             println!("  Skipped: {}", report.skipped.len());
@@ -254,15 +756,307 @@ impl CliExecutor {
         Ok(())
     }
 
-    fn cmd_delete(&self, name: String, force: bool) -> Result<()> {
+    fn cmd_backup(&self, command: BackupCommands) -> Result<()> {
+        match command {
+            BackupCommands::Inspect {
+                file,
+                passphrase,
+                identity,
+                format,
+            } => {
+                let unlock = unlock_backup(passphrase.as_deref(), &identity)?;
+                let summary = BackupManager::read_unlocked(&file, &unlock)?.summary();
+
+                match format {
+                    OutputFormat::Table => {
+                        let metadata = &summary.metadata;
+                        println!("Backup:      {}", file.display());
+                        println!(
+                            "Created:     {} by {}@{}",
+                            self.date_format()?.full(metadata.created_at),
+                            metadata.username,
+                            metadata.hostname
+                        );
+                        if let Some(ref description) = metadata.description {
+                            println!("Description: {}", description);
+                        }
+                        println!("Keys:        {}", summary.keys.len());
+                        if summary.keys.is_empty() {
+                            return Ok(());
+                        }
+
+                        println!();
+                        let mut table =
+                            Table::new(&["Name", "Type", "Private", "Fingerprint", "Comment"]);
+                        for key in &summary.keys {
+                            let private = match (key.has_private_key, key.encrypted) {
+                                (false, _) => "no",
+                                (true, true) => "encrypted",
+                                (true, false) => "yes",
+                            };
+                            table.row(vec![
+                                key.name.as_str().into(),
+                                key.key_type.as_str().into(),
+                                private.into(),
+                                key.fingerprint.as_deref().unwrap_or("-").into(),
+                                key.comment.as_deref().unwrap_or("-").into(),
+                            ]);
+                        }
+                        table.print();
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&summary)?);
+                    }
+                    OutputFormat::Names => {
+                        for key in &summary.keys {
+                            println!("{}", key.name);
+                        }
+                    }
+                }
+            }
+            BackupCommands::List {
+                passphrase,
+                identity,
+                format,
+            } => {
+                let unlock = if passphrase.is_some() || !identity.is_empty() {
+                    Some(unlock_backup(passphrase.as_deref(), &identity)?)
+                } else {
+                    None
+                };
+
+                let mut backups = Vec::new();
+                for path in BackupManager::find_backups(&self.config.export_dir)? {
+                    let mut backup = match BackupFile::probe(&path) {
+                        Ok(backup) => backup,
+                        Err(e) => {
+                            eprintln!("Skipping {}: {}", path.display(), e);
+                            continue;
+                        }
+                    };
+                    // Backups locked some other way just show no metadata
+                    if let Some(ref unlock) = unlock {
+                        backup.metadata = BackupManager::read_unlocked(&path, unlock)
+                            .ok()
+                            .map(|data| data.metadata);
+                    }
+                    backups.push(backup);
+                }
+
+                match format {
+                    OutputFormat::Table => {
+                        if backups.is_empty() {
+                            println!("No backups in {}", self.config.export_dir.display());
+                            return Ok(());
+                        }
+
+                        let dates = self.date_format()?;
+                        let mut table = Table::new(&[
+                            "File",
+                            "Modified",
+                            "Size",
+                            "Encrypted to",
+                            "Created by",
+                            "Keys",
+                            "Description",
+                        ]);
+                        for backup in &backups {
+                            let metadata = backup.metadata.as_ref();
+                            table.row(vec![
+                                backup
+                                    .path
+                                    .file_name()
+                                    .unwrap_or_default()
+                                    .to_string_lossy()
+                                    .to_string()
+                                    .into(),
+                                dates.short(backup.modified).into(),
+                                format_size(backup.size).into(),
+                                backup.protection.to_string().into(),
+                                metadata
+                                    .map(|m| format!("{}@{}", m.username, m.hostname))
+                                    .unwrap_or_else(|| "-".to_string())
+                                    .into(),
+                                metadata
+                                    .map(|m| m.key_count.to_string())
+                                    .unwrap_or_else(|| "-".to_string())
+                                    .into(),
+                                metadata
+                                    .and_then(|m| m.description.clone())
+                                    .unwrap_or_else(|| "-".to_string())
+                                    .into(),
+                            ]);
+                        }
+                        table.print();
+                        if unlock.is_none() {
+                            println!(
+                                "\nPass --passphrase or --identity to show who made them and what's inside."
+                            );
+                        }
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&backups)?);
+                    }
+                    OutputFormat::Names => {
+                        for backup in &backups {
+                            println!("{}", backup.path.display());
+                        }
+                    }
+                }
+            }
+            BackupCommands::Rekey {
+                file,
+                passphrase,
+                identity,
+                new_passphrase,
+                recipient,
+                recipients_file,
+            } => {
+                let unlock = unlock_backup(passphrase.as_deref(), &identity)?;
+                // Ask for everything up front, before the slow decryption
+                let lock = encryption_lock(
+                    new_passphrase,
+                    parse_recipients(&recipient, &recipients_file)?,
+                )?;
+                let metadata = BackupManager::rekey(&file, &unlock, &lock)?;
+                println!(
+                    "Re-encrypted {} ({} key(s))",
+                    file.display(),
+                    metadata.key_count
+                );
+                eprintln!("Copies made before, e.g. uploaded ones, still open the old way");
+            }
+            BackupCommands::Auto {
+                every,
+                keep,
+                recipient,
+                recipients_file,
+                now,
+                daemon,
+                disable,
+            } => {
+                let auto = AutoBackup::new(&self.config);
+                if disable {
+                    if auto.disable()? {
+                        println!(
+                            "Automatic backups disabled; existing ones in {} are kept",
+                            self.config.export_dir.display()
+                        );
+                    } else {
+                        println!("Automatic backups were not enabled");
+                    }
+                    return Ok(());
+                }
+
+                let configure = every.is_some()
+                    || keep.is_some()
+                    || !recipient.is_empty()
+                    || !recipients_file.is_empty();
+                let mut current = auto.load()?;
+                if configure {
+                    let mut updated = match self.config.load_settings()?.backup.auto {
+                        Some(settings) => settings,
+                        None => AutoBackupSettings {
+                            every: every.clone().ok_or_else(|| {
+                                crate::error::SkmError::Config(
+                                    "Pass --every to turn on automatic backups, e.g. --every 7d"
+                                        .to_string(),
+                                )
+                            })?,
+                            keep: None,
+                            recipients: Vec::new(),
+                            recipients_files: Vec::new(),
+                            ssh_dir: None,
+                        },
+                    };
+                    if let Some(every) = every {
+                        updated.every = every;
+                    }
+                    if let Some(keep) = keep {
+                        if keep == 0 {
+                            return Err(crate::error::SkmError::Config(
+                                "--keep must be at least 1".to_string(),
+                            ));
+                        }
+                        updated.keep = Some(keep);
+                    }
+                    if !recipient.is_empty() || !recipients_file.is_empty() {
+                        updated.recipients = recipient;
+                        // Runs start from wherever skm happens to be invoked
+                        updated.recipients_files = recipients_file
+                            .iter()
+                            .map(|file| {
+                                contract_path(&file.canonicalize().unwrap_or_else(|_| file.clone()))
+                            })
+                            .collect();
+                    }
+                    if updated.recipients.is_empty() && updated.recipients_files.is_empty() {
+                        return Err(crate::error::SkmError::Config(
+                            "Automatic backups are encrypted to age recipients; \
+                             pass --recipient or --recipients-file"
+                                .to_string(),
+                        ));
+                    }
+                    // Only recorded when it isn't the ssh directory skm uses by default
+                    updated.ssh_dir = (self.config.ssh_dir != Config::new().ssh_dir)
+                        .then(|| contract_path(&self.config.ssh_dir));
+
+                    let updated = auto.configure(&updated)?;
+                    println!(
+                        "Backing up {} every {}, keeping the last {} ([backup.auto] in {})",
+                        updated.ssh_dir.display(),
+                        schedule::format_interval(updated.every_secs),
+                        updated.keep,
+                        self.config.settings_path().display()
+                    );
+                    current = Some(updated);
+                }
+
+                let Some(mut current) = current else {
+                    if now || daemon {
+                        return Err(crate::error::SkmError::Config(
+                            "Automatic backups are not enabled; set them up with --every"
+                                .to_string(),
+                        ));
+                    }
+                    println!("Automatic backups are off.");
+                    println!("Turn them on with: skm backup auto --every 7d --recipient age1...");
+                    return Ok(());
+                };
+
+                if now {
+                    let report = auto.run(&mut current)?;
+                    println!("Automatic backup: {}", report.summary());
+                }
+                if daemon {
+                    return run_backup_daemon(&auto);
+                }
+                if !configure && !now {
+                    print_backup_schedule(&auto, &current)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_delete(&self, name: String, force: bool, permanent: bool, shred: bool) -> Result<()> {
         let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let shred = shred || self.config.load_settings()?.delete.shred;
 
         let key = scanner
             .find_key_by_name(&name)?
             .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.clone()))?;
 
         if !force {
-            print!("Delete key '{}' and its public key? [y/N] ", name);
+            if permanent {
+                print!(
+                    "Delete key '{}' and its public key for good? This cannot be undone [y/N] ",
+                    name
+                );
+            } else {
+                print!("Delete key '{}' and its public key? [y/N] ", name);
+            }
             io::stdout().flush()?;
 
             let mut input = String::new();
@@ -274,9 +1068,22 @@ impl CliExecutor {
             }
         }
 
+        if !permanent {
+            Trash::new(&self.config.export_dir)
+                .shredding(shred)
+                .put(&key)?;
+            println!("Moved key to the trash: {}", name);
+            println!("Restore it with `skm trash restore {}`", name);
+            return Ok(());
+        }
+
         // Delete private key if exists
         if key.path.exists() {
-            std::fs::remove_file(&key.path)?;
+            if shred {
+                shred::shred_file(&key.path)?;
+            } else {
+                std::fs::remove_file(&key.path)?;
+            }
         }
 
         // Delete public key if exists
@@ -284,17 +1091,110 @@ impl CliExecutor {
             std::fs::remove_file(&key.public_path)?;
         }
 
-        println!("Deleted key: {}", name);
+        // A certificate is useless without its key
+        if let Some(ref cert) = key.cert_path {
+            std::fs::remove_file(cert)?;
+        }
+
+        if shred {
+            println!("Shredded and deleted key: {}", name);
+        } else {
+            println!("Deleted key: {}", name);
+        }
+        Ok(())
+    }
+
+    fn cmd_trash(&self, command: TrashCommands) -> Result<()> {
+        let trash = Trash::new(&self.config.export_dir);
+
+        match command {
+            TrashCommands::List { format } => {
+                let entries = trash.list()?;
+                match format {
+                    OutputFormat::Table => {
+                        if entries.is_empty() {
+                            println!("The trash is empty");
+                            return Ok(());
+                        }
+
+                        let dates = self.date_format()?;
+                        let mut table = Table::new(&["Name", "Deleted", "Fingerprint", "Id"]);
+                        for entry in &entries {
+                            table.row(vec![
+                                entry.key_name.clone().into(),
+                                dates.short(entry.deleted_at).into(),
+                                entry.fingerprint.as_deref().unwrap_or("-").into(),
+                                entry.id.clone().into(),
+                            ]);
+                        }
+                        table.print();
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    }
+                    OutputFormat::Names => {
+                        for entry in &entries {
+                            println!("{}", entry.key_name);
+                        }
+                    }
+                }
+            }
+            TrashCommands::Restore { name } => {
+                let entry = trash.restore(&trash.find(&name)?.id)?;
+                println!("Restored key: {}", entry.key_name);
+                for file in &entry.files {
+                    println!("  {}", file.display());
+                }
+            }
+            TrashCommands::Empty { older_than, force } => {
+                let older_than = older_than
+                    .as_deref()
+                    .map(schedule::parse_interval)
+                    .transpose()?;
+                if trash.list()?.is_empty() {
+                    println!("The trash is empty");
+                    return Ok(());
+                }
+
+                if !force {
+                    print!("Delete the keys in the trash for good? [y/N] ");
+                    io::stdout().flush()?;
+
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input)?;
+
+                    if !input.trim().eq_ignore_ascii_case("y") {
+                        println!("Cancelled.");
+                        return Ok(());
+                    }
+                }
+
+                let removed = trash.empty(older_than)?;
+                println!("Deleted {} key(s) from the trash", removed);
+            }
+        }
+
         Ok(())
     }
 
-    fn cmd_show(&self, name: String) -> Result<()> {
+    fn cmd_show(
+        &self,
+        name: String,
+        authorized_line: bool,
+        options: Option<String>,
+        randomart: bool,
+    ) -> Result<()> {
         let scanner = KeyScanner::new(&self.config.ssh_dir);
 
         let key = scanner
             .find_key_by_name(&name)?
             .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.clone()))?;
 
+        if authorized_line {
+            println!("{}", key.authorized_line(options.as_deref())?);
+            return Ok(());
+        }
+
         println!("Name:        {}", key.name);
         println!("Type:        {}", key.key_type);
         println!("Status:      {:?}", key.status);
@@ -302,34 +1202,98 @@ impl CliExecutor {
         println!("Public:      {}", key.public_path.display());
         println!(
             "Fingerprint: {}",
-            key.fingerprint.as_deref().unwrap_or("N/A")
+            key.fingerprint_as(self.config.fingerprint_hash)
+                .as_deref()
+                .unwrap_or("N/A")
         );
         println!("Comment:     {}", key.comment.as_deref().unwrap_or("N/A"));
+        let dates = self.date_format()?;
         println!(
             "Created:     {}",
             key.created_at
-                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                .map(|d| dates.full(d))
                 .unwrap_or_else(|| "Unknown".to_string())
         );
         println!(
             "Modified:    {}",
             key.modified_at
-                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                .map(|d| dates.full(d))
                 .unwrap_or_else(|| "Unknown".to_string())
         );
 
+        match key.certificate() {
+            Some(Ok(cert)) => {
+                println!("Certificate: {}", cert.path.display());
+                println!("  Type:       {}", cert.cert_type);
+                println!("  Key ID:     {}", cert.key_id);
+                println!("  Serial:     {}", cert.serial);
+                println!("  Principals: {}", cert.principals_display());
+                println!("  Valid:      {} ({})", cert.window(), cert.validity());
+                println!("  CA:         {}", cert.ca_fingerprint);
+                if let Some(warning) = cert.warning() {
+                    println!("  Warning:    {}", warning);
+                }
+            }
+            Some(Err(e)) => println!("Certificate: unreadable ({})", e),
+            None => {}
+        }
+        if !key.artifacts.is_empty() {
+            println!("Backups:     {}", file_names(&key.artifacts));
+        }
+
+        let ssh_config = SshConfig::load(self.config.ssh_dir.join("config"))?;
+        let hosts = ssh_config.hosts_using(&key.path, &key.public_path);
+        if hosts.is_empty() {
+            println!(
+                "Used by:     no Host entries in {}",
+                ssh_config.path().display()
+            );
+        } else {
+            let aliases: Vec<String> = hosts.iter().map(|h| h.patterns.join(" ")).collect();
+            println!("Used by:     {}", aliases.join(", "));
+        }
+
+        let remotes = repos::scan_configured(&self.config.load_settings()?.repos);
+        let repos = repos::repos_by_key(&remotes, &ssh_config)
+            .remove(&key.path)
+            .unwrap_or_default();
+        if repos.is_empty() {
+            println!("Repos:       none found");
+        } else {
+            println!("Repos:       {}", repos.join(", "));
+        }
+
+        if let Some(ref fingerprint) = key.fingerprint {
+            let deployments = DeploymentStore::new(&self.config.export_dir).for_key(fingerprint)?;
+            if !deployments.is_empty() {
+                let hosts: Vec<String> = deployments.iter().map(|d| d.describe()).collect();
+                println!("Deployed to: {}", hosts.join(", "));
+            }
+            if let Some(provenance) =
+                ProvenanceStore::new(&self.config.export_dir).for_key(fingerprint)?
+            {
+                println!("Origin:      {}", provenance.describe());
+            }
+        }
+
         // Show public key content if available
         if let Some(content) = key.read_public_content()? {
             println!("\nPublic key content:");
             println!("{}", content.trim());
         }
 
+        if randomart {
+            let public_key = key.read_public_key()?;
+            println!(
+                "\n{}",
+                randomart::randomart(&public_key, self.config.fingerprint_hash)
+            );
+        }
+
         Ok(())
     }
 
     fn cmd_copy(&self, name: String, stdout: bool, full: bool) -> Result<()> {
-        use arboard::Clipboard;
-
         let scanner = KeyScanner::new(&self.config.ssh_dir);
 
         let key = scanner
@@ -361,18 +1325,14 @@ impl CliExecutor {
             println!("{}", content.trim());
         } else {
             // Copy to clipboard
-            let mut clipboard = Clipboard::new().map_err(|e| {
-                crate::error::SkmError::Unknown(format!("Failed to access clipboard: {}", e))
-            })?;
-
-            clipboard.set_text(content.trim()).map_err(|e| {
-                crate::error::SkmError::Unknown(format!("Failed to copy to clipboard: {}", e))
-            })?;
+            crate::terminal::copy_to_clipboard(content.trim())?;
 
             println!("✓ Public key '{}' copied to clipboard!", name);
             println!(
                 "  Fingerprint: {}",
-                key.fingerprint.as_deref().unwrap_or("N/A")
+                key.fingerprint_as(self.config.fingerprint_hash)
+                    .as_deref()
+                    .unwrap_or("N/A")
             );
             if full {
                 println!("  (Full key with comment)");
@@ -383,26 +1343,2215 @@ impl CliExecutor {
 
         Ok(())
     }
-}
 
-fn read_passphrase_from_stdin(prompt: &str) -> io::Result<Option<String>> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
+    fn cmd_convert(
+        &self,
+        key: String,
+        to: crate::cli::ConvertFormatArg,
+        public: bool,
+        output: Option<std::path::PathBuf>,
+        passphrase: Option<String>,
+        unencrypted: bool,
+    ) -> Result<()> {
+        let format = to.to_key_format();
+        // RFC 4716 has no private key encoding
+        let public = public || format == convert::KeyFormat::Rfc4716;
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let parsed = match scanner.find_key_by_name(&key)? {
+            Some(managed) if public || !managed.has_private() => {
+                ParsedKey::Public(managed.read_public_key()?)
+            }
+            Some(managed) => convert::decode(&crate::ssh::keys::read_key_text(&managed.path)?)?,
+            None if std::path::Path::new(&key).is_file() => convert::decode(
+                &crate::ssh::keys::read_key_text(std::path::Path::new(&key))?,
+            )?,
+            None => return Err(crate::error::SkmError::KeyNotFound(key)),
+        };
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+        // The encoding, whether it's a private key and whether that is encrypted
+        let (encoded, private, encrypted) = match parsed {
+            ParsedKey::Public(public_key) => {
+                (convert::encode_public(&public_key, format)?, false, false)
+            }
+            ParsedKey::Private(private_key) if public => (
+                convert::encode_public(private_key.public_key(), format)?,
+                false,
+                false,
+            ),
+            // Re-encoded as it is, so it keeps its passphrase
+            ParsedKey::Private(private_key) if private_key.is_encrypted() && !unencrypted => {
+                if format != convert::KeyFormat::OpenSsh {
+                    return Err(crate::error::SkmError::Config(format!(
+                        "{} private keys can't be encrypted; pass --unencrypted to write {} without its passphrase",
+                        format, key
+                    )));
+                }
+                (convert::encode_private(&private_key, format)?, true, true)
+            }
+            ParsedKey::Private(private_key) => {
+                let private_key = if private_key.is_encrypted() {
+                    let passphrase = match passphrase {
+                        Some(p) if p != "-" => p,
+                        _ => {
+                            read_passphrase_from_stdin(&format!("Enter passphrase for {}: ", key))?
+                                .ok_or(crate::error::SkmError::InvalidPassphrase)?
+                        }
+                    };
+                    private_key
+                        .decrypt(&passphrase)
+                        .map_err(|_| crate::error::SkmError::InvalidPassphrase)?
+                } else {
+                    *private_key
+                };
+                (convert::encode_private(&private_key, format)?, true, false)
+            }
+        };
 
-    let input = input.trim().to_string();
-    if input.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(input))
+        match output {
+            Some(path) if path.as_os_str() != "-" => {
+                convert::write_new(&path, &encoded, private)?;
+                eprintln!(
+                    "Wrote {} {} key to {}",
+                    format,
+                    if private { "private" } else { "public" },
+                    path.display()
+                );
+            }
+            _ => print!("{}", encoded),
+        }
+        // On stderr, so it shows even when the key goes to stdout
+        if private && !encrypted {
+            eprintln!("Note: the converted private key is not encrypted");
+        }
+
+        Ok(())
+    }
+
+    fn cmd_pubkey(&self, name: String, write: bool) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+
+        let mut key = scanner
+            .find_key_by_name(&name)?
+            .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.clone()))?;
+        if !key.has_private() {
+            return Err(crate::error::SkmError::KeyNotFound(format!(
+                "Private key for {}",
+                name
+            )));
+        }
+
+        let public_key = key.derive_public_key()?;
+        if !write {
+            let line = public_key
+                .to_openssh()
+                .map_err(|e| crate::error::SkmError::SshKey(e.to_string()))?;
+            println!("{}", line);
+            return Ok(());
+        }
+
+        if key.has_public() {
+            return Err(crate::error::SkmError::KeyAlreadyExists(
+                key.public_path.display().to_string(),
+            ));
+        }
+        key.regenerate_public(public_key.comment())?;
+        println!("Wrote public key: {}", key.public_path.display());
+        Ok(())
+    }
+
+    fn cmd_comment(&self, name: String, comment: String, regenerate_public: bool) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+
+        let mut key = scanner
+            .find_key_by_name(&name)?
+            .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.clone()))?;
+
+        if key.has_public() {
+            key.update_comment(&comment)?;
+        } else if regenerate_public && key.has_private() {
+            key.regenerate_public(&comment)?;
+            println!("Regenerated public key: {}", key.public_path.display());
+        } else {
+            return Err(crate::error::SkmError::KeyNotFound(format!(
+                "Public key for {} (use --regenerate-public to derive it from the private key)",
+                name
+            )));
+        }
+
+        println!("Updated comment for key: {}", name);
+        Ok(())
+    }
+
+    fn cmd_comment_rewrite(&self, from: &str, to: &str, dry_run: bool) -> Result<()> {
+        let keys = KeyScanner::new(&self.config.ssh_dir).scan()?;
+        let rewrites = rewrite_comments(keys, from, to, dry_run)?;
+        for rewrite in &rewrites {
+            println!("{}: {} -> {}", rewrite.key_name, rewrite.old, rewrite.new);
+        }
+
+        match (rewrites.len(), dry_run) {
+            (0, _) => println!("No key comments contain '{}'.", from),
+            (n, true) => println!("Dry run - would rewrite {} comment(s).", n),
+            (n, false) => println!("Rewrote {} comment(s).", n),
+        }
+        Ok(())
+    }
+
+    fn cmd_rename(&self, old: String, new: String, update_config: bool) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+
+        let key = scanner
+            .find_key_by_name(&old)?
+            .ok_or_else(|| crate::error::SkmError::KeyNotFound(old.clone()))?;
+
+        let renamed = key.rename(&new)?;
+        println!("Renamed key: {} -> {}", old, new);
+
+        if update_config {
+            let config_path = self.config.ssh_dir.join("config");
+            let changed = rewrite_identity_files(&config_path, &key.path, &renamed.path)?;
+            println!(
+                "Updated {} IdentityFile line(s) in {}",
+                changed,
+                config_path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn cmd_deploy(
+        &self,
+        name: String,
+        destination: Option<String>,
+        group: Option<String>,
+        port: Option<u16>,
+        identity: Option<String>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let key = find_key(&scanner, &name)?;
+        ApprovalStore::new(&self.config.export_dir).require_approved(&key, "deploy")?;
+
+        let public_key = key
+            .read_public_key()?
+            .to_openssh()
+            .map_err(|e| crate::error::SkmError::SshKey(e.to_string()))?;
+
+        // A group's hosts keep their own port unless --port overrides it
+        let targets = match group {
+            Some(ref group) => {
+                let group = HostGroupStore::new(&self.config.export_dir).get(group)?;
+                let mut targets = Vec::new();
+                for host in &group.hosts {
+                    let mut target =
+                        deploy_target(&scanner, &host.destination, port, identity.clone())?;
+                    target.port = port.or(host.port);
+                    targets.push(target);
+                }
+                targets
+            }
+            None => vec![deploy_target(
+                &scanner,
+                destination.as_deref().unwrap_or_default(),
+                port,
+                identity,
+            )?],
+        };
+
+        if dry_run {
+            println!("Dry run - would deploy key '{}':", name);
+            for target in &targets {
+                println!("  Destination: {}", target.destination());
+                println!("  Command:     {}", target.describe());
+            }
+            println!("  Key line:    {}", public_key);
+            return Ok(());
+        }
+
+        let store = DeploymentStore::new(&self.config.export_dir);
+        let mut failed = 0;
+        for target in targets {
+            match target.deploy(&public_key) {
+                Ok(DeployOutcome::Added) => {
+                    println!("Deployed key '{}' to {}", name, target.destination())
+                }
+                Ok(DeployOutcome::AlreadyPresent) => println!(
+                    "Key '{}' is already authorized on {}",
+                    name,
+                    target.destination()
+                ),
+                // One unreachable host doesn't stop the rest of a group
+                Err(e) if group.is_some() => {
+                    eprintln!("Failed to deploy to {}: {}", target.destination(), e);
+                    failed += 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            if let Some(ref fingerprint) = key.fingerprint {
+                store.record(Deployment {
+                    destination: target.destination(),
+                    port: target.port,
+                    identity: target.identity,
+                    fingerprint: fingerprint.clone(),
+                    key_name: name.clone(),
+                    deployed_at: chrono::Local::now(),
+                })?;
+            }
+        }
+
+        if failed > 0 {
+            return Err(crate::error::SkmError::Remote(format!(
+                "{} host(s) could not be deployed to",
+                failed
+            )));
+        }
+        Ok(())
+    }
+
+    fn cmd_ephemeral(
+        &self,
+        destination: String,
+        ttl: &str,
+        port: Option<u16>,
+        identity: Option<String>,
+    ) -> Result<()> {
+        let ttl = ephemeral::parse_ttl(ttl)?;
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let target = deploy_target(&scanner, &destination, port, identity)?;
+        // Fail before touching the remote host if there is no agent to hold the key
+        let mut agent = AgentClient::connect_env()?;
+
+        let created_at = chrono::Local::now();
+        let expires_at = created_at + chrono::Duration::seconds(ttl.into());
+        let comment = format!(
+            "skm-ephemeral@{}-{}",
+            get_hostname(),
+            created_at.format("%Y%m%d%H%M%S")
+        );
+        let key = ephemeral::generate_key(&comment)?;
+        let line = ephemeral::authorized_line(&key, expires_at)?;
+
+        // The agent drops the key itself once the lifetime runs out
+        agent.add_identity(&key, Some(ttl), false)?;
+        record_lifetime(
+            &LifetimeStore::new(&self.config.export_dir),
+            key.public_key().key_data(),
+            Some(ttl),
+        );
+        if let Err(e) = target.deploy(&line) {
+            let _ = agent.remove_identity(key.public_key().key_data());
+            return Err(e);
+        }
+
+        let fingerprint = ephemeral::fingerprint(&key);
+        EphemeralStore::new(&self.config.export_dir).record(EphemeralGrant {
+            destination: target.destination(),
+            port: target.port,
+            identity: target.identity.clone(),
+            fingerprint: fingerprint.clone(),
+            authorized_line: line,
+            created_at,
+            expires_at,
+        })?;
+
+        println!(
+            "Granted ephemeral key {} on {} until {}",
+            fingerprint,
+            target.destination(),
+            expires_at.format("%Y-%m-%d %H:%M")
+        );
+        println!(
+            "The key is only in ssh-agent; connect with: ssh {}",
+            target.ssh_args().join(" ")
+        );
+        println!("Run 'skm ephemeral --cleanup' after it expires to remove it from the host.");
+
+        Ok(())
+    }
+
+    fn cmd_ephemeral_cleanup(&self) -> Result<()> {
+        let store = EphemeralStore::new(&self.config.export_dir);
+        let grants = store.load()?;
+        let (expired, active): (Vec<_>, Vec<_>) = grants.into_iter().partition(|g| g.is_expired());
+
+        if expired.is_empty() {
+            println!("No expired ephemeral keys ({} still active).", active.len());
+            return Ok(());
+        }
+
+        let mut removed = Vec::new();
+        let mut failed = 0;
+        for grant in expired {
+            match grant
+                .target()
+                .and_then(|t| t.revoke(&grant.authorized_line))
+            {
+                Ok(RevokeOutcome::Removed) => {
+                    println!("Removed {} from {}", grant.fingerprint, grant.destination);
+                    removed.push(grant);
+                }
+                Ok(RevokeOutcome::NotPresent) => {
+                    println!(
+                        "{} was already gone from {}",
+                        grant.fingerprint, grant.destination
+                    );
+                    removed.push(grant);
+                }
+                Err(e) => {
+                    // The record is kept so the next cleanup retries
+                    eprintln!(
+                        "Failed to remove {} from {}: {}",
+                        grant.fingerprint, grant.destination, e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+
+        // Only drop what was removed; grants may have been added meanwhile
+        store.update(|grants| grants.retain(|g| !removed.contains(g)))?;
+        if failed > 0 {
+            return Err(crate::error::SkmError::Remote(format!(
+                "{} ephemeral key(s) could not be removed",
+                failed
+            )));
+        }
+        Ok(())
+    }
+
+    fn cmd_github(&self, command: GithubCommands) -> Result<()> {
+        let client = GithubClient::from_settings(&self.config.load_settings()?);
+
+        match command {
+            GithubCommands::Push { name, title } => {
+                self.cmd_publish(name, Provider::Github, title)?;
+            }
+            GithubCommands::Pull { user, dry_run } => {
+                let keys = client.user_keys(&user)?;
+                if keys.is_empty() {
+                    println!("GitHub user '{}' has no public keys.", user);
+                    return Ok(());
+                }
+
+                let path = self.config.ssh_dir.join("authorized_keys");
+                let new_keys: Vec<_> = if dry_run {
+                    let existing = authorized_keys::read_key_data(&path)?;
+                    keys.into_iter()
+                        .filter(|k| !existing.contains(k.key_data()))
+                        .collect()
+                } else {
+                    authorized_keys::append_keys(&path, &keys)?
+                };
+
+                let verb = if dry_run { "Would add" } else { "Added" };
+                for key in &new_keys {
+                    println!(
+                        "{} {} {}",
+                        verb,
+                        key.algorithm(),
+                        fingerprint_with(key, self.config.fingerprint_hash)
+                    );
+                }
+                println!(
+                    "{} {} key(s) from github.com/{} to {}",
+                    verb,
+                    new_keys.len(),
+                    user,
+                    path.display()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_authorized(&self, command: AuthorizedCommands) -> Result<()> {
+        match command {
+            AuthorizedCommands::SyncFromGithub {
+                org,
+                team,
+                file,
+                dry_run,
+            } => {
+                let client = GithubClient::from_settings(&self.config.load_settings()?);
+                let members = client.org_members(&org, team.as_deref())?;
+                let source = match team {
+                    Some(ref team) => format!("github:{}/{}", org, team),
+                    None => format!("github:{}", org),
+                };
+
+                let mut keys = Vec::new();
+                for member in &members {
+                    let member_keys = client.user_keys(member)?;
+                    if member_keys.is_empty() {
+                        println!("{} has no public keys", member);
+                    }
+                    keys.extend(member_keys);
+                }
+                // An empty list is far more likely a token without access than
+                // a team with nobody in it; don't lock everyone out over it
+                if keys.is_empty() {
+                    return Err(crate::error::SkmError::Remote(format!(
+                        "No keys found for {} ({} member(s)); authorized_keys left unchanged",
+                        source,
+                        members.len()
+                    )));
+                }
+
+                let path = file.unwrap_or_else(|| self.config.ssh_dir.join("authorized_keys"));
+                let sync = authorized_keys::sync_section(&path, &source, &keys, dry_run)?;
+
+                let (add, remove) = if dry_run {
+                    ("Would add", "Would remove")
+                } else {
+                    ("Added", "Removed")
+                };
+                for (verb, list) in [(add, &sync.added), (remove, &sync.removed)] {
+                    for key in list {
+                        println!(
+                            "{} {} {} ({})",
+                            verb,
+                            key.algorithm(),
+                            fingerprint_with(key, self.config.fingerprint_hash),
+                            key.comment()
+                        );
+                    }
+                }
+                if sync.is_empty() {
+                    println!("{} is up to date with {}", path.display(), source);
+                } else {
+                    println!(
+                        "{}: {} added, {} removed from {} member(s) of {}",
+                        path.display(),
+                        sync.added.len(),
+                        sync.removed.len(),
+                        members.len(),
+                        source
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_hosts(&self, command: HostsCommands) -> Result<()> {
+        let mut ssh_config = SshConfig::load(self.config.ssh_dir.join("config"))?;
+
+        match command {
+            HostsCommands::List { format } => match format {
+                OutputFormat::Table => {
+                    if ssh_config.hosts.is_empty() {
+                        println!("No hosts found in {}.", ssh_config.path().display());
+                        return Ok(());
+                    }
+
+                    println!(
+                        "{:<24} {:<24} {:<12} {:<6} IdentityFile",
+                        "Host", "HostName", "User", "Port"
+                    );
+                    println!("{}", "-".repeat(90));
+                    for host in &ssh_config.hosts {
+                        println!(
+                            "{:<24} {:<24} {:<12} {:<6} {}",
+                            host.patterns.join(" "),
+                            host.host_name.as_deref().unwrap_or("-"),
+                            host.user.as_deref().unwrap_or("-"),
+                            host.port.map(|p| p.to_string()).unwrap_or("-".to_string()),
+                            if host.identity_files.is_empty() {
+                                "-".to_string()
+                            } else {
+                                host.identity_files.join(", ")
+                            }
+                        );
+                    }
+                }
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&ssh_config.hosts)?);
+                }
+                OutputFormat::Names => {
+                    for host in ssh_config.hosts.iter().filter(|h| !h.is_pattern()) {
+                        println!("{}", host.patterns.join(" "));
+                    }
+                }
+            },
+            HostsCommands::Show { alias } => {
+                let host = ssh_config
+                    .find(&alias)
+                    .ok_or_else(|| crate::error::SkmError::HostNotFound(alias.clone()))?;
+
+                println!("Host:         {}", host.patterns.join(" "));
+                println!("HostName:     {}", host.host_name.as_deref().unwrap_or("-"));
+                println!("User:         {}", host.user.as_deref().unwrap_or("-"));
+                println!(
+                    "Port:         {}",
+                    host.port.map(|p| p.to_string()).unwrap_or("-".to_string())
+                );
+                if host.identity_files.is_empty() {
+                    println!("IdentityFile: -");
+                }
+                for (value, path) in host.identity_files.iter().zip(host.identity_paths()) {
+                    let missing = if path.exists() { "" } else { " (missing)" };
+                    println!("IdentityFile: {}{}", value, missing);
+                }
+            }
+            HostsCommands::Add {
+                alias,
+                hostname,
+                user,
+                port,
+                key,
+            } => {
+                let mut entry = HostEntry::new(&alias);
+                entry.host_name = hostname;
+                entry.user = user;
+                entry.port = port;
+
+                if let Some(key) = key {
+                    let scanner = KeyScanner::new(&self.config.ssh_dir);
+                    let path = match scanner.find_key_by_name(&key)? {
+                        Some(found) => found.path,
+                        None => std::path::PathBuf::from(&key),
+                    };
+                    entry.identity_files.push(contract_path(&path));
+                }
+
+                ssh_config.add_host(entry)?;
+                println!("Added host '{}' to {}", alias, ssh_config.path().display());
+            }
+            HostsCommands::Remove { alias } => {
+                ssh_config.remove_host(&alias)?;
+                println!(
+                    "Removed host '{}' from {}",
+                    alias,
+                    ssh_config.path().display()
+                );
+            }
+            HostsCommands::Import {
+                from,
+                group,
+                dry_run,
+            } => {
+                let groups = if from == inventory::SSH_CONFIG_GROUP {
+                    let name = group.as_deref().unwrap_or(inventory::SSH_CONFIG_GROUP);
+                    vec![inventory::ssh_config_group(&ssh_config, name)]
+                } else {
+                    let path = expand_path(&from);
+                    let content = std::fs::read_to_string(&path)?;
+                    let mut groups =
+                        inventory::parse_ansible_inventory(&content, &path.display().to_string())?;
+                    if let Some(ref group) = group {
+                        groups.retain(|g| &g.name == group);
+                        if groups.is_empty() {
+                            return Err(crate::error::SkmError::HostNotFound(format!(
+                                "no group '{}' with hosts in {}",
+                                group,
+                                path.display()
+                            )));
+                        }
+                    }
+                    groups
+                };
+
+                for group in &groups {
+                    let hosts: Vec<&str> =
+                        group.hosts.iter().map(|h| h.destination.as_str()).collect();
+                    println!("{} ({}): {}", group.name, hosts.len(), hosts.join(", "));
+                }
+                if dry_run {
+                    println!("Dry run - {} group(s) not saved", groups.len());
+                } else {
+                    let count = groups.len();
+                    HostGroupStore::new(&self.config.export_dir).replace(groups)?;
+                    println!(
+                        "Imported {} group(s); deploy to one with `skm deploy <key> --group <name>`",
+                        count
+                    );
+                }
+            }
+            HostsCommands::Groups => {
+                let groups = HostGroupStore::new(&self.config.export_dir).load()?;
+                if groups.is_empty() {
+                    println!(
+                        "No host groups yet; import some with `skm hosts import --from <inventory>`"
+                    );
+                    return Ok(());
+                }
+                let mut table = Table::new(&["Group", "Hosts", "Source"]);
+                for group in &groups {
+                    let hosts: Vec<&str> =
+                        group.hosts.iter().map(|h| h.destination.as_str()).collect();
+                    table.row(vec![
+                        group.name.clone().into(),
+                        hosts.join(", ").into(),
+                        group.source.clone().into(),
+                    ]);
+                }
+                table.print();
+            }
+            HostsCommands::Known { command } => self.cmd_known_hosts(command)?,
+        }
+
+        Ok(())
+    }
+
+    fn cmd_known_hosts(&self, command: KnownHostsCommands) -> Result<()> {
+        let mut known_hosts = KnownHosts::load(self.config.ssh_dir.join("known_hosts"))?;
+
+        match command {
+            KnownHostsCommands::List { host, port, format } => {
+                let entries = match host {
+                    Some(ref host) => known_hosts.find(host, port),
+                    None => known_hosts.entries.iter().collect(),
+                };
+                let hash = self.config.fingerprint_hash;
+                let entries: Vec<KnownHost> = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let mut entry = entry.clone();
+                        entry.fingerprint = entry.fingerprint_as(hash);
+                        entry
+                    })
+                    .collect();
+
+                match format {
+                    OutputFormat::Table => {
+                        if entries.is_empty() {
+                            println!("No known hosts found in {}.", known_hosts.path().display());
+                            return Ok(());
+                        }
+
+                        println!("{:<6} {:<40} {:<22} Fingerprint", "Line", "Host", "Type");
+                        println!("{}", "-".repeat(120));
+                        for entry in entries {
+                            let mut hosts = if entry.hashed {
+                                "(hashed)".to_string()
+                            } else {
+                                entry.hosts.join(",")
+                            };
+                            if let Some(ref marker) = entry.marker {
+                                hosts = format!("{} {}", marker, hosts);
+                            }
+                            println!(
+                                "{:<6} {:<40} {:<22} {}",
+                                entry.line,
+                                hosts,
+                                entry.key_type,
+                                entry.fingerprint.as_deref().unwrap_or("-")
+                            );
+                        }
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    }
+                    OutputFormat::Names => {
+                        let mut names: Vec<&str> = entries
+                            .iter()
+                            .filter(|e| !e.hashed)
+                            .flat_map(|e| e.hosts.iter().map(String::as_str))
+                            .collect();
+                        names.sort_unstable();
+                        names.dedup();
+                        for name in names {
+                            println!("{}", name);
+                        }
+                    }
+                }
+            }
+            KnownHostsCommands::Remove { host, port } => {
+                let name = host_key_name(&host, port);
+                match known_hosts.remove_host(&host, port)? {
+                    0 => {
+                        return Err(crate::error::SkmError::HostNotFound(format!(
+                            "{} in {}",
+                            name,
+                            known_hosts.path().display()
+                        )));
+                    }
+                    removed => println!(
+                        "Removed {} key{} for {} from {} (previous file kept as known_hosts.old)",
+                        removed,
+                        if removed == 1 { "" } else { "s" },
+                        name,
+                        known_hosts.path().display()
+                    ),
+                }
+            }
+            KnownHostsCommands::Hash => match known_hosts.hash_hosts()? {
+                0 => println!("No plain host names to hash."),
+                hashed => println!(
+                    "Hashed {} host name{} in {} (previous file kept as known_hosts.old)",
+                    hashed,
+                    if hashed == 1 { "" } else { "s" },
+                    known_hosts.path().display()
+                ),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn cmd_publish(&self, name: String, provider: Provider, title: Option<String>) -> Result<()> {
+        let settings = self.config.load_settings()?;
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let key = find_key(&scanner, &name)?;
+        ApprovalStore::new(&self.config.export_dir).require_approved(&key, "publish")?;
+        let public_key = key.read_public_key()?;
+
+        let title = title
+            .or_else(|| Some(public_key.comment().to_string()).filter(|c| !c.is_empty()))
+            .unwrap_or_else(|| format!("{}@{}", name, get_hostname()));
+
+        let line = public_key
+            .to_openssh()
+            .map_err(|e| crate::error::SkmError::SshKey(e.to_string()))?;
+
+        match provider {
+            Provider::Github => {
+                let uploaded = GithubClient::from_settings(&settings).add_key(&title, &line)?;
+                println!(
+                    "Uploaded key '{}' to GitHub as \"{}\" (id {})",
+                    name, uploaded.title, uploaded.id
+                );
+            }
+            Provider::Gitlab => {
+                let client = GitlabClient::from_settings(&settings);
+                let uploaded = client.add_key(&title, &line)?;
+                println!(
+                    "Uploaded key '{}' to {} as \"{}\" (id {})",
+                    name,
+                    client.base_url(),
+                    uploaded.title,
+                    uploaded.id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_auth(&self, command: AuthCommands) -> Result<()> {
+        let settings = self.config.load_settings()?;
+
+        match command {
+            AuthCommands::Login { provider, token } => {
+                let forge = provider.to_forge();
+                let token = match token {
+                    Some(token) if token != "-" => Some(token),
+                    _ => read_passphrase_from_stdin(&format!("Paste a {} token: ", forge.name()))?,
+                }
+                .ok_or_else(|| crate::error::SkmError::Config("No token given".to_string()))?;
+
+                let user = forge_user(provider, &settings, &token)?;
+                auth::store_token(forge, &settings, &token)?;
+                println!(
+                    "Logged in to {} as {}; token saved in the OS keychain",
+                    forge.name(),
+                    user
+                );
+                if let Some((_, source @ TokenSource::Env(_) | source @ TokenSource::Config)) =
+                    auth::resolve_token(forge, &settings)
+                {
+                    println!("Note: the token from {} still takes precedence", source);
+                }
+            }
+            AuthCommands::Logout { provider } => {
+                let forge = provider.to_forge();
+                if auth::delete_token(forge, &settings)? {
+                    println!("Removed the {} token from the OS keychain", forge.name());
+                } else {
+                    println!("No {} token in the OS keychain", forge.name());
+                }
+            }
+            AuthCommands::Status => {
+                for provider in [Provider::Github, Provider::Gitlab] {
+                    let forge = provider.to_forge();
+                    match auth::resolve_token(forge, &settings) {
+                        None => println!("{}: not logged in", forge.name()),
+                        Some((token, source)) => match forge_user(provider, &settings, &token) {
+                            Ok(user) => {
+                                println!("{}: {} (token from {})", forge.name(), user, source)
+                            }
+                            Err(e) => println!(
+                                "{}: token from {} doesn't work: {}",
+                                forge.name(),
+                                source,
+                                e
+                            ),
+                        },
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_agent(&self, command: AgentCommands) -> Result<()> {
+        if let AgentCommands::Status = command {
+            return agent_status();
+        }
+        if matches!(command, AgentCommands::Add { .. }) {
+            warn_forwarded_agent();
+        }
+
+        let mut agent = AgentClient::connect_env()?;
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let lifetimes = LifetimeStore::new(&self.config.export_dir);
+
+        match command {
+            AgentCommands::List => {
+                let identities = agent.list_identities()?;
+                if identities.is_empty() {
+                    println!("The agent has no identities.");
+                    return Ok(());
+                }
+
+                let keys = scanner.scan()?;
+                let hash = self.config.fingerprint_hash;
+                let lifetimes = lifetimes.active().unwrap_or_default();
+                let now = chrono::Local::now();
+                for identity in identities {
+                    let bits = key_bits(identity.key_data())
+                        .map(|b| b.to_string())
+                        .unwrap_or_else(|| "?".to_string());
+                    let key_type = KeyType::from_algorithm(&identity.public_key.algorithm());
+                    let local = keys
+                        .iter()
+                        .find(|k| {
+                            k.read_public_key()
+                                .is_ok_and(|pk| pk.key_data() == identity.key_data())
+                        })
+                        .map(|k| format!(" [{}]", k.name))
+                        .unwrap_or_default();
+                    let expires = agent::expires_at(&lifetimes, identity.key_data())
+                        .map(|at| format!(" expires {}", dates::relative(at, now)))
+                        .unwrap_or_default();
+
+                    println!(
+                        "{} {} {} ({}){}{}",
+                        bits,
+                        fingerprint_with(&identity.public_key, hash),
+                        identity.comment,
+                        key_type,
+                        local,
+                        expires
+                    );
+                }
+            }
+            AgentCommands::Add {
+                from_backup: Some(backup_path),
+                keys,
+                backup_passphrase,
+                passphrase,
+                lifetime,
+                confirm,
+                ..
+            } => {
+                let backup_passphrase = match backup_passphrase.as_deref() {
+                    Some(p) if p != "-" => p.to_string(),
+                    _ => read_passphrase_from_stdin("Enter backup passphrase: ")?.ok_or_else(
+                        || {
+                            std::io::Error::new(
+                                std::io::ErrorKind::InvalidInput,
+                                "Passphrase required",
+                            )
+                        },
+                    )?,
+                };
+                let backup = BackupManager::read(&backup_path, &backup_passphrase)?;
+
+                let missing: Vec<&str> = keys
+                    .iter()
+                    .filter(|name| !backup.keys.iter().any(|e| &e.name == *name))
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(crate::error::SkmError::KeyNotFound(format!(
+                        "{} (not in {})",
+                        missing.join(", "),
+                        backup_path.display()
+                    )));
+                }
+
+                let entries = backup
+                    .keys
+                    .iter()
+                    .filter(|e| keys.is_empty() || keys.contains(&e.name))
+                    .filter(|e| e.private_key.is_some());
+
+                let mut added = 0;
+                for entry in entries {
+                    let passphrase = if entry.is_encrypted() {
+                        match passphrase.as_deref() {
+                            Some(p) if p != "-" => Some(p.to_string()),
+                            _ => read_passphrase_from_stdin(&format!(
+                                "Enter passphrase for {}: ",
+                                entry.name
+                            ))?,
+                        }
+                    } else {
+                        None
+                    };
+
+                    let mut private_key = entry.load_private_key(passphrase.as_deref())?;
+                    if private_key.comment().is_empty() {
+                        private_key.set_comment(entry.comment.as_deref().unwrap_or(&entry.name));
+                    }
+                    agent.add_identity(&private_key, lifetime, confirm)?;
+                    record_lifetime(&lifetimes, private_key.public_key().key_data(), lifetime);
+                    println!("Added key to agent from backup: {}", entry.name);
+                    added += 1;
+                }
+
+                if added == 0 {
+                    println!("No private keys to load from {}.", backup_path.display());
+                }
+            }
+            AgentCommands::Add {
+                name,
+                passphrase,
+                lifetime,
+                confirm,
+                ..
+            } => {
+                let name = name.expect("clap requires a name without --from-backup");
+                let key = find_key(&scanner, &name)?;
+
+                let passphrase = if key.is_encrypted() {
+                    match passphrase.as_deref() {
+                        Some(p) if p != "-" => Some(p.to_string()),
+                        _ => {
+                            read_passphrase_from_stdin(&format!("Enter passphrase for {}: ", name))?
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let mut private_key = key.load_private_key(passphrase.as_deref())?;
+                if private_key.comment().is_empty() {
+                    // Like ssh-add, fall back to something that identifies the key
+                    let comment = key
+                        .comment
+                        .clone()
+                        .unwrap_or_else(|| key.path.display().to_string());
+                    private_key.set_comment(comment);
+                }
+                agent.add_identity(&private_key, lifetime, confirm)?;
+                record_lifetime(&lifetimes, private_key.public_key().key_data(), lifetime);
+
+                match lifetime {
+                    Some(seconds) => {
+                        println!("Added key to agent: {} (lifetime {}s)", name, seconds)
+                    }
+                    None => println!("Added key to agent: {}", name),
+                }
+            }
+            AgentCommands::Remove { name } => {
+                let key = find_key(&scanner, &name)?;
+                let public_key = key.read_public_key()?;
+                agent.remove_identity(public_key.key_data())?;
+                record_lifetime(&lifetimes, public_key.key_data(), None);
+                println!("Removed key from agent: {}", name);
+            }
+            AgentCommands::Clear => {
+                agent.remove_all()?;
+                if let Err(e) = lifetimes.clear() {
+                    tracing::warn!("Failed to forget agent key lifetimes: {}", e);
+                }
+                println!("All keys removed from agent.");
+            }
+            AgentCommands::Status => unreachable!("handled before connecting"),
+        }
+
+        Ok(())
+    }
+
+    fn cmd_audit(&self, format: OutputFormat, max_age: Option<String>) -> Result<()> {
+        let mut policy = self.expiry_policy()?;
+        if let Some(max_age) = max_age {
+            policy = policy.with_max_age(schedule::parse_interval(&max_age)?);
+        }
+
+        let keys = KeyScanner::new(&self.config.ssh_dir).scan()?;
+        let mut report = AuditReport::run(&self.config.ssh_dir, &keys);
+        report.add(audit::audit_agent(&AgentOrigin::detect()));
+        report.add(audit::audit_expiry(&keys, &policy, chrono::Local::now()));
+        let hash = self.config.fingerprint_hash;
+        if hash != FingerprintHash::Sha256 {
+            for finding in &mut report.findings {
+                finding.fingerprint = keys
+                    .iter()
+                    .find(|key| key.name == finding.key)
+                    .and_then(|key| key.fingerprint_as(hash));
+            }
+        }
+
+        match format {
+            OutputFormat::Table => {
+                if !report.findings.is_empty() {
+                    println!(
+                        "{:<9} {:<20} {:<51} Issue",
+                        "Severity", "Key", "Fingerprint"
+                    );
+                    println!("{}", "-".repeat(120));
+                    for finding in &report.findings {
+                        println!(
+                            "{:<9} {:<20} {:<51} {}",
+                            finding.severity,
+                            finding.key,
+                            finding.fingerprint.as_deref().unwrap_or("-"),
+                            finding.message
+                        );
+                    }
+                    println!();
+                }
+
+                println!(
+                    "{} key{} checked: {} critical, {} warning{}",
+                    report.keys_checked,
+                    if report.keys_checked == 1 { "" } else { "s" },
+                    report.count(Severity::Critical),
+                    report.count(Severity::Warning),
+                    if report.count(Severity::Warning) == 1 {
+                        ""
+                    } else {
+                        "s"
+                    }
+                );
+            }
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            OutputFormat::Names => {
+                let mut names: Vec<&str> = report.findings.iter().map(|f| f.key.as_str()).collect();
+                names.sort_unstable();
+                names.dedup();
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+        }
+
+        // Lets CI fail on keys that are due for rotation
+        if report
+            .findings
+            .iter()
+            .any(|finding| finding.kind == FindingKind::Expired)
+        {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    fn cmd_doctor(&self) -> Result<()> {
+        let checks = doctor::run(&self.config);
+
+        for check in &checks {
+            // Multi-line messages (e.g. TOML errors) stay under their heading
+            let message = check.message.trim_end().replace('\n', "\n        ");
+            println!("[{:<5}] {}: {}", check.status, check.name, message);
+            if let Some(ref fix) = check.fix {
+                println!("        fix: {}", fix);
+            }
+        }
+
+        let count = |status| checks.iter().filter(|c| c.status == status).count();
+        let (errors, warnings) = (count(Status::Error), count(Status::Warning));
+        println!();
+        if errors == 0 && warnings == 0 {
+            println!("Everything looks good.");
+        } else {
+            println!("{} error(s), {} warning(s)", errors, warnings);
+        }
+
+        // Scripts only need the exit status
+        if errors > 0 {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    fn cmd_fix_perms(&self, dry_run: bool) -> Result<()> {
+        let keys = KeyScanner::new(&self.config.ssh_dir).scan()?;
+        let changes = perms::plan(&self.config.ssh_dir, &keys);
+
+        if changes.is_empty() {
+            println!("All permissions are already safe.");
+            return Ok(());
+        }
+
+        if dry_run {
+            println!("Dry run - would change:");
+        } else {
+            perms::apply(&changes)?;
+        }
+        for change in &changes {
+            println!("  {}", change);
+        }
+        if !dry_run {
+            println!("Fixed permissions on {} file(s).", changes.len());
+        }
+
+        Ok(())
+    }
+
+    fn cmd_ca(&self, command: CaCommands) -> Result<()> {
+        match command {
+            CaCommands::Sign {
+                key,
+                ca,
+                identity,
+                principals,
+                host,
+                validity,
+                serial,
+                options,
+                output,
+                ca_passphrase,
+            } => {
+                let scanner = KeyScanner::new(&self.config.ssh_dir);
+
+                let ca_key = resolve_key(&scanner, &ca)?;
+                let ca_passphrase = if ca_key.is_encrypted() {
+                    match ca_passphrase.as_deref() {
+                        Some(p) if p != "-" => Some(p.to_string()),
+                        _ => read_passphrase_from_stdin(&format!(
+                            "Enter passphrase for CA key {}: ",
+                            ca_key.name
+                        ))?,
+                    }
+                } else {
+                    None
+                };
+                let ca_private = ca_key.load_private_key(ca_passphrase.as_deref())?;
+
+                // A .pub file is certified as is; anything else is a key
+                // whose public half is looked up the usual way
+                let (name, subject, default_output) =
+                    if key.ends_with(".pub") && scanner.find_key_by_name(&key)?.is_none() {
+                        let path = std::path::PathBuf::from(&key);
+                        let content = crate::ssh::keys::read_key_text(&path)?;
+                        let subject = ssh_key::PublicKey::from_openssh(content.trim())
+                            .map_err(|e| crate::error::SkmError::InvalidKeyFormat(e.to_string()))?;
+                        let private_path = path.with_extension("");
+                        let name = private_path
+                            .file_name()
+                            .map(|n| n.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        (name, subject, cert::cert_path(&private_path))
+                    } else {
+                        let key = resolve_key(&scanner, &key)?;
+                        let subject = key.read_public_key()?;
+                        (key.name, subject, cert::cert_path(&key.path))
+                    };
+
+                let cert_type = if host { CertType::Host } else { CertType::User };
+                let mut request =
+                    SignRequest::new(cert_type, identity.as_deref().unwrap_or(&name), &validity)?;
+                request.principals = principals;
+                request.serial = serial;
+                for option in &options {
+                    request.apply_option(option)?;
+                }
+
+                let certificate = ca::sign(&ca_private, &subject, &request)?;
+                let encoded = certificate
+                    .to_openssh()
+                    .map_err(|e| crate::error::SkmError::SshKey(e.to_string()))?;
+
+                let output = output.unwrap_or(default_output);
+                if output.as_os_str() == "-" {
+                    println!("{}", encoded);
+                    return Ok(());
+                }
+                std::fs::write(&output, format!("{}\n", encoded))?;
+
+                let info = cert::CertInfo::load(&output, Some(&subject))?;
+                println!(
+                    "Signed {} certificate: {}",
+                    info.cert_type,
+                    output.display()
+                );
+                println!("  Key ID:     {}", info.key_id);
+                println!("  Serial:     {}", info.serial);
+                println!("  Principals: {}", info.principals_display());
+                println!("  Valid:      {}", info.window());
+                println!("  CA:         {}", info.ca_fingerprint);
+                Ok(())
+            }
+            CaCommands::Verify {
+                cert,
+                ca,
+                principal,
+                format,
+            } => {
+                let certificate = cert::read_certificate(&cert)?;
+
+                let scanner = KeyScanner::new(&self.config.ssh_dir);
+                let mut trusted = Vec::new();
+                for ca in &ca {
+                    match scanner.find_key_by_name(ca)? {
+                        Some(key) => trusted.push(key.read_public_key()?),
+                        None => trusted.extend(ca::read_trusted_keys(std::path::Path::new(ca))?),
+                    }
+                }
+                if ca.is_empty() {
+                    for path in self.config.load_settings()?.ca.trusted_keys {
+                        trusted.extend(ca::read_trusted_keys(&expand_path(&path))?);
+                    }
+                }
+                if trusted.is_empty() {
+                    return Err(crate::error::SkmError::Config(
+                        "No trusted CA keys: pass --ca or set ca.trusted_keys in config.toml"
+                            .to_string(),
+                    ));
+                }
+
+                let now = chrono::Utc::now().timestamp().max(0) as u64;
+                let result = ca::verify(&cert, &certificate, &trusted, principal.as_deref(), now);
+
+                match format {
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                    OutputFormat::Table | OutputFormat::Names => {
+                        let info = &result.certificate;
+                        println!("Certificate: {}", cert.display());
+                        println!("  Type:       {}", info.cert_type);
+                        println!("  Key ID:     {}", info.key_id);
+                        println!("  Serial:     {}", info.serial);
+                        println!();
+                        for check in &result.checks {
+                            println!(
+                                "  {:<4}  {:<10} {}",
+                                if check.passed { "ok" } else { "FAIL" },
+                                check.check,
+                                check.detail
+                            );
+                        }
+                        println!();
+                        println!(
+                            "{}",
+                            if result.valid {
+                                "Certificate is valid"
+                            } else {
+                                "Certificate is NOT valid"
+                            }
+                        );
+                    }
+                }
+
+                // Scripts only need the exit status
+                if !result.valid {
+                    std::process::exit(1);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn cmd_sign(
+        &self,
+        files: Vec<std::path::PathBuf>,
+        key: String,
+        namespace: String,
+        passphrase: Option<String>,
+    ) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+        let key = resolve_key(&scanner, &key)?;
+
+        let passphrase = if key.is_encrypted() {
+            match passphrase.as_deref() {
+                Some(p) if p != "-" => Some(p.to_string()),
+                // stdin carries the data being signed, so it can't carry the passphrase
+                _ if files.iter().any(|f| f.as_os_str() == "-") => {
+                    return Err(crate::error::SkmError::Config(
+                        "Pass --passphrase to sign stdin with an encrypted key".to_string(),
+                    ));
+                }
+                _ => read_passphrase_from_stdin(&format!("Enter passphrase for {}: ", key.name))?,
+            }
+        } else {
+            None
+        };
+        let private_key = key.load_private_key(passphrase.as_deref())?;
+
+        for file in &files {
+            if file.as_os_str() == "-" {
+                let mut data = Vec::new();
+                io::Read::read_to_end(&mut io::stdin(), &mut data)?;
+                print!("{}", sshsig::sign(&private_key, &namespace, &data)?);
+                continue;
+            }
+
+            let data = std::fs::read(file)?;
+            let signature = sshsig::sign(&private_key, &namespace, &data)?;
+            let sig_path = sshsig::signature_path(file);
+            std::fs::write(&sig_path, signature)?;
+            eprintln!("Signed {} -> {}", file.display(), sig_path.display());
+        }
+
+        Ok(())
+    }
+
+    fn cmd_verify(
+        &self,
+        file: std::path::PathBuf,
+        signature: Option<std::path::PathBuf>,
+        identity: Option<String>,
+        namespace: String,
+        key: Option<String>,
+        allowed_signers: Option<std::path::PathBuf>,
+    ) -> Result<()> {
+        let from_stdin = file.as_os_str() == "-";
+        let sig_path = match signature {
+            Some(path) => path,
+            None if from_stdin => {
+                return Err(crate::error::SkmError::Config(
+                    "Pass --signature when verifying stdin".to_string(),
+                ));
+            }
+            None => sshsig::signature_path(&file),
+        };
+        let signature = ssh_key::SshSig::from_pem(std::fs::read(&sig_path)?).map_err(|e| {
+            crate::error::SkmError::InvalidKeyFormat(format!("{}: {}", sig_path.display(), e))
+        })?;
+
+        let mut data = Vec::new();
+        if from_stdin {
+            io::Read::read_to_end(&mut io::stdin(), &mut data)?;
+        } else {
+            data = std::fs::read(&file)?;
+        }
+
+        let fingerprint = fingerprint_with(
+            &ssh_key::PublicKey::from(signature.public_key().clone()),
+            self.config.fingerprint_hash,
+        );
+        let signer = match key {
+            Some(key) => {
+                let scanner = KeyScanner::new(&self.config.ssh_dir);
+                let public_key = resolve_public_key(&scanner, &key)?;
+                allowed_signers::verify_with_key(&public_key, &namespace, &data, &signature)
+                    .map(|_| key)
+            }
+            None => {
+                let path = allowed_signers
+                    .unwrap_or_else(|| self.config.ssh_dir.join(ALLOWED_SIGNERS_FILE));
+                let signers = AllowedSigners::load(&path)?;
+                signers
+                    .verify(identity.as_deref(), &namespace, &data, &signature)
+                    .map(|entry| match identity {
+                        Some(identity) => identity,
+                        None => entry.principals.join(","),
+                    })
+            }
+        };
+
+        match signer {
+            Ok(signer) => {
+                println!(
+                    "Good \"{}\" signature for {} with {} key {}",
+                    namespace,
+                    signer,
+                    signature.algorithm(),
+                    fingerprint
+                );
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Could not verify signature: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn cmd_signers(
+        &self,
+        command: SignersCommands,
+        file: Option<std::path::PathBuf>,
+    ) -> Result<()> {
+        let path = file.unwrap_or_else(|| self.config.ssh_dir.join(ALLOWED_SIGNERS_FILE));
+        let mut signers = AllowedSigners::load(&path)?;
+
+        match command {
+            SignersCommands::List { format } => {
+                let hash = self.config.fingerprint_hash;
+                for entry in &mut signers.entries {
+                    entry.fingerprint = entry.public_key().map(|key| fingerprint_with(key, hash));
+                }
+                match format {
+                    OutputFormat::Table => {
+                        if signers.entries.is_empty() {
+                            println!("No allowed signers in {}.", signers.path().display());
+                            return Ok(());
+                        }
+
+                        println!(
+                            "{:<6} {:<32} {:<16} {:<52} Comment",
+                            "Line", "Identities", "Namespaces", "Fingerprint"
+                        );
+                        println!("{}", "-".repeat(120));
+                        for entry in &signers.entries {
+                            let mut identities = entry.principals.join(",");
+                            if entry.cert_authority {
+                                identities = format!("@ca {}", identities);
+                            }
+                            println!(
+                                "{:<6} {:<32} {:<16} {:<52} {}",
+                                entry.line,
+                                identities,
+                                entry
+                                    .namespaces
+                                    .as_ref()
+                                    .map(|n| n.join(","))
+                                    .unwrap_or_else(|| "*".to_string()),
+                                entry.fingerprint.as_deref().unwrap_or("-"),
+                                entry.comment.as_deref().unwrap_or("")
+                            );
+                        }
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&signers.entries)?);
+                    }
+                    OutputFormat::Names => {
+                        let mut names: Vec<&str> = signers
+                            .entries
+                            .iter()
+                            .flat_map(|e| e.principals.iter().map(String::as_str))
+                            .collect();
+                        names.sort_unstable();
+                        names.dedup();
+                        for name in names {
+                            println!("{}", name);
+                        }
+                    }
+                }
+            }
+            SignersCommands::Add {
+                key,
+                identity,
+                namespaces,
+            } => {
+                let scanner = KeyScanner::new(&self.config.ssh_dir);
+                let public_key = resolve_public_key(&scanner, &key)?;
+                if signers.add(&identity, &public_key, &namespaces)? {
+                    println!(
+                        "Allowed {} to sign with {} in {}",
+                        identity.join(", "),
+                        key,
+                        signers.path().display()
+                    );
+                } else {
+                    println!("{} may already sign with {}", identity.join(", "), key);
+                }
+            }
+            SignersCommands::Remove { identity } => match signers.remove_principal(&identity)? {
+                0 => {
+                    return Err(crate::error::SkmError::KeyNotFound(format!(
+                        "{} in {}",
+                        identity,
+                        signers.path().display()
+                    )));
+                }
+                changed => println!(
+                    "Removed {} from {} entr{} in {}",
+                    identity,
+                    changed,
+                    if changed == 1 { "y" } else { "ies" },
+                    signers.path().display()
+                ),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn cmd_snapshot(&self, command: Option<SnapshotCommands>) -> Result<()> {
+        let store = SnapshotStore::new(&self.config.export_dir);
+
+        match command {
+            None => {
+                let previous = store.latest()?;
+                let mut snapshot = Snapshot::capture(&self.config.ssh_dir)?;
+                let path = store.save(&mut snapshot)?;
+                println!(
+                    "Saved snapshot {} of {} file(s) to {}",
+                    snapshot.id(),
+                    snapshot.files.len() - 1,
+                    path.display()
+                );
+                if let Some(previous) = previous {
+                    let changes = previous.diff(&snapshot);
+                    println!(
+                        "{} change(s) since snapshot {}",
+                        changes.len(),
+                        previous.id()
+                    );
+                }
+            }
+            Some(SnapshotCommands::Diff { id }) => {
+                let snapshot = match id {
+                    Some(id) => store.load(&id)?,
+                    None => store.latest()?.ok_or_else(|| {
+                        crate::error::SkmError::Config(
+                            "No snapshots yet; take one with `skm snapshot`".to_string(),
+                        )
+                    })?,
+                };
+                let current = Snapshot::capture(&self.config.ssh_dir)?;
+                let changes = snapshot.diff(&current);
+
+                if changes.is_empty() {
+                    println!(
+                        "No changes since snapshot {} ({})",
+                        snapshot.id(),
+                        snapshot.taken_at.format("%Y-%m-%d %H:%M:%S")
+                    );
+                    return Ok(());
+                }
+
+                println!(
+                    "{} change(s) since snapshot {} ({}):",
+                    changes.len(),
+                    snapshot.id(),
+                    snapshot.taken_at.format("%Y-%m-%d %H:%M:%S")
+                );
+                let mut table = Table::new(&["File", "Change"]);
+                for change in &changes {
+                    let color = match change {
+                        Change::Added(_) => Color::Green,
+                        Change::Removed(_) => Color::Red,
+                        Change::Modified { .. } => Color::Yellow,
+                    };
+                    table.row(vec![
+                        change.name().into(),
+                        Cell::colored(change.describe(), color),
+                    ]);
+                }
+                table.print();
+
+                // Like diff(1), so cron jobs and scripts can alert on it
+                std::process::exit(1);
+            }
+            Some(SnapshotCommands::List) => {
+                let ids = store.list()?;
+                if ids.is_empty() {
+                    println!("No snapshots yet; take one with `skm snapshot`");
+                    return Ok(());
+                }
+
+                let mut table = Table::new(&["Id", "Files", "SSH Directory"]).align_right(1);
+                for id in ids {
+                    let snapshot = store.load(&id)?;
+                    table.row(vec![
+                        id.into(),
+                        (snapshot.files.len() - 1).to_string().into(),
+                        snapshot.ssh_dir.display().to_string().into(),
+                    ]);
+                }
+                table.print();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_team(&self, command: Option<TeamCommands>) -> Result<()> {
+        let repo = TeamRepo::new(&self.config.export_dir);
+
+        match command {
+            Some(TeamCommands::Join { url }) => {
+                repo.join(&url)?;
+                println!("Joined the team at {} ({})", url, repo.dir().display());
+                println!("Run 'skm team sync' to publish this machine's public keys.");
+            }
+            Some(TeamCommands::Sync) => {
+                let member = team::member_name(&get_username(), &get_hostname());
+                let approvals = ApprovalStore::new(&self.config.export_dir);
+                let mut keys = KeyScanner::new(&self.config.ssh_dir).scan()?;
+                let mut held = Vec::new();
+                for key in std::mem::take(&mut keys) {
+                    if approvals.is_approved(&key)? {
+                        keys.push(key);
+                    } else {
+                        held.push(key.name);
+                    }
+                }
+                if !held.is_empty() {
+                    println!("Not publishing keys without approval: {}", held.join(", "));
+                }
+                let deployments = DeploymentStore::new(&self.config.export_dir).load()?;
+                if repo.sync(&member, &keys, &deployments)? {
+                    println!("Published {} key(s) as {}", keys.len(), member);
+                } else {
+                    println!("Already up to date as {}", member);
+                }
+            }
+            None | Some(TeamCommands::List) => {
+                if !repo.is_joined() {
+                    println!("Not in a team yet; join one with `skm team join <repository>`");
+                    return Ok(());
+                }
+                let members = repo.members()?;
+                if members.is_empty() {
+                    println!("Nobody has published keys yet; run `skm team sync`");
+                    return Ok(());
+                }
+
+                let mut table = Table::new(&["Member", "Key", "Fingerprint", "Deployed To"]);
+                for member in &members {
+                    for key in &member.keys {
+                        let hosts: Vec<String> = member
+                            .deployments_of(&key.fingerprint)
+                            .map(|d| match d.port {
+                                Some(port) => format!("{}:{}", d.destination, port),
+                                None => d.destination.clone(),
+                            })
+                            .collect();
+                        table.row(vec![
+                            member.name.clone().into(),
+                            key.name.clone().into(),
+                            key.fingerprint.clone().into(),
+                            hosts.join(", ").into(),
+                        ]);
+                    }
+                }
+                table.print();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_approval(&self, command: Option<ApprovalCommands>) -> Result<()> {
+        let store = ApprovalStore::new(&self.config.export_dir);
+        let decision = match command {
+            Some(ApprovalCommands::Approve { name }) => Some((name, ApprovalState::Approved)),
+            Some(ApprovalCommands::Reject { name }) => Some((name, ApprovalState::Rejected)),
+            None | Some(ApprovalCommands::List) => None,
+        };
+
+        if let Some((name, state)) = decision {
+            let key = find_key(&KeyScanner::new(&self.config.ssh_dir), &name)?;
+            let decided_by = team::member_name(&get_username(), &get_hostname());
+            let decided = store.decide(&key, state, &decided_by)?;
+            approval::notify_webhook(&self.config.load_settings()?.approval, &decided);
+            println!("Key '{}' {}", name, state);
+            return Ok(());
+        }
+
+        let approvals = store.load()?;
+        if approvals.is_empty() {
+            println!("No keys have been held for approval.");
+            return Ok(());
+        }
+        let date_format = self.date_format()?;
+        let mut table = Table::new(&["Key", "State", "Requested", "Decided"]);
+        for a in &approvals {
+            let requested = format!(
+                "{} by {}",
+                date_format.short(a.requested_at),
+                a.requested_by
+            );
+            let decided = match (a.decided_at, &a.decided_by) {
+                (Some(at), Some(by)) => format!("{} by {}", date_format.short(at), by),
+                _ => String::new(),
+            };
+            table.row(vec![
+                a.key_name.clone().into(),
+                a.state.to_string().into(),
+                requested.into(),
+                decided.into(),
+            ]);
+        }
+        table.print();
+        Ok(())
+    }
+
+    fn cmd_lock_passphrase(&self, remove: bool) -> Result<()> {
+        let lock = LockPassphrase::new(&self.config.export_dir);
+
+        if remove {
+            if lock.remove()? {
+                println!("Lock passphrase removed");
+            } else {
+                println!("No lock passphrase was set");
+            }
+            return Ok(());
+        }
+
+        let passphrase = read_passphrase_from_stdin("Enter lock passphrase: ")?;
+        let confirm = read_passphrase_from_stdin("Confirm lock passphrase: ")?;
+        if passphrase != confirm {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Passphrases do not match",
+            )
+            .into());
+        }
+
+        lock.set(passphrase.as_deref().unwrap_or_default())?;
+        println!("Lock passphrase set");
+        Ok(())
+    }
+
+    fn cmd_passphrase(&self, name: String, old: Option<String>, new: Option<String>) -> Result<()> {
+        let scanner = KeyScanner::new(&self.config.ssh_dir);
+
+        let key = scanner
+            .find_key_by_name(&name)?
+            .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.clone()))?;
+
+        // Only ask for the current passphrase if the key is actually encrypted
+        let old = if key.is_encrypted() {
+            match old.as_deref() {
+                Some(p) if p != "-" => Some(p.to_string()),
+                _ => read_passphrase_from_stdin("Enter current passphrase: ")?,
+            }
+        } else {
+            None
+        };
+
+        let new = match new.as_deref() {
+            Some("-") | None => {
+                let new =
+                    read_passphrase_from_stdin("Enter new passphrase (empty for no passphrase): ")?;
+                if new.is_some() {
+                    let confirm = read_passphrase_from_stdin("Confirm new passphrase: ")?;
+                    if confirm != new {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Passphrases do not match",
+                        )
+                        .into());
+                    }
+                }
+                new
+            }
+            Some(p) if !p.is_empty() => Some(p.to_string()),
+            _ => None,
+        };
+
+        if let Err(e) =
+            KeyAgeStore::new(&self.config.export_dir).remember(std::slice::from_ref(&key))
+        {
+            tracing::warn!("Failed to record the age of '{}': {}", name, e);
+        }
+        key.change_passphrase(old.as_deref(), new.as_deref())?;
+
+        if new.is_some() {
+            println!("Passphrase updated for key: {}", name);
+        } else {
+            println!("Passphrase removed from key: {}", name);
+        }
+
+        Ok(())
+    }
+}
+
+fn find_key(scanner: &KeyScanner, name: &str) -> Result<SshKey> {
+    scanner
+        .find_key_by_name(name)?
+        .ok_or_else(|| crate::error::SkmError::KeyNotFound(name.to_string()))
+}
+
+/// Account a forge token belongs to, which also proves the token works
+fn forge_user(
+    provider: Provider,
+    settings: &crate::config::Settings,
+    token: &str,
+) -> Result<String> {
+    match provider {
+        Provider::Github => GithubClient::new(Some(token.to_string())).authenticated_user(),
+        Provider::Gitlab => GitlabClient::new(
+            &crate::forge::gitlab::gitlab_url(settings),
+            Some(token.to_string()),
+        )
+        .authenticated_user(),
+    }
+}
+
+/// A managed key by name, or any key file by path
+fn resolve_key(scanner: &KeyScanner, name_or_path: &str) -> Result<SshKey> {
+    match scanner.find_key_by_name(name_or_path)? {
+        Some(key) => Ok(key),
+        None if std::path::Path::new(name_or_path).is_file() => SshKey::from_path(name_or_path),
+        None => Err(crate::error::SkmError::KeyNotFound(
+            name_or_path.to_string(),
+        )),
+    }
+}
+
+/// Public key of a managed key, or of a public or private key file
+fn resolve_public_key(scanner: &KeyScanner, name_or_path: &str) -> Result<ssh_key::PublicKey> {
+    if scanner.find_key_by_name(name_or_path)?.is_none() && name_or_path.ends_with(".pub") {
+        let content = crate::ssh::keys::read_key_text(std::path::Path::new(name_or_path))?;
+        return ssh_key::PublicKey::from_openssh(content.trim())
+            .map_err(|e| crate::error::SkmError::InvalidKeyFormat(e.to_string()));
+    }
+    resolve_key(scanner, name_or_path)?.read_public_key()
+}
+
+/// Deploy target for `destination`; `identity` may be a managed key name or a path
+fn deploy_target(
+    scanner: &KeyScanner,
+    destination: &str,
+    port: Option<u16>,
+    identity: Option<String>,
+) -> Result<DeployTarget> {
+    let mut target = DeployTarget::parse(destination)?;
+    target.port = port;
+    target.identity = match identity {
+        Some(identity) => Some(match scanner.find_key_by_name(&identity)? {
+            Some(auth_key) => auth_key.path,
+            None => std::path::PathBuf::from(identity),
+        }),
+        None => None,
+    };
+    Ok(target)
+}
+
+fn agent_status() -> Result<()> {
+    let origin = AgentOrigin::detect();
+    let socket = match origin {
+        AgentOrigin::NoAgent => {
+            println!("No agent: SSH_AUTH_SOCK is not set.");
+            return Ok(());
+        }
+        AgentOrigin::Local { ref socket } => {
+            println!("Agent:     local");
+            socket
+        }
+        AgentOrigin::Forwarded {
+            ref socket,
+            ref client,
+        } => {
+            println!(
+                "Agent:     forwarded from {}",
+                client.as_deref().unwrap_or("the ssh client")
+            );
+            socket
+        }
+    };
+    println!("Socket:    {}", socket.display());
+
+    match AgentClient::connect(socket).and_then(|mut agent| agent.list_identities()) {
+        Ok(identities) => println!("Keys:      {} loaded", identities.len()),
+        Err(e) => println!("Keys:      unavailable ({})", e),
+    }
+
+    if let Some(finding) = audit::audit_agent(&origin) {
+        println!();
+        println!("Warning: {}.", finding.message);
+        println!("Consider ProxyJump (`ssh -J`) instead of agent forwarding.");
+    }
+    Ok(())
+}
+
+/// Keys added to a forwarded agent leave this machine for the client's agent
+fn warn_forwarded_agent() {
+    if let AgentOrigin::Forwarded { client, .. } = AgentOrigin::detect() {
+        eprintln!(
+            "Warning: the agent is forwarded from {}; the key will be stored there, and root \
+             on this host can use it while you are connected.",
+            client.as_deref().unwrap_or("the ssh client")
+        );
+    }
+}
+
+/// Keep track of how long a key stays in the agent; the key is loaded either
+/// way, so failing to note it is only worth a warning
+fn record_lifetime(store: &LifetimeStore, key: &KeyData, lifetime: Option<u32>) {
+    if let Err(e) = store.record(key, lifetime) {
+        tracing::warn!("Failed to record the agent lifetime: {}", e);
+    }
+}
+
+/// Lock opened by any of the passphrases or recipients, prompting for each
+/// passphrase given as `-`, or for one when there is nothing at all
+fn encryption_lock(
+    mut passphrases: Vec<String>,
+    recipients: Vec<Box<dyn age::Recipient>>,
+) -> Result<Lock> {
+    if passphrases.is_empty() && recipients.is_empty() {
+        passphrases.push("-".to_string());
+    }
+    let passphrases = passphrases
+        .into_iter()
+        .map(|passphrase| match passphrase.as_str() {
+            "-" => read_passphrase_from_stdin("Enter encryption passphrase: ")?.ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required").into()
+            }),
+            _ => Ok(passphrase),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Lock::any_of(passphrases, recipients)
+}
+
+/// Identities when given, else the passphrase, prompting for it when missing or `-`
+fn unlock_backup(passphrase: Option<&str>, identities: &[std::path::PathBuf]) -> Result<Unlock> {
+    if !identities.is_empty() {
+        return Unlock::identities(identities);
+    }
+
+    let passphrase = match passphrase {
+        Some(p) if p != "-" => p.to_string(),
+        _ => read_passphrase_from_stdin("Enter decryption passphrase: ")?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Passphrase required")
+        })?,
+    };
+    Ok(Unlock::Passphrase(passphrase))
+}
+
+fn print_backup_schedule(auto: &AutoBackup, schedule: &BackupSchedule) -> Result<()> {
+    let backups = auto.backups()?;
+    let recipients = schedule.recipients.len() + schedule.recipients_files.len();
+    println!(
+        "Every:      {}",
+        schedule::format_interval(schedule.every_secs)
+    );
+    println!("Keep:       {}", schedule.keep);
+    println!("SSH dir:    {}", schedule.ssh_dir.display());
+    println!("Encrypted:  to {} recipient(s)/file(s)", recipients);
+    match schedule.state.last_run {
+        Some(last) => println!("Last run:   {}", last.format("%Y-%m-%d %H:%M")),
+        None => println!("Last run:   never"),
+    }
+    if let Some(failed) = schedule
+        .state
+        .last_failure
+        .filter(|_| schedule.state.failures > 0)
+    {
+        println!(
+            "Failed:     {} time(s) in a row, last {}",
+            schedule.state.failures,
+            failed.format("%Y-%m-%d %H:%M")
+        );
+    }
+    match schedule.next_due() {
+        Some(due) if due > chrono::Local::now() => {
+            println!("Next due:   {}", due.format("%Y-%m-%d %H:%M"))
+        }
+        _ => println!("Next due:   now (on the next run of skm)"),
+    }
+    match backups.last() {
+        Some(latest) => println!(
+            "Backups:    {} (latest {})",
+            backups.len(),
+            latest.display()
+        ),
+        None => println!("Backups:    none yet"),
+    }
+    Ok(())
+}
+
+/// `backup auto --daemon`: back up whenever due until the schedule is
+/// disabled. The schedule is re-read each round, so changes apply without a
+/// restart.
+fn run_backup_daemon(auto: &AutoBackup) -> Result<()> {
+    const MIN_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+    const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+    println!("Waiting for scheduled backups; Ctrl+C to stop");
+    loop {
+        if auto.load()?.is_none() {
+            println!("Automatic backups were disabled; stopping");
+            return Ok(());
+        }
+
+        // Keep going after a failure; it is retried once the backoff is over
+        match auto.run_if_due() {
+            Ok(Some(report)) => println!(
+                "{} {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M"),
+                report.summary()
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!("Automatic backup failed: {}", e),
+        }
+        let Some(schedule) = auto.load()? else {
+            continue;
+        };
+        let until_due = schedule
+            .next_due()
+            .and_then(|due| (due - chrono::Local::now()).to_std().ok())
+            .unwrap_or(MIN_SLEEP);
+        std::thread::sleep(until_due.clamp(MIN_SLEEP, MAX_SLEEP));
+    }
+}
+
+/// What `import` reads
+enum ImportInput {
+    File(std::path::PathBuf, ImportFormatArg),
+    /// An skm backup fetched from a remote, never written to disk
+    Fetched(RemoteBackup, Vec<u8>),
+}
+
+/// How `export` protects what it writes
+enum ExportProtection {
+    /// Encrypt so any one of the passphrases or recipients opens it; with
+    /// neither, prompt for a passphrase. `-` prompts for that passphrase.
+    Encrypted {
+        passphrases: Vec<String>,
+        recipients: Vec<Box<dyn age::Recipient>>,
+    },
+    /// `--plain-tar`: no encryption; `confirmed` skips the prompt
+    Plain { confirmed: bool },
+}
+
+/// Make the user spell out that they want private keys written in the
+/// clear. Scripts must pass --i-know-what-im-doing instead.
+fn confirm_plain_export(count: usize, output: &std::path::Path) -> Result<bool> {
+    use std::io::IsTerminal;
+
+    if !io::stdin().is_terminal() {
+        return Err(crate::error::SkmError::ImportExport(
+            "--plain-tar writes private keys unencrypted; pass --i-know-what-im-doing to confirm"
+                .to_string(),
+        ));
+    }
+
+    eprintln!(
+        "WARNING: {} key(s) will be written to {} WITHOUT ENCRYPTION.\n\
+         Anyone who can read that file can use the private keys in it.",
+        count,
+        output.display()
+    );
+    eprint!("Type 'yes' to continue: ");
+    io::stderr().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim() == "yes")
+}
+
+fn read_passphrase_from_stdin(prompt: &str) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let input = input.trim().to_string();
+    if input.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(input))
+    }
+}
+
+/// Comma separated file names, e.g. for a key's backups
+fn file_names(paths: &[std::path::PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Byte count in the largest unit that keeps it above 1, e.g. `3.2 KiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Certificates, backups and sockets that don't belong to a listed key
+fn print_artifacts(artifacts: &[Artifact]) {
+    if artifacts.is_empty() {
+        return;
+    }
+    let described: Vec<String> = artifacts
+        .iter()
+        .map(|a| {
+            let name = a.path.file_name().unwrap_or_default().to_string_lossy();
+            match a.kind {
+                ArtifactKind::Certificate => format!("{} (certificate without key)", name),
+                kind => format!("{} ({})", name, kind),
+            }
+        })
+        .collect();
+    println!("\nOther files: {}", described.join(", "));
+}
+
+/// Like the plain table, plus the details `ssh-add -l` shows
+fn print_long_table(
+    keys: &[SshKey],
+    show_header: bool,
+    dates: &DateFormat,
+    expiry: Option<&ExpiryPolicy>,
+) {
+    let mut headers = vec!["Name", "Type", "Bits", "Created", "Fingerprint", "Comment"];
+    if expiry.is_some() {
+        headers.push("Expires");
+    }
+    let mut table = Table::new(&headers).align_right(2).show_header(show_header);
+
+    for key in keys {
+        let bits = key
+            .read_public_key()
+            .ok()
+            .and_then(|pk| key_bits(pk.key_data()))
+            .map(|b| b.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        let fingerprint = key.fingerprint.as_deref().unwrap_or("-");
+        let created = key
+            .created_at
+            .or(key.modified_at)
+            .map(|time| dates.short(time))
+            .unwrap_or_else(|| "-".to_string());
+        let comment = key.comment.as_deref().unwrap_or("-");
+
+        let mut row = vec![
+            key.name.as_str().into(),
+            key.key_type.to_string().into(),
+            bits.into(),
+            created.into(),
+            fingerprint.into(),
+            comment.into(),
+        ];
+        row.extend(expiry.map(|policy| expiry_cell(policy, key)));
+        table.row(row);
+    }
+    table.print();
+}
+
+/// When the key expires, red once it has and yellow shortly before
+fn expiry_cell(policy: &ExpiryPolicy, key: &SshKey) -> Cell {
+    let now = chrono::Local::now();
+    match policy.check(key, now) {
+        None => "-".into(),
+        Some(expiry) => {
+            let when = crate::dates::relative(expiry.expires_at(), now);
+            match expiry {
+                Expiry::Valid(_) => when.into(),
+                Expiry::Soon(_) => Cell::colored(when, Color::Yellow),
+                Expiry::Expired(_) => Cell::colored(format!("expired {}", when), Color::Red),
+            }
+        }
     }
 }
 
-fn get_hostname() -> String {
-    hostname::get()
-        .ok()
-        .and_then(|h: std::ffi::OsString| h.into_string().ok())
-        .unwrap_or_else(|| "localhost".to_string())
+/// Key status, colored by how much attention it needs
+fn status_cell(status: KeyStatus) -> Cell {
+    let color = match status {
+        KeyStatus::Valid => Color::Green,
+        KeyStatus::Encrypted => Color::Cyan,
+        KeyStatus::MissingPublic | KeyStatus::MissingPrivate => Color::Yellow,
+        KeyStatus::Corrupted => Color::Red,
+    };
+    Cell::colored(format!("{:?}", status), color)
 }
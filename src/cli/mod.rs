@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -14,6 +14,14 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub debug: bool,
 
+    /// Never start the interactive TUI (print usage when no command is given)
+    #[arg(long, global = true)]
+    pub no_tui: bool,
+
+    /// Hash to show fingerprints with (default: `[display] fingerprint_hash`, else sha256)
+    #[arg(long, global = true, value_enum, value_name = "HASH")]
+    pub fingerprint_hash: Option<FingerprintHashArg>,
+
     /// CLI mode - run command without TUI
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -26,6 +34,22 @@ pub enum Commands {
         /// Output format
         #[arg(short, long, value_enum, default_value = "table")]
         format: OutputFormat,
+
+        /// Only show keys with this health status
+        #[arg(long, value_enum)]
+        status: Option<StatusFilter>,
+
+        /// Print a health summary after the list
+        #[arg(long)]
+        summary: bool,
+
+        /// Include fingerprints, bit sizes and key ages in the table
+        #[arg(short, long)]
+        long: bool,
+
+        /// Leave out the table header
+        #[arg(long)]
+        no_header: bool,
     },
 
     /// Generate a new SSH key
@@ -49,51 +73,168 @@ pub enum Commands {
         /// Key bits (for RSA only)
         #[arg(short, long, default_value = "4096")]
         bits: u32,
+
+        /// Dry run - show what would be created and the matching ssh-keygen command
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Write the key even if other users can write to the ssh directory or a parent
+        #[arg(long)]
+        allow_unsafe_dir: bool,
     },
 
     /// Export keys to encrypted backup
     Export {
         /// Output file path
-        #[arg(short, long)]
-        output: PathBuf,
+        #[arg(short, long, required_unless_present_any = ["plain_tar", "to"])]
+        output: Option<PathBuf>,
 
-        /// Passphrase for encryption (use '-' for stdin)
+        /// Also upload the backup to S3 or an S3-compatible store (s3://bucket/path.skm,
+        /// needs the `s3` feature) or over SFTP (sftp://[user@]host[:port]/path.skm);
+        /// without --output the local copy goes to ~/.skm
+        #[arg(long, value_name = "URL")]
+        to: Option<String>,
+
+        /// Key (name or path) to log in to an sftp:// host with
+        #[arg(long, value_name = "KEY")]
+        ssh_key: Option<String>,
+
+        /// Passphrase for encryption (use '-' for stdin); repeat it so any one of several
+        /// passphrases opens the backup, e.g. for a team lead and a deputy
         #[arg(short, long)]
-        passphrase: Option<String>,
+        passphrase: Vec<String>,
 
-        /// Export only specific keys (by name)
+        /// Encrypt to an age recipient (age1..., or a plugin one such as age1yubikey1...)
+        /// instead of, or besides, a passphrase; repeatable
         #[arg(short, long)]
+        recipient: Vec<String>,
+
+        /// Encrypt to every age recipient listed in a file, one per line; repeatable
+        #[arg(short = 'R', long)]
+        recipients_file: Vec<PathBuf>,
+
+        /// Export only these keys, by name (comma-separated or repeated)
+        #[arg(short, long, value_delimiter = ',')]
         keys: Vec<String>,
 
+        /// Export the keys that exist when some names given to --keys don't
+        #[arg(long)]
+        ignore_missing: bool,
+
         /// Export public keys only (no private keys)
         #[arg(long)]
         public_only: bool,
 
         /// Description for the backup
-        #[arg(short, long)]
+        #[arg(long)]
         description: Option<String>,
+
+        /// Skip keys the audit flags as broken (mismatched, unparseable, DSA, weak RSA)
+        #[arg(long, conflicts_with = "only_problem")]
+        only_healthy: bool,
+
+        /// Export only the keys the audit flags as broken, e.g. to quarantine them
+        #[arg(long)]
+        only_problem: bool,
+
+        /// Write ASCII-armored text that can be pasted into tickets, emails or password managers
+        #[arg(long)]
+        armor: bool,
+
+        /// Write the keys UNENCRYPTED to a standard tarball laid out like ~/.ssh, for tools
+        /// that can't read skm backups; gzipped for .gz/.tgz paths
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["output", "to", "passphrase", "recipient", "recipients_file", "armor", "description"]
+        )]
+        plain_tar: Option<PathBuf>,
+
+        /// Don't ask for confirmation before writing an unencrypted --plain-tar archive
+        #[arg(long, requires = "plain_tar")]
+        i_know_what_im_doing: bool,
     },
 
-    /// Import keys from encrypted backup
+    /// Import keys from an skm backup or another tool's export
     Import {
-        /// Backup file path
-        #[arg(short, long)]
-        file: PathBuf,
+        /// Backup file path; with a URL in --from, where to keep a copy of the download
+        #[arg(short, long, required_unless_present = "from")]
+        file: Option<PathBuf>,
+
+        /// Format of the file: auto (detected from its contents), skm, tar or json.
+        /// Or an s3:// or sftp:// URL to download the backup from first (see
+        /// `export --to`)
+        #[arg(long, default_value = "auto", value_parser = parse_import_from)]
+        from: ImportFrom,
+
+        /// Key (name or path) to log in to an sftp:// host with
+        #[arg(long, value_name = "KEY")]
+        ssh_key: Option<String>,
 
         /// Passphrase for decryption (use '-' for stdin)
-        #[arg(short, long)]
+        #[arg(short, long, conflicts_with = "identity")]
         passphrase: Option<String>,
 
+        /// Decrypt with an age identity file, plugin identities included, instead of a
+        /// passphrase; repeatable
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+
         /// Merge strategy when key exists
-        #[arg(short, long, value_enum, default_value = "skip")]
+        #[arg(long, value_enum, default_value = "skip")]
         strategy: MergeStrategyArg,
 
+        /// Import only these keys from the backup (comma-separated or repeated)
+        #[arg(short, long, value_delimiter = ',')]
+        keys: Vec<String>,
+
         /// Dry run - show what would be imported without actually importing
         #[arg(long)]
         dry_run: bool,
+
+        /// Import even if other users can write to the ssh directory or a parent
+        #[arg(long)]
+        allow_unsafe_dir: bool,
+    },
+
+    /// Look inside skm backups
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommands,
+    },
+
+    /// Import the newest backup in ~/.skm (for a specific file use `import`)
+    Restore {
+        /// Restore the most recent backup, as listed first by `skm backup list`
+        #[arg(long, required = true)]
+        latest: bool,
+
+        /// Passphrase for decryption (use '-' for stdin)
+        #[arg(short, long, conflicts_with = "identity")]
+        passphrase: Option<String>,
+
+        /// Decrypt with an age identity file instead of a passphrase; repeatable
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+
+        /// Merge strategy when key exists
+        #[arg(long, value_enum, default_value = "skip")]
+        strategy: MergeStrategyArg,
+
+        /// Restore only these keys from the backup (comma-separated or repeated)
+        #[arg(short, long, value_delimiter = ',')]
+        keys: Vec<String>,
+
+        /// Dry run - show what would be restored without actually restoring
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Restore even if other users can write to the ssh directory or a parent
+        #[arg(long)]
+        allow_unsafe_dir: bool,
     },
 
-    /// Delete an SSH key
+    /// Delete an SSH key; it goes to the trash, from where `skm trash restore` gets it back
     Delete {
         /// Key name to delete
         name: String,
@@ -101,12 +242,38 @@ pub enum Commands {
         /// Force deletion without confirmation
         #[arg(short, long)]
         force: bool,
+
+        /// Remove the files for good instead of moving them to the trash
+        #[arg(long)]
+        permanent: bool,
+
+        /// Overwrite the private key before unlinking it (default: `[delete] shred`)
+        #[arg(long)]
+        shred: bool,
+    },
+
+    /// Keys deleted with `skm delete`, kept encrypted until restored or emptied
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
     },
 
     /// Show details of a specific key
     Show {
         /// Key name
         name: String,
+
+        /// Only print the line to paste into a server's authorized_keys
+        #[arg(long)]
+        authorized_line: bool,
+
+        /// Restrictions to prepend to the authorized_keys line, e.g. 'from="1.2.3.4",no-pty'
+        #[arg(long, requires = "authorized_line")]
+        options: Option<String>,
+
+        /// Also draw the fingerprint's randomart, as `ssh-keygen -lv` does
+        #[arg(long, conflicts_with = "authorized_line")]
+        randomart: bool,
     },
 
     /// Copy public key to clipboard (or output to stdout)
@@ -115,13 +282,794 @@ pub enum Commands {
         name: String,
 
         /// Print to stdout instead of copying to clipboard
-        #[arg(short, long)]
+        #[arg(long)]
         stdout: bool,
 
         /// Copy full public key file content (with comment)
         #[arg(short, long)]
         full: bool,
     },
+
+    /// Derive the public key from a private key (like `ssh-keygen -y`)
+    Pubkey {
+        /// Key name
+        name: String,
+
+        /// Write it to the missing .pub file instead of printing it
+        #[arg(long)]
+        write: bool,
+    },
+
+    /// Convert a key to PEM, PKCS#8 or RFC4716, or back to OpenSSH
+    Convert {
+        /// Key name, or path to a key file in any supported encoding
+        key: String,
+
+        /// Target encoding
+        #[arg(long, value_enum)]
+        to: ConvertFormatArg,
+
+        /// Convert the public key (implied when the input is a public key)
+        #[arg(long)]
+        public: bool,
+
+        /// Write to this new file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Passphrase for an encrypted key (use '-' for stdin)
+        #[arg(short, long)]
+        passphrase: Option<String>,
+
+        /// Write an encrypted private key out without its passphrase; needed
+        /// for pem and pkcs8, which are never encrypted
+        #[arg(long)]
+        unencrypted: bool,
+    },
+
+    /// Set the comment stored in a key's public key file
+    Comment {
+        /// Key name
+        #[arg(required_unless_present = "rewrite", conflicts_with = "rewrite")]
+        name: Option<String>,
+
+        /// New comment
+        #[arg(required_unless_present = "rewrite")]
+        comment: Option<String>,
+
+        /// Derive the public key from the private key if the .pub file is missing
+        #[arg(long)]
+        regenerate_public: bool,
+
+        /// Rewrite the comments of all keys instead of setting one
+        #[arg(long, requires_all = ["from_pattern", "to"])]
+        rewrite: bool,
+
+        /// Text to replace in comments (every occurrence)
+        #[arg(long, requires = "rewrite")]
+        from_pattern: Option<String>,
+
+        /// Replacement text
+        #[arg(long, requires = "rewrite")]
+        to: Option<String>,
+
+        /// Show the changes without writing them
+        #[arg(long, requires = "rewrite")]
+        dry_run: bool,
+    },
+
+    /// Rename a key (private and public files)
+    Rename {
+        /// Current key name
+        old: String,
+
+        /// New key name
+        new: String,
+
+        /// Also rewrite matching IdentityFile lines in the ssh config
+        #[arg(long)]
+        update_config: bool,
+    },
+
+    /// Append a public key to a remote host's authorized_keys (like ssh-copy-id)
+    Deploy {
+        /// Key name
+        name: String,
+
+        /// Destination in the form [user@]host
+        #[arg(required_unless_present = "group", conflicts_with = "group")]
+        destination: Option<String>,
+
+        /// Deploy to every host of a group from `skm hosts import`
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// SSH port of the remote host
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Key (name or path) used to authenticate to the host
+        #[arg(short, long)]
+        identity: Option<String>,
+
+        /// Show what would be done without connecting
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Grant short-lived access with a throwaway key that only lives in ssh-agent
+    Ephemeral {
+        /// Destination in the form [user@]host
+        #[arg(long, required_unless_present = "cleanup")]
+        host: Option<String>,
+
+        /// How long the key stays valid, e.g. 30m, 1h, 2d
+        #[arg(long, default_value = "1h")]
+        ttl: String,
+
+        /// SSH port of the remote host
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Key (name or path) used to authenticate while deploying
+        #[arg(short, long)]
+        identity: Option<String>,
+
+        /// Remove expired ephemeral keys from the hosts they were granted on
+        #[arg(long, conflicts_with = "host")]
+        cleanup: bool,
+    },
+
+    /// Manage keys loaded in ssh-agent
+    Agent {
+        #[command(subcommand)]
+        command: AgentCommands,
+    },
+
+    /// Sync public keys with GitHub
+    Github {
+        #[command(subcommand)]
+        command: GithubCommands,
+    },
+
+    /// Keep managed sections of authorized_keys in sync with a key source
+    Authorized {
+        #[command(subcommand)]
+        command: AuthorizedCommands,
+    },
+
+    /// Store GitHub and GitLab tokens in the OS keychain
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+
+    /// Register a public key with a git hosting provider
+    Publish {
+        /// Key name
+        name: String,
+
+        /// Provider to publish to
+        #[arg(long, value_enum, default_value = "github")]
+        provider: Provider,
+
+        /// Title shown by the provider (default: key comment or name@hostname)
+        #[arg(short, long)]
+        title: Option<String>,
+    },
+
+    /// Manage Host entries in the ssh client config
+    Hosts {
+        #[command(subcommand)]
+        command: HostsCommands,
+    },
+
+    /// Use a key as an SSH certificate authority
+    Ca {
+        #[command(subcommand)]
+        command: CaCommands,
+    },
+
+    /// Sign files with a key, like ssh-keygen -Y sign
+    Sign {
+        /// Files to sign; each signature goes to <file>.sig ('-' signs stdin to stdout)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Key (name or path) to sign with
+        #[arg(short, long)]
+        key: String,
+
+        /// Signature namespace, e.g. file, git or email
+        #[arg(short, long, default_value = "file")]
+        namespace: String,
+
+        /// Passphrase for an encrypted key (use '-' for stdin)
+        #[arg(short, long)]
+        passphrase: Option<String>,
+    },
+
+    /// Verify a file signature, like ssh-keygen -Y verify
+    Verify {
+        /// Signed file ('-' for stdin)
+        file: PathBuf,
+
+        /// Signature file (default: <file>.sig)
+        #[arg(long)]
+        signature: Option<PathBuf>,
+
+        /// Signer identity that must be allowed in allowed_signers (default: any)
+        #[arg(short = 'I', long)]
+        identity: Option<String>,
+
+        /// Signature namespace, e.g. file, git or email
+        #[arg(short, long, default_value = "file")]
+        namespace: String,
+
+        /// Check against this key (name or path) instead of allowed_signers
+        #[arg(short, long, conflicts_with_all = ["identity", "allowed_signers"])]
+        key: Option<String>,
+
+        /// allowed_signers file (default: allowed_signers in the ssh directory)
+        #[arg(long)]
+        allowed_signers: Option<PathBuf>,
+    },
+
+    /// Manage who may sign, in the allowed_signers file used by verify and git
+    Signers {
+        #[command(subcommand)]
+        command: SignersCommands,
+
+        /// allowed_signers file (default: allowed_signers in the ssh directory)
+        #[arg(long, global = true)]
+        file: Option<PathBuf>,
+    },
+
+    /// Check keys for weak algorithms, missing passphrases and bad permissions
+    Audit {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+
+        /// Flag keys older than this, e.g. 365d or 52w, instead of `[expiry] max_age`;
+        /// exits 1 if any have expired
+        #[arg(long, value_name = "AGE")]
+        max_age: Option<String>,
+    },
+
+    /// Check the ssh setup end to end and suggest fixes for what's wrong
+    Doctor,
+
+    /// Tighten permissions: 700 on the ssh directory, 600 on private and 644 on public keys
+    FixPerms {
+        /// Show what would change without touching anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Record the ssh directory's files, modes and key fingerprints, to spot
+    /// unexpected changes later with `snapshot diff`
+    Snapshot {
+        #[command(subcommand)]
+        command: Option<SnapshotCommands>,
+    },
+
+    /// Share public keys and deploy records with a team through a git
+    /// repository; private keys never leave this machine
+    Team {
+        #[command(subcommand)]
+        command: Option<TeamCommands>,
+    },
+
+    /// Approve or reject keys held back by `[approval] required`; deploy,
+    /// publish and team sync leave pending keys alone
+    Approval {
+        #[command(subcommand)]
+        command: Option<ApprovalCommands>,
+    },
+
+    /// Set the passphrase that unlocks an idle-locked TUI session
+    LockPassphrase {
+        /// Remove the passphrase; unlocking then only needs a key press
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Change or remove the passphrase of a private key
+    Passphrase {
+        /// Key name
+        name: String,
+
+        /// Current passphrase (use '-' for stdin)
+        #[arg(long)]
+        old: Option<String>,
+
+        /// New passphrase, empty to remove it (use '-' for stdin)
+        #[arg(long)]
+        new: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AgentCommands {
+    /// Show which agent is in use, whether it is forwarded, and its key count
+    Status,
+
+    /// List keys currently loaded in the agent
+    List,
+
+    /// Load a key into the agent
+    Add {
+        /// Key name
+        #[arg(
+            required_unless_present = "from_backup",
+            conflicts_with = "from_backup"
+        )]
+        name: Option<String>,
+
+        /// Passphrase for encrypted keys (use '-' for stdin)
+        #[arg(short, long)]
+        passphrase: Option<String>,
+
+        /// Load keys from an skm backup, decrypted in memory and never written to disk
+        #[arg(long, value_name = "FILE")]
+        from_backup: Option<PathBuf>,
+
+        /// Keys to load from the backup, comma separated (default: all)
+        #[arg(long, value_delimiter = ',', requires = "from_backup")]
+        keys: Vec<String>,
+
+        /// Passphrase of the backup file (use '-' for stdin)
+        #[arg(long, requires = "from_backup")]
+        backup_passphrase: Option<String>,
+
+        /// Remove the key from the agent after this many seconds
+        #[arg(short, long)]
+        lifetime: Option<u32>,
+
+        /// Require confirmation each time the key is used
+        #[arg(short, long)]
+        confirm: bool,
+    },
+
+    /// Remove a key from the agent
+    Remove {
+        /// Key name
+        name: String,
+    },
+
+    /// Remove all keys from the agent
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HostsCommands {
+    /// List Host entries
+    List {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Show a single Host entry
+    Show {
+        /// Host alias
+        alias: String,
+    },
+
+    /// Add a Host entry
+    Add {
+        /// Host alias
+        alias: String,
+
+        /// Real host name or IP address
+        #[arg(short = 'H', long)]
+        hostname: Option<String>,
+
+        /// Remote user
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// SSH port
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Key (name or path) to use as IdentityFile
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+
+    /// Remove a Host entry
+    Remove {
+        /// Host alias
+        alias: String,
+    },
+
+    /// Import host groups for `skm deploy --group` from an Ansible YAML
+    /// inventory or the Host entries of ~/.ssh/config
+    Import {
+        /// Inventory file, or `ssh-config`
+        #[arg(long)]
+        from: String,
+
+        /// Only this inventory group; for `ssh-config`, the group to create
+        /// [default: ssh-config]
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// Show the groups without saving them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List imported host groups
+    Groups,
+
+    /// Manage ~/.ssh/known_hosts
+    Known {
+        #[command(subcommand)]
+        command: KnownHostsCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KnownHostsCommands {
+    /// List known host keys
+    List {
+        /// Only show entries for this host (hashed entries included)
+        #[arg(short = 'H', long)]
+        host: Option<String>,
+
+        /// Port of the host, for entries recorded as [host]:port
+        #[arg(short, long, requires = "host")]
+        port: Option<u16>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Remove all keys for a host, e.g. after its host key changed
+    Remove {
+        /// Host name or IP address
+        host: String,
+
+        /// Port of the host, for entries recorded as [host]:port
+        #[arg(short, long)]
+        port: Option<u16>,
+    },
+
+    /// Hash plain host names so the file no longer reveals them
+    Hash,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CaCommands {
+    /// Issue an OpenSSH user or host certificate for a public key
+    Sign {
+        /// Key (name or path to a public key) to certify
+        key: String,
+
+        /// CA private key (name or path)
+        #[arg(long)]
+        ca: String,
+
+        /// Key identity logged by sshd (default: the key's name)
+        #[arg(short = 'I', long)]
+        identity: Option<String>,
+
+        /// Users or host names the certificate is valid for, comma separated
+        /// (required for user certificates; host certificates default to any)
+        #[arg(
+            short = 'n',
+            long,
+            value_delimiter = ',',
+            required_unless_present = "host"
+        )]
+        principals: Vec<String>,
+
+        /// Issue a host certificate instead of a user certificate
+        #[arg(long)]
+        host: bool,
+
+        /// How long the certificate is valid, e.g. 8h, 30d, 52w or forever
+        #[arg(short = 'V', long, default_value = "52w")]
+        validity: String,
+
+        /// Serial number recorded in the certificate
+        #[arg(short = 'z', long, default_value_t = 0)]
+        serial: u64,
+
+        /// Certificate option as for ssh-keygen -O, e.g. no-pty or force-command=CMD (repeatable)
+        #[arg(short = 'O', long = "option")]
+        options: Vec<String>,
+
+        /// Where to write the certificate (default: <key>-cert.pub, '-' for stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Passphrase of the CA key (use '-' for stdin)
+        #[arg(long)]
+        ca_passphrase: Option<String>,
+    },
+
+    /// Check a certificate's signature, CA, validity window and principals
+    Verify {
+        /// Certificate file (e.g. id_ed25519-cert.pub)
+        cert: PathBuf,
+
+        /// Trusted CA public key (name or path, repeatable; default: ca.trusted_keys in config)
+        #[arg(long)]
+        ca: Vec<String>,
+
+        /// User or host name the certificate must be valid for
+        #[arg(short = 'n', long)]
+        principal: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SignersCommands {
+    /// List allowed signers
+    List {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Allow identities to sign with a key
+    Add {
+        /// Key (name or path to a public key)
+        key: String,
+
+        /// Signer identities, e.g. an email address (comma separated)
+        #[arg(short = 'I', long, required = true, value_delimiter = ',')]
+        identity: Vec<String>,
+
+        /// Only accept signatures in these namespaces (comma separated, default: all)
+        #[arg(short, long, value_delimiter = ',')]
+        namespaces: Vec<String>,
+    },
+
+    /// Remove an identity from every entry
+    Remove {
+        /// Signer identity
+        identity: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommands {
+    /// List the backups in ~/.skm, newest first
+    List {
+        /// Passphrase to decrypt them with, to show what's inside (use '-' for stdin)
+        #[arg(short, long, conflicts_with = "identity")]
+        passphrase: Option<String>,
+
+        /// Decrypt with an age identity file instead, to show what's inside; repeatable
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Show a backup's metadata and keys without importing anything
+    Inspect {
+        /// Backup file path
+        file: PathBuf,
+
+        /// Passphrase for decryption (use '-' for stdin)
+        #[arg(short, long, conflicts_with = "identity")]
+        passphrase: Option<String>,
+
+        /// Decrypt with an age identity file instead of a passphrase; repeatable
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Re-encrypt a backup in place with a new passphrase or new recipients,
+    /// e.g. after its passphrase leaked
+    Rekey {
+        /// Backup file path
+        file: PathBuf,
+
+        /// Current passphrase (use '-' for stdin)
+        #[arg(short, long, conflicts_with = "identity")]
+        passphrase: Option<String>,
+
+        /// Decrypt with an age identity file instead of a passphrase; repeatable
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+
+        /// New passphrase (use '-' for stdin); repeat it so any one of several opens the backup
+        #[arg(long, value_name = "PASSPHRASE")]
+        new_passphrase: Vec<String>,
+
+        /// Encrypt to an age recipient instead of, or besides, a new passphrase; repeatable
+        #[arg(short, long)]
+        recipient: Vec<String>,
+
+        /// Encrypt to every age recipient listed in a file; repeatable
+        #[arg(short = 'R', long)]
+        recipients_file: Vec<PathBuf>,
+    },
+
+    /// Back up every key on a schedule: whenever skm runs past the due date,
+    /// or continuously with --daemon. Without options, shows the schedule.
+    Auto {
+        /// How often to back up, e.g. 12h, 7d, 2w
+        #[arg(long)]
+        every: Option<String>,
+
+        /// How many automatic backups to keep [default: 10]
+        #[arg(long)]
+        keep: Option<usize>,
+
+        /// Encrypt to an age recipient; repeatable. Required when first setting a schedule
+        #[arg(short, long)]
+        recipient: Vec<String>,
+
+        /// Encrypt to every age recipient listed in a file; repeatable
+        #[arg(short = 'R', long)]
+        recipients_file: Vec<PathBuf>,
+
+        /// Back up right away, whether due or not
+        #[arg(long)]
+        now: bool,
+
+        /// Keep running, backing up whenever the schedule is due
+        #[arg(long)]
+        daemon: bool,
+
+        /// Turn automatic backups off; existing backups are kept
+        #[arg(long, conflicts_with_all = ["every", "keep", "recipient", "recipients_file", "now", "daemon"])]
+        disable: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotCommands {
+    /// Compare the ssh directory with a snapshot; exits 1 if anything changed
+    Diff {
+        /// Snapshot id (default: the latest)
+        id: Option<String>,
+    },
+
+    /// List saved snapshots
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ApprovalCommands {
+    /// List keys waiting for approval and past decisions
+    List,
+
+    /// Let a pending key be deployed and published
+    Approve {
+        /// Key name
+        name: String,
+    },
+
+    /// Keep a pending key from being deployed or published
+    Reject {
+        /// Key name
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TeamCommands {
+    /// Clone the team's git repository
+    Join {
+        /// Repository URL, e.g. git@github.com:acme/ssh-keys.git
+        url: String,
+    },
+
+    /// Pull the team's changes and push this machine's public keys and deploys
+    Sync,
+
+    /// Show who has which keys deployed where
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommands {
+    /// List deleted keys, most recent first
+    List {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+
+    /// Put a deleted key back where it was
+    Restore {
+        /// Key name (its latest deletion) or trash id
+        name: String,
+    },
+
+    /// Delete the keys in the trash for good
+    Empty {
+        /// Only keys deleted longer ago than this, e.g. 30d
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GithubCommands {
+    /// Upload a public key to your GitHub account (needs a token, see `skm auth login`)
+    Push {
+        /// Key name
+        name: String,
+
+        /// Title shown on GitHub (default: key comment or name@hostname)
+        #[arg(short, long)]
+        title: Option<String>,
+    },
+
+    /// Add a GitHub user's published keys to authorized_keys
+    Pull {
+        /// GitHub username
+        user: String,
+
+        /// Show which keys would be added without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthorizedCommands {
+    /// Authorize the keys of a GitHub organization's members, or of one team
+    SyncFromGithub {
+        /// Organization name
+        #[arg(long)]
+        org: String,
+
+        /// Team slug; all organization members when omitted
+        #[arg(long)]
+        team: Option<String>,
+
+        /// authorized_keys file to render (default: <ssh-dir>/authorized_keys)
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// Show what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommands {
+    /// Check a token against the provider and save it in the OS keychain
+    Login {
+        provider: Provider,
+
+        /// Token to store (use '-' for stdin; prompted for when omitted)
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Remove a stored token from the OS keychain
+    Logout { provider: Provider },
+
+    /// Show which token each provider uses and whether it works
+    Status,
 }
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
@@ -131,6 +1079,29 @@ pub enum OutputFormat {
     Names,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Provider {
+    Github,
+    Gitlab,
+}
+
+impl Provider {
+    pub fn to_forge(self) -> crate::forge::Forge {
+        match self {
+            Provider::Github => crate::forge::Forge::Github,
+            Provider::Gitlab => crate::forge::Forge::Gitlab,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StatusFilter {
+    /// Usable keys (valid or encrypted)
+    Valid,
+    /// Keys with a missing or unreadable half
+    Problem,
+}
+
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum KeyTypeArg {
     Ed25519,
@@ -153,6 +1124,47 @@ impl KeyTypeArg {
     }
 }
 
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum FingerprintHashArg {
+    /// SHA256:uNiV..., as current OpenSSH prints it
+    Sha256,
+    /// MD5:12:f8:..., for older OpenSSH and appliances
+    Md5,
+}
+
+impl FingerprintHashArg {
+    pub fn to_fingerprint_hash(self) -> crate::ssh::keys::FingerprintHash {
+        match self {
+            FingerprintHashArg::Sha256 => crate::ssh::keys::FingerprintHash::Sha256,
+            FingerprintHashArg::Md5 => crate::ssh::keys::FingerprintHash::Md5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ConvertFormatArg {
+    /// OpenSSH private key or authorized_keys line
+    Openssh,
+    /// PKCS#1 (RSA) or SEC1 (ECDSA); Ed25519 uses PKCS#8
+    Pem,
+    /// PKCS#8 private key or SubjectPublicKeyInfo
+    Pkcs8,
+    /// SSH2 public key, as used by commercial SSH servers and PuTTY
+    Rfc4716,
+}
+
+impl ConvertFormatArg {
+    pub fn to_key_format(self) -> crate::ssh::convert::KeyFormat {
+        use crate::ssh::convert::KeyFormat;
+        match self {
+            ConvertFormatArg::Openssh => KeyFormat::OpenSsh,
+            ConvertFormatArg::Pem => KeyFormat::Pem,
+            ConvertFormatArg::Pkcs8 => KeyFormat::Pkcs8,
+            ConvertFormatArg::Rfc4716 => KeyFormat::Rfc4716,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum MergeStrategyArg {
     /// Skip keys that already exist
@@ -174,5 +1186,80 @@ impl MergeStrategyArg {
     }
 }
 
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ImportFormatArg {
+    /// Detect from the file contents
+    Auto,
+    /// Encrypted skm backup
+    Skm,
+    /// Plain or gzipped tar of an ssh directory
+    Tar,
+    /// JSON key list exported by another key manager
+    Json,
+}
+
+/// What `import --from` names: the file's format, or where to fetch it
+#[derive(Debug, Clone)]
+pub enum ImportFrom {
+    Format(ImportFormatArg),
+    Remote(String),
+}
+
+fn parse_import_from(value: &str) -> Result<ImportFrom, String> {
+    if crate::crypto::RemoteBackup::is_url(value) {
+        return Ok(ImportFrom::Remote(value.to_string()));
+    }
+    ImportFormatArg::from_str(value, true).map(ImportFrom::Format)
+}
+
+impl ImportFormatArg {
+    /// `None` means the format has to be detected
+    pub fn to_source_format(self) -> Option<crate::crypto::SourceFormat> {
+        use crate::crypto::SourceFormat;
+        match self {
+            ImportFormatArg::Auto => None,
+            ImportFormatArg::Skm => Some(SourceFormat::Skm),
+            ImportFormatArg::Tar => Some(SourceFormat::Tar),
+            ImportFormatArg::Json => Some(SourceFormat::Json),
+        }
+    }
+}
+
 pub mod commands;
+pub mod table;
 pub use commands::CliExecutor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        // Catches clashing flags, e.g. a subcommand's `-d` next to the global --debug
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_export_description_has_no_short_flag() {
+        let cli = Cli::try_parse_from(["skm", "export", "-o", "out.skm", "-d"]).unwrap();
+        assert!(cli.debug);
+        let cli =
+            Cli::try_parse_from(["skm", "export", "-o", "out.skm", "--description", "laptop"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Export { description: Some(ref d), .. }) if d == "laptop"
+        ));
+    }
+
+    #[test]
+    fn test_export_keys_split_on_commas() {
+        let cli = Cli::try_parse_from(["skm", "export", "-o", "out.skm", "-k", "a,b", "-k", "c"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Export { ref keys, .. }) if keys == &["a", "b", "c"]
+        ));
+    }
+}
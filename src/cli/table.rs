@@ -0,0 +1,220 @@
+//! Aligned text tables for CLI output, squeezed to fit the terminal
+
+use std::io::IsTerminal;
+
+use crossterm::style::{Color, Stylize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const GAP: &str = "  ";
+
+/// Columns are never squeezed below this, so truncated text stays recognisable
+const MIN_COLUMN_WIDTH: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Self {
+            text: text.into(),
+            color: Some(color),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, color: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    headers: Vec<&'static str>,
+    align: Vec<Align>,
+    rows: Vec<Vec<Cell>>,
+    show_header: bool,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            align: vec![Align::Left; headers.len()],
+            rows: Vec::new(),
+            show_header: true,
+        }
+    }
+
+    pub fn align_right(mut self, column: usize) -> Self {
+        self.align[column] = Align::Right;
+        self
+    }
+
+    pub fn show_header(mut self, show: bool) -> Self {
+        self.show_header = show;
+        self
+    }
+
+    pub fn row(&mut self, cells: Vec<Cell>) {
+        self.rows.push(cells);
+    }
+
+    /// Print to stdout, fitted to the terminal. Piped output is never
+    /// truncated or colored, so scripts see every character.
+    pub fn print(&self) {
+        let stdout = std::io::stdout();
+        let terminal = stdout.is_terminal();
+        let max_width = terminal
+            .then(|| crossterm::terminal::size().ok())
+            .flatten()
+            .map(|(columns, _)| columns as usize);
+        let color = terminal && std::env::var_os("NO_COLOR").is_none();
+
+        for line in self.render(max_width, color) {
+            println!("{}", line);
+        }
+    }
+
+    fn render(&self, max_width: Option<usize>, color: bool) -> Vec<String> {
+        let natural: Vec<usize> = (0..self.headers.len())
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.text.width())
+                    .chain(self.show_header.then(|| self.headers[column].width()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let widths = fit_widths(natural, max_width);
+
+        let mut lines = Vec::new();
+        if self.show_header {
+            let header: Vec<Cell> = self.headers.iter().map(|&h| Cell::from(h)).collect();
+            lines.push(self.render_row(&header, &widths, false));
+            let total = widths.iter().sum::<usize>() + GAP.len() * (widths.len() - 1);
+            lines.push("-".repeat(total));
+        }
+        for row in &self.rows {
+            lines.push(self.render_row(row, &widths, color));
+        }
+        lines
+    }
+
+    fn render_row(&self, cells: &[Cell], widths: &[usize], color: bool) -> String {
+        let mut line = String::new();
+        for (column, &width) in widths.iter().enumerate() {
+            let cell = cells.get(column);
+            let text = truncate(cell.map_or("", |c| c.text.as_str()), width);
+            let padding = " ".repeat(width - text.width());
+            let last = column + 1 == widths.len();
+
+            if column > 0 {
+                line.push_str(GAP);
+            }
+            if self.align[column] == Align::Right {
+                line.push_str(&padding);
+            }
+            match cell.and_then(|c| c.color).filter(|_| color) {
+                Some(c) => line.push_str(&text.with(c).to_string()),
+                None => line.push_str(&text),
+            }
+            // No trailing spaces after the last column
+            if self.align[column] == Align::Left && !last {
+                line.push_str(&padding);
+            }
+        }
+        line
+    }
+}
+
+/// Shrink the widest column, one cell at a time, until the row fits
+fn fit_widths(mut widths: Vec<usize>, max_width: Option<usize>) -> Vec<usize> {
+    let Some(max_width) = max_width else {
+        return widths;
+    };
+    let gaps = GAP.len() * widths.len().saturating_sub(1);
+
+    while widths.iter().sum::<usize>() + gaps > max_width {
+        let Some((widest, &width)) = widths.iter().enumerate().max_by_key(|(_, w)| **w) else {
+            break;
+        };
+        if width <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        widths[widest] -= 1;
+    }
+    widths
+}
+
+/// Cut `text` to `width` display columns, marking the cut with `…`
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_aligns_wide_characters() {
+        let mut table = Table::new(&["Name", "Bits", "Comment"]).align_right(1);
+        table.row(vec!["id_ed25519".into(), "256".into(), "日本語".into()]);
+        table.row(vec!["日本".into(), "4096".into(), "-".into()]);
+
+        assert_eq!(
+            table.render(None, false),
+            vec![
+                "Name        Bits  Comment",
+                "-------------------------",
+                "id_ed25519   256  日本語",
+                "日本        4096  -",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_fits_width() {
+        let mut table = Table::new(&["Name", "Comment"]).show_header(false);
+        table.row(vec![
+            "id_ed25519".into(),
+            Cell::colored("a very long comment that does not fit", Color::Red),
+        ]);
+
+        let lines = table.render(Some(30), false);
+        assert_eq!(lines, vec!["id_ed25519  a very long comme…"]);
+        assert_eq!(lines[0].width(), 30);
+    }
+}
@@ -1,12 +1,168 @@
 use directories::BaseDirs;
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use crate::error::{Result, SkmError};
+use crate::ssh::keys::FingerprintHash;
+
+/// User settings file, stored in the export directory
+pub const SETTINGS_FILE: &str = "config.toml";
+
+/// Optional settings from `~/.skm/config.toml`; every field has a default
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub github: ForgeSettings,
+    pub gitlab: ForgeSettings,
+    pub repos: RepoSettings,
+    pub tui: TuiSettings,
+    pub ca: CaSettings,
+    pub display: DisplaySettings,
+    pub expiry: ExpirySettings,
+    pub delete: DeleteSettings,
+    pub keys: KeySettings,
+    pub approval: ApprovalSettings,
+    pub backup: BackupSettings,
+}
+
+/// Backups skm makes by itself
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Automatic backups are off without `[backup.auto]`
+    pub auto: Option<AutoBackupSettings>,
+}
+
+/// `[backup.auto]`, written by `skm backup auto`; when the last backup ran
+/// is kept apart in `~/.skm/auto-backup.json`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AutoBackupSettings {
+    /// How often to back up, e.g. `12h`, `7d` or `2w`
+    pub every: String,
+    /// How many automatic backups to keep [default: 10]
+    pub keep: Option<usize>,
+    /// Age recipients the backups are encrypted to
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Files listing age recipients, read again on every run
+    #[serde(default)]
+    pub recipients_files: Vec<String>,
+    /// Keys to back up; default: the ssh directory skm runs with
+    pub ssh_dir: Option<String>,
+}
+
+/// TUI key bindings: a preset, then keys by action name
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct KeySettings {
+    /// `default`, `vim` or `emacs`
+    pub preset: Option<String>,
+    /// e.g. `delete = "x"` or `down = ["j", "ctrl+n"]`; see [`crate::tui::keymap`]
+    #[serde(flatten)]
+    pub bindings: BTreeMap<String, KeyList>,
+}
+
+/// One key or several for an action
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn keys(&self) -> &[String] {
+        match self {
+            KeyList::One(key) => std::slice::from_ref(key),
+            KeyList::Many(keys) => keys,
+        }
+    }
+}
+
+/// Whether new keys wait for approval before they are deployed or published
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ApprovalSettings {
+    /// Hold newly generated keys as pending until `skm approval approve`
+    pub required: bool,
+    /// URL that gets a JSON POST when a key is held, approved or rejected
+    pub webhook: Option<String>,
+}
+
+/// How `skm delete` and the TUI remove keys
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeleteSettings {
+    /// Overwrite private keys before unlinking them, as with `--shred`
+    pub shred: bool,
+}
+
+/// When keys are due for rotation; ages look like `365d` or `52w`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExpirySettings {
+    /// Age at which every key expires; keys never expire when unset
+    pub max_age: Option<String>,
+    /// Flag keys this long before they expire [default: 30d]
+    pub warn_before: Option<String>,
+    /// Maximum age by key name, or `never`, ahead of `max_age`
+    pub keys: BTreeMap<String, String>,
+}
+
+/// How dates and fingerprints are shown
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// strftime pattern, e.g. `%d.%m.%Y %H:%M`; default: from the locale
+    pub date_format: Option<String>,
+    /// Add "3 months ago" to dates; on unless set to false
+    pub relative_dates: Option<bool>,
+    /// `sha256` or `md5`; `--fingerprint-hash` overrides it
+    pub fingerprint_hash: Option<FingerprintHash>,
+}
+
+/// Certificate authorities `skm ca verify` trusts when none are given
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CaSettings {
+    /// Public key files, one or more CA keys per file like `TrustedUserCAKeys`
+    pub trusted_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TuiSettings {
+    /// Lock the TUI after this many minutes without a key press
+    pub idle_lock_minutes: Option<u64>,
+    /// Rewrite IdentityFile lines in the ssh config when a key is renamed,
+    /// like `skm rename --update-config`
+    pub rename_updates_config: bool,
+}
+
+/// Where to look for git repositories whose remotes use a key
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RepoSettings {
+    /// Directories to search; common ones like `~/src` when empty
+    pub roots: Vec<String>,
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ForgeSettings {
+    pub token: Option<String>,
+    /// Base URL for self-hosted instances
+    pub url: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub ssh_dir: PathBuf,
     pub export_dir: PathBuf,
+    /// Hash to print fingerprints with; see `resolve_fingerprint_hash`
+    pub fingerprint_hash: FingerprintHash,
 }
 
 impl Default for Config {
@@ -27,6 +183,7 @@ impl Config {
         Self {
             ssh_dir,
             export_dir,
+            fingerprint_hash: FingerprintHash::default(),
         }
     }
 
@@ -43,9 +200,103 @@ impl Config {
         Ok(Self {
             ssh_dir,
             export_dir: Self::new().export_dir,
+            fingerprint_hash: FingerprintHash::default(),
         })
     }
 
+    pub fn settings_path(&self) -> PathBuf {
+        self.export_dir.join(SETTINGS_FILE)
+    }
+
+    /// Load settings, falling back to defaults when the file doesn't exist.
+    /// Files in an older format are migrated first, see [`crate::migrate`].
+    pub fn load_settings(&self) -> Result<Settings> {
+        let path = self.settings_path();
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let content = crate::migrate::read_config(&path)?;
+        toml::from_str(&content)
+            .map_err(|e| SkmError::Config(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    /// Write `[backup.auto]` into the settings file, or remove it with
+    /// `None`, leaving the rest of the file and its comments as they are.
+    /// Returns whether the section was there before.
+    pub fn set_auto_backup(&self, auto: Option<&AutoBackupSettings>) -> Result<bool> {
+        let path = self.settings_path();
+        let content = if path.exists() {
+            crate::migrate::read_config(&path)?
+        } else {
+            String::new()
+        };
+        let mut doc: toml_edit::DocumentMut = content
+            .parse()
+            .map_err(|e| SkmError::Config(format!("Invalid {}: {}", path.display(), e)))?;
+
+        let backup = doc
+            .entry("backup")
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| {
+                SkmError::Config(format!("Invalid {}: backup is not a table", path.display()))
+            })?;
+        let existed = backup.contains_key("auto");
+        match auto {
+            Some(auto) => {
+                let mut table = toml_edit::Table::new();
+                table["every"] = toml_edit::value(auto.every.as_str());
+                if let Some(keep) = auto.keep {
+                    table["keep"] = toml_edit::value(i64::try_from(keep).unwrap_or(i64::MAX));
+                }
+                let list = |values: &[String]| {
+                    toml_edit::value(values.iter().collect::<toml_edit::Array>())
+                };
+                if !auto.recipients.is_empty() {
+                    table["recipients"] = list(&auto.recipients);
+                }
+                if !auto.recipients_files.is_empty() {
+                    table["recipients_files"] = list(&auto.recipients_files);
+                }
+                if let Some(ref ssh_dir) = auto.ssh_dir {
+                    table["ssh_dir"] = toml_edit::value(ssh_dir.as_str());
+                }
+                backup.insert("auto", toml_edit::Item::Table(table));
+            }
+            None => {
+                backup.remove("auto");
+                if backup.is_empty() {
+                    doc.remove("backup");
+                }
+            }
+        }
+
+        if existed || auto.is_some() {
+            std::fs::create_dir_all(&self.export_dir)?;
+            std::fs::write(&path, doc.to_string())?;
+        }
+        Ok(existed)
+    }
+
+    /// Settle the hash to print fingerprints with, once at startup: the
+    /// `--fingerprint-hash` flag, then `[display]`, then SHA256
+    pub fn resolve_fingerprint_hash(&mut self, flag: Option<FingerprintHash>) -> Result<()> {
+        self.fingerprint_hash = match flag {
+            Some(hash) => hash,
+            None => self
+                .load_settings()?
+                .display
+                .fingerprint_hash
+                .unwrap_or_default(),
+        };
+        Ok(())
+    }
+
     pub fn ssh_dir_exists(&self) -> bool {
         self.ssh_dir.exists()
     }
@@ -95,4 +346,157 @@ mod tests {
         let result = Config::from_ssh_dir(&ssh_dir);
         assert!(result.is_err());
     }
+
+    /// Config whose data directory is a fresh temp dir
+    fn config_in(temp_dir: &TempDir) -> Config {
+        Config {
+            ssh_dir: temp_dir.path().join(".ssh"),
+            export_dir: temp_dir.path().to_path_buf(),
+            fingerprint_hash: FingerprintHash::default(),
+        }
+    }
+
+    /// Settings read from a `config.toml` with `content`
+    fn settings(content: &str) -> Settings {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_in(&temp_dir);
+        std::fs::write(config.settings_path(), content).unwrap();
+        config.load_settings().unwrap()
+    }
+
+    #[test]
+    fn test_load_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_in(&temp_dir);
+
+        // Missing file means defaults
+        assert!(config.load_settings().unwrap().github.token.is_none());
+
+        std::fs::write(config.settings_path(), "github = 3").unwrap();
+        assert!(config.load_settings().is_err());
+    }
+
+    #[test]
+    fn test_github_settings() {
+        let settings = settings("[github]\ntoken = \"ghp_test\"\n");
+        assert_eq!(settings.github.token.as_deref(), Some("ghp_test"));
+    }
+
+    #[test]
+    fn test_gitlab_settings() {
+        let settings = settings("[gitlab]\nurl = \"https://git.example.com\"\n");
+        assert_eq!(
+            settings.gitlab.url.as_deref(),
+            Some("https://git.example.com")
+        );
+    }
+
+    #[test]
+    fn test_repos_settings() {
+        let settings = settings("[repos]\nroots = [\"~/work\"]\nmax_depth = 2\n");
+        assert_eq!(settings.repos.roots, ["~/work"]);
+        assert_eq!(settings.repos.max_depth, Some(2));
+    }
+
+    #[test]
+    fn test_tui_settings() {
+        let settings = settings("[tui]\nidle_lock_minutes = 5\nrename_updates_config = true\n");
+        assert_eq!(settings.tui.idle_lock_minutes, Some(5));
+        assert!(settings.tui.rename_updates_config);
+    }
+
+    #[test]
+    fn test_ca_settings() {
+        let settings = settings("[ca]\ntrusted_keys = [\"/etc/ssh/user_ca.pub\"]\n");
+        assert_eq!(settings.ca.trusted_keys, ["/etc/ssh/user_ca.pub"]);
+    }
+
+    #[test]
+    fn test_expiry_settings() {
+        let settings =
+            settings("[expiry]\nmax_age = \"365d\"\n\n[expiry.keys]\ndeploy = \"never\"\n");
+        assert_eq!(settings.expiry.max_age.as_deref(), Some("365d"));
+        assert_eq!(settings.expiry.keys["deploy"], "never");
+    }
+
+    #[test]
+    fn test_delete_settings() {
+        assert!(!settings("").delete.shred);
+        assert!(settings("[delete]\nshred = true\n").delete.shred);
+    }
+
+    #[test]
+    fn test_approval_settings() {
+        assert!(!settings("").approval.required);
+        let settings =
+            settings("[approval]\nrequired = true\nwebhook = \"https://hooks.example.com/skm\"\n");
+        assert!(settings.approval.required);
+        assert_eq!(
+            settings.approval.webhook.as_deref(),
+            Some("https://hooks.example.com/skm")
+        );
+    }
+
+    #[test]
+    fn test_display_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = config_in(&temp_dir);
+
+        // [display] fingerprint_hash, unless the flag says otherwise
+        config.resolve_fingerprint_hash(None).unwrap();
+        assert_eq!(config.fingerprint_hash, FingerprintHash::Sha256);
+        std::fs::write(
+            config.settings_path(),
+            "[display]\nfingerprint_hash = \"md5\"\n",
+        )
+        .unwrap();
+        config.resolve_fingerprint_hash(None).unwrap();
+        assert_eq!(config.fingerprint_hash, FingerprintHash::Md5);
+        config
+            .resolve_fingerprint_hash(Some(FingerprintHash::Sha256))
+            .unwrap();
+        assert_eq!(config.fingerprint_hash, FingerprintHash::Sha256);
+        std::fs::write(config.settings_path(), "[display]\nfingerprint_hash = 5\n").unwrap();
+        assert!(config.resolve_fingerprint_hash(None).is_err());
+    }
+
+    #[test]
+    fn test_keys_settings() {
+        let settings =
+            settings("[keys]\npreset = \"vim\"\ndelete = \"x\"\ndown = [\"j\", \"ctrl+n\"]\n");
+        assert_eq!(settings.keys.preset.as_deref(), Some("vim"));
+        assert_eq!(settings.keys.bindings["delete"].keys(), ["x"]);
+        assert_eq!(settings.keys.bindings["down"].keys(), ["j", "ctrl+n"]);
+    }
+
+    #[test]
+    fn test_set_auto_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_in(&temp_dir);
+        let content = "# mine\n[display]\nrelative_dates = false # no \"ago\"\n";
+        std::fs::write(config.settings_path(), content).unwrap();
+
+        let auto = AutoBackupSettings {
+            every: "7d".to_string(),
+            keep: Some(3),
+            recipients: vec!["age1abc".to_string()],
+            recipients_files: Vec::new(),
+            ssh_dir: None,
+        };
+        assert!(!config.set_auto_backup(Some(&auto)).unwrap());
+        let settings = config.load_settings().unwrap();
+        assert_eq!(settings.backup.auto, Some(auto.clone()));
+        assert_eq!(settings.display.relative_dates, Some(false));
+        let written = std::fs::read_to_string(config.settings_path()).unwrap();
+        assert!(written.starts_with(content));
+        assert!(written.contains("[backup.auto]\nevery = \"7d\""));
+
+        assert!(config.set_auto_backup(None).unwrap());
+        assert!(config.load_settings().unwrap().backup.auto.is_none());
+        assert_eq!(
+            std::fs::read_to_string(config.settings_path()).unwrap(),
+            content
+        );
+        assert!(!config.set_auto_backup(None).unwrap());
+    }
 }
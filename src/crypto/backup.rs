@@ -1,16 +1,32 @@
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
-use crate::crypto::encrypt::EncryptionManager;
+use crate::crypto::encrypt::{self, EncryptionManager, Lock, Unlock};
+use crate::crypto::sources::ImportSource;
 use crate::error::{Result, SkmError};
-use crate::ssh::keys::SshKey;
+use crate::ssh::keys::{SshKey, fingerprint, read_key_file};
+use ssh_key::{PrivateKey, PublicKey};
 
-const BACKUP_VERSION: u32 = 1;
+/// Format written by `export`. Version 1 files are bare age output over JSON;
+/// version 2 starts with `BACKUP_MAGIC` and a version byte, and the JSON is
+/// compressed with zstd before encryption. Armored backups spell the header
+/// out as a text line (`SKMBACKUP 2`) followed by ASCII-armored age output.
+/// Version 3 is version 2 with passphrase slots ahead of the age output (see
+/// `Lock::Shared`); it is only written for them, so other backups still
+/// open in an older skm.
+const BACKUP_VERSION: u32 = 3;
+const SINGLE_LOCK_VERSION: u32 = 2;
+const BACKUP_MAGIC: &[u8] = b"SKMBACKUP";
 const BACKUP_EXTENSION: &str = "skm";
 
+/// Largest decompressed backup accepted; real ones are a few KiB per key
+const MAX_BACKUP_SIZE: u64 = 64 * 1024 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupMetadata {
     pub version: u32,
@@ -30,25 +46,167 @@ pub struct BackupEntry {
     pub public_key: Option<Vec<u8>>,
 }
 
+impl BackupEntry {
+    /// Parse the private key held in memory, decrypting it if needed
+    pub fn load_private_key(&self, passphrase: Option<&str>) -> Result<PrivateKey> {
+        let data = self.private_key.as_deref().ok_or_else(|| {
+            SkmError::ImportExport(format!("Backup has no private key for '{}'", self.name))
+        })?;
+        let key = PrivateKey::from_openssh(data)
+            .map_err(|e| SkmError::InvalidKeyFormat(format!("{}: {}", self.name, e)))?;
+        if !key.is_encrypted() {
+            return Ok(key);
+        }
+
+        let passphrase = passphrase.ok_or(SkmError::InvalidPassphrase)?;
+        key.decrypt(passphrase)
+            .map_err(|_| SkmError::InvalidPassphrase)
+    }
+
+    /// Whether the stored private key is passphrase protected
+    pub fn is_encrypted(&self) -> bool {
+        self.private_key
+            .as_deref()
+            .and_then(|data| PrivateKey::from_openssh(data).ok())
+            .is_some_and(|key| key.is_encrypted())
+    }
+
+    /// The stored `.pub`, else the public half of the private key, which
+    /// OpenSSH keeps readable even when the key is encrypted
+    pub fn public_key(&self) -> Option<PublicKey> {
+        let from_pub = self
+            .public_key
+            .as_deref()
+            .and_then(|data| std::str::from_utf8(data).ok())
+            .and_then(|text| PublicKey::from_openssh(text.trim()).ok());
+        from_pub.or_else(|| {
+            let key = PrivateKey::from_openssh(self.private_key.as_deref()?).ok()?;
+            Some(key.public_key().clone())
+        })
+    }
+
+    /// Everything about the entry except the key material
+    pub fn summary(&self) -> EntrySummary {
+        EntrySummary {
+            name: self.name.clone(),
+            key_type: self.key_type.clone(),
+            comment: self.comment.clone(),
+            fingerprint: self.public_key().map(|key| fingerprint(&key)),
+            has_private_key: self.private_key.is_some(),
+            encrypted: self.is_encrypted(),
+        }
+    }
+}
+
+/// A backup entry as shown by `skm backup inspect`
+#[derive(Debug, Clone, Serialize)]
+pub struct EntrySummary {
+    pub name: String,
+    pub key_type: String,
+    pub comment: Option<String>,
+    pub fingerprint: Option<String>,
+    pub has_private_key: bool,
+    pub encrypted: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupData {
     pub metadata: BackupMetadata,
     pub keys: Vec<BackupEntry>,
 }
 
+/// Metadata and key list of a backup, safe to print
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupSummary {
+    pub metadata: BackupMetadata,
+    pub keys: Vec<EntrySummary>,
+}
+
+impl BackupData {
+    pub fn summary(&self) -> BackupSummary {
+        BackupSummary {
+            metadata: self.metadata.clone(),
+            keys: self.keys.iter().map(BackupEntry::summary).collect(),
+        }
+    }
+}
+
+/// What a backup file is encrypted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    Passphrase,
+    Recipients,
+    /// Any one of this many passphrases, possibly besides recipients
+    Passphrases(usize),
+}
+
+impl std::fmt::Display for Protection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protection::Passphrase => f.pad("passphrase"),
+            Protection::Recipients => f.pad("age recipients"),
+            Protection::Passphrases(count) => f.pad(&format!("{} passphrases", count)),
+        }
+    }
+}
+
+/// A backup file as found on disk. The header is readable as is; the
+/// metadata only once the backup has been decrypted.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: DateTime<Local>,
+    pub version: u32,
+    pub armored: bool,
+    pub protection: Protection,
+    pub metadata: Option<BackupMetadata>,
+}
+
+impl BackupFile {
+    /// Read what the header tells without decrypting anything
+    pub fn probe(path: &Path) -> Result<Self> {
+        let content = fs::read(path)?;
+        let file_metadata = fs::metadata(path)?;
+        let (version, encrypted) = split_header(&content)?;
+        let protection = if let Some(count) = encrypt::passphrase_slots(&encrypted)? {
+            Protection::Passphrases(count)
+        } else if EncryptionManager::is_passphrase_protected(&encrypted)? {
+            Protection::Passphrase
+        } else {
+            Protection::Recipients
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: file_metadata.len(),
+            modified: file_metadata.modified()?.into(),
+            version,
+            armored: content
+                .trim_ascii_start()
+                .strip_prefix(BACKUP_MAGIC)
+                .is_some_and(|rest| rest.starts_with(b" ")),
+            protection,
+            metadata: None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ExportOptions {
     pub description: Option<String>,
     pub include_public_only: bool,
     pub selected_keys: Option<Vec<String>>, // None = all keys
+    /// Write ASCII-armored text instead of binary
+    pub armor: bool,
 }
 
-// impl Default for ExportOptions removed (derived instead)
-
 #[derive(Debug, Clone)]
 pub struct ImportOptions {
     pub merge_strategy: MergeStrategy,
     pub dry_run: bool,
+    pub selected_keys: Option<Vec<String>>, // None = all keys
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -58,11 +216,29 @@ pub enum MergeStrategy {
     Rename,       // Rename with timestamp suffix
 }
 
+impl MergeStrategy {
+    pub const ALL: [MergeStrategy; 3] = [
+        MergeStrategy::SkipExisting,
+        MergeStrategy::Overwrite,
+        MergeStrategy::Rename,
+    ];
+
+    /// What happens to a key that already exists, for menus
+    pub fn describe(self) -> &'static str {
+        match self {
+            MergeStrategy::SkipExisting => "Skip: keep the existing key, leave the backup's out",
+            MergeStrategy::Overwrite => "Overwrite: replace the existing key with the backup's",
+            MergeStrategy::Rename => "Rename: import the backup's as <name>_<timestamp>",
+        }
+    }
+}
+
 impl Default for ImportOptions {
     fn default() -> Self {
         Self {
             merge_strategy: MergeStrategy::SkipExisting,
             dry_run: false,
+            selected_keys: None,
         }
     }
 }
@@ -85,16 +261,26 @@ impl BackupManager {
         output_path: &Path,
         passphrase: &str,
         options: ExportOptions,
-    ) -> Result<()> {
+    ) -> Result<ExportReport> {
+        let lock = Lock::Passphrase(passphrase.to_string());
+        self.export_with_progress(keys, output_path, &lock, options, |_, _, _| {})
+    }
+
+    /// Like `export`, but encrypted to `lock` and calling `progress(done,
+    /// total, key)` before each key is read
+    pub fn export_with_progress(
+        &self,
+        keys: &[SshKey],
+        output_path: &Path,
+        lock: &Lock,
+        options: ExportOptions,
+        mut progress: impl FnMut(usize, usize, &SshKey),
+    ) -> Result<ExportReport> {
+        let (selected, missing) = select_keys(keys, options.selected_keys.as_deref());
         let mut backup_keys = Vec::new();
 
-        for key in keys {
-            // Filter if specific keys selected
-            if let Some(ref selected) = options.selected_keys {
-                if !selected.contains(&key.name) {
-                    continue;
-                }
-            }
+        for (i, key) in selected.iter().enumerate() {
+            progress(i, selected.len(), key);
 
             let entry = BackupEntry {
                 name: key.name.clone(),
@@ -111,9 +297,14 @@ impl BackupManager {
             backup_keys.push(entry);
         }
 
+        let report = ExportReport {
+            exported: backup_keys.iter().map(|entry| entry.name.clone()).collect(),
+            missing,
+        };
+
         let backup = BackupData {
             metadata: BackupMetadata {
-                version: BACKUP_VERSION,
+                version: format_version(lock),
                 created_at: Local::now(),
                 hostname: get_hostname(),
                 username: get_username(),
@@ -122,19 +313,81 @@ impl BackupManager {
             },
             keys: backup_keys,
         };
+        write_backup(output_path, &backup, lock, options.armor)?;
 
-        // Serialize to JSON
-        let json =
-            serde_json::to_vec(&backup).map_err(|e| SkmError::ImportExport(e.to_string()))?;
+        Ok(report)
+    }
 
-        // Encrypt
-        let encrypted = EncryptionManager::encrypt_with_passphrase(&json, passphrase)?;
+    /// Re-encrypt a backup in place to `lock`, e.g. when its passphrase has
+    /// leaked. Keys and metadata stay as they were; older format versions are
+    /// brought up to date on the way.
+    pub fn rekey(backup_path: &Path, unlock: &Unlock, lock: &Lock) -> Result<BackupMetadata> {
+        let armor = BackupFile::probe(backup_path)?.armored;
+        let mut backup = Self::read_unlocked(backup_path, unlock)?;
+        backup.metadata.version = format_version(lock);
 
-        // Write to file
-        let mut file = fs::File::create(output_path).map_err(SkmError::Io)?;
-        file.write_all(&encrypted).map_err(SkmError::Io)?;
+        // Swap in a complete file, so a failure leaves the old one intact
+        let mut temp_name = backup_path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".rekey");
+        let temp_path = backup_path.with_file_name(temp_name);
+        let written = write_backup(&temp_path, &backup, lock, armor).and_then(|()| {
+            fs::set_permissions(&temp_path, fs::metadata(backup_path)?.permissions())?;
+            Ok(fs::rename(&temp_path, backup_path)?)
+        });
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        written?;
 
-        Ok(())
+        Ok(backup.metadata)
+    }
+
+    /// Write the keys, unencrypted, to a tarball laid out like `~/.ssh`
+    /// (`tar xzf out.tar.gz -C ~` puts them back), for tools that can't read
+    /// skm backups. Gzipped when the path ends in `.gz` or `.tgz`; the
+    /// description and armor options don't apply.
+    pub fn export_plain_tar(
+        &self,
+        keys: &[SshKey],
+        output_path: &Path,
+        options: ExportOptions,
+    ) -> Result<ExportReport> {
+        let (selected, missing) = select_keys(keys, options.selected_keys.as_deref());
+
+        let mut files = Vec::new();
+        let mut exported = Vec::new();
+        for key in selected {
+            if !options.include_public_only
+                && let Some(private) = self.read_file_if_exists(&key.path)?
+            {
+                files.push((key.name.clone(), Zeroizing::new(private), 0o600));
+            }
+            if let Some(public) = self.read_file_if_exists(&key.public_path)? {
+                let name = format!("{}.pub", key.name);
+                files.push((name, Zeroizing::new(public), 0o644));
+            }
+            exported.push(key.name.clone());
+        }
+
+        // The archive holds private keys in the clear: never readable by
+        // others, including when it replaces an existing file
+        let file = fs::File::create(output_path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+
+        let name = output_path.to_string_lossy();
+        if name.ends_with(".gz") || name.ends_with(".tgz") {
+            let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            // Finish explicitly: dropping the encoder would swallow write errors
+            write_tar(gz, &files)?.finish()?;
+        } else {
+            write_tar(file, &files)?;
+        }
+
+        Ok(ExportReport { exported, missing })
     }
 
     /// Import keys from encrypted backup file
@@ -144,26 +397,57 @@ impl BackupManager {
         passphrase: &str,
         options: ImportOptions,
     ) -> Result<ImportReport> {
-        // Read encrypted file
-        let encrypted = fs::read(backup_path).map_err(SkmError::Io)?;
+        let backup = Self::read(backup_path, passphrase)?;
+        let mut report = self.import_entries(backup.keys, options)?;
+        report.source = Some(backup.metadata);
+        Ok(report)
+    }
 
-        // Decrypt
-        let decrypted = EncryptionManager::decrypt_with_passphrase(&encrypted, passphrase)?;
+    /// Import keys from any supported source, e.g. another tool's export
+    pub fn import_from(
+        &self,
+        source: &dyn ImportSource,
+        path: &Path,
+        unlock: Option<&Unlock>,
+        options: ImportOptions,
+    ) -> Result<ImportReport> {
+        let (entries, metadata) = source.read_backup(path, unlock)?;
+        let mut report = self.import_entries(entries, options)?;
+        report.source = metadata;
+        Ok(report)
+    }
 
-        // Parse JSON
-        let backup: BackupData = serde_json::from_slice(&decrypted)
-            .map_err(|e| SkmError::ImportExport(format!("Invalid backup format: {}", e)))?;
+    /// Import keys from an encrypted backup already in memory, such as one
+    /// fetched from a remote, so no copy of it is left on disk
+    pub fn import_bytes(
+        &self,
+        content: &[u8],
+        unlock: &Unlock,
+        options: ImportOptions,
+    ) -> Result<ImportReport> {
+        let backup = Self::read_bytes(content, unlock)?;
+        let mut report = self.import_entries(backup.keys, options)?;
+        report.source = Some(backup.metadata);
+        Ok(report)
+    }
 
+    fn import_entries(
+        &self,
+        entries: Vec<BackupEntry>,
+        options: ImportOptions,
+    ) -> Result<ImportReport> {
+        let entries = select_entries(entries, options.selected_keys.as_deref())?;
         let mut report = ImportReport {
             imported: Vec::new(),
             skipped: Vec::new(),
             overwritten: Vec::new(),
             errors: Vec::new(),
+            source: None,
         };
 
         if options.dry_run {
             // Just report what would happen
-            for entry in backup.keys {
+            for entry in entries {
                 let target_path = self.ssh_dir.join(&entry.name);
                 if target_path.exists() {
                     match options.merge_strategy {
@@ -181,7 +465,7 @@ impl BackupManager {
         }
 
         // Actually import
-        for entry in backup.keys {
+        for entry in entries {
             match self.import_entry(&entry, options.merge_strategy) {
                 Ok(ImportResult::Imported(name)) => report.imported.push(name),
                 Ok(ImportResult::Skipped(name)) => report.skipped.push(name),
@@ -193,6 +477,37 @@ impl BackupManager {
         Ok(report)
     }
 
+    /// Decrypt and parse a backup file without touching the ssh directory
+    pub fn read(backup_path: &Path, passphrase: &str) -> Result<BackupData> {
+        Self::read_unlocked(backup_path, &Unlock::Passphrase(passphrase.to_string()))
+    }
+
+    /// Read a backup encrypted to either a passphrase or recipients
+    pub fn read_unlocked(backup_path: &Path, unlock: &Unlock) -> Result<BackupData> {
+        // Read encrypted file
+        let content = fs::read(backup_path).map_err(SkmError::Io)?;
+        Self::read_bytes(&content, unlock)
+    }
+
+    /// Like `read_unlocked`, for the contents of a backup file
+    pub fn read_bytes(content: &[u8], unlock: &Unlock) -> Result<BackupData> {
+        let (version, encrypted) = split_header(content)?;
+
+        // Decrypt; the plaintext holds private keys, so wipe it once parsed
+        let decrypted = Zeroizing::new(EncryptionManager::decrypt(&encrypted, unlock)?);
+        let json = if version >= 2 {
+            decompress(&decrypted)?
+        } else {
+            decrypted
+        };
+
+        // Parse JSON
+        let backup: BackupData = serde_json::from_slice(&json)
+            .map_err(|e| SkmError::ImportExport(format!("Invalid backup format: {}", e)))?;
+        check_version(backup.metadata.version)?;
+        Ok(backup)
+    }
+
     fn import_entry(&self, entry: &BackupEntry, strategy: MergeStrategy) -> Result<ImportResult> {
         let private_path = self.ssh_dir.join(&entry.name);
         let public_path = private_path.with_extension("pub");
@@ -260,7 +575,7 @@ impl BackupManager {
 
     fn read_file_if_exists(&self, path: &Path) -> Result<Option<Vec<u8>>> {
         if path.exists() {
-            fs::read(path).map(Some).map_err(SkmError::Io)
+            read_key_file(path).map(Some)
         } else {
             Ok(None)
         }
@@ -269,6 +584,242 @@ impl BackupManager {
     pub fn get_backup_extension() -> &'static str {
         BACKUP_EXTENSION
     }
+
+    /// Backup files directly inside `dir`, newest first by modification time
+    pub fn find_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backups: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|extension| extension == BACKUP_EXTENSION)
+            })
+            .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+            .collect();
+        backups.sort_by(|a, b| b.cmp(a));
+        Ok(backups.into_iter().map(|(_, path)| path).collect())
+    }
+}
+
+/// Version written for backups encrypted to `lock`
+fn format_version(lock: &Lock) -> u32 {
+    if lock.has_slots() {
+        BACKUP_VERSION
+    } else {
+        SINGLE_LOCK_VERSION
+    }
+}
+
+/// Compress and encrypt `backup`, writing the header first
+fn write_backup(path: &Path, backup: &BackupData, lock: &Lock, armor: bool) -> Result<()> {
+    // Serialize to JSON and compress; both hold private keys
+    let json = Zeroizing::new(
+        serde_json::to_vec(backup).map_err(|e| SkmError::ImportExport(e.to_string()))?,
+    );
+    let compressed = Zeroizing::new(
+        zstd::encode_all(json.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)
+            .map_err(|e| SkmError::ImportExport(e.to_string()))?,
+    );
+
+    let version = backup.metadata.version;
+    let mut file = fs::File::create(path).map_err(SkmError::Io)?;
+    file.write_all(BACKUP_MAGIC).map_err(SkmError::Io)?;
+    if armor {
+        let armored = EncryptionManager::encrypt_to_armor(&compressed, lock)?;
+        write!(file, " {}\n{}", version, armored).map_err(SkmError::Io)?;
+    } else {
+        let encrypted = EncryptionManager::encrypt(&compressed, lock)?;
+        file.write_all(&[version as u8]).map_err(SkmError::Io)?;
+        file.write_all(&encrypted).map_err(SkmError::Io)?;
+    }
+    Ok(())
+}
+
+/// Format version and binary age payload of a backup file
+fn split_header(content: &[u8]) -> Result<(u32, Cow<'_, [u8]>)> {
+    // Text pasted from a ticket or email may pick up leading whitespace
+    let Some(rest) = content.trim_ascii_start().strip_prefix(BACKUP_MAGIC) else {
+        // Version 1 had no header
+        return Ok((1, Cow::Borrowed(content)));
+    };
+    let truncated = || SkmError::ImportExport("Truncated backup header".to_string());
+
+    // Armored: the version is the rest of the text line
+    if let Some(rest) = rest.strip_prefix(b" ") {
+        let newline = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(truncated)?;
+        let version = std::str::from_utf8(&rest[..newline])
+            .ok()
+            .and_then(|line| line.trim().parse::<u32>().ok())
+            .ok_or_else(|| SkmError::ImportExport("Invalid backup header".to_string()))?;
+        check_version(version)?;
+        return Ok((
+            version,
+            Cow::Owned(EncryptionManager::dearmor(&rest[newline + 1..])?),
+        ));
+    }
+
+    let (&version, payload) = rest.split_first().ok_or_else(truncated)?;
+    let version = u32::from(version);
+    check_version(version)?;
+    Ok((version, Cow::Borrowed(payload)))
+}
+
+/// Refuse backups written by a newer skm instead of misreading them
+fn check_version(version: u32) -> Result<()> {
+    if version > BACKUP_VERSION {
+        return Err(SkmError::ImportExport(format!(
+            "Backup uses format version {}, but this skm only reads up to version {}; \
+             upgrade skm to import it",
+            version, BACKUP_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Tar `files` (name, contents, mode) under `.ssh/`, returning the writer
+fn write_tar<W: Write>(writer: W, files: &[(String, Zeroizing<Vec<u8>>, u32)]) -> Result<W> {
+    let mtime = Local::now().timestamp().max(0) as u64;
+    let mut builder = tar::Builder::new(writer);
+    for (name, data, mode) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(*mode);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, format!(".ssh/{}", name), data.as_slice())?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Keys named in `selected` (all of them for `None`), plus the names that
+/// matched no key
+pub fn select_keys<'a>(
+    keys: &'a [SshKey],
+    selected: Option<&[String]>,
+) -> (Vec<&'a SshKey>, Vec<String>) {
+    let Some(selected) = selected else {
+        return (keys.iter().collect(), Vec::new());
+    };
+
+    let found = keys
+        .iter()
+        .filter(|key| selected.contains(&key.name))
+        .collect();
+    let missing = selected
+        .iter()
+        .filter(|name| !keys.iter().any(|key| &key.name == *name))
+        .cloned()
+        .collect();
+    (found, missing)
+}
+
+/// Backup entries named in `selected` (all of them for `None`); names that
+/// match no entry are an error, since importing fewer keys than asked for
+/// would go unnoticed
+fn select_entries(
+    entries: Vec<BackupEntry>,
+    selected: Option<&[String]>,
+) -> Result<Vec<BackupEntry>> {
+    let Some(selected) = selected else {
+        return Ok(entries);
+    };
+
+    let missing: Vec<String> = selected
+        .iter()
+        .filter(|name| !entries.iter().any(|entry| &entry.name == *name))
+        .map(|name| {
+            let names = entries.iter().map(|entry| entry.name.as_str());
+            match similar_names(name, names).as_slice() {
+                [] => name.clone(),
+                similar => format!("{} (did you mean {}?)", name, similar.join(", ")),
+            }
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(SkmError::KeyNotFound(format!(
+            "{} not in the backup",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| selected.contains(&entry.name))
+        .collect())
+}
+
+/// Names among `candidates` close to `name`, best match first, for "did you
+/// mean" hints
+pub fn similar_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let wanted = name.to_lowercase();
+    // Allow roughly one typo per three characters
+    let max_distance = (wanted.chars().count() / 3).max(1);
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = edit_distance(&wanted, &lower);
+            let related = lower.contains(&wanted) || wanted.contains(&lower);
+            (distance <= max_distance || related).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Levenshtein distance over characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn decompress(compressed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let invalid =
+        |e: std::io::Error| SkmError::ImportExport(format!("Invalid backup format: {}", e));
+
+    let mut json = Zeroizing::new(Vec::new());
+    zstd::Decoder::new(compressed)
+        .map_err(invalid)?
+        .take(MAX_BACKUP_SIZE + 1)
+        .read_to_end(&mut json)
+        .map_err(invalid)?;
+    if json.len() as u64 > MAX_BACKUP_SIZE {
+        return Err(SkmError::ImportExport(format!(
+            "Backup expands to more than {} MiB",
+            MAX_BACKUP_SIZE / 1024 / 1024
+        )));
+    }
+    Ok(json)
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportReport {
+    pub exported: Vec<String>,
+    /// Requested names that matched no key
+    pub missing: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -277,6 +828,8 @@ pub struct ImportReport {
     pub skipped: Vec<String>,
     pub overwritten: Vec<String>,
     pub errors: Vec<(String, String)>,
+    /// Metadata of the backup the keys came from, for skm backups
+    pub source: Option<BackupMetadata>,
 }
 
 enum ImportResult {
@@ -285,13 +838,13 @@ enum ImportResult {
     Overwritten(String),
 }
 
-fn get_username() -> String {
+pub(crate) fn get_username() -> String {
     std::env::var("USER")
         .or_else(|_| std::env::var("USERNAME"))
         .unwrap_or_else(|_| "user".to_string())
 }
 
-fn get_hostname() -> String {
+pub(crate) fn get_hostname() -> String {
     hostname::get()
         .ok()
         .and_then(|h: std::ffi::OsString| h.into_string().ok())
@@ -301,6 +854,7 @@ fn get_hostname() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::OsRng;
     use tempfile::TempDir;
 
     fn create_test_key(temp_dir: &TempDir, name: &str) -> SshKey {
@@ -342,6 +896,34 @@ mod tests {
         assert!(import_dir.path().join("test_key").exists());
     }
 
+    #[test]
+    fn test_import_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = create_test_key(&temp_dir, "test_key");
+        let backup_path = temp_dir.path().join("backup.skm");
+        BackupManager::new(temp_dir.path())
+            .export(&[key], &backup_path, "test_pass", ExportOptions::default())
+            .unwrap();
+        let content = fs::read(&backup_path).unwrap();
+
+        let import_dir = TempDir::new().unwrap();
+        let manager = BackupManager::new(import_dir.path());
+        let wrong = Unlock::Passphrase("wrong".to_string());
+        assert!(
+            manager
+                .import_bytes(&content, &wrong, ImportOptions::default())
+                .is_err()
+        );
+
+        let unlock = Unlock::Passphrase("test_pass".to_string());
+        let report = manager
+            .import_bytes(&content, &unlock, ImportOptions::default())
+            .unwrap();
+        assert_eq!(report.imported, ["test_key"]);
+        assert!(report.source.is_some());
+        assert!(import_dir.path().join("test_key").exists());
+    }
+
     #[test]
     fn test_import_wrong_passphrase() {
         let temp_dir = TempDir::new().unwrap();
@@ -357,4 +939,393 @@ mod tests {
         let result = manager.import(&backup_path, "wrong", ImportOptions::default());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_export_plain_tar() {
+        let temp_dir = TempDir::new().unwrap();
+        let keys = [
+            create_test_key(&temp_dir, "id_a"),
+            create_test_key(&temp_dir, "id_b"),
+        ];
+        let manager = BackupManager::new(temp_dir.path());
+        let path = temp_dir.path().join("keys.tar.gz");
+        let options = ExportOptions {
+            selected_keys: Some(vec!["id_b".to_string()]),
+            ..ExportOptions::default()
+        };
+
+        let report = manager.export_plain_tar(&keys, &path, options).unwrap();
+        assert_eq!(report.exported, vec!["id_b"]);
+
+        let gz = flate2::read::GzDecoder::new(fs::File::open(&path).unwrap());
+        let mut archive = tar::Archive::new(gz);
+        let entries: Vec<(String, u32)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().to_string();
+                (name, entry.header().mode().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (".ssh/id_b".to_string(), 0o600),
+                (".ssh/id_b.pub".to_string(), 0o644)
+            ]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_select_entries() {
+        let entry = |name: &str| BackupEntry {
+            name: name.to_string(),
+            key_type: "ed25519".to_string(),
+            comment: None,
+            private_key: None,
+            public_key: None,
+        };
+        let entries = || vec![entry("id_work"), entry("id_home"), entry("deploy")];
+        let names = |entries: Vec<BackupEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.name).collect()
+        };
+
+        assert_eq!(names(select_entries(entries(), None).unwrap()).len(), 3);
+        let chosen = ["deploy".to_string(), "id_work".to_string()];
+        assert_eq!(
+            names(select_entries(entries(), Some(&chosen)).unwrap()),
+            vec!["id_work", "deploy"]
+        );
+
+        let err = select_entries(entries(), Some(&["id_hom".to_string()])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Key not found: id_hom (did you mean id_home?) not in the backup"
+        );
+    }
+
+    #[test]
+    fn test_read_backup_in_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let private_key =
+            PrivateKey::random(&mut rand::rngs::OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let key_path = temp_dir.path().join("mem_key");
+        fs::write(
+            &key_path,
+            private_key.to_openssh(ssh_key::LineEnding::LF).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            key_path.with_extension("pub"),
+            private_key.public_key().to_openssh().unwrap(),
+        )
+        .unwrap();
+        let key = SshKey::from_path(&key_path).unwrap();
+
+        let manager = BackupManager::new(temp_dir.path());
+        let backup_path = temp_dir.path().join("backup.skm");
+        manager
+            .export(&[key], &backup_path, "pass", ExportOptions::default())
+            .unwrap();
+
+        let backup = BackupManager::read(&backup_path, "pass").unwrap();
+        let entry = &backup.keys[0];
+        assert!(!entry.is_encrypted());
+        let loaded = entry.load_private_key(None).unwrap();
+        assert_eq!(
+            loaded.public_key().key_data(),
+            private_key.public_key().key_data()
+        );
+
+        assert!(BackupManager::read(&backup_path, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_export_selected_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let keys = vec![
+            create_test_key(&temp_dir, "id_a"),
+            create_test_key(&temp_dir, "id_b"),
+        ];
+        let backup_path = temp_dir.path().join("backup.skm");
+
+        let options = ExportOptions {
+            selected_keys: Some(vec!["id_b".to_string(), "id_gone".to_string()]),
+            ..ExportOptions::default()
+        };
+        let lock = Lock::Passphrase("pass".to_string());
+        let mut seen = Vec::new();
+        let report = BackupManager::new(temp_dir.path())
+            .export_with_progress(&keys, &backup_path, &lock, options, |i, total, key| {
+                seen.push((i, total, key.name.clone()))
+            })
+            .unwrap();
+
+        assert_eq!(report.exported, vec!["id_b"]);
+        assert_eq!(report.missing, vec!["id_gone"]);
+        assert_eq!(seen, vec![(0, 1, "id_b".to_string())]);
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let keys = [
+            create_test_key(&temp_dir, "id_ed25519"),
+            create_test_key(&temp_dir, "id_ed25519_work"),
+            create_test_key(&temp_dir, "deploy"),
+        ];
+
+        let names = || keys.iter().map(|key| key.name.as_str());
+
+        assert_eq!(
+            similar_names("id_ed2551", names()),
+            vec!["id_ed25519", "id_ed25519_work"]
+        );
+        assert_eq!(similar_names("Deplyo", names()), vec!["deploy"]);
+        assert!(similar_names("github", names()).is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_entry_summary() {
+        let key = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let openssh = key.public_key().to_openssh().unwrap();
+        let mut entry = BackupEntry {
+            name: "id_ed25519".to_string(),
+            key_type: "ED25519".to_string(),
+            comment: None,
+            private_key: Some(
+                key.to_openssh(ssh_key::LineEnding::LF)
+                    .unwrap()
+                    .as_bytes()
+                    .to_vec(),
+            ),
+            public_key: None,
+        };
+
+        // Without a .pub the fingerprint comes from the private key
+        let expected = Some(fingerprint(key.public_key()));
+        assert_eq!(entry.summary().fingerprint, expected);
+        assert!(entry.summary().has_private_key);
+
+        entry.private_key = None;
+        entry.public_key = Some(format!("{}\n", openssh).into_bytes());
+        let summary = entry.summary();
+        assert_eq!(summary.fingerprint, expected);
+        assert!(!summary.has_private_key);
+    }
+
+    #[test]
+    fn test_backup_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = create_test_key(&temp_dir, "test_key");
+        let backup = BackupData {
+            metadata: BackupMetadata {
+                version: 1,
+                created_at: Local::now(),
+                hostname: "host".to_string(),
+                username: "user".to_string(),
+                key_count: 0,
+                description: None,
+            },
+            keys: Vec::new(),
+        };
+
+        // Version 1: bare age over JSON
+        let v1_path = temp_dir.path().join("v1.skm");
+        let json = serde_json::to_vec(&backup).unwrap();
+        fs::write(
+            &v1_path,
+            EncryptionManager::encrypt_with_passphrase(&json, "pass").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            BackupManager::read(&v1_path, "pass")
+                .unwrap()
+                .metadata
+                .version,
+            1
+        );
+
+        // Current version carries the header
+        let v2_path = temp_dir.path().join("v2.skm");
+        BackupManager::new(temp_dir.path())
+            .export(&[key], &v2_path, "pass", ExportOptions::default())
+            .unwrap();
+        assert!(fs::read(&v2_path).unwrap().starts_with(BACKUP_MAGIC));
+        assert_eq!(BackupManager::read(&v2_path, "pass").unwrap().keys.len(), 1);
+
+        // Armored text, as pasted somewhere with extra whitespace around it
+        let armor_path = temp_dir.path().join("armor.skm");
+        let key = create_test_key(&temp_dir, "test_key");
+        let options = ExportOptions {
+            armor: true,
+            ..ExportOptions::default()
+        };
+        BackupManager::new(temp_dir.path())
+            .export(&[key], &armor_path, "pass", options)
+            .unwrap();
+        let text = fs::read_to_string(&armor_path).unwrap();
+        assert!(text.starts_with("SKMBACKUP 2\n-----BEGIN AGE ENCRYPTED FILE-----"));
+        fs::write(
+            &armor_path,
+            format!("\n  {}\n\n", text.replace('\n', "\r\n")),
+        )
+        .unwrap();
+        assert_eq!(
+            BackupManager::read(&armor_path, "pass").unwrap().keys.len(),
+            1
+        );
+
+        // A newer version is rejected before asking age anything
+        let v9_path = temp_dir.path().join("v9.skm");
+        fs::write(&v9_path, [BACKUP_MAGIC, &[9], b"payload"].concat()).unwrap();
+        let err = BackupManager::read(&v9_path, "pass").unwrap_err();
+        assert!(err.to_string().contains("upgrade skm"));
+        fs::write(&v9_path, "SKMBACKUP 9\n-----BEGIN AGE ENCRYPTED FILE-----").unwrap();
+        let err = BackupManager::read(&v9_path, "pass").unwrap_err();
+        assert!(err.to_string().contains("upgrade skm"));
+    }
+
+    #[test]
+    fn test_shared_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = create_test_key(&temp_dir, "test_key");
+        let deputy = age::x25519::Identity::generate();
+        let lock = Lock::any_of(
+            vec!["lead".to_string(), "deputy".to_string()],
+            vec![Box::new(deputy.to_public())],
+        )
+        .unwrap();
+
+        let path = temp_dir.path().join("shared.skm");
+        let options = ExportOptions {
+            armor: true,
+            ..ExportOptions::default()
+        };
+        BackupManager::new(temp_dir.path())
+            .export_with_progress(&[key], &path, &lock, options, |_, _, _| {})
+            .unwrap();
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("SKMBACKUP 3\n")
+        );
+
+        // Either passphrase opens it, and so does the recipient's identity
+        for passphrase in ["lead", "deputy"] {
+            let backup = BackupManager::read(&path, passphrase).unwrap();
+            assert_eq!(backup.metadata.version, 3);
+            assert_eq!(backup.keys.len(), 1);
+        }
+        let unlock = Unlock::Identities(vec![Box::new(deputy)]);
+        assert_eq!(
+            BackupManager::read_unlocked(&path, &unlock)
+                .unwrap()
+                .keys
+                .len(),
+            1
+        );
+        assert!(matches!(
+            BackupManager::read(&path, "wrong"),
+            Err(SkmError::InvalidPassphrase)
+        ));
+
+        let probed = BackupFile::probe(&path).unwrap();
+        assert_eq!(probed.version, 3);
+        assert_eq!(probed.protection, Protection::Passphrases(2));
+    }
+
+    #[test]
+    fn test_rekey() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = create_test_key(&temp_dir, "test_key");
+        let path = temp_dir.path().join("backup.skm");
+        let options = ExportOptions {
+            description: Some("laptop".to_string()),
+            armor: true,
+            ..ExportOptions::default()
+        };
+        BackupManager::new(temp_dir.path())
+            .export(&[key], &path, "leaked", options)
+            .unwrap();
+
+        let admin = age::x25519::Identity::generate();
+        let lock = Lock::Recipients(vec![Box::new(admin.to_public())]);
+        let unlock = Unlock::Passphrase("wrong".to_string());
+        assert!(BackupManager::rekey(&path, &unlock, &lock).is_err());
+
+        let unlock = Unlock::Passphrase("leaked".to_string());
+        let metadata = BackupManager::rekey(&path, &unlock, &lock).unwrap();
+        assert_eq!(metadata.description.as_deref(), Some("laptop"));
+
+        // Still armored, now only for the new recipient
+        let probed = BackupFile::probe(&path).unwrap();
+        assert!(probed.armored);
+        assert_eq!(probed.protection, Protection::Recipients);
+        assert!(BackupManager::read(&path, "leaked").is_err());
+        let unlock = Unlock::Identities(vec![Box::new(admin)]);
+        let backup = BackupManager::read_unlocked(&path, &unlock).unwrap();
+        assert_eq!(backup.keys.len(), 1);
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_find_and_probe_backups() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        fs::create_dir(&backup_dir).unwrap();
+        let key = create_test_key(&temp_dir, "test_key");
+        let manager = BackupManager::new(temp_dir.path());
+
+        let old_path = backup_dir.join("old.skm");
+        manager
+            .export(
+                std::slice::from_ref(&key),
+                &old_path,
+                "pass",
+                ExportOptions::default(),
+            )
+            .unwrap();
+        let new_path = backup_dir.join("new.skm");
+        let options = ExportOptions {
+            armor: true,
+            ..ExportOptions::default()
+        };
+        manager.export(&[key], &new_path, "pass", options).unwrap();
+        fs::write(backup_dir.join("notes.txt"), "").unwrap();
+
+        // Order comes from the modification time, not the name
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&old_path)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+        assert_eq!(
+            BackupManager::find_backups(&backup_dir).unwrap(),
+            vec![new_path.clone(), old_path.clone()]
+        );
+        assert!(
+            BackupManager::find_backups(&temp_dir.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+
+        let probed = BackupFile::probe(&new_path).unwrap();
+        assert_eq!(probed.version, SINGLE_LOCK_VERSION);
+        assert!(probed.armored);
+        assert_eq!(probed.protection, Protection::Passphrase);
+        assert!(probed.metadata.is_none());
+        assert!(!BackupFile::probe(&old_path).unwrap().armored);
+    }
 }
@@ -1,15 +1,238 @@
-use age::secrecy::SecretString;
-use std::io::{Read, Write};
+use age::secrecy::{ExposeSecret, SecretString};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read, Write};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
 
 use crate::error::{Result, SkmError};
 
+/// Starts data encrypted to `Lock::Shared`. A count byte and the slots
+/// follow, each a big-endian `u16` length and a passphrase-encrypted age file
+/// holding the data key; the rest is age output encrypted to that key.
+const SLOTS_MAGIC: &[u8] = b"skm-slots\n";
+
+/// What data is encrypted to: a shared passphrase, or age recipients so each
+/// holder decrypts with their own identity. Recipients are X25519 keys or
+/// plugin recipients such as `age1yubikey1...`, which need the hardware to decrypt.
+pub enum Lock {
+    Passphrase(String),
+    Recipients(Vec<Box<dyn age::Recipient>>),
+    /// Any one of several passphrases, or any of the recipients. age can't
+    /// mix passphrases with anything, so the data is encrypted to a key of its
+    /// own plus the recipients, and each passphrase gets a slot holding that key.
+    Shared {
+        passphrases: Vec<String>,
+        recipients: Vec<Box<dyn age::Recipient>>,
+    },
+}
+
+/// What opens encrypted data: the passphrase, or identities matching a recipient
+pub enum Unlock {
+    Passphrase(String),
+    Identities(Vec<Box<dyn age::Identity>>),
+}
+
+impl Lock {
+    /// Recipients given directly (`age1...`) plus those listed in recipients
+    /// files, one per line with `#` comments
+    pub fn recipients(keys: &[String], files: &[PathBuf]) -> Result<Self> {
+        let recipients = parse_recipients(keys, files)?;
+        if recipients.is_empty() {
+            return Err(SkmError::Encryption("No age recipients given".to_string()));
+        }
+        Ok(Lock::Recipients(recipients))
+    }
+
+    /// Opened by any one of `passphrases` or `recipients`; plain age output
+    /// unless that takes more than one passphrase
+    pub fn any_of(
+        mut passphrases: Vec<String>,
+        recipients: Vec<Box<dyn age::Recipient>>,
+    ) -> Result<Self> {
+        match (passphrases.len(), recipients.is_empty()) {
+            (0, true) => Err(SkmError::Encryption(
+                "No passphrase or age recipient given".to_string(),
+            )),
+            (0, false) => Ok(Lock::Recipients(recipients)),
+            (1, true) => Ok(Lock::Passphrase(passphrases.remove(0))),
+            _ if passphrases.len() > usize::from(u8::MAX) => Err(SkmError::Encryption(format!(
+                "At most {} passphrases are supported",
+                u8::MAX
+            ))),
+            _ => Ok(Lock::Shared {
+                passphrases,
+                recipients,
+            }),
+        }
+    }
+
+    /// Whether the output needs the slots format of `Lock::Shared`
+    pub fn has_slots(&self) -> bool {
+        matches!(self, Lock::Shared { .. })
+    }
+}
+
+/// Recipients given directly (`age1...`) plus those listed in recipients
+/// files, which may be none at all
+pub fn parse_recipients(
+    keys: &[String],
+    files: &[PathBuf],
+) -> Result<Vec<Box<dyn age::Recipient>>> {
+    let mut lines: Vec<String> = keys.to_vec();
+    for file in files {
+        let content = std::fs::read_to_string(file).map_err(|e| {
+            SkmError::Encryption(format!("Could not read {}: {}", file.display(), e))
+        })?;
+        lines.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    let mut recipients: Vec<Box<dyn age::Recipient>> = Vec::new();
+    // One plugin process wraps the file key for all of its recipients
+    let mut by_plugin: BTreeMap<String, Vec<age::plugin::Recipient>> = BTreeMap::new();
+    for line in &lines {
+        if let Ok(recipient) = line.parse::<age::x25519::Recipient>() {
+            recipients.push(Box::new(recipient));
+        } else if let Ok(recipient) = line.parse::<age::plugin::Recipient>() {
+            by_plugin
+                .entry(recipient.plugin().to_string())
+                .or_default()
+                .push(recipient);
+        } else {
+            return Err(SkmError::Encryption(format!(
+                "Invalid age recipient: {}",
+                line
+            )));
+        }
+    }
+    for (plugin, plugin_recipients) in by_plugin {
+        let recipient =
+            age::plugin::RecipientPluginV1::new(&plugin, &plugin_recipients, &[], PluginPrompts)
+                .map_err(|e| SkmError::Encryption(e.to_string()))?;
+        recipients.push(Box::new(recipient));
+    }
+    Ok(recipients)
+}
+
+impl Unlock {
+    /// Identities from age identity files: `AGE-SECRET-KEY-1...` lines, or
+    /// plugin identities such as `AGE-PLUGIN-YUBIKEY-1...`
+    pub fn identities(files: &[PathBuf]) -> Result<Self> {
+        let mut identities = Vec::new();
+        for file in files {
+            let invalid = |e: String| {
+                SkmError::Encryption(format!("Invalid identity file {}: {}", file.display(), e))
+            };
+            let parsed = age::IdentityFile::from_file(file.to_string_lossy().to_string())
+                .map_err(|e| invalid(e.to_string()))?
+                .with_callbacks(PluginPrompts)
+                .into_identities()
+                .map_err(|e| invalid(e.to_string()))?;
+            identities.extend(parsed);
+        }
+        Ok(Unlock::Identities(identities))
+    }
+}
+
+/// Lets age plugins talk to the user on the terminal, e.g. to ask for a
+/// hardware token to be touched or for its PIN
+#[derive(Debug, Clone, Copy)]
+struct PluginPrompts;
+
+impl PluginPrompts {
+    fn read_line(prompt: &str) -> Option<String> {
+        eprint!("{}", prompt);
+        std::io::stderr().flush().ok()?;
+
+        let mut input = String::new();
+        std::io::stdin().lock().read_line(&mut input).ok()?;
+        Some(input.trim().to_string())
+    }
+}
+
+impl age::Callbacks for PluginPrompts {
+    fn display_message(&self, message: &str) {
+        eprintln!("{}", message);
+    }
+
+    fn confirm(&self, message: &str, yes_string: &str, no_string: Option<&str>) -> Option<bool> {
+        let no_string = no_string.unwrap_or("no");
+        let answer = Self::read_line(&format!("{} [{}/{}] ", message, yes_string, no_string))?;
+        Some(answer.eq_ignore_ascii_case(yes_string) || answer.eq_ignore_ascii_case("y"))
+    }
+
+    fn request_public_string(&self, description: &str) -> Option<String> {
+        Self::read_line(&format!("{} ", description)).filter(|input| !input.is_empty())
+    }
+
+    fn request_passphrase(&self, description: &str) -> Option<SecretString> {
+        Self::read_line(&format!("{} ", description))
+            .filter(|input| !input.is_empty())
+            .map(SecretString::from)
+    }
+}
+
 pub struct EncryptionManager;
 
 impl EncryptionManager {
+    /// Encrypt data to a passphrase or to recipients
+    pub fn encrypt(data: &[u8], lock: &Lock) -> Result<Vec<u8>> {
+        match lock {
+            Lock::Passphrase(passphrase) => Self::encrypt_with_passphrase(data, passphrase),
+            Lock::Recipients(recipients) => {
+                let encryptor =
+                    age::Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref()))
+                        .map_err(|e| SkmError::Encryption(e.to_string()))?;
+                Self::write_encrypted(encryptor, data)
+            }
+            Lock::Shared {
+                passphrases,
+                recipients,
+            } => Self::encrypt_with_slots(data, passphrases, recipients),
+        }
+    }
+
+    fn encrypt_with_slots(
+        data: &[u8],
+        passphrases: &[String],
+        recipients: &[Box<dyn age::Recipient>],
+    ) -> Result<Vec<u8>> {
+        let data_key = age::x25519::Identity::generate();
+        let mut encrypted = SLOTS_MAGIC.to_vec();
+        encrypted.push(passphrases.len() as u8);
+        for passphrase in passphrases {
+            let slot = Self::encrypt_with_passphrase(
+                data_key.to_string().expose_secret().as_bytes(),
+                passphrase,
+            )?;
+            let len = u16::try_from(slot.len())
+                .map_err(|_| SkmError::Encryption("Passphrase slot too large".to_string()))?;
+            encrypted.extend_from_slice(&len.to_be_bytes());
+            encrypted.extend_from_slice(&slot);
+        }
+
+        let data_recipient = data_key.to_public();
+        let encryptor = age::Encryptor::with_recipients(
+            std::iter::once(&data_recipient as &dyn age::Recipient)
+                .chain(recipients.iter().map(|r| r.as_ref())),
+        )
+        .map_err(|e| SkmError::Encryption(e.to_string()))?;
+        encrypted.extend(Self::write_encrypted(encryptor, data)?);
+        Ok(encrypted)
+    }
+
     /// Encrypt data with a passphrase using age
     pub fn encrypt_with_passphrase(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
         let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(passphrase));
+        Self::write_encrypted(encryptor, data)
+    }
 
+    fn write_encrypted(encryptor: age::Encryptor, data: &[u8]) -> Result<Vec<u8>> {
         let mut encrypted = vec![];
         let mut writer = encryptor
             .wrap_output(&mut encrypted)
@@ -25,10 +248,69 @@ impl EncryptionManager {
         Ok(encrypted)
     }
 
-    /// Decrypt data with a passphrase
+    /// Whether data was encrypted to a passphrase rather than to recipients;
+    /// only the age header is read
+    pub fn is_passphrase_protected(encrypted: &[u8]) -> Result<bool> {
+        if split_slots(encrypted)?.is_some() {
+            return Ok(true);
+        }
+        let decryptor =
+            age::Decryptor::new(encrypted).map_err(|e| SkmError::Encryption(e.to_string()))?;
+        Ok(decryptor.is_scrypt())
+    }
+
+    /// Decrypt data with a passphrase or identities, explaining a mismatch
+    /// between the two instead of failing with a bare "wrong passphrase"
+    pub fn decrypt(encrypted: &[u8], unlock: &Unlock) -> Result<Vec<u8>> {
+        let identities = match unlock {
+            Unlock::Passphrase(passphrase) => {
+                return Self::decrypt_with_passphrase(encrypted, passphrase);
+            }
+            Unlock::Identities(identities) => identities,
+        };
+        let encrypted = match split_slots(encrypted)? {
+            Some((_, payload)) => payload,
+            None => encrypted,
+        };
+
+        let decryptor =
+            age::Decryptor::new(encrypted).map_err(|e| SkmError::Encryption(e.to_string()))?;
+        if decryptor.is_scrypt() {
+            return Err(SkmError::Encryption(
+                "Encrypted with a passphrase, not to age recipients; use --passphrase".to_string(),
+            ));
+        }
+        Self::read_decrypted(decryptor, identities.iter().map(|i| i.as_ref()))
+    }
+
+    fn read_decrypted<'a>(
+        decryptor: age::Decryptor<&[u8]>,
+        identities: impl Iterator<Item = &'a dyn age::Identity>,
+    ) -> Result<Vec<u8>> {
+        let mut reader = decryptor
+            .decrypt(identities)
+            .map_err(|e| SkmError::Encryption(format!("Could not decrypt: {}", e)))?;
+        let mut decrypted = vec![];
+        reader
+            .read_to_end(&mut decrypted)
+            .map_err(|e| SkmError::Encryption(e.to_string()))?;
+
+        Ok(decrypted)
+    }
+
+    /// Decrypt data with a passphrase, or with any one of the passphrases of
+    /// `Lock::Shared` data
     pub fn decrypt_with_passphrase(encrypted: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+        if let Some((slots, payload)) = split_slots(encrypted)? {
+            return Self::decrypt_with_slots(&slots, payload, passphrase);
+        }
         let decryptor =
             age::Decryptor::new(encrypted).map_err(|e| SkmError::Encryption(e.to_string()))?;
+        if !decryptor.is_scrypt() {
+            return Err(SkmError::Encryption(
+                "Encrypted to age recipients; decrypt with --identity".to_string(),
+            ));
+        }
 
         let mut decrypted = vec![];
 
@@ -47,9 +329,33 @@ impl EncryptionManager {
         Ok(decrypted)
     }
 
+    /// Recover the data key from whichever slot the passphrase opens; each
+    /// try costs a full scrypt run
+    fn decrypt_with_slots(slots: &[&[u8]], payload: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+        for slot in slots {
+            let data_key = match Self::decrypt_with_passphrase(slot, passphrase) {
+                Ok(data_key) => Zeroizing::new(data_key),
+                Err(SkmError::InvalidPassphrase) => continue,
+                Err(e) => return Err(e),
+            };
+            let identity = std::str::from_utf8(&data_key)
+                .ok()
+                .and_then(|key| key.parse::<age::x25519::Identity>().ok())
+                .ok_or_else(|| SkmError::Encryption("Corrupt passphrase slot".to_string()))?;
+
+            let decryptor =
+                age::Decryptor::new(payload).map_err(|e| SkmError::Encryption(e.to_string()))?;
+            return Self::read_decrypted(
+                decryptor,
+                std::iter::once(&identity as &dyn age::Identity),
+            );
+        }
+        Err(SkmError::InvalidPassphrase)
+    }
+
     /// Encrypt and encode to armor format (ASCII)
-    pub fn encrypt_to_armor(data: &[u8], passphrase: &str) -> Result<String> {
-        let encrypted = Self::encrypt_with_passphrase(data, passphrase)?;
+    pub fn encrypt_to_armor(data: &[u8], lock: &Lock) -> Result<String> {
+        let encrypted = Self::encrypt(data, lock)?;
         let armor =
             age::armor::ArmoredWriter::wrap_output(Vec::new(), age::armor::Format::AsciiArmor)
                 .map_err(|e| SkmError::Encryption(e.to_string()))?;
@@ -64,6 +370,48 @@ impl EncryptionManager {
 
         String::from_utf8(result).map_err(|e| SkmError::Encryption(format!("Invalid UTF-8: {}", e)))
     }
+
+    /// Strip the ASCII armor added by `encrypt_to_armor`, giving binary age output
+    pub fn dearmor(armored: &[u8]) -> Result<Vec<u8>> {
+        let mut encrypted = vec![];
+        age::armor::ArmoredReader::new(armored)
+            .read_to_end(&mut encrypted)
+            .map_err(|e| SkmError::Encryption(format!("Invalid armor: {}", e)))?;
+
+        Ok(encrypted)
+    }
+}
+
+/// Passphrase slots and age payload of `Lock::Shared` data
+type Slots<'a> = (Vec<&'a [u8]>, &'a [u8]);
+
+/// Split `Lock::Shared` data into its slots and payload; `None` for plain
+/// age output
+fn split_slots(encrypted: &[u8]) -> Result<Option<Slots<'_>>> {
+    let Some(rest) = encrypted.strip_prefix(SLOTS_MAGIC) else {
+        return Ok(None);
+    };
+    let truncated = || SkmError::Encryption("Truncated passphrase slots".to_string());
+
+    let (&count, mut rest) = rest.split_first().ok_or_else(truncated)?;
+    let mut slots = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (len, after) = rest.split_first_chunk::<2>().ok_or_else(truncated)?;
+        let len = usize::from(u16::from_be_bytes(*len));
+        if after.len() < len {
+            return Err(truncated());
+        }
+        let (slot, after) = after.split_at(len);
+        slots.push(slot);
+        rest = after;
+    }
+    Ok(Some((slots, rest)))
+}
+
+/// Number of passphrases that open `Lock::Shared` data; `None` for plain age
+/// output
+pub fn passphrase_slots(encrypted: &[u8]) -> Result<Option<usize>> {
+    Ok(split_slots(encrypted)?.map(|(slots, _)| slots.len()))
 }
 
 #[cfg(test)]
@@ -90,4 +438,75 @@ mod tests {
         let result = EncryptionManager::decrypt_with_passphrase(&encrypted, "wrong");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_armor_roundtrip() {
+        let lock = Lock::Passphrase("pass".to_string());
+        let armored = EncryptionManager::encrypt_to_armor(b"test data", &lock).unwrap();
+        assert!(armored.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+
+        let encrypted = EncryptionManager::dearmor(armored.as_bytes()).unwrap();
+        let decrypted = EncryptionManager::decrypt_with_passphrase(&encrypted, "pass").unwrap();
+        assert_eq!(decrypted, b"test data".to_vec());
+    }
+
+    #[test]
+    fn test_recipients_roundtrip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let alice = age::x25519::Identity::generate();
+        let bob = age::x25519::Identity::generate();
+
+        let recipients_file = temp_dir.path().join("recipients.txt");
+        std::fs::write(&recipients_file, format!("# bob\n\n{}\n", bob.to_public())).unwrap();
+        let lock = Lock::recipients(&[alice.to_public().to_string()], &[recipients_file]).unwrap();
+        let encrypted = EncryptionManager::encrypt(b"test data", &lock).unwrap();
+
+        // Either admin can decrypt with their own identity file
+        let identity_file = temp_dir.path().join("bob.key");
+        std::fs::write(&identity_file, bob.to_string().expose_secret()).unwrap();
+        let unlock = Unlock::identities(&[identity_file]).unwrap();
+        assert_eq!(
+            EncryptionManager::decrypt(&encrypted, &unlock).unwrap(),
+            b"test data".to_vec()
+        );
+
+        let err = EncryptionManager::decrypt_with_passphrase(&encrypted, "pass").unwrap_err();
+        assert!(err.to_string().contains("--identity"));
+        assert!(Lock::recipients(&["age1nope".to_string()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_shared_lock() {
+        let lock =
+            Lock::any_of(vec!["lead".to_string(), "deputy".to_string()], Vec::new()).unwrap();
+        let encrypted = EncryptionManager::encrypt(b"test data", &lock).unwrap();
+        assert_eq!(passphrase_slots(&encrypted).unwrap(), Some(2));
+        assert!(EncryptionManager::is_passphrase_protected(&encrypted).unwrap());
+
+        for passphrase in ["lead", "deputy"] {
+            let decrypted = EncryptionManager::decrypt_with_passphrase(&encrypted, passphrase);
+            assert_eq!(decrypted.unwrap(), b"test data".to_vec());
+        }
+        assert!(matches!(
+            EncryptionManager::decrypt_with_passphrase(&encrypted, "wrong"),
+            Err(SkmError::InvalidPassphrase)
+        ));
+        assert!(split_slots(&encrypted[..SLOTS_MAGIC.len() + 3]).is_err());
+
+        // One passphrase, or recipients alone, stay plain age
+        let lock = Lock::any_of(vec!["pass".to_string()], Vec::new()).unwrap();
+        assert!(!lock.has_slots());
+        assert!(Lock::any_of(Vec::new(), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_plugin_identity_needs_plugin() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let identity_file = temp_dir.path().join("token.txt");
+        let identity = age::plugin::Identity::default_for_plugin("skmtest");
+        std::fs::write(&identity_file, format!("{}\n", identity)).unwrap();
+
+        let err = Unlock::identities(&[identity_file]).err().unwrap();
+        assert!(err.to_string().contains("age-plugin-skmtest"));
+    }
 }
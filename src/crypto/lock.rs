@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use crate::crypto::EncryptionManager;
+use crate::error::{Result, SkmError};
+
+/// File holding the TUI unlock passphrase, relative to the skm data directory
+pub const LOCK_FILE: &str = "lock.age";
+
+/// Known plaintext; decrypting it proves the passphrase without storing it
+const MARKER: &[u8] = b"skm-lock-v1";
+
+/// Passphrase required to resume a locked TUI session. Only an age-encrypted
+/// marker is stored, so the passphrase goes through age's scrypt KDF.
+pub struct LockPassphrase {
+    path: PathBuf,
+}
+
+impl LockPassphrase {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self {
+            path: data_dir.as_ref().join(LOCK_FILE),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn set(&self, passphrase: &str) -> Result<()> {
+        if passphrase.is_empty() {
+            return Err(SkmError::Config(
+                "Lock passphrase cannot be empty".to_string(),
+            ));
+        }
+
+        let encrypted = EncryptionManager::encrypt_with_passphrase(MARKER, passphrase)?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, encrypted)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether a passphrase was set
+    pub fn remove(&self) -> Result<bool> {
+        if !self.is_set() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&self.path)?;
+        Ok(true)
+    }
+
+    pub fn verify(&self, passphrase: &str) -> Result<bool> {
+        let encrypted = std::fs::read(&self.path)?;
+        match EncryptionManager::decrypt_with_passphrase(&encrypted, passphrase) {
+            Ok(plaintext) => Ok(plaintext == MARKER),
+            Err(SkmError::InvalidPassphrase) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_verify_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock = LockPassphrase::new(temp_dir.path().join("data"));
+        assert!(!lock.is_set());
+        assert!(lock.set("").is_err());
+
+        lock.set("hunter2").unwrap();
+        assert!(lock.is_set());
+        assert!(lock.verify("hunter2").unwrap());
+        assert!(!lock.verify("wrong").unwrap());
+
+        assert!(lock.remove().unwrap());
+        assert!(!lock.remove().unwrap());
+    }
+}
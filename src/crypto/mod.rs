@@ -1,5 +1,15 @@
 pub mod backup;
 pub mod encrypt;
+pub mod lock;
+pub mod provenance;
+pub mod remote;
+pub mod s3;
+pub mod schedule;
+pub mod sftp;
+pub mod sources;
 
 pub use backup::{BackupManager, ExportOptions, ImportOptions};
-pub use encrypt::EncryptionManager;
+pub use encrypt::{EncryptionManager, Lock, Unlock};
+pub use lock::LockPassphrase;
+pub use remote::RemoteBackup;
+pub use sources::{ImportSource, SourceFormat};
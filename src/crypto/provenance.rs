@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::backup::ImportReport;
+use crate::error::Result;
+use crate::migrate::RecordStore;
+use crate::ssh::SshKey;
+
+/// Where the origins of imported keys are recorded, relative to the skm data directory
+pub const PROVENANCE_FILE: &str = "provenance.json";
+
+/// Where an imported key came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Identifies the key across renames
+    pub fingerprint: String,
+    /// Name of the key when it was imported
+    pub key_name: String,
+    /// File the key was imported from
+    pub source: PathBuf,
+    /// Machine, user and date of the backup; unknown for other formats
+    pub hostname: Option<String>,
+    pub username: Option<String>,
+    pub backup_created_at: Option<DateTime<Local>>,
+    pub imported_at: DateTime<Local>,
+}
+
+impl Provenance {
+    /// e.g. `imported from laptop-a backup of 2024-02-01`
+    pub fn describe(&self) -> String {
+        match (&self.hostname, self.backup_created_at) {
+            (Some(hostname), Some(created_at)) => format!(
+                "imported from {} backup of {}",
+                hostname,
+                created_at.format("%Y-%m-%d")
+            ),
+            _ => format!(
+                "imported from {} on {}",
+                self.source
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                self.imported_at.format("%Y-%m-%d")
+            ),
+        }
+    }
+}
+
+/// Record of imports, so restored keys can be traced back to their backup
+pub type ProvenanceStore = RecordStore<Provenance>;
+
+impl ProvenanceStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(PROVENANCE_FILE))
+    }
+
+    /// Stamp the keys an import wrote with the file and backup they came
+    /// from; a key imported again keeps only its latest origin
+    pub fn record_import(&self, ssh_dir: &Path, file: &Path, report: &ImportReport) -> Result<()> {
+        let source = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let metadata = report.source.as_ref();
+        let imported_at = Local::now();
+
+        let stamped: Vec<Provenance> = report
+            .imported
+            .iter()
+            .chain(&report.overwritten)
+            .filter_map(|name| SshKey::from_path(ssh_dir.join(name)).ok())
+            .filter_map(|key| {
+                Some(Provenance {
+                    fingerprint: key.fingerprint?,
+                    key_name: key.name,
+                    source: source.clone(),
+                    hostname: metadata.map(|m| m.hostname.clone()),
+                    username: metadata.map(|m| m.username.clone()),
+                    backup_created_at: metadata.map(|m| m.created_at),
+                    imported_at,
+                })
+            })
+            .collect();
+        if stamped.is_empty() {
+            return Ok(());
+        }
+
+        self.update(|records| {
+            records.retain(|r| !stamped.iter().any(|s| s.fingerprint == r.fingerprint));
+            records.extend(stamped);
+        })
+    }
+
+    /// Where the key with this fingerprint was imported from, if it was
+    pub fn for_key(&self, fingerprint: &str) -> Result<Option<Provenance>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .find(|r| r.fingerprint == fingerprint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::backup::BackupMetadata;
+    use rand::rngs::OsRng;
+    use ssh_key::{Algorithm, LineEnding, PrivateKey};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_import() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        key.write_openssh_file(&ssh_dir.path().join("id_laptop"), LineEnding::LF)
+            .unwrap();
+        fs::write(
+            ssh_dir.path().join("id_laptop.pub"),
+            key.public_key().to_openssh().unwrap(),
+        )
+        .unwrap();
+
+        let created_at = "2024-02-01T10:00:00+00:00"
+            .parse::<DateTime<chrono::FixedOffset>>()
+            .unwrap()
+            .with_timezone(&Local);
+        let report = ImportReport {
+            imported: vec!["id_laptop".to_string()],
+            skipped: Vec::new(),
+            overwritten: Vec::new(),
+            errors: Vec::new(),
+            source: Some(BackupMetadata {
+                version: 2,
+                created_at,
+                hostname: "laptop-a".to_string(),
+                username: "me".to_string(),
+                key_count: 1,
+                description: None,
+            }),
+        };
+
+        let store = ProvenanceStore::new(data_dir.path());
+        let backup = data_dir.path().join("laptop.skm");
+        store
+            .record_import(ssh_dir.path(), &backup, &report)
+            .unwrap();
+        // Importing again replaces the earlier record
+        store
+            .record_import(ssh_dir.path(), &backup, &report)
+            .unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        let fingerprint = crate::ssh::keys::fingerprint(key.public_key());
+        let provenance = store.for_key(&fingerprint).unwrap().unwrap();
+        assert_eq!(
+            provenance.describe(),
+            format!(
+                "imported from laptop-a backup of {}",
+                created_at.format("%Y-%m-%d")
+            )
+        );
+        assert_eq!(provenance.key_name, "id_laptop");
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::crypto::s3::{self, S3Location};
+use crate::crypto::sftp::SftpLocation;
+use crate::error::{Result, SkmError};
+
+/// Where `export --to` uploads a backup and `import --from` fetches it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteBackup {
+    S3(S3Location),
+    Sftp(SftpLocation),
+}
+
+impl RemoteBackup {
+    pub fn is_url(value: &str) -> bool {
+        S3Location::is_url(value) || SftpLocation::is_url(value)
+    }
+
+    pub fn parse(url: &str) -> Result<Self> {
+        if S3Location::is_url(url) {
+            S3Location::parse(url).map(RemoteBackup::S3)
+        } else if SftpLocation::is_url(url) {
+            SftpLocation::parse(url).map(RemoteBackup::Sftp)
+        } else {
+            Err(SkmError::Config(format!(
+                "Unsupported backup URL '{}' (expected s3:// or sftp://)",
+                url
+            )))
+        }
+    }
+
+    /// Fail early, before anything is written, when the remote can't be used
+    pub fn check_configured(&self) -> Result<()> {
+        match self {
+            RemoteBackup::S3(_) => s3::check_configured(),
+            RemoteBackup::Sftp(_) => Ok(()),
+        }
+    }
+
+    /// Name for the local copy
+    pub fn file_name(&self) -> &str {
+        match self {
+            RemoteBackup::S3(location) => location.file_name(),
+            RemoteBackup::Sftp(location) => location.file_name(),
+        }
+    }
+
+    /// Copy the local `file` to the remote
+    pub fn upload(&self, file: &Path) -> Result<()> {
+        match self {
+            RemoteBackup::S3(location) => s3::upload(location, &std::fs::read(file)?),
+            RemoteBackup::Sftp(location) => location.upload(file),
+        }
+    }
+
+    /// Read the remote backup into memory, leaving no local copy
+    pub fn fetch(&self) -> Result<Vec<u8>> {
+        match self {
+            RemoteBackup::S3(location) => s3::download(location),
+            RemoteBackup::Sftp(location) => location.fetch(),
+        }
+    }
+
+    /// Copy the remote backup to the local `file`. An existing file is
+    /// refused, so a download never replaces a local backup.
+    pub fn download(&self, file: &Path) -> Result<()> {
+        if file.exists() {
+            return Err(SkmError::ImportExport(format!(
+                "{} already exists; download {} somewhere else",
+                file.display(),
+                self
+            )));
+        }
+        match self {
+            RemoteBackup::S3(location) => std::fs::write(file, s3::download(location)?)?,
+            RemoteBackup::Sftp(location) => location.download(file)?,
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RemoteBackup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteBackup::S3(location) => location.fmt(f),
+            RemoteBackup::Sftp(location) => location.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote() {
+        let remote = RemoteBackup::parse("s3://my-backups/laptop/ssh.skm").unwrap();
+        assert!(matches!(remote, RemoteBackup::S3(_)));
+        assert_eq!(remote.file_name(), "ssh.skm");
+        assert_eq!(remote.to_string(), "s3://my-backups/laptop/ssh.skm");
+
+        let remote = RemoteBackup::parse("sftp://me@nas:2222/volume1/ssh.skm").unwrap();
+        assert!(matches!(remote, RemoteBackup::Sftp(_)));
+        assert_eq!(remote.file_name(), "ssh.skm");
+        assert_eq!(remote.to_string(), "sftp://me@nas:2222/volume1/ssh.skm");
+        assert!(remote.check_configured().is_ok());
+
+        assert!(RemoteBackup::is_url("sftp://nas/ssh.skm"));
+        assert!(!RemoteBackup::is_url("/backups/ssh.skm"));
+        assert!(RemoteBackup::parse("ftp://nas/ssh.skm").is_err());
+        assert!(RemoteBackup::parse("sftp://-oProxyCommand=sh/ssh.skm").is_err());
+    }
+
+    #[test]
+    fn test_download_keeps_existing_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let local = temp_dir.path().join("ssh.skm");
+        std::fs::write(&local, "local backup").unwrap();
+
+        let remote = RemoteBackup::parse("sftp://nas/ssh.skm").unwrap();
+        assert!(remote.download(&local).is_err());
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), "local backup");
+    }
+}
@@ -0,0 +1,646 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AutoBackupSettings, Config};
+use crate::crypto::backup::{BackupManager, ExportOptions};
+use crate::crypto::encrypt::Lock;
+use crate::error::{Result, SkmError};
+use crate::migrate;
+use crate::ssh::KeyScanner;
+use crate::ssh::config::{contract_path, expand_path};
+
+/// Where the run state of automatic backups is kept, relative to the skm
+/// data directory; the schedule itself is `[backup.auto]` in config.toml
+pub const STATE_FILE: &str = "auto-backup.json";
+
+/// Automatic backups kept when `[backup.auto]` doesn't say
+pub const DEFAULT_KEEP: usize = 10;
+
+/// Held while a run backs up, so `skm` and `backup auto --daemon` don't run at once
+const LOCK_FILE: &str = "auto-backup.lock";
+
+/// A lock older than this was left by a run that died, and is taken over
+const STALE_LOCK_SECS: u64 = 60 * 60;
+
+/// A failed run is tried again after this long, doubling with each failure
+/// in a row, up to the schedule's interval
+const RETRY_AFTER_SECS: u64 = 60 * 60;
+
+/// Automatic backups are named `auto-<timestamp>.skm`; only these are pruned
+const FILE_PREFIX: &str = "auto-";
+const FILE_SUFFIX: &str = ".skm";
+
+/// How often to back up and how many backups to keep, from `[backup.auto]`,
+/// with how the runs so far went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupSchedule {
+    pub every_secs: u64,
+    pub keep: usize,
+    /// Backups are encrypted to these age recipients, so no passphrase has
+    /// to be stored; files are read again on every run
+    pub recipients: Vec<String>,
+    pub recipients_files: Vec<PathBuf>,
+    pub ssh_dir: PathBuf,
+    pub state: RunState,
+}
+
+/// What `auto-backup.json` records between runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunState {
+    pub last_run: Option<DateTime<Local>>,
+    /// Failed runs since the last one that worked, and when the latest was
+    pub failures: u32,
+    pub last_failure: Option<DateTime<Local>>,
+}
+
+impl BackupSchedule {
+    /// Check `[backup.auto]`; `ssh_dir` is backed up unless it names another
+    pub fn from_settings(settings: &AutoBackupSettings, ssh_dir: &Path) -> Result<Self> {
+        let keep = settings.keep.unwrap_or(DEFAULT_KEEP);
+        if keep == 0 {
+            return Err(SkmError::Config(
+                "[backup.auto] keep must be at least 1".to_string(),
+            ));
+        }
+        if settings.recipients.is_empty() && settings.recipients_files.is_empty() {
+            return Err(SkmError::Config(
+                "[backup.auto] needs recipients or recipients_files to encrypt to".to_string(),
+            ));
+        }
+        Ok(Self {
+            every_secs: parse_interval(&settings.every)?,
+            keep,
+            recipients: settings.recipients.clone(),
+            recipients_files: settings
+                .recipients_files
+                .iter()
+                .map(|file| expand_path(file))
+                .collect(),
+            ssh_dir: settings
+                .ssh_dir
+                .as_deref()
+                .map(expand_path)
+                .unwrap_or_else(|| ssh_dir.to_path_buf()),
+            state: RunState::default(),
+        })
+    }
+
+    /// When the next backup is due; right away if none was made yet. After
+    /// a failure it waits for the retry delay, so every later `skm` command
+    /// doesn't fail the same way again.
+    pub fn next_due(&self) -> Option<DateTime<Local>> {
+        let every = Duration::seconds(i64::try_from(self.every_secs).unwrap_or(i64::MAX));
+        let due = self
+            .state
+            .last_run
+            .and_then(|last| last.checked_add_signed(every));
+        let retry = self
+            .state
+            .last_failure
+            .filter(|_| self.state.failures > 0)
+            .and_then(|failed| failed.checked_add_signed(self.retry_delay()));
+        due.max(retry)
+    }
+
+    fn retry_delay(&self) -> Duration {
+        let failures = self.state.failures;
+        let doubled = RETRY_AFTER_SECS.saturating_mul(1 << failures.saturating_sub(1).min(16));
+        let secs = doubled.min(self.every_secs.max(RETRY_AFTER_SECS));
+        Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX))
+    }
+
+    pub fn is_due(&self, now: DateTime<Local>) -> bool {
+        self.next_due().is_none_or(|due| due <= now)
+    }
+}
+
+/// Before the schedule moved to config.toml, `auto-backup.json` held it too
+#[derive(Deserialize)]
+struct LegacySchedule {
+    every_secs: u64,
+    keep: usize,
+    recipients: Vec<String>,
+    recipients_files: Vec<PathBuf>,
+    ssh_dir: PathBuf,
+}
+
+/// What a run of the schedule did
+#[derive(Debug)]
+pub struct AutoBackupReport {
+    pub path: PathBuf,
+    pub key_count: usize,
+    pub pruned: Vec<PathBuf>,
+}
+
+impl AutoBackupReport {
+    /// e.g. `backed up 3 key(s) to ~/.skm/auto-20240101-000000.skm, removed 1 old backup(s)`
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "backed up {} key(s) to {}",
+            self.key_count,
+            self.path.display()
+        );
+        if !self.pruned.is_empty() {
+            summary.push_str(&format!(", removed {} old backup(s)", self.pruned.len()));
+        }
+        summary
+    }
+}
+
+/// Scheduled backups written to, and pruned from, the skm data directory.
+/// The schedule is read from config.toml each time, so edits to it apply to
+/// a running `--daemon` too.
+pub struct AutoBackup {
+    config: Config,
+}
+
+impl AutoBackup {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    fn dir(&self) -> &Path {
+        &self.config.export_dir
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir().join(STATE_FILE)
+    }
+
+    /// The schedule in `[backup.auto]`, if automatic backups are on
+    pub fn load(&self) -> Result<Option<BackupSchedule>> {
+        let settings = match self.config.load_settings()?.backup.auto {
+            Some(settings) => settings,
+            None => match self.carry_over_legacy()? {
+                Some(settings) => settings,
+                None => return Ok(None),
+            },
+        };
+        let mut schedule = BackupSchedule::from_settings(&settings, &self.config.ssh_dir)?;
+        schedule.state = self.load_state()?;
+        Ok(Some(schedule))
+    }
+
+    fn load_state(&self) -> Result<RunState> {
+        let path = self.state_path();
+        if !path.exists() {
+            return Ok(RunState::default());
+        }
+        migrate::load_json(&path)
+    }
+
+    fn save_state(&self, state: &RunState) -> Result<()> {
+        fs::create_dir_all(self.dir())?;
+        migrate::save_json(&self.state_path(), state)
+    }
+
+    /// Move a schedule still kept in `auto-backup.json` into config.toml,
+    /// leaving only the run state behind
+    fn carry_over_legacy(&self) -> Result<Option<AutoBackupSettings>> {
+        let path = self.state_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let value: serde_json::Value = migrate::load_json(&path)?;
+        let Ok(legacy) = serde_json::from_value::<LegacySchedule>(value) else {
+            return Ok(None);
+        };
+        let settings = AutoBackupSettings {
+            every: format_interval(legacy.every_secs),
+            keep: Some(legacy.keep),
+            recipients: legacy.recipients,
+            recipients_files: legacy
+                .recipients_files
+                .iter()
+                .map(|file| contract_path(file))
+                .collect(),
+            ssh_dir: Some(contract_path(&legacy.ssh_dir)),
+        };
+        self.config.set_auto_backup(Some(&settings))?;
+        let state = self.load_state()?;
+        self.save_state(&state)?;
+        tracing::info!(
+            "Moved the automatic backup schedule to [backup.auto] in {}",
+            self.config.settings_path().display()
+        );
+        Ok(Some(settings))
+    }
+
+    /// Write `settings` to `[backup.auto]`. A changed schedule gets a fresh
+    /// try, so earlier failures no longer hold the next run back.
+    pub fn configure(&self, settings: &AutoBackupSettings) -> Result<BackupSchedule> {
+        let mut schedule = BackupSchedule::from_settings(settings, &self.config.ssh_dir)?;
+        // Fail now rather than at the first scheduled run
+        Lock::recipients(&schedule.recipients, &schedule.recipients_files)?;
+        self.config.set_auto_backup(Some(settings))?;
+
+        schedule.state = self.load_state()?;
+        schedule.state.failures = 0;
+        schedule.state.last_failure = None;
+        self.save_state(&schedule.state)?;
+        Ok(schedule)
+    }
+
+    /// Stop backing up automatically; existing backups stay. Returns
+    /// whether a schedule was set.
+    pub fn disable(&self) -> Result<bool> {
+        self.config.set_auto_backup(None)
+    }
+
+    /// Back up now if a schedule is set and due, and no other run is under way
+    pub fn run_if_due(&self) -> Result<Option<AutoBackupReport>> {
+        let Some(_lock) = RunLock::take(self.dir())? else {
+            return Ok(None);
+        };
+        // Read under the lock, so a run that just finished is seen
+        match self.load()? {
+            Some(mut schedule) if schedule.is_due(Local::now()) => {
+                self.run_locked(&mut schedule).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Back up every key in the schedule's ssh directory, record the run
+    /// and prune down to `keep` automatic backups. A failure is recorded too,
+    /// delaying the next attempt.
+    pub fn run(&self, schedule: &mut BackupSchedule) -> Result<AutoBackupReport> {
+        let Some(_lock) = RunLock::take(self.dir())? else {
+            return Err(SkmError::ImportExport(
+                "Another automatic backup is running".to_string(),
+            ));
+        };
+        self.run_locked(schedule)
+    }
+
+    fn run_locked(&self, schedule: &mut BackupSchedule) -> Result<AutoBackupReport> {
+        match self.back_up(schedule) {
+            Ok(report) => Ok(report),
+            Err(e) => {
+                schedule.state.failures = schedule.state.failures.saturating_add(1);
+                schedule.state.last_failure = Some(Local::now());
+                self.save_state(&schedule.state)?;
+                Err(e)
+            }
+        }
+    }
+
+    fn back_up(&self, schedule: &mut BackupSchedule) -> Result<AutoBackupReport> {
+        let keys = KeyScanner::new(&schedule.ssh_dir).scan()?;
+        if keys.is_empty() {
+            return Err(SkmError::ImportExport(format!(
+                "No keys in {} to back up",
+                schedule.ssh_dir.display()
+            )));
+        }
+        let lock = Lock::recipients(&schedule.recipients, &schedule.recipients_files)?;
+
+        let now = Local::now();
+        fs::create_dir_all(self.dir())?;
+        let path = self.dir().join(format!(
+            "{}{}{}",
+            FILE_PREFIX,
+            now.format("%Y%m%d-%H%M%S"),
+            FILE_SUFFIX
+        ));
+        let options = ExportOptions {
+            description: Some("automatic backup".to_string()),
+            ..Default::default()
+        };
+        let report = BackupManager::new(&schedule.ssh_dir).export_with_progress(
+            &keys,
+            &path,
+            &lock,
+            options,
+            |_, _, _| {},
+        )?;
+
+        schedule.state = RunState {
+            last_run: Some(now),
+            failures: 0,
+            last_failure: None,
+        };
+        self.save_state(&schedule.state)?;
+        let pruned = self.prune(schedule.keep)?;
+
+        Ok(AutoBackupReport {
+            path,
+            key_count: report.exported.len(),
+            pruned,
+        })
+    }
+
+    /// Automatic backups on disk, oldest first
+    pub fn backups(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir().exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(self.dir())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX)
+            })
+            .map(|entry| entry.path())
+            .collect();
+        // Names are timestamps, so name order is time order
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Delete all but the newest `keep` automatic backups. Backups made
+    /// with `skm export` are never touched.
+    pub fn prune(&self, keep: usize) -> Result<Vec<PathBuf>> {
+        let backups = self.backups()?;
+        let excess = backups.len().saturating_sub(keep);
+        let pruned: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+        for path in &pruned {
+            fs::remove_file(path)?;
+        }
+        Ok(pruned)
+    }
+}
+
+/// `auto-backup.lock`, created exclusively and removed when dropped
+struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// `None` while another run holds the lock
+    fn take(dir: &Path) -> Result<Option<Self>> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE);
+        for _ in 0..2 {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    use std::io::Write;
+                    writeln!(file, "{}", std::process::id())?;
+                    return Ok(Some(Self { path }));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path)?
+                        .modified()?
+                        .elapsed()
+                        .unwrap_or_default();
+                    if age.as_secs() < STALE_LOCK_SECS {
+                        return Ok(None);
+                    }
+                    tracing::warn!("Taking over a stale lock, {}", path.display());
+                    fs::remove_file(&path)?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Parse an interval such as `12h`, `7d` or `2w` into seconds
+pub fn parse_interval(interval: &str) -> Result<u64> {
+    let interval = interval.trim();
+    let invalid = || {
+        SkmError::Config(format!(
+            "Invalid interval '{}' (expected e.g. 12h, 7d, 2w)",
+            interval
+        ))
+    };
+
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (number, unit) = interval.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    match number.checked_mul(multiplier) {
+        Some(seconds) if seconds > 0 => Ok(seconds),
+        _ => Err(invalid()),
+    }
+}
+
+/// Seconds as the largest whole unit, e.g. `7d`; the inverse of `parse_interval`
+pub fn format_interval(seconds: u64) -> String {
+    [(7 * 24 * 60 * 60, "w"), (24 * 60 * 60, "d"), (60 * 60, "h")]
+        .iter()
+        .find(|(unit, _)| seconds % unit == 0)
+        .map(|(unit, suffix)| format!("{}{}", seconds / unit, suffix))
+        .unwrap_or_else(|| format!("{}m", seconds / 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::generate::{KeyGenOptions, KeyGenerator};
+    use crate::ssh::keys::FingerprintHash;
+    use tempfile::TempDir;
+
+    fn settings(keep: usize) -> AutoBackupSettings {
+        let identity = age::x25519::Identity::generate();
+        AutoBackupSettings {
+            every: "7d".to_string(),
+            keep: Some(keep),
+            recipients: vec![identity.to_public().to_string()],
+            recipients_files: Vec::new(),
+            ssh_dir: None,
+        }
+    }
+
+    /// Automatic backups of `ssh_dir` into `data_dir`, set up with `keep`
+    fn auto_backup(ssh_dir: &Path, data_dir: &Path, keep: usize) -> AutoBackup {
+        let auto = AutoBackup::new(&Config {
+            ssh_dir: ssh_dir.to_path_buf(),
+            export_dir: data_dir.to_path_buf(),
+            fingerprint_hash: FingerprintHash::default(),
+        });
+        auto.configure(&settings(keep)).unwrap();
+        auto
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("30m").unwrap(), 1800);
+        assert_eq!(parse_interval("7d").unwrap(), 604800);
+        assert_eq!(parse_interval("2w").unwrap(), 1209600);
+        assert!(parse_interval("7").is_err());
+        assert!(parse_interval("0d").is_err());
+        assert!(parse_interval("1y").is_err());
+
+        assert_eq!(format_interval(604800), "1w");
+        assert_eq!(format_interval(172800), "2d");
+        assert_eq!(format_interval(1800), "30m");
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let dir = TempDir::new().unwrap();
+        let schedule = BackupSchedule::from_settings(&settings(3), dir.path()).unwrap();
+        assert_eq!(schedule.every_secs, 7 * 24 * 60 * 60);
+        assert_eq!(schedule.keep, 3);
+        assert_eq!(schedule.ssh_dir, dir.path());
+
+        let mut bad = settings(0);
+        assert!(BackupSchedule::from_settings(&bad, dir.path()).is_err());
+        bad.keep = None;
+        bad.every = "weekly".to_string();
+        assert!(BackupSchedule::from_settings(&bad, dir.path()).is_err());
+        bad.every = "1w".to_string();
+        bad.recipients.clear();
+        assert!(BackupSchedule::from_settings(&bad, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_is_due() {
+        let dir = TempDir::new().unwrap();
+        let mut schedule = BackupSchedule::from_settings(&settings(10), dir.path()).unwrap();
+        let now = Local::now();
+        assert!(schedule.is_due(now));
+
+        schedule.state.last_run = Some(now - Duration::days(3));
+        assert!(!schedule.is_due(now));
+        schedule.state.last_run = Some(now - Duration::days(8));
+        assert!(schedule.is_due(now));
+    }
+
+    #[test]
+    fn test_run_and_prune() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        KeyGenerator::new(ssh_dir.path())
+            .generate(KeyGenOptions::default())
+            .unwrap();
+        // Manual exports live next to automatic ones and are kept
+        fs::write(data_dir.path().join("manual.skm"), "").unwrap();
+        for stamp in ["20240101-000000", "20240108-000000"] {
+            fs::write(data_dir.path().join(format!("auto-{}.skm", stamp)), "").unwrap();
+        }
+
+        let auto = auto_backup(ssh_dir.path(), data_dir.path(), 2);
+        let report = auto.run_if_due().unwrap().unwrap();
+        assert_eq!(report.key_count, 1);
+        assert_eq!(
+            report.pruned,
+            vec![data_dir.path().join("auto-20240101-000000.skm")]
+        );
+        assert_eq!(auto.backups().unwrap().len(), 2);
+        assert!(data_dir.path().join("manual.skm").exists());
+
+        // The run was recorded, so nothing is due until next week
+        let schedule = auto.load().unwrap().unwrap();
+        assert!(schedule.state.last_run.is_some());
+        assert!(auto.run_if_due().unwrap().is_none());
+        // Only the run state is kept in the JSON file
+        let state = fs::read_to_string(data_dir.path().join(STATE_FILE)).unwrap();
+        assert!(state.contains("last_run"));
+        assert!(!state.contains("every") && !state.contains("recipients"));
+
+        assert!(auto.disable().unwrap());
+        assert!(auto.load().unwrap().is_none());
+        assert!(!auto.disable().unwrap());
+    }
+
+    #[test]
+    fn test_carries_over_legacy_schedule() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        let identity = age::x25519::Identity::generate();
+        let legacy = serde_json::json!({
+            "every_secs": 2 * 24 * 60 * 60,
+            "keep": 4,
+            "recipients": [identity.to_public().to_string()],
+            "recipients_files": [],
+            "ssh_dir": ssh_dir.path(),
+            "last_run": Local::now(),
+            "failures": 0,
+            "last_failure": null,
+        });
+        migrate::save_json(&data_dir.path().join(STATE_FILE), &legacy).unwrap();
+
+        let config = Config {
+            ssh_dir: ssh_dir.path().to_path_buf(),
+            export_dir: data_dir.path().to_path_buf(),
+            fingerprint_hash: FingerprintHash::default(),
+        };
+        let schedule = AutoBackup::new(&config).load().unwrap().unwrap();
+        assert_eq!(schedule.every_secs, 2 * 24 * 60 * 60);
+        assert_eq!(schedule.keep, 4);
+        assert!(schedule.state.last_run.is_some());
+
+        let auto = config.load_settings().unwrap().backup.auto.unwrap();
+        assert_eq!(auto.every, "2d");
+        let state = fs::read_to_string(data_dir.path().join(STATE_FILE)).unwrap();
+        assert!(!state.contains("every_secs"));
+    }
+
+    #[test]
+    fn test_failed_run_backs_off() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        let auto = auto_backup(ssh_dir.path(), data_dir.path(), 2);
+
+        // No keys to back up: the failure is recorded and not retried right away
+        assert!(auto.run_if_due().is_err());
+        let schedule = auto.load().unwrap().unwrap();
+        assert_eq!(schedule.state.failures, 1);
+        assert!(schedule.state.last_run.is_none());
+        assert!(auto.run_if_due().unwrap().is_none());
+
+        let now = Local::now();
+        assert!(!schedule.is_due(now + Duration::minutes(59)));
+        assert!(schedule.is_due(now + Duration::minutes(61)));
+        let mut again = schedule.clone();
+        again.state.failures = 3;
+        assert!(!again.is_due(now + Duration::hours(3)));
+        assert!(again.is_due(now + Duration::hours(5)));
+        // Never waiting longer than the interval
+        again.state.failures = 30;
+        assert!(again.is_due(now + Duration::days(8)));
+
+        // Setting the schedule again clears the failures
+        auto.configure(&settings(2)).unwrap();
+        assert_eq!(auto.load().unwrap().unwrap().state.failures, 0);
+    }
+
+    #[test]
+    fn test_runs_dont_overlap() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        KeyGenerator::new(ssh_dir.path())
+            .generate(KeyGenOptions::default())
+            .unwrap();
+        let auto = auto_backup(ssh_dir.path(), data_dir.path(), 2);
+        let mut schedule = auto.load().unwrap().unwrap();
+
+        let held = RunLock::take(data_dir.path()).unwrap().unwrap();
+        assert!(auto.run_if_due().unwrap().is_none());
+        assert!(auto.run(&mut schedule).is_err());
+        assert!(auto.backups().unwrap().is_empty());
+
+        drop(held);
+        assert!(auto.run_if_due().unwrap().is_some());
+        assert!(!data_dir.path().join(LOCK_FILE).exists());
+    }
+}
@@ -0,0 +1,212 @@
+//! Backups kept on any host reachable over SFTP, such as a NAS
+//!
+//! Transfers run the system `sftp` in batch mode, so they authenticate the
+//! way `ssh` would: with the given key, the agent, or `~/.ssh/config`, but
+//! never with a password prompt.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::deploy::DeployTarget;
+
+/// URL scheme that names a remote backup, as in `sftp://me@nas/backups/keys.skm`
+pub const SCHEME: &str = "sftp://";
+
+/// A backup file: `sftp://[user@]host[:port]/path`. Like curl, the path is
+/// absolute; start it with `/~/` for one relative to the home directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SftpLocation {
+    /// Host to log in to; `identity` is the key to log in with
+    pub target: DeployTarget,
+    pub path: String,
+}
+
+impl SftpLocation {
+    pub fn is_url(value: &str) -> bool {
+        value.starts_with(SCHEME)
+    }
+
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            SkmError::Config(format!(
+                "Invalid SFTP URL '{}': {} (expected sftp://[user@]host[:port]/path/backup.skm)",
+                url, reason
+            ))
+        };
+
+        let rest = url
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| invalid("it doesn't start with sftp://"))?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if path.is_empty() || path.ends_with('/') {
+            return Err(invalid("no file name after the host"));
+        }
+
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port)) => (
+                destination,
+                Some(port.parse::<u16>().map_err(|_| invalid("bad port"))?),
+            ),
+            None => (authority, None),
+        };
+        let mut target = DeployTarget::parse(destination).map_err(|_| invalid("no host"))?;
+        target.port = port;
+
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => relative.to_string(),
+            None => format!("/{}", path),
+        };
+        Ok(Self { target, path })
+    }
+
+    /// Last path segment, used for the local copy
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// Copy the local `file` to this location
+    pub fn upload(&self, file: &Path) -> Result<()> {
+        self.run(&format!(
+            "put {} {}",
+            quote(&file.to_string_lossy()),
+            quote(&self.path)
+        ))
+    }
+
+    /// Copy this location to the local `file`
+    pub fn download(&self, file: &Path) -> Result<()> {
+        self.run(&format!(
+            "get {} {}",
+            quote(&self.path),
+            quote(&file.to_string_lossy())
+        ))
+    }
+
+    /// Read this location into memory. `sftp` can only write to a file, so it
+    /// goes through a temporary one that is removed again.
+    pub fn fetch(&self) -> Result<Vec<u8>> {
+        let scratch = tempfile::NamedTempFile::new()?;
+        self.download(scratch.path())?;
+        Ok(std::fs::read(scratch.path())?)
+    }
+
+    /// Arguments for `sftp`; unlike `ssh` it takes the port as `-P`. The host
+    /// can't start with `-` (see `DeployTarget::parse`), and `--` ends the
+    /// options anyway.
+    fn sftp_args(&self) -> Vec<String> {
+        let mut args = vec!["-b".to_string(), "-".to_string()];
+        if let Some(port) = self.target.port {
+            args.push("-P".to_string());
+            args.push(port.to_string());
+        }
+        if let Some(ref identity) = self.target.identity {
+            args.push("-i".to_string());
+            args.push(identity.to_string_lossy().to_string());
+            args.push("-o".to_string());
+            args.push("IdentitiesOnly=yes".to_string());
+        }
+        args.push("--".to_string());
+        args.push(self.target.destination());
+        args
+    }
+
+    /// Run one batch command; `sftp -b` exits non-zero if it fails
+    fn run(&self, batch: &str) -> Result<()> {
+        let mut child = Command::new("sftp")
+            .args(self.sftp_args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| SkmError::Remote(format!("Failed to run sftp: {}", e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{}", batch)?;
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(SkmError::Remote(format!(
+                "sftp to {} failed ({})",
+                self.target.destination(),
+                status
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SftpLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", SCHEME, self.target.destination())?;
+        if let Some(port) = self.target.port {
+            write!(f, ":{}", port)?;
+        }
+        match self.path.strip_prefix('/') {
+            Some(absolute) => write!(f, "/{}", absolute),
+            None => write!(f, "/~/{}", self.path),
+        }
+    }
+}
+
+/// Quote a path for an sftp batch file
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        let location = SftpLocation::parse("sftp://me@nas:2222/volume1/backups/keys.skm").unwrap();
+        assert_eq!(location.target.destination(), "me@nas");
+        assert_eq!(location.target.port, Some(2222));
+        assert_eq!(location.path, "/volume1/backups/keys.skm");
+        assert_eq!(location.file_name(), "keys.skm");
+        assert_eq!(
+            location.to_string(),
+            "sftp://me@nas:2222/volume1/backups/keys.skm"
+        );
+
+        let location = SftpLocation::parse("sftp://nas/~/keys.skm").unwrap();
+        assert_eq!(location.target.destination(), "nas");
+        assert_eq!(location.path, "keys.skm");
+        assert_eq!(location.to_string(), "sftp://nas/~/keys.skm");
+
+        assert!(SftpLocation::is_url("sftp://nas/keys.skm"));
+        assert!(!SftpLocation::is_url("s3://bucket/keys.skm"));
+        assert!(SftpLocation::parse("sftp://nas").is_err());
+        assert!(SftpLocation::parse("sftp://nas/backups/").is_err());
+        assert!(SftpLocation::parse("sftp:///keys.skm").is_err());
+        assert!(SftpLocation::parse("sftp://nas:ssh/keys.skm").is_err());
+        assert!(SftpLocation::parse("sftp://-oProxyCommand=sh/keys.skm").is_err());
+        assert!(SftpLocation::parse("sftp://-F@nas/keys.skm").is_err());
+    }
+
+    #[test]
+    fn test_sftp_args() {
+        let mut location = SftpLocation::parse("sftp://me@nas:2222/keys.skm").unwrap();
+        location.target.identity = Some("/home/me/.ssh/id_nas".into());
+        assert_eq!(
+            location.sftp_args(),
+            vec![
+                "-b",
+                "-",
+                "-P",
+                "2222",
+                "-i",
+                "/home/me/.ssh/id_nas",
+                "-o",
+                "IdentitiesOnly=yes",
+                "--",
+                "me@nas"
+            ]
+        );
+        assert_eq!(quote(r#"my "keys".skm"#), r#""my \"keys\".skm""#);
+    }
+}
@@ -0,0 +1,371 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::crypto::backup::{BackupEntry, BackupManager, BackupMetadata};
+use crate::crypto::encrypt::Unlock;
+use crate::error::{Result, SkmError};
+use crate::ssh::KeyScanner;
+use crate::ssh::cert::CERT_SUFFIX;
+use crate::ssh::keys::{KeyType, MAX_KEY_FILE_SIZE};
+
+/// Archives holding more entries than this are not ssh directories
+const MAX_ARCHIVE_ENTRIES: usize = 10_000;
+
+/// Something keys can be imported from: an skm backup or another tool's export
+pub trait ImportSource {
+    /// Shown to the user, e.g. "tar archive"
+    fn label(&self) -> &'static str;
+
+    /// Whether `read_entries` needs a passphrase or identity to get at the keys
+    fn needs_passphrase(&self) -> bool {
+        false
+    }
+
+    /// Read the keys without touching the ssh directory
+    fn read_entries(&self, path: &Path, unlock: Option<&Unlock>) -> Result<Vec<BackupEntry>>;
+
+    /// The keys plus the backup's metadata, for formats that record one
+    fn read_backup(
+        &self,
+        path: &Path,
+        unlock: Option<&Unlock>,
+    ) -> Result<(Vec<BackupEntry>, Option<BackupMetadata>)> {
+        Ok((self.read_entries(path, unlock)?, None))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    /// Encrypted `.skm` backup
+    Skm,
+    /// Plain or gzipped tar of an ssh directory
+    Tar,
+    /// JSON list of keys as exported by other key managers
+    Json,
+}
+
+impl SourceFormat {
+    /// Guess the format from the first bytes of the file
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut header = [0u8; 512];
+        let mut file = fs::File::open(path)?;
+        let mut read = 0;
+        while read < header.len() {
+            match file.read(&mut header[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        let header = &header[..read];
+
+        if header.starts_with(&[0x1f, 0x8b]) || header.get(257..262) == Some(b"ustar") {
+            return Ok(SourceFormat::Tar);
+        }
+        match header.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{' | b'[') => Ok(SourceFormat::Json),
+            _ => Ok(SourceFormat::Skm),
+        }
+    }
+
+    pub fn source(self) -> Box<dyn ImportSource> {
+        match self {
+            SourceFormat::Skm => Box::new(SkmBackup),
+            SourceFormat::Tar => Box::new(TarArchive),
+            SourceFormat::Json => Box::new(JsonKeyList),
+        }
+    }
+
+    fn is_gzip(path: &Path) -> Result<bool> {
+        let mut magic = [0u8; 2];
+        let read = fs::File::open(path)?.read(&mut magic)?;
+        Ok(read == 2 && magic == [0x1f, 0x8b])
+    }
+}
+
+/// Backups written by `skm export`
+pub struct SkmBackup;
+
+impl ImportSource for SkmBackup {
+    fn label(&self) -> &'static str {
+        "skm backup"
+    }
+
+    fn needs_passphrase(&self) -> bool {
+        true
+    }
+
+    fn read_entries(&self, path: &Path, unlock: Option<&Unlock>) -> Result<Vec<BackupEntry>> {
+        Ok(self.read_backup(path, unlock)?.0)
+    }
+
+    fn read_backup(
+        &self,
+        path: &Path,
+        unlock: Option<&Unlock>,
+    ) -> Result<(Vec<BackupEntry>, Option<BackupMetadata>)> {
+        let unlock = unlock.ok_or(SkmError::InvalidPassphrase)?;
+        let backup = BackupManager::read_unlocked(path, unlock)?;
+        Ok((backup.keys, Some(backup.metadata)))
+    }
+}
+
+/// `tar czf ssh.tgz -C ~ .ssh` and the like. Only key files are picked up;
+/// config, known_hosts and anything that isn't a key is left out.
+pub struct TarArchive;
+
+impl ImportSource for TarArchive {
+    fn label(&self) -> &'static str {
+        "tar archive"
+    }
+
+    fn read_entries(&self, path: &Path, _unlock: Option<&Unlock>) -> Result<Vec<BackupEntry>> {
+        let file = fs::File::open(path)?;
+        let reader: Box<dyn Read> = if SourceFormat::is_gzip(path)? {
+            Box::new(flate2::read::GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+
+        let mut archive = tar::Archive::new(reader);
+        let mut files = BTreeMap::new();
+        let to_err = |e: std::io::Error| SkmError::ImportExport(format!("Invalid archive: {}", e));
+
+        for (index, entry) in archive.entries().map_err(to_err)?.enumerate() {
+            if index == MAX_ARCHIVE_ENTRIES {
+                return Err(SkmError::ImportExport(format!(
+                    "Archive has more than {} entries",
+                    MAX_ARCHIVE_ENTRIES
+                )));
+            }
+            let mut entry = entry.map_err(to_err)?;
+            // Symlinks and devices can't carry keys; never follow them
+            if !entry.header().entry_type().is_file() || entry.size() > MAX_KEY_FILE_SIZE {
+                continue;
+            }
+            // Only the file name is kept, so `../` tricks can't escape the ssh dir
+            let name = match entry.path().map_err(to_err)?.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(to_err)?;
+            files.insert(name, data);
+        }
+
+        Ok(pair_files(files))
+    }
+}
+
+/// Turn loose files into entries: each private key with its `.pub`, plus
+/// public keys whose private half isn't there
+fn pair_files(mut files: BTreeMap<String, Vec<u8>>) -> Vec<BackupEntry> {
+    files.retain(|name, _| {
+        !name.starts_with('.') && !name.ends_with(CERT_SUFFIX) && !KeyScanner::is_non_key_file(name)
+    });
+
+    let mut entries = Vec::new();
+    for (name, data) in &files {
+        let text = String::from_utf8_lossy(data);
+        if let Some(stem) = name.strip_suffix(".pub") {
+            if files.contains_key(stem) {
+                continue;
+            }
+            if let Ok(public) = ssh_key::PublicKey::from_openssh(text.trim()) {
+                entries.push(BackupEntry {
+                    name: stem.to_string(),
+                    key_type: KeyType::from_algorithm(&public.algorithm()).to_string(),
+                    comment: Some(public.comment().to_string()).filter(|c| !c.is_empty()),
+                    private_key: None,
+                    public_key: Some(data.clone()),
+                });
+            }
+            continue;
+        }
+
+        if !text.trim_start().starts_with("-----BEGIN") || !text.contains("PRIVATE KEY-----") {
+            continue;
+        }
+        let public_key = files.get(&format!("{}.pub", name)).cloned();
+        let public = public_key
+            .as_deref()
+            .and_then(|p| ssh_key::PublicKey::from_openssh(String::from_utf8_lossy(p).trim()).ok())
+            .or_else(|| {
+                ssh_key::PrivateKey::from_openssh(data.as_slice())
+                    .ok()
+                    .map(|k| k.public_key().clone())
+            });
+        let key_type = public
+            .as_ref()
+            .map(|p| KeyType::from_algorithm(&p.algorithm()))
+            .or_else(|| KeyType::from_pem(&text))
+            .unwrap_or_else(|| KeyType::from_filename(name));
+
+        entries.push(BackupEntry {
+            name: name.clone(),
+            key_type: key_type.to_string(),
+            comment: public
+                .map(|p| p.comment().to_string())
+                .filter(|c| !c.is_empty()),
+            private_key: Some(data.clone()),
+            public_key,
+        });
+    }
+    entries
+}
+
+/// Either `[{...}, ...]` or `{"keys": [{...}, ...]}`; the field names
+/// other managers use for the same thing are accepted as aliases
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonDocument {
+    List(Vec<JsonKey>),
+    Wrapped { keys: Vec<JsonKey> },
+}
+
+#[derive(Deserialize)]
+struct JsonKey {
+    #[serde(alias = "title", alias = "label")]
+    name: String,
+    #[serde(default, alias = "privateKey", alias = "private")]
+    private_key: Option<String>,
+    #[serde(default, alias = "publicKey", alias = "public")]
+    public_key: Option<String>,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// JSON key lists exported by other SSH key managers
+pub struct JsonKeyList;
+
+impl ImportSource for JsonKeyList {
+    fn label(&self) -> &'static str {
+        "JSON key list"
+    }
+
+    fn read_entries(&self, path: &Path, _unlock: Option<&Unlock>) -> Result<Vec<BackupEntry>> {
+        let content = fs::read(path)?;
+        let document: JsonDocument = serde_json::from_slice(&content)
+            .map_err(|e| SkmError::ImportExport(format!("Invalid key list: {}", e)))?;
+        let keys = match document {
+            JsonDocument::List(keys) | JsonDocument::Wrapped { keys } => keys,
+        };
+
+        let mut files = BTreeMap::new();
+        let mut comments = BTreeMap::new();
+        for key in keys {
+            let name = key.name.trim();
+            if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+                return Err(SkmError::ImportExport(format!(
+                    "Invalid key name in key list: '{}'",
+                    key.name
+                )));
+            }
+            if let Some(private) = key.private_key {
+                files.insert(name.to_string(), ensure_newline(private));
+            }
+            if let Some(public) = key.public_key {
+                files.insert(format!("{}.pub", name), ensure_newline(public));
+            }
+            if let Some(comment) = key.comment {
+                comments.insert(name.to_string(), comment);
+            }
+        }
+
+        let mut entries = pair_files(files);
+        for entry in &mut entries {
+            if let Some(comment) = comments.remove(&entry.name) {
+                entry.comment = Some(comment);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn ensure_newline(mut data: String) -> Vec<u8> {
+    if !data.ends_with('\n') {
+        data.push('\n');
+    }
+    data.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use ssh_key::{Algorithm, LineEnding, PrivateKey};
+    use tempfile::TempDir;
+
+    fn key_pair(comment: &str) -> (String, String) {
+        let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        key.set_comment(comment);
+        (
+            key.to_openssh(LineEnding::LF).unwrap().to_string(),
+            key.public_key().to_openssh().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_tar_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let (private, public) = key_pair("me@laptop");
+        let (_, github) = key_pair("github");
+
+        let path = temp_dir.path().join("ssh.tgz");
+        let gz = flate2::write::GzEncoder::new(
+            fs::File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(gz);
+        for (name, data) in [
+            (".ssh/id_ed25519", private.as_str()),
+            (".ssh/id_ed25519.pub", public.as_str()),
+            (".ssh/github.pub", github.as_str()),
+            (".ssh/config", "Host *\n"),
+            (".ssh/notes.txt", "not a key"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o600);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(SourceFormat::detect(&path).unwrap(), SourceFormat::Tar);
+        let entries = TarArchive.read_entries(&path, None).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["github", "id_ed25519"]);
+
+        assert!(entries[0].private_key.is_none());
+        assert_eq!(entries[1].key_type, "ED25519");
+        assert_eq!(entries[1].comment.as_deref(), Some("me@laptop"));
+        assert_eq!(entries[1].public_key.as_deref(), Some(public.as_bytes()));
+    }
+
+    #[test]
+    fn test_json_key_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let (private, public) = key_pair("");
+        let path = temp_dir.path().join("keys.json");
+        let json = serde_json::json!({
+            "keys": [{ "title": "work", "privateKey": private, "publicKey": public, "comment": "work laptop" }]
+        });
+        fs::write(&path, json.to_string()).unwrap();
+
+        assert_eq!(SourceFormat::detect(&path).unwrap(), SourceFormat::Json);
+        let entries = JsonKeyList.read_entries(&path, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "work");
+        assert_eq!(entries[0].comment.as_deref(), Some("work laptop"));
+        assert!(entries[0].private_key.is_some());
+
+        let json = serde_json::json!([{ "name": "../evil", "public_key": public }]);
+        fs::write(&path, json.to_string()).unwrap();
+        assert!(JsonKeyList.read_entries(&path, None).is_err());
+    }
+}
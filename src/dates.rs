@@ -0,0 +1,176 @@
+//! Dates as people read them: in their locale's order, with how long ago
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+use crate::config::DisplaySettings;
+
+/// ISO 8601, for the C locale and locales not listed in `locale_pattern`
+const ISO_PATTERN: &str = "%Y-%m-%d %H:%M";
+
+/// How dates are shown in `skm show`, `skm list` and the TUI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat {
+    pattern: String,
+    relative: bool,
+}
+
+impl DateFormat {
+    /// `date_format` from the settings, or the order the locale in
+    /// `LC_ALL` / `LC_TIME` / `LANG` uses
+    pub fn new(settings: &DisplaySettings) -> Self {
+        // chrono panics when asked to format with an invalid pattern
+        let configured = settings.date_format.clone().filter(|pattern| {
+            let valid = StrftimeItems::new(pattern).all(|item| item != Item::Error);
+            if !valid {
+                tracing::warn!("Ignoring invalid date_format '{}'", pattern);
+            }
+            valid
+        });
+        let pattern = configured.unwrap_or_else(|| {
+            let locale = ["LC_ALL", "LC_TIME", "LANG"]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok())
+                .find(|value| !value.is_empty())
+                .unwrap_or_default();
+            locale_pattern(&locale).to_string()
+        });
+
+        Self {
+            pattern,
+            relative: settings.relative_dates.unwrap_or(true),
+        }
+    }
+
+    /// The date, then how long ago it was, e.g. `2024-07-01 14:03 (3 months ago)`
+    pub fn full(&self, time: DateTime<Local>) -> String {
+        self.full_at(time, Local::now())
+    }
+
+    /// One of the two, for narrow columns: how long ago, or the date when
+    /// relative dates are turned off
+    pub fn short(&self, time: DateTime<Local>) -> String {
+        self.short_at(time, Local::now())
+    }
+
+    fn full_at(&self, time: DateTime<Local>, now: DateTime<Local>) -> String {
+        let absolute = time.format(&self.pattern).to_string();
+        if self.relative {
+            format!("{} ({})", absolute, relative(time, now))
+        } else {
+            absolute
+        }
+    }
+
+    fn short_at(&self, time: DateTime<Local>, now: DateTime<Local>) -> String {
+        if self.relative {
+            relative(time, now)
+        } else {
+            time.format(&self.pattern).to_string()
+        }
+    }
+}
+
+/// How long before (or after) `now` the time is, e.g. `3 months ago`
+pub fn relative(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let seconds = now.signed_duration_since(time).num_seconds();
+    if seconds.abs() < 60 {
+        return "just now".to_string();
+    }
+
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let (count, unit) = match seconds.abs() {
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < 30 * DAY => (s / DAY, "day"),
+        s if s < 365 * DAY => (s / (30 * DAY), "month"),
+        s => (s / (365 * DAY), "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+
+    if seconds > 0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+/// strftime pattern for a POSIX locale name such as `de_DE.UTF-8`
+fn locale_pattern(locale: &str) -> &'static str {
+    // Drop the encoding and modifier: `sr_RS.UTF-8@latin` -> `sr_RS`
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = name.split('_').next().unwrap_or_default();
+
+    match (language, name) {
+        (_, "en_US" | "en_PH") => "%m/%d/%Y %I:%M %p",
+        (_, "en_CA" | "fr_CA") | ("sv" | "lt" | "C" | "POSIX" | "", _) => ISO_PATTERN,
+        ("en" | "fr" | "es" | "it" | "pt" | "el" | "ga" | "ca", _) => "%d/%m/%Y %H:%M",
+        (
+            "de" | "ru" | "uk" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "da" | "tr" | "ro"
+            | "bg" | "sr" | "hr" | "sl" | "et" | "lv" | "be" | "kk",
+            _,
+        ) => "%d.%m.%Y %H:%M",
+        ("nl", _) => "%d-%m-%Y %H:%M",
+        ("ja" | "zh" | "ko" | "hu", _) => "%Y/%m/%d %H:%M",
+        _ => ISO_PATTERN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_relative() {
+        let now = Local.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+        assert_eq!(relative(now - Duration::seconds(20), now), "just now");
+        assert_eq!(relative(now - Duration::minutes(1), now), "1 minute ago");
+        assert_eq!(relative(now - Duration::hours(5), now), "5 hours ago");
+        assert_eq!(relative(now - Duration::days(95), now), "3 months ago");
+        assert_eq!(relative(now - Duration::days(800), now), "2 years ago");
+        assert_eq!(relative(now + Duration::days(2), now), "in 2 days");
+    }
+
+    #[test]
+    fn test_locale_pattern() {
+        assert_eq!(locale_pattern("de_DE.UTF-8"), "%d.%m.%Y %H:%M");
+        assert_eq!(locale_pattern("en_US.UTF-8"), "%m/%d/%Y %I:%M %p");
+        assert_eq!(locale_pattern("en_GB.UTF-8"), "%d/%m/%Y %H:%M");
+        assert_eq!(locale_pattern("sr_RS.UTF-8@latin"), "%d.%m.%Y %H:%M");
+        assert_eq!(locale_pattern("C.UTF-8"), ISO_PATTERN);
+        assert_eq!(locale_pattern("xx_YY"), ISO_PATTERN);
+    }
+
+    #[test]
+    fn test_format_settings() {
+        let now = Local.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+        let time = now - Duration::days(3);
+        let settings = DisplaySettings {
+            date_format: Some("%d %b %Y".to_string()),
+            relative_dates: None,
+            ..Default::default()
+        };
+
+        let format = DateFormat::new(&settings);
+        assert_eq!(format.full_at(time, now), "28 Sep 2024 (3 days ago)");
+        assert_eq!(format.short_at(time, now), "3 days ago");
+
+        let format = DateFormat::new(&DisplaySettings {
+            relative_dates: Some(false),
+            ..settings
+        });
+        assert_eq!(format.full_at(time, now), "28 Sep 2024");
+        assert_eq!(format.short_at(time, now), "28 Sep 2024");
+
+        // A broken pattern falls back instead of failing every date
+        let format = DateFormat::new(&DisplaySettings {
+            date_format: Some("%Q".to_string()),
+            relative_dates: Some(false),
+            ..Default::default()
+        });
+        assert!(!format.short_at(time, now).is_empty());
+    }
+}
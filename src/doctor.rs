@@ -0,0 +1,430 @@
+//! `skm doctor`: environment checks, each with a hint on how to fix it
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::Config;
+use crate::ssh::config::SshConfig;
+use crate::ssh::perms;
+use crate::ssh::{AgentClient, KeyScanner, KeyStatus, SshKey};
+use crate::terminal::{self, Multiplexer};
+use crate::tui::keymap::Keymap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    /// What to run or change to resolve a problem
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Run every check. Nothing here fails: problems become findings.
+pub fn run(config: &Config) -> Vec<Check> {
+    let mut checks = vec![check_ssh_dir(&config.ssh_dir)];
+
+    let (keys, orphans) = KeyScanner::new(&config.ssh_dir)
+        .scan_with_artifacts()
+        .unwrap_or_default();
+    checks.push(check_permissions(&config.ssh_dir, &keys));
+    checks.push(check_agent());
+    checks.push(check_ssh_binary());
+    checks.push(check_clipboard());
+    checks.extend(Multiplexer::detect().map(|multiplexer| check_multiplexer(config, multiplexer)));
+    checks.push(check_settings(config));
+    checks.extend(check_ssh_config(&config.ssh_dir));
+
+    let mut orphaned: Vec<PathBuf> = lone_public_keys(&config.ssh_dir, &keys);
+    orphaned.extend(orphans.into_iter().map(|artifact| artifact.path));
+    checks.extend(check_orphans(&keys, &orphaned));
+
+    checks
+}
+
+fn check_ssh_dir(ssh_dir: &Path) -> Check {
+    const NAME: &str = "ssh directory";
+
+    let metadata = match std::fs::metadata(ssh_dir) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            return Check::problem(
+                NAME,
+                Status::Warning,
+                format!("{} does not exist", ssh_dir.display()),
+                format!(
+                    "create it with `mkdir -m 700 {}` or generate a key with `skm generate`",
+                    ssh_dir.display()
+                ),
+            );
+        }
+    };
+    if !metadata.is_dir() {
+        return Check::problem(
+            NAME,
+            Status::Error,
+            format!("{} is not a directory", ssh_dir.display()),
+            "move the file out of the way; ssh expects a directory there",
+        );
+    }
+
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o777)
+    };
+    #[cfg(not(unix))]
+    let mode: Option<u32> = None;
+
+    match mode {
+        Some(mode) if mode & 0o022 != 0 => Check::problem(
+            NAME,
+            Status::Error,
+            format!(
+                "{} is writable by other users (mode {:o}); sshd will ignore it",
+                ssh_dir.display(),
+                mode
+            ),
+            "run `skm fix-perms`",
+        ),
+        Some(mode) => match perms::unsafe_dir(ssh_dir) {
+            // Only a parent can be left: the directory itself passed above
+            Some(parent) => Check::problem(
+                NAME,
+                Status::Error,
+                format!(
+                    "{}, so other users can swap out {}; skm won't write keys there",
+                    parent,
+                    ssh_dir.display()
+                ),
+                format!(
+                    "run `chmod go-w {}`, or move the ssh directory somewhere private",
+                    parent.path.display()
+                ),
+            ),
+            None => Check::ok(NAME, format!("{} (mode {:o})", ssh_dir.display(), mode)),
+        },
+        None => Check::ok(NAME, ssh_dir.display().to_string()),
+    }
+}
+
+fn check_permissions(ssh_dir: &Path, keys: &[SshKey]) -> Check {
+    const NAME: &str = "permissions";
+
+    let changes = perms::plan(ssh_dir, keys);
+    if changes.is_empty() {
+        return Check::ok(NAME, format!("{} key(s) have safe file modes", keys.len()));
+    }
+
+    let files: Vec<String> = changes
+        .iter()
+        .map(|change| file_name(&change.path))
+        .collect();
+    Check::problem(
+        NAME,
+        Status::Warning,
+        format!(
+            "{} file(s) have loose modes: {}",
+            changes.len(),
+            files.join(", ")
+        ),
+        "run `skm fix-perms`",
+    )
+}
+
+fn check_agent() -> Check {
+    const NAME: &str = "ssh-agent";
+
+    if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            "SSH_AUTH_SOCK is not set, so no agent is reachable",
+            "start one with `eval \"$(ssh-agent)\"` and load keys with `skm agent add <name>`",
+        );
+    }
+
+    match AgentClient::connect_env().and_then(|mut agent| agent.list_identities()) {
+        Ok(identities) => Check::ok(NAME, format!("{} key(s) loaded", identities.len())),
+        Err(e) => Check::problem(
+            NAME,
+            Status::Error,
+            e.to_string(),
+            "SSH_AUTH_SOCK points at a dead agent; restart it with `eval \"$(ssh-agent)\"`",
+        ),
+    }
+}
+
+fn check_ssh_binary() -> Check {
+    const NAME: &str = "ssh client";
+
+    // `ssh -V` prints the version to stderr
+    match Command::new("ssh").arg("-V").output() {
+        Ok(output) => {
+            let version = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Check::ok(NAME, version)
+        }
+        Err(_) => Check::problem(
+            NAME,
+            Status::Error,
+            "ssh not found in PATH; deploy, ephemeral and remote checks need it",
+            "install the OpenSSH client (e.g. `apt install openssh-client`)",
+        ),
+    }
+}
+
+fn check_clipboard() -> Check {
+    const NAME: &str = "clipboard";
+
+    match arboard::Clipboard::new() {
+        Ok(_) => Check::ok(NAME, "available"),
+        // Copies go to the outer terminal instead, see check_multiplexer
+        Err(_) if Multiplexer::detect().is_some() => {
+            Check::ok(NAME, "no system clipboard; copying through OSC 52")
+        }
+        Err(e) => Check::problem(
+            NAME,
+            Status::Warning,
+            format!("not available: {}", e),
+            "use `skm copy --stdout <name>` and pipe it to your clipboard tool",
+        ),
+    }
+}
+
+/// tmux and screen can swallow the TUI's Ctrl keys and block OSC 52 copies
+fn check_multiplexer(config: &Config, multiplexer: Multiplexer) -> Check {
+    const NAME: &str = "multiplexer";
+
+    // The keys the TUI would use; it falls back to the defaults too when
+    // [keys] is broken, which check_settings reports
+    let keymap = config
+        .load_settings()
+        .ok()
+        .and_then(|settings| Keymap::from_settings(&settings.keys).ok())
+        .unwrap_or_default();
+    if let Some(warning) = terminal::conflict_warning(multiplexer, &keymap.ctrl_keys()) {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            warning,
+            format!(
+                "rebind the prefix, or {}; F10 also quits the TUI",
+                multiplexer.send_prefix_hint()
+            ),
+        );
+    }
+    if multiplexer.passthrough_blocked() {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            "tmux allow-passthrough is off, so copies can't reach your terminal's clipboard",
+            "add `set -g allow-passthrough on` to ~/.tmux.conf",
+        );
+    }
+    Check::ok(
+        NAME,
+        format!(
+            "inside {} (prefix {}); copying through OSC 52",
+            multiplexer.name(),
+            multiplexer.prefixes().join(", ")
+        ),
+    )
+}
+
+fn check_settings(config: &Config) -> Check {
+    const NAME: &str = "skm config";
+
+    let path = config.settings_path();
+    match config.load_settings() {
+        Ok(_) if path.exists() => Check::ok(NAME, path.display().to_string()),
+        Ok(_) => Check::ok(
+            NAME,
+            format!("{} not present, using defaults", path.display()),
+        ),
+        Err(e) => Check::problem(
+            NAME,
+            Status::Error,
+            e.to_string(),
+            format!("fix the syntax in {}", path.display()),
+        ),
+    }
+}
+
+/// The ssh config must be readable, and its IdentityFiles must exist
+fn check_ssh_config(ssh_dir: &Path) -> Vec<Check> {
+    const NAME: &str = "ssh config";
+
+    let path = ssh_dir.join("config");
+    let ssh_config = match SshConfig::load(&path) {
+        Ok(ssh_config) => ssh_config,
+        Err(e) => {
+            return vec![Check::problem(
+                NAME,
+                Status::Error,
+                format!("{} could not be read: {}", path.display(), e),
+                format!("check that {} is a readable text file", path.display()),
+            )];
+        }
+    };
+
+    let missing: Vec<Check> = ssh_config
+        .hosts
+        .iter()
+        .flat_map(|host| {
+            host.identity_files
+                .iter()
+                .zip(host.identity_paths())
+                // Paths with tokens like %h depend on the host being connected to
+                .filter(|(value, path)| !value.contains('%') && !path.exists())
+                .map(move |(value, _)| {
+                    Check::problem(
+                        NAME,
+                        Status::Warning,
+                        format!(
+                            "Host {}: IdentityFile {} does not exist",
+                            host.patterns.join(" "),
+                            value
+                        ),
+                        "point it at an existing key or remove it with `skm hosts remove`",
+                    )
+                })
+        })
+        .collect();
+
+    if missing.is_empty() {
+        vec![Check::ok(
+            NAME,
+            format!("{} host(s) in {}", ssh_config.hosts.len(), path.display()),
+        )]
+    } else {
+        missing
+    }
+}
+
+/// `.pub` files with no private key next to them; the scanner pairs public
+/// keys with private ones, so these never show up as keys
+fn lone_public_keys(ssh_dir: &Path, keys: &[SshKey]) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(ssh_dir) else {
+        return Vec::new();
+    };
+
+    let mut lone: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pub"))
+        .filter(|path| !file_name(path).ends_with("-cert.pub"))
+        .filter(|path| !keys.iter().any(|key| &key.public_path == path))
+        .collect();
+    lone.sort();
+    lone
+}
+
+fn check_orphans(keys: &[SshKey], orphaned: &[PathBuf]) -> Vec<Check> {
+    const NAME: &str = "orphaned files";
+
+    let mut checks: Vec<Check> = keys
+        .iter()
+        .filter(|key| key.status == KeyStatus::MissingPublic)
+        .map(|key| {
+            Check::problem(
+                NAME,
+                Status::Warning,
+                format!("{} has no .pub file", key.name),
+                format!("run `skm pubkey --write {}`", key.name),
+            )
+        })
+        .collect();
+
+    if !orphaned.is_empty() {
+        let files: Vec<String> = orphaned.iter().map(|path| file_name(path)).collect();
+        checks.push(Check::problem(
+            NAME,
+            Status::Warning,
+            format!(
+                "{} file(s) without a matching private key: {}",
+                orphaned.len(),
+                files.join(", ")
+            ),
+            "restore the private key from a backup, or delete the leftovers",
+        ));
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::ok(NAME, "none"));
+    }
+    checks
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_orphaned_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        for name in ["id_a", "id_a.pub", "id_gone.pub", "id_b", "id_a-cert.pub"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let keys = KeyScanner::new(dir).scan().unwrap();
+
+        assert_eq!(lone_public_keys(dir, &keys), vec![dir.join("id_gone.pub")]);
+
+        let checks = check_orphans(&keys, &lone_public_keys(dir, &keys));
+        assert_eq!(checks.len(), 2);
+        assert_eq!(
+            checks[0].fix.as_deref(),
+            Some("run `skm pubkey --write id_b`")
+        );
+        assert!(checks[1].message.ends_with("id_gone.pub"));
+    }
+}
@@ -20,6 +20,12 @@ pub enum SkmError {
     #[error("Key already exists: {0}")]
     KeyAlreadyExists(String),
 
+    #[error("Host not found: {0}")]
+    HostNotFound(String),
+
+    #[error("Host already exists: {0}")]
+    HostAlreadyExists(String),
+
     #[error("Invalid key format: {0}")]
     InvalidKeyFormat(String),
 
@@ -29,9 +35,18 @@ pub enum SkmError {
     #[error("Import/Export error: {0}")]
     ImportExport(String),
 
+    #[error("SSH agent error: {0}")]
+    Agent(String),
+
+    #[error("Remote error: {0}")]
+    Remote(String),
+
     #[error("TUI error: {0}")]
     Tui(String),
 
+    #[error("Approval error: {0}")]
+    Approval(String),
+
     #[error("Configuration error: {0}")]
     Config(String),
 
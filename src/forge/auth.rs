@@ -0,0 +1,180 @@
+use crate::config::Settings;
+use crate::error::{Result, SkmError};
+
+use super::github::GITHUB_URL;
+use super::gitlab::gitlab_url_with;
+
+/// Service name skm's tokens are filed under in the OS keychain
+const KEYCHAIN_SERVICE: &str = "skm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    Github,
+    Gitlab,
+}
+
+/// Where a token was found, for `skm auth status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    Env(&'static str),
+    Config,
+    Keychain,
+}
+
+impl std::fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenSource::Env(var) => write!(f, "${}", var),
+            TokenSource::Config => write!(f, "~/.skm/config.toml"),
+            TokenSource::Keychain => write!(f, "OS keychain"),
+        }
+    }
+}
+
+impl Forge {
+    pub fn name(self) -> &'static str {
+        match self {
+            Forge::Github => "GitHub",
+            Forge::Gitlab => "GitLab",
+        }
+    }
+
+    /// Environment variables checked for a token, in order
+    fn env_vars(self) -> &'static [&'static str] {
+        match self {
+            Forge::Github => &["GITHUB_TOKEN", "GH_TOKEN"],
+            Forge::Gitlab => &["GITLAB_TOKEN"],
+        }
+    }
+
+    fn config_token(self, settings: &Settings) -> Option<String> {
+        match self {
+            Forge::Github => settings.github.token.clone(),
+            Forge::Gitlab => settings.gitlab.token.clone(),
+        }
+    }
+
+    /// Keychain account the token is stored under; GitLab tokens belong to
+    /// one instance, so self-hosted ones get their own entry
+    fn account(self, settings: &Settings) -> String {
+        self.account_with(settings, |var| std::env::var(var).ok())
+    }
+
+    /// `account` with environment variables looked up through `env`
+    fn account_with(self, settings: &Settings, env: impl Fn(&str) -> Option<String>) -> String {
+        let url = match self {
+            Forge::Github => GITHUB_URL.to_string(),
+            Forge::Gitlab => gitlab_url_with(settings, env),
+        };
+        let host = url
+            .split_once("://")
+            .map_or(url.as_str(), |(_, rest)| rest)
+            .trim_end_matches('/');
+        host.to_string()
+    }
+
+    /// Error for requests that need a token when none is set
+    pub fn missing_token(self) -> SkmError {
+        let config_key = match self {
+            Forge::Github => "github.token",
+            Forge::Gitlab => "gitlab.token",
+        };
+        SkmError::Config(format!(
+            "{} token not set; run `skm auth login {}`, export {} or set {} in ~/.skm/config.toml",
+            self.name(),
+            self.name().to_lowercase(),
+            self.env_vars()[0],
+            config_key
+        ))
+    }
+
+    fn entry(self, settings: &Settings) -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, &self.account(settings)).map_err(keychain_error)
+    }
+}
+
+fn keychain_error(e: keyring::Error) -> SkmError {
+    SkmError::Config(format!("OS keychain unavailable: {}", e))
+}
+
+/// Token from the environment, then the config file, then the OS keychain
+pub fn resolve_token(forge: Forge, settings: &Settings) -> Option<(String, TokenSource)> {
+    let usable = |token: &String| !token.trim().is_empty();
+
+    let from_env = forge.env_vars().iter().find_map(|&var| {
+        std::env::var(var)
+            .ok()
+            .filter(usable)
+            .map(|token| (token, TokenSource::Env(var)))
+    });
+    if from_env.is_some() {
+        return from_env;
+    }
+    if let Some(token) = forge.config_token(settings).filter(usable) {
+        return Some((token, TokenSource::Config));
+    }
+
+    match stored_token(forge, settings) {
+        Ok(token) => token
+            .filter(usable)
+            .map(|token| (token, TokenSource::Keychain)),
+        Err(e) => {
+            tracing::debug!("{}", e);
+            None
+        }
+    }
+}
+
+pub fn stored_token(forge: Forge, settings: &Settings) -> Result<Option<String>> {
+    match forge.entry(settings)?.get_password() {
+        Ok(token) => Ok(Some(token)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(keychain_error(e)),
+    }
+}
+
+pub fn store_token(forge: Forge, settings: &Settings, token: &str) -> Result<()> {
+    forge
+        .entry(settings)?
+        .set_password(token)
+        .map_err(keychain_error)
+}
+
+/// Remove the stored token; false if there was none
+pub fn delete_token(forge: Forge, settings: &Settings) -> Result<bool> {
+    match forge.entry(settings)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keychain_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForgeSettings;
+
+    #[test]
+    fn test_keychain_account() {
+        let settings = Settings {
+            gitlab: ForgeSettings {
+                token: None,
+                url: Some("https://git.example.com/".to_string()),
+            },
+            ..Settings::default()
+        };
+        let no_env = |_: &str| None;
+        assert_eq!(Forge::Github.account_with(&settings, no_env), "github.com");
+        assert_eq!(
+            Forge::Gitlab.account_with(&settings, no_env),
+            "git.example.com"
+        );
+        assert_eq!(
+            Forge::Gitlab.account_with(&Settings::default(), no_env),
+            "gitlab.com"
+        );
+
+        let env = |var: &str| (var == "GITLAB_URL").then(|| "https://gl.corp:8443/".to_string());
+        assert_eq!(Forge::Gitlab.account_with(&settings, env), "gl.corp:8443");
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use ssh_key::PublicKey;
+
+use super::auth::{self, Forge};
+use super::http::ForgeHttp;
+use crate::config::Settings;
+use crate::error::{Result, SkmError};
+
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+pub const GITHUB_URL: &str = "https://github.com";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GithubKey {
+    pub id: u64,
+    pub title: String,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GithubUser {
+    login: String,
+}
+
+/// Members listed per page; GitHub's maximum
+const PER_PAGE: u32 = 100;
+
+pub struct GithubClient {
+    http: ForgeHttp,
+    api_url: String,
+    web_url: String,
+}
+
+impl GithubClient {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            http: ForgeHttp::new(Forge::Github, token),
+            api_url: GITHUB_API_URL.to_string(),
+            web_url: GITHUB_URL.to_string(),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(auth::resolve_token(Forge::Github, settings).map(|(token, _)| token))
+    }
+
+    /// Login of the account the token belongs to
+    pub fn authenticated_user(&self) -> Result<String> {
+        let url = format!("{}/user", self.api_url);
+        let request = self.http.authorize(self.http.get(&url))?;
+        let user: GithubUser = self
+            .http
+            .send(&request, None)?
+            .into_json()
+            .map_err(|e| SkmError::Remote(format!("Unexpected GitHub response: {}", e)))?;
+        Ok(user.login)
+    }
+
+    /// Upload a public key to the authenticated user's account
+    pub fn add_key(&self, title: &str, public_key: &str) -> Result<GithubKey> {
+        let url = format!("{}/user/keys", self.api_url);
+        let request = self.http.authorize(self.http.post(&url))?;
+        let body = serde_json::json!({ "title": title, "key": public_key });
+
+        self.http
+            .send(&request, Some(&body))?
+            .into_json()
+            .map_err(|e| SkmError::Remote(format!("Unexpected GitHub response: {}", e)))
+    }
+
+    /// Logins of an organization's members, or of one team when `team` is set.
+    /// Private members and teams are only visible with a `read:org` token.
+    pub fn org_members(&self, org: &str, team: Option<&str>) -> Result<Vec<String>> {
+        validate_username(org)?;
+
+        let url = match team {
+            Some(team) => {
+                validate_team(team)?;
+                format!(
+                    "{}/orgs/{}/teams/{}/members?per_page={}",
+                    self.api_url, org, team, PER_PAGE
+                )
+            }
+            None => format!(
+                "{}/orgs/{}/members?per_page={}",
+                self.api_url, org, PER_PAGE
+            ),
+        };
+
+        let members: Vec<GithubUser> = self.http.get_pages(&url)?;
+        Ok(members.into_iter().map(|member| member.login).collect())
+    }
+
+    /// Public keys published at `https://github.com/<user>.keys`
+    pub fn user_keys(&self, user: &str) -> Result<Vec<PublicKey>> {
+        validate_username(user)?;
+
+        let url = format!("{}/{}.keys", self.web_url, user);
+        let body = self.http.send(&self.http.get(&url), None)?.into_string()?;
+
+        Ok(parse_keys(&body, &format!("github:{}", user)))
+    }
+}
+
+fn validate_username(user: &str) -> Result<()> {
+    let valid = !user.is_empty()
+        && !user.starts_with('-')
+        && user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(SkmError::Config(format!(
+            "Invalid GitHub username: '{}'",
+            user
+        )))
+    }
+}
+
+/// Team slugs are lowercase names with `-` and `_`
+fn validate_team(team: &str) -> Result<()> {
+    let valid = !team.is_empty()
+        && !team.starts_with('-')
+        && team
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(SkmError::Config(format!("Invalid GitHub team: '{}'", team)))
+    }
+}
+
+/// Parse one key per line, tagging each with `comment`; bad lines are skipped
+fn parse_keys(body: &str, comment: &str) -> Vec<PublicKey> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| PublicKey::from_openssh(line.trim()).ok())
+        .map(|mut key| {
+            key.set_comment(comment);
+            key
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_username() {
+        assert!(validate_username("octo-cat42").is_ok());
+        assert!(validate_username("").is_err());
+        assert!(validate_username("-octo").is_err());
+        assert!(validate_username("../user").is_err());
+        assert!(validate_team("infra_team-2").is_ok());
+        assert!(validate_team("infra/../x").is_err());
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let body = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X+rEWi9T9XCg49jAvfUVQGsYRWc5i5mJ1N/mlGAlL\n\
+                    not a key\n\n";
+        let keys = parse_keys(body, "github:octocat");
+
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].comment(), "github:octocat");
+    }
+}
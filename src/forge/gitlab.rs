@@ -0,0 +1,112 @@
+use serde::Deserialize;
+
+use super::auth::{self, Forge};
+use super::http::ForgeHttp;
+use crate::config::Settings;
+use crate::error::{Result, SkmError};
+
+pub const GITLAB_URL: &str = "https://gitlab.com";
+
+const URL_ENV_VAR: &str = "GITLAB_URL";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitlabKey {
+    pub id: u64,
+    pub title: String,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitlabUser {
+    username: String,
+}
+
+pub struct GitlabClient {
+    http: ForgeHttp,
+    base_url: String,
+}
+
+/// gitlab.com unless a self-hosted URL is set via GITLAB_URL or gitlab.url
+pub fn gitlab_url(settings: &Settings) -> String {
+    gitlab_url_with(settings, |var| std::env::var(var).ok())
+}
+
+/// `gitlab_url` with environment variables looked up through `env`
+pub(crate) fn gitlab_url_with(settings: &Settings, env: impl Fn(&str) -> Option<String>) -> String {
+    env(URL_ENV_VAR)
+        .or_else(|| settings.gitlab.url.clone())
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| GITLAB_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+impl GitlabClient {
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        Self {
+            http: ForgeHttp::new(Forge::Gitlab, token),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        let token = auth::resolve_token(Forge::Gitlab, settings).map(|(token, _)| token);
+        Self::new(&gitlab_url(settings), token)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Username of the account the token belongs to
+    pub fn authenticated_user(&self) -> Result<String> {
+        let url = format!("{}/api/v4/user", self.base_url);
+        let request = self.http.authorize(self.http.get(&url))?;
+        let user: GitlabUser = self
+            .http
+            .send(&request, None)?
+            .into_json()
+            .map_err(|e| SkmError::Remote(format!("Unexpected GitLab response: {}", e)))?;
+        Ok(user.username)
+    }
+
+    /// Register a public key on the account owning the personal access token
+    pub fn add_key(&self, title: &str, public_key: &str) -> Result<GitlabKey> {
+        let url = format!("{}/api/v4/user/keys", self.base_url);
+        let request = self.http.authorize(self.http.post(&url))?;
+        let body = serde_json::json!({ "title": title, "key": public_key });
+
+        self.http
+            .send(&request, Some(&body))?
+            .into_json()
+            .map_err(|e| SkmError::Remote(format!("Unexpected GitLab response: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForgeSettings;
+
+    #[test]
+    fn test_self_hosted_url_from_settings() {
+        let settings = Settings {
+            gitlab: ForgeSettings {
+                token: Some("glpat-test".to_string()),
+                url: Some("https://git.example.com/".to_string()),
+            },
+            ..Settings::default()
+        };
+
+        let no_env = |_: &str| None;
+        assert_eq!(
+            gitlab_url_with(&settings, no_env),
+            "https://git.example.com"
+        );
+        assert_eq!(gitlab_url_with(&Settings::default(), no_env), GITLAB_URL);
+
+        // GITLAB_URL wins over the settings
+        let env = |var: &str| (var == URL_ENV_VAR).then(|| "https://gl.corp/".to_string());
+        assert_eq!(gitlab_url_with(&settings, env), "https://gl.corp");
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+
+use super::auth::Forge;
+use crate::error::{Result, SkmError};
+
+const USER_AGENT: &str = concat!("skm/", env!("CARGO_PKG_VERSION"));
+
+/// Rate limits that reset sooner than this are waited out; longer ones fail
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// How often one request is retried after hitting a rate limit
+const MAX_RATE_LIMIT_RETRIES: u32 = 2;
+
+/// GitHub's advice for secondary rate limits that don't say when to retry
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// HTTP access shared by the forge clients: agent settings, the auth header
+/// and rate-limit handling live here so the clients only build requests
+pub struct ForgeHttp {
+    agent: ureq::Agent,
+    forge: Forge,
+    token: Option<String>,
+}
+
+impl ForgeHttp {
+    pub fn new(forge: Forge, token: Option<String>) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .user_agent(USER_AGENT)
+            .build();
+
+        Self {
+            agent,
+            forge,
+            token,
+        }
+    }
+
+    pub fn get(&self, url: &str) -> ureq::Request {
+        self.agent.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> ureq::Request {
+        self.agent.post(url)
+    }
+
+    /// Add the token the way the forge expects it, failing early without one
+    pub fn authorize(&self, request: ureq::Request) -> Result<ureq::Request> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| self.forge.missing_token())?;
+
+        Ok(match self.forge {
+            Forge::Github => request
+                .set("Authorization", &format!("Bearer {}", token))
+                .set("Accept", "application/vnd.github+json")
+                .set("X-GitHub-Api-Version", "2022-11-28"),
+            Forge::Gitlab => request.set("PRIVATE-TOKEN", token),
+        })
+    }
+
+    /// Send a request, with an optional JSON body, waiting out short rate limits
+    pub fn send(
+        &self,
+        request: &ureq::Request,
+        body: Option<&serde_json::Value>,
+    ) -> Result<ureq::Response> {
+        let mut attempt = 0;
+        loop {
+            let result = match body {
+                Some(body) => request.clone().send_json(body),
+                None => request.clone().call(),
+            };
+            let response = match result {
+                Err(ureq::Error::Status(code, response)) if is_rate_limited(code, &response) => {
+                    response
+                }
+                other => return other.map_err(|e| self.error(e)),
+            };
+
+            let wait = rate_limit_wait(|name| response.header(name), Utc::now());
+            if attempt == MAX_RATE_LIMIT_RETRIES || wait > MAX_RATE_LIMIT_WAIT {
+                let retry_at = Local::now() + chrono::Duration::from_std(wait).unwrap_or_default();
+                return Err(SkmError::Remote(format!(
+                    "{} rate limit reached; try again after {}",
+                    self.forge.name(),
+                    retry_at.format("%H:%M:%S")
+                )));
+            }
+
+            tracing::info!(
+                "{} rate limit reached, retrying in {}s",
+                self.forge.name(),
+                wait.as_secs()
+            );
+            std::thread::sleep(wait);
+            attempt += 1;
+        }
+    }
+
+    /// GET every page of an authorized list endpoint, following `Link: rel="next"`
+    pub fn get_pages<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = Some(url.to_string());
+
+        while let Some(url) = next.take() {
+            let response = self.send(&self.authorize(self.get(&url))?, None)?;
+            next = response.header("link").and_then(next_link);
+            let page: Vec<T> = response.into_json().map_err(|e| {
+                SkmError::Remote(format!("Unexpected {} response: {}", self.forge.name(), e))
+            })?;
+            items.extend(page);
+        }
+
+        Ok(items)
+    }
+
+    /// Turn a ureq error into a readable message, using the API's `message` field when present
+    fn error(&self, err: ureq::Error) -> SkmError {
+        let service = self.forge.name();
+        match err {
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message = serde_json::from_str::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|json| {
+                        json.get("message")
+                            .and_then(|m| m.as_str())
+                            .map(String::from)
+                    })
+                    .unwrap_or(body);
+                SkmError::Remote(format!("{} returned {}: {}", service, code, message.trim()))
+            }
+            ureq::Error::Transport(transport) => {
+                SkmError::Remote(format!("Could not reach {}: {}", service, transport))
+            }
+        }
+    }
+}
+
+/// 429, or GitHub's 403 with an exhausted quota
+fn is_rate_limited(code: u16, response: &ureq::Response) -> bool {
+    code == 429
+        || (code == 403
+            && (response.header("x-ratelimit-remaining") == Some("0")
+                || response.has("retry-after")))
+}
+
+/// URL of the next page in a `Link` header, if there is one
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+/// How long to wait before retrying: `Retry-After` seconds, else the
+/// GitHub (`x-ratelimit-reset`) or GitLab (`ratelimit-reset`) reset time
+fn rate_limit_wait<'a>(header: impl Fn(&str) -> Option<&'a str>, now: DateTime<Utc>) -> Duration {
+    if let Some(secs) = header("retry-after").and_then(|v| v.trim().parse::<u64>().ok()) {
+        return Duration::from_secs(secs);
+    }
+
+    ["x-ratelimit-reset", "ratelimit-reset"]
+        .iter()
+        .filter_map(|name| header(name)?.trim().parse::<i64>().ok())
+        .filter_map(|reset| Utc.timestamp_opt(reset, 0).single())
+        .map(|reset| (reset - now).to_std().unwrap_or_default() + Duration::from_secs(1))
+        .next()
+        .unwrap_or(DEFAULT_RATE_LIMIT_WAIT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let headers = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+        };
+
+        assert_eq!(
+            rate_limit_wait(headers(&[("retry-after", "7")]), now),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            rate_limit_wait(headers(&[("x-ratelimit-reset", "1700000030")]), now),
+            Duration::from_secs(31)
+        );
+        assert_eq!(
+            rate_limit_wait(headers(&[("ratelimit-reset", "1699999990")]), now),
+            Duration::from_secs(1)
+        );
+        assert_eq!(rate_limit_wait(headers(&[]), now), DEFAULT_RATE_LIMIT_WAIT);
+    }
+
+    #[test]
+    fn test_next_link() {
+        let header = "<https://api.github.com/orgs/o/members?page=2>; rel=\"next\", \
+                      <https://api.github.com/orgs/o/members?page=5>; rel=\"last\"";
+        assert_eq!(
+            next_link(header).as_deref(),
+            Some("https://api.github.com/orgs/o/members?page=2")
+        );
+        assert_eq!(
+            next_link("<https://api.github.com/orgs/o/members?page=1>; rel=\"prev\""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        let response = |raw: &str| raw.parse::<ureq::Response>().unwrap();
+
+        assert!(is_rate_limited(
+            429,
+            &response("HTTP/1.1 429 Too Many Requests\r\n\r\n")
+        ));
+        assert!(is_rate_limited(
+            403,
+            &response("HTTP/1.1 403 Forbidden\r\nx-ratelimit-remaining: 0\r\n\r\n")
+        ));
+        assert!(!is_rate_limited(
+            403,
+            &response("HTTP/1.1 403 Forbidden\r\nx-ratelimit-remaining: 12\r\n\r\n")
+        ));
+    }
+}
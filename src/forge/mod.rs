@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod github;
+pub mod gitlab;
+pub mod http;
+
+pub use auth::{Forge, TokenSource};
+pub use github::GithubClient;
+pub use gitlab::GitlabClient;
@@ -1,8 +1,14 @@
 pub mod cli;
 pub mod config;
 pub mod crypto;
+pub mod dates;
+pub mod doctor;
 pub mod error;
+pub mod forge;
+pub mod logging;
+pub mod migrate;
 pub mod ssh;
+pub mod terminal;
 pub mod tui;
 
 pub use config::Config;
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+use crate::error::{Result, SkmError};
+
+/// Number of log lines kept in memory for the TUI debug overlay
+pub const LOG_BUFFER_LINES: usize = 200;
+
+/// Log file written while the TUI owns the terminal
+pub const LOG_FILE_NAME: &str = "skm.log";
+
+/// Ring buffer of recent formatted log lines, shared with the TUI
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, line: &str) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    /// Oldest first
+    pub fn lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines.iter().cloned().collect()
+    }
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(LOG_BUFFER_LINES)
+    }
+}
+
+pub struct LogBufferWriter {
+    buffer: LogBuffer,
+}
+
+impl io::Write for LogBufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            self.buffer.push(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBufferWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogBufferWriter {
+            buffer: self.clone(),
+        }
+    }
+}
+
+fn level_filter(debug: bool) -> LevelFilter {
+    if debug {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    }
+}
+
+/// CLI mode: logs go to stderr so they never mix with command output
+pub fn init_cli(debug: bool) -> Result<()> {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level_filter(debug))
+        .with_writer(io::stderr)
+        .with_target(false)
+        .with_thread_ids(false)
+        .with_file(false)
+        .with_line_number(false)
+        .finish();
+
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| SkmError::Unknown(e.to_string()))
+}
+
+/// TUI mode: nothing may touch the terminal, so logs go to `<log_dir>/skm.log`
+/// and to the returned buffer, which backs the debug overlay
+pub fn init_tui(debug: bool, log_dir: &Path) -> Result<LogBuffer> {
+    let buffer = LogBuffer::default();
+
+    // The overlay still works if the log file can't be opened
+    let file = fs::create_dir_all(log_dir).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_dir.join(LOG_FILE_NAME))
+    });
+    let file_layer = file.ok().map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_target(false)
+    });
+
+    let buffer_layer = tracing_subscriber::fmt::layer()
+        .with_writer(buffer.clone())
+        .with_ansi(false)
+        .with_target(false)
+        .without_time();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(level_filter(debug))
+        .with(file_layer)
+        .with(buffer_layer);
+
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| SkmError::Unknown(e.to_string()))?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_log_buffer_capacity() {
+        let buffer = LogBuffer::new(2);
+        buffer.push("one");
+        buffer.push("two");
+        buffer.push("three");
+
+        assert_eq!(buffer.lines(), vec!["two", "three"]);
+    }
+
+    #[test]
+    fn test_log_buffer_writer_splits_lines() {
+        let buffer = LogBuffer::new(10);
+        let mut writer = buffer.make_writer();
+        writer.write_all(b" INFO first\n\n DEBUG second\n").unwrap();
+
+        assert_eq!(buffer.lines(), vec![" INFO first", " DEBUG second"]);
+    }
+}
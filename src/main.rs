@@ -1,39 +1,44 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::io;
+use std::io::{self, IsTerminal};
 use tracing::{error, info};
 
 use ssh_key_manager::{
     Result,
-    cli::{Cli, CliExecutor},
+    cli::{BackupCommands, Cli, CliExecutor, Commands},
     config::Config,
+    crypto::schedule::AutoBackup,
+    logging::{self, LogBuffer},
     tui::{app::App, events::handle_events, ui::draw},
 };
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Setup logging
-    setup_logging(cli.debug)?;
-
     // Load configuration
-    let config = if let Some(ref ssh_dir) = cli.ssh_dir {
+    let mut config = if let Some(ref ssh_dir) = cli.ssh_dir {
         Config::from_ssh_dir(ssh_dir)?
     } else {
         Config::new()
     };
+    config.resolve_fingerprint_hash(cli.fingerprint_hash.map(|hash| hash.to_fingerprint_hash()))?;
 
     // Ensure SSH directory exists
     config.ensure_ssh_dir()?;
 
+    if may_run_auto_backup(cli.command.as_ref()) {
+        run_due_auto_backup(&config);
+    }
+
     // Check if CLI command is provided
     if let Some(command) = cli.command {
         // CLI mode
+        logging::init_cli(cli.debug)?;
         info!("Running in CLI mode");
         let executor = CliExecutor::new(config);
 
@@ -48,14 +53,64 @@ fn main() -> Result<()> {
                 std::process::exit(1);
             }
         }
+    } else if cli.no_tui {
+        Cli::command().print_help()?;
+        Ok(())
+    } else if let Some(reason) = tui_unavailable_reason() {
+        // Entering raw mode / the alternate screen would garble a dumb or piped terminal
+        eprintln!("skm: interactive mode is unavailable ({}).", reason);
+        eprintln!("Use a subcommand instead, e.g. `skm list` or `skm show <name>`.\n");
+        Cli::command().print_help()?;
+        std::process::exit(2);
     } else {
-        // TUI mode (default)
+        // TUI mode (default). Logging to the terminal would corrupt the screen.
+        let log_buffer = logging::init_tui(cli.debug, &config.export_dir)?;
         info!("Starting SSH Key Manager in TUI mode");
-        run_tui(config)
+        run_tui(config, log_buffer)
+    }
+}
+
+/// Make the scheduled backup if it's due. Problems are reported but never
+/// stop the command that was asked for.
+fn run_due_auto_backup(config: &Config) {
+    match AutoBackup::new(config).run_if_due() {
+        Ok(Some(report)) => eprintln!("skm: automatic backup: {}", report.summary()),
+        Ok(None) => {}
+        Err(e) => eprintln!("skm: automatic backup failed: {}", e),
+    }
+}
+
+/// A due automatic backup only runs alongside interactive commands: a
+/// plugin recipient may prompt, and scripts piping `pubkey`, `sign` or
+/// `verify` output must not stall or get extra output. `backup auto` reports
+/// on its own runs.
+fn may_run_auto_backup(command: Option<&Commands>) -> bool {
+    let scripted = matches!(
+        command,
+        Some(
+            Commands::Backup {
+                command: BackupCommands::Auto { .. }
+            } | Commands::Pubkey { .. }
+                | Commands::Sign { .. }
+                | Commands::Verify { .. }
+        )
+    );
+    !scripted && io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+fn tui_unavailable_reason() -> Option<&'static str> {
+    if std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        Some("TERM=dumb")
+    } else if !io::stdin().is_terminal() {
+        Some("stdin is not a terminal")
+    } else if !io::stdout().is_terminal() {
+        Some("stdout is not a terminal")
+    } else {
+        None
     }
 }
 
-fn run_tui(config: Config) -> Result<()> {
+fn run_tui(config: Config, log_buffer: LogBuffer) -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -65,6 +120,7 @@ fn run_tui(config: Config) -> Result<()> {
 
     // Create app state
     let mut app = App::new(config)?;
+    app.log_buffer = log_buffer;
 
     // Main event loop
     let result = run_app(&mut terminal, &mut app);
@@ -95,15 +151,29 @@ fn run_tui(config: Config) -> Result<()> {
 fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     let mut last_tick = std::time::Instant::now();
     let tick_rate = std::time::Duration::from_millis(250);
+    let job_tick_rate = std::time::Duration::from_millis(100);
 
     loop {
+        // Pick up the result of a finished background job
+        app.poll_job()?;
+        // Quit was pressed while it ran
+        if app.should_quit() {
+            break;
+        }
+        // Rescan if keys were added or removed outside skm
+        app.poll_watcher();
+
         // Draw UI
         terminal.draw(|f| draw(f, app))?;
 
         // Handle events
-        let timeout = tick_rate
+        let mut timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| std::time::Duration::from_secs(0));
+        // Redraw often enough to keep a job's spinner moving
+        if app.job.is_some() {
+            timeout = timeout.min(job_tick_rate);
+        }
 
         if crossterm::event::poll(timeout)? && handle_events(app)? && app.should_quit() {
             break;
@@ -111,30 +181,11 @@ fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut A
 
         // Handle tick events
         if last_tick.elapsed() >= tick_rate {
+            app.check_idle_lock();
+            app.expire_toast();
             last_tick = std::time::Instant::now();
         }
     }
 
     Ok(())
 }
-
-fn setup_logging(debug: bool) -> Result<()> {
-    let level = if debug {
-        tracing::Level::DEBUG
-    } else {
-        tracing::Level::INFO
-    };
-
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(level)
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_file(false)
-        .with_line_number(false)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber)
-        .map_err(|e| ssh_key_manager::SkmError::Unknown(e.to_string()))?;
-
-    Ok(())
-}
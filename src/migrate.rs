@@ -0,0 +1,407 @@
+//! Format versions of the files skm keeps in `~/.skm`, and the migrations that
+//! carry older files forward.
+//!
+//! A file is migrated the first time it is read by an skm that knows a newer
+//! format. The old file is kept next to it as `<name>.v<version>.bak`, so a
+//! downgrade can always go back to it. Files from a newer skm are refused
+//! rather than misread.
+//!
+//! Changing a format means appending a migration to the list for that kind of
+//! file; the current version is the length of the list plus one.
+//!
+//! Most JSON stores are lists of records, read and changed through
+//! [`RecordStore`].
+
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::error::{Result, SkmError};
+
+/// A store lock older than this was left by an skm that died, and is taken
+/// over; a read-modify-write takes milliseconds
+const STALE_STORE_LOCK_SECS: u64 = 10;
+
+/// How often a held store lock is tried again
+const STORE_LOCK_POLL: Duration = Duration::from_millis(20);
+
+/// One upgrade step; entry `i` of a list turns version `i + 1` into `i + 2`
+pub type Migration<T> = fn(T) -> Result<T>;
+
+/// `config.toml`. Settings files are written by hand, so a missing `version`
+/// key means version 1 rather than asking people to add one.
+pub const CONFIG_MIGRATIONS: &[Migration<String>] = &[];
+
+/// JSON stores: deployments, provenance, ephemeral grants, automatic backup runs
+/// and snapshots
+pub const STORE_MIGRATIONS: &[Migration<Value>] = &[
+    // 1 -> 2: wrap the bare array or object in `{"version", "data"}`
+    |value| Ok(json!({ "version": 2, "data": value })),
+];
+
+pub const fn current_version<T>(migrations: &[Migration<T>]) -> u32 {
+    migrations.len() as u32 + 1
+}
+
+/// Run the migrations from `version` up to the current one. `None` when the
+/// content is already current; an error when it comes from a newer skm.
+pub fn upgrade<T>(
+    path: &Path,
+    version: u32,
+    migrations: &[Migration<T>],
+    mut content: T,
+) -> Result<Option<T>> {
+    let current = current_version(migrations);
+    if version == 0 || version > current {
+        return Err(SkmError::Config(format!(
+            "{} has format version {}, but this skm only reads up to version {}; upgrade skm",
+            path.display(),
+            version,
+            current
+        )));
+    }
+    if version == current {
+        return Ok(None);
+    }
+
+    for migration in &migrations[version as usize - 1..] {
+        content = migration(content)?;
+    }
+    Ok(Some(content))
+}
+
+/// Copy a file about to be migrated to `<name>.v<version>.bak`
+pub fn backup(path: &Path, version: u32) -> Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    let backup = path.with_file_name(name);
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Read `config.toml`, migrating it in place if it is in an older format
+pub fn read_config(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)?;
+    let table: toml::Table = toml::from_str(&content)
+        .map_err(|e| SkmError::Config(format!("Invalid {}: {}", path.display(), e)))?;
+    let version = match table.get("version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) => u32::try_from(*version).unwrap_or(0),
+        Some(_) => {
+            return Err(SkmError::Config(format!(
+                "Invalid {}: version must be a number",
+                path.display()
+            )));
+        }
+    };
+
+    match upgrade(path, version, CONFIG_MIGRATIONS, content.clone())? {
+        Some(migrated) => {
+            let backup = backup(path, version)?;
+            fs::write(path, &migrated)?;
+            tracing::info!(
+                "Migrated {} to format version {} (old file: {})",
+                path.display(),
+                current_version(CONFIG_MIGRATIONS),
+                backup.display()
+            );
+            Ok(migrated)
+        }
+        None => Ok(content),
+    }
+}
+
+/// Read a JSON store, migrating it in place if it is in an older format
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let version = store_version(&value);
+
+    let value = match upgrade(path, version, STORE_MIGRATIONS, value.clone())? {
+        Some(migrated) => {
+            let backup = backup(path, version)?;
+            fs::write(path, serde_json::to_string_pretty(&migrated)?)?;
+            tracing::info!(
+                "Migrated {} to format version {} (old file: {})",
+                path.display(),
+                current_version(STORE_MIGRATIONS),
+                backup.display()
+            );
+            migrated
+        }
+        None => value,
+    };
+
+    Ok(serde_json::from_value(
+        value.get("data").cloned().unwrap_or_default(),
+    )?)
+}
+
+/// Write a JSON store in the current format
+pub fn save_json<T: Serialize>(path: &Path, data: &T) -> Result<()> {
+    let envelope = json!({
+        "version": current_version(STORE_MIGRATIONS),
+        "data": data,
+    });
+    fs::write(path, serde_json::to_string_pretty(&envelope)?)?;
+    Ok(())
+}
+
+/// A JSON store holding a list of records, such as `deployments.json`.
+/// Changes go through [`RecordStore::update`], which holds `<file>.lock`
+/// from reading the records to writing them back, so two skm processes
+/// changing the store at once don't drop each other's records.
+pub struct RecordStore<T> {
+    path: PathBuf,
+    records: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned + PartialEq + Clone> RecordStore<T> {
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            records: PhantomData,
+        }
+    }
+
+    /// Every record; none before the store is first written
+    pub fn load(&self) -> Result<Vec<T>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        load_json(&self.path)
+    }
+
+    /// Replace every record
+    pub fn save(&self, records: &[T]) -> Result<()> {
+        let _lock = self.lock()?;
+        save_json(&self.path, &records)
+    }
+
+    /// Change the records under the lock. They are only written back if
+    /// `change` changed them.
+    pub fn update<R>(&self, change: impl FnOnce(&mut Vec<T>) -> R) -> Result<R> {
+        let _lock = self.lock()?;
+        let before = self.load()?;
+        let mut records = before.clone();
+        let result = change(&mut records);
+        if records != before {
+            save_json(&self.path, &records)?;
+        }
+        Ok(result)
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        StoreLock::take(self.path.with_file_name(name))
+    }
+}
+
+/// Lock file held while a [`RecordStore`] is changed; removed on drop
+struct StoreLock {
+    path: PathBuf,
+}
+
+impl StoreLock {
+    /// Wait for the lock, taking it over once it is stale
+    fn take(path: PathBuf) -> Result<Self> {
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = match fs::metadata(&path).and_then(|m| m.modified()) {
+                        Ok(modified) => modified.elapsed().unwrap_or_default(),
+                        // Released in the meantime
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                        Err(e) => return Err(e.into()),
+                    };
+                    if age.as_secs() < STALE_STORE_LOCK_SECS {
+                        thread::sleep(STORE_LOCK_POLL);
+                        continue;
+                    }
+                    tracing::warn!("Taking over a stale lock, {}", path.display());
+                    match fs::remove_file(&path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            return Err(e.into());
+                        }
+                        _ => {}
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Version 1 stores are the bare data, without an envelope
+fn store_version(value: &Value) -> u32 {
+    match value {
+        Value::Object(object) if object.contains_key("data") => object
+            .get("version")
+            .and_then(Value::as_u64)
+            .map_or(0, |version| u32::try_from(version).unwrap_or(0)),
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_upgrade() {
+        let path = Path::new("config.toml");
+        let migrations: &[Migration<String>] = &[
+            |content| Ok(format!("{}a", content)),
+            |content| Ok(format!("{}b", content)),
+        ];
+
+        assert_eq!(current_version(migrations), 3);
+        assert_eq!(
+            upgrade(path, 1, migrations, String::new()).unwrap(),
+            Some("ab".to_string())
+        );
+        assert_eq!(
+            upgrade(path, 2, migrations, String::new()).unwrap(),
+            Some("b".to_string())
+        );
+        assert_eq!(upgrade(path, 3, migrations, String::new()).unwrap(), None);
+
+        let err = upgrade(path, 4, migrations, String::new()).unwrap_err();
+        assert!(err.to_string().contains("upgrade skm"));
+        assert!(upgrade(path, 0, migrations, String::new()).is_err());
+    }
+
+    #[test]
+    fn test_store_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("deployments.json");
+
+        // A store written before formats were versioned
+        fs::write(&path, r#"["a", "b"]"#).unwrap();
+        let data: Vec<String> = load_json(&path).unwrap();
+        assert_eq!(data, vec!["a", "b"]);
+
+        let backup = temp_dir.path().join("deployments.json.v1.bak");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), r#"["a", "b"]"#);
+        let migrated: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(migrated["version"], 2);
+
+        // Current files load as they are
+        save_json(&path, &vec!["c"]).unwrap();
+        let data: Vec<String> = load_json(&path).unwrap();
+        assert_eq!(data, vec!["c"]);
+
+        fs::write(&path, r#"{"version": 99, "data": []}"#).unwrap();
+        assert!(load_json::<Vec<String>>(&path).is_err());
+    }
+
+    #[test]
+    fn test_record_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let store: RecordStore<String> = RecordStore::at(temp_dir.path().join("records.json"));
+        let path = temp_dir.path().join("records.json");
+        let lock = temp_dir.path().join("records.json.lock");
+
+        assert!(store.load().unwrap().is_empty());
+        // Nothing changed, so nothing is written
+        assert_eq!(store.update(|records| records.len()).unwrap(), 0);
+        assert!(!path.exists());
+
+        store
+            .update(|records| records.push("a".to_string()))
+            .unwrap();
+        store
+            .update(|records| records.push("b".to_string()))
+            .unwrap();
+        assert_eq!(store.load().unwrap(), vec!["a", "b"]);
+        assert!(!lock.exists());
+
+        store.save(&["c".to_string()]).unwrap();
+        assert_eq!(store.load().unwrap(), vec!["c"]);
+
+        // A lock left behind by an skm that died is taken over
+        fs::write(&lock, "").unwrap();
+        let stale = std::time::SystemTime::now() - Duration::from_secs(STALE_STORE_LOCK_SECS + 1);
+        fs::File::options()
+            .write(true)
+            .open(&lock)
+            .unwrap()
+            .set_modified(stale)
+            .unwrap();
+        store.update(|records| records.clear()).unwrap();
+        assert!(store.load().unwrap().is_empty());
+        assert!(!lock.exists());
+    }
+
+    #[test]
+    fn test_record_store_updates_dont_interleave() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("records.json");
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let store: RecordStore<u32> = RecordStore::at(path);
+                    for n in 0..10 {
+                        store
+                            .update(|records| records.push(writer * 10 + n))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let mut records = RecordStore::<u32>::at(path).load().unwrap();
+        records.sort_unstable();
+        assert_eq!(records, (0..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_config_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+
+        fs::write(&path, "[tui]\nidle_lock_minutes = 5\n").unwrap();
+        assert!(read_config(&path).unwrap().contains("idle_lock_minutes"));
+        fs::write(&path, "version = 1\n").unwrap();
+        assert!(read_config(&path).is_ok());
+
+        fs::write(&path, "version = 2\n").unwrap();
+        assert!(
+            read_config(&path)
+                .unwrap_err()
+                .to_string()
+                .contains("upgrade skm")
+        );
+        fs::write(&path, "version = \"one\"\n").unwrap();
+        assert!(read_config(&path).is_err());
+        // Nothing to migrate, so nothing was backed up
+        assert!(!temp_dir.path().join("config.toml.v1.bak").exists());
+    }
+}
@@ -0,0 +1,518 @@
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use ssh_encoding::Encode;
+use ssh_key::public::KeyData;
+use ssh_key::{HashAlg, PrivateKey, PublicKey};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SkmError};
+use crate::migrate::RecordStore;
+
+// Message numbers from draft-miller-ssh-agent
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_ADD_IDENTITY: u8 = 17;
+const SSH_AGENTC_REMOVE_IDENTITY: u8 = 18;
+const SSH_AGENTC_REMOVE_ALL_IDENTITIES: u8 = 19;
+const SSH_AGENTC_ADD_ID_CONSTRAINED: u8 = 25;
+
+const SSH_AGENT_CONSTRAIN_LIFETIME: u8 = 1;
+const SSH_AGENT_CONSTRAIN_CONFIRM: u8 = 2;
+
+/// Upper bound for a single agent reply, guards against a misbehaving socket
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// Where lifetimes of keys skm loaded are recorded, relative to the skm data directory
+pub const LIFETIMES_FILE: &str = "agent-lifetimes.json";
+
+trait AgentStream: Read + Write {}
+impl<T: Read + Write> AgentStream for T {}
+
+/// A key currently loaded in the agent
+#[derive(Debug, Clone)]
+pub struct AgentIdentity {
+    pub public_key: PublicKey,
+    pub comment: String,
+}
+
+impl AgentIdentity {
+    pub fn key_data(&self) -> &KeyData {
+        self.public_key.key_data()
+    }
+}
+
+/// Where the agent behind `SSH_AUTH_SOCK` runs: on this machine, or on the
+/// client of the ssh session this shell came in on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentOrigin {
+    NoAgent,
+    Local {
+        socket: PathBuf,
+    },
+    Forwarded {
+        socket: PathBuf,
+        /// Client address from `SSH_CONNECTION`
+        client: Option<String>,
+    },
+}
+
+impl AgentOrigin {
+    pub fn detect() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// sshd puts forwarded agents at `$TMPDIR/ssh-XXXXXXXXXX/agent.<pid>`,
+    /// `<pid>` being its own process. ssh-agent uses the same layout, so on
+    /// Linux the process is looked up; elsewhere the layout alone decides.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let Some(socket) = var("SSH_AUTH_SOCK").filter(|s| !s.is_empty()) else {
+            return AgentOrigin::NoAgent;
+        };
+        let socket = PathBuf::from(socket);
+        let Some(connection) = var("SSH_CONNECTION").or_else(|| var("SSH_CLIENT")) else {
+            return AgentOrigin::Local { socket };
+        };
+
+        let pid = socket
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("agent."))
+            .filter(|_| {
+                socket
+                    .parent()
+                    .and_then(|dir| dir.file_name()?.to_str())
+                    .is_some_and(|dir| dir.starts_with("ssh-"))
+            })
+            .and_then(|pid| pid.parse::<u32>().ok());
+        let forwarded = pid.is_some_and(|pid| is_sshd(pid).unwrap_or(true));
+
+        if forwarded {
+            let client = connection.split_whitespace().next().map(String::from);
+            AgentOrigin::Forwarded { socket, client }
+        } else {
+            AgentOrigin::Local { socket }
+        }
+    }
+}
+
+/// Whether `pid` is an sshd process; `None` where that can't be told
+fn is_sshd(pid: u32) -> Option<bool> {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    // OpenSSH 9.8 moved sessions into sshd-session
+    Some(matches!(comm.trim(), "sshd" | "sshd-session"))
+}
+
+/// Minimal client for the ssh-agent protocol
+pub struct AgentClient {
+    stream: Box<dyn AgentStream>,
+}
+
+impl AgentClient {
+    /// Connect to the agent referenced by `SSH_AUTH_SOCK`
+    pub fn connect_env() -> Result<Self> {
+        let path = std::env::var_os("SSH_AUTH_SOCK")
+            .ok_or_else(|| SkmError::Agent("SSH_AUTH_SOCK is not set".to_string()))?;
+        Self::connect(path)
+    }
+
+    #[cfg(unix)]
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path.as_ref()).map_err(|e| {
+            SkmError::Agent(format!(
+                "Cannot connect to {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Ok(Self::from_stream(stream))
+    }
+
+    /// On Windows the OpenSSH agent listens on a named pipe, which opens like a file
+    #[cfg(not(unix))]
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let stream = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+            .map_err(|e| {
+                SkmError::Agent(format!(
+                    "Cannot connect to {}: {}",
+                    path.as_ref().display(),
+                    e
+                ))
+            })?;
+        Ok(Self::from_stream(stream))
+    }
+
+    fn from_stream<S: Read + Write + 'static>(stream: S) -> Self {
+        Self {
+            stream: Box::new(stream),
+        }
+    }
+
+    pub fn list_identities(&mut self) -> Result<Vec<AgentIdentity>> {
+        let reply = self.request(&[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let (&msg_type, mut body) = reply
+            .split_first()
+            .ok_or_else(|| SkmError::Agent("Empty reply".to_string()))?;
+
+        if msg_type != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(SkmError::Agent(format!(
+                "Unexpected reply to identity request: {}",
+                msg_type
+            )));
+        }
+
+        let count = read_u32(&mut body)?;
+        let mut identities = Vec::new();
+        for _ in 0..count {
+            let blob = read_string(&mut body)?;
+            let comment = String::from_utf8_lossy(read_string(&mut body)?).to_string();
+
+            // Skip keys we can't decode (e.g. certificates) instead of failing the whole list
+            if let Ok(mut public_key) = PublicKey::from_bytes(blob) {
+                public_key.set_comment(comment.clone());
+                identities.push(AgentIdentity {
+                    public_key,
+                    comment,
+                });
+            }
+        }
+
+        Ok(identities)
+    }
+
+    /// Add a decrypted private key, optionally limited to `lifetime` seconds
+    /// and/or requiring confirmation on each use
+    pub fn add_identity(
+        &mut self,
+        key: &PrivateKey,
+        lifetime: Option<u32>,
+        confirm: bool,
+    ) -> Result<()> {
+        if key.is_encrypted() {
+            return Err(SkmError::Agent(
+                "Private key must be decrypted before adding it".to_string(),
+            ));
+        }
+
+        let constrained = lifetime.is_some() || confirm;
+        let mut msg = vec![if constrained {
+            SSH_AGENTC_ADD_ID_CONSTRAINED
+        } else {
+            SSH_AGENTC_ADD_IDENTITY
+        }];
+
+        key.key_data()
+            .encode(&mut msg)
+            .map_err(|e| SkmError::Agent(e.to_string()))?;
+        key.comment()
+            .encode(&mut msg)
+            .map_err(|e| SkmError::Agent(e.to_string()))?;
+
+        if let Some(seconds) = lifetime {
+            msg.push(SSH_AGENT_CONSTRAIN_LIFETIME);
+            msg.extend_from_slice(&seconds.to_be_bytes());
+        }
+        if confirm {
+            msg.push(SSH_AGENT_CONSTRAIN_CONFIRM);
+        }
+
+        self.expect_success(&msg, "add key")
+    }
+
+    pub fn remove_identity(&mut self, key_data: &KeyData) -> Result<()> {
+        let mut msg = vec![SSH_AGENTC_REMOVE_IDENTITY];
+        let mut blob = Vec::new();
+        key_data
+            .encode(&mut blob)
+            .map_err(|e| SkmError::Agent(e.to_string()))?;
+        blob.as_slice()
+            .encode(&mut msg)
+            .map_err(|e| SkmError::Agent(e.to_string()))?;
+
+        self.expect_success(&msg, "remove key")
+    }
+
+    pub fn remove_all(&mut self) -> Result<()> {
+        self.expect_success(&[SSH_AGENTC_REMOVE_ALL_IDENTITIES], "remove all keys")
+    }
+
+    fn expect_success(&mut self, msg: &[u8], action: &str) -> Result<()> {
+        let reply = self.request(msg)?;
+        match reply.first() {
+            Some(&SSH_AGENT_SUCCESS) => Ok(()),
+            Some(&SSH_AGENT_FAILURE) => {
+                Err(SkmError::Agent(format!("Agent refused to {}", action)))
+            }
+            other => Err(SkmError::Agent(format!(
+                "Unexpected reply to {}: {:?}",
+                action, other
+            ))),
+        }
+    }
+
+    fn request(&mut self, msg: &[u8]) -> Result<Vec<u8>> {
+        let len = u32::try_from(msg.len())
+            .map_err(|_| SkmError::Agent("Request too large".to_string()))?;
+        self.stream.write_all(&len.to_be_bytes())?;
+        self.stream.write_all(msg)?;
+        self.stream.flush()?;
+
+        let mut len_buf = [0u8; 4];
+        self.stream.read_exact(&mut len_buf)?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(SkmError::Agent(format!("Reply too large: {} bytes", len)));
+        }
+
+        let mut reply = vec![0u8; len];
+        self.stream.read_exact(&mut reply)?;
+        Ok(reply)
+    }
+}
+
+/// When a key skm loaded with a lifetime drops out of the agent again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentLifetime {
+    /// SHA256 fingerprint of the key
+    pub fingerprint: String,
+    pub expires_at: DateTime<Local>,
+}
+
+/// Lifetimes of the keys skm loaded. The agent protocol has no way to ask
+/// for them, so keys loaded by `ssh-add` or an earlier agent have none here.
+pub type LifetimeStore = RecordStore<AgentLifetime>;
+
+impl LifetimeStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(LIFETIMES_FILE))
+    }
+
+    /// Lifetimes that haven't run out yet
+    pub fn active(&self) -> Result<Vec<AgentLifetime>> {
+        let now = Local::now();
+        let mut lifetimes = self.load()?;
+        lifetimes.retain(|lifetime| lifetime.expires_at > now);
+        Ok(lifetimes)
+    }
+
+    /// Note that `key` was just loaded for `lifetime` seconds, or for as long
+    /// as the agent runs
+    pub fn record(&self, key: &KeyData, lifetime: Option<u32>) -> Result<()> {
+        let fingerprint = lifetime_fingerprint(key);
+        let now = Local::now();
+        self.update(|lifetimes| {
+            lifetimes.retain(|l| l.fingerprint != fingerprint && l.expires_at > now);
+            if let Some(seconds) = lifetime {
+                lifetimes.push(AgentLifetime {
+                    fingerprint,
+                    expires_at: now + Duration::seconds(seconds.into()),
+                });
+            }
+        })
+    }
+
+    /// `key` was removed from the agent
+    pub fn forget(&self, key: &KeyData) -> Result<()> {
+        self.record(key, None)
+    }
+
+    /// Every key was removed from the agent
+    pub fn clear(&self) -> Result<()> {
+        self.update(|lifetimes| lifetimes.clear())
+    }
+}
+
+/// When `key` leaves the agent, if it was loaded with a lifetime
+pub fn expires_at(lifetimes: &[AgentLifetime], key: &KeyData) -> Option<DateTime<Local>> {
+    let fingerprint = lifetime_fingerprint(key);
+    lifetimes
+        .iter()
+        .find(|lifetime| lifetime.fingerprint == fingerprint)
+        .map(|lifetime| lifetime.expires_at)
+}
+
+fn lifetime_fingerprint(key: &KeyData) -> String {
+    key.fingerprint(HashAlg::Sha256).to_string()
+}
+
+fn read_u32(buf: &mut &[u8]) -> Result<u32> {
+    if buf.len() < 4 {
+        return Err(SkmError::Agent("Truncated reply".to_string()));
+    }
+    let (head, rest) = buf.split_at(4);
+    *buf = rest;
+    Ok(u32::from_be_bytes([head[0], head[1], head[2], head[3]]))
+}
+
+fn read_string<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = read_u32(buf)? as usize;
+    if buf.len() < len {
+        return Err(SkmError::Agent("Truncated reply".to_string()));
+    }
+    let (head, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory agent connection: replies are canned, requests are captured
+    struct MockStream {
+        reply: Cursor<Vec<u8>>,
+        sent: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reply.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mock_client(reply: &[u8]) -> (AgentClient, Arc<Mutex<Vec<u8>>>) {
+        let mut framed = (reply.len() as u32).to_be_bytes().to_vec();
+        framed.extend_from_slice(reply);
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let stream = MockStream {
+            reply: Cursor::new(framed),
+            sent: sent.clone(),
+        };
+        (AgentClient::from_stream(stream), sent)
+    }
+
+    #[test]
+    fn test_agent_origin() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        // No process has this pid, so the socket layout decides
+        const SSHD_SOCKET: &str = "/tmp/ssh-XXXXabcdef/agent.4294967295";
+
+        assert_eq!(AgentOrigin::from_vars(vars(&[])), AgentOrigin::NoAgent);
+        assert_eq!(
+            AgentOrigin::from_vars(vars(&[("SSH_AUTH_SOCK", SSHD_SOCKET)])),
+            AgentOrigin::Local {
+                socket: PathBuf::from(SSHD_SOCKET)
+            }
+        );
+        assert_eq!(
+            AgentOrigin::from_vars(vars(&[
+                ("SSH_AUTH_SOCK", SSHD_SOCKET),
+                ("SSH_CONNECTION", "10.0.0.5 51234 10.0.0.1 22"),
+            ])),
+            AgentOrigin::Forwarded {
+                socket: PathBuf::from(SSHD_SOCKET),
+                client: Some("10.0.0.5".to_string()),
+            }
+        );
+        assert!(matches!(
+            AgentOrigin::from_vars(vars(&[
+                ("SSH_AUTH_SOCK", "/run/user/1000/keyring/ssh"),
+                ("SSH_CONNECTION", "10.0.0.5 51234 10.0.0.1 22"),
+            ])),
+            AgentOrigin::Local { .. }
+        ));
+    }
+
+    #[test]
+    fn test_list_identities() {
+        let key = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let blob = key.public_key().to_bytes().unwrap();
+
+        let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        reply.extend_from_slice(&1u32.to_be_bytes());
+        blob.as_slice().encode(&mut reply).unwrap();
+        "me@host".encode(&mut reply).unwrap();
+
+        let (mut client, sent) = mock_client(&reply);
+        let identities = client.list_identities().unwrap();
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![0, 0, 0, 1, SSH_AGENTC_REQUEST_IDENTITIES]
+        );
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].comment, "me@host");
+        assert_eq!(identities[0].key_data(), key.public_key().key_data());
+    }
+
+    #[test]
+    fn test_add_identity_with_lifetime() {
+        let key = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let (mut client, sent) = mock_client(&[SSH_AGENT_SUCCESS]);
+
+        client.add_identity(&key, Some(60), false).unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[4], SSH_AGENTC_ADD_ID_CONSTRAINED);
+        assert_eq!(
+            &sent[sent.len() - 5..],
+            &[SSH_AGENT_CONSTRAIN_LIFETIME, 0, 0, 0, 60]
+        );
+    }
+
+    #[test]
+    fn test_agent_failure() {
+        let (mut client, _) = mock_client(&[SSH_AGENT_FAILURE]);
+        assert!(matches!(client.remove_all(), Err(SkmError::Agent(_))));
+    }
+
+    #[test]
+    fn test_lifetime_store() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = LifetimeStore::new(temp_dir.path());
+        let a = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let b = PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519).unwrap();
+        let (a, b) = (a.public_key().key_data(), b.public_key().key_data());
+
+        assert!(store.active().unwrap().is_empty());
+        store.record(a, Some(3600)).unwrap();
+        store.record(b, Some(60)).unwrap();
+        let lifetimes = store.active().unwrap();
+        let expires = expires_at(&lifetimes, a).unwrap();
+        assert!(expires > Local::now() + Duration::minutes(59));
+        assert!(expires_at(&lifetimes, b).unwrap() < expires);
+
+        // Loading it again without a lifetime, or removing it, drops the entry
+        store.record(a, None).unwrap();
+        assert!(expires_at(&store.active().unwrap(), a).is_none());
+        store.forget(b).unwrap();
+        assert!(store.active().unwrap().is_empty());
+
+        // Run out ones are left out
+        store
+            .save(&[AgentLifetime {
+                fingerprint: lifetime_fingerprint(a),
+                expires_at: Local::now() - Duration::seconds(1),
+            }])
+            .unwrap();
+        assert!(store.active().unwrap().is_empty());
+        // and pruned by the next change
+        store.record(b, Some(60)).unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,469 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use ssh_key::{HashAlg, PublicKey, SshSig};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::known_hosts::wildcard_match;
+
+/// Where git (`gpg.ssh.allowedSignersFile`) and most guides keep it
+pub const ALLOWED_SIGNERS_FILE: &str = "allowed_signers";
+
+/// A single line of an allowed_signers file (see `ssh-keygen(1)`,
+/// "ALLOWED SIGNERS")
+#[derive(Debug, Clone, Serialize)]
+pub struct AllowedSigner {
+    /// 1-based line number in the file
+    pub line: usize,
+    /// Principal patterns, e.g. `alice@example.com` or `*@example.com`
+    pub principals: Vec<String>,
+    /// `namespaces="git,file"`; `None` allows every namespace
+    pub namespaces: Option<Vec<String>>,
+    pub cert_authority: bool,
+    pub valid_after: Option<DateTime<Local>>,
+    pub valid_before: Option<DateTime<Local>>,
+    pub key_type: String,
+    /// SHA256 fingerprint, if the key could be parsed
+    pub fingerprint: Option<String>,
+    pub comment: Option<String>,
+
+    #[serde(skip)]
+    key: Option<PublicKey>,
+}
+
+impl AllowedSigner {
+    fn parse(index: usize, line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+
+        let mut fields = split_fields(trimmed).into_iter().peekable();
+        let principals = fields.next()?.split(',').map(String::from).collect();
+        let options = fields.next_if(|f| !is_key_type(f));
+        let key_type = fields.next()?;
+        let key_data = fields.next()?;
+        let comment = Some(fields.collect::<Vec<_>>().join(" ")).filter(|c| !c.is_empty());
+
+        let mut signer = Self {
+            line: index + 1,
+            principals,
+            namespaces: None,
+            cert_authority: false,
+            valid_after: None,
+            valid_before: None,
+            key: PublicKey::from_openssh(&format!("{} {}", key_type, key_data)).ok(),
+            fingerprint: None,
+            key_type,
+            comment,
+        };
+        signer.fingerprint = signer
+            .key
+            .as_ref()
+            .map(|k| k.fingerprint(HashAlg::Sha256).to_string());
+
+        for option in options.as_deref().map(split_options).unwrap_or_default() {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_lowercase(), Some(value.trim_matches('"'))),
+                None => (option.to_lowercase(), None),
+            };
+            match (name.as_str(), value) {
+                ("cert-authority", None) => signer.cert_authority = true,
+                ("namespaces", Some(value)) => {
+                    signer.namespaces = Some(value.split(',').map(String::from).collect())
+                }
+                ("valid-after", Some(value)) => signer.valid_after = Some(parse_time(value)?),
+                ("valid-before", Some(value)) => signer.valid_before = Some(parse_time(value)?),
+                // ssh-keygen rejects lines with options it doesn't know
+                _ => return None,
+            }
+        }
+
+        Some(signer)
+    }
+
+    /// Whether this line lets `identity` sign in `namespace` at `now`
+    pub fn allows(&self, identity: Option<&str>, namespace: &str, now: DateTime<Local>) -> bool {
+        let principal_ok = identity.is_none_or(|identity| {
+            let mut matched = false;
+            for pattern in &self.principals {
+                if let Some(negated) = pattern.strip_prefix('!') {
+                    if wildcard_match(negated, identity) {
+                        return false;
+                    }
+                } else if wildcard_match(pattern, identity) {
+                    matched = true;
+                }
+            }
+            matched
+        });
+        let namespace_ok = self
+            .namespaces
+            .as_ref()
+            .is_none_or(|patterns| patterns.iter().any(|p| wildcard_match(p, namespace)));
+        let time_ok = self.valid_after.is_none_or(|after| now >= after)
+            && self.valid_before.is_none_or(|before| now < before);
+
+        principal_ok && namespace_ok && time_ok
+    }
+
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.key.as_ref()
+    }
+}
+
+/// An allowed_signers file. Edits keep comments and unrelated lines intact.
+#[derive(Debug, Clone)]
+pub struct AllowedSigners {
+    path: PathBuf,
+    lines: Vec<String>,
+    pub entries: Vec<AllowedSigner>,
+}
+
+impl AllowedSigners {
+    /// Load an allowed_signers file; a missing file is treated as empty
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+
+        Ok(Self::parse(path, &content))
+    }
+
+    fn parse(path: PathBuf, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let entries = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| AllowedSigner::parse(index, line))
+            .collect();
+
+        Self {
+            path,
+            lines,
+            entries,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Allow `principals` to sign with `key`, optionally only in `namespaces`.
+    /// Returns false when an identical entry is already there.
+    pub fn add(
+        &mut self,
+        principals: &[String],
+        key: &PublicKey,
+        namespaces: &[String],
+    ) -> Result<bool> {
+        if principals.is_empty()
+            || principals
+                .iter()
+                .any(|p| p.is_empty() || p.contains([',', ' ', '"']))
+        {
+            return Err(SkmError::Config(format!(
+                "Invalid principals '{}'",
+                principals.join(",")
+            )));
+        }
+
+        let exists = self.entries.iter().any(|e| {
+            e.key
+                .as_ref()
+                .is_some_and(|k| k.key_data() == key.key_data())
+                && !e.cert_authority
+                && principals.iter().all(|p| e.principals.contains(p))
+        });
+        if exists {
+            return Ok(false);
+        }
+
+        let mut line = principals.join(",");
+        if !namespaces.is_empty() {
+            line.push_str(&format!(" namespaces=\"{}\"", namespaces.join(",")));
+        }
+        line.push(' ');
+        line.push_str(
+            &key.to_openssh()
+                .map_err(|e| SkmError::SshKey(e.to_string()))?,
+        );
+
+        let mut lines = self.lines.clone();
+        lines.push(line);
+        self.replace_lines(lines)?;
+        Ok(true)
+    }
+
+    /// Take `principal` off every line; lines left without principals are
+    /// removed. Returns the number of lines changed.
+    pub fn remove_principal(&mut self, principal: &str) -> Result<usize> {
+        let mut changed = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+
+        for (index, line) in self.lines.iter().enumerate() {
+            let entry = self.entries.iter().find(|e| e.line == index + 1);
+            let Some(entry) = entry.filter(|e| e.principals.iter().any(|p| p == principal)) else {
+                lines.push(line.clone());
+                continue;
+            };
+
+            changed += 1;
+            let remaining: Vec<&str> = entry
+                .principals
+                .iter()
+                .filter(|p| *p != principal)
+                .map(String::as_str)
+                .collect();
+            if remaining.is_empty() {
+                continue;
+            }
+            // The principals are the first field; everything after it stays as is
+            let rest = line.trim_start();
+            let rest = &rest[split_fields(rest)[0].len()..];
+            lines.push(format!("{}{}", remaining.join(","), rest));
+        }
+
+        if changed > 0 {
+            self.replace_lines(lines)?;
+        }
+        Ok(changed)
+    }
+
+    /// Verify an SSHSIG signature over `data` like `ssh-keygen -Y verify`:
+    /// the signing key must be listed for `identity` (any principal when
+    /// `None`) and `namespace`. Returns the entry that allowed it.
+    pub fn verify(
+        &self,
+        identity: Option<&str>,
+        namespace: &str,
+        data: &[u8],
+        signature: &SshSig,
+    ) -> Result<&AllowedSigner> {
+        let now = Local::now();
+        let signer = self
+            .entries
+            .iter()
+            .filter(|e| !e.cert_authority && e.allows(identity, namespace, now))
+            .find(|e| {
+                e.key
+                    .as_ref()
+                    .is_some_and(|k| k.key_data() == signature.public_key())
+            })
+            .ok_or_else(|| {
+                SkmError::KeyNotFound(format!(
+                    "No allowed signer{} for namespace '{}' has the signing key {} in {}",
+                    identity.map(|i| format!(" '{}'", i)).unwrap_or_default(),
+                    namespace,
+                    signature.public_key().fingerprint(HashAlg::Sha256),
+                    self.path.display()
+                ))
+            })?;
+
+        verify_with_key(
+            signer.key.as_ref().expect("matched on key"),
+            namespace,
+            data,
+            signature,
+        )?;
+        Ok(signer)
+    }
+
+    fn replace_lines(&mut self, lines: Vec<String>) -> Result<()> {
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        std::fs::write(&self.path, &content)?;
+
+        *self = Self::parse(self.path.clone(), &content);
+        Ok(())
+    }
+}
+
+/// Check an SSHSIG signature against a single public key
+pub fn verify_with_key(
+    key: &PublicKey,
+    namespace: &str,
+    data: &[u8],
+    signature: &SshSig,
+) -> Result<()> {
+    if key.key_data() != signature.public_key() {
+        return Err(SkmError::SshKey(format!(
+            "Signature was made by {}, not {}",
+            signature.public_key().fingerprint(HashAlg::Sha256),
+            key.fingerprint(HashAlg::Sha256)
+        )));
+    }
+    if signature.namespace() != namespace {
+        return Err(SkmError::SshKey(format!(
+            "Signature is for namespace '{}', not '{}'",
+            signature.namespace(),
+            namespace
+        )));
+    }
+    key.verify(namespace, data, signature)
+        .map_err(|_| SkmError::SshKey("Signature does not match the data".to_string()))
+}
+
+/// Split on whitespace, keeping double-quoted parts together
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    fields.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        fields.push(current);
+    }
+    fields
+}
+
+/// Split an options field on commas outside of quotes
+fn split_options(options: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in options.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn is_key_type(field: &str) -> bool {
+    field.starts_with("ssh-") || field.starts_with("ecdsa-") || field.starts_with("sk-")
+}
+
+/// `YYYYMMDD[HHMM[SS]]`, in UTC with a trailing `Z` and local time otherwise
+fn parse_time(value: &str) -> Option<DateTime<Local>> {
+    let (digits, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(digits) => (digits, true),
+        None => (value, false),
+    };
+    let naive = match digits.len() {
+        8 => NaiveDate::parse_from_str(digits, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+        12 => NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M").ok()?,
+        14 => NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M%S").ok()?,
+        _ => return None,
+    };
+    if utc {
+        Some(Utc.from_utc_datetime(&naive).into())
+    } else {
+        Local.from_local_datetime(&naive).earliest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use ssh_key::{Algorithm, PrivateKey};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_entries() {
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let public = key.public_key().to_openssh().unwrap();
+        let content = format!(
+            "# team\n\
+             alice@example.com,!bob@example.com {public} laptop\n\
+             *@example.com namespaces=\"git,file\",valid-before=\"20200101Z\" {public}\n\
+             ops@example.com bogus-option {public}\n"
+        );
+        let signers = AllowedSigners::parse(PathBuf::from("allowed_signers"), &content);
+
+        assert_eq!(signers.entries.len(), 2);
+        let first = &signers.entries[0];
+        assert_eq!(first.line, 2);
+        assert_eq!(first.comment.as_deref(), Some("laptop"));
+        let now = Local::now();
+        assert!(first.allows(Some("alice@example.com"), "anything", now));
+        assert!(!first.allows(Some("bob@example.com"), "file", now));
+
+        let second = &signers.entries[1];
+        assert_eq!(
+            second.namespaces,
+            Some(vec!["git".to_string(), "file".to_string()])
+        );
+        // Expired in 2020
+        assert!(!second.allows(Some("carol@example.com"), "git", now));
+        let then = Utc.with_ymd_and_hms(2019, 6, 1, 0, 0, 0).unwrap().into();
+        assert!(second.allows(Some("carol@example.com"), "git", then));
+        assert!(!second.allows(Some("carol@example.com"), "email", then));
+    }
+
+    #[test]
+    fn test_add_verify_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(ALLOWED_SIGNERS_FILE);
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let other = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let alice = vec!["alice@example.com".to_string(), "alice@work".to_string()];
+
+        let mut signers = AllowedSigners::load(&path).unwrap();
+        assert!(
+            signers
+                .add(&alice, key.public_key(), &["git".to_string()])
+                .unwrap()
+        );
+        assert!(!signers.add(&alice, key.public_key(), &[]).unwrap());
+
+        let data = b"commit contents";
+        let signature = key.sign("git", HashAlg::Sha512, data).unwrap();
+        let signer = signers
+            .verify(Some("alice@work"), "git", data, &signature)
+            .unwrap();
+        assert_eq!(signer.line, 1);
+        assert!(signers.verify(None, "git", data, &signature).is_ok());
+        assert!(
+            signers
+                .verify(Some("bob"), "git", data, &signature)
+                .is_err()
+        );
+        assert!(
+            signers
+                .verify(Some("alice@work"), "file", data, &signature)
+                .is_err()
+        );
+        assert!(
+            signers
+                .verify(Some("alice@work"), "git", b"tampered", &signature)
+                .is_err()
+        );
+        let forged = other.sign("git", HashAlg::Sha512, data).unwrap();
+        assert!(signers.verify(None, "git", data, &forged).is_err());
+
+        assert_eq!(signers.remove_principal("alice@work").unwrap(), 1);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("alice@example.com namespaces=\"git\" ssh-ed25519 "));
+        assert_eq!(signers.remove_principal("alice@example.com").unwrap(), 1);
+        assert!(AllowedSigners::load(&path).unwrap().entries.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Approval of new keys for managed teams. With `[approval] required = true`
+//! a newly generated key is held as pending, and deploy, publish and team
+//! sync leave it alone until someone runs `skm approval approve`.
+//!
+//! Keys with no record, e.g. ones made before approval was turned on, count
+//! as approved; only a pending or rejected record holds a key back.
+
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ApprovalSettings;
+use crate::error::{Result, SkmError};
+use crate::migrate::RecordStore;
+use crate::ssh::SshKey;
+
+/// Where approvals are recorded, relative to the skm data directory
+pub const APPROVALS_FILE: &str = "approvals.json";
+
+/// How long the webhook gets to answer
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalState {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl std::fmt::Display for ApprovalState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalState::Pending => write!(f, "pending"),
+            ApprovalState::Approved => write!(f, "approved"),
+            ApprovalState::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+/// Where a key stands, and who asked and decided
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    /// Identifies the key across renames
+    pub fingerprint: String,
+    /// Name of the key when approval was asked for
+    pub key_name: String,
+    pub state: ApprovalState,
+    pub requested_by: String,
+    pub requested_at: DateTime<Local>,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Local>>,
+}
+
+pub type ApprovalStore = RecordStore<Approval>;
+
+impl ApprovalStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(APPROVALS_FILE))
+    }
+
+    /// Hold `key` as pending, replacing an earlier decision about it
+    pub fn request(&self, key: &SshKey, requested_by: &str) -> Result<Approval> {
+        let approval = Approval {
+            fingerprint: key_fingerprint(key)?,
+            key_name: key.name.clone(),
+            state: ApprovalState::Pending,
+            requested_by: requested_by.to_string(),
+            requested_at: Local::now(),
+            decided_by: None,
+            decided_at: None,
+        };
+        self.update(|approvals| {
+            approvals.retain(|a| a.fingerprint != approval.fingerprint);
+            approvals.push(approval.clone());
+        })?;
+        Ok(approval)
+    }
+
+    /// Approve or reject `key`; fails unless it is waiting for a decision
+    pub fn decide(&self, key: &SshKey, state: ApprovalState, decided_by: &str) -> Result<Approval> {
+        let fingerprint = key_fingerprint(key)?;
+        self.update(|approvals| {
+            let approval = approvals
+                .iter_mut()
+                .find(|a| a.fingerprint == fingerprint)
+                .filter(|a| a.state == ApprovalState::Pending)
+                .ok_or_else(|| {
+                    SkmError::Approval(format!("'{}' is not waiting for approval", key.name))
+                })?;
+            approval.state = state;
+            approval.decided_by = Some(decided_by.to_string());
+            approval.decided_at = Some(Local::now());
+            Ok(approval.clone())
+        })?
+    }
+
+    /// The record for the key with this fingerprint, if there is one
+    pub fn for_key(&self, fingerprint: &str) -> Result<Option<Approval>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .find(|a| a.fingerprint == fingerprint))
+    }
+
+    /// Whether `key` may be deployed or published
+    pub fn is_approved(&self, key: &SshKey) -> Result<bool> {
+        let Some(ref fingerprint) = key.fingerprint else {
+            return Ok(true);
+        };
+        Ok(self
+            .for_key(fingerprint)?
+            .is_none_or(|a| a.state == ApprovalState::Approved))
+    }
+
+    /// Fail with the reason when `key` is held back from `action`, e.g. "deploy"
+    pub fn require_approved(&self, key: &SshKey, action: &str) -> Result<()> {
+        let Some(ref fingerprint) = key.fingerprint else {
+            return Ok(());
+        };
+        match self.for_key(fingerprint)? {
+            Some(a) if a.state == ApprovalState::Pending => Err(SkmError::Approval(format!(
+                "can't {} '{}' while it waits for approval; approve it with `skm approval approve {}`",
+                action, key.name, key.name
+            ))),
+            Some(a) if a.state == ApprovalState::Rejected => Err(SkmError::Approval(format!(
+                "can't {} '{}': it was rejected by {}",
+                action,
+                key.name,
+                a.decided_by.as_deref().unwrap_or("someone")
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hold a newly generated key for approval when the settings ask for it,
+/// telling the webhook if one is set. A webhook that can't be reached is
+/// only logged; the key is held either way.
+pub fn hold_new_key(
+    settings: &ApprovalSettings,
+    data_dir: &Path,
+    key: &SshKey,
+    requested_by: &str,
+) -> Result<Option<Approval>> {
+    if !settings.required {
+        return Ok(None);
+    }
+    let approval = ApprovalStore::new(data_dir).request(key, requested_by)?;
+    notify_webhook(settings, &approval);
+    Ok(Some(approval))
+}
+
+/// Post a request or decision to the webhook, if one is set
+pub fn notify_webhook(settings: &ApprovalSettings, approval: &Approval) {
+    let Some(ref url) = settings.webhook else {
+        return;
+    };
+    if let Err(e) = post(url, approval) {
+        tracing::warn!("Failed to notify the approval webhook: {}", e);
+    }
+}
+
+fn post(url: &str, approval: &Approval) -> Result<()> {
+    let agent = ureq::AgentBuilder::new().timeout(WEBHOOK_TIMEOUT).build();
+    let body = serde_json::json!({
+        "event": format!("key_{}", approval.state),
+        "key": approval.key_name,
+        "fingerprint": approval.fingerprint,
+        "requested_by": approval.requested_by,
+        "decided_by": approval.decided_by,
+    });
+    agent
+        .post(url)
+        .send_json(body)
+        .map_err(|e| SkmError::Remote(format!("{}: {}", url, e)))?;
+    Ok(())
+}
+
+fn key_fingerprint(key: &SshKey) -> Result<String> {
+    key.fingerprint.clone().ok_or_else(|| {
+        SkmError::SshKey(format!(
+            "'{}' has no public key to identify it by",
+            key.name
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::generate::{KeyGenOptions, KeyGenerator};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pending_key_is_held_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = KeyGenerator::new(temp_dir.path())
+            .generate(KeyGenOptions::default())
+            .unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let store = ApprovalStore::new(&data_dir);
+
+        // Nothing recorded: approval was never asked for
+        assert!(store.is_approved(&key).unwrap());
+        let off = ApprovalSettings::default();
+        assert!(hold_new_key(&off, &data_dir, &key, "me").unwrap().is_none());
+        assert!(store.require_approved(&key, "deploy").is_ok());
+
+        let on = ApprovalSettings {
+            required: true,
+            webhook: None,
+        };
+        let held = hold_new_key(&on, &data_dir, &key, "me").unwrap().unwrap();
+        assert_eq!(held.state, ApprovalState::Pending);
+        assert!(!store.is_approved(&key).unwrap());
+        let err = store.require_approved(&key, "deploy").unwrap_err();
+        assert!(err.to_string().contains("skm approval approve"));
+
+        let approved = store.decide(&key, ApprovalState::Approved, "lead").unwrap();
+        assert_eq!(approved.decided_by.as_deref(), Some("lead"));
+        assert!(store.is_approved(&key).unwrap());
+        // Only pending keys can be decided on
+        assert!(store.decide(&key, ApprovalState::Rejected, "lead").is_err());
+
+        store.request(&key, "me").unwrap();
+        store.decide(&key, ApprovalState::Rejected, "lead").unwrap();
+        let err = store.require_approved(&key, "publish").unwrap_err();
+        assert!(err.to_string().contains("rejected by lead"));
+        assert_eq!(store.load().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,281 @@
+use ssh_key::PublicKey;
+use ssh_key::authorized_keys::AuthorizedKeys;
+use ssh_key::public::KeyData;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use crate::error::{Result, SkmError};
+
+/// Markers around a block of keys skm owns, e.g. `# BEGIN skm github:acme/infra`
+const SECTION_BEGIN: &str = "# BEGIN skm ";
+const SECTION_END: &str = "# END skm ";
+
+/// What [`sync_section`] changed in a managed section
+#[derive(Debug, Default)]
+pub struct SectionSync {
+    pub added: Vec<PublicKey>,
+    pub removed: Vec<PublicKey>,
+}
+
+impl SectionSync {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Key data of every parseable entry; unparseable lines are ignored
+pub fn read_key_data(path: &Path) -> Result<Vec<KeyData>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    Ok(AuthorizedKeys::new(&content)
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.public_key().key_data().clone())
+        .collect())
+}
+
+/// Append keys that aren't already authorized, creating the file with mode 0600.
+/// Returns the keys that were added.
+pub fn append_keys(path: &Path, keys: &[PublicKey]) -> Result<Vec<PublicKey>> {
+    let mut existing = read_key_data(path)?;
+    let mut added = Vec::new();
+
+    for key in keys {
+        if !existing.contains(key.key_data()) {
+            existing.push(key.key_data().clone());
+            added.push(key.clone());
+        }
+    }
+
+    if added.is_empty() {
+        return Ok(added);
+    }
+
+    // Don't glue the first new key onto a last line without a newline
+    let needs_newline = fs::read(path)
+        .map(|content| content.last().is_some_and(|&b| b != b'\n'))
+        .unwrap_or(false);
+
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+
+    if needs_newline {
+        writeln!(file)?;
+    }
+    for key in &added {
+        let line = key
+            .to_openssh()
+            .map_err(|e| crate::error::SkmError::SshKey(e.to_string()))?;
+        writeln!(file, "{}", line)?;
+    }
+
+    Ok(added)
+}
+
+/// Make the managed section `name` hold exactly `keys`, appending the section
+/// if the file doesn't have it yet. Lines outside the markers are left alone,
+/// so the file can mix managed and hand-written entries.
+pub fn sync_section(
+    path: &Path,
+    name: &str,
+    keys: &[PublicKey],
+    dry_run: bool,
+) -> Result<SectionSync> {
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let (new_content, sync) = render_section(&content, name, keys)?;
+
+    if !dry_run && new_content != content {
+        // Replace the file in one step so sshd never sees half a section
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".skm-tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(new_content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+    }
+
+    Ok(sync)
+}
+
+fn render_section(content: &str, name: &str, keys: &[PublicKey]) -> Result<(String, SectionSync)> {
+    let begin = format!("{}{}", SECTION_BEGIN, name);
+    let end = format!("{}{}", SECTION_END, name);
+    let lines: Vec<&str> = content.lines().collect();
+
+    let (before, old, after) = match lines.iter().position(|line| line.trim() == begin) {
+        Some(start) => {
+            let len = lines[start + 1..]
+                .iter()
+                .position(|line| line.trim() == end)
+                .ok_or_else(|| {
+                    SkmError::InvalidKeyFormat(format!(
+                        "'{}' has no matching '{}'; fix authorized_keys by hand",
+                        begin, end
+                    ))
+                })?;
+            (
+                &lines[..start],
+                &lines[start + 1..start + 1 + len],
+                &lines[start + 2 + len..],
+            )
+        }
+        None => (&lines[..], &lines[..0], &lines[..0]),
+    };
+
+    let old_keys: Vec<PublicKey> = old
+        .iter()
+        .filter_map(|line| line.parse::<ssh_key::authorized_keys::Entry>().ok())
+        .map(|entry| entry.public_key().clone())
+        .collect();
+
+    // The same key may belong to several people; list it once
+    let mut new_keys: Vec<&PublicKey> = Vec::new();
+    for key in keys {
+        if !new_keys.iter().any(|k| k.key_data() == key.key_data()) {
+            new_keys.push(key);
+        }
+    }
+
+    let sync = SectionSync {
+        added: new_keys
+            .iter()
+            .filter(|key| !old_keys.iter().any(|k| k.key_data() == key.key_data()))
+            .map(|&key| key.clone())
+            .collect(),
+        removed: old_keys
+            .iter()
+            .filter(|key| !new_keys.iter().any(|k| k.key_data() == key.key_data()))
+            .cloned()
+            .collect(),
+    };
+
+    let mut out = String::new();
+    for line in before {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(&begin);
+    out.push('\n');
+    for key in new_keys {
+        let line = key
+            .to_openssh()
+            .map_err(|e| SkmError::SshKey(e.to_string()))?;
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out.push_str(&end);
+    out.push('\n');
+    for line in after {
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    Ok((out, sync))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const KEY_A: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X+rEWi9T9XCg49jAvfUVQGsYRWc5i5mJ1N/mlGAlL a";
+    const KEY_B: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl b";
+
+    #[test]
+    fn test_append_keys_skips_existing() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("authorized_keys");
+        // Existing entry with options and no trailing newline
+        fs::write(&path, format!("no-pty {}", KEY_A.replace(" a", " old"))).unwrap();
+
+        let keys = vec![
+            PublicKey::from_openssh(KEY_A).unwrap(),
+            PublicKey::from_openssh(KEY_B).unwrap(),
+        ];
+        let added = append_keys(&path, &keys).unwrap();
+
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].comment(), "b");
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.ends_with(&format!("{}\n", KEY_B)));
+
+        // Running again is a no-op
+        assert!(append_keys(&path, &keys).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_section() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("authorized_keys");
+        fs::write(&path, format!("{}\n", KEY_A)).unwrap();
+        let key_a = PublicKey::from_openssh(KEY_A).unwrap();
+        let key_b = PublicKey::from_openssh(KEY_B).unwrap();
+
+        let sync =
+            sync_section(&path, "github:acme", &[key_a.clone(), key_b.clone()], false).unwrap();
+        assert_eq!(sync.added.len(), 2);
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            format!("{KEY_A}\n# BEGIN skm github:acme\n{KEY_A}\n{KEY_B}\n# END skm github:acme\n")
+        );
+
+        // Hand-written lines after the section survive a re-sync
+        fs::write(&path, format!("{content}no-pty {KEY_B}\n")).unwrap();
+        let sync = sync_section(&path, "github:acme", std::slice::from_ref(&key_b), false).unwrap();
+        assert!(sync.added.is_empty());
+        assert_eq!(sync.removed.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!(
+                "{KEY_A}\n# BEGIN skm github:acme\n{KEY_B}\n# END skm github:acme\nno-pty {KEY_B}\n"
+            )
+        );
+        assert!(
+            sync_section(&path, "github:acme", &[key_b], false)
+                .unwrap()
+                .is_empty()
+        );
+
+        // An unterminated section is never rewritten
+        fs::write(&path, "# BEGIN skm github:acme\n").unwrap();
+        assert!(sync_section(&path, "github:acme", &[key_a], false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_append_keys_creates_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("authorized_keys");
+        append_keys(&path, &[PublicKey::from_openssh(KEY_A).unwrap()]).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read_key_data(&path).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use rand::rngs::OsRng;
+use serde::Serialize;
+use ssh_key::certificate::{Builder, CertType};
+use ssh_key::{Certificate, PrivateKey, PublicKey};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::cert::CertInfo;
+use crate::ssh::keys::read_key_text;
+
+/// Extensions `ssh-keygen -s` grants user certificates unless told otherwise
+const DEFAULT_USER_EXTENSIONS: &[&str] = &[
+    "permit-X11-forwarding",
+    "permit-agent-forwarding",
+    "permit-port-forwarding",
+    "permit-pty",
+    "permit-user-rc",
+];
+
+/// Certificates start a minute early so small clock skew doesn't reject them
+const BACKDATE_SECS: u64 = 60;
+
+/// What to put into a certificate; mirrors the `ssh-keygen -s` options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignRequest {
+    pub cert_type: CertType,
+    pub key_id: String,
+    /// Empty means valid for any principal, which only host certificates
+    /// may be; sshd refuses user certificates without principals
+    pub principals: Vec<String>,
+    pub serial: u64,
+    pub valid_after: u64,
+    pub valid_before: u64,
+    pub critical_options: BTreeMap<String, String>,
+    pub extensions: BTreeSet<String>,
+}
+
+impl SignRequest {
+    /// A certificate valid from now on for `validity` (see [`parse_validity`])
+    pub fn new(cert_type: CertType, key_id: &str, validity: &str) -> Result<Self> {
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let (valid_after, valid_before) = parse_validity(validity, now)?;
+
+        let extensions = if cert_type.is_user() {
+            DEFAULT_USER_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+
+        Ok(Self {
+            cert_type,
+            key_id: key_id.to_string(),
+            principals: Vec::new(),
+            serial: 0,
+            valid_after,
+            valid_before,
+            critical_options: BTreeMap::new(),
+            extensions,
+        })
+    }
+
+    /// Apply an `ssh-keygen -O` style option: `clear`, `no-pty` and friends,
+    /// `permit-*`, `force-command=CMD` or `source-address=CIDRS`. Host
+    /// certificates take no options.
+    pub fn apply_option(&mut self, option: &str) -> Result<()> {
+        let invalid = || SkmError::Config(format!("Unsupported certificate option '{}'", option));
+        if self.cert_type.is_host() {
+            return Err(SkmError::Config(format!(
+                "Host certificates take no options (got '{}')",
+                option
+            )));
+        }
+
+        match option.split_once('=') {
+            Some((name @ ("force-command" | "source-address"), value)) if !value.is_empty() => {
+                self.critical_options
+                    .insert(name.to_string(), value.to_string());
+            }
+            Some(_) => return Err(invalid()),
+            None if option == "clear" => {
+                self.critical_options.clear();
+                self.extensions.clear();
+            }
+            None => {
+                if let Some(feature) = option.strip_prefix("no-") {
+                    let extension = extension_name(feature).ok_or_else(invalid)?;
+                    self.extensions.remove(extension);
+                } else if let Some(feature) = option.strip_prefix("permit-") {
+                    let extension = extension_name(feature).ok_or_else(invalid)?;
+                    self.extensions.insert(extension.to_string());
+                } else {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `permit-*` extension for an option suffix such as `pty` or `x11-forwarding`
+fn extension_name(feature: &str) -> Option<&'static str> {
+    DEFAULT_USER_EXTENSIONS
+        .iter()
+        .copied()
+        .find(|e| e["permit-".len()..].eq_ignore_ascii_case(feature))
+}
+
+/// Parse a validity such as `+8h`, `30d`, `52w` or `forever` into the
+/// `(valid_after, valid_before)` window in Unix seconds
+pub fn parse_validity(spec: &str, now: u64) -> Result<(u64, u64)> {
+    let spec = spec.trim();
+    if spec == "forever" {
+        return Ok((0, u64::MAX));
+    }
+
+    let invalid = || {
+        SkmError::Config(format!(
+            "Invalid validity '{}' (expected e.g. 8h, 30d, 52w or forever)",
+            spec
+        ))
+    };
+    let duration = spec.strip_prefix('+').unwrap_or(spec);
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    let seconds = number
+        .checked_mul(multiplier)
+        .filter(|&s| s > 0)
+        .ok_or_else(invalid)?;
+    Ok((
+        now.saturating_sub(BACKDATE_SECS),
+        now.saturating_add(seconds),
+    ))
+}
+
+/// Issue a certificate for `subject`, signed by the CA key
+pub fn sign(ca: &PrivateKey, subject: &PublicKey, request: &SignRequest) -> Result<Certificate> {
+    if ca.is_encrypted() {
+        return Err(SkmError::InvalidPassphrase);
+    }
+    if request.cert_type.is_user() && request.principals.is_empty() {
+        return Err(SkmError::Config(
+            "A user certificate needs principals (-n), sshd refuses it without".to_string(),
+        ));
+    }
+    let to_err = |e: ssh_key::Error| SkmError::SshKey(e.to_string());
+
+    let mut builder = Builder::new_with_random_nonce(
+        &mut OsRng,
+        subject.key_data().clone(),
+        request.valid_after,
+        request.valid_before,
+    )
+    .map_err(to_err)?;
+
+    builder.serial(request.serial).map_err(to_err)?;
+    builder.cert_type(request.cert_type).map_err(to_err)?;
+    builder.key_id(request.key_id.as_str()).map_err(to_err)?;
+    if request.principals.is_empty() {
+        builder.all_principals_valid().map_err(to_err)?;
+    }
+    for principal in &request.principals {
+        builder
+            .valid_principal(principal.as_str())
+            .map_err(to_err)?;
+    }
+    for (name, value) in &request.critical_options {
+        builder
+            .critical_option(name.as_str(), value.as_str())
+            .map_err(to_err)?;
+    }
+    for extension in &request.extensions {
+        builder.extension(extension.as_str(), "").map_err(to_err)?;
+    }
+    builder.comment(subject.comment()).map_err(to_err)?;
+
+    builder.sign(ca).map_err(to_err)
+}
+
+/// Read CA public keys, one per line as in `TrustedUserCAKeys`
+pub fn read_trusted_keys(path: &Path) -> Result<Vec<PublicKey>> {
+    read_key_text(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            PublicKey::from_openssh(line)
+                .map_err(|e| SkmError::InvalidKeyFormat(format!("{}: {}", path.display(), e)))
+        })
+        .collect()
+}
+
+/// Outcome of one of the checks `ssh` and `sshd` make on a certificate
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// `signature`, `ca`, `validity` or `principal`
+    pub check: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+    pub valid: bool,
+    pub certificate: CertInfo,
+    pub checks: Vec<Check>,
+}
+
+/// Check a certificate the way a server would: signed by one of the
+/// `trusted` CA keys, inside its validity window at `now` (Unix seconds) and,
+/// when given, issued for `principal`
+pub fn verify(
+    path: &Path,
+    cert: &Certificate,
+    trusted: &[PublicKey],
+    principal: Option<&str>,
+    now: u64,
+) -> Verification {
+    let info = CertInfo::from_certificate(path, cert, None);
+    let mut checks = Vec::new();
+
+    let signed = cert.verify_signature().is_ok();
+    checks.push(Check {
+        check: "signature",
+        passed: signed,
+        detail: if signed {
+            "signature matches the signing key".to_string()
+        } else {
+            "signature does not match; the certificate was altered".to_string()
+        },
+    });
+
+    let trusted_ca = trusted
+        .iter()
+        .any(|ca| ca.key_data() == cert.signature_key());
+    checks.push(Check {
+        check: "ca",
+        passed: trusted_ca,
+        detail: if trusted_ca {
+            format!("signed by trusted CA {}", info.ca_fingerprint)
+        } else {
+            format!("signed by unknown CA {}", info.ca_fingerprint)
+        },
+    });
+
+    let in_window = cert.valid_after() <= now && now < cert.valid_before();
+    checks.push(Check {
+        check: "validity",
+        passed: in_window,
+        detail: if now < cert.valid_after() {
+            format!("not valid yet ({})", info.window())
+        } else if !in_window {
+            format!("expired ({})", info.window())
+        } else {
+            info.window()
+        },
+    });
+
+    // sshd refuses user certificates without principals; only host
+    // certificates may leave the list empty to match any name
+    let no_principals = cert.valid_principals().is_empty();
+    if no_principals && cert.cert_type().is_user() {
+        checks.push(Check {
+            check: "principal",
+            passed: false,
+            detail: "user certificate lists no principals; sshd refuses it".to_string(),
+        });
+    } else if let Some(principal) = principal {
+        let allowed = no_principals || cert.valid_principals().iter().any(|p| p == principal);
+        checks.push(Check {
+            check: "principal",
+            passed: allowed,
+            detail: if allowed {
+                format!("'{}' is allowed ({})", principal, info.principals_display())
+            } else {
+                format!("'{}' is not among {}", principal, info.principals_display())
+            },
+        });
+    }
+
+    Verification {
+        valid: checks.iter().all(|c| c.passed),
+        certificate: info,
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssh_key::{Algorithm, HashAlg};
+
+    #[test]
+    fn test_parse_validity() {
+        assert_eq!(parse_validity("+8h", 1000).unwrap(), (940, 1000 + 8 * 3600));
+        assert_eq!(parse_validity("2w", 1000).unwrap().1, 1000 + 14 * 86400);
+        assert_eq!(parse_validity("forever", 1000).unwrap(), (0, u64::MAX));
+
+        assert!(parse_validity("0d", 1000).is_err());
+        assert!(parse_validity("1y", 1000).is_err());
+        assert!(parse_validity("soon", 1000).is_err());
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let mut request = SignRequest::new(CertType::User, "alice", "1d").unwrap();
+        assert!(request.extensions.contains("permit-pty"));
+
+        request.apply_option("no-pty").unwrap();
+        request
+            .apply_option("force-command=/usr/bin/backup")
+            .unwrap();
+        assert!(!request.extensions.contains("permit-pty"));
+        assert_eq!(request.critical_options["force-command"], "/usr/bin/backup");
+
+        request.apply_option("clear").unwrap();
+        request.apply_option("permit-agent-forwarding").unwrap();
+        assert!(request.critical_options.is_empty());
+        assert_eq!(
+            request.extensions.iter().collect::<Vec<_>>(),
+            vec!["permit-agent-forwarding"]
+        );
+
+        assert!(request.apply_option("no-such-thing").is_err());
+        let mut host = SignRequest::new(CertType::Host, "web", "1d").unwrap();
+        assert!(host.extensions.is_empty());
+        assert!(host.apply_option("no-pty").is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        let ca = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let user = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+
+        let mut request = SignRequest::new(CertType::User, "alice@laptop", "1h").unwrap();
+        request.principals = vec!["alice".to_string(), "deploy".to_string()];
+        request.serial = 7;
+
+        let cert = sign(&ca, user.public_key(), &request).unwrap();
+        let ca_fingerprint = ca.public_key().fingerprint(HashAlg::Sha256);
+        cert.validate([&ca_fingerprint]).unwrap();
+        assert_eq!(cert.public_key(), user.public_key().key_data());
+        assert_eq!(cert.valid_principals(), ["alice", "deploy"]);
+        assert_eq!(cert.serial(), 7);
+        assert!(cert.extensions().contains_key("permit-pty"));
+
+        request.principals.clear();
+        assert!(sign(&ca, user.public_key(), &request).is_err());
+        let mut host = SignRequest::new(CertType::Host, "web", "1h").unwrap();
+        let cert = sign(&ca, user.public_key(), &host).unwrap();
+        assert!(cert.valid_principals().is_empty());
+        host.principals = vec!["web.example.com".to_string()];
+        assert!(sign(&ca, user.public_key(), &host).is_ok());
+    }
+
+    #[test]
+    fn test_verify() {
+        let ca = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let other_ca = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let user = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+
+        let mut request = SignRequest::new(CertType::User, "alice", "1h").unwrap();
+        request.principals = vec!["alice".to_string()];
+        let cert = sign(&ca, user.public_key(), &request).unwrap();
+        let path = Path::new("alice-cert.pub");
+        let now = request.valid_after + 120;
+        let trusted = [ca.public_key().clone()];
+
+        let result = verify(path, &cert, &trusted, Some("alice"), now);
+        assert!(result.valid);
+        assert_eq!(result.checks.len(), 4);
+
+        let failed = |result: Verification| -> Vec<&'static str> {
+            result
+                .checks
+                .iter()
+                .filter(|c| !c.passed)
+                .map(|c| c.check)
+                .collect()
+        };
+        let other = [other_ca.public_key().clone()];
+        assert_eq!(failed(verify(path, &cert, &other, None, now)), vec!["ca"]);
+        assert_eq!(
+            failed(verify(path, &cert, &trusted, Some("root"), now)),
+            vec!["principal"]
+        );
+        assert_eq!(
+            failed(verify(path, &cert, &trusted, None, now + 7200)),
+            vec!["validity"]
+        );
+        // skm won't sign a user certificate without principals, other tools might
+        let mut builder = Builder::new_with_random_nonce(
+            &mut OsRng,
+            user.public_key().key_data().clone(),
+            request.valid_after,
+            request.valid_before,
+        )
+        .unwrap();
+        builder.cert_type(CertType::User).unwrap();
+        builder.key_id("alice").unwrap();
+        builder.all_principals_valid().unwrap();
+        let open = builder.sign(&ca).unwrap();
+        assert_eq!(
+            failed(verify(path, &open, &trusted, Some("alice"), now)),
+            vec!["principal"]
+        );
+        assert_eq!(
+            failed(verify(path, &open, &trusted, None, now)),
+            vec!["principal"]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use ssh_key::{Certificate, HashAlg, PublicKey};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::keys::read_key_text;
+
+/// `ssh-keygen -s` writes the certificate for `key` to `key-cert.pub`
+pub const CERT_SUFFIX: &str = "-cert.pub";
+
+/// Certificates expiring within this many days get a warning
+pub const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// Certificate file belonging to a private key
+pub fn cert_path(private_path: &Path) -> PathBuf {
+    let mut name = private_path
+        .file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(CERT_SUFFIX);
+    private_path.with_file_name(name)
+}
+
+pub fn read_certificate(path: &Path) -> Result<Certificate> {
+    let content = read_key_text(path)?;
+    Certificate::from_openssh(content.trim())
+        .map_err(|e| SkmError::InvalidKeyFormat(format!("{}: {}", path.display(), e)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertValidity {
+    NotYetValid,
+    Valid,
+    ExpiringSoon,
+    Expired,
+}
+
+impl fmt::Display for CertValidity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertValidity::NotYetValid => write!(f, "not yet valid"),
+            CertValidity::Valid => write!(f, "valid"),
+            CertValidity::ExpiringSoon => write!(f, "expiring soon"),
+            CertValidity::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// The parts of an OpenSSH certificate worth showing next to its key
+#[derive(Debug, Clone, Serialize)]
+pub struct CertInfo {
+    pub path: PathBuf,
+    /// `user` or `host`
+    pub cert_type: String,
+    pub key_id: String,
+    pub serial: u64,
+    /// Empty means valid for any principal
+    pub principals: Vec<String>,
+    /// `None` when valid from the beginning of time
+    pub valid_after: Option<DateTime<Local>>,
+    /// `None` when the certificate never expires
+    pub valid_before: Option<DateTime<Local>>,
+    /// SHA256 fingerprint of the signing CA key
+    pub ca_fingerprint: String,
+    /// Whether the certificate was issued for the key it sits next to
+    pub matches_key: bool,
+}
+
+impl CertInfo {
+    /// Load a certificate; `key` is the public key it should certify
+    pub fn load(path: &Path, key: Option<&PublicKey>) -> Result<Self> {
+        let cert = read_certificate(path)?;
+        Ok(Self::from_certificate(path, &cert, key))
+    }
+
+    pub fn from_certificate(path: &Path, cert: &Certificate, key: Option<&PublicKey>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            cert_type: if cert.cert_type().is_host() {
+                "host".to_string()
+            } else {
+                "user".to_string()
+            },
+            key_id: cert.key_id().to_string(),
+            serial: cert.serial(),
+            principals: cert.valid_principals().to_vec(),
+            valid_after: (cert.valid_after() > 0).then(|| cert.valid_after_time().into()),
+            valid_before: (cert.valid_before() < u64::MAX).then(|| cert.valid_before_time().into()),
+            ca_fingerprint: cert
+                .signature_key()
+                .fingerprint(HashAlg::Sha256)
+                .to_string(),
+            matches_key: key.is_none_or(|key| key.key_data() == cert.public_key()),
+        }
+    }
+
+    pub fn validity(&self) -> CertValidity {
+        self.validity_at(Local::now())
+    }
+
+    pub fn validity_at(&self, now: DateTime<Local>) -> CertValidity {
+        if self.valid_after.is_some_and(|after| now < after) {
+            return CertValidity::NotYetValid;
+        }
+        match self.valid_before {
+            Some(before) if now >= before => CertValidity::Expired,
+            Some(before) if before - now <= Duration::days(EXPIRY_WARNING_DAYS) => {
+                CertValidity::ExpiringSoon
+            }
+            _ => CertValidity::Valid,
+        }
+    }
+
+    /// Validity window such as `2026-01-01 00:00 -> 2026-02-01 00:00`
+    pub fn window(&self) -> String {
+        let format = |time: Option<DateTime<Local>>, open: &str| {
+            time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| open.to_string())
+        };
+        format!(
+            "{} -> {}",
+            format(self.valid_after, "always"),
+            format(self.valid_before, "forever")
+        )
+    }
+
+    /// Principals joined for display
+    pub fn principals_display(&self) -> String {
+        if self.principals.is_empty() {
+            "(any)".to_string()
+        } else {
+            self.principals.join(", ")
+        }
+    }
+
+    /// One-line warning when the certificate needs attention
+    pub fn warning(&self) -> Option<String> {
+        if !self.matches_key {
+            return Some("certificate was issued for a different key".to_string());
+        }
+        match self.validity() {
+            CertValidity::Expired => Some("certificate has expired".to_string()),
+            CertValidity::ExpiringSoon => {
+                let left = self.valid_before? - Local::now();
+                Some(format!(
+                    "certificate expires in {}",
+                    if left.num_hours() < 24 {
+                        format!("{}h", left.num_hours().max(0))
+                    } else {
+                        format!("{}d", left.num_days())
+                    }
+                ))
+            }
+            CertValidity::NotYetValid => Some("certificate is not valid yet".to_string()),
+            CertValidity::Valid => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use ssh_key::certificate::{Builder, CertType};
+    use ssh_key::{Algorithm, PrivateKey};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cert_path() {
+        assert_eq!(
+            cert_path(Path::new("/home/me/.ssh/id_ed25519")),
+            PathBuf::from("/home/me/.ssh/id_ed25519-cert.pub")
+        );
+    }
+
+    #[test]
+    fn test_load_certificate() {
+        let temp_dir = TempDir::new().unwrap();
+        let ca = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let user = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let now = Local::now().timestamp() as u64;
+
+        let mut builder =
+            Builder::new_with_random_nonce(&mut OsRng, user.public_key(), now - 60, now + 3600)
+                .unwrap();
+        builder.serial(42).unwrap();
+        builder.key_id("alice@laptop").unwrap();
+        builder.cert_type(CertType::User).unwrap();
+        builder.valid_principal("alice").unwrap();
+        let cert = builder.sign(&ca).unwrap();
+
+        let path = temp_dir.path().join("id_ed25519-cert.pub");
+        std::fs::write(&path, cert.to_openssh().unwrap()).unwrap();
+
+        let info = CertInfo::load(&path, Some(user.public_key())).unwrap();
+        assert_eq!(info.cert_type, "user");
+        assert_eq!(info.serial, 42);
+        assert_eq!(info.key_id, "alice@laptop");
+        assert_eq!(info.principals, vec!["alice"]);
+        assert!(info.matches_key);
+        assert_eq!(
+            info.ca_fingerprint,
+            ca.public_key().fingerprint(HashAlg::Sha256).to_string()
+        );
+        // Expires within the hour
+        assert_eq!(info.validity(), CertValidity::ExpiringSoon);
+        assert!(info.warning().unwrap().contains("expires in"));
+        assert_eq!(
+            info.validity_at(Local::now() + Duration::hours(2)),
+            CertValidity::Expired
+        );
+
+        let other = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let info = CertInfo::load(&path, Some(other.public_key())).unwrap();
+        assert!(!info.matches_key);
+    }
+}
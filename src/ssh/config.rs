@@ -0,0 +1,592 @@
+use directories::BaseDirs;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::known_hosts::wildcard_match;
+
+/// Identities ssh tries, in order, when no `IdentityFile` applies
+const DEFAULT_IDENTITIES: &[&str] = &[
+    "id_rsa",
+    "id_ecdsa",
+    "id_ecdsa_sk",
+    "id_ed25519",
+    "id_ed25519_sk",
+    "id_dsa",
+];
+
+/// A `Host` block from an ssh client config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HostEntry {
+    /// Patterns from the `Host` line, e.g. `["web", "web.internal"]`
+    pub patterns: Vec<String>,
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// `IdentityFile` values as written (unexpanded)
+    pub identity_files: Vec<String>,
+    /// Line range of the block in the file, `Host` line first
+    #[serde(skip)]
+    lines: (usize, usize),
+}
+
+impl HostEntry {
+    pub fn new(alias: &str) -> Self {
+        Self {
+            patterns: vec![alias.to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// Wildcard and negated patterns don't name a single host
+    pub fn is_pattern(&self) -> bool {
+        self.patterns
+            .iter()
+            .all(|p| p.contains(['*', '?']) || p.starts_with('!'))
+    }
+
+    pub fn matches_alias(&self, alias: &str) -> bool {
+        self.patterns.iter().any(|p| p == alias)
+    }
+
+    /// Whether ssh applies this block to `host`, following `Host` pattern rules
+    pub fn matches_host(&self, host: &str) -> bool {
+        let mut matched = false;
+        for pattern in &self.patterns {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if wildcard_match(negated, host) {
+                    return false;
+                }
+            } else if wildcard_match(pattern, host) {
+                matched = true;
+            }
+        }
+        matched
+    }
+
+    pub fn identity_paths(&self) -> Vec<PathBuf> {
+        self.identity_files.iter().map(|f| expand_path(f)).collect()
+    }
+
+    fn render(&self) -> String {
+        let mut block = format!("Host {}\n", self.patterns.join(" "));
+        if let Some(ref host_name) = self.host_name {
+            block.push_str(&format!("    HostName {}\n", host_name));
+        }
+        if let Some(ref user) = self.user {
+            block.push_str(&format!("    User {}\n", user));
+        }
+        if let Some(port) = self.port {
+            block.push_str(&format!("    Port {}\n", port));
+        }
+        for identity_file in &self.identity_files {
+            block.push_str(&format!("    IdentityFile {}\n", quote(identity_file)));
+        }
+        block
+    }
+}
+
+/// Parsed `~/.ssh/config`. Global options, `Match` blocks and `Include`s are kept
+/// in the file untouched but not interpreted.
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    path: PathBuf,
+    lines: Vec<String>,
+    pub hosts: Vec<HostEntry>,
+}
+
+impl SshConfig {
+    /// Load a config file; a missing file is an empty config
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+
+        Ok(Self::parse(path, &content))
+    }
+
+    fn parse(path: PathBuf, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let mut hosts: Vec<HostEntry> = Vec::new();
+        let mut current: Option<HostEntry> = None;
+
+        for (index, line) in lines.iter().enumerate() {
+            let Some((keyword, value)) = parse_directive(line) else {
+                continue;
+            };
+
+            if keyword.eq_ignore_ascii_case("Host") || keyword.eq_ignore_ascii_case("Match") {
+                if let Some(mut host) = current.take() {
+                    host.lines.1 = index;
+                    hosts.push(host);
+                }
+                if keyword.eq_ignore_ascii_case("Host") {
+                    current = Some(HostEntry {
+                        patterns: value.split_whitespace().map(String::from).collect(),
+                        lines: (index, index + 1),
+                        ..HostEntry::default()
+                    });
+                }
+                continue;
+            }
+
+            let Some(ref mut host) = current else {
+                continue;
+            };
+            let value = unquote(value).to_string();
+
+            // ssh uses the first value it sees for single-valued options
+            match keyword.to_ascii_lowercase().as_str() {
+                "hostname" if host.host_name.is_none() => host.host_name = Some(value),
+                "user" if host.user.is_none() => host.user = Some(value),
+                "port" if host.port.is_none() => host.port = value.parse().ok(),
+                "identityfile" => host.identity_files.push(value),
+                _ => {}
+            }
+        }
+
+        if let Some(mut host) = current {
+            host.lines.1 = lines.len();
+            hosts.push(host);
+        }
+
+        Self { path, lines, hosts }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Host block listing `alias` as one of its patterns
+    pub fn find(&self, alias: &str) -> Option<&HostEntry> {
+        self.hosts.iter().find(|h| h.matches_alias(alias))
+    }
+
+    /// Host blocks whose `IdentityFile` points at the key (private or .pub path)
+    pub fn hosts_using(&self, private_path: &Path, public_path: &Path) -> Vec<&HostEntry> {
+        self.hosts
+            .iter()
+            .filter(|host| {
+                host.identity_paths()
+                    .iter()
+                    .any(|p| p == private_path || p == public_path)
+            })
+            .collect()
+    }
+
+    /// Keys ssh would offer when connecting to `alias`: the `IdentityFile`s of
+    /// every matching block (wildcards included), or ssh's default identities
+    /// when none is configured
+    pub fn identities_for(&self, alias: &str) -> Vec<PathBuf> {
+        let mut identities: Vec<PathBuf> = Vec::new();
+        for host in self.hosts.iter().filter(|h| h.matches_host(alias)) {
+            for path in host.identity_paths() {
+                if !identities.contains(&path) {
+                    identities.push(path);
+                }
+            }
+        }
+
+        if identities.is_empty() {
+            let ssh_dir = self.path.parent().unwrap_or(Path::new("."));
+            identities = DEFAULT_IDENTITIES
+                .iter()
+                .map(|name| ssh_dir.join(name))
+                .collect();
+        }
+        identities
+    }
+
+    /// Add a new `Host` block and save. It goes before any `Host *` block, since
+    /// ssh takes the first value it finds and the catch-all would otherwise win.
+    pub fn add_host(&mut self, entry: HostEntry) -> Result<()> {
+        if let Some(alias) = entry.patterns.iter().find(|p| self.find(p).is_some()) {
+            return Err(SkmError::HostAlreadyExists(alias.clone()));
+        }
+
+        let block: Vec<String> = entry.render().lines().map(String::from).collect();
+        match self.hosts.iter().find(|h| h.patterns == ["*"]) {
+            Some(catch_all) => {
+                let at = catch_all.lines.0;
+                self.lines
+                    .splice(at..at, block.into_iter().chain([String::new()]));
+            }
+            None => {
+                if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.extend(block);
+            }
+        }
+        self.save()?;
+
+        *self = Self::parse(self.path.clone(), &self.lines.join("\n"));
+        Ok(())
+    }
+
+    /// Remove `alias` and save. A block with several patterns only loses that
+    /// pattern; otherwise the whole block goes.
+    pub fn remove_host(&mut self, alias: &str) -> Result<()> {
+        let host = self
+            .find(alias)
+            .cloned()
+            .ok_or_else(|| SkmError::HostNotFound(alias.to_string()))?;
+        let (start, end) = host.lines;
+
+        if host.patterns.len() > 1 {
+            let remaining: Vec<&str> = host
+                .patterns
+                .iter()
+                .map(String::as_str)
+                .filter(|p| *p != alias)
+                .collect();
+            let indent_len = self.lines[start].len() - self.lines[start].trim_start().len();
+            self.lines[start] = format!(
+                "{}Host {}",
+                &self.lines[start][..indent_len],
+                remaining.join(" ")
+            );
+        } else {
+            self.lines.drain(start..end);
+            // Don't leave a double blank line where the block used to be
+            while start > 0
+                && self
+                    .lines
+                    .get(start - 1)
+                    .is_some_and(|l| l.trim().is_empty())
+                && self.lines.get(start).is_none_or(|l| l.trim().is_empty())
+            {
+                self.lines.remove(start - 1);
+            }
+        }
+
+        self.save()?;
+        *self = Self::parse(self.path.clone(), &self.lines.join("\n"));
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut content = self.lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        // New config files get 0600; existing ones keep their permissions
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&self.path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Rewrite every `IdentityFile` in an ssh config that points at `old_path` so
+/// that it points at `new_path` instead. Returns the number of lines changed.
+pub fn rewrite_identity_files(
+    config_path: &Path,
+    old_path: &Path,
+    new_path: &Path,
+) -> Result<usize> {
+    if !config_path.exists() {
+        return Ok(0);
+    }
+
+    let old_name = file_name(old_path);
+    let new_name = file_name(new_path);
+    let content = std::fs::read_to_string(config_path)?;
+
+    let mut changed = 0;
+    let mut lines = Vec::new();
+
+    for line in content.lines() {
+        match identity_file_value(line) {
+            Some(value) if expand_path(value) == old_path && value.ends_with(old_name) => {
+                // Only swap the final path component so `~/` or `%d/` prefixes survive
+                let start = line.rfind(value).unwrap_or(0);
+                let prefix_len = start + value.len() - old_name.len();
+                let rewritten = format!(
+                    "{}{}{}",
+                    &line[..prefix_len],
+                    new_name,
+                    &line[start + value.len()..]
+                );
+                lines.push(rewritten);
+                changed += 1;
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    if changed > 0 {
+        let mut new_content = lines.join("\n");
+        if content.ends_with('\n') {
+            new_content.push('\n');
+        }
+        std::fs::write(config_path, new_content)?;
+    }
+
+    Ok(changed)
+}
+
+/// Split a config line into keyword and raw value, skipping blanks and comments
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    let split_at = trimmed
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(trimmed.len());
+    let (keyword, rest) = trimmed.split_at(split_at);
+    let value = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '=');
+
+    Some((keyword, value))
+}
+
+/// Extract the (unquoted) value of an `IdentityFile` directive
+fn identity_file_value(line: &str) -> Option<&str> {
+    let (keyword, value) = parse_directive(line)?;
+    if !keyword.eq_ignore_ascii_case("IdentityFile") {
+        return None;
+    }
+
+    let value = unquote(value);
+    (!value.is_empty()).then_some(value)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// The host a key search such as `@prod-bastion` asks about; `None` for
+/// searches by name, comment and the like
+pub fn search_alias(query: &str) -> Option<&str> {
+    query
+        .trim()
+        .strip_prefix('@')
+        .map(str::trim)
+        .filter(|alias| !alias.is_empty())
+}
+
+/// Expand `~` and `%d` (home directory) in an ssh config path
+pub fn expand_path(value: &str) -> PathBuf {
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+
+    match home {
+        Some(home) if value == "~" || value == "%d" => home,
+        Some(home) => match value
+            .strip_prefix("~/")
+            .or_else(|| value.strip_prefix("%d/"))
+        {
+            Some(rest) => home.join(rest),
+            None => PathBuf::from(value),
+        },
+        None => PathBuf::from(value),
+    }
+}
+
+/// Inverse of `expand_path`: write paths under the home directory as `~/...`
+pub fn contract_path(path: &Path) -> String {
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+
+    match home
+        .as_deref()
+        .and_then(|home| path.strip_prefix(home).ok())
+    {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_identity_file_value() {
+        assert_eq!(
+            identity_file_value("  IdentityFile ~/.ssh/id_rsa"),
+            Some("~/.ssh/id_rsa")
+        );
+        assert_eq!(
+            identity_file_value("identityfile=\"/a b/key\""),
+            Some("/a b/key")
+        );
+        assert_eq!(identity_file_value("HostName example.com"), None);
+    }
+
+    #[test]
+    fn test_rewrite_identity_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_key = temp_dir.path().join("old_key");
+        let new_key = temp_dir.path().join("new_key");
+        let config_path = temp_dir.path().join("config");
+
+        std::fs::write(
+            &config_path,
+            format!(
+                "Host work\n    IdentityFile {}\n\nHost other\n    IdentityFile {}/other_key\n",
+                old_key.display(),
+                temp_dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        let changed = rewrite_identity_files(&config_path, &old_key, &new_key).unwrap();
+        assert_eq!(changed, 1);
+
+        let content = std::fs::read_to_string(&config_path).unwrap();
+        assert!(content.contains(&format!("IdentityFile {}", new_key.display())));
+        assert!(content.contains("other_key"));
+        assert!(!content.contains("old_key"));
+    }
+
+    const SAMPLE: &str = "\
+# global options
+ServerAliveInterval 30
+
+Host web web.internal
+    HostName 10.0.0.5
+    User deploy
+    Port 2222
+    IdentityFile ~/.ssh/web_key
+
+Host *
+    IdentitiesOnly yes
+";
+
+    #[test]
+    fn test_parse_hosts() {
+        let config = SshConfig::parse(PathBuf::from("config"), SAMPLE);
+        assert_eq!(config.hosts.len(), 2);
+
+        let web = config.find("web.internal").unwrap();
+        assert_eq!(web.host_name.as_deref(), Some("10.0.0.5"));
+        assert_eq!(web.user.as_deref(), Some("deploy"));
+        assert_eq!(web.port, Some(2222));
+        assert_eq!(web.identity_files, vec!["~/.ssh/web_key"]);
+        assert!(!web.is_pattern());
+        assert!(config.hosts[1].is_pattern());
+    }
+
+    #[test]
+    fn test_add_and_remove_host() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config");
+        std::fs::write(&config_path, SAMPLE).unwrap();
+
+        let mut config = SshConfig::load(&config_path).unwrap();
+        let mut entry = HostEntry::new("db");
+        entry.host_name = Some("db.example.com".to_string());
+        entry.identity_files.push("~/.ssh/db key".to_string());
+        config.add_host(entry).unwrap();
+
+        assert!(matches!(
+            config.add_host(HostEntry::new("web")),
+            Err(SkmError::HostAlreadyExists(_))
+        ));
+
+        let reloaded = SshConfig::load(&config_path).unwrap();
+        assert_eq!(
+            reloaded.find("db").unwrap().identity_files,
+            vec!["~/.ssh/db key"]
+        );
+
+        // Removing one alias of a multi-pattern block keeps the block
+        config.remove_host("web.internal").unwrap();
+        config.remove_host("db").unwrap();
+        let content = std::fs::read_to_string(&config_path).unwrap();
+        assert!(content.contains("Host web\n"));
+        assert!(!content.contains("db.example.com"));
+        assert!(content.contains("ServerAliveInterval 30"));
+        assert!(content.contains("Host *"));
+    }
+
+    #[test]
+    fn test_hosts_using() {
+        let content = "Host a\n  IdentityFile /keys/work\nHost b\n  IdentityFile /keys/work.pub\n\
+                       Host c\n  IdentityFile /keys/other\n";
+        let config = SshConfig::parse(PathBuf::from("config"), content);
+
+        let hosts = config.hosts_using(Path::new("/keys/work"), Path::new("/keys/work.pub"));
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.patterns[0].as_str()).collect();
+        assert_eq!(aliases, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_identities_for() {
+        let content = "Host github.com\n  IdentityFile /keys/github\n\
+                       Host *.corp !build.corp\n  IdentityFile /keys/corp\n\
+                       Host *\n  IdentityFile /keys/fallback\n";
+        let config = SshConfig::parse(PathBuf::from("/home/me/.ssh/config"), content);
+
+        assert_eq!(
+            config.identities_for("github.com"),
+            vec![
+                PathBuf::from("/keys/github"),
+                PathBuf::from("/keys/fallback")
+            ]
+        );
+        assert_eq!(
+            config.identities_for("git.corp"),
+            vec![PathBuf::from("/keys/corp"), PathBuf::from("/keys/fallback")]
+        );
+        assert_eq!(
+            config.identities_for("build.corp"),
+            vec![PathBuf::from("/keys/fallback")]
+        );
+
+        let empty = SshConfig::parse(PathBuf::from("/home/me/.ssh/config"), "");
+        assert_eq!(
+            empty.identities_for("example.com")[0],
+            PathBuf::from("/home/me/.ssh/id_rsa")
+        );
+    }
+
+    #[test]
+    fn test_search_alias() {
+        assert_eq!(search_alias("@prod-bastion"), Some("prod-bastion"));
+        assert_eq!(search_alias(" @ github.com "), Some("github.com"));
+        assert_eq!(search_alias("@"), None);
+        assert_eq!(search_alias("me@work"), None);
+    }
+
+    #[test]
+    fn test_add_host_before_catch_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config");
+        std::fs::write(&config_path, SAMPLE).unwrap();
+
+        let mut config = SshConfig::load(&config_path).unwrap();
+        config.add_host(HostEntry::new("db")).unwrap();
+
+        let aliases: Vec<String> = config.hosts.iter().map(|h| h.patterns.join(" ")).collect();
+        assert_eq!(aliases, vec!["web web.internal", "db", "*"]);
+    }
+}
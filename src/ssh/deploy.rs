@@ -0,0 +1,340 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use ssh_key::PublicKey;
+
+use crate::error::{Result, SkmError};
+use crate::migrate::RecordStore;
+
+/// Where successful deploys are recorded, relative to the skm data directory
+pub const DEPLOYMENTS_FILE: &str = "deployments.json";
+
+/// Remote side of a deploy, run under `sh` so it works whatever the login shell is.
+/// The key is read from stdin, which avoids quoting it into the command line.
+const DEPLOY_SCRIPT: &str = "umask 077; \
+    mkdir -p .ssh && touch .ssh/authorized_keys || exit 1; \
+    read -r key; \
+    if grep -qxF \"$key\" .ssh/authorized_keys; then echo skm:present; \
+    else \
+    if [ -s .ssh/authorized_keys ] && [ -n \"$(tail -c1 .ssh/authorized_keys)\" ]; then echo >> .ssh/authorized_keys; fi; \
+    printf \"%s\\n\" \"$key\" >> .ssh/authorized_keys && echo skm:added; \
+    fi";
+
+/// Remote side of a revoke. Rewrites the file in place so its mode and owner are kept.
+const REVOKE_SCRIPT: &str = "umask 077; \
+    read -r key; f=.ssh/authorized_keys; \
+    if [ -f $f ] && grep -qxF \"$key\" $f; then \
+    grep -vxF \"$key\" $f > $f.skm-tmp; cat $f.skm-tmp > $f && rm -f $f.skm-tmp && echo skm:removed; \
+    else echo skm:absent; fi";
+
+/// Read-only presence check. Matches on the key blob alone, so lines with
+/// options in front or a different comment still count.
+const CHECK_SCRIPT: &str = "read -r key; f=.ssh/authorized_keys; \
+    if [ -f $f ] && grep -qF \"$key\" $f; then echo skm:present; else echo skm:absent; fi";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeployTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub identity: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployOutcome {
+    Added,
+    AlreadyPresent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevokeOutcome {
+    Removed,
+    NotPresent,
+}
+
+impl DeployTarget {
+    /// Parse a `[user@]host` destination. A host or user starting with `-`
+    /// is refused, since ssh would take it as an option.
+    pub fn parse(destination: &str) -> Result<Self> {
+        let (user, host) = match destination.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host.to_string()),
+            None => (None, destination.to_string()),
+        };
+
+        let invalid = |part: &str| part.is_empty() || part.starts_with('-');
+        if invalid(&host) || user.as_deref().is_some_and(invalid) {
+            return Err(SkmError::Remote(format!(
+                "Invalid destination: '{}' (expected [user@]host)",
+                destination
+            )));
+        }
+
+        Ok(Self {
+            user,
+            host,
+            port: None,
+            identity: None,
+        })
+    }
+
+    pub fn destination(&self) -> String {
+        match self.user {
+            Some(ref user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// Arguments passed to `ssh` before the remote command. `--` ends the
+    /// options, so the destination is never read as one.
+    pub fn ssh_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(port) = self.port {
+            args.push("-p".to_string());
+            args.push(port.to_string());
+        }
+        if let Some(ref identity) = self.identity {
+            args.push("-i".to_string());
+            args.push(identity.to_string_lossy().to_string());
+            args.push("-o".to_string());
+            args.push("IdentitiesOnly=yes".to_string());
+        }
+        args.push("--".to_string());
+        args.push(self.destination());
+        args
+    }
+
+    /// The command line a deploy would run, for dry runs
+    pub fn describe(&self) -> String {
+        format!("ssh {} 'sh -c ...'", self.ssh_args().join(" "))
+    }
+
+    /// Append `public_key_line` to the remote authorized_keys unless it is already there
+    pub fn deploy(&self, public_key_line: &str) -> Result<DeployOutcome> {
+        let stdout = self.run_script(DEPLOY_SCRIPT, public_key_line)?;
+
+        if stdout.contains("skm:present") {
+            Ok(DeployOutcome::AlreadyPresent)
+        } else if stdout.contains("skm:added") {
+            Ok(DeployOutcome::Added)
+        } else {
+            Err(self.unexpected_response())
+        }
+    }
+
+    /// Remove `public_key_line` (matched exactly) from the remote authorized_keys
+    pub fn revoke(&self, public_key_line: &str) -> Result<RevokeOutcome> {
+        let stdout = self.run_script(REVOKE_SCRIPT, public_key_line)?;
+
+        if stdout.contains("skm:removed") {
+            Ok(RevokeOutcome::Removed)
+        } else if stdout.contains("skm:absent") {
+            Ok(RevokeOutcome::NotPresent)
+        } else {
+            Err(self.unexpected_response())
+        }
+    }
+
+    /// Whether `key` is in the remote authorized_keys. Never prompts, so it is
+    /// safe to run from the TUI: hosts needing a password or an unknown host
+    /// key fail instead.
+    pub fn check(&self, key: &PublicKey) -> Result<bool> {
+        let mut key = key.clone();
+        key.set_comment("");
+        let blob = key
+            .to_openssh()
+            .map_err(|e| SkmError::SshKey(e.to_string()))?;
+
+        let stdout = self.run_batch_script(CHECK_SCRIPT, &blob)?;
+        if stdout.contains("skm:present") {
+            Ok(true)
+        } else if stdout.contains("skm:absent") {
+            Ok(false)
+        } else {
+            Err(self.unexpected_response())
+        }
+    }
+
+    /// Run `script` on the remote host with `line` on its stdin, returning stdout
+    fn run_script(&self, script: &str, line: &str) -> Result<String> {
+        let mut command = Command::new("ssh");
+        command.args(self.ssh_args()).stderr(Stdio::inherit());
+        self.run(command, script, line)
+    }
+
+    /// Like `run_script`, but without prompts or terminal output
+    fn run_batch_script(&self, script: &str, line: &str) -> Result<String> {
+        let mut command = Command::new("ssh");
+        command
+            .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+            .args(self.ssh_args())
+            .stderr(Stdio::null());
+        self.run(command, script, line)
+    }
+
+    fn run(&self, mut command: Command, script: &str, line: &str) -> Result<String> {
+        let mut child = command
+            .arg(format!("sh -c '{}'", script))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| SkmError::Remote(format!("Failed to run ssh: {}", e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{}", line.trim())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(SkmError::Remote(format!(
+                "ssh to {} failed ({})",
+                self.destination(),
+                output.status
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn unexpected_response(&self) -> SkmError {
+        SkmError::Remote(format!("Unexpected response from {}", self.destination()))
+    }
+}
+
+/// A key that `skm deploy` authorized on a host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub destination: String,
+    pub port: Option<u16>,
+    /// Key used to log in for the deploy, needed again to check the host
+    pub identity: Option<PathBuf>,
+    /// Identifies the key across renames
+    pub fingerprint: String,
+    /// Name of the key when it was deployed
+    pub key_name: String,
+    pub deployed_at: DateTime<Local>,
+}
+
+impl Deployment {
+    pub fn target(&self) -> Result<DeployTarget> {
+        let mut target = DeployTarget::parse(&self.destination)?;
+        target.port = self.port;
+        target.identity = self.identity.clone();
+        Ok(target)
+    }
+
+    /// `user@host` plus the port when it isn't the default
+    pub fn describe(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", self.destination, port),
+            None => self.destination.clone(),
+        }
+    }
+}
+
+/// Record of deploys, so a key's hosts can be checked again later
+pub type DeploymentStore = RecordStore<Deployment>;
+
+impl DeploymentStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(DEPLOYMENTS_FILE))
+    }
+
+    /// Add a deploy, replacing an earlier one of the same key to the same host
+    pub fn record(&self, deployment: Deployment) -> Result<()> {
+        self.update(|deployments| {
+            deployments.retain(|d| {
+                d.fingerprint != deployment.fingerprint
+                    || d.destination != deployment.destination
+                    || d.port != deployment.port
+            });
+            deployments.push(deployment);
+        })
+    }
+
+    /// Hosts the key with this fingerprint was deployed to
+    pub fn for_key(&self, fingerprint: &str) -> Result<Vec<Deployment>> {
+        let mut deployments = self.load()?;
+        deployments.retain(|d| d.fingerprint == fingerprint);
+        Ok(deployments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_destination() {
+        let target = DeployTarget::parse("deploy@example.com").unwrap();
+        assert_eq!(target.user.as_deref(), Some("deploy"));
+        assert_eq!(target.host, "example.com");
+
+        let target = DeployTarget::parse("example.com").unwrap();
+        assert_eq!(target.user, None);
+
+        assert!(DeployTarget::parse("user@").is_err());
+        assert!(DeployTarget::parse("@host").is_err());
+        assert!(DeployTarget::parse("-oProxyCommand=sh").is_err());
+        assert!(DeployTarget::parse("-oProxyCommand=sh@host").is_err());
+        assert!(DeployTarget::parse("me@-host").is_err());
+    }
+
+    #[test]
+    fn test_ssh_args() {
+        let mut target = DeployTarget::parse("me@host").unwrap();
+        target.port = Some(2222);
+        target.identity = Some(PathBuf::from("/keys/admin"));
+
+        assert_eq!(
+            target.ssh_args(),
+            vec![
+                "-p",
+                "2222",
+                "-i",
+                "/keys/admin",
+                "-o",
+                "IdentitiesOnly=yes",
+                "--",
+                "me@host"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deployment_store() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = DeploymentStore::new(temp_dir.path().join("data"));
+        assert!(store.for_key("SHA256:abc").unwrap().is_empty());
+
+        let deployment = Deployment {
+            destination: "me@host".to_string(),
+            port: Some(2222),
+            identity: None,
+            fingerprint: "SHA256:abc".to_string(),
+            key_name: "id_ed25519".to_string(),
+            deployed_at: Local::now(),
+        };
+        store.record(deployment.clone()).unwrap();
+        store
+            .record(Deployment {
+                key_name: "work".to_string(),
+                ..deployment.clone()
+            })
+            .unwrap();
+        store
+            .record(Deployment {
+                fingerprint: "SHA256:other".to_string(),
+                ..deployment.clone()
+            })
+            .unwrap();
+
+        let deployments = store.for_key("SHA256:abc").unwrap();
+        assert_eq!(deployments.len(), 1);
+        assert_eq!(deployments[0].key_name, "work");
+        assert_eq!(deployments[0].describe(), "me@host:2222");
+        assert_eq!(deployments[0].target().unwrap().port, Some(2222));
+    }
+}
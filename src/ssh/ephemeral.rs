@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use ssh_key::{Algorithm, HashAlg, PrivateKey};
+
+use crate::error::{Result, SkmError};
+use crate::migrate::RecordStore;
+use crate::ssh::deploy::DeployTarget;
+
+/// Where grants are recorded, relative to the skm data directory
+pub const EPHEMERAL_FILE: &str = "ephemeral.json";
+
+/// Longest TTL accepted; ephemeral keys are meant for short-lived access
+const MAX_TTL_SECS: u32 = 30 * 24 * 60 * 60;
+
+/// A throwaway key that was authorized on a host for a limited time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EphemeralGrant {
+    pub destination: String,
+    pub port: Option<u16>,
+    /// Key used to log in for the grant, needed again to remove it
+    pub identity: Option<PathBuf>,
+    pub fingerprint: String,
+    /// Exact line added to the remote authorized_keys, needed to remove it again
+    pub authorized_line: String,
+    pub created_at: DateTime<Local>,
+    pub expires_at: DateTime<Local>,
+}
+
+impl EphemeralGrant {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Local::now()
+    }
+
+    /// Deploy target to use for removing the key again
+    pub fn target(&self) -> Result<DeployTarget> {
+        let mut target = DeployTarget::parse(&self.destination)?;
+        target.port = self.port;
+        target.identity = self.identity.clone();
+        Ok(target)
+    }
+}
+
+/// Record of ephemeral grants, kept so expired keys can be cleaned up later
+pub type EphemeralStore = RecordStore<EphemeralGrant>;
+
+impl EphemeralStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(EPHEMERAL_FILE))
+    }
+
+    pub fn record(&self, grant: EphemeralGrant) -> Result<()> {
+        self.update(|grants| grants.push(grant))
+    }
+}
+
+/// Generate an Ed25519 key that only ever lives in memory
+pub fn generate_key(comment: &str) -> Result<PrivateKey> {
+    let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519)
+        .map_err(|e| SkmError::SshKey(e.to_string()))?;
+    key.set_comment(comment);
+    Ok(key)
+}
+
+/// authorized_keys line for `key` that sshd itself stops accepting at
+/// `expires_at` (`expiry-time`, OpenSSH 8.2+), even if cleanup never runs
+pub fn authorized_line(key: &PrivateKey, expires_at: DateTime<Local>) -> Result<String> {
+    let public = key
+        .public_key()
+        .to_openssh()
+        .map_err(|e| SkmError::SshKey(e.to_string()))?;
+    // Round up to the next minute so the server never cuts access short
+    let expiry = (expires_at.with_timezone(&Utc) + Duration::seconds(59)).format("%Y%m%d%H%MZ");
+    Ok(format!("expiry-time=\"{}\" {}", expiry, public))
+}
+
+pub fn fingerprint(key: &PrivateKey) -> String {
+    key.public_key().fingerprint(HashAlg::Sha256).to_string()
+}
+
+/// Parse a TTL such as `90s`, `30m`, `1h` or `2d` into seconds
+pub fn parse_ttl(ttl: &str) -> Result<u32> {
+    let ttl = ttl.trim();
+    let invalid = || SkmError::Config(format!("Invalid TTL '{}' (expected e.g. 30m, 1h, 2d)", ttl));
+
+    let split = ttl.find(|c: char| !c.is_ascii_digit()).unwrap_or(ttl.len());
+    let (number, unit) = ttl.split_at(split);
+    let number: u32 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    let seconds = number.checked_mul(multiplier).ok_or_else(invalid)?;
+    if seconds == 0 || seconds > MAX_TTL_SECS {
+        return Err(SkmError::Config(format!(
+            "TTL must be between 1s and {}d",
+            MAX_TTL_SECS / (24 * 60 * 60)
+        )));
+    }
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("45").unwrap(), 45);
+        assert_eq!(parse_ttl("30m").unwrap(), 1800);
+        assert_eq!(parse_ttl("1h").unwrap(), 3600);
+        assert_eq!(parse_ttl("2d").unwrap(), 172800);
+
+        assert!(parse_ttl("0h").is_err());
+        assert!(parse_ttl("1w").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("31d").is_err());
+    }
+
+    #[test]
+    fn test_authorized_line_has_expiry() {
+        let key = generate_key("skm-ephemeral").unwrap();
+        let expires_at = DateTime::parse_from_rfc3339("2030-01-02T03:04:30Z")
+            .unwrap()
+            .with_timezone(&Local);
+
+        let line = authorized_line(&key, expires_at).unwrap();
+        assert!(line.starts_with("expiry-time=\"203001020305Z\" ssh-ed25519 "));
+        assert!(line.ends_with(" skm-ephemeral"));
+        assert!(line.parse::<ssh_key::authorized_keys::Entry>().is_ok());
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = EphemeralStore::new(temp_dir.path().join("data"));
+        assert!(store.load().unwrap().is_empty());
+
+        let grant = EphemeralGrant {
+            destination: "me@host".to_string(),
+            port: Some(2222),
+            identity: Some(PathBuf::from("/keys/admin")),
+            fingerprint: "SHA256:abc".to_string(),
+            authorized_line: "ssh-ed25519 AAAA".to_string(),
+            created_at: Local::now() - Duration::hours(2),
+            expires_at: Local::now() - Duration::hours(1),
+        };
+        store.record(grant.clone()).unwrap();
+
+        let grants = store.load().unwrap();
+        assert_eq!(grants, vec![grant]);
+        assert!(grants[0].is_expired());
+        let target = grants[0].target().unwrap();
+        assert_eq!(target.port, Some(2222));
+        assert_eq!(target.identity, Some(PathBuf::from("/keys/admin")));
+    }
+}
@@ -0,0 +1,304 @@
+//! Key expiry: keys older than a maximum age are due for rotation
+//!
+//! A key's age counts from when its file was created, or last modified on
+//! filesystems that don't record creation times. Changing the passphrase,
+//! restoring from the trash and importing write the key anew, so the file's
+//! age from before is recorded by fingerprint in [`KeyAgeStore`] first, and an
+//! imported key is at least as old as its backup.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ExpirySettings;
+use crate::crypto::provenance::ProvenanceStore;
+use crate::crypto::schedule::{format_interval, parse_interval};
+use crate::dates;
+use crate::error::Result;
+use crate::migrate::RecordStore;
+use crate::ssh::SshKey;
+
+/// Where key ages that outlive rewrites are kept, relative to the skm data directory
+pub const KEY_AGES_FILE: &str = "key_ages.json";
+
+/// How long before expiry keys are flagged when `warn_before` isn't set
+pub const DEFAULT_WARN_BEFORE: u64 = 30 * 24 * 60 * 60;
+
+/// Per-key setting that exempts a key from the global maximum age
+const NEVER: &str = "never";
+
+/// Maximum key ages from the `[expiry]` settings; all in seconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiryPolicy {
+    max_age: Option<u64>,
+    warn_before: u64,
+    /// By key name; `None` for keys that never expire
+    per_key: BTreeMap<String, Option<u64>>,
+    /// Earliest time each key is known to have existed, by fingerprint
+    known_ages: BTreeMap<String, DateTime<Local>>,
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            warn_before: DEFAULT_WARN_BEFORE,
+            per_key: BTreeMap::new(),
+            known_ages: BTreeMap::new(),
+        }
+    }
+}
+
+/// A key's earliest known time, by SHA256 fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyAge {
+    pub fingerprint: String,
+    pub born: DateTime<Local>,
+}
+
+/// Ages of keys whose files were written anew, so rewriting a key doesn't
+/// make it young again
+pub type KeyAgeStore = RecordStore<KeyAge>;
+
+impl KeyAgeStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(KEY_AGES_FILE))
+    }
+
+    /// Record how old `keys` are now, before their files are rewritten. An
+    /// earlier record of a key is kept.
+    pub fn remember(&self, keys: &[SshKey]) -> Result<()> {
+        self.update(|records| {
+            for key in keys {
+                let (Some(fingerprint), Some(born)) =
+                    (&key.fingerprint, key.created_at.or(key.modified_at))
+                else {
+                    continue;
+                };
+                match records.iter_mut().find(|r| r.fingerprint == *fingerprint) {
+                    Some(record) => record.born = record.born.min(born),
+                    None => records.push(KeyAge {
+                        fingerprint: fingerprint.clone(),
+                        born,
+                    }),
+                }
+            }
+        })
+    }
+}
+
+/// Where a key stands, with the time it expires (or expired)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    Valid(DateTime<Local>),
+    Soon(DateTime<Local>),
+    Expired(DateTime<Local>),
+}
+
+impl Expiry {
+    pub fn expires_at(&self) -> DateTime<Local> {
+        match *self {
+            Expiry::Valid(at) | Expiry::Soon(at) | Expiry::Expired(at) => at,
+        }
+    }
+
+    /// e.g. `expires in 3 months` or `expired 2 days ago`
+    pub fn describe(&self, now: DateTime<Local>) -> String {
+        let when = dates::relative(self.expires_at(), now);
+        match self {
+            Expiry::Expired(_) => format!("expired {}", when),
+            Expiry::Valid(_) | Expiry::Soon(_) => format!("expires {}", when),
+        }
+    }
+}
+
+impl ExpiryPolicy {
+    pub fn from_settings(settings: &ExpirySettings) -> Result<Self> {
+        let per_key = settings
+            .keys
+            .iter()
+            .map(|(name, age)| {
+                let age = match age.trim() {
+                    NEVER => None,
+                    age => Some(parse_interval(age)?),
+                };
+                Ok((name.clone(), age))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            max_age: settings
+                .max_age
+                .as_deref()
+                .map(parse_interval)
+                .transpose()?,
+            warn_before: settings
+                .warn_before
+                .as_deref()
+                .map(parse_interval)
+                .transpose()?
+                .unwrap_or(DEFAULT_WARN_BEFORE),
+            per_key,
+            known_ages: BTreeMap::new(),
+        })
+    }
+
+    /// Also count the ages recorded under `data_dir`: the ones kept before a
+    /// key was rewritten, and the backup dates of imported keys
+    pub fn with_known_ages(mut self, data_dir: &Path) -> Result<Self> {
+        let recorded = KeyAgeStore::new(data_dir)
+            .load()?
+            .into_iter()
+            .map(|age| (age.fingerprint, age.born));
+        let imported = ProvenanceStore::new(data_dir)
+            .load()?
+            .into_iter()
+            .filter_map(|p| Some((p.fingerprint, p.backup_created_at?)));
+
+        for (fingerprint, born) in recorded.chain(imported) {
+            self.known_ages
+                .entry(fingerprint)
+                .and_modify(|earliest| *earliest = (*earliest).min(born))
+                .or_insert(born);
+        }
+        Ok(self)
+    }
+
+    /// Replace the maximum age for keys without one of their own
+    pub fn with_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Whether any key can expire at all
+    pub fn is_set(&self) -> bool {
+        self.max_age.is_some() || self.per_key.values().any(Option::is_some)
+    }
+
+    /// Maximum age of `key`, e.g. `365d`; `None` when it doesn't expire
+    pub fn max_age(&self, key: &SshKey) -> Option<u64> {
+        match self.per_key.get(&key.name) {
+            Some(age) => *age,
+            None => self.max_age,
+        }
+    }
+
+    /// `None` when the key doesn't expire or its age is unknown
+    pub fn check(&self, key: &SshKey, now: DateTime<Local>) -> Option<Expiry> {
+        let max_age = i64::try_from(self.max_age(key)?).ok()?;
+        let born = self.born(key)?;
+        let expires_at = born + Duration::seconds(max_age);
+
+        let left = expires_at.signed_duration_since(now).num_seconds();
+        Some(if left <= 0 {
+            Expiry::Expired(expires_at)
+        } else if left <= i64::try_from(self.warn_before).unwrap_or(i64::MAX) {
+            Expiry::Soon(expires_at)
+        } else {
+            Expiry::Valid(expires_at)
+        })
+    }
+
+    /// The earlier of the key file's time and any recorded age
+    fn born(&self, key: &SshKey) -> Option<DateTime<Local>> {
+        let recorded = key
+            .fingerprint
+            .as_ref()
+            .and_then(|fingerprint| self.known_ages.get(fingerprint))
+            .copied();
+        key.created_at
+            .or(key.modified_at)
+            .into_iter()
+            .chain(recorded)
+            .min()
+    }
+
+    /// `max_age` as set, e.g. `52w`, for messages
+    pub fn describe_max_age(&self, key: &SshKey) -> Option<String> {
+        self.max_age(key).map(format_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_expiry_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("id_ed25519");
+        std::fs::write(&path, "private").unwrap();
+        let mut key = SshKey::from_path(&path).unwrap();
+        let now = Local::now();
+
+        let settings = ExpirySettings {
+            max_age: Some("365d".to_string()),
+            warn_before: None,
+            keys: BTreeMap::from([
+                ("deploy".to_string(), "90d".to_string()),
+                ("legacy".to_string(), "never".to_string()),
+            ]),
+        };
+        let policy = ExpiryPolicy::from_settings(&settings).unwrap();
+        assert!(policy.is_set());
+
+        key.created_at = Some(now - Duration::days(10));
+        assert!(matches!(policy.check(&key, now), Some(Expiry::Valid(_))));
+        key.created_at = Some(now - Duration::days(350));
+        let soon = policy.check(&key, now).unwrap();
+        assert!(matches!(soon, Expiry::Soon(_)));
+        assert_eq!(soon.describe(now), "expires in 15 days");
+        key.created_at = Some(now - Duration::days(400));
+        let expired = policy.check(&key, now).unwrap();
+        assert!(matches!(expired, Expiry::Expired(_)));
+        assert_eq!(expired.describe(now), "expired 1 month ago");
+
+        // Per-key settings win over the global one
+        key.name = "deploy".to_string();
+        key.created_at = Some(now - Duration::days(100));
+        assert!(matches!(policy.check(&key, now), Some(Expiry::Expired(_))));
+        key.name = "legacy".to_string();
+        assert_eq!(policy.check(&key, now), None);
+        assert_eq!(policy.clone().with_max_age(86400).check(&key, now), None);
+
+        assert!(!ExpiryPolicy::default().is_set());
+        let invalid = ExpirySettings {
+            max_age: Some("1y".to_string()),
+            ..ExpirySettings::default()
+        };
+        assert!(ExpiryPolicy::from_settings(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_recorded_age_survives_rewrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("id_ed25519");
+        std::fs::write(&path, "private").unwrap();
+        let mut key = SshKey::from_path(&path).unwrap();
+        key.fingerprint = Some("SHA256:abc".to_string());
+        let now = Local::now();
+
+        // Recorded before the file was written anew; a later record doesn't
+        // replace it
+        let store = KeyAgeStore::new(temp_dir.path().join("data"));
+        key.created_at = Some(now - Duration::days(400));
+        store.remember(std::slice::from_ref(&key)).unwrap();
+        key.created_at = Some(now - Duration::days(1));
+        store.remember(std::slice::from_ref(&key)).unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        let policy = ExpiryPolicy::default().with_max_age(365 * 24 * 60 * 60);
+        assert!(matches!(policy.check(&key, now), Some(Expiry::Valid(_))));
+        let policy = policy
+            .with_known_ages(&temp_dir.path().join("data"))
+            .unwrap();
+        assert!(matches!(policy.check(&key, now), Some(Expiry::Expired(_))));
+
+        // Other keys go by their file
+        key.fingerprint = Some("SHA256:other".to_string());
+        assert!(matches!(policy.check(&key, now), Some(Expiry::Valid(_))));
+    }
+}
@@ -1,5 +1,6 @@
 use rand::rngs::OsRng;
-use ssh_key::{Algorithm, PrivateKey};
+use ssh_key::private::{KeypairData, RsaKeypair};
+use ssh_key::{Algorithm, EcdsaCurve, PrivateKey};
 use std::fs::OpenOptions;
 use std::io::Write;
 #[cfg(unix)]
@@ -9,6 +10,29 @@ use std::path::{Path, PathBuf};
 use crate::error::{Result, SkmError};
 use crate::ssh::keys::{KeyType, SshKey};
 
+/// RSA sizes offered; 4096 unless asked otherwise
+pub const RSA_BITS: &[u32] = &[2048, 3072, 4096];
+/// ECDSA sizes, one per NIST curve, as `ssh-keygen -b` takes them
+pub const ECDSA_BITS: &[u32] = &[256, 384, 521];
+
+/// Sizes a key type can be generated in; empty for a fixed size
+pub fn bit_choices(key_type: KeyType) -> &'static [u32] {
+    match key_type {
+        KeyType::Rsa => RSA_BITS,
+        KeyType::Ecdsa => ECDSA_BITS,
+        _ => &[],
+    }
+}
+
+/// Size used when none is given, as ssh-keygen would for RSA but larger
+pub fn default_bits(key_type: KeyType) -> Option<u32> {
+    match key_type {
+        KeyType::Rsa => Some(4096),
+        KeyType::Ecdsa => Some(256),
+        _ => None,
+    }
+}
+
 pub struct KeyGenerator {
     ssh_dir: PathBuf,
 }
@@ -34,6 +58,46 @@ impl Default for KeyGenOptions {
     }
 }
 
+/// Everything `generate` would write, worked out without touching the disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationPlan {
+    pub key_type: KeyType,
+    pub bits: Option<u32>,
+    pub private_path: PathBuf,
+    pub public_path: PathBuf,
+    pub comment: String,
+    pub encrypted: bool,
+}
+
+impl GenerationPlan {
+    pub const PRIVATE_MODE: u32 = 0o600;
+    pub const PUBLIC_MODE: u32 = 0o644;
+
+    /// The ssh-keygen invocation that makes the same key. The passphrase is
+    /// left out so it never ends up in shell history; ssh-keygen prompts
+    /// for it instead.
+    pub fn ssh_keygen_command(&self) -> String {
+        let mut args = vec![
+            "ssh-keygen".to_string(),
+            "-t".to_string(),
+            self.key_type.to_string().to_lowercase(),
+        ];
+        if let Some(bits) = self.bits {
+            args.extend(["-b".to_string(), bits.to_string()]);
+        }
+        args.extend([
+            "-f".to_string(),
+            shell_quote(&self.private_path.to_string_lossy()),
+            "-C".to_string(),
+            shell_quote(&self.comment),
+        ]);
+        if !self.encrypted {
+            args.extend(["-N".to_string(), "''".to_string()]);
+        }
+        args.join(" ")
+    }
+}
+
 impl KeyGenerator {
     pub fn new<P: AsRef<Path>>(ssh_dir: P) -> Self {
         Self {
@@ -41,7 +105,8 @@ impl KeyGenerator {
         }
     }
 
-    pub fn generate(&self, options: KeyGenOptions) -> Result<SshKey> {
+    /// Check `options` the way `generate` does and describe the result
+    pub fn plan(&self, options: &KeyGenOptions) -> Result<GenerationPlan> {
         let private_path = self.ssh_dir.join(&options.filename);
         let public_path = private_path.with_extension("pub");
 
@@ -51,12 +116,24 @@ impl KeyGenerator {
             ));
         }
 
-        let (private_key, public_key) = match options.key_type {
-            KeyType::Ed25519 => self.generate_ed25519()?,
-            KeyType::Rsa => {
-                return Err(SkmError::SshKey(
-                    "RSA generation not yet implemented".to_string(),
-                ));
+        let bits = match options.key_type {
+            KeyType::Ed25519 => None,
+            KeyType::Rsa | KeyType::Ecdsa => {
+                let bits = options.bits.or(default_bits(options.key_type));
+                let choices = bit_choices(options.key_type);
+                if let Some(bits) = bits.filter(|bits| !choices.contains(bits)) {
+                    return Err(SkmError::SshKey(format!(
+                        "{} keys can't be {} bits (expected one of {})",
+                        options.key_type,
+                        bits,
+                        choices
+                            .iter()
+                            .map(u32::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )));
+                }
+                bits
             }
             _ => {
                 return Err(SkmError::SshKey(format!(
@@ -66,32 +143,50 @@ impl KeyGenerator {
             }
         };
 
+        Ok(GenerationPlan {
+            key_type: options.key_type,
+            bits,
+            private_path,
+            public_path,
+            comment: options.comment.clone(),
+            encrypted: options.passphrase.as_deref().is_some_and(|p| !p.is_empty()),
+        })
+    }
+
+    pub fn generate(&self, options: KeyGenOptions) -> Result<SshKey> {
+        let plan = self.plan(&options)?;
+        let private_key = generate_private_key(plan.key_type, plan.bits)?;
+        let public_key = private_key.public_key().clone();
+
         // Write private key
-        self.write_private_key(&private_path, &private_key, options.passphrase.as_deref())?;
+        let passphrase = options.passphrase.as_deref().filter(|_| plan.encrypted);
+        self.write_private_key(&plan.private_path, &private_key, passphrase)?;
 
-        // Write public key
-        let public_key_openssh = public_key
+        // Write public key (to_openssh already yields "<algorithm> <base64>")
+        let public_content = public_key
             .to_openssh()
             .map_err(|e| SkmError::SshKey(e.to_string()))?;
-        let public_content = format!("{} {}", public_key.algorithm(), public_key_openssh);
-        self.write_public_key(&public_path, &public_content, &options.comment)?;
+        self.write_public_key(&plan.public_path, &public_content, &plan.comment)?;
 
-        SshKey::from_path(&private_path)
-    }
-
-    fn generate_ed25519(&self) -> Result<(PrivateKey, ssh_key::PublicKey)> {
-        let private_key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519)
-            .map_err(|e| SkmError::SshKey(e.to_string()))?;
-        let public_key = private_key.public_key().clone();
-        Ok((private_key, public_key))
+        SshKey::from_path(&plan.private_path)
     }
 
     fn write_private_key(
         &self,
         path: &Path,
         key: &PrivateKey,
-        _passphrase: Option<&str>,
+        passphrase: Option<&str>,
     ) -> Result<()> {
+        let encrypted;
+        let key = match passphrase {
+            Some(passphrase) => {
+                encrypted = key
+                    .encrypt(&mut OsRng, passphrase)
+                    .map_err(|e| SkmError::SshKey(e.to_string()))?;
+                &encrypted
+            }
+            None => key,
+        };
         let pem = key
             .to_openssh(ssh_key::LineEnding::default())
             .map_err(|e| SkmError::SshKey(e.to_string()))?;
@@ -144,6 +239,37 @@ impl KeyGenerator {
     }
 }
 
+/// A new key of a type and size `plan` has checked
+fn generate_private_key(key_type: KeyType, bits: Option<u32>) -> Result<PrivateKey> {
+    let key = match (key_type, bits) {
+        (KeyType::Rsa, Some(bits)) => RsaKeypair::random(&mut OsRng, bits as usize)
+            .and_then(|keypair| PrivateKey::new(KeypairData::from(keypair), "")),
+        (KeyType::Ecdsa, Some(bits)) => {
+            let curve = match bits {
+                384 => EcdsaCurve::NistP384,
+                521 => EcdsaCurve::NistP521,
+                _ => EcdsaCurve::NistP256,
+            };
+            PrivateKey::random(&mut OsRng, Algorithm::Ecdsa { curve })
+        }
+        _ => PrivateKey::random(&mut OsRng, Algorithm::Ed25519),
+    };
+    key.map_err(|e| SkmError::SshKey(e.to_string()))
+}
+
+/// Single-quote `value` for a POSIX shell unless it is plainly safe
+fn shell_quote(value: &str) -> String {
+    let safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c));
+    if safe {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
 fn get_username() -> String {
     std::env::var("USER")
         .or_else(|_| std::env::var("USERNAME"))
@@ -188,6 +314,103 @@ mod tests {
         assert_eq!(key.key_type, KeyType::Ed25519);
         assert!(key.path.exists());
         assert!(key.public_path.exists());
+
+        let public = std::fs::read_to_string(&key.public_path).unwrap();
+        let public_key = ssh_key::PublicKey::from_openssh(&public).unwrap();
+        assert_eq!(public_key.comment(), "test@example.com");
+    }
+
+    #[test]
+    fn test_generate_encrypts_with_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let generator = KeyGenerator::new(temp_dir.path());
+
+        let opts = KeyGenOptions {
+            filename: "id_locked".to_string(),
+            passphrase: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        let key = generator.generate(opts).unwrap();
+        assert!(key.is_encrypted());
+        assert!(key.load_private_key(Some("secret")).is_ok());
+    }
+
+    #[test]
+    fn test_plan_and_ssh_keygen_command() {
+        let temp_dir = TempDir::new().unwrap();
+        let generator = KeyGenerator::new(temp_dir.path());
+
+        let opts = KeyGenOptions {
+            filename: "id_work".to_string(),
+            comment: "me at work".to_string(),
+            ..Default::default()
+        };
+
+        let plan = generator.plan(&opts).unwrap();
+        assert_eq!(plan.private_path, temp_dir.path().join("id_work"));
+        assert_eq!(plan.public_path, temp_dir.path().join("id_work.pub"));
+        assert!(!plan.encrypted);
+        assert_eq!(
+            plan.ssh_keygen_command(),
+            format!(
+                "ssh-keygen -t ed25519 -f {} -C 'me at work' -N ''",
+                shell_quote(&plan.private_path.to_string_lossy())
+            )
+        );
+        // Planning writes nothing
+        assert!(!plan.private_path.exists());
+
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_generate_ecdsa() {
+        let temp_dir = TempDir::new().unwrap();
+        let generator = KeyGenerator::new(temp_dir.path());
+
+        let opts = KeyGenOptions {
+            key_type: KeyType::Ecdsa,
+            filename: "id_ecdsa".to_string(),
+            bits: Some(384),
+            ..Default::default()
+        };
+        let plan = generator.plan(&opts).unwrap();
+        assert!(
+            plan.ssh_keygen_command()
+                .starts_with("ssh-keygen -t ecdsa -b 384 ")
+        );
+
+        let key = generator.generate(opts).unwrap();
+        assert_eq!(key.key_type, KeyType::Ecdsa);
+        let public = std::fs::read_to_string(temp_dir.path().join("id_ecdsa.pub")).unwrap();
+        assert!(public.starts_with("ecdsa-sha2-nistp384 "));
+    }
+
+    #[test]
+    fn test_plan_checks_bits() {
+        let temp_dir = TempDir::new().unwrap();
+        let generator = KeyGenerator::new(temp_dir.path());
+
+        let rsa = KeyGenOptions {
+            key_type: KeyType::Rsa,
+            filename: "id_rsa".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(generator.plan(&rsa).unwrap().bits, Some(4096));
+
+        let small = KeyGenOptions {
+            bits: Some(1024),
+            ..rsa.clone()
+        };
+        assert!(generator.plan(&small).is_err());
+
+        // Ed25519 keys have one size, whatever is asked for
+        let ed25519 = KeyGenOptions {
+            bits: Some(4096),
+            ..Default::default()
+        };
+        assert_eq!(generator.plan(&ed25519).unwrap().bits, None);
     }
 
     #[test]
@@ -0,0 +1,370 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use ssh_key::{Certificate, PublicKey};
+
+use crate::error::Result;
+use crate::ssh::cert::CertInfo;
+use crate::ssh::convert::{self, ParsedKey};
+use crate::ssh::keys::{FingerprintHash, fingerprint_with, key_bits, read_key_text};
+use crate::ssh::known_hosts::KnownHost;
+
+/// Options sshd accepts in front of an authorized_keys entry. Anything else in
+/// that position is taken for a known_hosts host list.
+const AUTHORIZED_KEYS_OPTIONS: &[&str] = &[
+    "agent-forwarding",
+    "cert-authority",
+    "command",
+    "environment",
+    "expiry-time",
+    "from",
+    "no-agent-forwarding",
+    "no-port-forwarding",
+    "no-pty",
+    "no-touch-required",
+    "no-user-rc",
+    "no-x11-forwarding",
+    "permitlisten",
+    "permitopen",
+    "port-forwarding",
+    "principals",
+    "pty",
+    "restrict",
+    "tunnel",
+    "user-rc",
+    "verify-required",
+    "x11-forwarding",
+];
+
+/// What a line (or the whole file, for PEM blocks) turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    PublicKey,
+    Certificate,
+    AuthorizedKey,
+    KnownHost,
+    PrivateKey,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::PublicKey => write!(f, "public key"),
+            EntryKind::Certificate => write!(f, "certificate"),
+            EntryKind::AuthorizedKey => write!(f, "authorized_keys entry"),
+            EntryKind::KnownHost => write!(f, "known_hosts entry"),
+            EntryKind::PrivateKey => write!(f, "private key"),
+        }
+    }
+}
+
+/// One key found in an inspected file
+#[derive(Debug, Clone)]
+pub struct InspectedEntry {
+    /// 1-based line number; 1 for multi-line PEM blocks
+    pub line: usize,
+    pub kind: EntryKind,
+    /// Algorithm name, e.g. `ssh-ed25519`
+    pub algorithm: String,
+    pub bits: Option<u32>,
+    pub fingerprint: String,
+    pub comment: Option<String>,
+    /// authorized_keys options such as `no-pty` or `from="10.0.0.0/8"`
+    pub options: Vec<String>,
+    /// known_hosts host names, or the hashed token
+    pub hosts: Vec<String>,
+    /// known_hosts `@cert-authority` / `@revoked`
+    pub marker: Option<String>,
+    pub cert: Option<CertInfo>,
+    /// Private keys only: whether a passphrase protects it
+    pub encrypted: bool,
+}
+
+impl InspectedEntry {
+    fn new(line: usize, kind: EntryKind, key: &PublicKey, hash: FingerprintHash) -> Self {
+        Self {
+            line,
+            kind,
+            algorithm: key.algorithm().to_string(),
+            bits: key_bits(key.key_data()),
+            fingerprint: fingerprint_with(key, hash),
+            comment: Some(key.comment().to_string()).filter(|c| !c.is_empty()),
+            options: Vec::new(),
+            hosts: Vec::new(),
+            marker: None,
+            cert: None,
+            encrypted: false,
+        }
+    }
+}
+
+/// Structured view of a file of unknown origin: .pub files, certificates,
+/// authorized_keys, known_hosts, or the public half of a private key
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    pub path: PathBuf,
+    pub entries: Vec<InspectedEntry>,
+    /// Lines that are neither blank, comments nor any known key format
+    pub unrecognised: Vec<usize>,
+}
+
+impl Inspection {
+    pub fn load(path: &Path, hash: FingerprintHash) -> Result<Self> {
+        let content = read_key_text(path)?;
+        Ok(Self::parse(path, &content, hash))
+    }
+
+    pub fn parse(path: &Path, content: &str, hash: FingerprintHash) -> Self {
+        let mut inspection = Self {
+            path: path.to_path_buf(),
+            entries: Vec::new(),
+            unrecognised: Vec::new(),
+        };
+
+        // PEM and RFC4716 blocks span lines; everything else is one key per line
+        if content.trim_start().starts_with("-----BEGIN") || content.contains("---- BEGIN SSH2") {
+            match convert::decode(content) {
+                Ok(ParsedKey::Private(key)) => {
+                    let mut entry =
+                        InspectedEntry::new(1, EntryKind::PrivateKey, key.public_key(), hash);
+                    entry.encrypted = key.is_encrypted();
+                    inspection.entries.push(entry);
+                }
+                Ok(ParsedKey::Public(key)) => {
+                    let entry = InspectedEntry::new(1, EntryKind::PublicKey, &key, hash);
+                    inspection.entries.push(entry);
+                }
+                Err(_) => inspection.unrecognised.push(1),
+            }
+            return inspection;
+        }
+
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match parse_line(path, index, trimmed, hash) {
+                Some(entry) => inspection.entries.push(entry),
+                None => inspection.unrecognised.push(index + 1),
+            }
+        }
+        inspection
+    }
+
+    /// The kind shared by every entry, if the file holds only one kind
+    pub fn kind(&self) -> Option<EntryKind> {
+        let first = self.entries.first()?.kind;
+        self.entries
+            .iter()
+            .all(|entry| entry.kind == first)
+            .then_some(first)
+    }
+
+    /// One line such as `3 authorized_keys entries, 1 line not recognised`
+    pub fn summary(&self) -> String {
+        let mut summary = match (self.entries.len(), self.kind()) {
+            (0, _) => "no keys found".to_string(),
+            (1, Some(kind)) => format!("1 {}", kind),
+            (count, Some(EntryKind::AuthorizedKey)) => {
+                format!("{} authorized_keys entries", count)
+            }
+            (count, Some(EntryKind::KnownHost)) => {
+                format!("{} known_hosts entries", count)
+            }
+            (count, Some(kind)) => format!("{} {}s", count, kind),
+            (count, None) => format!("{} keys of mixed kinds", count),
+        };
+        match self.unrecognised.len() {
+            0 => {}
+            1 => summary.push_str(", 1 line not recognised"),
+            n => summary.push_str(&format!(", {} lines not recognised", n)),
+        }
+        summary
+    }
+}
+
+fn parse_line(
+    path: &Path,
+    index: usize,
+    line: &str,
+    hash: FingerprintHash,
+) -> Option<InspectedEntry> {
+    if let Ok(cert) = Certificate::from_openssh(line) {
+        let mut key = PublicKey::from(cert.public_key().clone());
+        key.set_comment(cert.comment());
+        let mut entry = InspectedEntry::new(index + 1, EntryKind::Certificate, &key, hash);
+        entry.algorithm = cert.algorithm().to_certificate_type().to_string();
+        entry.cert = Some(CertInfo::from_certificate(path, &cert, None));
+        return Some(entry);
+    }
+
+    if let Ok(key) = PublicKey::from_openssh(line) {
+        return Some(InspectedEntry::new(
+            index + 1,
+            EntryKind::PublicKey,
+            &key,
+            hash,
+        ));
+    }
+
+    if let Some((options, key)) = split_options(line)
+        && options.iter().all(|option| {
+            let name = option.split('=').next().unwrap_or_default();
+            AUTHORIZED_KEYS_OPTIONS.contains(&name.to_ascii_lowercase().as_str())
+        })
+        && let Ok(key) = PublicKey::from_openssh(key)
+    {
+        let mut entry = InspectedEntry::new(index + 1, EntryKind::AuthorizedKey, &key, hash);
+        entry.options = options;
+        return Some(entry);
+    }
+
+    let host = KnownHost::parse(index, line)?;
+    let key = host.public_key()?;
+    let mut entry = InspectedEntry::new(index + 1, EntryKind::KnownHost, &key, hash);
+    entry.comment = host.comment;
+    entry.hosts = host.hosts;
+    entry.marker = host.marker;
+    Some(entry)
+}
+
+/// Split the leading options field off a line, e.g. `no-pty,command="a b"`,
+/// into its comma-separated options and the rest of the line
+fn split_options(line: &str) -> Option<(Vec<String>, &str)> {
+    let mut options = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                options.push(line[start..i].to_string());
+                start = i + 1;
+            }
+            c if c.is_whitespace() && !quoted => {
+                options.push(line[start..i].to_string());
+                return Some((options, line[i..].trim_start()));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use ssh_key::certificate::{Builder, CertType};
+    use ssh_key::{Algorithm, PrivateKey};
+
+    fn public_line(key: &PrivateKey) -> String {
+        key.public_key().to_openssh().unwrap()
+    }
+
+    #[test]
+    fn test_public_key_file() {
+        let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        key.set_comment("me@laptop");
+        let inspection = Inspection::parse(
+            Path::new("id.pub"),
+            &format!("{}\n", public_line(&key)),
+            FingerprintHash::Sha256,
+        );
+
+        assert_eq!(inspection.kind(), Some(EntryKind::PublicKey));
+        let entry = &inspection.entries[0];
+        assert_eq!(entry.algorithm, "ssh-ed25519");
+        assert_eq!(entry.bits, Some(256));
+        assert_eq!(entry.comment.as_deref(), Some("me@laptop"));
+        assert!(entry.fingerprint.starts_with("SHA256:"));
+        assert_eq!(inspection.summary(), "1 public key");
+    }
+
+    #[test]
+    fn test_authorized_keys_and_known_hosts() {
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let line = public_line(&key);
+
+        let authorized = format!(
+            "# managed by hand\nno-pty,command=\"echo hi, there\" {}\n{}\nnot a key\n",
+            line, line
+        );
+        let inspection = Inspection::parse(
+            Path::new("authorized_keys"),
+            &authorized,
+            FingerprintHash::Md5,
+        );
+        assert_eq!(inspection.entries.len(), 2);
+        assert_eq!(inspection.entries[0].kind, EntryKind::AuthorizedKey);
+        assert_eq!(
+            inspection.entries[0].options,
+            vec!["no-pty", "command=\"echo hi, there\""]
+        );
+        assert_eq!(inspection.entries[0].line, 2);
+        assert!(inspection.entries[0].fingerprint.starts_with("MD5:"));
+        assert_eq!(inspection.entries[1].kind, EntryKind::PublicKey);
+        assert_eq!(inspection.unrecognised, vec![4]);
+        assert_eq!(
+            inspection.summary(),
+            "2 keys of mixed kinds, 1 line not recognised"
+        );
+
+        // A host list where options would go is a known_hosts line
+        let known = format!(
+            "github.com,140.82.121.4 {}\n@cert-authority *.example.com {}\n",
+            line, line
+        );
+        let inspection =
+            Inspection::parse(Path::new("known_hosts"), &known, FingerprintHash::Sha256);
+        assert_eq!(inspection.kind(), Some(EntryKind::KnownHost));
+        assert_eq!(
+            inspection.entries[0].hosts,
+            vec!["github.com", "140.82.121.4"]
+        );
+        assert_eq!(
+            inspection.entries[1].marker.as_deref(),
+            Some("@cert-authority")
+        );
+        assert_eq!(inspection.summary(), "2 known_hosts entries");
+    }
+
+    #[test]
+    fn test_certificate_and_private_key() {
+        let ca = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let user = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let now = chrono::Local::now().timestamp() as u64;
+        let mut builder =
+            Builder::new_with_random_nonce(&mut OsRng, user.public_key(), now, now + 86400)
+                .unwrap();
+        builder.serial(7).unwrap();
+        builder.key_id("alice").unwrap();
+        builder.cert_type(CertType::User).unwrap();
+        builder.all_principals_valid().unwrap();
+        let cert = builder.sign(&ca).unwrap();
+
+        let inspection = Inspection::parse(
+            Path::new("id-cert.pub"),
+            &cert.to_openssh().unwrap(),
+            FingerprintHash::Sha256,
+        );
+        let entry = &inspection.entries[0];
+        assert_eq!(entry.kind, EntryKind::Certificate);
+        assert_eq!(entry.algorithm, "ssh-ed25519-cert-v01@openssh.com");
+        assert_eq!(
+            entry.fingerprint,
+            fingerprint_with(user.public_key(), FingerprintHash::Sha256)
+        );
+        assert_eq!(entry.cert.as_ref().unwrap().key_id, "alice");
+
+        let private = user.to_openssh(ssh_key::LineEnding::LF).unwrap();
+        let inspection = Inspection::parse(Path::new("id"), &private, FingerprintHash::Sha256);
+        assert_eq!(inspection.kind(), Some(EntryKind::PrivateKey));
+        assert!(!inspection.entries[0].encrypted);
+        assert_eq!(
+            inspection.entries[0].fingerprint,
+            fingerprint_with(user.public_key(), FingerprintHash::Sha256)
+        );
+    }
+}
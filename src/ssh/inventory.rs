@@ -0,0 +1,360 @@
+//! Host groups for `skm deploy --group`, imported from an Ansible inventory
+//! or the Host entries of `~/.ssh/config` so they don't have to be retyped
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{Result, SkmError};
+use crate::migrate::RecordStore;
+use crate::ssh::config::SshConfig;
+use crate::ssh::deploy::DeployTarget;
+
+/// Where host groups are kept, relative to the skm data directory
+pub const HOST_GROUPS_FILE: &str = "host_groups.json";
+
+/// Group the Host entries of the ssh config go into unless told otherwise
+pub const SSH_CONFIG_GROUP: &str = "ssh-config";
+
+/// One host of a group, as `skm deploy` would be given it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupHost {
+    /// `[user@]host`; an ssh config alias is kept as is, so ssh applies
+    /// its HostName, User and Port
+    pub destination: String,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostGroup {
+    pub name: String,
+    pub hosts: Vec<GroupHost>,
+    /// The inventory file it came from, or `ssh-config`
+    pub source: String,
+    pub imported_at: DateTime<Local>,
+}
+
+pub type HostGroupStore = RecordStore<HostGroup>;
+
+impl HostGroupStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self::at(data_dir.as_ref().join(HOST_GROUPS_FILE))
+    }
+
+    /// Add `groups`, replacing the ones of the same name
+    pub fn replace(&self, groups: Vec<HostGroup>) -> Result<()> {
+        self.update(|stored| {
+            stored.retain(|g| !groups.iter().any(|new| new.name == g.name));
+            stored.extend(groups);
+            stored.sort_by(|a, b| a.name.cmp(&b.name));
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<HostGroup> {
+        self.load()?
+            .into_iter()
+            .find(|g| g.name == name)
+            .ok_or_else(|| SkmError::HostNotFound(format!("no host group '{}'", name)))
+    }
+}
+
+/// Groups of an Ansible YAML inventory. A group holds its own hosts and
+/// those of its children, wherever in the file either is defined.
+/// `ansible_host`, `ansible_user` and `ansible_port` come from the host,
+/// else from its groups, a child group's vars winning over its parent's.
+/// Groups without hosts are left out.
+pub fn parse_ansible_inventory(content: &str, source: &str) -> Result<Vec<HostGroup>> {
+    let root: Value = serde_yaml::from_str(content)
+        .map_err(|e| SkmError::Config(format!("Invalid inventory {}: {}", source, e)))?;
+    let mut inventory = Inventory::default();
+    if let Some(root) = root.as_mapping() {
+        for (name, group) in root {
+            inventory.read_group(name, group, None, source)?;
+        }
+    }
+
+    let imported_at = Local::now();
+    let mut groups = Vec::new();
+    for group in inventory.groups.keys() {
+        let mut hosts = Vec::new();
+        for name in inventory.hosts_of(group, &mut Vec::new()) {
+            let host = inventory.host(&name, source)?;
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        if !hosts.is_empty() {
+            groups.push(HostGroup {
+                name: group.clone(),
+                hosts,
+                source: source.to_string(),
+                imported_at,
+            });
+        }
+    }
+    Ok(groups)
+}
+
+/// An inventory's groups by name, merged from everywhere they appear
+#[derive(Default)]
+struct Inventory {
+    groups: BTreeMap<String, InventoryGroup>,
+    host_vars: BTreeMap<String, Mapping>,
+}
+
+#[derive(Default)]
+struct InventoryGroup {
+    vars: Mapping,
+    hosts: Vec<String>,
+    children: Vec<String>,
+    parents: Vec<String>,
+}
+
+impl Inventory {
+    fn read_group(
+        &mut self,
+        name: &Value,
+        group: &Value,
+        parent: Option<&str>,
+        source: &str,
+    ) -> Result<()> {
+        let name = name
+            .as_str()
+            .ok_or_else(|| SkmError::Config(format!("Invalid inventory {}: group name", source)))?
+            .to_string();
+        let entry = self.groups.entry(name.clone()).or_default();
+        if let Some(parent) = parent {
+            if !entry.parents.iter().any(|p| p == parent) {
+                entry.parents.push(parent.to_string());
+            }
+        }
+        if let Some(vars) = group.get("vars").and_then(Value::as_mapping) {
+            entry.vars.extend(vars.clone());
+        }
+
+        if let Some(hosts) = group.get("hosts").and_then(Value::as_mapping) {
+            for (host, vars) in hosts {
+                let host = host.as_str().ok_or_else(|| {
+                    SkmError::Config(format!("Invalid inventory {}: host name", source))
+                })?;
+                if !entry.hosts.iter().any(|h| h == host) {
+                    entry.hosts.push(host.to_string());
+                }
+                let host_vars = self.host_vars.entry(host.to_string()).or_default();
+                if let Some(vars) = vars.as_mapping() {
+                    host_vars.extend(vars.clone());
+                }
+            }
+        }
+
+        if let Some(children) = group.get("children").and_then(Value::as_mapping) {
+            for (child, child_group) in children {
+                if let Some(child_name) = child.as_str() {
+                    let entry = self.groups.entry(name.clone()).or_default();
+                    if !entry.children.iter().any(|c| c == child_name) {
+                        entry.children.push(child_name.to_string());
+                    }
+                }
+                self.read_group(child, child_group, Some(&name), source)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Host names of `group` and its children; `seen` guards against cycles
+    fn hosts_of(&self, group: &str, seen: &mut Vec<String>) -> Vec<String> {
+        if seen.iter().any(|g| g == group) {
+            return Vec::new();
+        }
+        seen.push(group.to_string());
+        let Some(entry) = self.groups.get(group) else {
+            return Vec::new();
+        };
+        let mut hosts = entry.hosts.clone();
+        for child in &entry.children {
+            hosts.extend(self.hosts_of(child, seen));
+        }
+        hosts
+    }
+
+    /// How far `group` is below the top of the inventory, its deepest path
+    fn depth(&self, group: &str, seen: &mut Vec<String>) -> usize {
+        if seen.iter().any(|g| g == group) {
+            return 0;
+        }
+        seen.push(group.to_string());
+        let parents = self.groups.get(group).map(|g| g.parents.as_slice());
+        let depth = parents
+            .unwrap_or_default()
+            .iter()
+            .map(|parent| self.depth(parent, seen) + 1)
+            .max()
+            .unwrap_or(0);
+        seen.pop();
+        depth
+    }
+
+    /// Vars of every group `host` is in, through children too, shallowest
+    /// first, then the host's own
+    fn vars_of(&self, host: &str) -> Mapping {
+        let mut groups: Vec<(usize, &String)> = self
+            .groups
+            .keys()
+            .filter(|group| {
+                self.hosts_of(group, &mut Vec::new())
+                    .iter()
+                    .any(|h| h == host)
+            })
+            .map(|group| (self.depth(group, &mut Vec::new()), group))
+            .collect();
+        groups.sort();
+
+        let mut vars = Mapping::new();
+        for (_, group) in groups {
+            vars.extend(self.groups[group].vars.clone());
+        }
+        if let Some(own) = self.host_vars.get(host) {
+            vars.extend(own.clone());
+        }
+        vars
+    }
+
+    fn host(&self, name: &str, source: &str) -> Result<GroupHost> {
+        let vars = self.vars_of(name);
+        let var = |key: &str| vars.get(key).and_then(Value::as_str);
+        let host = var("ansible_host").unwrap_or(name);
+        let destination = match var("ansible_user") {
+            Some(user) => format!("{}@{}", user, host),
+            None => host.to_string(),
+        };
+        let port = match vars.get("ansible_port") {
+            None => None,
+            Some(port) => Some(
+                port.as_u64()
+                    .and_then(|p| u16::try_from(p).ok())
+                    .ok_or_else(|| {
+                        SkmError::Config(format!(
+                            "Invalid inventory {}: ansible_port of {}",
+                            source, name
+                        ))
+                    })?,
+            ),
+        };
+        // Refuse what deploy would refuse, at import rather than deploy time
+        DeployTarget::parse(&destination)?;
+        Ok(GroupHost { destination, port })
+    }
+}
+
+/// The concrete Host entries of the ssh config as one group; wildcard
+/// patterns name no host to deploy to and are skipped
+pub fn ssh_config_group(config: &SshConfig, name: &str) -> HostGroup {
+    let hosts = config
+        .hosts
+        .iter()
+        .filter(|h| !h.is_pattern())
+        .filter_map(|h| h.patterns.first())
+        .filter(|alias| DeployTarget::parse(alias).is_ok())
+        .map(|alias| GroupHost {
+            destination: alias.clone(),
+            port: None,
+        })
+        .collect();
+    HostGroup {
+        name: name.to_string(),
+        hosts,
+        source: SSH_CONFIG_GROUP.to_string(),
+        imported_at: Local::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const INVENTORY: &str = "\
+all:
+  hosts:
+    mail.example.com:
+  children:
+    webservers:
+      vars:
+        ansible_user: web
+      hosts:
+        foo.example.com:
+        bar.example.com:
+          ansible_host: 10.0.0.2
+          ansible_port: 2222
+    production:
+      children:
+        webservers:
+        dbservers:
+          hosts:
+            db.example.com:
+              ansible_user: postgres
+";
+
+    fn hosts(group: &HostGroup) -> Vec<(&str, Option<u16>)> {
+        group
+            .hosts
+            .iter()
+            .map(|h| (h.destination.as_str(), h.port))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_ansible_inventory() {
+        let groups = parse_ansible_inventory(INVENTORY, "hosts.yaml").unwrap();
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["all", "dbservers", "production", "webservers"]);
+
+        let web = &groups[3];
+        assert_eq!(
+            hosts(web),
+            [("web@foo.example.com", None), ("web@10.0.0.2", Some(2222))]
+        );
+        let production = &groups[2];
+        assert_eq!(production.hosts.len(), 3);
+        assert!(hosts(production).contains(&("postgres@db.example.com", None)));
+        // Children's hosts count towards every group above them
+        assert_eq!(groups[0].hosts.len(), 4);
+
+        assert!(parse_ansible_inventory("all: [", "bad.yaml").is_err());
+        let bad_port = "all:\n  hosts:\n    h:\n      ansible_port: 70000\n";
+        assert!(parse_ansible_inventory(bad_port, "bad.yaml").is_err());
+    }
+
+    #[test]
+    fn test_ssh_config_group() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config");
+        std::fs::write(
+            &path,
+            "Host *\n  User me\n\nHost bastion jump\n  HostName 10.0.0.1\n\nHost db\n  Port 2200\n",
+        )
+        .unwrap();
+        let config = SshConfig::load(&path).unwrap();
+
+        let group = ssh_config_group(&config, SSH_CONFIG_GROUP);
+        assert_eq!(hosts(&group), [("bastion", None), ("db", None)]);
+    }
+
+    #[test]
+    fn test_store_replaces_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = HostGroupStore::new(temp_dir.path());
+        let groups = parse_ansible_inventory(INVENTORY, "hosts.yaml").unwrap();
+        store.replace(groups).unwrap();
+
+        let mut web = store.get("webservers").unwrap();
+        web.hosts.truncate(1);
+        store.replace(vec![web]).unwrap();
+        assert_eq!(store.get("webservers").unwrap().hosts.len(), 1);
+        assert_eq!(store.load().unwrap().len(), 4);
+        assert!(store.get("nope").is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use base64ct::{Base64, Encoding};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha1::Sha1;
+use ssh_key::{HashAlg, PublicKey};
+
+use crate::error::Result;
+use crate::ssh::keys::{FingerprintHash, fingerprint_with};
+
+/// Prefix of a hashed host name (`HashKnownHosts yes`)
+const HASH_MAGIC: &str = "|1|";
+
+/// Salt length used by OpenSSH, the SHA-1 output size
+const SALT_LEN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostNames {
+    Plain(Vec<String>),
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// A single key line from a known_hosts file
+#[derive(Debug, Clone, Serialize)]
+pub struct KnownHost {
+    /// 1-based line number in the file
+    pub line: usize,
+    /// `@cert-authority` or `@revoked`
+    pub marker: Option<String>,
+    /// Host names or patterns; a single opaque token for hashed entries
+    pub hosts: Vec<String>,
+    pub hashed: bool,
+    pub key_type: String,
+    /// SHA256 fingerprint, if the key could be parsed
+    pub fingerprint: Option<String>,
+    pub comment: Option<String>,
+
+    #[serde(skip)]
+    names: HostNames,
+    #[serde(skip)]
+    key: String,
+}
+
+impl KnownHost {
+    /// Parse line `index` (0-based); `None` for comments, blanks and garbage
+    pub(crate) fn parse(index: usize, line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+
+        let mut fields = trimmed.split_whitespace().peekable();
+        let marker = fields.next_if(|f| f.starts_with('@')).map(String::from);
+        let host_field = fields.next()?;
+        let key_type = fields.next()?.to_string();
+        let key = fields.next()?.to_string();
+        let comment = Some(fields.collect::<Vec<_>>().join(" ")).filter(|c| !c.is_empty());
+
+        let names = match host_field.strip_prefix(HASH_MAGIC) {
+            Some(rest) => {
+                let (salt, hash) = rest.split_once('|')?;
+                HostNames::Hashed {
+                    salt: Base64::decode_vec(salt).ok()?,
+                    hash: Base64::decode_vec(hash).ok()?,
+                }
+            }
+            None => HostNames::Plain(host_field.split(',').map(String::from).collect()),
+        };
+
+        let fingerprint = PublicKey::from_openssh(&format!("{} {}", key_type, key))
+            .ok()
+            .map(|k| k.fingerprint(HashAlg::Sha256).to_string());
+
+        Some(Self {
+            line: index + 1,
+            marker,
+            hosts: host_field.split(',').map(String::from).collect(),
+            hashed: matches!(names, HostNames::Hashed { .. }),
+            key_type,
+            fingerprint,
+            comment,
+            names,
+            key,
+        })
+    }
+
+    /// The host key, if it could be parsed
+    pub fn public_key(&self) -> Option<PublicKey> {
+        PublicKey::from_openssh(&format!("{} {}", self.key_type, self.key)).ok()
+    }
+
+    /// The fingerprint in `hash`, if the key could be parsed
+    pub fn fingerprint_as(&self, hash: FingerprintHash) -> Option<String> {
+        self.public_key().map(|key| fingerprint_with(&key, hash))
+    }
+
+    /// Whether this entry applies to `host` (already in `[host]:port` form for
+    /// non-default ports, see [`host_key_name`])
+    pub fn matches(&self, host: &str) -> bool {
+        match &self.names {
+            HostNames::Plain(names) => {
+                let mut matched = false;
+                for name in names {
+                    if let Some(negated) = name.strip_prefix('!') {
+                        if wildcard_match(negated, host) {
+                            return false;
+                        }
+                    } else if wildcard_match(name, host) {
+                        matched = true;
+                    }
+                }
+                matched
+            }
+            HostNames::Hashed { salt, hash } => hash_host(salt, host) == *hash,
+        }
+    }
+
+    /// Re-render the entry with its host names replaced by `host_field`
+    fn render(&self, host_field: &str) -> String {
+        let mut fields: Vec<&str> = Vec::new();
+        if let Some(ref marker) = self.marker {
+            fields.push(marker);
+        }
+        fields.extend([host_field, &self.key_type, &self.key]);
+        if let Some(ref comment) = self.comment {
+            fields.push(comment);
+        }
+        fields.join(" ")
+    }
+}
+
+/// An OpenSSH known_hosts file. Edits keep comments and unrelated lines intact.
+#[derive(Debug, Clone)]
+pub struct KnownHosts {
+    path: PathBuf,
+    lines: Vec<String>,
+    pub entries: Vec<KnownHost>,
+}
+
+impl KnownHosts {
+    /// Load a known_hosts file; a missing file is treated as empty
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+
+        Ok(Self::parse(path, &content))
+    }
+
+    fn parse(path: PathBuf, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let entries = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| KnownHost::parse(index, line))
+            .collect();
+
+        Self {
+            path,
+            lines,
+            entries,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries for `host`, including hashed ones
+    pub fn find(&self, host: &str, port: Option<u16>) -> Vec<&KnownHost> {
+        let name = host_key_name(host, port);
+        self.entries.iter().filter(|e| e.matches(&name)).collect()
+    }
+
+    /// Remove every line with a key for `host`, e.g. after its host key
+    /// changed. Like `ssh-keygen -R`, a line listing several names is removed
+    /// as a whole. `@revoked` and `@cert-authority` lines stay, so a revoked
+    /// host key isn't trusted again. The previous file is kept as
+    /// `known_hosts.old`.
+    pub fn remove_host(&mut self, host: &str, port: Option<u16>) -> Result<usize> {
+        let remove: HashSet<usize> = self
+            .find(host, port)
+            .iter()
+            .filter(|e| e.marker.is_none())
+            .map(|e| e.line - 1)
+            .collect();
+        if remove.is_empty() {
+            return Ok(0);
+        }
+
+        let lines = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !remove.contains(index))
+            .map(|(_, line)| line.clone())
+            .collect();
+        self.replace_lines(lines)?;
+        Ok(remove.len())
+    }
+
+    /// Hash all plain host names, one line per name, like `ssh-keygen -H`.
+    /// Entries with wildcards, negations or markers cannot be hashed and are
+    /// left alone. Returns the number of names hashed.
+    pub fn hash_hosts(&mut self) -> Result<usize> {
+        let mut hashed = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+
+        for (index, line) in self.lines.iter().enumerate() {
+            let entry = self.entries.iter().find(|e| e.line == index + 1);
+            let Some(entry) = entry.filter(|e| e.marker.is_none()) else {
+                lines.push(line.clone());
+                continue;
+            };
+            let HostNames::Plain(ref names) = entry.names else {
+                lines.push(line.clone());
+                continue;
+            };
+            if names.iter().any(|n| n.contains(['*', '?', '!'])) {
+                lines.push(line.clone());
+                continue;
+            }
+
+            for name in names {
+                lines.push(entry.render(&hashed_name(name)));
+                hashed += 1;
+            }
+        }
+
+        if hashed > 0 {
+            self.replace_lines(lines)?;
+        }
+        Ok(hashed)
+    }
+
+    fn replace_lines(&mut self, lines: Vec<String>) -> Result<()> {
+        if self.path.exists() {
+            let mut backup = self.path.clone().into_os_string();
+            backup.push(".old");
+            std::fs::copy(&self.path, backup)?;
+        }
+
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        std::fs::write(&self.path, &content)?;
+
+        *self = Self::parse(self.path.clone(), &content);
+        Ok(())
+    }
+}
+
+/// Name ssh records a host under: the lowercased host, or `[host]:port` for
+/// non-default ports
+pub fn host_key_name(host: &str, port: Option<u16>) -> String {
+    let host = host.to_lowercase();
+    match port {
+        Some(port) if port != 22 => format!("[{}]:{}", host, port),
+        _ => host,
+    }
+}
+
+fn hash_host(salt: &[u8], host: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(salt).expect("HMAC accepts any key length");
+    mac.update(host.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hashed_name(host: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    format!(
+        "{}{}|{}",
+        HASH_MAGIC,
+        Base64::encode_string(&salt),
+        Base64::encode_string(&hash_host(&salt, host))
+    )
+}
+
+/// Case-insensitive `*`/`?` matching as used in known_hosts and ssh config
+/// `Host` patterns
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    fn write_known_hosts(dir: &Path, content: &str) -> PathBuf {
+        let path = dir.join("known_hosts");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_and_find() {
+        let hashed = hashed_name("hashed.example.com");
+        let content = format!(
+            "# comment\n\
+             github.com,140.82.121.4 {KEY}\n\
+             [git.example.com]:2222 {KEY} note\n\
+             {hashed} {KEY}\n\
+             @cert-authority *.corp.example.com {KEY}\n"
+        );
+        let known = KnownHosts::parse(PathBuf::from("known_hosts"), &content);
+
+        assert_eq!(known.entries.len(), 4);
+        assert_eq!(known.entries[0].line, 2);
+        assert!(known.entries[0].fingerprint.is_some());
+        assert_eq!(known.entries[1].comment.as_deref(), Some("note"));
+        assert!(known.entries[2].hashed);
+        assert_eq!(known.entries[3].marker.as_deref(), Some("@cert-authority"));
+
+        assert_eq!(known.find("140.82.121.4", None).len(), 1);
+        assert_eq!(known.find("git.example.com", Some(2222)).len(), 1);
+        assert!(known.find("git.example.com", None).is_empty());
+        assert_eq!(known.find("HASHED.example.com", None).len(), 1);
+        assert_eq!(known.find("build.corp.example.com", None).len(), 1);
+    }
+
+    #[test]
+    fn test_remove_host() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_known_hosts(
+            temp_dir.path(),
+            &format!(
+                "# keep me\ngithub.com {KEY}\nexample.com {KEY}\n{} {KEY}\n",
+                hashed_name("example.com")
+            ),
+        );
+
+        let mut known = KnownHosts::load(&path).unwrap();
+        assert_eq!(known.remove_host("example.com", None).unwrap(), 2);
+        assert_eq!(known.remove_host("example.com", None).unwrap(), 0);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, format!("# keep me\ngithub.com {KEY}\n"));
+        assert!(temp_dir.path().join("known_hosts.old").exists());
+    }
+
+    #[test]
+    fn test_remove_host_keeps_revoked() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_known_hosts(
+            temp_dir.path(),
+            &format!(
+                "example.com {KEY}
+@revoked example.com {KEY}
+"
+            ),
+        );
+
+        let mut known = KnownHosts::load(&path).unwrap();
+        assert_eq!(known.remove_host("example.com", None).unwrap(), 1);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            format!(
+                "@revoked example.com {KEY}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn test_hash_hosts() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_known_hosts(
+            temp_dir.path(),
+            &format!("github.com,140.82.121.4 {KEY}\n*.example.com {KEY}\n"),
+        );
+
+        let mut known = KnownHosts::load(&path).unwrap();
+        assert_eq!(known.hash_hosts().unwrap(), 2);
+
+        assert_eq!(known.entries.len(), 3);
+        assert!(known.entries[0].hashed && known.entries[1].hashed);
+        assert!(!known.entries[2].hashed);
+        assert_eq!(known.find("github.com", None).len(), 1);
+        assert_eq!(known.find("140.82.121.4", None).len(), 1);
+
+        // Nothing left to hash
+        assert_eq!(known.hash_hosts().unwrap(), 0);
+    }
+}
@@ -1,7 +1,31 @@
+pub mod agent;
+pub mod allowed_signers;
+pub mod approval;
+pub mod audit;
+pub mod authorized_keys;
+pub mod ca;
+pub mod cert;
+pub mod config;
+pub mod convert;
+pub mod deploy;
+pub mod ephemeral;
+pub mod expiry;
 pub mod generate;
+pub mod inspect;
+pub mod inventory;
 pub mod keys;
+pub mod known_hosts;
+pub mod perms;
+pub mod randomart;
+pub mod repos;
 pub mod scan;
+pub mod shred;
+pub mod snapshot;
+pub mod sshsig;
+pub mod team;
+pub mod trash;
 
+pub use agent::{AgentClient, AgentOrigin};
 pub use generate::KeyGenerator;
 pub use keys::{KeyStatus, KeyType, SshKey};
-pub use scan::KeyScanner;
+pub use scan::{KeyScanner, ScanSummary};
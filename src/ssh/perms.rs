@@ -0,0 +1,215 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::ssh::keys::SshKey;
+
+/// Modes OpenSSH expects; anything looser gets tightened to these
+pub const SSH_DIR_MODE: u32 = 0o700;
+pub const PRIVATE_KEY_MODE: u32 = 0o600;
+pub const PUBLIC_KEY_MODE: u32 = 0o644;
+
+/// A file whose mode is looser than it should be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionChange {
+    pub path: PathBuf,
+    pub old_mode: u32,
+    pub new_mode: u32,
+}
+
+impl fmt::Display for PermissionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:o} -> {:o}",
+            self.path.display(),
+            self.old_mode,
+            self.new_mode
+        )
+    }
+}
+
+/// Changes needed to bring the ssh directory and key files to safe modes.
+/// Files that are already stricter than required (e.g. a 0400 private key)
+/// are left alone.
+pub fn plan(ssh_dir: &Path, keys: &[SshKey]) -> Vec<PermissionChange> {
+    let mut changes = Vec::new();
+    check(ssh_dir, SSH_DIR_MODE, &mut changes);
+    for key in keys {
+        check(&key.path, PRIVATE_KEY_MODE, &mut changes);
+        check(&key.public_path, PUBLIC_KEY_MODE, &mut changes);
+    }
+    changes
+}
+
+/// A directory other users can write to, which lets them read or swap keys
+/// written below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsafeDir {
+    pub path: PathBuf,
+    pub mode: u32,
+}
+
+impl fmt::Display for UnsafeDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is writable by other users (mode {:o})",
+            self.path.display(),
+            self.mode
+        )
+    }
+}
+
+/// The first directory, from `dir` up to `/`, that makes writing keys into
+/// `dir` unsafe: `dir` itself when group or world writable, or a parent that
+/// is writable without the sticky bit, since anyone could then rename `dir`
+/// away. `/tmp` is fine as a parent; a shared 777 directory is not. When
+/// `dir` doesn't exist yet its nearest existing parent stands in for it.
+pub fn unsafe_dir(dir: &Path) -> Option<UnsafeDir> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = dir.canonicalize().ok().unwrap_or_else(|| dir.to_path_buf());
+        let mut ancestors = dir.ancestors().filter_map(|path| {
+            let metadata = std::fs::metadata(path).ok()?;
+            Some((path, metadata.permissions().mode() & 0o7777))
+        });
+
+        let (target, mode) = ancestors.next()?;
+        if mode & 0o022 != 0 {
+            return Some(UnsafeDir {
+                path: target.to_path_buf(),
+                mode: mode & 0o777,
+            });
+        }
+        ancestors
+            .find(|(_, mode)| mode & 0o022 != 0 && mode & 0o1000 == 0)
+            .map(|(path, mode)| UnsafeDir {
+                path: path.to_path_buf(),
+                mode: mode & 0o777,
+            })
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        None
+    }
+}
+
+pub fn apply(changes: &[PermissionChange]) -> Result<()> {
+    #[cfg(unix)]
+    for change in changes {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(
+            &change.path,
+            std::fs::Permissions::from_mode(change.new_mode),
+        )?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = changes;
+
+    Ok(())
+}
+
+fn check(path: &Path, wanted: u32, changes: &mut Vec<PermissionChange>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // symlink_metadata: never chmod the target of a symlinked key
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return;
+        };
+        if metadata.file_type().is_symlink() {
+            return;
+        }
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & !wanted != 0 {
+            changes.push(PermissionChange {
+                path: path.to_path_buf(),
+                old_mode: mode,
+                new_mode: wanted,
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, wanted, changes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_and_apply() {
+        use std::os::unix::fs::PermissionsExt;
+        let set_mode = |path: &Path, mode: u32| {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        for (name, mode) in [("loose", 0o644), ("strict", 0o400)] {
+            std::fs::write(dir.join(name), "private").unwrap();
+            set_mode(&dir.join(name), mode);
+        }
+        std::fs::write(dir.join("loose.pub"), "public").unwrap();
+        set_mode(&dir.join("loose.pub"), 0o666);
+        set_mode(dir, 0o755);
+
+        let keys: Vec<SshKey> = ["loose", "strict"]
+            .iter()
+            .map(|name| SshKey::from_path(dir.join(name)).unwrap())
+            .collect();
+
+        let changes = plan(dir, &keys);
+        let modes: Vec<(u32, u32)> = changes.iter().map(|c| (c.old_mode, c.new_mode)).collect();
+        assert_eq!(modes, vec![(0o755, 0o700), (0o644, 0o600), (0o666, 0o644)]);
+
+        apply(&changes).unwrap();
+        assert!(plan(dir, &keys).is_empty());
+        let mode = std::fs::metadata(dir.join("strict"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o400);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unsafe_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let set_mode = |path: &Path, mode: u32| {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        let ssh_dir = shared.join(".ssh");
+        std::fs::create_dir_all(&ssh_dir).unwrap();
+        set_mode(temp_dir.path(), 0o700);
+        set_mode(&shared, 0o755);
+        set_mode(&ssh_dir, 0o700);
+        assert_eq!(unsafe_dir(&ssh_dir), None);
+
+        set_mode(&ssh_dir, 0o770);
+        assert_eq!(unsafe_dir(&ssh_dir).unwrap().path, ssh_dir);
+        // Not created yet: judged by the parent it would go in
+        set_mode(&ssh_dir, 0o700);
+        assert_eq!(unsafe_dir(&ssh_dir.join("new")), None);
+
+        set_mode(&shared, 0o777);
+        let found = unsafe_dir(&ssh_dir).unwrap();
+        assert_eq!((found.path, found.mode), (shared.clone(), 0o777));
+        // Sticky, like /tmp: others can't move .ssh away
+        set_mode(&shared, 0o1777);
+        assert_eq!(unsafe_dir(&ssh_dir), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! The "drunken bishop" randomart `ssh-keygen -lv` draws for a fingerprint
+//!
+//! A bishop starts in the middle of a 17x9 board and takes four diagonal steps
+//! for every byte of the digest, two bits per step. Each square counts its
+//! visits, and the counts are drawn with the same characters OpenSSH uses, so
+//! the picture can be compared against `ssh-keygen` or a server's
+//! `VisualHostKey` output.
+
+use ssh_key::PublicKey;
+use ssh_key::public::KeyData;
+
+use crate::ssh::keys::{self, FingerprintHash};
+
+const WIDTH: usize = 17;
+const HEIGHT: usize = 9;
+
+/// Characters in each line of the art, borders included
+pub const LINE_WIDTH: usize = WIDTH + 2;
+
+/// Visit counts from none to many, then the start and end squares
+const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+
+/// Randomart for `key` under `hash`, one line per row, boxed with the key
+/// type and size on top and the hash below
+pub fn randomart(key: &PublicKey, hash: FingerprintHash) -> String {
+    let digest = keys::fingerprint_digest(key, hash);
+    let title = title(key.key_data());
+    let hash = match hash {
+        FingerprintHash::Sha256 => "[SHA256]",
+        FingerprintHash::Md5 => "[MD5]",
+    };
+    draw(&digest, &title, hash)
+}
+
+fn draw(digest: &[u8], title: &str, hash: &str) -> String {
+    let start = SYMBOLS.len() - 2;
+    let end = SYMBOLS.len() - 1;
+
+    let mut field = [[0usize; HEIGHT]; WIDTH];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+    let origin = (x, y);
+    for byte in digest {
+        let mut bits = *byte;
+        for _ in 0..4 {
+            x = if bits & 1 != 0 {
+                (x + 1).min(WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if bits & 2 != 0 {
+                (y + 1).min(HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            // Busy squares stop counting below the start and end markers
+            if field[x][y] < start - 1 {
+                field[x][y] += 1;
+            }
+            bits >>= 2;
+        }
+    }
+    field[origin.0][origin.1] = start;
+    field[x][y] = end;
+
+    let mut art = border(title);
+    art.push('\n');
+    for row in 0..HEIGHT {
+        art.push('|');
+        for column in &field {
+            art.push(SYMBOLS[column[row]] as char);
+        }
+        art.push_str("|\n");
+    }
+    art.push_str(&border(hash));
+    art
+}
+
+/// `+---[label]---+`, with the label centred and any odd dash on the right
+fn border(label: &str) -> String {
+    let left = (WIDTH - label.len()) / 2;
+    let right = WIDTH - left - label.len();
+    format!("+{}{}{}+", "-".repeat(left), label, "-".repeat(right))
+}
+
+/// `[ED25519 256]`, or just the type when the size doesn't fit on the border
+fn title(key_data: &KeyData) -> String {
+    let name = match key_data {
+        KeyData::Ed25519(_) => "ED25519",
+        KeyData::SkEd25519(_) => "ED25519-SK",
+        KeyData::Rsa(_) => "RSA",
+        KeyData::Ecdsa(_) => "ECDSA",
+        KeyData::SkEcdsaSha2NistP256(_) => "ECDSA-SK",
+        KeyData::Dsa(_) => "DSA",
+        _ => "UNKNOWN",
+    };
+    let title = match keys::key_bits(key_data) {
+        Some(bits) => format!("[{} {}]", name, bits),
+        None => format!("[{}]", name),
+    };
+    if title.len() <= WIDTH - 2 {
+        title
+    } else {
+        format!("[{}]", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMizQvi9/MOhNCQeoGoHidgEDYeiwhUUqXUMwhoryiKI me@x";
+    const RSA: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDJyGVwha2htLwLZWwCkC69mhaNcY84DZ3EBf8oAWL3jRi5g+rNmrRbJrkHqcj3elgiXZhCbrWxg+x1VDw16C0F0i+YMcsS1JFVFRkc1e7HhhJoAasnbP//hVSbOZRBz4Z7AZZkOye0CQNrkyucCM49mOigEnXzkng56ieb88rYEG+ITzMyjYSVcPh81W5gs1ZwBxyJ7HSLs7q29xbGulpztohm4I4FWr5tRDCj2mutD6UotBu4LwehMqaBO/P5/EMrs1J5M3YwgGK6A2yzb3E6EzhLkHFp6+BuGYRE89bOwtaMl4yL2Ydz2oXtZCOKi6pE+bFwWo0kGeSmHWV5U4Ap";
+
+    #[test]
+    fn test_randomart_matches_ssh_keygen() {
+        // `ssh-keygen -lv -f id_ed25519.pub`
+        let key = PublicKey::from_openssh(ED25519).unwrap();
+        assert_eq!(
+            randomart(&key, FingerprintHash::Sha256),
+            "\
++--[ED25519 256]--+
+| ..oE=B+ =.      |
+|  o.+*+ *..      |
+|+ oo o +.        |
+|.Oo.o.+o..       |
+|+...o+ooS.       |
+|     o.=o        |
+|    .+o          |
+|    .o=.         |
+|    ..oo         |
++----[SHA256]-----+"
+        );
+
+        // `ssh-keygen -lv -E md5 -f id_rsa.pub`
+        let key = PublicKey::from_openssh(RSA).unwrap();
+        assert_eq!(
+            randomart(&key, FingerprintHash::Md5),
+            "\
++---[RSA 2048]----+
+|      .          |
+|   . o           |
+|  . +     .      |
+| o o .   . .     |
+|+ o o E S o o    |
+|=o o o . o . .   |
+|=.+.. . .        |
+|.+o  .           |
+| .               |
++------[MD5]------+"
+        );
+    }
+
+    #[test]
+    fn test_border() {
+        assert_eq!(border("[ED25519-SK 256]").len(), LINE_WIDTH);
+        assert_eq!(border(""), format!("+{}+", "-".repeat(WIDTH)));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::config::RepoSettings;
+use crate::ssh::config::{SshConfig, expand_path};
+
+/// Roots searched when none are configured; missing ones are skipped
+pub const DEFAULT_ROOTS: &[&str] = &["~/src", "~/code", "~/projects", "~/dev", "~/git", "~/work"];
+
+/// How deep below a root repositories are looked for
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+
+/// Directories that never contain repositories worth reporting
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "venv"];
+
+/// A git remote that is reached over ssh
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SshRemote {
+    pub repo: PathBuf,
+    pub remote: String,
+    pub url: String,
+    /// Host as written in the URL, which may be an ssh config alias
+    pub host: String,
+}
+
+impl SshRemote {
+    /// Repository name for display: its directory name
+    pub fn repo_name(&self) -> String {
+        self.repo
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.repo.display().to_string())
+    }
+}
+
+/// Scan the roots from the `[repos]` settings, or [`DEFAULT_ROOTS`] if none
+/// are configured
+pub fn scan_configured(settings: &RepoSettings) -> Vec<SshRemote> {
+    let roots: Vec<PathBuf> = if settings.roots.is_empty() {
+        DEFAULT_ROOTS.iter().map(|r| expand_path(r)).collect()
+    } else {
+        settings.roots.iter().map(|r| expand_path(r)).collect()
+    };
+    scan_remotes(&roots, settings.max_depth.unwrap_or(DEFAULT_MAX_DEPTH))
+}
+
+/// Find ssh remotes of all git repositories below `roots`. Nested
+/// repositories (submodules checked out inside another repo) aren't searched.
+pub fn scan_remotes(roots: &[PathBuf], max_depth: usize) -> Vec<SshRemote> {
+    let mut remotes = Vec::new();
+
+    for root in roots.iter().filter(|r| r.is_dir()) {
+        let mut walker = WalkDir::new(root).max_depth(max_depth).into_iter();
+        while let Some(entry) = walker.next() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy();
+            if entry.depth() > 0 && (name.starts_with('.') || SKIPPED_DIRS.contains(&&*name)) {
+                walker.skip_current_dir();
+                continue;
+            }
+
+            if let Some(git_config) = git_config_path(entry.path()) {
+                if let Ok(content) = std::fs::read_to_string(git_config) {
+                    remotes.extend(parse_remotes(entry.path(), &content));
+                }
+                walker.skip_current_dir();
+            }
+        }
+    }
+
+    remotes
+}
+
+/// Group remotes by the private key ssh would use for them, keyed by path
+pub fn repos_by_key(
+    remotes: &[SshRemote],
+    ssh_config: &SshConfig,
+) -> BTreeMap<PathBuf, Vec<String>> {
+    let mut by_key: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+
+    for remote in remotes {
+        for identity in ssh_config.identities_for(&remote.host) {
+            // IdentityFile may name the .pub half
+            let identity = match identity.extension() {
+                Some(ext) if ext == "pub" => identity.with_extension(""),
+                _ => identity,
+            };
+            let repos = by_key.entry(identity).or_default();
+            let name = remote.repo_name();
+            if !repos.contains(&name) {
+                repos.push(name);
+            }
+        }
+    }
+
+    by_key
+}
+
+/// `.git/config` of a working tree, following `gitdir:` files used by
+/// worktrees and submodules
+fn git_config_path(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git.join("config"));
+    }
+
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let git_dir = dir.join(content.strip_prefix("gitdir:")?.trim());
+    // Worktrees keep their config in the main repository
+    let common = std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|c| git_dir.join(c.trim()))
+        .unwrap_or(git_dir);
+    Some(common.join("config"))
+}
+
+/// `url` entries of `[remote "..."]` sections that point at ssh hosts
+fn parse_remotes(repo: &Path, content: &str) -> Vec<SshRemote> {
+    let mut remotes = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            current = line
+                .strip_prefix("[remote \"")
+                .and_then(|rest| rest.strip_suffix("\"]"))
+                .map(String::from);
+            continue;
+        }
+
+        let Some(ref remote) = current else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !key.trim().eq_ignore_ascii_case("url") {
+            continue;
+        }
+
+        let url = value.trim().trim_matches('"');
+        if let Some(host) = ssh_host(url) {
+            remotes.push(SshRemote {
+                repo: repo.to_path_buf(),
+                remote: remote.clone(),
+                url: url.to_string(),
+                host,
+            });
+        }
+    }
+
+    remotes
+}
+
+/// Host of an ssh remote URL: `ssh://[user@]host[:port]/path` or the
+/// scp-like `[user@]host:path`. `None` for other transports and local paths.
+fn ssh_host(url: &str) -> Option<String> {
+    let authority = if let Some((scheme, rest)) = url.split_once("://") {
+        if !matches!(scheme, "ssh" | "git+ssh" | "ssh+git") {
+            return None;
+        }
+        let authority = rest.split('/').next()?;
+        // Strip the port, leaving bracketed IPv6 addresses intact
+        match authority.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') && port.parse::<u16>().is_ok() => host,
+            _ => authority,
+        }
+    } else {
+        // git treats it as a local path if a slash comes before the colon
+        let (authority, _) = url.split_once(':')?;
+        if authority.contains('/') || authority.is_empty() {
+            return None;
+        }
+        authority
+    };
+
+    let host = authority.rsplit('@').next()?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn init_repo(dir: &Path, remotes: &[(&str, &str)]) {
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        let mut config = String::from("[core]\n\tbare = false\n");
+        for (name, url) in remotes {
+            config.push_str(&format!("[remote \"{}\"]\n\turl = {}\n", name, url));
+        }
+        std::fs::write(dir.join(".git/config"), config).unwrap();
+    }
+
+    #[test]
+    fn test_ssh_host() {
+        assert_eq!(
+            ssh_host("git@github.com:me/repo.git").as_deref(),
+            Some("github.com")
+        );
+        assert_eq!(ssh_host("work-gh:me/repo.git").as_deref(), Some("work-gh"));
+        assert_eq!(
+            ssh_host("ssh://git@git.example.com:2222/me/repo.git").as_deref(),
+            Some("git.example.com")
+        );
+        assert_eq!(ssh_host("ssh://[::1]:22/repo").as_deref(), Some("::1"));
+        assert_eq!(ssh_host("https://github.com/me/repo.git"), None);
+        assert_eq!(ssh_host("./local/path:with-colon"), None);
+        assert_eq!(ssh_host("/srv/repo.git"), None);
+    }
+
+    #[test]
+    fn test_scan_and_map_to_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("src");
+        init_repo(
+            &root.join("api"),
+            &[
+                ("origin", "git@work-gh:corp/api.git"),
+                ("mirror", "https://example.com/api"),
+            ],
+        );
+        init_repo(
+            &root.join("dotfiles"),
+            &[("origin", "git@github.com:me/dotfiles.git")],
+        );
+        // Nested repositories and hidden directories are not searched
+        init_repo(
+            &root.join("api/vendored"),
+            &[("origin", "git@github.com:x/y.git")],
+        );
+        init_repo(
+            &root.join(".cache/tool"),
+            &[("origin", "git@github.com:x/z.git")],
+        );
+
+        let remotes = scan_remotes(&[root, temp_dir.path().join("missing")], DEFAULT_MAX_DEPTH);
+        assert_eq!(remotes.len(), 2);
+
+        let config_path = temp_dir.path().join("config");
+        std::fs::write(
+            &config_path,
+            "Host work-gh\n  HostName github.com\n  IdentityFile /keys/work.pub\n\
+             Host github.com\n  IdentityFile /keys/personal\n",
+        )
+        .unwrap();
+        let config = SshConfig::load(&config_path).unwrap();
+
+        let by_key = repos_by_key(&remotes, &config);
+        assert_eq!(by_key[Path::new("/keys/work")], vec!["api"]);
+        assert_eq!(by_key[Path::new("/keys/personal")], vec!["dotfiles"]);
+    }
+}
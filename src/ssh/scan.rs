@@ -1,37 +1,141 @@
+use serde::Serialize;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use std::fmt;
+
 use crate::error::Result;
-use crate::ssh::keys::SshKey;
+use crate::ssh::audit::{self, FindingKind};
+use crate::ssh::cert::CERT_SUFFIX;
+use crate::ssh::keys::{KeyStatus, MAX_KEY_FILE_SIZE, SshKey};
+
+/// Suffixes editors, tools and people use for copies of a file
+const BACKUP_SUFFIXES: &[&str] = &[".old", ".bak", ".orig", "~"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// `<key>-cert.pub`
+    Certificate,
+    /// `id_rsa.old`, `known_hosts.bak` and the like
+    Backup,
+    /// ssh-agent or ControlMaster socket
+    Socket,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactKind::Certificate => write!(f, "certificate"),
+            ArtifactKind::Backup => write!(f, "backup"),
+            ArtifactKind::Socket => write!(f, "socket"),
+        }
+    }
+}
+
+/// A file in the ssh dir that belongs to a key or to ssh but isn't a key itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub kind: ArtifactKind,
+    /// Name of the file it belongs to, e.g. `id_rsa` for `id_rsa.pub.bak`
+    pub parent: Option<String>,
+}
+
+impl Artifact {
+    /// Recognise an artifact by its name; sockets by their file type alone
+    pub fn classify(path: &Path, is_socket: bool) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let artifact = |kind, parent: Option<&str>| {
+            Some(Self {
+                path: path.to_path_buf(),
+                kind,
+                parent: parent.filter(|p| !p.is_empty()).map(str::to_string),
+            })
+        };
+
+        if is_socket {
+            return artifact(ArtifactKind::Socket, None);
+        }
+        if let Some(key) = file_name.strip_suffix(CERT_SUFFIX) {
+            return artifact(ArtifactKind::Certificate, Some(key));
+        }
+        let original = BACKUP_SUFFIXES
+            .iter()
+            .find_map(|suffix| file_name.strip_suffix(suffix))?;
+        // A backed up public key still belongs to its private key
+        let parent = original.strip_suffix(".pub").unwrap_or(original);
+        artifact(ArtifactKind::Backup, Some(parent))
+    }
+}
+
+/// Directory entries looked at before giving up; a real ssh dir has a few
+/// dozen, so this only trips on misconfigured or bogus mounts
+pub const MAX_SCAN_ENTRIES: usize = 1000;
 
 pub struct KeyScanner {
     ssh_dir: PathBuf,
+    max_entries: usize,
 }
 
 impl KeyScanner {
     pub fn new<P: AsRef<Path>>(ssh_dir: P) -> Self {
         Self {
             ssh_dir: ssh_dir.as_ref().to_path_buf(),
+            max_entries: MAX_SCAN_ENTRIES,
         }
     }
 
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
     pub fn scan(&self) -> Result<Vec<SshKey>> {
+        self.scan_with_artifacts().map(|(keys, _)| keys)
+    }
+
+    /// Keys, with backups attached to the key they belong to, plus the
+    /// artifacts that don't belong to any key
+    pub fn scan_with_artifacts(&self) -> Result<(Vec<SshKey>, Vec<Artifact>)> {
         if !self.ssh_dir.exists() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), Vec::new()));
         }
 
         let mut keys = Vec::new();
+        let mut artifacts = Vec::new();
         let mut processed = std::collections::HashSet::new();
 
-        for entry in WalkDir::new(&self.ssh_dir)
+        for (index, entry) in WalkDir::new(&self.ssh_dir)
+            .min_depth(1)
             .max_depth(1)
             .follow_links(false)
             .into_iter()
             .filter_map(|e| e.ok())
+            .enumerate()
         {
+            if index == self.max_entries {
+                tracing::warn!(
+                    "Stopped scanning {} after {} entries; keys beyond that are not listed",
+                    self.ssh_dir.display(),
+                    self.max_entries
+                );
+                break;
+            }
+
             let path = entry.path();
 
-            if path.is_dir() {
+            // Follows symlinks. Sockets become artifacts, directories and FIFOs
+            // (reading one blocks) are skipped; dangling symlinks show up as
+            // broken keys.
+            let metadata = std::fs::metadata(path).ok();
+            if let Some(artifact) =
+                Artifact::classify(path, metadata.as_ref().is_some_and(is_socket))
+            {
+                artifacts.push(artifact);
+                continue;
+            }
+            if metadata.as_ref().is_some_and(|m| !m.is_file()) {
                 continue;
             }
 
@@ -53,6 +157,15 @@ impl KeyScanner {
                 continue;
             }
 
+            if let Some(size) = metadata.map(|m| m.len()).filter(|&s| s > MAX_KEY_FILE_SIZE) {
+                tracing::warn!(
+                    "Skipping {}: {} bytes is too big for a key file",
+                    path.display(),
+                    size
+                );
+                continue;
+            }
+
             match SshKey::from_path(path) {
                 Ok(key) => keys.push(key),
                 Err(e) => {
@@ -64,13 +177,30 @@ impl KeyScanner {
         // Sort by name for consistent display
         keys.sort_by(|a, b| a.name.cmp(&b.name));
 
-        Ok(keys)
+        // Certificates are already paired by SshKey itself; backups are
+        // attached here. Whatever has no key left goes to the bucket.
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut orphans = Vec::new();
+        for artifact in artifacts {
+            let key = artifact
+                .parent
+                .as_deref()
+                .and_then(|parent| keys.iter_mut().find(|k| k.name == parent));
+            match (key, artifact.kind) {
+                (Some(_), ArtifactKind::Certificate) => {}
+                (Some(key), _) => key.artifacts.push(artifact.path),
+                (None, _) => orphans.push(artifact),
+            }
+        }
+
+        Ok((keys, orphans))
     }
 
-    fn is_non_key_file(filename: &str) -> bool {
+    pub(crate) fn is_non_key_file(filename: &str) -> bool {
         const NON_KEY_FILES: &[&str] = &[
             "authorized_keys",
             "authorized_keys2",
+            "allowed_signers",
             "known_hosts",
             "known_hosts.old",
             "config",
@@ -96,6 +226,88 @@ impl KeyScanner {
     }
 }
 
+#[cfg(unix)]
+fn is_socket(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_socket()
+}
+
+#[cfg(not(unix))]
+fn is_socket(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Per-status counts for a set of keys, e.g. for `skm list --summary`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    pub total: usize,
+    pub valid: usize,
+    pub encrypted: usize,
+    pub missing_public: usize,
+    pub missing_private: usize,
+    pub corrupted: usize,
+    pub audit_warnings: usize,
+}
+
+impl ScanSummary {
+    pub fn from_keys(keys: &[SshKey]) -> Self {
+        let mut summary = Self {
+            total: keys.len(),
+            ..Self::default()
+        };
+
+        for key in keys {
+            match key.status {
+                KeyStatus::Valid => summary.valid += 1,
+                KeyStatus::Encrypted => summary.encrypted += 1,
+                KeyStatus::MissingPublic => summary.missing_public += 1,
+                KeyStatus::MissingPrivate => summary.missing_private += 1,
+                KeyStatus::Corrupted => summary.corrupted += 1,
+            }
+            // Missing or unreadable files are already counted by status
+            summary.audit_warnings += audit::audit_key(key)
+                .iter()
+                .filter(|f| !matches!(f.kind, FindingKind::MissingPublic | FindingKind::Unreadable))
+                .count();
+        }
+
+        summary
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = [
+            (self.valid, "valid"),
+            (self.encrypted, "encrypted"),
+            (self.missing_public, "missing public"),
+            (self.missing_private, "missing private"),
+            (self.corrupted, "corrupted"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, label)| format!("{} {}", count, label))
+            .collect();
+
+        write!(
+            f,
+            "{} key{}",
+            self.total,
+            if self.total == 1 { "" } else { "s" }
+        )?;
+        if !parts.is_empty() {
+            write!(f, ": {}", parts.join(", "))?;
+        }
+        write!(
+            f,
+            "; {} audit warning{}",
+            self.audit_warnings,
+            if self.audit_warnings == 1 { "" } else { "s" }
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +337,81 @@ mod tests {
         assert_eq!(keys.len(), 2);
     }
 
+    #[test]
+    fn test_scan_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("id_ed25519"), "private").unwrap();
+        std::fs::write(
+            temp_dir.path().join("huge"),
+            vec![b'A'; MAX_KEY_FILE_SIZE as usize + 1],
+        )
+        .unwrap();
+
+        let keys = KeyScanner::new(temp_dir.path()).scan().unwrap();
+        let names: Vec<&str> = keys.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, vec!["id_ed25519"]);
+
+        for i in 0..5 {
+            std::fs::write(temp_dir.path().join(format!("key{}", i)), "private").unwrap();
+        }
+        let keys = KeyScanner::new(temp_dir.path())
+            .with_max_entries(3)
+            .scan()
+            .unwrap();
+        assert!(keys.len() <= 3);
+    }
+
+    #[test]
+    fn test_scan_artifacts() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in [
+            "id_rsa",
+            "id_rsa.pub",
+            "id_rsa-cert.pub",
+            "id_rsa.old",
+            "id_rsa.pub.bak",
+            "gone-cert.pub",
+            "known_hosts.old",
+            // A plain file isn't taken for an agent socket by its name
+            "agent.notes",
+        ] {
+            std::fs::write(temp_dir.path().join(name), "data").unwrap();
+        }
+        #[cfg(unix)]
+        let _socket =
+            std::os::unix::net::UnixListener::bind(temp_dir.path().join("agent.1234")).unwrap();
+
+        let (keys, orphans) = KeyScanner::new(temp_dir.path())
+            .scan_with_artifacts()
+            .unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, "id_rsa");
+        assert!(keys[0].cert_path.is_some());
+        assert_eq!(
+            keys[0].artifacts,
+            vec![
+                temp_dir.path().join("id_rsa.old"),
+                temp_dir.path().join("id_rsa.pub.bak")
+            ]
+        );
+
+        let orphans: Vec<(String, ArtifactKind)> = orphans
+            .iter()
+            .map(|a| {
+                let name = a.path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, a.kind)
+            })
+            .collect();
+        let mut expected = vec![
+            ("gone-cert.pub".to_string(), ArtifactKind::Certificate),
+            ("known_hosts.old".to_string(), ArtifactKind::Backup),
+        ];
+        if cfg!(unix) {
+            expected.insert(0, ("agent.1234".to_string(), ArtifactKind::Socket));
+        }
+        assert_eq!(orphans, expected);
+    }
+
     #[test]
     fn test_skip_non_key_files() {
         let temp_dir = TempDir::new().unwrap();
@@ -151,4 +438,29 @@ mod tests {
         assert!(key.is_some());
         assert_eq!(key.unwrap().name, "my_key");
     }
+
+    #[test]
+    fn test_scan_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a"), "private").unwrap();
+        std::fs::write(temp_dir.path().join("a.pub"), "public").unwrap();
+        std::fs::write(temp_dir.path().join("b"), "private").unwrap();
+
+        #[cfg(unix)]
+        for name in ["a", "b"] {
+            use std::os::unix::fs::PermissionsExt;
+            let path = temp_dir.path().join(name);
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let keys = KeyScanner::new(temp_dir.path()).scan().unwrap();
+        let summary = ScanSummary::from_keys(&keys);
+
+        assert_eq!(summary.valid, 1);
+        assert_eq!(summary.missing_public, 1);
+        assert_eq!(
+            summary.to_string(),
+            "2 keys: 1 valid, 1 missing public; 0 audit warnings"
+        );
+    }
 }
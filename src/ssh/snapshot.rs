@@ -0,0 +1,386 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use ssh_key::{HashAlg, PrivateKey, PublicKey};
+
+use crate::error::{Result, SkmError};
+use crate::migrate;
+use crate::ssh::keys::fingerprint;
+
+/// Where snapshots are kept, relative to the skm data directory
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Snapshot ids are the time they were taken, e.g. `20240201-093000`
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Name the ssh directory itself is recorded under
+const DIR_ENTRY: &str = ".";
+
+/// Files bigger than this are recorded by size alone; nothing ssh reads
+/// from its own directory comes close
+const MAX_HASHED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// One file of the ssh directory as it was when the snapshot was taken
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub name: String,
+    pub mode: Option<u32>,
+    pub size: u64,
+    /// Hex SHA-256 of the contents; None for the directory itself
+    pub sha256: Option<String>,
+    /// Fingerprint of the key in the file, for public and private keys
+    pub fingerprint: Option<String>,
+}
+
+/// Hash manifest of an ssh directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Local>,
+    pub ssh_dir: PathBuf,
+    pub files: Vec<FileState>,
+    /// Id it was saved or loaded under; the file name, not stored in it
+    #[serde(skip)]
+    saved_as: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(FileState),
+    Removed(FileState),
+    Modified { old: FileState, new: FileState },
+}
+
+impl Change {
+    pub fn name(&self) -> &str {
+        match self {
+            Change::Added(file) | Change::Removed(file) => &file.name,
+            Change::Modified { new, .. } => &new.name,
+        }
+    }
+
+    /// What changed, e.g. "mode 600 -> 644, contents changed"
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Added(file) => match &file.fingerprint {
+                Some(fp) => format!("added ({})", fp),
+                None => "added".to_string(),
+            },
+            Change::Removed(file) => match &file.fingerprint {
+                Some(fp) => format!("removed ({})", fp),
+                None => "removed".to_string(),
+            },
+            Change::Modified { old, new } => {
+                let mut what = Vec::new();
+                if old.mode != new.mode {
+                    what.push(format!(
+                        "mode {} -> {}",
+                        format_mode(old.mode),
+                        format_mode(new.mode)
+                    ));
+                }
+                if old.fingerprint != new.fingerprint {
+                    what.push(format!(
+                        "fingerprint {} -> {}",
+                        old.fingerprint.as_deref().unwrap_or("none"),
+                        new.fingerprint.as_deref().unwrap_or("none")
+                    ));
+                } else if old.sha256 != new.sha256 || old.size != new.size {
+                    what.push("contents changed".to_string());
+                }
+                what.join(", ")
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Record every regular file directly inside `ssh_dir`, plus the
+    /// directory's own mode. Sockets (e.g. ControlMaster) and subdirectories
+    /// come and go on their own and are left out.
+    pub fn capture(ssh_dir: &Path) -> Result<Self> {
+        let dir_metadata = fs::metadata(ssh_dir)?;
+        let mut files = vec![FileState {
+            name: DIR_ENTRY.to_string(),
+            mode: mode_of(&dir_metadata),
+            size: 0,
+            sha256: None,
+            fingerprint: None,
+        }];
+
+        for entry in fs::read_dir(ssh_dir)? {
+            let entry = entry?;
+            let metadata = fs::symlink_metadata(entry.path())?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let (sha256, fingerprint) = if metadata.len() <= MAX_HASHED_SIZE {
+                let data = fs::read(entry.path())?;
+                (
+                    Some(hex(&HashAlg::Sha256.digest(&data))),
+                    key_fingerprint(&data),
+                )
+            } else {
+                (None, None)
+            };
+
+            files.push(FileState {
+                name: entry.file_name().to_string_lossy().to_string(),
+                mode: mode_of(&metadata),
+                size: metadata.len(),
+                sha256,
+                fingerprint,
+            });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            taken_at: Local::now(),
+            ssh_dir: ssh_dir.to_path_buf(),
+            files,
+            saved_as: None,
+        })
+    }
+
+    /// Snapshot id, as used in its file name: when it was taken, plus `-N`
+    /// for later snapshots taken in the same second
+    pub fn id(&self) -> String {
+        self.saved_as
+            .clone()
+            .unwrap_or_else(|| self.taken_at.format(ID_FORMAT).to_string())
+    }
+
+    /// Everything that differs going from `self` to `newer`, sorted by name
+    pub fn diff(&self, newer: &Snapshot) -> Vec<Change> {
+        let find = |snapshot: &Snapshot, name: &str| {
+            snapshot.files.iter().find(|f| f.name == name).cloned()
+        };
+
+        let mut changes: Vec<Change> = self
+            .files
+            .iter()
+            .filter_map(|old| match find(newer, &old.name) {
+                None => Some(Change::Removed(old.clone())),
+                Some(new) if &new != old => Some(Change::Modified {
+                    old: old.clone(),
+                    new,
+                }),
+                Some(_) => None,
+            })
+            .collect();
+        changes.extend(
+            newer
+                .files
+                .iter()
+                .filter(|new| find(self, &new.name).is_none())
+                .map(|new| Change::Added(new.clone())),
+        );
+        changes.sort_by(|a, b| a.name().cmp(b.name()));
+        changes
+    }
+}
+
+/// Snapshots saved under the data directory, one JSON file each
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self {
+            dir: data_dir.as_ref().join(SNAPSHOTS_DIR),
+        }
+    }
+
+    /// Save under a new id; a snapshot taken in the same second as an
+    /// earlier one gets a `-2`, `-3`, ... suffix
+    pub fn save(&self, snapshot: &mut Snapshot) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let stem = snapshot.taken_at.format(ID_FORMAT).to_string();
+        let mut id = stem.clone();
+        let mut n = 1;
+        while self.path(&id).exists() {
+            n += 1;
+            id = format!("{}-{}", stem, n);
+        }
+
+        let path = self.path(&id);
+        migrate::save_json(&path, snapshot)?;
+        snapshot.saved_as = Some(id);
+        Ok(path)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Ids of the saved snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut ids: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_suffix(".json").map(String::from)
+            })
+            .collect();
+        ids.sort_by(|a, b| id_order(a).cmp(&id_order(b)));
+        Ok(ids)
+    }
+
+    pub fn load(&self, id: &str) -> Result<Snapshot> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Err(SkmError::Config(format!(
+                "No snapshot '{}'; `skm snapshot list` shows the saved ones",
+                id
+            )));
+        }
+
+        let mut snapshot: Snapshot = migrate::load_json(&path)?;
+        snapshot.saved_as = Some(id.to_string());
+        Ok(snapshot)
+    }
+
+    /// The most recent snapshot, if any was taken
+    pub fn latest(&self) -> Result<Option<Snapshot>> {
+        match self.list()?.last() {
+            Some(id) => self.load(id).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Time order of snapshot ids: by timestamp, then by the `-N` suffix, so
+/// `-10` comes after `-9`
+fn id_order(id: &str) -> (&str, u32) {
+    let stamp_len = "YYYYmmdd-HHMMSS".len();
+    match (id.get(..stamp_len), id.get(stamp_len..)) {
+        (Some(stamp), Some(rest)) => (
+            stamp,
+            rest.strip_prefix('-')
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1),
+        ),
+        _ => (id, 1),
+    }
+}
+
+/// Fingerprint of a public key or OpenSSH private key file; private keys
+/// keep their public half readable even when encrypted
+fn key_fingerprint(data: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(data).ok()?;
+    if let Ok(key) = PublicKey::from_openssh(text.trim()) {
+        return Some(fingerprint(&key));
+    }
+    let key = PrivateKey::from_openssh(data).ok()?;
+    Some(fingerprint(key.public_key()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn format_mode(mode: Option<u32>) -> String {
+    mode.map_or_else(|| "-".to_string(), |mode| format!("{:o}", mode))
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("config"), "Host a\n").unwrap();
+        fs::write(dir.join("known_hosts"), "").unwrap();
+        fs::write(dir.join("old"), "").unwrap();
+        let before = Snapshot::capture(dir).unwrap();
+        assert!(before.diff(&before).is_empty());
+
+        fs::write(dir.join("config"), "Host a\nHost b\n").unwrap();
+        fs::remove_file(dir.join("old")).unwrap();
+        fs::write(dir.join("new"), "").unwrap();
+        let after = Snapshot::capture(dir).unwrap();
+
+        let changes = before.diff(&after);
+        let names: Vec<&str> = changes.iter().map(Change::name).collect();
+        assert_eq!(names, vec!["config", "new", "old"]);
+        assert_eq!(changes[0].describe(), "contents changed");
+        assert!(matches!(changes[1], Change::Added(_)));
+        assert!(matches!(changes[2], Change::Removed(_)));
+    }
+
+    #[test]
+    fn test_describe_mode_and_fingerprint() {
+        let file = |mode, fp: Option<&str>| FileState {
+            name: "id_a".to_string(),
+            mode: Some(mode),
+            size: 1,
+            sha256: Some("00".to_string()),
+            fingerprint: fp.map(String::from),
+        };
+        let change = Change::Modified {
+            old: file(0o600, Some("SHA256:old")),
+            new: file(0o644, Some("SHA256:new")),
+        };
+        assert_eq!(
+            change.describe(),
+            "mode 600 -> 644, fingerprint SHA256:old -> SHA256:new"
+        );
+    }
+
+    #[test]
+    fn test_store_latest() {
+        let data_dir = TempDir::new().unwrap();
+        let ssh_dir = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data_dir.path());
+        assert!(store.latest().unwrap().is_none());
+
+        let mut snapshot = Snapshot::capture(ssh_dir.path()).unwrap();
+        store.save(&mut snapshot).unwrap();
+        assert_eq!(store.list().unwrap(), vec![snapshot.id()]);
+        assert_eq!(store.latest().unwrap().unwrap().files, snapshot.files);
+        assert!(store.load("19700101-000000").is_err());
+    }
+
+    #[test]
+    fn test_store_same_second() {
+        let data_dir = TempDir::new().unwrap();
+        let ssh_dir = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data_dir.path());
+
+        // Taken within one second, each snapshot still gets its own file
+        let first = Snapshot::capture(ssh_dir.path()).unwrap();
+        let stem = first.id();
+        let mut ids = Vec::new();
+        for _ in 0..10 {
+            let mut snapshot = first.clone();
+            store.save(&mut snapshot).unwrap();
+            ids.push(snapshot.id());
+        }
+        assert_eq!(ids[0], stem);
+        assert_eq!(ids[1], format!("{}-2", stem));
+        assert_eq!(ids[9], format!("{}-10", stem));
+        assert_eq!(store.list().unwrap(), ids);
+        assert_eq!(store.latest().unwrap().unwrap().id(), ids[9]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use ssh_key::{HashAlg, LineEnding, PrivateKey};
+
+use crate::error::{Result, SkmError};
+
+/// Namespace `ssh-keygen -Y sign` uses for plain files
+pub const DEFAULT_NAMESPACE: &str = "file";
+
+/// Signatures are written next to the signed file with this suffix
+pub const SIG_SUFFIX: &str = ".sig";
+
+/// Signature file for `path`, e.g. `release.tar.gz.sig`
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(SIG_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Sign `data` like `ssh-keygen -Y sign` does, returning the armored
+/// `-----BEGIN SSH SIGNATURE-----` block. The key must already be decrypted.
+pub fn sign(key: &PrivateKey, namespace: &str, data: &[u8]) -> Result<String> {
+    if namespace.is_empty() {
+        return Err(SkmError::Config(
+            "Signature namespace must not be empty".to_string(),
+        ));
+    }
+    if key.is_encrypted() {
+        return Err(SkmError::InvalidPassphrase);
+    }
+
+    key.sign(namespace, HashAlg::Sha512, data)
+        .and_then(|sig| sig.to_pem(LineEnding::LF))
+        .map_err(|e| SkmError::SshKey(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use ssh_key::{Algorithm, SshSig};
+
+    #[test]
+    fn test_sign() {
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let armored = sign(&key, "file", b"release contents").unwrap();
+        assert!(armored.starts_with("-----BEGIN SSH SIGNATURE-----"));
+
+        let sig = SshSig::from_pem(&armored).unwrap();
+        assert_eq!(sig.namespace(), "file");
+        key.public_key()
+            .verify("file", b"release contents", &sig)
+            .unwrap();
+        assert!(
+            key.public_key()
+                .verify("git", b"release contents", &sig)
+                .is_err()
+        );
+
+        assert!(sign(&key, "", b"data").is_err());
+        assert_eq!(
+            signature_path(Path::new("dist/app.tar.gz")),
+            PathBuf::from("dist/app.tar.gz.sig")
+        );
+    }
+}
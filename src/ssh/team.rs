@@ -0,0 +1,412 @@
+//! Team mode: public keys and deploy records shared through a git repository,
+//! so a small team can see who has which keys deployed where.
+//!
+//! Every machine writes only its own `members/<user>@<host>/` directory: a
+//! `.pub` file per key and `deployments.json`. Private keys are never written
+//! to the repository.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use ssh_key::{HashAlg, PublicKey};
+
+use crate::error::{Result, SkmError};
+use crate::migrate;
+use crate::ssh::SshKey;
+use crate::ssh::deploy::Deployment;
+
+/// Where the team repository is cloned, relative to the skm data directory
+pub const TEAM_DIR: &str = "team";
+
+const MEMBERS_DIR: &str = "members";
+const KEYS_DIR: &str = "keys";
+const DEPLOYMENTS_FILE: &str = "deployments.json";
+
+/// A public key a member published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamKey {
+    pub name: String,
+    /// SHA256, as deploy records have it
+    pub fingerprint: String,
+    pub comment: String,
+}
+
+/// One machine's keys and where they were deployed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// `user@host`
+    pub name: String,
+    pub keys: Vec<TeamKey>,
+    pub deployments: Vec<Deployment>,
+}
+
+impl Member {
+    /// Deploys of the key with this fingerprint
+    pub fn deployments_of(&self, fingerprint: &str) -> impl Iterator<Item = &Deployment> {
+        self.deployments
+            .iter()
+            .filter(move |d| d.fingerprint == fingerprint)
+    }
+}
+
+/// Local clone of the team repository
+pub struct TeamRepo {
+    dir: PathBuf,
+}
+
+impl TeamRepo {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self {
+            dir: data_dir.as_ref().join(TEAM_DIR),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_joined(&self) -> bool {
+        self.dir.join(".git").exists()
+    }
+
+    /// Clone the team repository
+    pub fn join(&self, url: &str) -> Result<()> {
+        if self.is_joined() {
+            return Err(SkmError::Config(format!(
+                "Already in a team; its repository is {}",
+                self.dir.display()
+            )));
+        }
+        if url.is_empty() || url.starts_with('-') {
+            return Err(SkmError::Config(format!(
+                "Invalid repository URL: '{}'",
+                url
+            )));
+        }
+        if let Some(parent) = self.dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut command = Command::new("git");
+        command
+            .args(["clone", "--quiet", "--"])
+            .arg(url)
+            .arg(&self.dir);
+        run(command, "git clone")?;
+        Ok(())
+    }
+
+    /// Pull the others' changes, publish `member`'s keys and deploys and push
+    /// them. Returns whether anything of ours changed.
+    pub fn sync(&self, member: &str, keys: &[SshKey], deployments: &[Deployment]) -> Result<bool> {
+        self.require_joined()?;
+        let branch = self.git(&["symbolic-ref", "--short", "HEAD"])?;
+        let branch = branch.trim();
+        // A repository nobody pushed to yet has nothing to pull
+        if !self
+            .git(&["ls-remote", "--heads", "origin", branch])?
+            .trim()
+            .is_empty()
+        {
+            self.git(&["pull", "--quiet", "--rebase", "origin", branch])?;
+        }
+
+        self.publish(member, keys, deployments)?;
+        let member_dir = format!("{}/{}", MEMBERS_DIR, member);
+        self.git(&["add", "--all", "--", &member_dir])?;
+        let mut diff = self.command(&["diff", "--cached", "--quiet"]);
+        diff.stdout(Stdio::null());
+        if diff.status()?.success() {
+            return Ok(false);
+        }
+
+        self.git(&["commit", "--quiet", "-m", &format!("Update {}", member)])?;
+        self.git(&["push", "--quiet", "--set-upstream", "origin", branch])?;
+        Ok(true)
+    }
+
+    /// Write `member`'s directory: the public half of each key, replacing
+    /// what was published before, and the deploys of those keys
+    pub fn publish(&self, member: &str, keys: &[SshKey], deployments: &[Deployment]) -> Result<()> {
+        validate_member(member)?;
+        let member_dir = self.dir.join(MEMBERS_DIR).join(member);
+        let keys_dir = member_dir.join(KEYS_DIR);
+        if keys_dir.exists() {
+            fs::remove_dir_all(&keys_dir)?;
+        }
+        fs::create_dir_all(&keys_dir)?;
+
+        let mut published = Vec::new();
+        for key in keys {
+            let public_key = match key.read_public_key() {
+                Ok(public_key) => public_key,
+                Err(e) => {
+                    tracing::warn!("Failed to read the public key of {}: {}", key.name, e);
+                    continue;
+                }
+            };
+            let line = public_key
+                .to_openssh()
+                .map_err(|e| SkmError::SshKey(e.to_string()))?;
+            fs::write(keys_dir.join(format!("{}.pub", key.name)), line + "\n")?;
+            if let Some(fingerprint) = &key.fingerprint {
+                published.push(fingerprint.as_str());
+            }
+        }
+
+        // Which key logged in is only meaningful on this machine
+        let deployments: Vec<Deployment> = deployments
+            .iter()
+            .filter(|d| published.contains(&d.fingerprint.as_str()))
+            .map(|d| Deployment {
+                identity: None,
+                ..d.clone()
+            })
+            .collect();
+        migrate::save_json(&member_dir.join(DEPLOYMENTS_FILE), &deployments)
+    }
+
+    /// Everyone who published, by name
+    pub fn members(&self) -> Result<Vec<Member>> {
+        let members_dir = self.dir.join(MEMBERS_DIR);
+        if !members_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut members = Vec::new();
+        for entry in fs::read_dir(&members_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let dir = entry.path();
+            let deployments_path = dir.join(DEPLOYMENTS_FILE);
+            members.push(Member {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                keys: read_keys(&dir.join(KEYS_DIR))?,
+                deployments: if deployments_path.exists() {
+                    migrate::load_json(&deployments_path)?
+                } else {
+                    Vec::new()
+                },
+            });
+        }
+        members.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(members)
+    }
+
+    fn require_joined(&self) -> Result<()> {
+        if self.is_joined() {
+            Ok(())
+        } else {
+            Err(SkmError::Config(
+                "Not in a team yet; join one with `skm team join <repository>`".to_string(),
+            ))
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir).args(args);
+        command
+    }
+
+    /// Run git in the clone and return what it printed
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = run(self.command(args), &format!("git {}", args[0]))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// This machine's member name, `user@host`
+pub fn member_name(username: &str, hostname: &str) -> String {
+    format!("{}@{}", username, hostname).replace(['/', '\\'], "_")
+}
+
+/// Member names are directory names in the repository
+fn validate_member(member: &str) -> Result<()> {
+    if member.is_empty() || member.starts_with('.') || member.contains(['/', '\\']) {
+        return Err(SkmError::Config(format!(
+            "Invalid team member name: '{}'",
+            member
+        )));
+    }
+    Ok(())
+}
+
+fn read_keys(dir: &Path) -> Result<Vec<TeamKey>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "pub") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        let Ok(public_key) = PublicKey::from_openssh(content.trim()) else {
+            tracing::warn!("Failed to parse team key {}", path.display());
+            continue;
+        };
+        keys.push(TeamKey {
+            name: path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            fingerprint: public_key.fingerprint(HashAlg::Sha256).to_string(),
+            comment: public_key.comment().to_string(),
+        });
+    }
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(keys)
+}
+
+fn run(mut command: Command, what: &str) -> Result<Output> {
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| SkmError::Remote(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(SkmError::Remote(format!(
+            "{} failed ({})",
+            what, output.status
+        )));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::KeyScanner;
+    use chrono::Local;
+    use tempfile::TempDir;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMizQvi9/MOhNCQeoGoHidgEDYeiwhUUqXUMwhoryiKI";
+
+    fn keys(dir: &Path) -> Vec<SshKey> {
+        fs::write(dir.join("id_ed25519"), "private").unwrap();
+        fs::write(
+            dir.join("id_ed25519.pub"),
+            format!("{} me@laptop\n", ED25519),
+        )
+        .unwrap();
+        KeyScanner::new(dir).scan().unwrap()
+    }
+
+    fn deployment(fingerprint: &str) -> Deployment {
+        Deployment {
+            destination: "admin@server".to_string(),
+            port: None,
+            identity: Some(PathBuf::from("/home/me/.ssh/admin")),
+            fingerprint: fingerprint.to_string(),
+            key_name: "id_ed25519".to_string(),
+            deployed_at: Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_publish_and_members() {
+        let ssh_dir = TempDir::new().unwrap();
+        let data_dir = TempDir::new().unwrap();
+        let keys = keys(ssh_dir.path());
+        let fingerprint = keys[0].fingerprint.clone().unwrap();
+        let repo = TeamRepo::new(data_dir.path());
+
+        repo.publish(
+            "me@laptop",
+            &keys,
+            &[deployment(&fingerprint), deployment("SHA256:other")],
+        )
+        .unwrap();
+        // Only the public half goes into the repository
+        let published = repo.dir().join("members/me@laptop/keys/id_ed25519.pub");
+        assert!(fs::read_to_string(&published).unwrap().starts_with(ED25519));
+        assert!(
+            !repo
+                .dir()
+                .join("members/me@laptop/keys/id_ed25519")
+                .exists()
+        );
+
+        let members = repo.members().unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].name, "me@laptop");
+        assert_eq!(members[0].keys[0].fingerprint, fingerprint);
+        assert_eq!(members[0].keys[0].comment, "me@laptop");
+        // Deploys of keys that weren't published are left out, and so is
+        // the local login key
+        let deployments: Vec<_> = members[0].deployments_of(&fingerprint).collect();
+        assert_eq!(deployments.len(), 1);
+        assert_eq!(members[0].deployments.len(), 1);
+        assert_eq!(deployments[0].identity, None);
+
+        // A key that's gone is unpublished
+        repo.publish("me@laptop", &[], &[]).unwrap();
+        assert!(!published.exists());
+        assert!(repo.members().unwrap()[0].keys.is_empty());
+
+        assert!(repo.publish("../escape", &keys, &[]).is_err());
+        assert!(repo.publish(".git", &keys, &[]).is_err());
+    }
+
+    #[test]
+    fn test_sync_through_git() {
+        let remote = TempDir::new().unwrap();
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(remote.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let url = remote.path().to_str().unwrap();
+
+        let ssh_dir = TempDir::new().unwrap();
+        let keys = keys(ssh_dir.path());
+        let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let (alice, bob) = (
+            TeamRepo::new(alice_dir.path()),
+            TeamRepo::new(bob_dir.path()),
+        );
+        for repo in [&alice, &bob] {
+            assert!(repo.sync("x@y", &keys, &[]).is_err());
+            repo.join(url).unwrap();
+            repo.git(&["config", "user.name", "skm test"]).unwrap();
+            repo.git(&["config", "user.email", "skm@example.com"])
+                .unwrap();
+        }
+        assert!(alice.join(url).is_err());
+        assert!(
+            TeamRepo::new(bob_dir.path())
+                .join("--upload-pack=x")
+                .is_err()
+        );
+
+        assert!(alice.sync("alice@laptop", &keys, &[]).unwrap());
+        assert!(!alice.sync("alice@laptop", &keys, &[]).unwrap());
+        assert!(bob.sync("bob@desktop", &keys, &[]).unwrap());
+
+        let names = |repo: &TeamRepo| -> Vec<String> {
+            repo.members()
+                .unwrap()
+                .into_iter()
+                .map(|member| member.name)
+                .collect()
+        };
+        assert_eq!(names(&bob), ["alice@laptop", "bob@desktop"]);
+        alice.sync("alice@laptop", &keys, &[]).unwrap();
+        assert_eq!(names(&alice), ["alice@laptop", "bob@desktop"]);
+    }
+
+    #[test]
+    fn test_member_name() {
+        assert_eq!(member_name("me", "laptop"), "me@laptop");
+        assert_eq!(member_name("DOMAIN\\me", "laptop"), "DOMAIN_me@laptop");
+    }
+}
@@ -0,0 +1,373 @@
+//! Deleted keys, kept until they are restored or the trash is emptied
+//!
+//! Each entry is a directory holding a plain manifest, so the trash can be
+//! listed without any secrets, and the key files themselves age-encrypted to
+//! a key of the trash's own. Private keys never sit in the trash in the clear.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::{EncryptionManager, Lock, Unlock};
+use crate::error::{Result, SkmError};
+use crate::migrate;
+use crate::ssh::SshKey;
+use crate::ssh::expiry::KeyAgeStore;
+use crate::ssh::shred::{check_shreddable, shred_file};
+
+/// Where deleted keys are kept, relative to the skm data directory
+pub const TRASH_DIR: &str = "trash";
+
+/// age identity the trash is encrypted to, relative to the skm data directory.
+/// Created with the first deletion.
+pub const TRASH_KEY_FILE: &str = "trash.key";
+
+/// Plain description of an entry, inside its directory
+const MANIFEST_FILE: &str = "entry.json";
+
+/// The encrypted files, inside the entry's directory
+const FILES_FILE: &str = "files.age";
+
+/// A deleted key as listed by `skm trash list`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub key_name: String,
+    pub fingerprint: Option<String>,
+    pub deleted_at: DateTime<Local>,
+    /// Where the files were; restoring puts them back there
+    pub files: Vec<PathBuf>,
+}
+
+/// One file of a deleted key, as encrypted in `files.age`
+#[derive(Serialize, Deserialize)]
+struct TrashedFile {
+    path: PathBuf,
+    mode: Option<u32>,
+    /// Base64
+    contents: String,
+}
+
+pub struct Trash {
+    dir: PathBuf,
+    key_file: PathBuf,
+    ages: KeyAgeStore, // restoring writes the key anew, so its age is kept here
+    /// Overwrite the original private key once it is encrypted
+    shred: bool,
+}
+
+impl Trash {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
+        Self {
+            dir: data_dir.as_ref().join(TRASH_DIR),
+            key_file: data_dir.as_ref().join(TRASH_KEY_FILE),
+            ages: KeyAgeStore::new(&data_dir),
+            shred: false,
+        }
+    }
+
+    /// Shred private keys put in the trash rather than just unlinking them
+    pub fn shredding(mut self, shred: bool) -> Self {
+        self.shred = shred;
+        self
+    }
+
+    /// Move a key, its public key and certificate into the trash. The
+    /// originals are only removed once the encrypted copy is written.
+    pub fn put(&self, key: &SshKey) -> Result<TrashEntry> {
+        let mut paths = Vec::new();
+        for path in [&key.path, &key.public_path] {
+            if path.exists() {
+                paths.push(path.clone());
+            }
+        }
+        paths.extend(key.cert_path.iter().cloned());
+        if paths.is_empty() {
+            return Err(SkmError::KeyNotFound(key.name.clone()));
+        }
+        // Refused before anything is written, not halfway through
+        if self.shred && paths.contains(&key.path) {
+            check_shreddable(&key.path)?;
+        }
+
+        let mut files = Vec::new();
+        for path in &paths {
+            let contents = Zeroizing::new(fs::read(path)?);
+            files.push(TrashedFile {
+                path: path.clone(),
+                mode: mode_of(&fs::metadata(path)?),
+                contents: Base64::encode_string(&contents),
+            });
+        }
+        let payload = Zeroizing::new(serde_json::to_vec(&files)?);
+        for file in &mut files {
+            zeroize::Zeroize::zeroize(&mut file.contents);
+        }
+        let encrypted = EncryptionManager::encrypt(&payload, &self.lock()?)?;
+
+        let deleted_at = Local::now();
+        let stem = format!("{}-{}", deleted_at.format("%Y%m%d-%H%M%S"), key.name);
+        let mut id = stem.clone();
+        let mut n = 1;
+        while self.dir.join(&id).exists() {
+            n += 1;
+            id = format!("{}-{}", stem, n);
+        }
+
+        let entry = TrashEntry {
+            id,
+            key_name: key.name.clone(),
+            fingerprint: key.fingerprint.clone(),
+            deleted_at,
+            files: paths,
+        };
+        let entry_dir = self.dir.join(&entry.id);
+        fs::create_dir_all(&entry_dir)?;
+        fs::write(entry_dir.join(FILES_FILE), encrypted)?;
+        migrate::save_json(&entry_dir.join(MANIFEST_FILE), &entry)?;
+        if let Err(e) = self.ages.remember(std::slice::from_ref(key)) {
+            tracing::warn!("Failed to record the age of '{}': {}", key.name, e);
+        }
+
+        for path in &entry.files {
+            if self.shred && *path == key.path {
+                shred_file(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Entries in the trash, most recently deleted first
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let manifest = dir_entry?.path().join(MANIFEST_FILE);
+            if manifest.exists() {
+                entries.push(migrate::load_json::<TrashEntry>(&manifest)?);
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+        Ok(entries)
+    }
+
+    /// The entry with this id, or the latest deletion of a key with this name
+    pub fn find(&self, name_or_id: &str) -> Result<TrashEntry> {
+        let entries = self.list()?;
+        entries
+            .iter()
+            .find(|entry| entry.id == name_or_id)
+            .or_else(|| entries.iter().find(|entry| entry.key_name == name_or_id))
+            .cloned()
+            .ok_or_else(|| {
+                SkmError::KeyNotFound(format!(
+                    "{} (not in the trash; `skm trash list` shows what is)",
+                    name_or_id
+                ))
+            })
+    }
+
+    /// Put the files of an entry back where they were and drop it from the
+    /// trash. Refuses to overwrite anything created there since.
+    pub fn restore(&self, id: &str) -> Result<TrashEntry> {
+        let entry_dir = self.dir.join(id);
+        let entry: TrashEntry = migrate::load_json(&entry_dir.join(MANIFEST_FILE))?;
+
+        if let Some(taken) = entry.files.iter().find(|path| path.exists()) {
+            return Err(SkmError::KeyAlreadyExists(format!(
+                "{} (move it away to restore '{}')",
+                taken.display(),
+                entry.key_name
+            )));
+        }
+
+        let encrypted = fs::read(entry_dir.join(FILES_FILE))?;
+        let unlock = Unlock::identities(std::slice::from_ref(&self.key_file))?;
+        let payload = Zeroizing::new(EncryptionManager::decrypt(&encrypted, &unlock)?);
+        let files: Vec<TrashedFile> = serde_json::from_slice(&payload)?;
+
+        for file in &files {
+            let contents = Zeroizing::new(
+                Base64::decode_vec(&file.contents)
+                    .map_err(|e| SkmError::Encryption(format!("Corrupt trash entry: {}", e)))?,
+            );
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_new(&file.path, &contents, file.mode)?;
+        }
+
+        fs::remove_dir_all(&entry_dir)?;
+        Ok(entry)
+    }
+
+    /// Delete entries for good; only those deleted more than `older_than`
+    /// seconds ago when given. Returns how many were removed.
+    pub fn empty(&self, older_than: Option<u64>) -> Result<usize> {
+        let now = Local::now();
+        let mut removed = 0;
+        for entry in self.list()? {
+            let age = now.signed_duration_since(entry.deleted_at).num_seconds();
+            if older_than
+                .is_some_and(|older_than| age < i64::try_from(older_than).unwrap_or(i64::MAX))
+            {
+                continue;
+            }
+            fs::remove_dir_all(self.dir.join(&entry.id))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Encrypt to the trash key, creating it the first time
+    fn lock(&self) -> Result<Lock> {
+        if !self.key_file.exists() {
+            use age::secrecy::ExposeSecret;
+
+            let identity = age::x25519::Identity::generate();
+            if let Some(parent) = self.key_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let content = Zeroizing::new(format!(
+                "# skm trash key; without it deleted keys can't be restored\n{}\n",
+                identity.to_string().expose_secret()
+            ));
+            write_new(&self.key_file, content.as_bytes(), Some(0o600))?;
+        }
+
+        let content = Zeroizing::new(fs::read_to_string(&self.key_file)?);
+        let identity = content
+            .lines()
+            .find_map(|line| line.trim().parse::<age::x25519::Identity>().ok())
+            .ok_or_else(|| {
+                SkmError::Encryption(format!("Invalid trash key {}", self.key_file.display()))
+            })?;
+        Ok(Lock::Recipients(vec![Box::new(identity.to_public())]))
+    }
+}
+
+/// Create `path` with `mode` from the start, so secrets are never readable
+/// by others, even briefly
+#[cfg(unix)]
+fn write_new(path: &Path, contents: &[u8], mode: Option<u32>) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mode = mode.unwrap_or(0o600);
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)?;
+    file.write_all(contents)?;
+    // The umask may have taken bits off
+    file.set_permissions(fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_new(path: &Path, contents: &[u8], _mode: Option<u32>) -> Result<()> {
+    use std::io::Write;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_key(dir: &Path, name: &str) -> SshKey {
+        let path = dir.join(name);
+        fs::write(&path, "private").unwrap();
+        fs::write(
+            path.with_extension("pub"),
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X me@host",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        SshKey::from_path(&path).unwrap()
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let ssh_dir = temp_dir.path().join("ssh");
+        fs::create_dir(&ssh_dir).unwrap();
+        // Shredding only changes how the original goes away
+        let trash = Trash::new(temp_dir.path().join("data")).shredding(true);
+        assert!(trash.list().unwrap().is_empty());
+
+        let key = write_key(&ssh_dir, "work");
+        let entry = trash.put(&key).unwrap();
+        assert_eq!(entry.key_name, "work");
+        assert_eq!(entry.files.len(), 2);
+        assert!(!key.path.exists() && !key.public_path.exists());
+
+        // Nothing readable is left in the trash
+        let files = fs::read(trash.dir.join(&entry.id).join(FILES_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&files).contains("private"));
+
+        // Restoring never overwrites
+        fs::write(&key.public_path, "new").unwrap();
+        assert!(trash.restore(&entry.id).is_err());
+        fs::remove_file(&key.public_path).unwrap();
+
+        let found = trash.find("work").unwrap();
+        assert_eq!(found, entry);
+        trash.restore(&found.id).unwrap();
+        assert_eq!(fs::read_to_string(&key.path).unwrap(), "private");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&key.path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(trash.list().unwrap().is_empty());
+        assert!(trash.find("work").is_err());
+    }
+
+    #[test]
+    fn test_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let trash = Trash::new(temp_dir.path().join("data"));
+
+        let first = trash.put(&write_key(temp_dir.path(), "a")).unwrap();
+        let second = trash.put(&write_key(temp_dir.path(), "a")).unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(trash.list().unwrap().len(), 2);
+
+        assert_eq!(trash.empty(Some(3600)).unwrap(), 0);
+        assert_eq!(trash.empty(None).unwrap(), 2);
+        assert!(trash.list().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+//! tmux and screen awareness: multiplexers keep programs away from the outer
+//! terminal's clipboard and claim a prefix key the TUI may want too
+
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::process::Command;
+
+use base64ct::{Base64, Encoding};
+
+use crate::error::{Result, SkmError};
+
+/// screen drops DCS strings longer than this, so OSC 52 goes in pieces
+const SCREEN_CHUNK: usize = 76;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// The multiplexer skm runs under, if any
+    pub fn detect() -> Option<Self> {
+        Self::from_vars(
+            std::env::var("TMUX").ok().as_deref(),
+            std::env::var("STY").ok().as_deref(),
+        )
+    }
+
+    fn from_vars(tmux: Option<&str>, sty: Option<&str>) -> Option<Self> {
+        if tmux.is_some_and(|v| !v.is_empty()) {
+            Some(Multiplexer::Tmux)
+        } else if sty.is_some_and(|v| !v.is_empty()) {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Screen => "screen",
+        }
+    }
+
+    /// Prefix keys in tmux notation, e.g. `C-b`. tmux is asked for its
+    /// (possibly two) prefixes; screen's escape comes from ~/.screenrc,
+    /// defaulting to `C-a`.
+    pub fn prefixes(self) -> Vec<String> {
+        match self {
+            Multiplexer::Tmux => ["prefix", "prefix2"]
+                .iter()
+                .filter_map(|option| tmux_option(option))
+                .filter(|value| value != "None")
+                .collect(),
+            Multiplexer::Screen => {
+                let screenrc = directories::BaseDirs::new()
+                    .map(|dirs| dirs.home_dir().join(".screenrc"))
+                    .and_then(|path| fs::read_to_string(path).ok())
+                    .unwrap_or_default();
+                vec![screen_escape(&screenrc).unwrap_or_else(|| "C-a".to_string())]
+            }
+        }
+    }
+
+    /// How to get the prefix key itself through to the program
+    pub fn send_prefix_hint(self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "press the prefix twice to send it through",
+            Multiplexer::Screen => "press the prefix, then its letter, to send it through",
+        }
+    }
+
+    /// tmux 3.3+ drops pass-through sequences unless `allow-passthrough` is on
+    pub fn passthrough_blocked(self) -> bool {
+        self == Multiplexer::Tmux && tmux_option("allow-passthrough").as_deref() == Some("off")
+    }
+
+    /// Wrap an escape sequence so it reaches the terminal outside
+    fn passthrough(self, sequence: &str) -> String {
+        match self {
+            // Escapes inside the wrapper are doubled
+            Multiplexer::Tmux => {
+                format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+            }
+            Multiplexer::Screen => sequence
+                .as_bytes()
+                .chunks(SCREEN_CHUNK)
+                .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+                .collect(),
+        }
+    }
+}
+
+/// Which of `ctrl_keys`, the TUI's Ctrl bindings as (letter, what it does),
+/// a multiplexer prefix swallows, e.g. `Ctrl+P (palette)`
+pub fn prefix_conflicts(prefix: &str, ctrl_keys: &[(char, String)]) -> Vec<String> {
+    let Some(key) = ctrl_key(prefix) else {
+        return Vec::new();
+    };
+    ctrl_keys
+        .iter()
+        .filter(|(binding, _)| *binding == key)
+        .map(|(binding, action)| format!("Ctrl+{} ({})", binding.to_ascii_uppercase(), action))
+        .collect()
+}
+
+/// One line warning about swallowed bindings, for the TUI and `skm doctor`
+pub fn conflict_warning(multiplexer: Multiplexer, ctrl_keys: &[(char, String)]) -> Option<String> {
+    let conflicts: Vec<String> = multiplexer
+        .prefixes()
+        .iter()
+        .flat_map(|prefix| {
+            prefix_conflicts(prefix, ctrl_keys)
+                .into_iter()
+                .map(move |conflict| {
+                    format!(
+                        "{} prefix {} takes {}",
+                        multiplexer.name(),
+                        prefix,
+                        conflict
+                    )
+                })
+        })
+        .collect();
+    (!conflicts.is_empty()).then(|| conflicts.join("; "))
+}
+
+/// Copy to the clipboard. Inside tmux or screen the text also goes to the
+/// outer terminal as OSC 52, which works over ssh where there is no display
+/// for the system clipboard.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let native = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| SkmError::Unknown(format!("Failed to copy to clipboard: {}", e)));
+
+    let Some(multiplexer) = Multiplexer::detect() else {
+        return native;
+    };
+    if let Err(e) = native {
+        tracing::debug!("{}; relying on OSC 52", e);
+    }
+    let sequence = multiplexer.passthrough(&osc52(text));
+    write_to_terminal(sequence.as_bytes())
+}
+
+/// OSC 52 "set clipboard" sequence for `text`
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", Base64::encode_string(text.as_bytes()))
+}
+
+/// Write straight to the controlling terminal, so it works while stdout
+/// is piped; falls back to stdout when there is no tty to open
+fn write_to_terminal(bytes: &[u8]) -> Result<()> {
+    if let Ok(mut tty) = fs::OpenOptions::new().write(true).open("/dev/tty") {
+        tty.write_all(bytes)?;
+        return Ok(tty.flush()?);
+    }
+
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return Err(SkmError::Unknown(
+            "Failed to copy to clipboard: no terminal to send OSC 52 to".to_string(),
+        ));
+    }
+    stdout.write_all(bytes)?;
+    Ok(stdout.flush()?)
+}
+
+fn tmux_option(name: &str) -> Option<String> {
+    let output = Command::new("tmux")
+        .args(["show-options", "-gv", name])
+        .output()
+        .ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// `escape ^Xx` in a screenrc, as `C-x`
+fn screen_escape(screenrc: &str) -> Option<String> {
+    screenrc.lines().rev().find_map(|line| {
+        let mut words = line.split_whitespace();
+        if words.next()? != "escape" {
+            return None;
+        }
+        let key = words.next()?.strip_prefix('^')?.chars().next()?;
+        Some(format!("C-{}", key.to_ascii_lowercase()))
+    })
+}
+
+/// The letter of a Ctrl prefix such as `C-b` or `^B`
+fn ctrl_key(prefix: &str) -> Option<char> {
+    let rest = prefix
+        .strip_prefix("C-")
+        .or_else(|| prefix.strip_prefix('^'))?;
+    let mut chars = rest.chars();
+    let key = chars.next()?;
+    chars.next().is_none().then(|| key.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Multiplexer::from_vars(Some("/tmp/tmux-0/default,1,0"), None),
+            Some(Multiplexer::Tmux)
+        );
+        assert_eq!(
+            Multiplexer::from_vars(None, Some("1234.pts-0.host")),
+            Some(Multiplexer::Screen)
+        );
+        assert_eq!(Multiplexer::from_vars(Some(""), None), None);
+    }
+
+    #[test]
+    fn test_prefix_conflicts() {
+        let ctrl_keys = [('p', "palette".to_string()), ('q', "quit".to_string())];
+        assert!(prefix_conflicts("C-b", &ctrl_keys).is_empty());
+        assert_eq!(
+            prefix_conflicts("C-p", &ctrl_keys),
+            vec!["Ctrl+P (palette)"]
+        );
+        assert_eq!(prefix_conflicts("^Q", &ctrl_keys), vec!["Ctrl+Q (quit)"]);
+        assert!(prefix_conflicts("M-a", &ctrl_keys).is_empty());
+        assert_eq!(
+            screen_escape("startup_message off\nescape ^Rr\n").as_deref(),
+            Some("C-r")
+        );
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let osc = osc52("hi");
+        assert_eq!(osc, "\x1b]52;c;aGk=\x07");
+        assert_eq!(
+            Multiplexer::Tmux.passthrough(&osc),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+
+        let long = osc52(&"x".repeat(200));
+        let wrapped = Multiplexer::Screen.passthrough(&long);
+        assert_eq!(
+            wrapped.matches("\x1bP").count(),
+            long.len().div_ceil(SCREEN_CHUNK)
+        );
+    }
+}
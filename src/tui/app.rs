@@ -1,17 +1,74 @@
-use crate::config::Config;
+use crate::config::{Config, Settings};
+use crate::crypto::backup::{
+    BackupManager, EntrySummary, ExportOptions, ImportOptions, MergeStrategy, get_hostname,
+    get_username,
+};
+use crate::crypto::provenance::{Provenance, ProvenanceStore};
+use crate::crypto::{Lock, LockPassphrase};
+use crate::dates::DateFormat;
 use crate::error::Result;
-use crate::ssh::{KeyScanner, SshKey};
+use crate::logging::LogBuffer;
+use crate::ssh::agent::{self, AgentIdentity, AgentOrigin, LifetimeStore};
+use crate::ssh::approval;
+use crate::ssh::audit::{self, AuditReport};
+use crate::ssh::cert::CertInfo;
+use crate::ssh::config::{self, HostEntry, SshConfig, expand_path};
+use crate::ssh::deploy::{Deployment, DeploymentStore};
+use crate::ssh::ephemeral;
+use crate::ssh::expiry::{ExpiryPolicy, KeyAgeStore};
+use crate::ssh::generate::KeyGenerator;
+use crate::ssh::inspect::Inspection;
+use crate::ssh::keys::fingerprint_with;
+use crate::ssh::perms::{self, PermissionChange};
+use crate::ssh::randomart;
+use crate::ssh::repos::{self, SshRemote};
+use crate::ssh::team;
+use crate::ssh::trash::Trash;
+use crate::ssh::{AgentClient, KeyScanner, KeyStatus, SshKey};
+use crate::terminal::{self, Multiplexer};
+use crate::tui::components::finder::KeyFinder;
+use crate::tui::components::migrate::MigrateWizard;
+use crate::tui::components::palette::CommandPalette;
 use crate::tui::components::wizard::{CreateWizard, WizardStep};
+use crate::tui::dashboard::Dashboard;
+use crate::tui::events::Pending;
+use crate::tui::keymap::Keymap;
+use crate::tui::undo::{Edit, UndoStack};
+use crate::tui::watch::DirWatcher;
+use crate::tui::worker::{Job, Outcome, Reporter};
+use chrono::{DateTime, Local};
+use ssh_key::PublicKey;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a success or info toast stays up
+pub const TOAST_DURATION: Duration = Duration::from_secs(4);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     KeyList,
     KeyDetail,
+    HostList,  // the Host blocks of the ssh config, on the second tab
+    Dashboard, // counts, oldest key, last backup, agent and audit at a glance
     CreateWizard,
     ExportDialog,
     ImportDialog,
     DeleteConfirm,
+    ChangePassphrase,
+    RenameDialog,
+    EditComment,
+    CommandPalette,
+    FindKey, // fuzzy finder over key names and comments, over the key list
+    FixPermissions,
+    InspectFile,
+    FilterInput,
+    Locked,
+    AgentAdd,      // lifetime (and passphrase) for loading the selected key into ssh-agent
+    MigrateWizard, // restore a backup, pull GitHub keys and load the agent on a new machine
+    Working,       // a job is running; see `App::job`
     MessageDialog,
     Quit,
 }
@@ -21,29 +78,157 @@ pub enum DialogState {
     EnterPath,
     EnterPassphrase,
     Confirm,
+    SelectKeys,     // checklist of the keys to export, or of the backup's keys to import
+    ChooseStrategy, // import only: what to do with keys that already exist
+    EnterLifetime,  // agent only: how long the key stays loaded
 }
 
+/// A key the agent holds, as the agent panel shows it
 #[derive(Debug, Clone)]
+pub struct AgentEntry {
+    pub identity: AgentIdentity,
+    pub fingerprint: String,
+    pub key_name: Option<String>, // the key in the ssh directory it was loaded from
+    pub expires_at: Option<DateTime<Local>>, // if skm loaded it with a lifetime
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphraseStep {
+    EnterOld,
+    EnterNew,
+    ConfirmNew,
+}
+
+#[derive(Debug)]
 pub struct App {
     pub state: AppState,
     pub config: Config,
     pub keys: Vec<SshKey>,
-    pub selected_index: usize,
+    pub agent_keys: HashSet<String>, // names of keys currently loaded in ssh-agent
+    pub agent_entries: Vec<AgentEntry>, // everything the agent holds, for the agent panel
+    pub agent_error: Option<String>, // why the agent couldn't be listed
+    pub agent_origin: AgentOrigin,   // forwarded agents get a warning before adding
+    pub show_agent: bool,            // the agent panel under the key list
+    pub agent_lifetime_input: String, // typed into the add-to-agent dialog
+    pub selected_index: usize,       // into `visible`
+    pub ssh_config: Option<SshConfig>, // loaded when the hosts tab opens
+    pub ssh_config_error: Option<String>, // why it couldn't be
+    pub host_index: usize,           // into `ssh_config.hosts`
+    pub dashboard: Option<Dashboard>, // gathered when the dashboard opens
+    pub filter: String,              // typed after '/'; narrows the key list
+    pub visible: Vec<usize>,         // indices into `keys` of those matching the filter
+    pub marked: BTreeSet<String>, // names of keys picked with Space; export and delete act on them
+    pub fingerprints: Vec<Option<String>>, // per entry of `keys`, in the hash from --fingerprint-hash
     pub selected_key: Option<SshKey>,
+    pub selected_key_hosts: Vec<String>, // ssh config Host aliases using the selected key
+    pub selected_key_repos: Vec<String>, // git repos whose ssh remotes use the selected key
+    pub selected_key_cert: Option<CertInfo>, // certificate paired with the selected key
+    pub selected_key_deployments: Vec<Deployment>, // hosts `skm deploy` put the selected key on
+    pub selected_key_provenance: Option<Provenance>, // backup the selected key was imported from
+    pub selected_key_fingerprint: Option<String>, // in the hash from --fingerprint-hash
+    pub selected_key_randomart: Option<String>, // drawn from the same fingerprint
+    pub selected_key_authorized_line: Option<String>, // the public key as a server expects it
+    pub detail_scroll: u16,              // first line of the detail view in sight
+    pub detail_height: u16,              // rows the detail view showed last frame, for paging
+    pub list_offset: usize, // first key the table showed, so it only scrolls to follow the highlight
+    pub list_height: u16,   // keys the table showed last frame, for paging
+    pub pending_keys: Pending, // vim count or `g` typed in the list, waiting for the rest
+    pub remote_checks: Arc<Mutex<Vec<RemoteStatus>>>, // one per deployment, filled in by check threads
+    pub repo_remotes: Option<Vec<SshRemote>>,         // scanned on first use, cleared on refresh
+    pub audit: AuditReport,                           // rerun whenever the keys are refreshed
     pub message: Option<(String, MessageType, AppState)>, // (message, type, return_state)
+    pub toast: Option<(String, MessageType, Instant)>, // non-modal notice and when it was raised
+    pub job: Option<Job>,                             // long operation running on a worker thread
+    pub quit_after_job: bool, // quit was pressed during the job; quit once it is done
+    pub watcher: Option<DirWatcher>, // notices keys changed outside skm; None if the dir can't be watched
+    pub keys_stale: bool,            // the watcher saw a change the list hasn't picked up yet
     pub show_help: bool,
+    pub help_scroll: u16, // first line of the help overlay shown
+    pub help_height: u16, // lines the help overlay showed when last drawn
+    pub show_debug_log: bool,
+    pub log_buffer: LogBuffer,
+    pub multiplexer: Option<Multiplexer>, // tmux or screen the TUI runs inside
+    pub date_format: DateFormat,
+    pub expiry: ExpiryPolicy, // from [expiry]; keys near or past their max age are highlighted
+    pub shred_on_delete: bool, // from [delete]; private keys are overwritten on their way to the trash
+    pub rename_updates_config: bool, // from [tui]; renames rewrite IdentityFile lines too
+    pub keymap: Keymap,        // from [keys]; what the list, detail and global keys do
 
     // Wizard state
     pub wizard: Option<CreateWizard>,
     pub wizard_input: String,
-    pub wizard_confirm_passphrase: String,
+    pub migrate: Option<MigrateWizard>,
 
     // Dialog states
     pub export_path: String,
+    pub export_checked: Vec<bool>, // one per key, true = export it
+    pub export_index: usize,
+    pub export_private: bool, // false exports public keys only
     pub import_path: String,
+    pub import_entries: Vec<EntrySummary>, // keys in the decrypted backup
+    pub import_checked: Vec<bool>,         // one per entry, true = import it
+    pub import_index: usize,
+    pub import_strategy: MergeStrategy, // for checked keys that already exist
     pub dialog_passphrase: String,
     pub dialog_state: DialogState,
     pub confirm_delete: bool,
+
+    // Change passphrase dialog state
+    pub passphrase_step: PassphraseStep,
+    pub old_passphrase: String,
+    pub new_passphrase: String,
+    pub confirm_new_passphrase: String,
+
+    // Rename dialog state
+    pub rename_input: String,
+
+    // Comment editing state
+    pub comment_input: String,
+
+    // Inspect file view: path prompt, then the parsed file
+    pub inspect_input: String,
+    pub inspection: Option<Inspection>,
+    pub inspect_scroll: u16,
+
+    // Command palette (Ctrl+P)
+    pub palette: Option<CommandPalette>,
+
+    // Fuzzy key finder (f)
+    pub finder: Option<KeyFinder>,
+
+    // Session undo history for comment and rename edits and deletions
+    pub undo_stack: UndoStack,
+
+    // Unsafe file modes found on startup, offered for fixing
+    pub permission_changes: Vec<PermissionChange>,
+
+    // Idle lock
+    pub idle_timeout: Option<Duration>,
+    pub last_activity: Instant,
+    pub unlock_input: String,
+    pub unlock_error: Option<String>,
+}
+
+/// Whether a deployed key is still in the host's authorized_keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteStatus {
+    Unchecked,
+    Checking,
+    Present,
+    Absent,
+    Failed(String),
+}
+
+impl fmt::Display for RemoteStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteStatus::Unchecked => write!(f, "not checked"),
+            RemoteStatus::Checking => write!(f, "checking..."),
+            RemoteStatus::Present => write!(f, "present"),
+            RemoteStatus::Absent => write!(f, "ABSENT"),
+            RemoteStatus::Failed(e) => write!(f, "error: {}", e),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -57,72 +242,713 @@ impl App {
     pub fn new(config: Config) -> Result<Self> {
         let scanner = KeyScanner::new(&config.ssh_dir);
         let keys = scanner.scan()?;
+        // Settings that don't parse are left out, and said so once the UI is up
+        let mut ignored = Vec::new();
+        let settings = config.load_settings().unwrap_or_else(|e| {
+            ignored.push(format!("Ignoring the settings file: {}", e));
+            Settings::default()
+        });
+        let expiry = ExpiryPolicy::from_settings(&settings.expiry).unwrap_or_else(|e| {
+            ignored.push(format!("Ignoring [expiry] settings: {}", e));
+            ExpiryPolicy::default()
+        });
+        let expiry = match expiry.clone().with_known_ages(&config.export_dir) {
+            Ok(expiry) => expiry,
+            Err(e) => {
+                tracing::warn!("Failed to load recorded key ages: {}", e);
+                expiry
+            }
+        };
+        let keymap = Keymap::from_settings(&settings.keys).unwrap_or_else(|e| {
+            ignored.push(format!("Ignoring [keys] settings: {}", e));
+            Keymap::default()
+        });
+        let audit = audit_keys(&config.ssh_dir, &keys, &expiry);
+        let watcher = DirWatcher::new(&config.ssh_dir)
+            .inspect_err(|e| tracing::warn!("Not refreshing keys automatically: {}", e))
+            .ok();
+        let idle_timeout = settings
+            .tui
+            .idle_lock_minutes
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60));
 
-        Ok(Self {
+        let mut app = Self {
             state: AppState::KeyList,
             config,
             keys,
+            agent_keys: HashSet::new(),
+            agent_entries: Vec::new(),
+            agent_error: None,
+            agent_origin: AgentOrigin::NoAgent,
+            show_agent: false,
+            agent_lifetime_input: String::new(),
             selected_index: 0,
+            ssh_config: None,
+            ssh_config_error: None,
+            host_index: 0,
+            dashboard: None,
+            filter: String::new(),
+            visible: Vec::new(),
+            marked: BTreeSet::new(),
+            fingerprints: Vec::new(),
             selected_key: None,
+            selected_key_hosts: Vec::new(),
+            selected_key_repos: Vec::new(),
+            selected_key_cert: None,
+            selected_key_deployments: Vec::new(),
+            selected_key_provenance: None,
+            selected_key_fingerprint: None,
+            selected_key_randomart: None,
+            selected_key_authorized_line: None,
+            detail_scroll: 0,
+            detail_height: 0,
+            list_offset: 0,
+            list_height: 0,
+            pending_keys: Pending::Nothing,
+            remote_checks: Arc::default(),
+            repo_remotes: None,
+            audit,
             message: None,
+            toast: None,
+            job: None,
+            quit_after_job: false,
+            watcher,
+            keys_stale: false,
             show_help: false,
+            help_scroll: 0,
+            help_height: 0,
+            show_debug_log: false,
+            log_buffer: LogBuffer::default(),
+            multiplexer: Multiplexer::detect(),
+            date_format: DateFormat::new(&settings.display),
+            expiry,
+            shred_on_delete: settings.delete.shred,
+            rename_updates_config: settings.tui.rename_updates_config,
+            keymap,
             wizard: None,
             wizard_input: String::new(),
-            wizard_confirm_passphrase: String::new(),
+            migrate: None,
             export_path: String::new(),
+            export_checked: Vec::new(),
+            export_index: 0,
+            export_private: true,
             import_path: String::new(),
+            import_entries: Vec::new(),
+            import_checked: Vec::new(),
+            import_index: 0,
+            import_strategy: MergeStrategy::SkipExisting,
             dialog_passphrase: String::new(),
             dialog_state: DialogState::EnterPath,
             confirm_delete: false,
-        })
+            passphrase_step: PassphraseStep::EnterOld,
+            old_passphrase: String::new(),
+            new_passphrase: String::new(),
+            confirm_new_passphrase: String::new(),
+            rename_input: String::new(),
+            comment_input: String::new(),
+            inspect_input: String::new(),
+            inspection: None,
+            inspect_scroll: 0,
+            palette: None,
+            finder: None,
+            undo_stack: UndoStack::default(),
+            permission_changes: Vec::new(),
+            idle_timeout,
+            last_activity: Instant::now(),
+            unlock_input: String::new(),
+            unlock_error: None,
+        };
+        app.apply_filter();
+        app.refresh_fingerprints();
+        app.refresh_agent_status();
+        app.check_permissions();
+        app.warn_prefix_conflicts();
+        if !ignored.is_empty() {
+            for problem in &ignored {
+                tracing::warn!("{}", problem);
+            }
+            let state = app.state;
+            app.set_message(ignored.join("\n"), MessageType::Error, state);
+        }
+
+        Ok(app)
     }
 
     pub fn refresh_keys(&mut self) -> Result<()> {
         let scanner = KeyScanner::new(&self.config.ssh_dir);
         self.keys = scanner.scan()?;
+        self.visible = self.matching_keys();
+        let keys = &self.keys;
+        self.marked
+            .retain(|name| keys.iter().any(|key| &key.name == name));
 
         // Adjust selected index if out of bounds
-        if !self.keys.is_empty() && self.selected_index >= self.keys.len() {
-            self.selected_index = self.keys.len() - 1;
+        if !self.visible.is_empty() && self.selected_index >= self.visible.len() {
+            self.selected_index = self.visible.len() - 1;
         }
 
+        self.refresh_fingerprints();
+        self.refresh_agent_status();
+        self.repo_remotes = None;
+        self.reload_key_ages();
+        self.audit = audit_keys(&self.config.ssh_dir, &self.keys, &self.expiry);
         Ok(())
     }
 
+    /// Pick up key ages recorded since the policy was loaded
+    fn reload_key_ages(&mut self) {
+        match self.expiry.clone().with_known_ages(&self.config.export_dir) {
+            Ok(expiry) => self.expiry = expiry,
+            Err(e) => tracing::warn!("Failed to load recorded key ages: {}", e),
+        }
+    }
+
+    /// Record how old `keys` are before they are written anew
+    pub fn remember_key_ages(&self, keys: &[SshKey]) {
+        if let Err(e) = KeyAgeStore::new(&self.config.export_dir).remember(keys) {
+            tracing::warn!("Failed to record key ages: {}", e);
+        }
+    }
+
+    /// Rescan once the watcher has seen keys change outside skm. It waits
+    /// while a dialog, the wizard or a job is open, so nothing changes under
+    /// them, and keeps the highlighted key highlighted. The detail view stays
+    /// as it is unless its key is gone.
+    pub fn poll_watcher(&mut self) {
+        if self.watcher.as_mut().is_some_and(DirWatcher::poll) {
+            self.keys_stale = true;
+        }
+        let in_view = matches!(
+            self.state,
+            AppState::KeyList | AppState::KeyDetail | AppState::HostList | AppState::Dashboard
+        );
+        if !self.keys_stale || !in_view || self.job.is_some() {
+            return;
+        }
+        self.keys_stale = false;
+
+        let selected = self.get_selected_key().map(|key| key.name.clone());
+        if let Err(e) = self.refresh_keys() {
+            tracing::warn!("Failed to refresh keys: {}", e);
+            return;
+        }
+        tracing::info!("Keys changed on disk, {} found", self.keys.len());
+
+        let still_there = selected
+            .as_deref()
+            .and_then(|name| self.visible_keys().position(|key| key.name == name));
+        match (still_there, self.state) {
+            (Some(index), _) => self.selected_index = index,
+            // The key on show is gone
+            (None, AppState::KeyDetail) => self.state = AppState::KeyList,
+            _ => {}
+        }
+        if self.state == AppState::Dashboard {
+            self.show_dashboard();
+        }
+    }
+
+    /// Worked out once per refresh rather than on every frame, as MD5 means
+    /// reading the key files
+    fn refresh_fingerprints(&mut self) {
+        let hash = self.config.fingerprint_hash;
+        self.fingerprints = self
+            .keys
+            .iter()
+            .map(|key| key.fingerprint_as(hash))
+            .collect();
+    }
+
+    /// Work out which keys are loaded in ssh-agent; no agent simply means none are
+    pub fn refresh_agent_status(&mut self) {
+        self.agent_origin = AgentOrigin::detect();
+        let loaded = match AgentClient::connect_env().and_then(|mut agent| agent.list_identities())
+        {
+            Ok(loaded) => {
+                self.agent_error = None;
+                loaded
+            }
+            Err(e) => {
+                self.agent_error = Some(e.to_string());
+                Vec::new()
+            }
+        };
+        let lifetimes = LifetimeStore::new(&self.config.export_dir)
+            .active()
+            .unwrap_or_default();
+        let hash = self.config.fingerprint_hash;
+
+        let public_keys: Vec<(String, PublicKey)> = self
+            .keys
+            .iter()
+            .filter_map(|key| Some((key.name.clone(), key.read_public_key().ok()?)))
+            .collect();
+        self.agent_entries = loaded
+            .into_iter()
+            .map(|identity| AgentEntry {
+                key_name: public_keys
+                    .iter()
+                    .find(|(_, public_key)| public_key.key_data() == identity.key_data())
+                    .map(|(name, _)| name.clone()),
+                fingerprint: fingerprint_with(&identity.public_key, hash),
+                expires_at: agent::expires_at(&lifetimes, identity.key_data()),
+                identity,
+            })
+            .collect();
+        self.agent_keys = self
+            .agent_entries
+            .iter()
+            .filter_map(|entry| entry.key_name.clone())
+            .collect();
+    }
+
+    pub fn toggle_agent_panel(&mut self) {
+        self.show_agent = !self.show_agent;
+        if self.show_agent {
+            self.refresh_agent_status();
+        }
+    }
+
+    /// Ask how long the selected key should stay in the agent, and for its
+    /// passphrase if it has one
+    pub fn start_agent_add(&mut self) {
+        let Some(key) = self.get_selected_key() else {
+            return;
+        };
+        if !key.has_private() {
+            let msg = format!("'{}' has no private key to load", key.name);
+            self.set_message(msg, MessageType::Error, AppState::KeyList);
+            return;
+        }
+        self.agent_lifetime_input.clear();
+        self.dialog_passphrase.clear();
+        self.dialog_state = DialogState::EnterLifetime;
+        self.state = AppState::AgentAdd;
+    }
+
+    /// The lifetime typed into the add-to-agent dialog, `Some(None)` for none;
+    /// a typo is reported and sends the dialog back to the lifetime prompt
+    pub fn agent_lifetime(&mut self) -> Option<Option<u32>> {
+        match self.agent_lifetime_input.trim() {
+            "" => Some(None),
+            ttl => match ephemeral::parse_ttl(ttl) {
+                Ok(seconds) => Some(Some(seconds)),
+                Err(e) => {
+                    self.dialog_state = DialogState::EnterLifetime;
+                    self.set_message(e.to_string(), MessageType::Error, AppState::AgentAdd);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Load the selected key into the agent on a worker thread, as decrypting
+    /// it can take a moment
+    pub fn add_selected_to_agent(&mut self) {
+        let Some(key) = self.get_selected_key().cloned() else {
+            self.state = AppState::KeyList;
+            return;
+        };
+        let Some(lifetime) = self.agent_lifetime() else {
+            return;
+        };
+        let passphrase = (!self.dialog_passphrase.is_empty())
+            .then(|| std::mem::take(&mut self.dialog_passphrase));
+        let store = LifetimeStore::new(&self.config.export_dir);
+
+        self.start_job("Adding to agent", AppState::AgentAdd, move |_| {
+            let mut private_key = key.load_private_key(passphrase.as_deref())?;
+            if private_key.comment().is_empty() {
+                // Like ssh-add, fall back to something that identifies the key
+                let comment = key
+                    .comment
+                    .clone()
+                    .unwrap_or_else(|| key.path.display().to_string());
+                private_key.set_comment(comment);
+            }
+            AgentClient::connect_env()?.add_identity(&private_key, lifetime, false)?;
+            if let Err(e) = store.record(private_key.public_key().key_data(), lifetime) {
+                tracing::warn!("Failed to record the agent lifetime: {}", e);
+            }
+            Ok(Outcome::AddedToAgent {
+                name: key.name,
+                lifetime,
+            })
+        });
+    }
+
+    pub fn remove_selected_from_agent(&mut self) {
+        let Some(key) = self.get_selected_key().cloned() else {
+            return;
+        };
+        if !self.agent_keys.contains(&key.name) {
+            let msg = format!("'{}' is not loaded in ssh-agent", key.name);
+            self.set_message(msg, MessageType::Info, AppState::KeyList);
+            return;
+        }
+
+        let result = key.read_public_key().and_then(|public_key| {
+            AgentClient::connect_env()?.remove_identity(public_key.key_data())?;
+            if let Err(e) =
+                LifetimeStore::new(&self.config.export_dir).forget(public_key.key_data())
+            {
+                tracing::warn!("Failed to forget the agent lifetime: {}", e);
+            }
+            Ok(())
+        });
+        self.refresh_agent_status();
+        match result {
+            Ok(()) => self.set_message(
+                format!("Removed '{}' from ssh-agent", key.name),
+                MessageType::Success,
+                AppState::KeyList,
+            ),
+            Err(e) => self.set_message(
+                format!("Failed to remove '{}' from ssh-agent: {}", key.name, e),
+                MessageType::Error,
+                AppState::KeyList,
+            ),
+        }
+    }
+
     pub fn next_key(&mut self) {
-        if !self.keys.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.keys.len();
+        if !self.visible.is_empty() {
+            self.selected_index = (self.selected_index + 1) % self.visible.len();
         }
     }
 
     pub fn previous_key(&mut self) {
-        if !self.keys.is_empty() {
+        if !self.visible.is_empty() {
             if self.selected_index == 0 {
-                self.selected_index = self.keys.len() - 1;
+                self.selected_index = self.visible.len() - 1;
             } else {
                 self.selected_index -= 1;
             }
         }
     }
 
+    /// A screenful of keys further down, stopping at the last
+    pub fn next_page(&mut self) {
+        let page = self.list_height.max(1) as usize;
+        let last = self.visible.len().saturating_sub(1);
+        self.selected_index = (self.selected_index + page).min(last);
+    }
+
+    /// A screenful of keys further up, stopping at the first
+    pub fn previous_page(&mut self) {
+        let page = self.list_height.max(1) as usize;
+        self.selected_index = self.selected_index.saturating_sub(page);
+    }
+
+    /// Move the highlight `delta` keys down, or up if negative, stopping at
+    /// either end
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected_index = self.selected_index.saturating_add_signed(delta).min(last);
+    }
+
+    /// Half a screenful down or up, like vim's Ctrl+D and Ctrl+U
+    pub fn half_page(&mut self, down: bool) {
+        let half = (self.list_height / 2).max(1) as isize;
+        self.move_selection(if down { half } else { -half });
+    }
+
+    /// Highlight the `n`th key shown, counting from 1; past the end means
+    /// the last
+    pub fn go_to_key(&mut self, n: usize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected_index = n.saturating_sub(1).min(last);
+    }
+
+    pub fn first_key(&mut self) {
+        self.selected_index = 0;
+    }
+
+    pub fn last_key(&mut self) {
+        self.selected_index = self.visible.len().saturating_sub(1);
+    }
+
     pub fn get_selected_key(&self) -> Option<&SshKey> {
-        self.keys.get(self.selected_index)
+        self.visible
+            .get(self.selected_index)
+            .and_then(|&index| self.keys.get(index))
+    }
+
+    /// Keys shown in the list, in order
+    pub fn visible_keys(&self) -> impl Iterator<Item = &SshKey> {
+        self.visible
+            .iter()
+            .filter_map(|&index| self.keys.get(index))
+    }
+
+    /// Mark or unmark the highlighted key and move on to the next one
+    pub fn toggle_mark(&mut self) {
+        let Some(name) = self.get_selected_key().map(|key| key.name.clone()) else {
+            return;
+        };
+        if !self.marked.remove(&name) {
+            self.marked.insert(name);
+        }
+        self.next_key();
+    }
+
+    /// Mark every key the filter shows, or unmark them if they all are
+    pub fn toggle_mark_shown(&mut self) {
+        let shown: Vec<String> = self.visible_keys().map(|key| key.name.clone()).collect();
+        if shown.iter().all(|name| self.marked.contains(name)) {
+            for name in &shown {
+                self.marked.remove(name);
+            }
+        } else {
+            self.marked.extend(shown);
+        }
+    }
+
+    /// Keys export and delete act on: the marked ones, or else the highlighted one
+    pub fn target_keys(&self) -> Vec<SshKey> {
+        if self.marked.is_empty() {
+            return self.get_selected_key().cloned().into_iter().collect();
+        }
+        self.keys
+            .iter()
+            .filter(|key| self.marked.contains(&key.name))
+            .cloned()
+            .collect()
+    }
+
+    pub fn start_filter(&mut self) {
+        self.state = AppState::FilterInput;
+    }
+
+    /// Narrow the list to the keys matching `filter`, keeping the
+    /// highlighted key selected while it still matches
+    pub fn apply_filter(&mut self) {
+        let selected = self.get_selected_key().map(|key| key.name.clone());
+        self.visible = self.matching_keys();
+        self.selected_index = selected
+            .and_then(|name| self.visible_keys().position(|key| key.name == name))
+            .unwrap_or(0);
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter.clear();
+        self.apply_filter();
+    }
+
+    fn matching_keys(&self) -> Vec<usize> {
+        let identities = config::search_alias(&self.filter).map(|alias| {
+            self.load_ssh_config()
+                .map(|config| config.identities_for(alias))
+                .unwrap_or_default()
+        });
+        self.keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| key_matches(key, &self.filter, identities.as_deref()))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// `~/.ssh/config` as it is now; `None`, logged, if it can't be read
+    fn load_ssh_config(&self) -> Option<SshConfig> {
+        SshConfig::load(self.config.ssh_dir.join("config"))
+            .inspect_err(|e| tracing::warn!("Failed to read ssh config: {}", e))
+            .ok()
+    }
+
+    /// Open the highlighted key in the detail view
+    pub fn show_key_detail(&mut self) {
+        let Some(key) = self.get_selected_key().cloned() else {
+            return;
+        };
+
+        // A broken or missing ssh config shouldn't block viewing the key
+        let ssh_config = SshConfig::load(self.config.ssh_dir.join("config")).ok();
+        self.selected_key_hosts = ssh_config
+            .as_ref()
+            .map(|config| {
+                config
+                    .hosts_using(&key.path, &key.public_path)
+                    .iter()
+                    .map(|h| h.patterns.join(" "))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let settings = self.config.load_settings().unwrap_or_default();
+        let remotes = self
+            .repo_remotes
+            .get_or_insert_with(|| repos::scan_configured(&settings.repos));
+        self.selected_key_repos = ssh_config
+            .map(|config| repos::repos_by_key(remotes, &config))
+            .and_then(|mut by_key| by_key.remove(&key.path))
+            .unwrap_or_default();
+        self.selected_key_cert = match key.certificate() {
+            Some(Ok(cert)) => Some(cert),
+            Some(Err(e)) => {
+                tracing::warn!("Failed to read certificate for {}: {}", key.name, e);
+                None
+            }
+            None => None,
+        };
+        self.selected_key_deployments = key
+            .fingerprint
+            .as_deref()
+            .map(|fp| DeploymentStore::new(&self.config.export_dir).for_key(fp))
+            .transpose()
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to read deployments: {}", e);
+                None
+            })
+            .unwrap_or_default();
+        self.selected_key_provenance = key.fingerprint.as_deref().and_then(|fp| {
+            ProvenanceStore::new(&self.config.export_dir)
+                .for_key(fp)
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Failed to read key origins: {}", e);
+                    None
+                })
+        });
+        self.selected_key_fingerprint = key.fingerprint_as(self.config.fingerprint_hash);
+        self.selected_key_randomart = key
+            .read_public_key()
+            .ok()
+            .map(|public_key| randomart::randomart(&public_key, self.config.fingerprint_hash));
+        self.selected_key_authorized_line = key.authorized_line(None).ok();
+        self.detail_scroll = 0;
+        // Threads still checking the previous key keep writing to the old list
+        self.remote_checks = Arc::new(Mutex::new(vec![
+            RemoteStatus::Unchecked;
+            self.selected_key_deployments.len()
+        ]));
+        self.selected_key = Some(key);
+        self.state = AppState::KeyDetail;
+    }
+
+    /// Check every recorded deployment of the selected key in the background;
+    /// the detail view shows each result as it comes in
+    pub fn check_deployments(&mut self) {
+        let Some(public_key) = self
+            .selected_key
+            .as_ref()
+            .and_then(|key| key.read_public_key().ok())
+        else {
+            return;
+        };
+        if self.selected_key_deployments.is_empty() {
+            self.set_message(
+                "No recorded deployments for this key.\nKeys deployed with `skm deploy` are checked here.",
+                MessageType::Info,
+                AppState::KeyDetail,
+            );
+            return;
+        }
+
+        let checks = Arc::new(Mutex::new(vec![
+            RemoteStatus::Checking;
+            self.selected_key_deployments.len()
+        ]));
+        self.remote_checks = Arc::clone(&checks);
+
+        for (index, deployment) in self.selected_key_deployments.iter().cloned().enumerate() {
+            let checks = Arc::clone(&checks);
+            let public_key = public_key.clone();
+            std::thread::spawn(move || {
+                let status = match deployment.target().and_then(|t| t.check(&public_key)) {
+                    Ok(true) => RemoteStatus::Present,
+                    Ok(false) => RemoteStatus::Absent,
+                    Err(crate::error::SkmError::Remote(e)) => RemoteStatus::Failed(e),
+                    Err(e) => RemoteStatus::Failed(e.to_string()),
+                };
+                if let Ok(mut checks) = checks.lock() {
+                    checks[index] = status;
+                }
+            });
+        }
+    }
+
+    /// Write the missing .pub file of the selected key from its private key
+    pub fn regenerate_public_key(&mut self) {
+        let Some(mut key) = self.selected_key.clone() else {
+            return;
+        };
+        if key.status != KeyStatus::MissingPublic {
+            return;
+        }
+
+        let result = key
+            .derive_public_key()
+            .and_then(|public_key| key.regenerate_public(public_key.comment()));
+        let _ = self.refresh_keys();
+        match result {
+            Ok(()) => {
+                self.select_key_by_name(&key.name);
+                self.show_key_detail();
+                self.set_message(
+                    format!("Wrote {}", key.public_path.display()),
+                    MessageType::Success,
+                    AppState::KeyDetail,
+                );
+            }
+            Err(e) => self.set_message(
+                format!("Failed to regenerate public key: {}", e),
+                MessageType::Error,
+                AppState::KeyDetail,
+            ),
+        }
     }
 
     pub fn select_key(&mut self, index: usize) {
-        if index < self.keys.len() {
+        if index < self.visible.len() {
             self.selected_index = index;
         }
     }
 
+    /// Errors wait in a dialog to be acknowledged; anything else shows as a
+    /// toast over `return_state`, which is where the app carries on
     pub fn set_message(
         &mut self,
         text: impl Into<String>,
         msg_type: MessageType,
         return_state: AppState,
     ) {
-        self.message = Some((text.into(), msg_type, return_state));
-        self.state = AppState::MessageDialog;
+        if msg_type == MessageType::Error {
+            self.message = Some((text.into(), msg_type, return_state));
+            self.state = AppState::MessageDialog;
+        } else {
+            self.toast = Some((text.into(), msg_type, Instant::now()));
+            self.state = return_state;
+        }
+    }
+
+    /// Drop the toast once it has been up for `TOAST_DURATION`
+    pub fn expire_toast(&mut self) {
+        if self
+            .toast
+            .as_ref()
+            .is_some_and(|(_, _, raised)| raised.elapsed() >= TOAST_DURATION)
+        {
+            self.toast = None;
+        }
+    }
+
+    /// Keys aren't written where other users could read or swap them. Shows
+    /// why and returns true when the ssh directory is such a place.
+    pub fn refuse_unsafe_key_dir(&mut self) -> bool {
+        let Some(found) = perms::unsafe_dir(&self.config.ssh_dir) else {
+            return false;
+        };
+        self.set_message(
+            format!(
+                "Refusing to write keys:\n{}\nRun `chmod go-w {}` first",
+                found,
+                found.path.display()
+            ),
+            MessageType::Error,
+            AppState::KeyList,
+        );
+        true
     }
 
     pub fn clear_message(&mut self) {
@@ -147,7 +973,6 @@ impl App {
     pub fn start_wizard(&mut self) {
         self.wizard = Some(CreateWizard::new());
         self.wizard_input = String::new();
-        self.wizard_confirm_passphrase = String::new();
     }
 
     pub fn get_wizard_step(&self) -> Option<WizardStep> {
@@ -161,10 +986,14 @@ impl App {
                     // Handled separately with number keys
                     false
                 }
+                WizardStep::ChooseSize => {
+                    // Keeps the size already picked, the type's default at first
+                    wizard.next_step();
+                    true
+                }
                 WizardStep::EnterFilename => {
                     if wizard.set_filename(&self.wizard_input) {
                         self.wizard_input.clear();
-                        wizard.next_step();
                         true
                     } else {
                         false
@@ -173,11 +1002,10 @@ impl App {
                 WizardStep::EnterComment => {
                     wizard.set_comment(&self.wizard_input);
                     self.wizard_input.clear();
-                    wizard.next_step();
                     true
                 }
                 WizardStep::EnterPassphrase => {
-                    if wizard.set_passphrase(&self.wizard_input, &self.wizard_confirm_passphrase) {
+                    if wizard.submit_passphrase() {
                         wizard.next_step();
                         true
                     } else {
@@ -191,11 +1019,17 @@ impl App {
         }
     }
 
+    /// Back a step, with what was entered there to edit. Going back past
+    /// the filename keeps the one being typed for when it comes round again.
     pub fn wizard_previous(&mut self) {
         if let Some(ref mut wizard) = self.wizard {
             wizard.previous_step();
-            self.wizard_input.clear();
-            self.wizard_confirm_passphrase.clear();
+            match wizard.step {
+                WizardStep::EnterFilename => self.wizard_input = wizard.options.filename.clone(),
+                WizardStep::EnterComment => self.wizard_input = wizard.options.comment.clone(),
+                WizardStep::SelectType | WizardStep::ChooseSize => {}
+                WizardStep::EnterPassphrase | WizardStep::Confirm => self.wizard_input.clear(),
+            }
         }
     }
 
@@ -206,6 +1040,12 @@ impl App {
         }
     }
 
+    pub fn wizard_choose_size(&mut self, n: usize) {
+        if let Some(ref mut wizard) = self.wizard {
+            wizard.choose_size(n);
+        }
+    }
+
     pub fn get_wizard_options(&self) -> Option<crate::ssh::generate::KeyGenOptions> {
         self.wizard.as_ref().map(|w| w.options.clone())
     }
@@ -223,21 +1063,810 @@ impl App {
     pub fn end_wizard(&mut self) {
         self.wizard = None;
         self.wizard_input.clear();
-        self.wizard_confirm_passphrase.clear();
     }
 
     // Dialog helper methods
+    /// Open the export checklist with the marked keys checked, or all of
+    /// them when none are marked
     pub fn start_export(&mut self) {
         self.export_path = self.get_default_export_path().to_string_lossy().to_string();
+        self.export_checked = self
+            .keys
+            .iter()
+            .map(|key| self.marked.is_empty() || self.marked.contains(&key.name))
+            .collect();
+        self.export_index = 0;
+        self.export_private = true;
         self.dialog_passphrase.clear();
-        self.dialog_state = DialogState::EnterPath;
+        self.dialog_state = DialogState::SelectKeys;
+    }
+
+    pub fn toggle_export_key(&mut self) {
+        if let Some(checked) = self.export_checked.get_mut(self.export_index) {
+            *checked = !*checked;
+        }
+    }
+
+    /// Check every key, or clear them all if they're all checked already
+    pub fn toggle_all_export_keys(&mut self) {
+        let all = self.export_checked.iter().all(|&checked| checked);
+        self.export_checked.fill(!all);
+    }
+
+    /// Names of the checked keys, in list order
+    pub fn checked_export_keys(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .zip(&self.export_checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(key, _)| key.name.clone())
+            .collect()
     }
 
     pub fn start_import(&mut self) {
         self.import_path.clear();
+        self.import_entries.clear();
+        self.import_checked.clear();
+        self.import_index = 0;
+        self.import_strategy = MergeStrategy::SkipExisting;
         self.dialog_passphrase.clear();
         self.dialog_state = DialogState::EnterPath;
     }
+
+    /// Decrypt the backup in the background; the checklist of its keys, all
+    /// of them checked, opens once that's done
+    pub fn load_import_entries(&mut self) {
+        let path = PathBuf::from(&self.import_path);
+        let passphrase = self.dialog_passphrase.clone();
+        self.start_job("Import", AppState::KeyList, move |_| {
+            let backup = BackupManager::read(&path, &passphrase)?;
+            Ok(Outcome::ImportListed(
+                backup.keys.iter().map(|entry| entry.summary()).collect(),
+            ))
+        });
+    }
+
+    /// Checked entries whose name is taken by an existing key
+    pub fn import_conflicts(&self) -> Vec<String> {
+        self.checked_import_keys()
+            .into_iter()
+            .filter(|name| self.keys.iter().any(|key| &key.name == name))
+            .collect()
+    }
+
+    /// Step through the merge strategies, wrapping around
+    pub fn cycle_import_strategy(&mut self, forward: bool) {
+        let all = MergeStrategy::ALL;
+        let i = all
+            .iter()
+            .position(|&s| s == self.import_strategy)
+            .unwrap_or(0);
+        let next = if forward {
+            (i + 1) % all.len()
+        } else {
+            (i + all.len() - 1) % all.len()
+        };
+        self.import_strategy = all[next];
+    }
+
+    /// Import the checked keys from the decrypted backup, resolving
+    /// existing ones with `import_strategy`
+    pub fn import_checked_keys(&mut self) {
+        // Keys imported over themselves keep their age
+        self.remember_key_ages(&self.keys);
+        let manager = BackupManager::new(&self.config.ssh_dir);
+        let path = PathBuf::from(&self.import_path);
+        let passphrase = self.dialog_passphrase.clone();
+        let opts = ImportOptions {
+            merge_strategy: self.import_strategy,
+            dry_run: false,
+            selected_keys: Some(self.checked_import_keys()),
+        };
+        self.start_job("Import", AppState::KeyList, move |_| {
+            let report = manager.import(&path, &passphrase, opts)?;
+            Ok(Outcome::Imported { report, path })
+        });
+    }
+
+    /// Export the checked keys to the dialog's path
+    pub fn export_keys(&mut self) {
+        let manager = BackupManager::new(&self.config.ssh_dir);
+        let path = PathBuf::from(&self.export_path);
+        let passphrase = self.dialog_passphrase.clone();
+        let keys = self.keys.clone();
+        let opts = ExportOptions {
+            description: Some(format!(
+                "Backup from {}",
+                chrono::Local::now().format("%Y-%m-%d")
+            )),
+            include_public_only: !self.export_private,
+            selected_keys: Some(self.checked_export_keys()),
+            armor: false,
+        };
+        self.start_job("Export", AppState::KeyList, move |reporter| {
+            // Ensure parent directory exists
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).ok();
+            }
+            let lock = Lock::Passphrase(passphrase);
+            let report =
+                manager.export_with_progress(&keys, &path, &lock, opts, |i, total, key| {
+                    reporter.progress(i + 1, total, format!("Adding {}", key.name))
+                })?;
+            Ok(Outcome::Exported { report, path })
+        });
+    }
+
+    /// Generate the key the wizard describes
+    pub fn generate_key(&mut self) {
+        let Some(options) = self.get_wizard_options() else {
+            return;
+        };
+        let generator = KeyGenerator::new(&self.config.ssh_dir);
+        self.start_job("Key generation", AppState::CreateWizard, move |_| {
+            Ok(Outcome::Generated(generator.generate(options)?))
+        });
+    }
+
+    pub fn start_migration(&mut self) {
+        self.migrate = Some(MigrateWizard::new());
+        self.state = AppState::MigrateWizard;
+    }
+
+    /// Run the steps the migration wizard was given on a worker thread
+    pub fn run_migration(&mut self) {
+        let Some(plan) = self.migrate.as_ref().map(MigrateWizard::plan) else {
+            return;
+        };
+        let ssh_dir = self.config.ssh_dir.clone();
+        let export_dir = self.config.export_dir.clone();
+        let settings = self.config.load_settings().unwrap_or_default();
+        self.start_job("Migration", AppState::MigrateWizard, move |reporter| {
+            let report = plan.run(&ssh_dir, &export_dir, &settings, |i, total, detail| {
+                reporter.progress(i, total, detail)
+            });
+            Ok(Outcome::Migrated(Box::new(report)))
+        });
+    }
+
+    /// Run `work` on a worker thread; a spinner shows until `poll_job` picks
+    /// up its outcome
+    pub fn start_job<F>(&mut self, action: &str, return_state: AppState, work: F)
+    where
+        F: FnOnce(&Reporter) -> Result<Outcome> + Send + 'static,
+    {
+        self.job = Some(Job::spawn(action, return_state, work));
+        self.state = AppState::Working;
+    }
+
+    /// Called every frame; finishes the running job once its worker is done
+    pub fn poll_job(&mut self) -> Result<()> {
+        let Some(outcome) = self.job.as_mut().and_then(Job::poll) else {
+            return Ok(());
+        };
+        let Some(job) = self.job.take() else {
+            return Ok(());
+        };
+        // The result is shown before the idle lock can hide it
+        self.touch();
+        let quit = std::mem::take(&mut self.quit_after_job);
+
+        match outcome {
+            Ok(outcome) => {
+                self.finish_job(outcome)?;
+                if quit {
+                    self.state = AppState::Quit;
+                }
+            }
+            // A failure is shown rather than quitting past it
+            Err(e) => self.set_message(
+                format!("{} failed: {}", job.action, e),
+                MessageType::Error,
+                job.return_state,
+            ),
+        }
+        Ok(())
+    }
+
+    fn finish_job(&mut self, outcome: Outcome) -> Result<()> {
+        match outcome {
+            Outcome::Generated(key) => {
+                self.refresh_keys()?;
+                self.end_wizard();
+                let settings = self.config.load_settings().unwrap_or_default();
+                let requested_by = team::member_name(&get_username(), &get_hostname());
+                let held = approval::hold_new_key(
+                    &settings.approval,
+                    &self.config.export_dir,
+                    &key,
+                    &requested_by,
+                )
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Failed to hold the key for approval: {}", e);
+                    None
+                });
+                let msg = match held {
+                    Some(_) => "Key created; it can be deployed or published once approved",
+                    None => "Key created successfully",
+                };
+                self.set_message(msg, MessageType::Success, AppState::KeyList);
+            }
+            Outcome::AddedToAgent { name, lifetime } => {
+                self.refresh_agent_status();
+                let until = match lifetime {
+                    Some(seconds) => format!(" for {}", format_lifetime(seconds)),
+                    None => String::new(),
+                };
+                self.set_message(
+                    format!("Added '{}' to ssh-agent{}", name, until),
+                    MessageType::Success,
+                    AppState::KeyList,
+                );
+            }
+            Outcome::Exported { report, path } => self.set_message(
+                format!(
+                    "Exported {} keys to {}",
+                    report.exported.len(),
+                    path.display()
+                ),
+                MessageType::Success,
+                AppState::KeyList,
+            ),
+            Outcome::ImportListed(entries) => {
+                self.import_checked = vec![true; entries.len()];
+                self.import_entries = entries;
+                self.import_index = 0;
+                self.dialog_state = DialogState::SelectKeys;
+                self.state = AppState::ImportDialog;
+            }
+            Outcome::Migrated(report) => {
+                self.migrate = None;
+                self.refresh_keys()?;
+                self.refresh_agent_status();
+                let msg_type = if report.has_failures() {
+                    MessageType::Error
+                } else {
+                    MessageType::Success
+                };
+                self.set_message(
+                    format!("Migration finished:\n{}", report.summary()),
+                    msg_type,
+                    AppState::KeyList,
+                );
+            }
+            Outcome::Imported { report, path } => {
+                if let Err(e) = ProvenanceStore::new(&self.config.export_dir).record_import(
+                    &self.config.ssh_dir,
+                    &path,
+                    &report,
+                ) {
+                    tracing::warn!("Failed to record where the keys came from: {}", e);
+                }
+                self.refresh_keys()?;
+                self.set_message(
+                    format!(
+                        "Import complete: {} imported, {} skipped, {} overwritten",
+                        report.imported.len(),
+                        report.skipped.len(),
+                        report.overwritten.len()
+                    ),
+                    MessageType::Success,
+                    AppState::KeyList,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn toggle_import_entry(&mut self) {
+        if let Some(checked) = self.import_checked.get_mut(self.import_index) {
+            *checked = !*checked;
+        }
+    }
+
+    /// Check everything, or clear everything if it's all checked already
+    pub fn toggle_all_import_entries(&mut self) {
+        let all = self.import_checked.iter().all(|&checked| checked);
+        self.import_checked.fill(!all);
+    }
+
+    /// Names of the checked entries, in backup order
+    pub fn checked_import_keys(&self) -> Vec<String> {
+        self.import_entries
+            .iter()
+            .zip(&self.import_checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(entry, _)| entry.name.clone())
+            .collect()
+    }
+
+    /// Tell the user when a tmux/screen prefix swallows one of our Ctrl keys;
+    /// the permissions prompt goes first if there is one
+    fn warn_prefix_conflicts(&mut self) {
+        let Some(multiplexer) = self.multiplexer else {
+            return;
+        };
+        if self.state != AppState::KeyList {
+            return;
+        }
+        if let Some(warning) = terminal::conflict_warning(multiplexer, &self.keymap.ctrl_keys()) {
+            self.set_message(
+                format!(
+                    "{}.\nTo use it, {}.\nF10 always quits.",
+                    warning,
+                    multiplexer.send_prefix_hint()
+                ),
+                MessageType::Info,
+                AppState::KeyList,
+            );
+        }
+    }
+
+    /// Look for unsafe modes and, if there are any, ask whether to fix them
+    pub fn check_permissions(&mut self) {
+        self.permission_changes = perms::plan(&self.config.ssh_dir, &self.keys);
+        if !self.permission_changes.is_empty() {
+            self.state = AppState::FixPermissions;
+        }
+    }
+
+    pub fn fix_permissions(&mut self) {
+        let changes = std::mem::take(&mut self.permission_changes);
+        let result = perms::apply(&changes);
+        self.audit = audit_keys(&self.config.ssh_dir, &self.keys, &self.expiry);
+        match result {
+            Ok(()) => self.set_message(
+                format!("Fixed permissions on {} file(s)", changes.len()),
+                MessageType::Success,
+                AppState::KeyList,
+            ),
+            Err(e) => self.set_message(
+                format!("Failed to fix permissions: {}", e),
+                MessageType::Error,
+                AppState::KeyList,
+            ),
+        }
+    }
+
+    /// Note user activity, postponing the idle lock
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn check_idle_lock(&mut self) {
+        let idle = self
+            .idle_timeout
+            .is_some_and(|timeout| self.last_activity.elapsed() >= timeout);
+        // A running job finishes first; its result shouldn't be dropped
+        if idle && self.job.is_none() && !matches!(self.state, AppState::Locked | AppState::Quit) {
+            self.lock();
+        }
+    }
+
+    /// Hide everything and drop in-progress dialogs, so typed passphrases
+    /// don't survive the lock
+    pub fn lock(&mut self) {
+        self.end_wizard();
+        self.end_passphrase_change();
+        self.dialog_passphrase.clear();
+        self.palette = None;
+        self.finder = None;
+        self.message = None;
+        self.toast = None;
+        self.selected_key = None;
+        self.show_help = false;
+        self.show_debug_log = false;
+        self.unlock_input.clear();
+        self.unlock_error = None;
+        self.state = AppState::Locked;
+    }
+
+    /// Resume the session; needs the lock passphrase if one is set
+    pub fn unlock(&mut self) {
+        let lock = LockPassphrase::new(&self.config.export_dir);
+        if lock.is_set() {
+            match lock.verify(&self.unlock_input) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.unlock_input.clear();
+                    self.unlock_error = Some("Wrong passphrase".to_string());
+                    return;
+                }
+                Err(e) => {
+                    self.unlock_input.clear();
+                    self.unlock_error = Some(e.to_string());
+                    return;
+                }
+            }
+        }
+
+        self.unlock_input.clear();
+        self.unlock_error = None;
+        self.touch();
+        self.state = AppState::KeyList;
+    }
+
+    pub fn record_edit(&mut self, edit: Edit) {
+        self.undo_stack.record(edit);
+    }
+
+    /// Undo (`redo == false`) or redo the last edit and report the outcome
+    pub fn undo_edit(&mut self, redo: bool) {
+        let return_state = self.state;
+        let result = if redo {
+            self.undo_stack.redo()
+        } else {
+            self.undo_stack.undo()
+        };
+
+        let Some(result) = result else {
+            let what = if redo { "redo" } else { "undo" };
+            self.set_message(
+                format!("Nothing to {}", what),
+                MessageType::Info,
+                return_state,
+            );
+            return;
+        };
+
+        // Pick up the change on disk either way, it may have partially applied
+        let _ = self.refresh_keys();
+        match result {
+            Ok(applied) => {
+                self.select_key_by_name(&applied.key_name);
+                if return_state == AppState::KeyDetail {
+                    self.show_key_detail();
+                }
+                self.set_message(applied.description, MessageType::Success, return_state);
+            }
+            Err(e) => {
+                let what = if redo { "Redo" } else { "Undo" };
+                self.set_message(
+                    format!("{} failed: {}", what, e),
+                    MessageType::Error,
+                    return_state,
+                );
+            }
+        }
+    }
+
+    /// Move the marked keys, or else the highlighted one, to the trash, where
+    /// undo can get them back from
+    pub fn delete_target_keys(&mut self) {
+        let targets = self.target_keys();
+        if targets.is_empty() {
+            return;
+        }
+
+        let shred = self.shred_on_delete;
+        let trash = Trash::new(&self.config.export_dir).shredding(shred);
+        let mut trashed = Vec::new();
+        let mut failed = None;
+        for key in &targets {
+            match trash.put(key) {
+                Ok(entry) => trashed.push((key.path.clone(), entry.id)),
+                Err(e) => {
+                    failed = Some(format!("Failed to delete key '{}': {}", key.name, e));
+                    break;
+                }
+            }
+        }
+
+        let count = trashed.len();
+        if count > 0 {
+            self.record_edit(Edit::Delete {
+                data_dir: self.config.export_dir.clone(),
+                keys: trashed,
+                shred,
+            });
+        }
+        if failed.is_none() {
+            self.marked.clear();
+        }
+        let _ = self.refresh_keys();
+
+        match failed {
+            Some(error) if count == 0 => {
+                self.set_message(error, MessageType::Error, AppState::KeyList)
+            }
+            Some(error) => self.set_message(
+                format!("Moved {} key(s) to the trash (u to undo). {}", count, error),
+                MessageType::Error,
+                AppState::KeyList,
+            ),
+            None if count == 1 => self.set_message(
+                format!("Moved key '{}' to the trash (u to undo)", targets[0].name),
+                MessageType::Success,
+                AppState::KeyList,
+            ),
+            None => self.set_message(
+                format!("Moved {} keys to the trash (u to undo)", count),
+                MessageType::Success,
+                AppState::KeyList,
+            ),
+        }
+    }
+
+    /// Restore the most recently deleted key, including ones deleted before
+    /// this session or with the CLI
+    pub fn restore_last_deleted(&mut self) {
+        let return_state = self.state;
+        let trash = Trash::new(&self.config.export_dir);
+        let result = trash.list().and_then(|entries| match entries.first() {
+            Some(entry) => trash.restore(&entry.id).map(Some),
+            None => Ok(None),
+        });
+
+        match result {
+            Ok(Some(entry)) => {
+                let _ = self.refresh_keys();
+                self.select_key_by_name(&entry.key_name);
+                self.set_message(
+                    format!("Restored key '{}' from the trash", entry.key_name),
+                    MessageType::Success,
+                    return_state,
+                );
+            }
+            Ok(None) => self.set_message(
+                "The trash is empty".to_string(),
+                MessageType::Info,
+                return_state,
+            ),
+            Err(e) => self.set_message(
+                format!("Restore failed: {}", e),
+                MessageType::Error,
+                return_state,
+            ),
+        }
+    }
+
+    /// Show or hide the help overlay, which opens at its top
+    pub fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        self.help_scroll = 0;
+    }
+
+    pub fn open_palette(&mut self) {
+        self.palette = Some(CommandPalette::new(self.state));
+        self.state = AppState::CommandPalette;
+    }
+
+    /// Close the palette and go back to where it was opened
+    pub fn close_palette(&mut self) {
+        if let Some(palette) = self.palette.take() {
+            self.state = palette.origin;
+        }
+    }
+
+    /// Open the finder over all keys, whatever the filter hides
+    pub fn open_finder(&mut self) {
+        self.finder = Some(KeyFinder::new(&self.keys, self.load_ssh_config()));
+        self.state = AppState::FindKey;
+    }
+
+    pub fn close_finder(&mut self) {
+        self.finder = None;
+        self.state = AppState::KeyList;
+    }
+
+    /// Close the finder on the key it has highlighted, if any
+    pub fn jump_to_found_key(&mut self) {
+        let name = self
+            .finder
+            .as_ref()
+            .and_then(|finder| finder.selected_key())
+            .and_then(|index| self.keys.get(index))
+            .map(|key| key.name.clone());
+        self.close_finder();
+        if let Some(name) = name {
+            self.select_key_by_name(&name);
+        }
+    }
+
+    pub fn start_rename(&mut self) {
+        self.rename_input = self
+            .get_selected_key()
+            .map(|k| k.name.clone())
+            .unwrap_or_default();
+    }
+
+    /// Prompt for a file to inspect, starting in the ssh directory
+    pub fn start_inspect(&mut self) {
+        self.inspect_input = format!("{}/", self.config.ssh_dir.display());
+        self.inspection = None;
+        self.inspect_scroll = 0;
+    }
+
+    /// Parse the file at the prompt's path for the inspect view
+    pub fn load_inspection(&mut self) -> Result<()> {
+        let path = expand_path(self.inspect_input.trim());
+        self.inspection = Some(Inspection::load(&path, self.config.fingerprint_hash)?);
+        self.inspect_scroll = 0;
+        Ok(())
+    }
+
+    pub fn start_comment_edit(&mut self) {
+        self.comment_input = self
+            .selected_key
+            .as_ref()
+            .and_then(|k| k.comment.clone())
+            .unwrap_or_default();
+    }
+
+    /// Open the dashboard with the agent and the backups looked at afresh
+    pub fn show_dashboard(&mut self) {
+        self.refresh_agent_status();
+        self.dashboard = Some(Dashboard::collect(&self.keys, &self.config));
+        self.state = AppState::Dashboard;
+    }
+
+    /// Switch to the hosts tab, on a host that uses the highlighted key if
+    /// there is one
+    pub fn show_hosts(&mut self) {
+        self.refresh_hosts();
+        let using = self
+            .get_selected_key()
+            .zip(self.ssh_config.as_ref())
+            .and_then(|(key, config)| {
+                config
+                    .hosts
+                    .iter()
+                    .position(|host| self.host_key(host).is_some_and(|k| k.path == key.path))
+            });
+        if let Some(index) = using {
+            self.host_index = index;
+        }
+        self.selected_key = None;
+        self.state = AppState::HostList;
+    }
+
+    /// Reread the ssh config, keeping the highlighted host in range
+    pub fn refresh_hosts(&mut self) {
+        match SshConfig::load(self.config.ssh_dir.join("config")) {
+            Ok(config) => {
+                self.host_index = self.host_index.min(config.hosts.len().saturating_sub(1));
+                self.ssh_config = Some(config);
+                self.ssh_config_error = None;
+            }
+            Err(e) => {
+                self.ssh_config = None;
+                self.ssh_config_error = Some(e.to_string());
+            }
+        }
+    }
+
+    pub fn get_selected_host(&self) -> Option<&HostEntry> {
+        self.ssh_config.as_ref()?.hosts.get(self.host_index)
+    }
+
+    pub fn next_host(&mut self) {
+        let count = self.ssh_config.as_ref().map_or(0, |c| c.hosts.len());
+        if count > 0 {
+            self.host_index = (self.host_index + 1) % count;
+        }
+    }
+
+    pub fn previous_host(&mut self) {
+        let count = self.ssh_config.as_ref().map_or(0, |c| c.hosts.len());
+        if count > 0 {
+            self.host_index = (self.host_index + count - 1) % count;
+        }
+    }
+
+    /// The key ssh would offer first for `host`: its own `IdentityFile`s for a
+    /// wildcard block, otherwise whatever applies to its alias, ssh's default
+    /// identities included
+    pub fn host_key(&self, host: &HostEntry) -> Option<&SshKey> {
+        let identities = match (host.is_pattern(), self.ssh_config.as_ref()) {
+            (false, Some(config)) => config.identities_for(&host.patterns[0]),
+            _ => host.identity_paths(),
+        };
+        identities.iter().find_map(|path| {
+            self.keys
+                .iter()
+                .find(|key| &key.path == path || &key.public_path == path)
+        })
+    }
+
+    /// Back to the key list on the highlighted host's key; `open` shows its
+    /// detail too
+    pub fn show_host_key(&mut self, open: bool) {
+        let Some(host) = self.get_selected_host() else {
+            self.state = AppState::KeyList;
+            return;
+        };
+        let alias = host.patterns.join(" ");
+        let Some(name) = self.host_key(host).map(|key| key.name.clone()) else {
+            self.set_message(
+                format!("No key in the ssh directory is used for '{}'", alias),
+                MessageType::Info,
+                AppState::HostList,
+            );
+            return;
+        };
+
+        self.select_key_by_name(&name);
+        self.state = AppState::KeyList;
+        if open {
+            self.show_key_detail();
+        }
+    }
+
+    /// Select the key with the given name, if present. Clears the filter
+    /// if it hides the key.
+    pub fn select_key_by_name(&mut self, name: &str) {
+        if !self.visible_keys().any(|k| k.name == name) && self.keys.iter().any(|k| k.name == name)
+        {
+            self.clear_filter();
+        }
+        let index = self.visible_keys().position(|k| k.name == name);
+        if let Some(index) = index {
+            self.selected_index = index;
+        }
+    }
+
+    pub fn start_passphrase_change(&mut self) {
+        let encrypted = self
+            .selected_key
+            .as_ref()
+            .is_some_and(|key| key.is_encrypted());
+
+        self.passphrase_step = if encrypted {
+            PassphraseStep::EnterOld
+        } else {
+            PassphraseStep::EnterNew
+        };
+        self.old_passphrase.clear();
+        self.new_passphrase.clear();
+        self.confirm_new_passphrase.clear();
+    }
+
+    pub fn end_passphrase_change(&mut self) {
+        self.old_passphrase.clear();
+        self.new_passphrase.clear();
+        self.confirm_new_passphrase.clear();
+    }
+}
+
+/// Whether `key`'s name, comment, type or fingerprint contains `query`,
+/// ignoring case; everything matches an empty query. An `@alias` query
+/// matches the keys ssh would offer that host, resolved into `identities`.
+pub fn key_matches(key: &SshKey, query: &str, identities: Option<&[PathBuf]>) -> bool {
+    if let Some(identities) = identities {
+        return key.is_at_any(identities);
+    }
+
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return true;
+    }
+
+    [
+        Some(key.name.as_str()),
+        key.comment.as_deref(),
+        Some(key.key_type.to_string().as_str()),
+        key.fingerprint.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|field| field.to_lowercase().contains(&query))
+}
+
+/// `3600` as `1h`, the way lifetimes are typed
+fn format_lifetime(seconds: u32) -> String {
+    match seconds {
+        s if s % (24 * 60 * 60) == 0 => format!("{}d", s / (24 * 60 * 60)),
+        s if s % (60 * 60) == 0 => format!("{}h", s / (60 * 60)),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Audit of the keys, including the ones due for rotation
+fn audit_keys(ssh_dir: &std::path::Path, keys: &[SshKey], expiry: &ExpiryPolicy) -> AuditReport {
+    let mut report = AuditReport::run(ssh_dir, keys);
+    report.add(audit::audit_expiry(keys, expiry, chrono::Local::now()));
+    report
 }
 
 #[cfg(test)]
@@ -257,6 +1886,26 @@ mod tests {
         assert!(matches!(app.state, AppState::KeyList));
     }
 
+    #[test]
+    fn test_invalid_settings_are_shown() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        config.export_dir = temp_dir.path().join("skm");
+        std::fs::create_dir(&config.export_dir).unwrap();
+        std::fs::write(
+            config.settings_path(),
+            "[keys]\npreset = \"nope\"\n\n[expiry]\nmax_age = \"soon\"\n",
+        )
+        .unwrap();
+
+        let app = App::new(config).unwrap();
+        assert_eq!(app.state, AppState::MessageDialog);
+        let (text, msg_type, _) = app.message.unwrap();
+        assert_eq!(msg_type, MessageType::Error);
+        assert!(text.contains("Ignoring [keys] settings"));
+        assert!(text.contains("Ignoring [expiry] settings"));
+    }
+
     #[test]
     fn test_navigation() {
         let temp_dir = TempDir::new().unwrap();
@@ -275,6 +1924,376 @@ mod tests {
         assert_eq!(app.selected_index, 1);
     }
 
+    #[test]
+    fn test_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, comment) in [("work", "me@work"), ("home", "me@home"), ("deploy", "ci")] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, "test").unwrap();
+            std::fs::write(
+                path.with_extension("pub"),
+                format!("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X {}", comment),
+            )
+            .unwrap();
+        }
+
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+        assert_eq!(app.visible_keys().count(), 3);
+        app.select_key_by_name("work");
+
+        app.filter = "ME@".to_string();
+        app.apply_filter();
+        assert_eq!(app.visible_keys().count(), 2);
+        // The highlighted key stays selected while it matches
+        assert_eq!(app.get_selected_key().unwrap().name, "work");
+        app.next_key();
+        app.next_key();
+        assert_eq!(app.get_selected_key().unwrap().name, "work");
+
+        app.filter = "ci".to_string();
+        app.apply_filter();
+        assert_eq!(app.get_selected_key().unwrap().name, "deploy");
+
+        app.filter = "nothing".to_string();
+        app.apply_filter();
+        assert!(app.get_selected_key().is_none());
+
+        // Selecting a hidden key by name brings the whole list back
+        app.select_key_by_name("deploy");
+        assert!(app.filter.is_empty());
+        assert_eq!(app.get_selected_key().unwrap().name, "deploy");
+
+        // `@alias` shows the keys ssh would offer that host
+        std::fs::write(
+            temp_dir.path().join("config"),
+            format!(
+                "Host prod-bastion\n  IdentityFile {}\n",
+                temp_dir.path().join("home").display()
+            ),
+        )
+        .unwrap();
+        app.filter = "@prod-bastion".to_string();
+        app.apply_filter();
+        let shown: Vec<&str> = app.visible_keys().map(|key| key.name.as_str()).collect();
+        assert_eq!(shown, ["home"]);
+        // None of ssh's default identities exist here
+        app.filter = "@elsewhere".to_string();
+        app.apply_filter();
+        assert!(app.get_selected_key().is_none());
+    }
+
+    #[test]
+    fn test_finder() {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, comment) in [("work", "me@work"), ("home", "me@home"), ("deploy", "ci")] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, "test").unwrap();
+            std::fs::write(
+                path.with_extension("pub"),
+                format!("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X {}", comment),
+            )
+            .unwrap();
+        }
+
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+        app.filter = "home".to_string();
+        app.apply_filter();
+
+        // The finder searches past the filter and clears it to jump
+        app.open_finder();
+        assert_eq!(app.state, AppState::FindKey);
+        let keys = app.keys.clone();
+        for c in "dpl".chars() {
+            app.finder.as_mut().unwrap().push(c, &keys);
+        }
+        app.jump_to_found_key();
+        assert_eq!(app.state, AppState::KeyList);
+        assert!(app.finder.is_none());
+        assert!(app.filter.is_empty());
+        assert_eq!(app.get_selected_key().unwrap().name, "deploy");
+
+        // Cancelling leaves the selection alone
+        app.open_finder();
+        app.finder.as_mut().unwrap().push('w', &keys);
+        app.close_finder();
+        assert_eq!(app.get_selected_key().unwrap().name, "deploy");
+    }
+
+    #[test]
+    fn test_paging() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..12 {
+            std::fs::write(temp_dir.path().join(format!("key{:02}", i)), "test").unwrap();
+        }
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+        app.list_height = 5;
+
+        app.next_page();
+        assert_eq!(app.selected_index, 5);
+        app.next_page();
+        app.next_page();
+        // Paging stops at the ends rather than wrapping
+        assert_eq!(app.selected_index, 11);
+        app.previous_page();
+        assert_eq!(app.selected_index, 6);
+        app.first_key();
+        app.previous_page();
+        assert_eq!(app.selected_index, 0);
+        app.last_key();
+        assert_eq!(app.get_selected_key().unwrap().name, "key11");
+
+        // The vim motions
+        app.go_to_key(3);
+        assert_eq!(app.selected_index, 2);
+        app.go_to_key(99);
+        assert_eq!(app.selected_index, 11);
+        app.move_selection(-20);
+        assert_eq!(app.selected_index, 0);
+        app.half_page(true);
+        assert_eq!(app.selected_index, 2);
+        app.half_page(false);
+        assert_eq!(app.selected_index, 0);
+    }
+
+    #[test]
+    fn test_messages() {
+        let config = create_test_config();
+        let mut app = App::new(config).unwrap();
+
+        // Successes don't stop anything and go away by themselves
+        app.state = AppState::KeyDetail;
+        app.set_message("Done", MessageType::Success, AppState::KeyList);
+        assert_eq!(app.state, AppState::KeyList);
+        assert!(app.message.is_none());
+        app.expire_toast();
+        assert!(app.toast.is_some());
+        if let Some((_, _, raised)) = app.toast.as_mut() {
+            *raised -= TOAST_DURATION;
+        }
+        app.expire_toast();
+        assert!(app.toast.is_none());
+
+        // Errors wait for Enter
+        app.set_message("Failed", MessageType::Error, AppState::KeyDetail);
+        assert_eq!(app.state, AppState::MessageDialog);
+        app.clear_message();
+        assert_eq!(app.state, AppState::KeyDetail);
+    }
+
+    #[test]
+    fn test_quit_after_job() {
+        let config = create_test_config();
+        let mut app = App::new(config).unwrap();
+        let wait = |app: &mut App| {
+            while app.job.is_some() {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                app.poll_job().unwrap();
+            }
+        };
+
+        // A failed job shows its error instead of quitting past it
+        app.start_job("Import", AppState::KeyList, |_| {
+            Err(crate::error::SkmError::ImportExport("broken".to_string()))
+        });
+        app.quit_after_job = true;
+        wait(&mut app);
+        assert_eq!(app.state, AppState::MessageDialog);
+        assert!(!app.quit_after_job);
+
+        app.clear_message();
+        app.start_job("Import", AppState::KeyList, |_| {
+            Ok(Outcome::ImportListed(Vec::new()))
+        });
+        app.quit_after_job = true;
+        wait(&mut app);
+        assert!(app.should_quit());
+    }
+
+    #[test]
+    fn test_watcher_refreshes_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("id_b"), "test").unwrap();
+        let config = Config::from_ssh_dir(dir).unwrap();
+        let mut app = App::new(config).unwrap();
+        assert!(app.watcher.is_some());
+
+        let wait_for = |app: &mut App, count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while app.keys.len() != count && Instant::now() < deadline {
+                app.poll_watcher();
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            assert_eq!(app.keys.len(), count);
+        };
+
+        // A key sorting before the highlighted one doesn't move the highlight
+        app.show_key_detail();
+        std::fs::write(dir.join("id_a"), "test").unwrap();
+        wait_for(&mut app, 2);
+        assert_eq!(app.get_selected_key().unwrap().name, "id_b");
+        assert_eq!(app.state, AppState::KeyDetail);
+
+        // Nothing changes under the wizard
+        app.start_wizard();
+        app.state = AppState::CreateWizard;
+        std::fs::write(dir.join("id_c"), "test").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !app.keys_stale && Instant::now() < deadline {
+            app.poll_watcher();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(app.keys_stale);
+        assert_eq!(app.keys.len(), 2);
+        app.end_wizard();
+        app.state = AppState::KeyDetail;
+        app.poll_watcher();
+        assert_eq!(app.keys.len(), 3);
+
+        // The key on show going away closes the detail
+        std::fs::remove_file(dir.join("id_b")).unwrap();
+        wait_for(&mut app, 2);
+        assert_eq!(app.state, AppState::KeyList);
+        assert!(app.keys.iter().all(|key| key.name != "id_b"));
+    }
+
+    #[test]
+    fn test_hosts_tab() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        for name in ["id_ed25519", "work"] {
+            std::fs::write(dir.join(name), "test").unwrap();
+        }
+        std::fs::write(
+            dir.join("config"),
+            format!(
+                "Host web\n    IdentityFile {}\n\nHost *.corp\n    IdentityFile {}\n\nHost plain\n",
+                dir.join("work").display(),
+                dir.join("missing").display()
+            ),
+        )
+        .unwrap();
+
+        let config = Config::from_ssh_dir(dir).unwrap();
+        let mut app = App::new(config).unwrap();
+
+        // Opening the tab lands on a host using the highlighted key
+        app.select_key_by_name("work");
+        app.show_hosts();
+        assert_eq!(app.state, AppState::HostList);
+        assert_eq!(app.get_selected_host().unwrap().patterns, ["web"]);
+
+        // Without an IdentityFile, ssh's default identities apply
+        app.next_host();
+        app.next_host();
+        let plain = app.get_selected_host().unwrap();
+        assert_eq!(app.host_key(plain).unwrap().name, "id_ed25519");
+        app.show_host_key(false);
+        assert_eq!(app.state, AppState::KeyList);
+        assert_eq!(app.get_selected_key().unwrap().name, "id_ed25519");
+
+        // A host whose key isn't in the ssh directory says so and stays put
+        app.show_hosts();
+        app.previous_host();
+        app.show_host_key(false);
+        assert_eq!(app.state, AppState::HostList);
+        assert!(app.toast.is_some());
+        assert_eq!(app.get_selected_key().unwrap().name, "id_ed25519");
+    }
+
+    #[test]
+    fn test_export_selection() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(temp_dir.path().join(name), "test").unwrap();
+        }
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+
+        app.start_export();
+        assert_eq!(app.dialog_state, DialogState::SelectKeys);
+        assert_eq!(app.checked_export_keys(), vec!["a", "b", "c"]);
+        app.export_index = 1;
+        app.toggle_export_key();
+        assert_eq!(app.checked_export_keys(), vec!["a", "c"]);
+        app.toggle_all_export_keys();
+        assert_eq!(app.checked_export_keys().len(), 3);
+        app.toggle_all_export_keys();
+        assert!(app.checked_export_keys().is_empty());
+
+        // Marked keys start out as the only checked ones
+        app.marked.insert("b".to_string());
+        app.start_export();
+        assert_eq!(app.checked_export_keys(), vec!["b"]);
+    }
+
+    #[test]
+    fn test_import_strategy() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("id_a"), "test").unwrap();
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+
+        app.import_entries = ["id_a", "id_b"]
+            .into_iter()
+            .map(|name| EntrySummary {
+                name: name.to_string(),
+                key_type: "ED25519".to_string(),
+                comment: None,
+                fingerprint: None,
+                has_private_key: true,
+                encrypted: false,
+            })
+            .collect();
+        app.import_checked = vec![true, true];
+        assert_eq!(app.import_conflicts(), vec!["id_a"]);
+        app.import_checked = vec![false, true];
+        assert!(app.import_conflicts().is_empty());
+
+        assert_eq!(app.import_strategy, MergeStrategy::SkipExisting);
+        app.cycle_import_strategy(false);
+        assert_eq!(app.import_strategy, MergeStrategy::Rename);
+        app.cycle_import_strategy(true);
+        app.cycle_import_strategy(true);
+        assert_eq!(app.import_strategy, MergeStrategy::Overwrite);
+    }
+
+    #[test]
+    fn test_marks() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(temp_dir.path().join(name), "test").unwrap();
+        }
+
+        let config = Config::from_ssh_dir(temp_dir.path()).unwrap();
+        let mut app = App::new(config).unwrap();
+        app.select_key_by_name("a");
+        let highlighted = |app: &App| app.get_selected_key().unwrap().name.clone();
+
+        // Nothing marked: the highlighted key
+        assert_eq!(app.target_keys().len(), 1);
+        app.toggle_mark();
+        app.toggle_mark();
+        assert_eq!(app.marked.len(), 2);
+        assert!(!app.marked.contains(&highlighted(&app)));
+        assert_eq!(app.target_keys().len(), 2);
+
+        app.toggle_mark_shown();
+        assert_eq!(app.marked.len(), 3);
+        app.toggle_mark_shown();
+        assert!(app.marked.is_empty());
+
+        // Marks of keys gone from disk are dropped
+        app.select_key_by_name("a");
+        app.toggle_mark();
+        std::fs::remove_file(temp_dir.path().join("a")).unwrap();
+        app.refresh_keys().unwrap();
+        assert!(app.marked.is_empty());
+    }
+
     #[test]
     fn test_wizard_flow() {
         let config = create_test_config();
@@ -290,8 +2309,20 @@ mod tests {
         // Enter filename
         app.wizard_input = "test_key".to_string();
         assert!(app.wizard_next());
+        assert_eq!(app.get_wizard_step(), Some(WizardStep::EnterComment));
+
+        app.wizard_input = "me@work".to_string();
+        assert!(app.wizard_next());
         assert_eq!(app.get_wizard_step(), Some(WizardStep::EnterPassphrase));
 
+        // Going back brings what was entered to edit
+        app.wizard_previous();
+        assert_eq!(app.get_wizard_step(), Some(WizardStep::EnterComment));
+        assert_eq!(app.wizard_input, "me@work");
+        app.wizard_previous();
+        assert_eq!(app.get_wizard_step(), Some(WizardStep::EnterFilename));
+        assert_eq!(app.wizard_input, "test_key");
+
         app.end_wizard();
         assert!(app.wizard.is_none());
     }
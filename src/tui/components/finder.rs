@@ -0,0 +1,220 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+
+use crate::ssh::SshKey;
+use crate::ssh::config::{self, SshConfig};
+
+/// A key the query matches, with the characters that matched for
+/// highlighting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinderMatch {
+    pub key: usize, // index into the app's keys
+    pub score: i64,
+    pub name_indices: Vec<usize>, // char positions in the name
+    pub comment_indices: Vec<usize>,
+}
+
+/// The `f` finder: keys ranked by how well their name or comment matches
+/// what has been typed, best first. Lowercase queries ignore case, as in skim.
+/// `@alias` finds the keys ssh would offer that host instead.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFinder {
+    pub query: String,
+    pub selected: usize,
+    pub matches: Vec<FinderMatch>,
+    ssh_config: Option<SshConfig>, // resolves `@alias`
+}
+
+impl KeyFinder {
+    pub fn new(keys: &[SshKey], ssh_config: Option<SshConfig>) -> Self {
+        let mut finder = Self {
+            ssh_config,
+            ..Self::default()
+        };
+        finder.update(keys);
+        finder
+    }
+
+    /// Rank `keys` against the query again; the best match is selected
+    pub fn update(&mut self, keys: &[SshKey]) {
+        self.selected = 0;
+        if let Some(alias) = config::search_alias(&self.query) {
+            let identities = self
+                .ssh_config
+                .as_ref()
+                .map(|config| config.identities_for(alias))
+                .unwrap_or_default();
+            self.matches = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| key.is_at_any(&identities))
+                .map(|(index, _)| FinderMatch {
+                    key: index,
+                    score: 0,
+                    name_indices: Vec::new(),
+                    comment_indices: Vec::new(),
+                })
+                .collect();
+            return;
+        }
+
+        let matcher = SkimMatcherV2::default();
+        let query = self.query.trim();
+        let mut matches: Vec<FinderMatch> = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(index, key)| {
+                let name = matcher.fuzzy_indices(&key.name, query);
+                let comment = key
+                    .comment
+                    .as_deref()
+                    .and_then(|comment| matcher.fuzzy_indices(comment, query));
+                // The better of the two counts, and a name match wins a tie
+                let (score, name_indices, comment_indices) = match (name, comment) {
+                    (Some(name), Some(comment)) if comment.0 > name.0 => {
+                        (comment.0, Vec::new(), comment.1)
+                    }
+                    (Some(name), _) => (name.0, name.1, Vec::new()),
+                    (None, Some(comment)) => (comment.0, Vec::new(), comment.1),
+                    (None, None) => return None,
+                };
+                Some(FinderMatch {
+                    key: index,
+                    score,
+                    name_indices,
+                    comment_indices,
+                })
+            })
+            .collect();
+
+        // Stable, so equal scores keep the list's order
+        matches.sort_by_key(|found| std::cmp::Reverse(found.score));
+        self.matches = matches;
+    }
+
+    pub fn push(&mut self, c: char, keys: &[SshKey]) {
+        self.query.push(c);
+        self.update(keys);
+    }
+
+    pub fn pop(&mut self, keys: &[SshKey]) {
+        self.query.pop();
+        self.update(keys);
+    }
+
+    pub fn next(&mut self) {
+        let count = self.matches.len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        let count = self.matches.len();
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+
+    /// Index into the app's keys of the highlighted match
+    pub fn selected_key(&self) -> Option<usize> {
+        self.matches.get(self.selected).map(|found| found.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::KeyScanner;
+    use tempfile::TempDir;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMizQvi9/MOhNCQeoGoHidgEDYeiwhUUqXUMwhoryiKI";
+
+    fn keys(named: &[(&str, &str)]) -> (TempDir, Vec<SshKey>) {
+        let dir = TempDir::new().unwrap();
+        for (name, comment) in named {
+            std::fs::write(dir.path().join(name), "test").unwrap();
+            std::fs::write(
+                dir.path().join(format!("{}.pub", name)),
+                format!("{} {}", ED25519, comment),
+            )
+            .unwrap();
+        }
+        let keys = KeyScanner::new(dir.path()).scan().unwrap();
+        (dir, keys)
+    }
+
+    fn names(finder: &KeyFinder, keys: &[SshKey]) -> Vec<String> {
+        finder
+            .matches
+            .iter()
+            .map(|found| keys[found.key].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_finder_ranks_matches() {
+        let (_dir, keys) = keys(&[
+            ("deploy_prod", "ci@build"),
+            ("github", "me@laptop"),
+            ("id_ed25519", "work github account"),
+        ]);
+        let mut finder = KeyFinder::new(&keys, None);
+        assert_eq!(finder.matches.len(), 3);
+
+        for c in "gh".chars() {
+            finder.push(c, &keys);
+        }
+        // The name match comes before the comment match
+        assert_eq!(names(&finder, &keys), ["github", "id_ed25519"]);
+        assert_eq!(finder.matches[0].name_indices, [0, 3]);
+        assert!(finder.matches[1].comment_indices.contains(&5));
+
+        finder.pop(&keys);
+        finder.pop(&keys);
+        for c in "dpr".chars() {
+            finder.push(c, &keys);
+        }
+        assert_eq!(names(&finder, &keys), ["deploy_prod"]);
+
+        finder.push('x', &keys);
+        assert!(finder.matches.is_empty());
+        assert_eq!(finder.selected_key(), None);
+    }
+
+    #[test]
+    fn test_finder_host_alias() {
+        let (dir, keys) = keys(&[("github", "me@laptop"), ("work", "me@work")]);
+        let path = dir.path().join("config");
+        std::fs::write(
+            &path,
+            format!(
+                "Host gh\n  IdentityFile {}\n",
+                dir.path().join("github.pub").display()
+            ),
+        )
+        .unwrap();
+        let config = SshConfig::load(&path).unwrap();
+
+        let mut finder = KeyFinder::new(&keys, Some(config));
+        for c in "@gh".chars() {
+            finder.push(c, &keys);
+        }
+        assert_eq!(names(&finder, &keys), ["github"]);
+        finder.pop(&keys);
+        finder.pop(&keys);
+        // A bare `@` is searched for like any other text
+        assert_eq!(finder.matches.len(), 2);
+    }
+
+    #[test]
+    fn test_finder_selection_wraps() {
+        let (_dir, keys) = keys(&[("a", "x"), ("b", "y")]);
+        let mut finder = KeyFinder::new(&keys, None);
+        finder.previous();
+        assert_eq!(finder.selected, 1);
+        finder.next();
+        assert_eq!(finder.selected_key(), Some(finder.matches[0].key));
+    }
+}
@@ -9,7 +9,7 @@ pub struct InputField {
     pub value: String,
     pub is_password: bool,
     pub is_active: bool,
-    pub cursor_position: usize,
+    pub cursor_position: usize, // byte offset into `value`, always on a char boundary
 }
 
 impl InputField {
@@ -34,9 +34,14 @@ impl InputField {
         self
     }
 
+    pub fn with_active(mut self, active: bool) -> Self {
+        self.is_active = active;
+        self
+    }
+
     pub fn insert_char(&mut self, c: char) {
         self.value.insert(self.cursor_position, c);
-        self.cursor_position += 1;
+        self.cursor_position += c.len_utf8();
     }
 
     pub fn delete_char(&mut self) {
@@ -47,20 +52,20 @@ impl InputField {
 
     pub fn backspace(&mut self) {
         if self.cursor_position > 0 {
-            self.cursor_position -= 1;
+            self.move_cursor_left();
             self.value.remove(self.cursor_position);
         }
     }
 
     pub fn move_cursor_left(&mut self) {
-        if self.cursor_position > 0 {
-            self.cursor_position -= 1;
+        if let Some(c) = self.value[..self.cursor_position].chars().next_back() {
+            self.cursor_position -= c.len_utf8();
         }
     }
 
     pub fn move_cursor_right(&mut self) {
-        if self.cursor_position < self.value.len() {
-            self.cursor_position += 1;
+        if let Some(c) = self.value[self.cursor_position..].chars().next() {
+            self.cursor_position += c.len_utf8();
         }
     }
 
@@ -79,12 +84,18 @@ impl InputField {
 
     pub fn display_value(&self) -> String {
         if self.is_password {
-            "•".repeat(self.value.len())
+            "•".repeat(self.value.chars().count())
         } else {
             self.value.clone()
         }
     }
 
+    /// Columns from the start of the value to the cursor, for placing the
+    /// terminal cursor
+    pub fn cursor_column(&self) -> u16 {
+        self.value[..self.cursor_position].chars().count() as u16
+    }
+
     pub fn to_paragraph(&self) -> Paragraph<'_> {
         let display = self.display_value();
         let style = if self.is_active {
@@ -141,6 +152,25 @@ mod tests {
         assert_eq!(field.cursor_position, 5);
     }
 
+    #[test]
+    fn test_input_field_multibyte() {
+        let mut field = InputField::new("Test");
+        for c in "pässwörd".chars() {
+            field.insert_char(c);
+        }
+        field.move_cursor_left();
+        field.move_cursor_left();
+        field.backspace();
+        assert_eq!(field.value, "pässwrd");
+        assert_eq!(field.cursor_column(), 5);
+
+        field.move_cursor_start();
+        field.move_cursor_right();
+        field.move_cursor_right();
+        field.delete_char();
+        assert_eq!(field.value, "päswrd");
+    }
+
     #[test]
     fn test_password_masking() {
         let field = InputField::new("Password")
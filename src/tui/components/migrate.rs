@@ -0,0 +1,486 @@
+//! The "Migrate from another computer" wizard: restore a backup made on the
+//! old machine, authorize the keys published on GitHub and load the restored
+//! keys into ssh-agent, in one go
+
+use std::path::{Path, PathBuf};
+
+use crate::config::Settings;
+use crate::crypto::backup::{BackupManager, ImportOptions, ImportReport, MergeStrategy};
+use crate::crypto::provenance::ProvenanceStore;
+use crate::error::Result;
+use crate::forge::GithubClient;
+use crate::ssh::agent::LifetimeStore;
+use crate::ssh::config::expand_path;
+use crate::ssh::{AgentClient, KeyScanner, authorized_keys};
+use crate::tui::components::InputField;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrateStep {
+    EnterBackup,
+    EnterPassphrase, // skipped without a backup
+    EnterGithubUser,
+    ChooseAgent, // skipped without a backup
+    Confirm,
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrateWizard {
+    pub step: MigrateStep,
+    pub backup: InputField,
+    pub passphrase: InputField,
+    pub github_user: InputField,
+    pub add_to_agent: bool,
+    pub error_message: Option<String>,
+}
+
+impl Default for MigrateWizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MigrateWizard {
+    pub fn new() -> Self {
+        Self {
+            step: MigrateStep::EnterBackup,
+            backup: InputField::new("Backup file").with_active(true),
+            passphrase: InputField::new("Backup passphrase").with_password(),
+            github_user: InputField::new("GitHub user"),
+            add_to_agent: true,
+            error_message: None,
+        }
+    }
+
+    fn has_backup(&self) -> bool {
+        !self.backup.value.trim().is_empty()
+    }
+
+    fn github_user(&self) -> Option<&str> {
+        Some(self.github_user.value.trim()).filter(|user| !user.is_empty())
+    }
+
+    /// The text field of the current step, if it has one
+    pub fn active_field(&mut self) -> Option<&mut InputField> {
+        match self.step {
+            MigrateStep::EnterBackup => Some(&mut self.backup),
+            MigrateStep::EnterPassphrase => Some(&mut self.passphrase),
+            MigrateStep::EnterGithubUser => Some(&mut self.github_user),
+            MigrateStep::ChooseAgent | MigrateStep::Confirm => None,
+        }
+    }
+
+    /// Enter on the current step: checks what was typed and moves on; false,
+    /// with `error_message` set, when it doesn't check out
+    pub fn next_step(&mut self) -> bool {
+        if let Err(e) = self.check_step() {
+            self.error_message = Some(e);
+            return false;
+        }
+        self.error_message = None;
+        self.step = match self.step {
+            MigrateStep::EnterBackup if self.has_backup() => MigrateStep::EnterPassphrase,
+            MigrateStep::EnterBackup => MigrateStep::EnterGithubUser,
+            MigrateStep::EnterPassphrase => MigrateStep::EnterGithubUser,
+            MigrateStep::EnterGithubUser if self.has_backup() => MigrateStep::ChooseAgent,
+            MigrateStep::EnterGithubUser => MigrateStep::Confirm,
+            MigrateStep::ChooseAgent | MigrateStep::Confirm => MigrateStep::Confirm,
+        };
+        true
+    }
+
+    fn check_step(&self) -> std::result::Result<(), String> {
+        match self.step {
+            MigrateStep::EnterBackup if self.has_backup() => {
+                let path = expand_path(self.backup.value.trim());
+                if !path.is_file() {
+                    return Err(format!("No backup at {}", path.display()));
+                }
+            }
+            MigrateStep::EnterGithubUser => match self.github_user() {
+                Some(user) if user.contains(|c: char| c == '/' || c.is_whitespace()) => {
+                    return Err(format!("'{}' is not a GitHub user name", user));
+                }
+                None if !self.has_backup() => {
+                    return Err(
+                        "Nothing to migrate: give a backup file, a GitHub user or both".to_string(),
+                    );
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn previous_step(&mut self) {
+        self.error_message = None;
+        self.step = match self.step {
+            MigrateStep::EnterBackup | MigrateStep::EnterPassphrase => MigrateStep::EnterBackup,
+            MigrateStep::EnterGithubUser if self.has_backup() => MigrateStep::EnterPassphrase,
+            MigrateStep::EnterGithubUser => MigrateStep::EnterBackup,
+            MigrateStep::ChooseAgent => MigrateStep::EnterGithubUser,
+            MigrateStep::Confirm if self.has_backup() => MigrateStep::ChooseAgent,
+            MigrateStep::Confirm => MigrateStep::EnterGithubUser,
+        };
+    }
+
+    /// The current step's number and how many steps there are, which
+    /// depends on whether a backup is restored
+    pub fn step_number(&self) -> (usize, usize) {
+        if self.has_backup() {
+            let index = match self.step {
+                MigrateStep::EnterBackup => 1,
+                MigrateStep::EnterPassphrase => 2,
+                MigrateStep::EnterGithubUser => 3,
+                MigrateStep::ChooseAgent => 4,
+                MigrateStep::Confirm => 5,
+            };
+            (index, 5)
+        } else {
+            let index = match self.step {
+                MigrateStep::EnterBackup | MigrateStep::EnterPassphrase => 1,
+                MigrateStep::EnterGithubUser | MigrateStep::ChooseAgent => 2,
+                MigrateStep::Confirm => 3,
+            };
+            (index, 3)
+        }
+    }
+
+    pub fn get_step_description(&self) -> &'static str {
+        match self.step {
+            MigrateStep::EnterBackup => {
+                "Backup made on the old computer (skm export), or leave empty to skip"
+            }
+            MigrateStep::EnterPassphrase => "Passphrase the backup was encrypted with",
+            MigrateStep::EnterGithubUser => {
+                "GitHub user whose public keys may log in here, or leave empty to skip"
+            }
+            MigrateStep::ChooseAgent => "Load the restored keys into ssh-agent?",
+            MigrateStep::Confirm => "Confirm migration",
+        }
+    }
+
+    pub fn get_summary(&self) -> String {
+        let mut lines = Vec::new();
+        if self.has_backup() {
+            lines.push(format!(
+                "Restore keys from: {} (existing keys are kept)",
+                self.backup.value.trim()
+            ));
+            lines.push(format!(
+                "Load them into ssh-agent: {}",
+                if self.add_to_agent { "Yes" } else { "No" }
+            ));
+        }
+        if let Some(user) = self.github_user() {
+            lines.push(format!("Add github.com/{}'s keys to authorized_keys", user));
+        }
+        lines.join("\n")
+    }
+
+    pub fn plan(&self) -> MigratePlan {
+        MigratePlan {
+            backup: self.has_backup().then(|| {
+                (
+                    expand_path(self.backup.value.trim()),
+                    self.passphrase.value.clone(),
+                )
+            }),
+            github_user: self.github_user().map(str::to_string),
+            add_to_agent: self.has_backup() && self.add_to_agent,
+        }
+    }
+}
+
+/// What the wizard was asked to do
+#[derive(Debug, Clone)]
+pub struct MigratePlan {
+    pub backup: Option<(PathBuf, String)>, // file and passphrase
+    pub github_user: Option<String>,
+    pub add_to_agent: bool,
+}
+
+/// What a migration did. A step that fails doesn't stop the ones after it;
+/// its error is kept in `failures`.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub import: Option<ImportReport>,
+    pub authorized: Option<(String, usize)>, // GitHub user and keys newly added
+    pub agent_added: Vec<String>,
+    pub agent_skipped: Vec<String>, // encrypted, so they need their passphrase
+    pub failures: Vec<String>,
+}
+
+impl MigratePlan {
+    /// Run the steps in order, calling `progress` before each
+    pub fn run(
+        self,
+        ssh_dir: &Path,
+        export_dir: &Path,
+        settings: &Settings,
+        progress: impl Fn(usize, usize, &str),
+    ) -> MigrationReport {
+        let total = self.backup.is_some() as usize
+            + self.github_user.is_some() as usize
+            + self.add_to_agent as usize;
+        let mut step = 0;
+        let mut next = |detail: &str| {
+            step += 1;
+            progress(step, total, detail);
+        };
+        let mut report = MigrationReport::default();
+
+        if let Some((path, passphrase)) = &self.backup {
+            next("Restoring the backup");
+            let opts = ImportOptions {
+                merge_strategy: MergeStrategy::SkipExisting,
+                dry_run: false,
+                selected_keys: None,
+            };
+            match BackupManager::new(ssh_dir).import(path, passphrase, opts) {
+                Ok(imported) => {
+                    if let Err(e) =
+                        ProvenanceStore::new(export_dir).record_import(ssh_dir, path, &imported)
+                    {
+                        tracing::warn!("Failed to record where the keys came from: {}", e);
+                    }
+                    report.import = Some(imported);
+                }
+                Err(e) => report.failures.push(format!("Restoring the backup: {}", e)),
+            }
+        }
+
+        if let Some(user) = &self.github_user {
+            next("Fetching GitHub keys");
+            let added = GithubClient::from_settings(settings)
+                .user_keys(user)
+                .and_then(|keys| {
+                    authorized_keys::append_keys(&ssh_dir.join("authorized_keys"), &keys)
+                });
+            match added {
+                Ok(added) => report.authorized = Some((user.clone(), added.len())),
+                Err(e) => report
+                    .failures
+                    .push(format!("Adding github.com/{}'s keys: {}", user, e)),
+            }
+        }
+
+        if self.add_to_agent {
+            next("Loading keys into ssh-agent");
+            let names = report
+                .import
+                .as_ref()
+                .map(|imported| imported.imported.clone())
+                .unwrap_or_default();
+            if let Err(e) = add_to_agent(ssh_dir, export_dir, &names, &mut report) {
+                report
+                    .failures
+                    .push(format!("Loading into ssh-agent: {}", e));
+            }
+        }
+
+        report
+    }
+}
+
+/// Load the unencrypted keys among `names` into the agent; encrypted ones are
+/// left for `+`, where their passphrase can be typed
+fn add_to_agent(
+    ssh_dir: &Path,
+    export_dir: &Path,
+    names: &[String],
+    report: &mut MigrationReport,
+) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let keys = KeyScanner::new(ssh_dir).scan()?;
+    let mut agent = AgentClient::connect_env()?;
+    let store = LifetimeStore::new(export_dir);
+
+    for key in keys.iter().filter(|key| names.contains(&key.name)) {
+        if !key.has_private() {
+            continue;
+        }
+        if key.is_encrypted() {
+            report.agent_skipped.push(key.name.clone());
+            continue;
+        }
+        let mut private_key = key.load_private_key(None)?;
+        if private_key.comment().is_empty() {
+            private_key.set_comment(key.path.display().to_string());
+        }
+        agent.add_identity(&private_key, None, false)?;
+        if let Err(e) = store.record(private_key.public_key().key_data(), None) {
+            tracing::warn!("Failed to record the agent lifetime: {}", e);
+        }
+        report.agent_added.push(key.name.clone());
+    }
+    Ok(())
+}
+
+impl MigrationReport {
+    /// One line per step, for the message shown when it's done
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(ref imported) = self.import {
+            lines.push(format!(
+                "Restored {} keys ({} already here, kept)",
+                imported.imported.len(),
+                imported.skipped.len()
+            ));
+            for (name, error) in &imported.errors {
+                lines.push(format!("  {}: {}", name, error));
+            }
+        }
+        if let Some((ref user, added)) = self.authorized {
+            lines.push(format!(
+                "Added {} of github.com/{}'s keys to authorized_keys",
+                added, user
+            ));
+        }
+        if !self.agent_added.is_empty() {
+            lines.push(format!(
+                "Loaded into ssh-agent: {}",
+                self.agent_added.join(", ")
+            ));
+        }
+        if !self.agent_skipped.is_empty() {
+            lines.push(format!(
+                "Encrypted, add them with '+': {}",
+                self.agent_skipped.join(", ")
+            ));
+        }
+        lines.extend(self.failures.iter().cloned());
+        lines.join("\n")
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+            || self
+                .import
+                .as_ref()
+                .is_some_and(|imported| !imported.errors.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::backup::ExportOptions;
+    use crate::ssh::generate::{KeyGenOptions, KeyGenerator};
+    use tempfile::TempDir;
+
+    fn type_into(field: &mut InputField, text: &str) {
+        for c in text.chars() {
+            field.insert_char(c);
+        }
+    }
+
+    #[test]
+    fn test_steps_without_backup() {
+        let mut wizard = MigrateWizard::new();
+        assert_eq!(wizard.step_number(), (1, 3));
+
+        // Empty backup skips its passphrase
+        assert!(wizard.next_step());
+        assert_eq!(wizard.step, MigrateStep::EnterGithubUser);
+        assert_eq!(wizard.step_number(), (2, 3));
+
+        // Without a backup there has to be a user
+        assert!(!wizard.next_step());
+        assert!(wizard.error_message.is_some());
+
+        type_into(wizard.active_field().unwrap(), "some/one");
+        assert!(!wizard.next_step());
+        wizard.github_user.clear();
+        type_into(wizard.active_field().unwrap(), "octocat");
+        assert!(wizard.next_step());
+        assert!(wizard.error_message.is_none());
+        assert_eq!(wizard.step, MigrateStep::Confirm);
+
+        let plan = wizard.plan();
+        assert!(plan.backup.is_none());
+        assert_eq!(plan.github_user.as_deref(), Some("octocat"));
+        assert!(!plan.add_to_agent);
+
+        wizard.previous_step();
+        assert_eq!(wizard.step, MigrateStep::EnterGithubUser);
+        wizard.previous_step();
+        assert_eq!(wizard.step, MigrateStep::EnterBackup);
+    }
+
+    #[test]
+    fn test_steps_with_backup() {
+        let dir = TempDir::new().unwrap();
+        let mut wizard = MigrateWizard::new();
+
+        type_into(wizard.active_field().unwrap(), "/nonexistent/backup.skm");
+        assert!(!wizard.next_step());
+        assert_eq!(wizard.step, MigrateStep::EnterBackup);
+
+        let backup = dir.path().join("backup.skm");
+        std::fs::write(&backup, "").unwrap();
+        wizard.backup.clear();
+        type_into(
+            wizard.active_field().unwrap(),
+            &backup.display().to_string(),
+        );
+        assert!(wizard.next_step());
+        assert_eq!(wizard.step, MigrateStep::EnterPassphrase);
+        assert_eq!(wizard.step_number(), (2, 5));
+
+        type_into(wizard.active_field().unwrap(), "secret");
+        assert!(wizard.next_step());
+        // The user is optional once there is a backup
+        assert!(wizard.next_step());
+        assert_eq!(wizard.step, MigrateStep::ChooseAgent);
+        assert!(wizard.active_field().is_none());
+        wizard.add_to_agent = false;
+        assert!(wizard.next_step());
+        assert_eq!(wizard.step_number(), (5, 5));
+        assert!(wizard.get_summary().contains("ssh-agent: No"));
+
+        let plan = wizard.plan();
+        assert_eq!(plan.backup, Some((backup, "secret".to_string())));
+        assert!(plan.github_user.is_none());
+        assert!(!plan.add_to_agent);
+    }
+
+    #[test]
+    fn test_run_restores_backup() {
+        let old = TempDir::new().unwrap();
+        let key = KeyGenerator::new(old.path())
+            .generate(KeyGenOptions {
+                filename: "id_work".to_string(),
+                ..KeyGenOptions::default()
+            })
+            .unwrap();
+        let backup = old.path().join("backup.skm");
+        BackupManager::new(old.path())
+            .export(&[key], &backup, "secret", ExportOptions::default())
+            .unwrap();
+
+        let new = TempDir::new().unwrap();
+        let data = TempDir::new().unwrap();
+        let plan = MigratePlan {
+            backup: Some((backup.clone(), "secret".to_string())),
+            github_user: None,
+            add_to_agent: false,
+        };
+        let report = plan.run(new.path(), data.path(), &Settings::default(), |_, _, _| {});
+        assert!(!report.has_failures(), "{}", report.summary());
+        assert_eq!(report.import.unwrap().imported, ["id_work"]);
+        assert!(new.path().join("id_work").exists());
+
+        // A wrong passphrase is reported rather than aborting the migration
+        let plan = MigratePlan {
+            backup: Some((backup, "wrong".to_string())),
+            github_user: None,
+            add_to_agent: false,
+        };
+        let report = plan.run(new.path(), data.path(), &Settings::default(), |_, _, _| {});
+        assert!(report.has_failures());
+        assert!(report.summary().starts_with("Restoring the backup:"));
+    }
+}
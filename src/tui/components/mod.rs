@@ -1,5 +1,11 @@
+pub mod finder;
 pub mod input;
+pub mod migrate;
+pub mod palette;
 pub mod wizard;
 
+pub use finder::KeyFinder;
 pub use input::InputField;
+pub use migrate::MigrateWizard;
+pub use palette::{CommandPalette, PaletteAction};
 pub use wizard::CreateWizard;
@@ -0,0 +1,247 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+
+use crate::tui::app::AppState;
+use crate::tui::keymap::Action;
+
+/// Actions reachable from the command palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteAction {
+    ViewDetails,
+    FilterKeys,
+    FindKey,
+    ToggleMark,
+    MarkAllShown,
+    NewKey,
+    RenameKey,
+    EditComment,
+    ChangePassphrase,
+    CopyPublicKey,
+    CopyFullPublicKey,
+    CopyAuthorizedLine,
+    ToggleAgentPanel,
+    AddToAgent,
+    RemoveFromAgent,
+    ShowHosts,
+    ShowDashboard,
+    ExportKeys,
+    ImportKeys,
+    Migrate,
+    InspectFile,
+    DeleteKey,
+    Undo,
+    Redo,
+    RestoreDeleted,
+    RefreshKeys,
+    FixPermissions,
+    Lock,
+    ToggleHelp,
+    ToggleDebugLog,
+    Quit,
+}
+
+impl PaletteAction {
+    pub const ALL: &'static [PaletteAction] = &[
+        PaletteAction::ViewDetails,
+        PaletteAction::FilterKeys,
+        PaletteAction::FindKey,
+        PaletteAction::ToggleMark,
+        PaletteAction::MarkAllShown,
+        PaletteAction::NewKey,
+        PaletteAction::RenameKey,
+        PaletteAction::EditComment,
+        PaletteAction::ChangePassphrase,
+        PaletteAction::CopyPublicKey,
+        PaletteAction::CopyFullPublicKey,
+        PaletteAction::CopyAuthorizedLine,
+        PaletteAction::ToggleAgentPanel,
+        PaletteAction::AddToAgent,
+        PaletteAction::RemoveFromAgent,
+        PaletteAction::ShowHosts,
+        PaletteAction::ShowDashboard,
+        PaletteAction::ExportKeys,
+        PaletteAction::ImportKeys,
+        PaletteAction::Migrate,
+        PaletteAction::InspectFile,
+        PaletteAction::DeleteKey,
+        PaletteAction::Undo,
+        PaletteAction::Redo,
+        PaletteAction::RestoreDeleted,
+        PaletteAction::RefreshKeys,
+        PaletteAction::FixPermissions,
+        PaletteAction::Lock,
+        PaletteAction::ToggleHelp,
+        PaletteAction::ToggleDebugLog,
+        PaletteAction::Quit,
+    ];
+
+    pub const fn label(&self) -> &'static str {
+        match self {
+            PaletteAction::ViewDetails => "View key details",
+            PaletteAction::FilterKeys => "Filter key list",
+            PaletteAction::FindKey => "Find key (fuzzy)",
+            PaletteAction::ToggleMark => "Mark/unmark key for export or delete",
+            PaletteAction::MarkAllShown => "Mark/unmark all shown keys",
+            PaletteAction::NewKey => "New key",
+            PaletteAction::RenameKey => "Rename key",
+            PaletteAction::EditComment => "Edit comment",
+            PaletteAction::ChangePassphrase => "Change passphrase",
+            PaletteAction::CopyPublicKey => "Copy public key",
+            PaletteAction::CopyFullPublicKey => "Copy full public key (with comment)",
+            PaletteAction::CopyAuthorizedLine => "Copy authorized_keys line",
+            PaletteAction::ToggleAgentPanel => "Show/hide ssh-agent keys",
+            PaletteAction::AddToAgent => "Add key to ssh-agent",
+            PaletteAction::RemoveFromAgent => "Remove key from ssh-agent",
+            PaletteAction::ShowHosts => "Show ssh config hosts",
+            PaletteAction::ShowDashboard => "Show dashboard",
+            PaletteAction::ExportKeys => "Export keys",
+            PaletteAction::ImportKeys => "Import keys",
+            PaletteAction::Migrate => "Migrate from another computer",
+            PaletteAction::InspectFile => "Inspect a key file",
+            PaletteAction::DeleteKey => "Delete key",
+            PaletteAction::Undo => "Undo last edit",
+            PaletteAction::Redo => "Redo last edit",
+            PaletteAction::RestoreDeleted => "Undo last delete (restore from trash)",
+            PaletteAction::RefreshKeys => "Refresh key list",
+            PaletteAction::FixPermissions => "Fix file permissions",
+            PaletteAction::Lock => "Lock session",
+            PaletteAction::ToggleHelp => "Toggle help",
+            PaletteAction::ToggleDebugLog => "Toggle debug log",
+            PaletteAction::Quit => "Quit",
+        }
+    }
+
+    /// Key action that does the same, whose key is shown next to the label
+    pub const fn binding(&self) -> Option<Action> {
+        match self {
+            PaletteAction::ViewDetails => Some(Action::Open),
+            PaletteAction::FilterKeys => Some(Action::Filter),
+            PaletteAction::FindKey => Some(Action::Find),
+            PaletteAction::ToggleMark => Some(Action::Mark),
+            PaletteAction::NewKey => Some(Action::New),
+            PaletteAction::RenameKey => Some(Action::Rename),
+            PaletteAction::CopyPublicKey => Some(Action::CopyKey),
+            PaletteAction::CopyFullPublicKey => Some(Action::CopyFull),
+            PaletteAction::CopyAuthorizedLine => Some(Action::CopyAuthorizedLine),
+            PaletteAction::ToggleAgentPanel => Some(Action::AgentPanel),
+            PaletteAction::AddToAgent => Some(Action::AgentAdd),
+            PaletteAction::RemoveFromAgent => Some(Action::AgentRemove),
+            PaletteAction::ShowHosts => Some(Action::SwitchTab),
+            PaletteAction::ShowDashboard => Some(Action::Dashboard),
+            PaletteAction::ExportKeys => Some(Action::Export),
+            PaletteAction::ImportKeys => Some(Action::Import),
+            PaletteAction::InspectFile => Some(Action::Inspect),
+            PaletteAction::DeleteKey => Some(Action::Delete),
+            PaletteAction::Undo => Some(Action::Undo),
+            PaletteAction::Redo => Some(Action::Redo),
+            PaletteAction::RefreshKeys => Some(Action::Refresh),
+            PaletteAction::ToggleHelp => Some(Action::Help),
+            PaletteAction::ToggleDebugLog => Some(Action::DebugLog),
+            PaletteAction::Quit => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandPalette {
+    pub query: String,
+    pub selected: usize,
+    /// State to return to when the palette closes
+    pub origin: AppState,
+}
+
+impl CommandPalette {
+    pub fn new(origin: AppState) -> Self {
+        Self {
+            query: String::new(),
+            selected: 0,
+            origin,
+        }
+    }
+
+    /// Actions matching the query, best match first; ranked like the finder
+    pub fn matches(&self) -> Vec<PaletteAction> {
+        let matcher = SkimMatcherV2::default();
+        let query = self.query.trim();
+        let mut scored: Vec<(i64, PaletteAction)> = PaletteAction::ALL
+            .iter()
+            .filter_map(|&action| {
+                matcher
+                    .fuzzy_match(action.label(), query)
+                    .map(|score| (score, action))
+            })
+            .collect();
+
+        // Stable sort keeps the declaration order for equal scores
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+
+    pub fn selected_action(&self) -> Option<PaletteAction> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn next(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_matches() {
+        let mut palette = CommandPalette::new(AppState::KeyList);
+        assert_eq!(palette.matches().len(), PaletteAction::ALL.len());
+
+        for c in "rename".chars() {
+            palette.push(c);
+        }
+        assert_eq!(palette.selected_action(), Some(PaletteAction::RenameKey));
+
+        palette.pop();
+        palette.pop();
+        palette.pop();
+        palette.pop();
+        palette.push('f');
+        // A prefix match beats characters scattered through other labels
+        assert_eq!(palette.selected_action(), Some(PaletteAction::RefreshKeys));
+
+        // Lowercase queries ignore case, and characters must come in order
+        palette.query = "expk".to_string();
+        assert_eq!(palette.selected_action(), Some(PaletteAction::ExportKeys));
+        palette.query = "xyz".to_string();
+        assert!(palette.matches().is_empty());
+    }
+
+    #[test]
+    fn test_palette_selection_wraps() {
+        let mut palette = CommandPalette::new(AppState::KeyList);
+        palette.previous();
+        assert_eq!(palette.selected, PaletteAction::ALL.len() - 1);
+        palette.next();
+        assert_eq!(palette.selected, 0);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::ssh::generate::KeyGenOptions;
+use crate::ssh::generate::{self, KeyGenOptions};
 use crate::ssh::keys::KeyType;
+use crate::tui::components::InputField;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WizardStep {
     SelectType,
+    ChooseSize, // RSA bits or ECDSA curve; skipped for Ed25519
     EnterFilename,
     EnterComment,
     EnterPassphrase,
@@ -16,8 +18,9 @@ pub struct CreateWizard {
     pub options: KeyGenOptions,
     pub temp_filename: String,
     pub temp_comment: String,
-    pub temp_passphrase: String,
-    pub confirm_passphrase: String,
+    /// The passphrase step's two fields; Tab moves between them
+    pub passphrase: InputField,
+    pub confirm_passphrase: InputField,
     pub error_message: Option<String>,
 }
 
@@ -34,16 +37,61 @@ impl CreateWizard {
             options: KeyGenOptions::default(),
             temp_filename: "id_ed25519".to_string(),
             temp_comment: format!("{}@{}", get_username(), get_hostname()),
-            temp_passphrase: String::new(),
-            confirm_passphrase: String::new(),
+            passphrase: InputField::new("Passphrase")
+                .with_password()
+                .with_active(true),
+            confirm_passphrase: InputField::new("Confirm passphrase").with_password(),
             error_message: None,
         }
     }
 
     pub fn select_type(&mut self, key_type: KeyType) {
         self.options.key_type = key_type;
+        self.options.bits = generate::default_bits(key_type);
         self.temp_filename = key_type.default_filename().to_string();
+        self.step = WizardStep::SelectType;
+        self.next_step();
+    }
+
+    /// Sizes the chosen type can be made in, numbered from 1 on screen
+    pub fn size_choices(&self) -> &'static [u32] {
+        generate::bit_choices(self.options.key_type)
+    }
+
+    /// Pick the `n`th size (from 1) and move on; false if there is no such
+    /// choice
+    pub fn choose_size(&mut self, n: usize) -> bool {
+        let Some(&bits) = n.checked_sub(1).and_then(|i| self.size_choices().get(i)) else {
+            return false;
+        };
+        self.options.bits = Some(bits);
         self.step = WizardStep::EnterFilename;
+        true
+    }
+
+    /// How a size reads for the chosen type: bits for RSA, the curve for ECDSA
+    pub fn size_label(&self, bits: u32) -> String {
+        match self.options.key_type {
+            KeyType::Ecdsa => format!("nistp{}", bits),
+            _ => format!("{} bits", bits),
+        }
+    }
+
+    /// The current step's number and how many steps there are, which
+    /// depends on whether the type has a size to pick
+    pub fn step_number(&self) -> (usize, usize) {
+        let has_size = !self.size_choices().is_empty();
+        let index = match self.step {
+            WizardStep::SelectType => 1,
+            WizardStep::ChooseSize => 2,
+            WizardStep::EnterFilename => 2,
+            WizardStep::EnterComment => 3,
+            WizardStep::EnterPassphrase => 4,
+            WizardStep::Confirm => 5,
+        };
+        let after_size =
+            has_size && !matches!(self.step, WizardStep::SelectType | WizardStep::ChooseSize);
+        (index + after_size as usize, 5 + has_size as usize)
     }
 
     pub fn set_filename(&mut self, filename: &str) -> bool {
@@ -72,6 +120,45 @@ impl CreateWizard {
         self.step = WizardStep::EnterPassphrase;
     }
 
+    /// The passphrase field being typed into
+    pub fn active_passphrase_field(&mut self) -> &mut InputField {
+        if self.confirm_passphrase.is_active {
+            &mut self.confirm_passphrase
+        } else {
+            &mut self.passphrase
+        }
+    }
+
+    pub fn switch_passphrase_field(&mut self) {
+        self.passphrase.is_active = !self.passphrase.is_active;
+        self.confirm_passphrase.is_active = !self.passphrase.is_active;
+    }
+
+    /// Enter on the passphrase step: from a filled-in passphrase field it
+    /// moves on to the confirmation, otherwise the two have to match. On a
+    /// mismatch both are cleared to be typed again.
+    pub fn submit_passphrase(&mut self) -> bool {
+        if self.passphrase.is_active && !self.passphrase.value.is_empty() {
+            self.switch_passphrase_field();
+            return false;
+        }
+
+        let passphrase = self.passphrase.value.clone();
+        let confirm = self.confirm_passphrase.value.clone();
+        if self.set_passphrase(&passphrase, &confirm) {
+            return true;
+        }
+        self.clear_passphrase_fields();
+        false
+    }
+
+    fn clear_passphrase_fields(&mut self) {
+        self.passphrase.clear();
+        self.confirm_passphrase.clear();
+        self.passphrase.is_active = true;
+        self.confirm_passphrase.is_active = false;
+    }
+
     pub fn set_passphrase(&mut self, passphrase: &str, confirm: &str) -> bool {
         if !passphrase.is_empty() && passphrase != confirm {
             self.error_message = Some("Passphrases do not match".to_string());
@@ -89,8 +176,11 @@ impl CreateWizard {
     }
 
     pub fn next_step(&mut self) {
+        let has_size = !self.size_choices().is_empty();
         self.step = match self.step {
+            WizardStep::SelectType if has_size => WizardStep::ChooseSize,
             WizardStep::SelectType => WizardStep::EnterFilename,
+            WizardStep::ChooseSize => WizardStep::EnterFilename,
             WizardStep::EnterFilename => WizardStep::EnterComment,
             WizardStep::EnterComment => WizardStep::EnterPassphrase,
             WizardStep::EnterPassphrase => WizardStep::Confirm,
@@ -99,8 +189,12 @@ impl CreateWizard {
     }
 
     pub fn previous_step(&mut self) {
+        self.clear_passphrase_fields();
+        let has_size = !self.size_choices().is_empty();
         self.step = match self.step {
             WizardStep::SelectType => WizardStep::SelectType,
+            WizardStep::ChooseSize => WizardStep::SelectType,
+            WizardStep::EnterFilename if has_size => WizardStep::ChooseSize,
             WizardStep::EnterFilename => WizardStep::SelectType,
             WizardStep::EnterComment => WizardStep::EnterFilename,
             WizardStep::EnterPassphrase => WizardStep::EnterComment,
@@ -115,6 +209,7 @@ impl CreateWizard {
     pub fn get_step_description(&self) -> &'static str {
         match self.step {
             WizardStep::SelectType => "Select key type",
+            WizardStep::ChooseSize => "Select key size",
             WizardStep::EnterFilename => "Enter filename",
             WizardStep::EnterComment => "Enter comment (optional)",
             WizardStep::EnterPassphrase => "Enter passphrase (optional)",
@@ -123,12 +218,16 @@ impl CreateWizard {
     }
 
     pub fn get_summary(&self) -> String {
+        let key_type = match self.options.bits {
+            Some(bits) => format!("{} {}", self.options.key_type, self.size_label(bits)),
+            None => self.options.key_type.to_string(),
+        };
         format!(
             "Key Type: {}\n\
              Filename: {}\n\
              Comment: {}\n\
              Passphrase: {}",
-            self.options.key_type,
+            key_type,
             self.options.filename,
             self.options.comment,
             if self.options.passphrase.is_some() {
@@ -170,7 +269,36 @@ mod tests {
         wizard.select_type(KeyType::Rsa);
         assert_eq!(wizard.options.key_type, KeyType::Rsa);
         assert_eq!(wizard.temp_filename, "id_rsa");
+        assert!(matches!(wizard.step, WizardStep::ChooseSize));
+        assert_eq!(wizard.options.bits, Some(4096));
+
+        wizard.select_type(KeyType::Ed25519);
+        assert!(matches!(wizard.step, WizardStep::EnterFilename));
+        assert_eq!(wizard.options.bits, None);
+    }
+
+    #[test]
+    fn test_choose_size() {
+        let mut wizard = CreateWizard::new();
+        wizard.select_type(KeyType::Ecdsa);
+        assert_eq!(wizard.step_number(), (2, 6));
+
+        assert!(!wizard.choose_size(0));
+        assert!(!wizard.choose_size(4));
+        assert!(matches!(wizard.step, WizardStep::ChooseSize));
+
+        assert!(wizard.choose_size(2));
+        assert_eq!(wizard.options.bits, Some(384));
         assert!(matches!(wizard.step, WizardStep::EnterFilename));
+        assert_eq!(wizard.step_number(), (3, 6));
+        assert!(
+            wizard
+                .get_summary()
+                .starts_with("Key Type: ECDSA nistp384\n")
+        );
+
+        wizard.previous_step();
+        assert!(matches!(wizard.step, WizardStep::ChooseSize));
     }
 
     #[test]
@@ -206,6 +334,41 @@ mod tests {
         assert_eq!(wizard.options.passphrase, None);
     }
 
+    #[test]
+    fn test_passphrase_fields() {
+        let mut wizard = CreateWizard::new();
+        for c in "secret".chars() {
+            wizard.active_passphrase_field().insert_char(c);
+        }
+        // Enter on a filled-in passphrase moves on to the confirmation
+        assert!(!wizard.submit_passphrase());
+        assert!(wizard.confirm_passphrase.is_active);
+        assert!(wizard.error_message.is_none());
+
+        for c in "secrte".chars() {
+            wizard.active_passphrase_field().insert_char(c);
+        }
+        assert!(!wizard.submit_passphrase());
+        assert!(wizard.error_message.is_some());
+        assert!(wizard.passphrase.value.is_empty());
+        assert!(wizard.passphrase.is_active);
+
+        for c in "secret".chars() {
+            wizard.active_passphrase_field().insert_char(c);
+        }
+        wizard.switch_passphrase_field();
+        for c in "secret".chars() {
+            wizard.active_passphrase_field().insert_char(c);
+        }
+        assert!(wizard.submit_passphrase());
+        assert_eq!(wizard.options.passphrase, Some("secret".to_string()));
+
+        // No passphrase at all needs no confirmation
+        let mut wizard = CreateWizard::new();
+        assert!(wizard.submit_passphrase());
+        assert_eq!(wizard.options.passphrase, None);
+    }
+
     #[test]
     fn test_step_navigation() {
         let mut wizard = CreateWizard::new();
@@ -0,0 +1,129 @@
+//! The overview `D` opens: how many keys of each type and status there are,
+//! the oldest one, when the last backup was made and what the audit found
+//!
+//! It is gathered once when the view opens (and on refresh), as finding the
+//! last backup means listing the data directory.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::config::Config;
+use crate::crypto::backup::BackupManager;
+use crate::crypto::schedule::{AutoBackup, BackupSchedule};
+use crate::ssh::SshKey;
+
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    pub total: usize,
+    pub by_type: Vec<(String, usize)>, // most common first
+    pub by_status: Vec<(String, usize)>,
+    pub problems: usize, // keys missing a half or unreadable
+    pub oldest: Option<(String, DateTime<Local>)>,
+    pub last_backup: Option<(PathBuf, DateTime<Local>)>, // newest backup in the data directory
+    pub schedule: Option<BackupSchedule>,                // automatic backups, if set up
+}
+
+impl Dashboard {
+    pub fn collect(keys: &[SshKey], config: &Config) -> Self {
+        let oldest = keys
+            .iter()
+            .filter_map(|key| Some((key.name.clone(), key.created_at.or(key.modified_at)?)))
+            .min_by_key(|(_, born)| *born);
+
+        let last_backup = BackupManager::find_backups(&config.export_dir)
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to list backups: {}", e);
+                Vec::new()
+            })
+            .into_iter()
+            .next()
+            .and_then(|path| {
+                let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                Some((path, DateTime::<Local>::from(modified)))
+            });
+        let schedule = AutoBackup::new(config).load().unwrap_or_else(|e| {
+            tracing::warn!("Failed to read the backup schedule: {}", e);
+            None
+        });
+
+        Self {
+            total: keys.len(),
+            by_type: count(keys.iter().map(|key| key.key_type.to_string())),
+            by_status: count(keys.iter().map(|key| key.status.to_string())),
+            problems: keys.iter().filter(|key| key.status.is_problem()).count(),
+            oldest,
+            last_backup,
+            schedule,
+        }
+    }
+}
+
+/// How often each value occurs, most common first, ties by name
+fn count(values: impl Iterator<Item = String>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::KeyScanner;
+    use tempfile::TempDir;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMizQvi9/MOhNCQeoGoHidgEDYeiwhUUqXUMwhoryiKI me@x";
+
+    #[test]
+    fn test_collect() {
+        let ssh_dir = TempDir::new().unwrap();
+        for name in ["id_ed25519", "work_ed25519"] {
+            std::fs::write(ssh_dir.path().join(name), "test").unwrap();
+            std::fs::write(ssh_dir.path().join(format!("{}.pub", name)), ED25519).unwrap();
+        }
+        std::fs::write(ssh_dir.path().join("id_rsa"), "test").unwrap();
+        let keys = KeyScanner::new(ssh_dir.path()).scan().unwrap();
+
+        let data_dir = TempDir::new().unwrap();
+        let mut config = Config::from_ssh_dir(ssh_dir.path()).unwrap();
+        config.export_dir = data_dir.path().to_path_buf();
+        let dashboard = Dashboard::collect(&keys, &config);
+        assert_eq!(dashboard.total, 3);
+        assert_eq!(
+            dashboard.by_type,
+            [("ED25519".to_string(), 2), ("RSA".to_string(), 1)]
+        );
+        assert_eq!(dashboard.problems, 1);
+        assert!(dashboard.oldest.is_some());
+        assert!(dashboard.last_backup.is_none());
+        assert!(dashboard.schedule.is_none());
+
+        std::fs::write(data_dir.path().join("old.skm"), "x").unwrap();
+        std::fs::write(data_dir.path().join("notes.txt"), "x").unwrap();
+        let dashboard = Dashboard::collect(&keys, &config);
+        assert_eq!(
+            dashboard.last_backup.unwrap().0,
+            data_dir.path().join("old.skm")
+        );
+    }
+
+    #[test]
+    fn test_count() {
+        let values = ["b", "a", "b", "c", "a", "b"].map(String::from);
+        assert_eq!(
+            count(values.into_iter()),
+            [
+                ("b".to_string(), 3),
+                ("a".to_string(), 2),
+                ("c".to_string(), 1)
+            ]
+        );
+    }
+}
@@ -2,11 +2,14 @@ use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use std::time::Duration;
 
 use crate::error::Result;
-use crate::ssh::generate::KeyGenerator;
+use crate::ssh::config::rewrite_identity_files;
 use crate::ssh::keys::KeyType;
 
-use crate::crypto::backup::{BackupManager, ExportOptions, ImportOptions, MergeStrategy};
-use crate::tui::app::{App, AppState, DialogState, MessageType};
+use crate::crypto::backup::MergeStrategy;
+use crate::tui::app::{App, AppState, DialogState, MessageType, PassphraseStep};
+use crate::tui::components::PaletteAction;
+use crate::tui::keymap::{Action, Context};
+use crate::tui::undo::Edit;
 
 pub fn handle_events(app: &mut App) -> Result<bool> {
     if event::poll(Duration::from_millis(50))? {
@@ -18,135 +21,374 @@ pub fn handle_events(app: &mut App) -> Result<bool> {
 }
 
 fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<bool> {
-    // Global shortcuts
-    if key.code == KeyCode::Char('q') && key.modifiers.contains(KeyModifiers::CONTROL) {
-        app.state = AppState::Quit;
-        return Ok(true);
+    app.touch();
+
+    // Nothing but unlocking (or quitting) while locked
+    if app.state == AppState::Locked {
+        return handle_locked(app, key);
     }
 
-    if key.code == KeyCode::Char('h') && key.modifiers.contains(KeyModifiers::CONTROL) {
-        app.show_help = !app.show_help;
+    // Global shortcuts. Quitting during a job waits for it to finish, so
+    // an import or export isn't cut off halfway; quitting again abandons it.
+    if is_quit_key(app, key) {
+        if app.job.is_some() && !app.quit_after_job {
+            app.quit_after_job = true;
+        } else {
+            app.state = AppState::Quit;
+        }
         return Ok(true);
     }
 
+    match app.keymap.action(Context::Global, key) {
+        Some(Action::Help) => {
+            app.toggle_help();
+            return Ok(true);
+        }
+        Some(Action::DebugLog) => {
+            app.show_debug_log = !app.show_debug_log;
+            return Ok(true);
+        }
+        _ => {}
+    }
+
+    if app.show_help {
+        return handle_help(app, key);
+    }
+
     // State-specific handling
     match app.state {
         AppState::KeyList => handle_key_list(app, key),
         AppState::KeyDetail => handle_key_detail(app, key),
+        AppState::HostList => handle_host_list(app, key),
+        AppState::Dashboard => handle_dashboard(app, key),
         AppState::CreateWizard => handle_create_wizard(app, key),
         AppState::ExportDialog => handle_export_dialog(app, key),
         AppState::ImportDialog => handle_import_dialog(app, key),
         AppState::DeleteConfirm => handle_delete_confirm(app, key),
+        AppState::ChangePassphrase => handle_change_passphrase(app, key),
+        AppState::RenameDialog => handle_rename_dialog(app, key),
+        AppState::EditComment => handle_edit_comment(app, key),
+        AppState::CommandPalette => handle_command_palette(app, key),
+        AppState::FindKey => handle_find_key(app, key),
+        AppState::FixPermissions => handle_fix_permissions(app, key),
+        AppState::InspectFile => handle_inspect_file(app, key),
+        AppState::FilterInput => handle_filter_input(app, key),
+        AppState::Locked => handle_locked(app, key),
+        AppState::AgentAdd => handle_agent_add(app, key),
+        AppState::MigrateWizard => handle_migrate_wizard(app, key),
+        // Only the quit keys, handled above, do anything until the job is done
+        AppState::Working => Ok(true),
         AppState::MessageDialog => handle_message_dialog(app, key),
         AppState::Quit => Ok(true),
     }
 }
 
-fn handle_key_list(app: &mut App, key: KeyEvent) -> Result<bool> {
-    match key.code {
-        KeyCode::Char('q') | KeyCode::Esc => {
-            app.state = AppState::Quit;
-            Ok(true)
+/// The help overlay takes the keys while it is open and scrolls like the key
+/// detail; the view underneath waits until it is closed
+fn handle_help(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let page = app.help_height.max(1);
+    match app.keymap.action(Context::Detail, key) {
+        // The overlay stops scrolling at its last line when it is drawn
+        Some(Action::Down) => app.help_scroll = app.help_scroll.saturating_add(1),
+        Some(Action::Up) => app.help_scroll = app.help_scroll.saturating_sub(1),
+        Some(Action::PageDown) => app.help_scroll = app.help_scroll.saturating_add(page),
+        Some(Action::PageUp) => app.help_scroll = app.help_scroll.saturating_sub(page),
+        Some(Action::Top) => app.help_scroll = 0,
+        Some(Action::Bottom) => app.help_scroll = u16::MAX,
+        Some(Action::Back | Action::Close) => app.toggle_help(),
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Counts above this are taken as a typo rather than a key number
+const MAX_COUNT: usize = 9999;
+
+/// The vim keys typed so far in the key list towards a motion: a count, as
+/// in `5j`, and the first `g` of `gg`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pending {
+    #[default]
+    Nothing,
+    Count(usize),
+    G(Option<usize>), // `g`, after a count if there was one
+}
+
+impl Pending {
+    fn count(self) -> Option<usize> {
+        match self {
+            Pending::Nothing => None,
+            Pending::Count(count) => Some(count),
+            Pending::G(count) => count,
         }
-        KeyCode::Down | KeyCode::Char('j') => {
-            app.next_key();
-            Ok(true)
+    }
+}
+
+impl std::fmt::Display for Pending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(count) = self.count() {
+            write!(f, "{}", count)?;
         }
-        KeyCode::Up | KeyCode::Char('k') => {
-            app.previous_key();
-            Ok(true)
+        if let Pending::G(_) = self {
+            write!(f, "g")?;
         }
-        KeyCode::Enter => {
-            if let Some(key) = app.get_selected_key() {
-                app.selected_key = Some(key.clone());
-                app.state = AppState::KeyDetail;
+        Ok(())
+    }
+}
+
+/// The vim motions the help overlay lists for the key list
+pub const VIM_MOTIONS: &[(&str, &str)] = &[
+    ("gg/G", "First/last key, or key N after a count (5G)"),
+    ("<count>j/k", "Move that many keys (5j)"),
+];
+
+/// `gg`, `G` and counts in front of the up and down keys;
+/// true if the key was one of them. Keys bound in `[keys]` or the preset
+/// keep their action (a bound `G` still takes a count), and any other key
+/// drops what was pending.
+fn handle_vim_motion(app: &mut App, key: KeyEvent, action: Option<Action>) -> bool {
+    let pending = std::mem::take(&mut app.pending_keys);
+    let plain = !key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    let unbound = action.is_none();
+
+    match key.code {
+        KeyCode::Char(c @ '0'..='9')
+            if plain && unbound && (c != '0' || pending.count().is_some()) =>
+        {
+            let digit = c.to_digit(10).unwrap_or(0) as usize;
+            let count = pending.count().unwrap_or(0) * 10 + digit;
+            app.pending_keys = Pending::Count(count.min(MAX_COUNT));
+        }
+        KeyCode::Char('g') if plain && unbound => match pending {
+            Pending::G(count) => app.go_to_key(count.unwrap_or(1)),
+            _ => app.pending_keys = Pending::G(pending.count()),
+        },
+        KeyCode::Char('G') if plain && (unbound || action == Some(Action::Bottom)) => {
+            match pending.count() {
+                Some(count) => app.go_to_key(count),
+                None => app.last_key(),
             }
-            Ok(true)
         }
-        KeyCode::Char('n') => {
+        _ => match (pending.count(), action) {
+            (Some(count), Some(Action::Down)) => app.move_selection(count as isize),
+            (Some(count), Some(Action::Up)) => app.move_selection(-(count as isize)),
+            _ => return false,
+        },
+    }
+    true
+}
+
+fn handle_key_list(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let action = app.keymap.action(Context::List, key);
+    if handle_vim_motion(app, key, action) {
+        return Ok(true);
+    }
+    let Some(action) = action else {
+        return Ok(true);
+    };
+
+    match action {
+        // ESC drops a filter, then the marks, before it quits
+        Action::Back if !app.filter.is_empty() => app.clear_filter(),
+        Action::Back if !app.marked.is_empty() => app.marked.clear(),
+        Action::Back | Action::Close => app.state = AppState::Quit,
+        Action::Palette => app.open_palette(),
+        Action::Redo => app.undo_edit(true),
+        Action::Mark => app.toggle_mark(),
+        Action::Filter => app.start_filter(),
+        Action::Find => app.open_finder(),
+        Action::Down => app.next_key(),
+        Action::Up => app.previous_key(),
+        Action::PageDown => app.next_page(),
+        Action::PageUp => app.previous_page(),
+        Action::HalfPageDown => app.half_page(true),
+        Action::HalfPageUp => app.half_page(false),
+        Action::Top => app.first_key(),
+        Action::Bottom => app.last_key(),
+        Action::Open => app.show_key_detail(),
+        Action::New if !app.refuse_unsafe_key_dir() => {
             app.start_wizard();
             app.state = AppState::CreateWizard;
-            Ok(true)
         }
-        KeyCode::Char('e') => {
+        Action::Export => {
             app.start_export();
             app.state = AppState::ExportDialog;
-            Ok(true)
         }
-        KeyCode::Char('i') => {
+        Action::Import if !app.refuse_unsafe_key_dir() => {
             app.start_import();
             app.state = AppState::ImportDialog;
-            Ok(true)
         }
-        KeyCode::Char('d') => {
-            if app.get_selected_key().is_some() {
-                app.confirm_delete = false;
-                app.state = AppState::DeleteConfirm;
-            }
-            Ok(true)
+        Action::Inspect => {
+            app.start_inspect();
+            app.state = AppState::InspectFile;
         }
-        KeyCode::Char('r') => {
-            match app.refresh_keys() {
-                Ok(()) => {
-                    app.set_message("Keys refreshed", MessageType::Success, AppState::KeyList)
-                }
-                Err(e) => app.set_message(
-                    format!("Error: {}", e),
-                    MessageType::Error,
-                    AppState::KeyList,
-                ),
-            }
-            Ok(true)
-        }
-        KeyCode::Char('y') => {
-            // Copy public key to clipboard
-            if let Some(key) = app.get_selected_key() {
-                match copy_key_to_clipboard(key, false) {
-                    Ok(()) => app.set_message(
-                        format!("Public key '{}' copied to clipboard!", key.name),
-                        MessageType::Success,
-                        AppState::KeyList,
-                    ),
-                    Err(e) => app.set_message(
-                        format!("Failed to copy: {}", e),
-                        MessageType::Error,
-                        AppState::KeyList,
-                    ),
-                }
-            }
-            Ok(true)
+        Action::Delete if !app.target_keys().is_empty() => {
+            app.confirm_delete = false;
+            app.state = AppState::DeleteConfirm;
         }
-        KeyCode::Char('c') => {
-            // Copy full public key with comment
-            if let Some(key) = app.get_selected_key() {
-                match copy_key_to_clipboard(key, true) {
-                    Ok(()) => app.set_message(
-                        format!("Full public key '{}' copied to clipboard!", key.name),
-                        MessageType::Success,
-                        AppState::KeyList,
-                    ),
-                    Err(e) => app.set_message(
-                        format!("Failed to copy: {}", e),
-                        MessageType::Error,
-                        AppState::KeyList,
-                    ),
-                }
-            }
-            Ok(true)
+        Action::Rename if app.get_selected_key().is_some() => {
+            app.start_rename();
+            app.state = AppState::RenameDialog;
         }
-        _ => Ok(true),
+        Action::Refresh => refresh_keys_with_message(app),
+        Action::Undo => app.undo_edit(false),
+        Action::CopyKey => copy_selected_key(app, CopyKind::PublicKey),
+        Action::CopyAuthorizedLine => copy_selected_key(app, CopyKind::AuthorizedLine),
+        Action::CopyFull => copy_selected_key(app, CopyKind::FullPublicKey),
+        Action::AgentPanel => app.toggle_agent_panel(),
+        Action::AgentAdd => app.start_agent_add(),
+        Action::AgentRemove => app.remove_selected_from_agent(),
+        Action::SwitchTab => app.show_hosts(),
+        Action::Dashboard => app.show_dashboard(),
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_dashboard(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match app.keymap.action(Context::List, key) {
+        Some(Action::Back | Action::Close | Action::Dashboard) => app.state = AppState::KeyList,
+        Some(Action::Refresh) => app.show_dashboard(),
+        _ => {}
     }
+    Ok(true)
+}
+
+/// The hosts tab moves like the key list; Enter and Tab go back to the keys
+/// on the host's key
+fn handle_host_list(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let Some(action) = app.keymap.action(Context::List, key) else {
+        return Ok(true);
+    };
+
+    match action {
+        Action::Down => app.next_host(),
+        Action::Up => app.previous_host(),
+        Action::Open => app.show_host_key(true),
+        Action::SwitchTab => app.show_host_key(false),
+        Action::Back => app.state = AppState::KeyList,
+        Action::Close => app.state = AppState::Quit,
+        Action::Refresh => app.refresh_hosts(),
+        _ => {}
+    }
+    Ok(true)
 }
 
 fn handle_key_detail(app: &mut App, key: KeyEvent) -> Result<bool> {
-    match key.code {
-        KeyCode::Esc | KeyCode::Char('q') => {
+    let Some(action) = app.keymap.action(Context::Detail, key) else {
+        return Ok(true);
+    };
+
+    match action {
+        Action::Back | Action::Close => {
             app.state = AppState::KeyList;
             app.selected_key = None;
+        }
+        Action::Palette => app.open_palette(),
+        Action::Redo => app.undo_edit(true),
+        // The view stops scrolling at its last line when it is drawn
+        Action::Down => app.detail_scroll = app.detail_scroll.saturating_add(1),
+        Action::Up => app.detail_scroll = app.detail_scroll.saturating_sub(1),
+        Action::PageDown => {
+            app.detail_scroll = app.detail_scroll.saturating_add(app.detail_height.max(1));
+        }
+        Action::PageUp => {
+            app.detail_scroll = app.detail_scroll.saturating_sub(app.detail_height.max(1));
+        }
+        Action::Top => app.detail_scroll = 0,
+        Action::Bottom => app.detail_scroll = u16::MAX,
+        Action::CopyAuthorizedLine => copy_selected_key(app, CopyKind::AuthorizedLine),
+        Action::EditComment if app.selected_key.as_ref().is_some_and(|k| k.has_public()) => {
+            app.start_comment_edit();
+            app.state = AppState::EditComment;
+        }
+        Action::ChangePassphrase if app.selected_key.as_ref().is_some_and(|k| k.has_private()) => {
+            app.start_passphrase_change();
+            app.state = AppState::ChangePassphrase;
+        }
+        Action::Undo => app.undo_edit(false),
+        Action::CheckDeployments => app.check_deployments(),
+        Action::RegeneratePublicKey => app.regenerate_public_key(),
+        Action::SwitchTab => app.show_hosts(),
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_change_passphrase(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc => {
+            app.end_passphrase_change();
+            app.state = AppState::KeyDetail;
             Ok(true)
         }
-        KeyCode::Char('c') => {
-            // TODO: Edit comment - would need an input dialog
+        KeyCode::Enter => {
+            match app.passphrase_step {
+                PassphraseStep::EnterOld => app.passphrase_step = PassphraseStep::EnterNew,
+                PassphraseStep::EnterNew => app.passphrase_step = PassphraseStep::ConfirmNew,
+                PassphraseStep::ConfirmNew => {
+                    if app.new_passphrase != app.confirm_new_passphrase {
+                        app.new_passphrase.clear();
+                        app.confirm_new_passphrase.clear();
+                        app.passphrase_step = PassphraseStep::EnterNew;
+                        app.set_message(
+                            "Passphrases do not match",
+                            MessageType::Error,
+                            AppState::ChangePassphrase,
+                        );
+                        return Ok(true);
+                    }
+
+                    let Some(selected) = app.selected_key.clone() else {
+                        app.state = AppState::KeyList;
+                        return Ok(true);
+                    };
+
+                    let old =
+                        (!app.old_passphrase.is_empty()).then_some(app.old_passphrase.as_str());
+                    let new =
+                        (!app.new_passphrase.is_empty()).then_some(app.new_passphrase.as_str());
+
+                    app.remember_key_ages(std::slice::from_ref(&selected));
+                    match selected.change_passphrase(old, new) {
+                        Ok(()) => {
+                            let msg = if new.is_some() {
+                                format!("Passphrase updated for '{}'", selected.name)
+                            } else {
+                                format!("Passphrase removed from '{}'", selected.name)
+                            };
+                            app.end_passphrase_change();
+                            app.set_message(msg, MessageType::Success, AppState::KeyDetail);
+                        }
+                        Err(e) => {
+                            app.start_passphrase_change();
+                            app.set_message(
+                                format!("Failed to change passphrase: {}", e),
+                                MessageType::Error,
+                                AppState::ChangePassphrase,
+                            );
+                        }
+                    }
+                }
+            }
+            Ok(true)
+        }
+        KeyCode::Backspace => {
+            match app.passphrase_step {
+                PassphraseStep::EnterOld => app.old_passphrase.pop(),
+                PassphraseStep::EnterNew => app.new_passphrase.pop(),
+                PassphraseStep::ConfirmNew => app.confirm_new_passphrase.pop(),
+            };
+            Ok(true)
+        }
+        KeyCode::Char(c) => {
+            match app.passphrase_step {
+                PassphraseStep::EnterOld => app.old_passphrase.push(c),
+                PassphraseStep::EnterNew => app.new_passphrase.push(c),
+                PassphraseStep::ConfirmNew => app.confirm_new_passphrase.push(c),
+            }
             Ok(true)
         }
         _ => Ok(true),
@@ -157,13 +399,34 @@ fn handle_create_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
     use crate::tui::components::wizard::WizardStep;
 
     let current_step = app.get_wizard_step().unwrap_or(WizardStep::SelectType);
-
-    match key.code {
-        KeyCode::Esc => {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.end_wizard();
+        app.state = AppState::KeyList;
+        return Ok(true);
+    }
+    // Left or Backspace with nothing typed step back like ESC, though not
+    // out of the wizard
+    let back = match key.code {
+        KeyCode::Esc => true,
+        KeyCode::Backspace | KeyCode::Left => {
+            current_step != WizardStep::SelectType && wizard_input_empty(app, current_step)
+        }
+        _ => false,
+    };
+    if back {
+        if current_step == WizardStep::SelectType {
             app.end_wizard();
             app.state = AppState::KeyList;
-            Ok(true)
+        } else {
+            app.wizard_previous();
         }
+        return Ok(true);
+    }
+    if current_step == WizardStep::EnterPassphrase && edit_passphrase_field(app, key) {
+        return Ok(true);
+    }
+
+    match key.code {
         KeyCode::Backspace => {
             app.wizard_input.pop();
             Ok(true)
@@ -174,6 +437,9 @@ fn handle_create_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
                 WizardStep::SelectType => {
                     // Handled by number keys
                 }
+                WizardStep::ChooseSize => {
+                    app.wizard_next();
+                }
                 WizardStep::EnterFilename | WizardStep::EnterComment => {
                     if !app.wizard_next() {
                         if let Some(err) = app.get_wizard_error() {
@@ -182,37 +448,10 @@ fn handle_create_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
                     }
                 }
                 WizardStep::EnterPassphrase => {
-                    // Store passphrase and move to confirmation
-                    if !app.wizard_next() {
-                        if let Some(err) = app.get_wizard_error() {
-                            app.set_message(err, MessageType::Error, AppState::CreateWizard);
-                        }
-                    }
-                }
-                WizardStep::Confirm => {
-                    // Generate the key
-                    if let Some(options) = app.get_wizard_options() {
-                        let generator = KeyGenerator::new(&app.config.ssh_dir);
-                        match generator.generate(options) {
-                            Ok(_) => {
-                                app.refresh_keys()?;
-                                app.end_wizard();
-                                app.set_message(
-                                    "Key created successfully",
-                                    MessageType::Success,
-                                    AppState::KeyList,
-                                );
-                            }
-                            Err(e) => {
-                                app.set_message(
-                                    format!("Failed to create key: {}", e),
-                                    MessageType::Error,
-                                    AppState::CreateWizard,
-                                );
-                            }
-                        }
-                    }
+                    // A mismatch is shown under the fields, which are cleared for another go
+                    app.wizard_next();
                 }
+                WizardStep::Confirm => app.generate_key(),
             }
             Ok(true)
         }
@@ -222,10 +461,13 @@ fn handle_create_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
                 WizardStep::SelectType => match c {
                     '1' => app.wizard_select_type(KeyType::Ed25519),
                     '2' => app.wizard_select_type(KeyType::Rsa),
+                    '3' => app.wizard_select_type(KeyType::Ecdsa),
                     _ => {}
                 },
-                WizardStep::EnterPassphrase if c == '\t' => {
-                    // Tab to switch between passphrase and confirm
+                WizardStep::ChooseSize => {
+                    if let Some(n) = c.to_digit(10) {
+                        app.wizard_choose_size(n as usize);
+                    }
                 }
                 _ => {
                     app.wizard_input.push(c);
@@ -237,61 +479,73 @@ fn handle_create_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
     }
 }
 
+/// Whether the wizard step has nothing typed in it; steps without a text
+/// field count as empty
+fn wizard_input_empty(app: &App, step: crate::tui::components::wizard::WizardStep) -> bool {
+    use crate::tui::components::wizard::WizardStep;
+
+    match (step, &app.wizard) {
+        (WizardStep::EnterFilename | WizardStep::EnterComment, _) => app.wizard_input.is_empty(),
+        (WizardStep::EnterPassphrase, Some(wizard)) => {
+            wizard.passphrase.value.is_empty() && wizard.confirm_passphrase.value.is_empty()
+        }
+        _ => true,
+    }
+}
+
+/// Typing, cursor keys and Tab in the wizard's two passphrase fields; false
+/// for the keys the wizard handles itself
+fn edit_passphrase_field(app: &mut App, key: KeyEvent) -> bool {
+    let Some(ref mut wizard) = app.wizard else {
+        return false;
+    };
+    if matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+        wizard.switch_passphrase_field();
+        return true;
+    }
+
+    wizard.error_message = None;
+    let field = wizard.active_passphrase_field();
+    match key.code {
+        KeyCode::Char(c) => field.insert_char(c),
+        KeyCode::Backspace => field.backspace(),
+        KeyCode::Delete => field.delete_char(),
+        KeyCode::Left => field.move_cursor_left(),
+        KeyCode::Right => field.move_cursor_right(),
+        KeyCode::Home => field.move_cursor_start(),
+        KeyCode::End => field.move_cursor_end(),
+        _ => return false,
+    }
+    true
+}
+
 fn handle_export_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if app.dialog_state == DialogState::SelectKeys {
+        return handle_export_selection(app, key);
+    }
+
     match key.code {
         KeyCode::Esc => {
             app.state = AppState::KeyList;
             Ok(true)
         }
-        KeyCode::Enter => {
-            match app.dialog_state {
-                DialogState::EnterPath => {
-                    app.dialog_state = DialogState::EnterPassphrase;
-                    Ok(true)
-                }
-                DialogState::EnterPassphrase => {
-                    app.dialog_state = DialogState::Confirm;
-                    Ok(true)
-                }
-                DialogState::Confirm => {
-                    // Perform export
-                    let manager = BackupManager::new(&app.config.ssh_dir);
-                    let opts = ExportOptions {
-                        description: Some(format!(
-                            "Backup from {}",
-                            chrono::Local::now().format("%Y-%m-%d")
-                        )),
-                        include_public_only: false,
-                        selected_keys: None,
-                    };
-
-                    let path = std::path::PathBuf::from(&app.export_path);
-
-                    // Ensure parent directory exists
-                    if let Some(parent) = path.parent() {
-                        std::fs::create_dir_all(parent).ok();
-                    }
-
-                    match manager.export(&app.keys, &path, &app.dialog_passphrase, opts) {
-                        Ok(()) => {
-                            app.set_message(
-                                format!("Exported {} keys to {}", app.keys.len(), app.export_path),
-                                MessageType::Success,
-                                AppState::KeyList,
-                            );
-                        }
-                        Err(e) => {
-                            app.set_message(
-                                format!("Export failed: {}", e),
-                                MessageType::Error,
-                                AppState::KeyList,
-                            );
-                        }
-                    }
-                    Ok(true)
-                }
+        KeyCode::Enter => match app.dialog_state {
+            DialogState::EnterPath => {
+                app.dialog_state = DialogState::EnterPassphrase;
+                Ok(true)
             }
-        }
+            DialogState::EnterPassphrase => {
+                app.dialog_state = DialogState::Confirm;
+                Ok(true)
+            }
+            DialogState::Confirm
+            | DialogState::SelectKeys
+            | DialogState::ChooseStrategy
+            | DialogState::EnterLifetime => {
+                app.export_keys();
+                Ok(true)
+            }
+        },
         KeyCode::Backspace => {
             match app.dialog_state {
                 DialogState::EnterPath => {
@@ -320,7 +574,45 @@ fn handle_export_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
     }
 }
 
+/// The checklist of keys to export, the dialog's first step
+fn handle_export_selection(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let movement = movement(app, key);
+    match key.code {
+        KeyCode::Esc => {
+            app.state = AppState::KeyList;
+        }
+        _ if movement == Some(Action::Down) && app.export_index + 1 < app.keys.len() => {
+            app.export_index += 1;
+        }
+        _ if movement == Some(Action::Up) => {
+            app.export_index = app.export_index.saturating_sub(1);
+        }
+        KeyCode::Char(' ') => app.toggle_export_key(),
+        KeyCode::Char('a') => app.toggle_all_export_keys(),
+        KeyCode::Char('p') => app.export_private = !app.export_private,
+        KeyCode::Enter => {
+            if app.checked_export_keys().is_empty() {
+                app.set_message(
+                    "No keys selected; press Space to check the ones to export".to_string(),
+                    MessageType::Info,
+                    AppState::ExportDialog,
+                );
+            } else {
+                app.dialog_state = DialogState::EnterPath;
+            }
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
 fn handle_import_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match app.dialog_state {
+        DialogState::SelectKeys => return handle_import_selection(app, key),
+        DialogState::ChooseStrategy => return handle_import_strategy(app, key),
+        _ => {}
+    }
+
     match key.code {
         KeyCode::Esc => {
             app.state = AppState::KeyList;
@@ -330,44 +622,10 @@ fn handle_import_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
             match app.dialog_state {
                 DialogState::EnterPath => {
                     app.dialog_state = DialogState::EnterPassphrase;
-                    Ok(true)
-                }
-                DialogState::EnterPassphrase => {
-                    app.dialog_state = DialogState::Confirm;
-                    Ok(true)
-                }
-                DialogState::Confirm => {
-                    // Perform import
-                    let manager = BackupManager::new(&app.config.ssh_dir);
-                    let opts = ImportOptions {
-                        merge_strategy: MergeStrategy::SkipExisting,
-                        dry_run: false,
-                    };
-
-                    let path = std::path::PathBuf::from(&app.import_path);
-
-                    match manager.import(&path, &app.dialog_passphrase, opts) {
-                        Ok(report) => {
-                            app.refresh_keys()?;
-                            let msg = format!(
-                                "Import complete: {} imported, {} skipped, {} overwritten",
-                                report.imported.len(),
-                                report.skipped.len(),
-                                report.overwritten.len()
-                            );
-                            app.set_message(msg, MessageType::Success, AppState::KeyList);
-                        }
-                        Err(e) => {
-                            app.set_message(
-                                format!("Import failed: {}", e),
-                                MessageType::Error,
-                                AppState::KeyList,
-                            );
-                        }
-                    }
-                    Ok(true)
                 }
+                _ => app.load_import_entries(),
             }
+            Ok(true)
         }
         KeyCode::Backspace => {
             match app.dialog_state {
@@ -397,33 +655,308 @@ fn handle_import_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
     }
 }
 
-fn handle_delete_confirm(app: &mut App, key: KeyEvent) -> Result<bool> {
+/// The checklist shown once the backup is decrypted
+fn handle_import_selection(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let movement = movement(app, key);
     match key.code {
-        KeyCode::Esc | KeyCode::Char('n') => {
-            app.confirm_delete = false;
+        KeyCode::Esc => {
             app.state = AppState::KeyList;
+        }
+        _ if movement == Some(Action::Down) && app.import_index + 1 < app.import_entries.len() => {
+            app.import_index += 1;
+        }
+        _ if movement == Some(Action::Up) => {
+            app.import_index = app.import_index.saturating_sub(1);
+        }
+        KeyCode::Char(' ') => app.toggle_import_entry(),
+        KeyCode::Char('a') => app.toggle_all_import_entries(),
+        KeyCode::Enter => {
+            let selected = app.checked_import_keys();
+            if selected.is_empty() {
+                app.set_message(
+                    "No keys selected; press Space to check the ones to import".to_string(),
+                    MessageType::Info,
+                    AppState::ImportDialog,
+                );
+                return Ok(true);
+            }
+
+            // Only ask how to merge when something would clash
+            if app.import_conflicts().is_empty() {
+                app.import_checked_keys();
+            } else {
+                app.dialog_state = DialogState::ChooseStrategy;
+            }
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// What to do with checked keys that already exist, before importing
+fn handle_import_strategy(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let movement = movement(app, key);
+    match key.code {
+        KeyCode::Esc => app.dialog_state = DialogState::SelectKeys,
+        _ if movement == Some(Action::Down) => app.cycle_import_strategy(true),
+        _ if movement == Some(Action::Up) => app.cycle_import_strategy(false),
+        KeyCode::Char('s') => app.import_strategy = MergeStrategy::SkipExisting,
+        KeyCode::Char('o') => app.import_strategy = MergeStrategy::Overwrite,
+        KeyCode::Char('r') => app.import_strategy = MergeStrategy::Rename,
+        KeyCode::Enter => app.import_checked_keys(),
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_edit_comment(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc => {
+            app.comment_input.clear();
+            app.state = AppState::KeyDetail;
             Ok(true)
         }
-        KeyCode::Char('y') => {
-            if let Some(key) = app.get_selected_key().cloned() {
-                let private_deleted = std::fs::remove_file(&key.path).is_ok();
-                let public_deleted = std::fs::remove_file(&key.public_path).is_ok();
+        KeyCode::Enter => {
+            let Some(mut selected) = app.selected_key.clone() else {
+                app.state = AppState::KeyList;
+                return Ok(true);
+            };
 
-                if private_deleted || public_deleted {
+            let comment = app.comment_input.trim().to_string();
+            let old_comment = selected.comment.clone().unwrap_or_default();
+            match selected.update_comment(&comment) {
+                Ok(()) => {
+                    app.record_edit(Edit::Comment {
+                        key_path: selected.path.clone(),
+                        old: old_comment,
+                        new: comment,
+                    });
                     app.refresh_keys()?;
+                    app.select_key_by_name(&selected.name);
+                    app.selected_key = Some(selected.clone());
+                    app.comment_input.clear();
                     app.set_message(
-                        format!("Deleted key '{}'", key.name),
+                        format!("Comment updated for '{}'", selected.name),
                         MessageType::Success,
-                        AppState::KeyList,
+                        AppState::KeyDetail,
                     );
-                } else {
+                }
+                Err(e) => {
+                    app.set_message(
+                        format!("Failed to update comment: {}", e),
+                        MessageType::Error,
+                        AppState::EditComment,
+                    );
+                }
+            }
+            Ok(true)
+        }
+        KeyCode::Backspace => {
+            app.comment_input.pop();
+            Ok(true)
+        }
+        KeyCode::Char(c) => {
+            app.comment_input.push(c);
+            Ok(true)
+        }
+        _ => Ok(true),
+    }
+}
+
+fn handle_agent_add(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let input = match app.dialog_state {
+        DialogState::EnterPassphrase => &mut app.dialog_passphrase,
+        _ => &mut app.agent_lifetime_input,
+    };
+    match key.code {
+        KeyCode::Esc => {
+            app.agent_lifetime_input.clear();
+            app.dialog_passphrase.clear();
+            app.state = AppState::KeyList;
+        }
+        KeyCode::Enter => match app.dialog_state {
+            // Ask for the passphrase only once the lifetime is known to be good
+            DialogState::EnterLifetime => {
+                if app.agent_lifetime().is_some() {
+                    if app.get_selected_key().is_some_and(|k| k.is_encrypted()) {
+                        app.dialog_state = DialogState::EnterPassphrase;
+                    } else {
+                        app.add_selected_to_agent();
+                    }
+                }
+            }
+            _ => app.add_selected_to_agent(),
+        },
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// The migration wizard: a text field per step, then whether to load the
+/// restored keys into the agent and a last look before it runs
+fn handle_migrate_wizard(app: &mut App, key: KeyEvent) -> Result<bool> {
+    use crate::tui::components::migrate::MigrateStep;
+
+    let Some(ref mut wizard) = app.migrate else {
+        app.state = AppState::KeyList;
+        return Ok(true);
+    };
+    let cancel = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+    if cancel || (key.code == KeyCode::Esc && wizard.step == MigrateStep::EnterBackup) {
+        app.migrate = None;
+        app.state = AppState::KeyList;
+        return Ok(true);
+    }
+
+    match key.code {
+        KeyCode::Esc => wizard.previous_step(),
+        KeyCode::Enter if wizard.step == MigrateStep::Confirm => app.run_migration(),
+        KeyCode::Enter => {
+            wizard.next_step();
+        }
+        KeyCode::Char('y') if wizard.step == MigrateStep::ChooseAgent => {
+            wizard.add_to_agent = true;
+            wizard.next_step();
+        }
+        KeyCode::Char('n') if wizard.step == MigrateStep::ChooseAgent => {
+            wizard.add_to_agent = false;
+            wizard.next_step();
+        }
+        code => {
+            if let Some(field) = wizard.active_field() {
+                match code {
+                    KeyCode::Char(c) => field.insert_char(c),
+                    KeyCode::Backspace => field.backspace(),
+                    KeyCode::Delete => field.delete_char(),
+                    KeyCode::Left => field.move_cursor_left(),
+                    KeyCode::Right => field.move_cursor_right(),
+                    KeyCode::Home => field.move_cursor_start(),
+                    KeyCode::End => field.move_cursor_end(),
+                    _ => {}
+                }
+                wizard.error_message = None;
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn handle_rename_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc => {
+            app.rename_input.clear();
+            app.state = AppState::KeyList;
+            Ok(true)
+        }
+        KeyCode::Enter => {
+            let Some(selected) = app.get_selected_key().cloned() else {
+                app.state = AppState::KeyList;
+                return Ok(true);
+            };
+
+            let new_name = app.rename_input.trim().to_string();
+            if new_name == selected.name {
+                app.state = AppState::KeyList;
+                return Ok(true);
+            }
+
+            match selected.rename(&new_name) {
+                Ok(renamed) => {
+                    // Only with [tui] rename_updates_config, as the CLI only
+                    // does it with --update-config
+                    let config_path = app
+                        .rename_updates_config
+                        .then(|| app.config.ssh_dir.join("config"));
+                    let updated = config_path.as_ref().map(|config_path| {
+                        rewrite_identity_files(config_path, &selected.path, &renamed.path)
+                    });
+                    app.record_edit(Edit::Rename {
+                        old_path: selected.path.clone(),
+                        new_path: renamed.path.clone(),
+                        config_path: config_path
+                            .filter(|_| matches!(updated, Some(Ok(n)) if n > 0)),
+                    });
+
+                    app.refresh_keys()?;
+                    app.select_key_by_name(&renamed.name);
+                    app.rename_input.clear();
+
+                    let mut msg = format!("Renamed '{}' to '{}'", selected.name, renamed.name);
+                    match updated {
+                        Some(Ok(0)) | None => {
+                            app.set_message(msg, MessageType::Success, AppState::KeyList)
+                        }
+                        Some(Ok(updated)) => {
+                            msg.push_str(&format!(" ({} IdentityFile line(s) updated)", updated));
+                            app.set_message(msg, MessageType::Success, AppState::KeyList);
+                        }
+                        Some(Err(e)) => {
+                            msg.push_str(&format!(
+                                ", but the ssh config couldn't be updated: {}",
+                                e
+                            ));
+                            app.set_message(msg, MessageType::Error, AppState::KeyList);
+                        }
+                    }
+                }
+                Err(e) => {
                     app.set_message(
-                        format!("Failed to delete key '{}'", key.name),
+                        format!("Rename failed: {}", e),
                         MessageType::Error,
-                        AppState::KeyList,
+                        AppState::RenameDialog,
                     );
                 }
             }
+            Ok(true)
+        }
+        KeyCode::Backspace => {
+            app.rename_input.pop();
+            Ok(true)
+        }
+        KeyCode::Char(c) => {
+            app.rename_input.push(c);
+            Ok(true)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Typing narrows the key list as it goes; Enter keeps the filter, ESC drops it
+fn handle_filter_input(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc => {
+            app.clear_filter();
+            app.state = AppState::KeyList;
+        }
+        KeyCode::Enter => app.state = AppState::KeyList,
+        KeyCode::Down => app.next_key(),
+        KeyCode::Up => app.previous_key(),
+        KeyCode::Backspace => {
+            app.filter.pop();
+            app.apply_filter();
+        }
+        KeyCode::Char(c) => {
+            app.filter.push(c);
+            app.apply_filter();
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_delete_confirm(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.confirm_delete = false;
+            app.state = AppState::KeyList;
+            Ok(true)
+        }
+        KeyCode::Char('y') => {
+            app.delete_target_keys();
             app.confirm_delete = false;
             Ok(true)
         }
@@ -431,6 +964,266 @@ fn handle_delete_confirm(app: &mut App, key: KeyEvent) -> Result<bool> {
     }
 }
 
+/// Path prompt until a file is loaded, then a scrollable view of it
+fn handle_inspect_file(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if app.inspection.is_some() {
+        let movement = movement(app, key);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.inspection = None;
+                app.state = AppState::KeyList;
+            }
+            _ if movement == Some(Action::Down) => {
+                app.inspect_scroll = app.inspect_scroll.saturating_add(1);
+            }
+            _ if movement == Some(Action::Up) => {
+                app.inspect_scroll = app.inspect_scroll.saturating_sub(1);
+            }
+            KeyCode::Char('o') => app.inspection = None,
+            _ => {}
+        }
+        return Ok(true);
+    }
+
+    match key.code {
+        KeyCode::Esc => app.state = AppState::KeyList,
+        KeyCode::Enter => {
+            if let Err(e) = app.load_inspection() {
+                app.set_message(
+                    format!("Cannot inspect: {}", e),
+                    MessageType::Error,
+                    AppState::InspectFile,
+                );
+            }
+        }
+        KeyCode::Backspace => {
+            app.inspect_input.pop();
+        }
+        KeyCode::Char(c) => app.inspect_input.push(c),
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_fix_permissions(app: &mut App, key: KeyEvent) -> Result<bool> {
+    match key.code {
+        KeyCode::Char('y') => app.fix_permissions(),
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.permission_changes.clear();
+            app.state = AppState::KeyList;
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Ctrl+Q, or F10 for when tmux, screen or terminal flow control takes Ctrl+Q
+/// Checklists and scrolled results move with the key list's up and down keys
+fn movement(app: &App, key: KeyEvent) -> Option<Action> {
+    app.keymap
+        .action(Context::List, key)
+        .filter(|action| matches!(action, Action::Down | Action::Up))
+}
+
+/// The quit keys, and F10 whatever they are
+fn is_quit_key(app: &App, key: KeyEvent) -> bool {
+    key.code == KeyCode::F(10) || app.keymap.action(Context::Global, key) == Some(Action::Quit)
+}
+
+fn handle_locked(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if is_quit_key(app, key) {
+        app.state = AppState::Quit;
+        return Ok(true);
+    }
+
+    match key.code {
+        KeyCode::Enter => app.unlock(),
+        KeyCode::Char(c) => app.unlock_input.push(c),
+        KeyCode::Backspace => {
+            app.unlock_input.pop();
+        }
+        KeyCode::Esc => app.unlock_input.clear(),
+        _ => {}
+    }
+    Ok(true)
+}
+
+fn handle_command_palette(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let Some(ref mut palette) = app.palette else {
+        app.state = AppState::KeyList;
+        return Ok(true);
+    };
+
+    match key.code {
+        KeyCode::Esc => app.close_palette(),
+        KeyCode::Down => palette.next(),
+        KeyCode::Up => palette.previous(),
+        KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => palette.next(),
+        KeyCode::Backspace => palette.pop(),
+        KeyCode::Enter => {
+            let action = palette.selected_action();
+            app.close_palette();
+            if let Some(action) = action {
+                run_palette_action(app, action)?;
+            }
+        }
+        KeyCode::Char(c) => palette.push(c),
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+fn handle_find_key(app: &mut App, key: KeyEvent) -> Result<bool> {
+    let Some(ref mut finder) = app.finder else {
+        app.state = AppState::KeyList;
+        return Ok(true);
+    };
+
+    match key.code {
+        KeyCode::Esc => app.close_finder(),
+        KeyCode::Down => finder.next(),
+        KeyCode::Up => finder.previous(),
+        KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => finder.next(),
+        KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => finder.previous(),
+        KeyCode::Backspace => finder.pop(&app.keys),
+        KeyCode::Enter => app.jump_to_found_key(),
+        KeyCode::Char(c) => finder.push(c, &app.keys),
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+/// Run an action on the current selection, as if its shortcut had been pressed
+/// in the view the palette was opened from
+fn run_palette_action(app: &mut App, action: PaletteAction) -> Result<()> {
+    let has_key = app.get_selected_key().is_some();
+
+    match action {
+        PaletteAction::ViewDetails => app.show_key_detail(),
+        PaletteAction::NewKey if !app.refuse_unsafe_key_dir() => {
+            app.start_wizard();
+            app.state = AppState::CreateWizard;
+        }
+        PaletteAction::RenameKey if has_key => {
+            app.start_rename();
+            app.state = AppState::RenameDialog;
+        }
+        PaletteAction::EditComment if has_key => {
+            app.show_key_detail();
+            if app.selected_key.as_ref().is_some_and(|k| k.has_public()) {
+                app.start_comment_edit();
+                app.state = AppState::EditComment;
+            }
+        }
+        PaletteAction::ChangePassphrase if has_key => {
+            app.show_key_detail();
+            if app.selected_key.as_ref().is_some_and(|k| k.has_private()) {
+                app.start_passphrase_change();
+                app.state = AppState::ChangePassphrase;
+            }
+        }
+        PaletteAction::CopyPublicKey => copy_selected_key(app, CopyKind::PublicKey),
+        PaletteAction::CopyFullPublicKey => copy_selected_key(app, CopyKind::FullPublicKey),
+        PaletteAction::CopyAuthorizedLine => copy_selected_key(app, CopyKind::AuthorizedLine),
+        PaletteAction::ToggleAgentPanel => app.toggle_agent_panel(),
+        PaletteAction::AddToAgent if has_key => app.start_agent_add(),
+        PaletteAction::RemoveFromAgent if has_key => app.remove_selected_from_agent(),
+        PaletteAction::ShowHosts => app.show_hosts(),
+        PaletteAction::ShowDashboard => app.show_dashboard(),
+        PaletteAction::ExportKeys => {
+            app.start_export();
+            app.state = AppState::ExportDialog;
+        }
+        PaletteAction::ImportKeys if !app.refuse_unsafe_key_dir() => {
+            app.start_import();
+            app.state = AppState::ImportDialog;
+        }
+        PaletteAction::Migrate if !app.refuse_unsafe_key_dir() => app.start_migration(),
+        PaletteAction::InspectFile => {
+            app.start_inspect();
+            app.state = AppState::InspectFile;
+        }
+        PaletteAction::DeleteKey if has_key => {
+            app.confirm_delete = false;
+            app.state = AppState::DeleteConfirm;
+        }
+        PaletteAction::Undo => app.undo_edit(false),
+        PaletteAction::Redo => app.undo_edit(true),
+        PaletteAction::RestoreDeleted => app.restore_last_deleted(),
+        PaletteAction::FilterKeys => app.start_filter(),
+        PaletteAction::FindKey => app.open_finder(),
+        PaletteAction::ToggleMark if has_key => app.toggle_mark(),
+        PaletteAction::MarkAllShown => app.toggle_mark_shown(),
+        PaletteAction::RefreshKeys => refresh_keys_with_message(app),
+        PaletteAction::Lock => app.lock(),
+        PaletteAction::FixPermissions => {
+            app.check_permissions();
+            if app.state != AppState::FixPermissions {
+                app.set_message(
+                    "All permissions are already safe",
+                    MessageType::Info,
+                    AppState::KeyList,
+                );
+            }
+        }
+        PaletteAction::ToggleHelp => app.toggle_help(),
+        PaletteAction::ToggleDebugLog => app.show_debug_log = !app.show_debug_log,
+        PaletteAction::Quit => app.state = AppState::Quit,
+        // Key actions with nothing selected
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn refresh_keys_with_message(app: &mut App) {
+    let return_state = app.state;
+    match app.refresh_keys() {
+        Ok(()) => app.set_message("Keys refreshed", MessageType::Success, return_state),
+        Err(e) => app.set_message(format!("Error: {}", e), MessageType::Error, return_state),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopyKind {
+    PublicKey,
+    FullPublicKey,
+    AuthorizedLine,
+}
+
+/// Copy the selected key to the clipboard and report the result
+fn copy_selected_key(app: &mut App, kind: CopyKind) {
+    let Some(key) = app.get_selected_key() else {
+        return;
+    };
+    let return_state = app.state;
+
+    let (result, what) = match kind {
+        CopyKind::PublicKey => (copy_key_to_clipboard(key, false), "Public key"),
+        CopyKind::FullPublicKey => (copy_key_to_clipboard(key, true), "Full public key"),
+        CopyKind::AuthorizedLine => (
+            key.authorized_line(None)
+                .and_then(|line| set_clipboard(&line)),
+            "authorized_keys line for",
+        ),
+    };
+
+    match result {
+        Ok(()) => app.set_message(
+            format!("{} '{}' copied to clipboard!", what, key.name),
+            MessageType::Success,
+            return_state,
+        ),
+        Err(e) => app.set_message(
+            format!("Failed to copy: {}", e),
+            MessageType::Error,
+            return_state,
+        ),
+    }
+}
+
 fn handle_message_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
     match key.code {
         KeyCode::Enter | KeyCode::Esc => {
@@ -443,8 +1236,6 @@ fn handle_message_dialog(app: &mut App, key: KeyEvent) -> Result<bool> {
 
 /// Copy public key to clipboard
 fn copy_key_to_clipboard(key: &crate::ssh::keys::SshKey, full: bool) -> Result<()> {
-    use arboard::Clipboard;
-
     let content = if full {
         key.read_public_content()?.ok_or_else(|| {
             crate::error::SkmError::KeyNotFound(format!("Public key for {}", key.name))
@@ -464,13 +1255,9 @@ fn copy_key_to_clipboard(key: &crate::ssh::keys::SshKey, full: bool) -> Result<(
         }
     };
 
-    let mut clipboard = Clipboard::new().map_err(|e| {
-        crate::error::SkmError::Unknown(format!("Failed to access clipboard: {}", e))
-    })?;
-
-    clipboard.set_text(content.trim()).map_err(|e| {
-        crate::error::SkmError::Unknown(format!("Failed to copy to clipboard: {}", e))
-    })?;
+    set_clipboard(content.trim())
+}
 
-    Ok(())
+fn set_clipboard(text: &str) -> Result<()> {
+    crate::terminal::copy_to_clipboard(text)
 }
@@ -0,0 +1,828 @@
+//! Which key does what in the TUI
+//!
+//! A preset (`default`, `vim` or `emacs`) supplies the bindings and the
+//! `[keys]` section of the settings file replaces them action by action, e.g.
+//! `delete = "x"` or `down = ["j", "ctrl+n"]`. Only the key list, the key
+//! detail and the global shortcuts are mapped; text fields and the small
+//! confirmation dialogs keep their fixed keys.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeySettings;
+use crate::error::{Result, SkmError};
+
+/// Something a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    Quit,
+    Help,
+    DebugLog,
+    Palette,
+    Redo,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    Open,
+    Back,
+    Close,
+    Filter,
+    Find,
+    Mark,
+    CopyKey,
+    CopyFull,
+    CopyAuthorizedLine,
+    New,
+    Rename,
+    EditComment,
+    ChangePassphrase,
+    Export,
+    Import,
+    Inspect,
+    Delete,
+    Undo,
+    Refresh,
+    CheckDeployments,
+    RegeneratePublicKey,
+    AgentPanel,
+    AgentAdd,
+    AgentRemove,
+    SwitchTab,
+    Dashboard,
+}
+
+/// Where an action's keys work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// In every view and dialog, text fields included
+    Everywhere,
+    /// The key list and the key detail
+    Views,
+    List,
+    Detail,
+}
+
+/// The view a key press is looked up for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// Any view or dialog; only the `Everywhere` actions
+    Global,
+    List,
+    Detail,
+}
+
+impl Scope {
+    fn applies_to(self, context: Context) -> bool {
+        match self {
+            Scope::Everywhere => true,
+            Scope::Views => context != Context::Global,
+            Scope::List => context == Context::List,
+            Scope::Detail => context == Context::Detail,
+        }
+    }
+
+    /// Whether a key can mean both at once, i.e. the scopes share a view
+    fn overlaps(self, other: Scope) -> bool {
+        [Context::Global, Context::List, Context::Detail]
+            .into_iter()
+            .any(|context| self.applies_to(context) && other.applies_to(context))
+    }
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Help,
+        Action::DebugLog,
+        Action::Palette,
+        Action::Redo,
+        Action::Down,
+        Action::Up,
+        Action::PageDown,
+        Action::PageUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::Top,
+        Action::Bottom,
+        Action::Open,
+        Action::Back,
+        Action::Close,
+        Action::Filter,
+        Action::Find,
+        Action::Mark,
+        Action::CopyKey,
+        Action::CopyFull,
+        Action::CopyAuthorizedLine,
+        Action::New,
+        Action::Rename,
+        Action::EditComment,
+        Action::ChangePassphrase,
+        Action::Export,
+        Action::Import,
+        Action::Inspect,
+        Action::Delete,
+        Action::Undo,
+        Action::Refresh,
+        Action::CheckDeployments,
+        Action::RegeneratePublicKey,
+        Action::AgentPanel,
+        Action::AgentAdd,
+        Action::AgentRemove,
+        Action::SwitchTab,
+        Action::Dashboard,
+    ];
+
+    /// Name in the `[keys]` section
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::DebugLog => "debug_log",
+            Action::Palette => "palette",
+            Action::Redo => "redo",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Back => "back",
+            Action::Close => "close",
+            Action::Undo => "undo",
+            Action::CopyAuthorizedLine => "copy_authorized_line",
+            Action::SwitchTab => "switch_tab",
+            Action::Open => "open",
+            Action::Filter => "filter",
+            Action::Find => "find",
+            Action::Mark => "mark",
+            Action::New => "new",
+            Action::Export => "export",
+            Action::Import => "import",
+            Action::Inspect => "inspect",
+            Action::Delete => "delete",
+            Action::Rename => "rename",
+            Action::Refresh => "refresh",
+            Action::CopyKey => "copy_key",
+            Action::CopyFull => "copy_full",
+            Action::AgentPanel => "agent_panel",
+            Action::AgentAdd => "agent_add",
+            Action::AgentRemove => "agent_remove",
+            Action::Dashboard => "dashboard",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::EditComment => "edit_comment",
+            Action::ChangePassphrase => "change_passphrase",
+            Action::CheckDeployments => "check_deployments",
+            Action::RegeneratePublicKey => "regenerate_public_key",
+        }
+    }
+
+    /// What the action does, for the help overlay
+    pub const fn description(&self, context: Context) -> &'static str {
+        match (self, context) {
+            (Action::Quit, _) => "Quit",
+            (Action::Help, _) => "Toggle this help",
+            (Action::DebugLog, _) => "Toggle debug log",
+            (Action::Palette, _) => "Command palette: every action by name",
+            (Action::Redo, _) => "Redo last undone edit",
+            (Action::Down, Context::Detail) => "Scroll down",
+            (Action::Up, Context::Detail) => "Scroll up",
+            (Action::Down, _) => "Move down",
+            (Action::Up, _) => "Move up",
+            (Action::PageDown, Context::List) => "Move a page down",
+            (Action::PageUp, Context::List) => "Move a page up",
+            (Action::HalfPageDown, _) => "Move half a page down",
+            (Action::HalfPageUp, _) => "Move half a page up",
+            (Action::Top, Context::List) => "Go to the first key",
+            (Action::Bottom, Context::List) => "Go to the last key",
+            (Action::PageDown, _) => "Scroll a page down",
+            (Action::PageUp, _) => "Scroll a page up",
+            (Action::Top, _) => "Scroll to the top",
+            (Action::Bottom, _) => "Scroll to the bottom",
+            (Action::Open, _) => "View key details",
+            (Action::Back, Context::Detail) | (Action::Close, Context::Detail) => {
+                "Back to the list"
+            }
+            (Action::Back, _) => "Clear the filter, then the marks, then quit",
+            (Action::Close, _) => "Quit",
+            (Action::Filter, _) => "Filter by name, comment, type or fingerprint",
+            (Action::Find, _) => "Fuzzy-find a key by name or comment",
+            (Action::Mark, _) => "Mark key; export and delete then act on the marked keys",
+            (Action::CopyKey, _) => "Copy public key (without comment)",
+            (Action::CopyFull, _) => "Copy full public key (with comment)",
+            (Action::AgentPanel, _) => "Toggle the ssh-agent panel",
+            (Action::AgentAdd, _) => "Add selected key to ssh-agent",
+            (Action::AgentRemove, _) => "Remove selected key from ssh-agent",
+            (Action::Dashboard, _) => "Dashboard: key counts, last backup, agent and audit",
+            (Action::CopyAuthorizedLine, _) => "Copy authorized_keys line",
+            (Action::SwitchTab, _) => "Switch to the ssh config hosts, on one using this key",
+            (Action::New, _) => "Create new key",
+            (Action::Rename, _) => "Rename selected key",
+            (Action::EditComment, _) => "Edit comment",
+            (Action::ChangePassphrase, _) => "Change passphrase",
+            (Action::Export, _) => "Export keys (pick which; p: public keys only)",
+            (Action::Import, _) => "Import keys (Space picks which)",
+            (Action::Inspect, _) => "Inspect any key file",
+            (Action::Delete, _) => "Delete selected key (to the trash)",
+            (Action::Undo, Context::Detail) => "Undo last edit",
+            (Action::Undo, _) => "Undo last comment/rename edit or delete",
+            (Action::Refresh, _) => "Refresh list",
+            (Action::CheckDeployments, _) => "Check deployed hosts still have the key",
+            (Action::RegeneratePublicKey, _) => "Regenerate a missing .pub file",
+        }
+    }
+
+    /// The actions of `context` itself: the `Everywhere` ones for `Global`,
+    /// everything else that works in the list or detail view otherwise
+    pub fn in_context(context: Context) -> impl Iterator<Item = Action> {
+        Action::ALL.iter().copied().filter(move |action| {
+            let scope = action.scope();
+            (scope == Scope::Everywhere) == (context == Context::Global)
+                && scope.applies_to(context)
+        })
+    }
+
+    fn scope(&self) -> Scope {
+        match self {
+            Action::Quit | Action::Help | Action::DebugLog => Scope::Everywhere,
+            Action::Palette
+            | Action::Redo
+            | Action::Down
+            | Action::Up
+            | Action::PageDown
+            | Action::PageUp
+            | Action::Top
+            | Action::Bottom
+            | Action::Back
+            | Action::Close
+            | Action::Undo
+            | Action::CopyAuthorizedLine
+            | Action::SwitchTab => Scope::Views,
+            Action::Open
+            | Action::Filter
+            | Action::Find
+            | Action::Mark
+            | Action::New
+            | Action::Export
+            | Action::Import
+            | Action::Inspect
+            | Action::Delete
+            | Action::Rename
+            | Action::Refresh
+            | Action::CopyKey
+            | Action::CopyFull
+            | Action::AgentPanel
+            | Action::AgentAdd
+            | Action::AgentRemove
+            | Action::Dashboard
+            | Action::HalfPageDown
+            | Action::HalfPageUp => Scope::List,
+            Action::EditComment
+            | Action::ChangePassphrase
+            | Action::CheckDeployments
+            | Action::RegeneratePublicKey => Scope::Detail,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
+
+/// A key with the Ctrl/Alt modifiers it is pressed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        // Terminals don't tell Ctrl+P from Ctrl+Shift+P; Shift is in the
+        // character itself otherwise, so `I` is just `I`
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parse `x`, `I`, `ctrl+p`, `alt+x`, `enter`, `pgdn`, `f12`, ...
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |why: &str| SkmError::Config(format!("Invalid key '{}': {}", spec, why));
+
+        // `+` on its own, or after a modifier as in `ctrl++`
+        let (prefix, name) = match spec.strip_suffix("++") {
+            Some(prefix) => (Some(prefix), "+"),
+            None if spec == "+" => (None, "+"),
+            None => match spec.rsplit_once('+') {
+                Some((prefix, name)) => (Some(prefix), name),
+                None => (None, spec),
+            },
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut shift = false;
+        for modifier in prefix.into_iter().flat_map(|prefix| prefix.split('+')) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" | "meta" => modifiers |= KeyModifiers::ALT,
+                "shift" => shift = true,
+                _ => return Err(invalid(&format!("unknown modifier '{}'", modifier))),
+            }
+        }
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if shift && c.is_ascii_alphabetic() => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) if !shift => KeyCode::Char(c),
+            (Some(_), None) => return Err(invalid("shift only goes with letters")),
+            (None, _) => return Err(invalid("no key given")),
+            _ if shift => return Err(invalid("shift only goes with letters")),
+            _ => named_key(&name.to_ascii_lowercase()).ok_or_else(|| invalid("unknown key"))?,
+        };
+        Ok(Key::new(code, modifiers))
+    }
+
+    /// Whether the key still works while typing into a text field
+    fn leaves_text_alone(&self) -> bool {
+        !self.modifiers.is_empty() || matches!(self.code, KeyCode::F(_))
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pgup" | "pageup" => KeyCode::PageUp,
+        "pgdn" | "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        _ => {
+            let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+            return (1..=12).contains(&n).then_some(KeyCode::F(n));
+        }
+    };
+    Some(code)
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if !self.modifiers.is_empty() => write!(f, "{}", c.to_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "ESC"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Insert => write!(f, "Ins"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Ctrl keys outside the keymap, for moving through the palette and the finder
+const FIXED_CTRL_KEYS: &[(char, &str)] =
+    &[('n', "next palette entry"), ('p', "previous finder entry")];
+
+/// Bindings that ship with skm, picked with `preset = "..."`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preset {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl Preset {
+    fn parse(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Ok(Preset::Default),
+            "vim" => Ok(Preset::Vim),
+            "emacs" => Ok(Preset::Emacs),
+            _ => Err(SkmError::Config(format!(
+                "Unknown key preset '{}' (expected default, vim or emacs)",
+                name
+            ))),
+        }
+    }
+
+    fn bindings(self, action: Action) -> &'static [&'static str] {
+        match (self, action) {
+            (Preset::Vim, Action::Open) => &["enter", "l"],
+            (Preset::Vim, Action::Palette) => &["ctrl+p", ":"],
+            (Preset::Vim, Action::Delete) => &["d", "x"],
+            (Preset::Vim, Action::PageDown) => &["pgdn", "ctrl+f"],
+            (Preset::Vim, Action::PageUp) => &["pgup", "ctrl+b"],
+            (Preset::Vim, Action::Bottom) => &["end", "G"],
+
+            (Preset::Emacs, Action::Down) => &["j", "down", "ctrl+n"],
+            (Preset::Emacs, Action::Up) => &["k", "up", "ctrl+p"],
+            (Preset::Emacs, Action::Palette) => &["alt+x"],
+            (Preset::Emacs, Action::Filter) => &["/", "ctrl+s"],
+            (Preset::Emacs, Action::Back) => &["esc", "ctrl+g"],
+            (Preset::Emacs, Action::PageDown) => &["pgdn", "ctrl+v"],
+            (Preset::Emacs, Action::PageUp) => &["pgup", "alt+v"],
+            (Preset::Emacs, Action::Top) => &["home", "alt+<"],
+            (Preset::Emacs, Action::Bottom) => &["end", "alt+>"],
+
+            (_, Action::Quit) => &["ctrl+q", "f10"],
+            (_, Action::Help) => &["ctrl+h"],
+            (_, Action::DebugLog) => &["f12"],
+            (_, Action::Palette) => &["ctrl+p"],
+            (_, Action::Redo) => &["ctrl+r"],
+            (_, Action::Down) => &["j", "down"],
+            (_, Action::Up) => &["k", "up"],
+            (_, Action::Back) => &["esc"],
+            (_, Action::Close) => &["q"],
+            (_, Action::Undo) => &["u"],
+            (_, Action::CopyAuthorizedLine) => &["A"],
+            (_, Action::SwitchTab) => &["tab"],
+            (_, Action::Open) => &["enter"],
+            (_, Action::Filter) => &["/"],
+            (_, Action::Find) => &["f"],
+            (_, Action::Mark) => &["space"],
+            (_, Action::New) => &["n"],
+            (_, Action::Export) => &["e"],
+            (_, Action::Import) => &["i"],
+            (_, Action::Inspect) => &["I"],
+            (_, Action::Delete) => &["d"],
+            (_, Action::Rename) => &["m", "R"],
+            (_, Action::Refresh) => &["r"],
+            (_, Action::CopyKey) => &["y"],
+            (_, Action::CopyFull) => &["c"],
+            (_, Action::AgentPanel) => &["a"],
+            (_, Action::AgentAdd) => &["+"],
+            (_, Action::AgentRemove) => &["-"],
+            (_, Action::Dashboard) => &["D"],
+            (_, Action::PageDown) => &["pgdn"],
+            (_, Action::PageUp) => &["pgup"],
+            (_, Action::HalfPageDown) => &["ctrl+d"],
+            (_, Action::HalfPageUp) => &["ctrl+u"],
+            (_, Action::Top) => &["home"],
+            (_, Action::Bottom) => &["end"],
+            (_, Action::EditComment) => &["c"],
+            (_, Action::ChangePassphrase) => &["p"],
+            (_, Action::CheckDeployments) => &["v"],
+            (_, Action::RegeneratePublicKey) => &["g"],
+        }
+    }
+}
+
+/// Keys by action, for looking up key presses and for showing the keys in
+/// the footer and the command palette
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(Preset::Default)
+    }
+}
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .map(|&action| {
+                let keys = preset
+                    .bindings(action)
+                    .iter()
+                    .map(|spec| Key::parse(spec).expect("preset keys parse"))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+
+    /// The preset from `[keys]`, with the actions it lists bound to its keys.
+    /// A key taken over this way stops doing whatever the preset had it do.
+    pub fn from_settings(settings: &KeySettings) -> Result<Self> {
+        let preset = match settings.preset.as_deref() {
+            Some(name) => Preset::parse(name)?,
+            None => Preset::default(),
+        };
+        let mut keymap = Self::preset(preset);
+
+        let mut overrides: Vec<(Action, Vec<Key>)> = Vec::new();
+        for (name, specs) in &settings.bindings {
+            let action = Action::from_name(name)
+                .ok_or_else(|| SkmError::Config(format!("Unknown key action '{}'", name)))?;
+            let keys = specs
+                .keys()
+                .iter()
+                .map(|spec| Key::parse(spec))
+                .collect::<Result<Vec<_>>>()?;
+
+            if action.scope() == Scope::Everywhere {
+                if let Some(key) = keys.iter().find(|key| !key.leaves_text_alone()) {
+                    return Err(SkmError::Config(format!(
+                        "{} works in text fields too, so it needs Ctrl, Alt or a function key, not '{}'",
+                        name, key
+                    )));
+                }
+            }
+            for (other, other_keys) in &overrides {
+                if let Some(key) = keys
+                    .iter()
+                    .find(|key| other_keys.contains(key) && action.scope().overlaps(other.scope()))
+                {
+                    return Err(SkmError::Config(format!(
+                        "'{}' is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        name
+                    )));
+                }
+            }
+            overrides.push((action, keys));
+        }
+
+        for (action, keys) in overrides {
+            for (other, other_keys) in keymap.bindings.iter_mut() {
+                if *other != action && action.scope().overlaps(other.scope()) {
+                    other_keys.retain(|key| !keys.contains(key));
+                }
+            }
+            keymap.bindings.insert(action, keys);
+        }
+        Ok(keymap)
+    }
+
+    /// The action `event` is bound to in `context`, if any
+    pub fn action(&self, context: Context, event: KeyEvent) -> Option<Action> {
+        let key = Key::from(event);
+        self.bindings
+            .iter()
+            .find(|(action, keys)| action.scope().applies_to(context) && keys.contains(&key))
+            .map(|(&action, _)| action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The first key bound to `action`, as shown in hints
+    pub fn key(&self, action: Action) -> Option<String> {
+        self.keys(action).first().map(Key::to_string)
+    }
+
+    /// Every Ctrl+letter the TUI answers to, bound here or fixed, as (letter,
+    /// what it does); a tmux or screen prefix among them swallows it
+    pub fn ctrl_keys(&self) -> Vec<(char, String)> {
+        let mut ctrl_keys: Vec<(char, String)> = Vec::new();
+        for (action, keys) in &self.bindings {
+            for key in keys {
+                if let (KeyCode::Char(c), KeyModifiers::CONTROL) = (key.code, key.modifiers) {
+                    ctrl_keys.push((c, action.name().replace('_', " ")));
+                }
+            }
+        }
+        for &(c, what) in FIXED_CTRL_KEYS {
+            if ctrl_keys.iter().all(|(bound, _)| *bound != c) {
+                ctrl_keys.push((c, what.to_string()));
+            }
+        }
+        ctrl_keys
+    }
+
+    /// Every key bound to `action`, e.g. `j/↓`
+    pub fn describe(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(Key::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyList;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn settings(toml: &str) -> KeySettings {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Key::parse("x").unwrap().to_string(), "x");
+        assert_eq!(Key::parse("I").unwrap().to_string(), "I");
+        assert_eq!(Key::parse("shift+i").unwrap(), Key::parse("I").unwrap());
+        assert_eq!(Key::parse("ctrl+P").unwrap().to_string(), "Ctrl+P");
+        assert_eq!(Key::parse("Alt+x").unwrap().to_string(), "Alt+X");
+        assert_eq!(Key::parse("space").unwrap().to_string(), "Space");
+        assert_eq!(Key::parse("pagedown").unwrap().to_string(), "PgDn");
+        assert_eq!(Key::parse("F12").unwrap().to_string(), "F12");
+        assert_eq!(Key::parse("+").unwrap().to_string(), "+");
+        assert_eq!(Key::parse("ctrl++").unwrap().to_string(), "Ctrl++");
+
+        assert!(Key::parse("").is_err());
+        assert!(Key::parse("hyper+x").is_err());
+        assert!(Key::parse("f13").is_err());
+        assert!(Key::parse("shift+enter").is_err());
+        assert!(Key::parse("sapce").is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let keymap = Keymap::default();
+        let ctrl = KeyModifiers::CONTROL;
+        let none = KeyModifiers::NONE;
+
+        assert_eq!(
+            keymap.action(Context::List, press(KeyCode::Char('c'), none)),
+            Some(Action::CopyFull)
+        );
+        assert_eq!(
+            keymap.action(Context::Detail, press(KeyCode::Char('c'), none)),
+            Some(Action::EditComment)
+        );
+        // Terminals report capitals with Shift held
+        assert_eq!(
+            keymap.action(
+                Context::List,
+                press(KeyCode::Char('I'), KeyModifiers::SHIFT)
+            ),
+            Some(Action::Inspect)
+        );
+        assert_eq!(
+            keymap.action(Context::Global, press(KeyCode::Char('P'), ctrl)),
+            None
+        );
+        assert_eq!(
+            keymap.action(Context::List, press(KeyCode::Char('P'), ctrl)),
+            Some(Action::Palette)
+        );
+        assert_eq!(
+            keymap.action(Context::Global, press(KeyCode::F(10), none)),
+            Some(Action::Quit)
+        );
+        // Ctrl+J is not j
+        assert_eq!(
+            keymap.action(Context::List, press(KeyCode::Char('j'), ctrl)),
+            None
+        );
+        assert_eq!(keymap.describe(Action::Down), "j/↓");
+    }
+
+    #[test]
+    fn test_in_context() {
+        let global: Vec<Action> = Action::in_context(Context::Global).collect();
+        assert_eq!(global, [Action::Quit, Action::Help, Action::DebugLog]);
+
+        let list: Vec<Action> = Action::in_context(Context::List).collect();
+        assert!(list.contains(&Action::Palette) && list.contains(&Action::Delete));
+        assert!(!list.contains(&Action::Quit) && !list.contains(&Action::EditComment));
+
+        // Every action turns up somewhere
+        for action in Action::ALL {
+            assert!(
+                [Context::Global, Context::List, Context::Detail]
+                    .into_iter()
+                    .any(|context| Action::in_context(context).any(|a| a == *action))
+            );
+        }
+    }
+
+    #[test]
+    fn test_presets_have_no_conflicts() {
+        for preset in [Preset::Default, Preset::Vim, Preset::Emacs] {
+            let keymap = Keymap::preset(preset);
+            for (i, a) in Action::ALL.iter().enumerate() {
+                for b in &Action::ALL[i + 1..] {
+                    if !a.scope().overlaps(b.scope()) {
+                        continue;
+                    }
+                    for key in keymap.keys(*a) {
+                        assert!(
+                            !keymap.keys(*b).contains(key),
+                            "{:?}: {} is bound to {} and {}",
+                            preset,
+                            key,
+                            a.name(),
+                            b.name()
+                        );
+                    }
+                }
+                if a.scope() == Scope::Everywhere {
+                    assert!(keymap.keys(*a).iter().all(Key::leaves_text_alone));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let keymap = Keymap::from_settings(&settings("preset = \"emacs\"")).unwrap();
+        assert_eq!(keymap.describe(Action::Up), "k/↑/Ctrl+P");
+        assert_eq!(keymap.describe(Action::Palette), "Alt+X");
+
+        // Taking `x` over from the vim preset's delete
+        let keymap = Keymap::from_settings(&settings(
+            "preset = \"vim\"\nrefresh = \"x\"\ndown = [\"j\", \"ctrl+n\"]",
+        ))
+        .unwrap();
+        assert_eq!(keymap.describe(Action::Refresh), "x");
+        assert_eq!(keymap.describe(Action::Delete), "d");
+        assert_eq!(keymap.describe(Action::Down), "j/Ctrl+N");
+        // Refresh is list only, so a detail view key leaves it alone
+        let keymap = Keymap::from_settings(&settings("check_deployments = \"r\"")).unwrap();
+        assert_eq!(keymap.describe(Action::Refresh), "r");
+
+        let keymap = Keymap::from_settings(&settings("filter = []")).unwrap();
+        assert!(keymap.keys(Action::Filter).is_empty());
+
+        assert!(Keymap::from_settings(&settings("preset = \"nano\"")).is_err());
+        assert!(Keymap::from_settings(&settings("explode = \"x\"")).is_err());
+        assert!(Keymap::from_settings(&settings("delete = \"ctrl+\"")).is_err());
+        assert!(Keymap::from_settings(&settings("help = \"?\"")).is_err());
+        assert!(Keymap::from_settings(&settings("delete = \"x\"\nnew = \"x\"")).is_err());
+
+        assert!(matches!(
+            settings("delete = \"x\"").bindings["delete"],
+            KeyList::One(_)
+        ));
+    }
+
+    #[test]
+    fn test_ctrl_keys() {
+        let has = |keymap: &Keymap, c: char, what: &str| {
+            keymap
+                .ctrl_keys()
+                .iter()
+                .any(|(key, action)| *key == c && action == what)
+        };
+
+        let keymap = Keymap::default();
+        assert!(has(&keymap, 'q', "quit"));
+        assert!(has(&keymap, 'p', "palette"));
+        assert!(has(&keymap, 'd', "half page down"));
+        let keymap = Keymap::from_settings(&settings("half_page_down = \"ctrl+e\"")).unwrap();
+        assert!(has(&keymap, 'e', "half page down"));
+        assert!(!keymap.ctrl_keys().iter().any(|(key, _)| *key == 'd'));
+        assert!(!keymap.ctrl_keys().iter().any(|(key, _)| *key == 'v'));
+
+        // Preset and [keys] bindings count too
+        let keymap = Keymap::from_settings(&settings("preset = \"emacs\"")).unwrap();
+        assert!(has(&keymap, 'v', "page down"));
+        assert!(has(&keymap, 'g', "back"));
+        let keymap = Keymap::from_settings(&settings("refresh = \"ctrl+t\"")).unwrap();
+        assert!(has(&keymap, 't', "refresh"));
+    }
+}
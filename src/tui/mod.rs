@@ -1,6 +1,11 @@
 pub mod app;
 pub mod components;
+pub mod dashboard;
 pub mod events;
+pub mod keymap;
 pub mod ui;
+pub mod undo;
+pub mod watch;
+pub mod worker;
 
 pub use app::{App, AppState};
@@ -1,12 +1,30 @@
 use ratatui::{
     Frame,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, Paragraph},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Cell, Clear, Gauge, HighlightSpacing, List, ListItem, Padding, Paragraph,
+        Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState, Wrap,
+    },
 };
 
+use crate::crypto::LockPassphrase;
+use crate::crypto::backup::MergeStrategy;
+use crate::crypto::schedule;
+use crate::dates;
+use crate::ssh::agent::AgentOrigin;
+use crate::ssh::audit::Severity;
+use crate::ssh::expiry::Expiry;
+use crate::ssh::inspect::{EntryKind, Inspection};
 use crate::ssh::keys::KeyStatus;
-use crate::tui::app::{App, AppState, DialogState, MessageType};
+use crate::ssh::randomart;
+use crate::tui::app::{App, AppState, DialogState, MessageType, PassphraseStep};
+use crate::tui::components::CreateWizard;
+use crate::tui::components::migrate::MigrateStep;
+use crate::tui::events::VIM_MOTIONS;
+use crate::tui::keymap::{Action, Context};
+use crate::tui::worker::Job;
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -18,44 +36,227 @@ pub fn draw(f: &mut Frame, app: &mut App) {
         ])
         .split(f.area());
 
-    draw_header(f, chunks[0]);
+    draw_header(f, app, chunks[0]);
 
     match app.state {
         AppState::KeyList => draw_key_list(f, app, chunks[1]),
         AppState::KeyDetail => draw_key_detail(f, app, chunks[1]),
+        AppState::HostList => draw_host_list(f, app, chunks[1]),
+        AppState::Dashboard => draw_dashboard(f, app, chunks[1]),
         AppState::CreateWizard => draw_create_wizard(f, app, chunks[1]),
         AppState::ExportDialog => draw_export_dialog(f, app, chunks[1]),
         AppState::ImportDialog => draw_import_dialog(f, app, chunks[1]),
         AppState::DeleteConfirm => draw_delete_confirm(f, app, chunks[1]),
-        AppState::MessageDialog => {
+        AppState::ChangePassphrase => draw_change_passphrase(f, app, chunks[1]),
+        AppState::RenameDialog => draw_rename_dialog(f, app, chunks[1]),
+        AppState::EditComment => draw_edit_comment(f, app, chunks[1]),
+        AppState::CommandPalette => {
+            match app.palette.as_ref().map(|p| p.origin) {
+                Some(AppState::KeyDetail) => draw_key_detail(f, app, chunks[1]),
+                _ => draw_key_list(f, app, chunks[1]),
+            }
+            draw_command_palette(f, app);
+        }
+        AppState::FindKey => {
+            draw_key_list(f, app, chunks[1]);
+            draw_key_finder(f, app);
+        }
+        AppState::FixPermissions => {
             draw_key_list(f, app, chunks[1]);
+            draw_fix_permissions(f, app);
+        }
+        AppState::InspectFile => draw_inspect_file(f, app, chunks[1]),
+        AppState::FilterInput => draw_key_list(f, app, chunks[1]),
+        // Key list deliberately not drawn
+        AppState::Locked => draw_locked(f, app),
+        AppState::MessageDialog => {
+            match app.message {
+                Some((_, _, AppState::HostList)) => draw_host_list(f, app, chunks[1]),
+                _ => draw_key_list(f, app, chunks[1]),
+            }
             if let Some((ref msg, ref msg_type, _)) = app.message {
                 draw_message(f, msg, *msg_type);
             }
         }
+        AppState::AgentAdd => {
+            draw_key_list(f, app, chunks[1]);
+            draw_agent_add(f, app);
+        }
+        AppState::MigrateWizard => draw_migrate_wizard(f, app, chunks[1]),
+        AppState::Working => {
+            draw_key_list(f, app, chunks[1]);
+            if let Some(ref job) = app.job {
+                draw_job(f, job, app.quit_after_job);
+            }
+        }
         AppState::Quit => {}
     }
 
     draw_footer(f, app, chunks[2]);
 
+    if let Some((ref msg, msg_type, _)) = app.toast {
+        draw_toast(f, msg, msg_type, chunks[1]);
+    }
+
     if app.show_help {
-        draw_help_popup(f);
+        draw_help_popup(f, app);
+    }
+
+    if app.show_debug_log {
+        draw_debug_log(f, app);
     }
 }
 
-fn draw_header(f: &mut Frame, area: Rect) {
-    let header = Paragraph::new("SSH Key Manager (skm)")
-        .style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
+/// Title, with the tabs under it; the hosts tab is the one in front while
+/// its list is shown
+fn draw_header(f: &mut Frame, app: &App, area: Rect) {
+    let title = Line::from(Span::styled(
+        "SSH Key Manager (skm)",
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let hosts = app.state == AppState::HostList
+        || matches!(app.message, Some((_, _, AppState::HostList)))
+            && app.state == AppState::MessageDialog;
+    let tab = |label: &'static str, active: bool| {
+        if active {
+            Span::styled(
+                label,
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            )
+        } else {
+            Span::styled(label, Style::default().fg(Color::Gray))
+        }
+    };
+    let mut tabs = vec![tab("Keys", !hosts), Span::raw(" | "), tab("Hosts", hosts)];
+    if let Some(key) = app.keymap.key(Action::SwitchTab) {
+        tabs.push(Span::styled(
+            format!("  ({} switches)", key),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let header = Paragraph::new(vec![title, Line::from(tabs)])
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::BOTTOM));
     f.render_widget(header, area);
 }
 
-fn draw_key_list(f: &mut Frame, app: &App, area: Rect) {
+/// Host blocks of the ssh config, with the key in the ssh directory each
+/// one would use
+fn draw_host_list(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL);
+    let Some(ref config) = app.ssh_config else {
+        let text = format!(
+            "Failed to read the ssh config: {}",
+            app.ssh_config_error.as_deref().unwrap_or("not loaded")
+        );
+        let paragraph = Paragraph::new(text).block(block.title("Hosts"));
+        f.render_widget(paragraph, area);
+        return;
+    };
+
+    let title = format!(
+        "Hosts ({}) - {}",
+        config.hosts.len(),
+        config.path().display()
+    );
+    if config.hosts.is_empty() {
+        let paragraph = Paragraph::new("No Host entries in the ssh config.")
+            .block(block.title(title))
+            .alignment(Alignment::Center);
+        f.render_widget(paragraph, area);
+        return;
+    }
+
+    let rows: Vec<Row> = config
+        .hosts
+        .iter()
+        .map(|host| {
+            let host_name = match (host.host_name.as_deref(), host.port) {
+                (Some(name), Some(port)) => format!("{}:{}", name, port),
+                (Some(name), None) => name.to_string(),
+                (None, Some(port)) => format!(":{}", port),
+                (None, None) => "-".to_string(),
+            };
+            let identity_files = if host.identity_files.is_empty() {
+                "-".to_string()
+            } else {
+                host.identity_files.join(", ")
+            };
+            let key = app
+                .host_key(host)
+                .map(|key| key.name.clone())
+                .unwrap_or_else(|| "-".to_string());
+
+            let style = if host.is_pattern() {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            Row::new([
+                host.patterns.join(" "),
+                host_name,
+                host.user.clone().unwrap_or_else(|| "-".to_string()),
+                identity_files,
+                key,
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Fill(2),
+        Constraint::Fill(2),
+        Constraint::Fill(1),
+        Constraint::Fill(3),
+        Constraint::Fill(1),
+    ];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(["Host", "HostName", "User", "IdentityFile", "Key"]).style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        )
+        .block(block.title(title))
+        .row_highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_symbol("> ");
+
+    let mut state = TableState::default();
+    state.select(Some(app.host_index));
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// The key table, with the agent panel under it when that is shown
+fn draw_key_list(f: &mut Frame, app: &mut App, area: Rect) {
+    if !app.show_agent {
+        return draw_key_table(f, app, area);
+    }
+
+    // Tall enough for every key the agent holds, up to half the view
+    let rows = app.agent_entries.len().max(1) as u16 + 2;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(rows.min(area.height / 2)),
+        ])
+        .split(area);
+    draw_key_table(f, app, chunks[0]);
+    draw_agent_panel(f, app, chunks[1]);
+}
+
+fn draw_key_table(f: &mut Frame, app: &mut App, area: Rect) {
     if app.keys.is_empty() {
         let paragraph = Paragraph::new("No SSH keys found.\n\nPress 'n' to create a new key.")
             .block(Block::default().title("SSH Keys").borders(Borders::ALL))
@@ -64,35 +265,138 @@ fn draw_key_list(f: &mut Frame, app: &App, area: Rect) {
         return;
     }
 
-    let items: Vec<ListItem> = app
-        .keys
+    // Columns that don't fit are dropped, least useful first
+    let marking = !app.marked.is_empty();
+    let wide = area.width >= 120;
+    let medium = area.width >= 80;
+    let expiring = app.expiry.is_set();
+
+    let mut header = Vec::new();
+    let mut widths = Vec::new();
+    if marking {
+        header.push("");
+        widths.push(Constraint::Length(3));
+    }
+    header.extend(["Name", "Type", "Status"]);
+    widths.extend([
+        Constraint::Fill(2),
+        Constraint::Length(7),
+        Constraint::Length(15),
+    ]);
+    if wide {
+        header.push("Fingerprint");
+        widths.push(Constraint::Length(50));
+    }
+    if medium {
+        header.extend(["Comment", "Modified"]);
+        widths.extend([Constraint::Fill(2), Constraint::Length(14)]);
+    }
+    if expiring {
+        header.push("Expires");
+        widths.push(Constraint::Length(18));
+    }
+
+    let now = chrono::Local::now();
+    let rows: Vec<Row> = app
+        .visible
         .iter()
-        .map(|key| {
-            let status_symbol = match key.status {
-                KeyStatus::Valid => "[OK]",
-                KeyStatus::Encrypted => "[LOCKED]",
-                _ => "[!]",
+        .filter_map(|&index| Some((app.keys.get(index)?, app.fingerprints.get(index))))
+        .map(|(key, fingerprint)| {
+            let mut cells = Vec::new();
+            if marking {
+                let mark = if app.marked.contains(&key.name) {
+                    "[x]"
+                } else {
+                    "[ ]"
+                };
+                cells.push(Cell::from(mark));
+            }
+
+            let name = if app.agent_keys.contains(&key.name) {
+                format!("{} (agent)", key.name)
+            } else {
+                key.name.clone()
             };
+            cells.push(Cell::from(name));
+            cells.push(Cell::from(key.key_type.to_string()));
+            cells.push(Cell::from(key.status.to_string()).style(status_style(key.status)));
 
-            let content = format!(
-                " {} {} - {} [{}]",
-                status_symbol,
-                key.name,
-                key.key_type,
-                key.comment.as_deref().unwrap_or("no comment")
-            );
+            if wide {
+                let fingerprint = fingerprint.cloned().flatten();
+                cells.push(Cell::from(fingerprint.unwrap_or_else(|| "-".to_string())));
+            }
+            if medium {
+                cells.push(Cell::from(key.comment.clone().unwrap_or_default()));
+                let modified = key
+                    .modified_at
+                    .map(|time| app.date_format.short(time))
+                    .unwrap_or_else(|| "-".to_string());
+                cells.push(Cell::from(modified));
+            }
 
-            ListItem::new(content).style(Style::default())
+            // Keys due for rotation stand out, with when they expire(d)
+            let mut style = Style::default();
+            let expiry = app.expiry.check(key, now);
+            match expiry {
+                Some(Expiry::Soon(_)) => style = style.fg(Color::Yellow),
+                Some(Expiry::Expired(_)) => style = style.fg(Color::Red),
+                Some(Expiry::Valid(_)) | None => {}
+            }
+            if expiring {
+                let when = expiry
+                    .map(|expiry| expiry.describe(now))
+                    .unwrap_or_else(|| "-".to_string());
+                cells.push(Cell::from(when));
+            }
+
+            Row::new(cells).style(style)
         })
         .collect();
 
-    let list = List::new(items)
-        .block(
-            Block::default()
-                .title(format!("SSH Keys ({})", app.keys.len()))
-                .borders(Borders::ALL),
+    let mut block = Block::default()
+        .title(key_list_title(app))
+        .borders(Borders::ALL);
+    let editing = app.state == AppState::FilterInput;
+    if editing || !app.filter.is_empty() {
+        let cursor = if editing { "_" } else { "" };
+        block = block.title_bottom(Line::from(Span::styled(
+            format!(" /{}{} ", app.filter, cursor),
+            Style::default().fg(Color::Cyan),
+        )));
+    }
+    if !rows.is_empty() {
+        // Any count or `g` typed so far goes in front, as vim shows it
+        let pending = match app.pending_keys.to_string() {
+            keys if keys.is_empty() => keys,
+            keys => format!(" {}", keys),
+        };
+        block = block.title_bottom(
+            Line::from(vec![
+                Span::styled(pending, Style::default().fg(Color::Yellow)),
+                Span::raw(format!(" {}/{} ", app.selected_index + 1, rows.len())),
+            ])
+            .right_aligned(),
+        );
+    }
+
+    if rows.is_empty() {
+        let paragraph = Paragraph::new(format!("No keys match '{}'", app.filter))
+            .block(block)
+            .alignment(Alignment::Center);
+        f.render_widget(paragraph, area);
+        return;
+    }
+
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(header).style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
         )
-        .highlight_style(
+        .block(block)
+        .row_highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
                 .add_modifier(Modifier::BOLD),
@@ -100,15 +404,349 @@ fn draw_key_list(f: &mut Frame, app: &App, area: Rect) {
         .highlight_spacing(HighlightSpacing::Always)
         .highlight_symbol("> ");
 
-    let mut state = ratatui::widgets::ListState::default();
-    state.select(Some(app.selected_index));
+    // Below the borders and the header row
+    app.list_height = area.height.saturating_sub(3);
+    let count = app.visible.len();
+    let mut state = TableState::default()
+        .with_offset(app.list_offset)
+        .with_selected(Some(app.selected_index));
+    f.render_stateful_widget(table, area, &mut state);
+    app.list_offset = state.offset();
 
-    f.render_stateful_widget(list, area, &mut state);
+    if count > app.list_height as usize {
+        let mut state = ScrollbarState::new(count.saturating_sub(app.list_height as usize))
+            .position(app.list_offset)
+            .viewport_content_length(app.list_height as usize);
+        // Alongside the keys, not the header
+        let track = Rect {
+            y: area.y + 2,
+            height: app.list_height,
+            ..area
+        };
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            track,
+            &mut state,
+        );
+    }
+}
+
+/// The keys ssh-agent holds, the selected key's highlighted, with how long
+/// the ones skm loaded have left
+fn draw_agent_panel(f: &mut Frame, app: &App, area: Rect) {
+    let count = app.agent_entries.len();
+    let mut title = format!(
+        "ssh-agent ({} key{})",
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    if let AgentOrigin::Forwarded { ref client, .. } = app.agent_origin {
+        title.push_str(&format!(
+            " - forwarded from {}",
+            client.as_deref().unwrap_or("the ssh client")
+        ));
+    }
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    if app.agent_entries.is_empty() {
+        let text = match app.agent_error {
+            Some(ref error) => format!("No agent: {}", error),
+            None => "The agent holds no keys".to_string(),
+        };
+        f.render_widget(Paragraph::new(text).block(block), area);
+        return;
+    }
+
+    let now = chrono::Local::now();
+    let selected = app.get_selected_key().map(|key| key.name.as_str());
+    let rows: Vec<Row> = app
+        .agent_entries
+        .iter()
+        .map(|entry| {
+            let name = match entry.key_name {
+                Some(ref name) => name.clone(),
+                None => entry.identity.comment.clone(),
+            };
+            let lifetime = match entry.expires_at {
+                Some(at) => format!("expires {}", dates::relative(at, now)),
+                None => "-".to_string(),
+            };
+            let mut style = Style::default();
+            if entry.key_name.is_some() && entry.key_name.as_deref() == selected {
+                style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+            }
+            Row::new([name, entry.fingerprint.clone(), lifetime]).style(style)
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Fill(1),
+        Constraint::Length(52),
+        Constraint::Length(24),
+    ];
+    f.render_widget(Table::new(rows, widths).block(block), area);
+}
+
+/// Lifetime, then passphrase, for loading the selected key into the agent
+fn draw_agent_add(f: &mut Frame, app: &App) {
+    let name = app
+        .get_selected_key()
+        .map(|k| k.name.as_str())
+        .unwrap_or("selected key");
+
+    let mut text = match app.dialog_state {
+        DialogState::EnterPassphrase => format!(
+            "Passphrase for '{}':\n\n> {}",
+            name,
+            "*".repeat(app.dialog_passphrase.len())
+        ),
+        _ => format!(
+            "How long should '{}' stay loaded? (e.g. 30m, 8h; empty for no limit)\n\n> {}",
+            name, app.agent_lifetime_input
+        ),
+    };
+    if let AgentOrigin::Forwarded { ref client, .. } = app.agent_origin {
+        text.push_str(&format!(
+            "\n\nWarning: the agent is forwarded from {}; the key will be stored there, \
+             and root on this host can use it while you are connected.",
+            client.as_deref().unwrap_or("the ssh client")
+        ));
+    }
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Add to ssh-agent")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .wrap(Wrap { trim: false });
+
+    let area = centered_rect(60, 30, f.area());
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Key status, colored by how much attention it needs
+fn status_style(status: KeyStatus) -> Style {
+    let color = match status {
+        KeyStatus::Valid => Color::Green,
+        KeyStatus::Encrypted => Color::Cyan,
+        KeyStatus::MissingPublic | KeyStatus::MissingPrivate => Color::Yellow,
+        KeyStatus::Corrupted => Color::Red,
+    };
+    Style::default().fg(color)
+}
+
+/// "SSH Keys (12) — 2 warnings, 1 critical", coloured by the worst finding
+fn key_list_title(app: &App) -> Line<'static> {
+    let mut count = if app.visible.len() == app.keys.len() {
+        format!("SSH Keys ({}", app.keys.len())
+    } else {
+        format!("SSH Keys ({} of {}", app.visible.len(), app.keys.len())
+    };
+    if !app.marked.is_empty() {
+        count.push_str(&format!(", {} marked", app.marked.len()));
+    }
+    let count = Span::raw(count + ")");
+    let critical = app.audit.count(Severity::Critical);
+    let warnings = app.audit.count(Severity::Warning);
+    if critical == 0 && warnings == 0 {
+        return Line::from(count);
+    }
+
+    let mut parts = Vec::new();
+    if warnings > 0 {
+        parts.push(format!(
+            "{} warning{}",
+            warnings,
+            if warnings == 1 { "" } else { "s" }
+        ));
+    }
+    if critical > 0 {
+        parts.push(format!("{} critical", critical));
+    }
+    let color = if critical > 0 {
+        Color::Red
+    } else {
+        Color::Yellow
+    };
+    Line::from(vec![
+        count,
+        Span::raw(" — "),
+        Span::styled(
+            parts.join(", "),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ),
+    ])
+}
+
+/// Keys and backups side by side, the audit findings under them
+fn draw_dashboard(f: &mut Frame, app: &App, area: Rect) {
+    let Some(ref dashboard) = app.dashboard else {
+        return;
+    };
+    let now = chrono::Local::now();
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Cyan));
+    let counts = |counts: &[(String, usize)]| {
+        counts
+            .iter()
+            .map(|(value, n)| format!("{} {}", value, n))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut keys = vec![Line::from(vec![
+        label("Keys:       "),
+        Span::raw(dashboard.total.to_string()),
+    ])];
+    if dashboard.problems > 0 {
+        keys.push(Line::from(vec![
+            label("Problems:   "),
+            Span::styled(
+                format!("{} missing a half or unreadable", dashboard.problems),
+                Style::default().fg(Color::Red),
+            ),
+        ]));
+    }
+    keys.push(Line::from(vec![
+        label("By type:    "),
+        Span::raw(counts(&dashboard.by_type)),
+    ]));
+    keys.push(Line::from(vec![
+        label("By status:  "),
+        Span::raw(counts(&dashboard.by_status)),
+    ]));
+    if let Some((ref name, born)) = dashboard.oldest {
+        keys.push(Line::from(vec![
+            label("Oldest:     "),
+            Span::raw(format!("{}, {}", name, app.date_format.full(born))),
+        ]));
+    }
+
+    let mut health = Vec::new();
+    health.push(Line::from(vec![
+        label("Last backup: "),
+        match dashboard.last_backup {
+            Some((ref path, at)) => Span::raw(format!(
+                "{} ({})",
+                app.date_format.short(at),
+                path.file_name().unwrap_or_default().to_string_lossy()
+            )),
+            None => Span::styled(
+                "none in the data directory",
+                Style::default().fg(Color::Yellow),
+            ),
+        },
+    ]));
+    health.push(Line::from(vec![
+        label("Automatic:   "),
+        match dashboard.schedule {
+            Some(ref schedule) => Span::raw(format!(
+                "every {}, next {}",
+                schedule::format_interval(schedule.every_secs),
+                schedule
+                    .next_due()
+                    .filter(|due| *due > now)
+                    .map(|due| dates::relative(due, now))
+                    .unwrap_or_else(|| "when skm next runs".to_string())
+            )),
+            None => Span::styled("not set up", Style::default().fg(Color::DarkGray)),
+        },
+    ]));
+    let agent = match (&app.agent_origin, &app.agent_error) {
+        (AgentOrigin::NoAgent, _) => Span::styled(
+            "not running (SSH_AUTH_SOCK is not set)",
+            Style::default().fg(Color::DarkGray),
+        ),
+        (_, Some(error)) => Span::styled(
+            format!("unavailable ({})", error),
+            Style::default().fg(Color::Yellow),
+        ),
+        (origin, None) => {
+            let mut text = format!(
+                "{} key(s) loaded, {} from here",
+                app.agent_entries.len(),
+                app.agent_keys.len()
+            );
+            if let AgentOrigin::Forwarded { client, .. } = origin {
+                text.push_str(&format!(
+                    ", forwarded from {}",
+                    client.as_deref().unwrap_or("the ssh client")
+                ));
+            }
+            Span::raw(text)
+        }
+    };
+    health.push(Line::from(vec![label("ssh-agent:   "), agent]));
+
+    let findings: Vec<Line> = if app.audit.findings.is_empty() {
+        vec![Line::from(Span::styled(
+            "No findings",
+            Style::default().fg(Color::Green),
+        ))]
+    } else {
+        app.audit
+            .findings
+            .iter()
+            .map(|finding| {
+                let color = match finding.severity {
+                    Severity::Critical => Color::Red,
+                    Severity::Warning => Color::Yellow,
+                };
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:<9}", finding.severity),
+                        Style::default().fg(color),
+                    ),
+                    Span::raw(format!("{}: {}", finding.key, finding.message)),
+                ])
+            })
+            .collect()
+    };
+    let audit_title = format!(
+        "Audit ({} critical, {} warnings)",
+        app.audit.count(Severity::Critical),
+        app.audit.count(Severity::Warning)
+    );
+
+    let top = keys.len().max(health.len()) as u16 + 2;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(top), Constraint::Min(0)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[0]);
+
+    let pane = |title: &'static str| Block::default().title(title).borders(Borders::ALL);
+    f.render_widget(
+        Paragraph::new(keys)
+            .block(pane("Keys"))
+            .wrap(Wrap { trim: false }),
+        columns[0],
+    );
+    f.render_widget(
+        Paragraph::new(health)
+            .block(pane("Backups and agent"))
+            .wrap(Wrap { trim: false }),
+        columns[1],
+    );
+    f.render_widget(
+        Paragraph::new(findings)
+            .block(Block::default().title(audit_title).borders(Borders::ALL))
+            .wrap(Wrap { trim: false }),
+        rows[1],
+    );
 }
 
-fn draw_key_detail(f: &mut Frame, app: &App, area: Rect) {
+fn draw_key_detail(f: &mut Frame, app: &mut App, area: Rect) {
     if let Some(ref key) = app.selected_key {
-        let text = format!(
+        let mut text = format!(
             "Name: {}\n\
              Type: {}\n\
              Status: {}\n\
@@ -117,28 +755,194 @@ fn draw_key_detail(f: &mut Frame, app: &App, area: Rect) {
              Fingerprint: {}\n\
              Comment: {}\n\
              Created: {}\n\
-             Modified: {}",
+             Modified: {}\n\
+             Used by hosts: {}\n\
+             Used by repos: {}",
             key.name,
             key.key_type,
             key.status,
             key.path.display(),
             key.public_path.display(),
-            key.fingerprint.as_deref().unwrap_or("N/A"),
+            app.selected_key_fingerprint.as_deref().unwrap_or("N/A"),
             key.comment.as_deref().unwrap_or("N/A"),
             key.created_at
-                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                .map(|d| app.date_format.full(d))
                 .unwrap_or_else(|| "Unknown".to_string()),
             key.modified_at
-                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                .map(|d| app.date_format.full(d))
                 .unwrap_or_else(|| "Unknown".to_string()),
+            if app.selected_key_hosts.is_empty() {
+                "none".to_string()
+            } else {
+                app.selected_key_hosts.join(", ")
+            },
+            if app.selected_key_repos.is_empty() {
+                "none found".to_string()
+            } else {
+                app.selected_key_repos.join(", ")
+            },
         );
 
-        let paragraph = Paragraph::new(text)
-            .block(Block::default().title("Key Details").borders(Borders::ALL))
-            .wrap(ratatui::widgets::Wrap { trim: true });
+        let now = chrono::Local::now();
+        if let Some(expiry) = app.expiry.check(key, now) {
+            text.push_str(&format!(
+                "\n{}: {}, max age {}",
+                if matches!(expiry, Expiry::Expired(_)) {
+                    "Expired"
+                } else {
+                    "Expires"
+                },
+                app.date_format.full(expiry.expires_at()),
+                app.expiry.describe_max_age(key).unwrap_or_default()
+            ));
+        }
 
-        f.render_widget(paragraph, area);
+        if let Some(ref provenance) = app.selected_key_provenance {
+            text.push_str(&format!("\nOrigin: {}", provenance.describe()));
+        }
+
+        if key.status == KeyStatus::MissingPublic {
+            text.push_str("\n\nThe .pub file is missing (g: regenerate it from the private key)");
+        }
+
+        if !key.artifacts.is_empty() {
+            let names: Vec<String> = key
+                .artifacts
+                .iter()
+                .map(|p| {
+                    p.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            text.push_str(&format!("\nBackups: {}", names.join(", ")));
+        }
+
+        if !app.selected_key_deployments.is_empty() {
+            text.push_str("\n\nDeployed to (v: check authorized_keys):");
+            let checks = app
+                .remote_checks
+                .lock()
+                .map(|checks| checks.clone())
+                .unwrap_or_default();
+            for (deployment, status) in app.selected_key_deployments.iter().zip(checks) {
+                text.push_str(&format!(
+                    "\n- {}: {} (deployed {})",
+                    deployment.describe(),
+                    status,
+                    deployment.deployed_at.format("%Y-%m-%d")
+                ));
+            }
+        }
+
+        if let Some(ref cert) = app.selected_key_cert {
+            text.push_str(&format!(
+                "\n\nCertificate: {} ({})\n\
+                 Key ID: {}\n\
+                 Serial: {}\n\
+                 Principals: {}\n\
+                 Valid: {} ({})\n\
+                 CA: {}",
+                cert.path.display(),
+                cert.cert_type,
+                cert.key_id,
+                cert.serial,
+                cert.principals_display(),
+                cert.window(),
+                cert.validity(),
+                cert.ca_fingerprint,
+            ));
+            if let Some(warning) = cert.warning() {
+                text.push_str(&format!("\nWarning: {}", warning));
+            }
+        }
+
+        if let Some(ref line) = app.selected_key_authorized_line {
+            let copy = app
+                .keymap
+                .key(Action::CopyAuthorizedLine)
+                .map(|key| format!(" ({}: copy)", key))
+                .unwrap_or_default();
+            text.push_str(&format!("\n\nauthorized_keys line{}:\n{}", copy, line));
+        }
+
+        let block = Block::default().title("Key Details").borders(Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        // The randomart sits beside the details when there is room, and
+        // below them otherwise
+        let mut details = inner;
+        if let Some(ref art) = app.selected_key_randomart {
+            if inner.width >= 60 {
+                let chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Min(0),
+                        Constraint::Length(randomart::LINE_WIDTH as u16 + 1),
+                    ])
+                    .spacing(1)
+                    .split(inner);
+                details = chunks[0];
+                f.render_widget(Paragraph::new(art.as_str()), chunks[1]);
+            } else {
+                text.push_str(&format!("\n\n{}", art));
+            }
+        }
+
+        // Wrapped here rather than by the paragraph so the scroll can stop
+        // at the last line
+        let lines = wrap_text(&text, details.width as usize);
+        app.detail_height = details.height;
+        let max_scroll = lines.len().saturating_sub(details.height as usize) as u16;
+        app.detail_scroll = app.detail_scroll.min(max_scroll);
+
+        let paragraph = Paragraph::new(lines.join("\n")).scroll((app.detail_scroll, 0));
+        f.render_widget(paragraph, details);
+
+        if max_scroll > 0 {
+            let mut state = ScrollbarState::new(max_scroll as usize)
+                .position(app.detail_scroll as usize)
+                .viewport_content_length(details.height as usize);
+            f.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight),
+                area.inner(Margin {
+                    vertical: 1,
+                    horizontal: 0,
+                }),
+                &mut state,
+            );
+        }
+    }
+}
+
+/// Word-wrap `text` to `width` columns, breaking words longer than a line
+/// (base64 keys and paths) wherever they run out of room
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for (i, word) in paragraph.split(' ').enumerate() {
+            let mut word: Vec<char> = word.chars().collect();
+            let len = line.chars().count();
+            if len > 0 && len + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+            } else if i > 0 {
+                // Runs of spaces, indentation included, are kept
+                line.push(' ');
+            }
+            while line.chars().count() + word.len() > width {
+                let room = width - line.chars().count();
+                line.extend(word.drain(..room));
+                lines.push(std::mem::take(&mut line));
+            }
+            line.extend(word);
+        }
+        lines.push(line);
     }
+    lines
 }
 
 fn draw_create_wizard(f: &mut Frame, app: &App, area: Rect) {
@@ -149,56 +953,57 @@ fn draw_create_wizard(f: &mut Frame, app: &App, area: Rect) {
         None => return,
     };
 
-    let (title, content) = match wizard.step {
-        WizardStep::SelectType => (
-            "Create New Key - Step 1/5",
-            "Select key type:\n\n\
+    let content = match wizard.step {
+        WizardStep::SelectType => "Select key type:\n\n\
              [1] ED25519 (Recommended - modern, fast, secure)\n\
-             [2] RSA (4096 bits - for legacy compatibility)\n\n\
-             Press 1 or 2 to select, ESC to cancel"
-                .to_string(),
-        ),
-        WizardStep::EnterFilename => (
-            "Create New Key - Step 2/5",
-            format!(
-                "Enter filename for the key:\n\n\
+             [2] RSA (for legacy compatibility)\n\
+             [3] ECDSA (NIST curves, where ED25519 isn't accepted)\n\n\
+             Press 1, 2 or 3 to select, ESC to cancel"
+            .to_string(),
+        WizardStep::ChooseSize => {
+            let mut content = format!("Select {} key size:\n\n", wizard.options.key_type);
+            for (i, &bits) in wizard.size_choices().iter().enumerate() {
+                let marker = if wizard.options.bits == Some(bits) {
+                    " (selected)"
+                } else {
+                    ""
+                };
+                content.push_str(&format!(
+                    "[{}] {}{}\n",
+                    i + 1,
+                    wizard.size_label(bits),
+                    marker
+                ));
+            }
+            content.push_str(
+                "\nPress a number to select, Enter keeps the selected one, ESC to go back",
+            );
+            content
+        }
+        WizardStep::EnterFilename => format!(
+            "Enter filename for the key:\n\n\
                  > {}\n\n\
                  Press Enter to continue, ESC to go back",
-                app.wizard_input
-            ),
+            app.wizard_input
         ),
-        WizardStep::EnterComment => (
-            "Create New Key - Step 3/5",
-            format!(
-                "Enter comment (or leave empty for default):\n\n\
+        WizardStep::EnterComment => format!(
+            "Enter comment (or leave empty for default):\n\n\
                  > {}\n\n\
                  Default: {}\n\
                  Press Enter to continue, ESC to go back",
-                app.wizard_input, wizard.options.comment
-            ),
-        ),
-        WizardStep::EnterPassphrase => (
-            "Create New Key - Step 4/5",
-            format!(
-                "Enter passphrase (or leave empty for no passphrase):\n\n\
-                 > {}\n\n\
-                 Press Enter to continue, ESC to go back",
-                "*".repeat(app.wizard_input.len())
-            ),
+            app.wizard_input, wizard.temp_comment
         ),
-        WizardStep::Confirm => (
-            "Create New Key - Step 5/5",
-            format!(
-                "Please confirm:\n\n\
+        WizardStep::EnterPassphrase => return draw_wizard_passphrase(f, wizard, area),
+        WizardStep::Confirm => format!(
+            "Please confirm:\n\n\
                  {}\n\n\
                  Press Enter to create, ESC to go back",
-                wizard.get_summary()
-            ),
+            wizard.get_summary()
         ),
     };
 
     let block = Block::default()
-        .title(title)
+        .title(wizard_title(wizard))
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Green));
 
@@ -206,36 +1011,231 @@ fn draw_create_wizard(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
-fn draw_export_dialog(f: &mut Frame, app: &App, area: Rect) {
-    let (title, prompt, value) = match app.dialog_state {
-        DialogState::EnterPath => (
-            "Export Keys - Path",
-            "Enter export path:",
-            app.export_path.clone(),
-        ),
-        DialogState::EnterPassphrase => (
-            "Export Keys - Passphrase",
-            "Enter encryption passphrase:",
-            "*".repeat(app.dialog_passphrase.len()),
-        ),
-        DialogState::Confirm => (
-            "Export Keys - Confirm",
-            "Press Enter to export or ESC to cancel",
-            format!("Path: {} | Keys: {}", app.export_path, app.keys.len()),
-        ),
-    };
+fn wizard_title(wizard: &CreateWizard) -> String {
+    let (step, steps) = wizard.step_number();
+    format!("Create New Key - Step {}/{}", step, steps)
+}
 
+/// The passphrase and its confirmation as two masked fields, the cursor in
+/// the one being typed into
+fn draw_wizard_passphrase(f: &mut Frame, wizard: &CreateWizard, area: Rect) {
     let block = Block::default()
-        .title(title)
+        .title(wizard_title(wizard))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Blue));
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
 
-    let text = format!("{}\n\n> {}", prompt, value);
-    let paragraph = Paragraph::new(text).block(block);
-    f.render_widget(paragraph, area);
-}
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+
+    f.render_widget(
+        Paragraph::new("Enter a passphrase and confirm it (or leave both empty for none):"),
+        rows[0],
+    );
+    let fields = [&wizard.passphrase, &wizard.confirm_passphrase];
+    for (field, &row) in fields.iter().zip(&rows[1..3]) {
+        let row = Rect {
+            width: row.width.min(60),
+            ..row
+        };
+        f.render_widget(field.to_paragraph(), row);
+        if field.is_active {
+            f.set_cursor_position((row.x + 1 + field.cursor_column(), row.y + 1));
+        }
+    }
+
+    let mut hint = vec![Line::from(
+        "Tab switches fields, Enter continues, ESC goes back, Ctrl+C cancels",
+    )];
+    if let Some(ref error) = wizard.error_message {
+        hint.insert(
+            0,
+            Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))),
+        );
+    }
+    f.render_widget(Paragraph::new(hint), rows[3]);
+}
+
+/// One step of the migration wizard: its question, and the text field when
+/// it has one
+fn draw_migrate_wizard(f: &mut Frame, app: &App, area: Rect) {
+    let Some(ref wizard) = app.migrate else {
+        return;
+    };
+    let (step, steps) = wizard.step_number();
+    let block = Block::default()
+        .title(format!(
+            "Migrate from Another Computer - Step {}/{}",
+            step, steps
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+    f.render_widget(Paragraph::new(wizard.get_step_description()), rows[0]);
+
+    let field = match wizard.step {
+        MigrateStep::EnterBackup => Some(&wizard.backup),
+        MigrateStep::EnterPassphrase => Some(&wizard.passphrase),
+        MigrateStep::EnterGithubUser => Some(&wizard.github_user),
+        MigrateStep::ChooseAgent | MigrateStep::Confirm => None,
+    };
+    let mut lines = Vec::new();
+    match field {
+        Some(field) => {
+            let row = Rect {
+                width: rows[1].width.min(70),
+                ..rows[1]
+            };
+            f.render_widget(field.to_paragraph(), row);
+            f.set_cursor_position((row.x + 1 + field.cursor_column(), row.y + 1));
+        }
+        None if wizard.step == MigrateStep::ChooseAgent => lines.push(Line::from(
+            "Encrypted keys are left out; add them with '+' once their passphrase is at hand.",
+        )),
+        None => lines.extend(
+            wizard
+                .get_summary()
+                .lines()
+                .map(|l| Line::from(l.to_string())),
+        ),
+    }
+    if let Some(ref error) = wizard.error_message {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    // Without a field the text takes its place
+    let below = if field.is_some() {
+        rows[2]
+    } else {
+        rows[1].union(rows[2])
+    };
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), below);
+}
+
+fn draw_export_dialog(f: &mut Frame, app: &App, area: Rect) {
+    if app.dialog_state == DialogState::SelectKeys {
+        return draw_export_selection(f, app, area);
+    }
+
+    let (title, prompt, value) = match app.dialog_state {
+        DialogState::EnterPath => (
+            "Export Keys - Path",
+            "Enter export path:",
+            app.export_path.clone(),
+        ),
+        DialogState::EnterPassphrase => (
+            "Export Keys - Passphrase",
+            "Enter encryption passphrase:",
+            "*".repeat(app.dialog_passphrase.len()),
+        ),
+        DialogState::Confirm
+        | DialogState::SelectKeys
+        | DialogState::ChooseStrategy
+        | DialogState::EnterLifetime => (
+            "Export Keys - Confirm",
+            "Press Enter to export or ESC to cancel",
+            format!(
+                "Path: {} | Keys: {}{}",
+                app.export_path,
+                app.checked_export_keys().len(),
+                if app.export_private {
+                    ""
+                } else {
+                    " (public keys only)"
+                }
+            ),
+        ),
+    };
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Blue));
+
+    let text = format!("{}\n\n> {}", prompt, value);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+/// Checklist of the keys to export; only checked ones go into the backup
+fn draw_export_selection(f: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .keys
+        .iter()
+        .zip(&app.export_checked)
+        .map(|(key, &checked)| {
+            ListItem::new(format!(
+                " [{}] {} - {} [{}]",
+                if checked { "x" } else { " " },
+                key.name,
+                key.key_type,
+                key.comment.as_deref().unwrap_or("no comment"),
+            ))
+        })
+        .collect();
+
+    let title = format!(
+        "Export Keys - {} of {} selected, {}",
+        app.export_checked
+            .iter()
+            .filter(|&&checked| checked)
+            .count(),
+        app.keys.len(),
+        if app.export_private {
+            "private keys included"
+        } else {
+            "public keys only"
+        }
+    );
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_symbol("> ");
+
+    let mut state = ratatui::widgets::ListState::default();
+    if !app.keys.is_empty() {
+        state.select(Some(app.export_index));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
 
 fn draw_import_dialog(f: &mut Frame, app: &App, area: Rect) {
+    match app.dialog_state {
+        DialogState::SelectKeys => return draw_import_selection(f, app, area),
+        DialogState::ChooseStrategy => return draw_import_strategy(f, app, area),
+        _ => {}
+    }
+
     let (title, prompt, value) = match app.dialog_state {
         DialogState::EnterPath => (
             "Import Keys - Path",
@@ -247,7 +1247,10 @@ fn draw_import_dialog(f: &mut Frame, app: &App, area: Rect) {
             "Enter decryption passphrase:",
             "*".repeat(app.dialog_passphrase.len()),
         ),
-        DialogState::Confirm => (
+        DialogState::Confirm
+        | DialogState::SelectKeys
+        | DialogState::ChooseStrategy
+        | DialogState::EnterLifetime => (
             "Import Keys - Confirm",
             "Press Enter to import or ESC to cancel",
             format!("Path: {}", app.import_path),
@@ -264,18 +1267,275 @@ fn draw_import_dialog(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
-fn draw_delete_confirm(f: &mut Frame, app: &App, area: Rect) {
+/// Skip, overwrite or rename the checked keys that already exist
+fn draw_import_strategy(f: &mut Frame, app: &App, area: Rect) {
+    let conflicts = app.import_conflicts();
+    let mut lines = vec![
+        Line::from(format!(
+            "{} of the keys to import already exist: {}",
+            conflicts.len(),
+            conflicts.join(", ")
+        )),
+        Line::from(""),
+        Line::from("What should happen to them?"),
+        Line::from(""),
+    ];
+    for strategy in MergeStrategy::ALL {
+        let selected = strategy == app.import_strategy;
+        let style = if selected {
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::styled(
+            format!(
+                "{} {}",
+                if selected { ">" } else { " " },
+                strategy.describe()
+            ),
+            style,
+        ));
+    }
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title("Import Keys - Existing Keys")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(paragraph, area);
+}
+
+/// Checklist of the backup's keys; only checked ones are imported
+fn draw_import_selection(f: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .import_entries
+        .iter()
+        .zip(&app.import_checked)
+        .map(|(entry, &checked)| {
+            let exists = app.keys.iter().any(|key| key.name == entry.name);
+            let content = format!(
+                " [{}] {} - {} [{}]{}",
+                if checked { "x" } else { " " },
+                entry.name,
+                entry.key_type,
+                entry.comment.as_deref().unwrap_or("no comment"),
+                if exists { " (exists)" } else { "" }
+            );
+            let style = if exists {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            ListItem::new(content).style(style)
+        })
+        .collect();
+
+    let title = format!(
+        "Import Keys - {} of {} selected",
+        app.import_checked
+            .iter()
+            .filter(|&&checked| checked)
+            .count(),
+        app.import_entries.len()
+    );
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_symbol("> ");
+
+    let mut state = ratatui::widgets::ListState::default();
+    if !app.import_entries.is_empty() {
+        state.select(Some(app.import_index));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_change_passphrase(f: &mut Frame, app: &App, area: Rect) {
+    let name = app
+        .selected_key
+        .as_ref()
+        .map(|k| k.name.as_str())
+        .unwrap_or("selected key");
+
+    let (prompt, value) = match app.passphrase_step {
+        PassphraseStep::EnterOld => ("Enter current passphrase:", &app.old_passphrase),
+        PassphraseStep::EnterNew => (
+            "Enter new passphrase (or leave empty to remove it):",
+            &app.new_passphrase,
+        ),
+        PassphraseStep::ConfirmNew => ("Confirm new passphrase:", &app.confirm_new_passphrase),
+    };
+
+    let block = Block::default()
+        .title(format!("Change Passphrase - {}", name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+
+    let text = format!("{}\n\n> {}", prompt, "*".repeat(value.chars().count()));
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_edit_comment(f: &mut Frame, app: &App, area: Rect) {
+    let name = app
+        .selected_key
+        .as_ref()
+        .map(|k| k.name.as_str())
+        .unwrap_or("selected key");
+
+    let block = Block::default()
+        .title(format!("Edit Comment - {}", name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Green));
+
+    let text = format!(
+        "Enter new comment (leave empty to remove it):\n\n> {}",
+        app.comment_input
+    );
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_rename_dialog(f: &mut Frame, app: &App, area: Rect) {
     let name = app
         .get_selected_key()
         .map(|k| k.name.as_str())
         .unwrap_or("selected key");
 
+    let block = Block::default()
+        .title(format!("Rename Key - {}", name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Blue));
+
     let text = format!(
-        "Are you sure you want to delete '{}'?\n\n\
-         This action cannot be undone!\n\n\
+        "Enter new name (IdentityFile lines in ssh config are updated too):\n\n> {}",
+        app.rename_input
+    );
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_inspect_file(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let Some(ref inspection) = app.inspection else {
+        let text = format!(
+            "Enter the path of a public key, certificate, authorized_keys or known_hosts file:\n\n> {}",
+            app.inspect_input
+        );
+        let paragraph = Paragraph::new(text).block(block.title("Inspect File"));
+        f.render_widget(paragraph, area);
+        return;
+    };
+
+    let lines = inspection_lines(inspection);
+    // Stop scrolling once the last line is in view
+    let visible = area.height.saturating_sub(2) as usize;
+    let max_scroll = lines.len().saturating_sub(visible) as u16;
+    app.inspect_scroll = app.inspect_scroll.min(max_scroll);
+
+    let title = format!(
+        "Inspect - {} ({})",
+        inspection.path.display(),
+        inspection.summary()
+    );
+    let paragraph = Paragraph::new(lines.join("\n"))
+        .block(block.title(title))
+        .scroll((app.inspect_scroll, 0));
+    f.render_widget(paragraph, area);
+}
+
+/// One block of fields per key, in file order
+fn inspection_lines(inspection: &Inspection) -> Vec<String> {
+    let mut lines = Vec::new();
+    for entry in &inspection.entries {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("Line {}: {}", entry.line, entry.kind));
+        lines.push(match entry.bits {
+            Some(bits) => format!("  Type: {} ({} bits)", entry.algorithm, bits),
+            None => format!("  Type: {}", entry.algorithm),
+        });
+        lines.push(format!("  Fingerprint: {}", entry.fingerprint));
+        lines.push(format!(
+            "  Comment: {}",
+            entry.comment.as_deref().unwrap_or("none")
+        ));
+        if let Some(ref marker) = entry.marker {
+            lines.push(format!("  Marker: {}", marker));
+        }
+        if !entry.hosts.is_empty() {
+            lines.push(format!("  Hosts: {}", entry.hosts.join(", ")));
+        }
+        if !entry.options.is_empty() {
+            lines.push(format!("  Options: {}", entry.options.join(", ")));
+        }
+        if entry.kind == EntryKind::PrivateKey {
+            lines.push(format!(
+                "  Encrypted: {}",
+                if entry.encrypted { "yes" } else { "NO" }
+            ));
+        }
+        if let Some(ref cert) = entry.cert {
+            lines.push(format!("  Certificate: {}", cert.cert_type));
+            lines.push(format!("  Key ID: {}", cert.key_id));
+            lines.push(format!("  Serial: {}", cert.serial));
+            lines.push(format!("  Principals: {}", cert.principals_display()));
+            lines.push(format!("  Valid: {} ({})", cert.window(), cert.validity()));
+            lines.push(format!("  CA: {}", cert.ca_fingerprint));
+        }
+    }
+
+    if !inspection.unrecognised.is_empty() {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        let numbers: Vec<String> = inspection
+            .unrecognised
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        lines.push(format!("Not recognised: line {}", numbers.join(", ")));
+    }
+    lines
+}
+
+fn draw_delete_confirm(f: &mut Frame, app: &App, area: Rect) {
+    let targets = app.target_keys();
+    let question = match targets.as_slice() {
+        [key] => format!("Are you sure you want to delete '{}'?", key.name),
+        keys => format!(
+            "Are you sure you want to delete {} marked keys?\n\n{}",
+            keys.len(),
+            keys.iter()
+                .map(|key| key.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let text = format!(
+        "{}\n\n\
+         They go to the trash; u undoes the delete.\n\n\
          [y] Yes, delete\n\
          [n] No, cancel",
-        name
+        question
     );
 
     let block = Block::default()
@@ -290,55 +1550,506 @@ fn draw_delete_confirm(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+fn draw_fix_permissions(f: &mut Frame, app: &App) {
+    let mut text = String::from("These files have unsafe permissions:\n\n");
+    for change in &app.permission_changes {
+        text.push_str(&format!("{}\n", change));
+    }
+    text.push_str("\n[y] Fix them now\n[n] Leave as is");
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Unsafe Permissions")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .alignment(Alignment::Center);
+
+    let area = centered_rect(70, 50, f.area());
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_locked(f: &mut Frame, app: &App) {
+    let prompt = if LockPassphrase::new(&app.config.export_dir).is_set() {
+        format!(
+            "Enter the lock passphrase to resume:\n\n{}",
+            "*".repeat(app.unlock_input.len())
+        )
+    } else {
+        "Press Enter to resume".to_string()
+    };
+    let text = match app.unlock_error {
+        Some(ref error) => format!("Session locked\n\n{}\n\n{}", prompt, error),
+        None => format!("Session locked\n\n{}", prompt),
+    };
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Locked")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .alignment(Alignment::Center);
+
+    let area = centered_rect(50, 30, f.area());
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
-    let help_text = match app.state {
+    let footer = Paragraph::new(footer_text(app))
+        .style(Style::default().fg(Color::Gray))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::TOP));
+
+    f.render_widget(footer, area);
+}
+
+/// The keys of the current view as `key: label` pairs separated by `|`; the
+/// help overlay lists the ones of dialogs from here too
+fn footer_text(app: &App) -> String {
+    let hints;
+    let text = match app.state {
         AppState::KeyList => {
-            "j/k: Navigate | Enter: Details | y: Copy Key | c: Copy Full | n: New | e: Export | i: Import | d: Delete | q: Quit"
+            hints = key_hints(
+                app,
+                &[
+                    (&[Action::Palette], "Actions"),
+                    (&[Action::Down, Action::Up], "Navigate"),
+                    (&[Action::Filter], "Filter"),
+                    (&[Action::Mark], "Mark"),
+                    (&[Action::Open], "Details"),
+                    (&[Action::CopyKey], "Copy Key"),
+                    (&[Action::CopyFull], "Copy Full"),
+                    (&[Action::CopyAuthorizedLine], "Copy authorized_keys Line"),
+                    (&[Action::AgentPanel], "Agent"),
+                    (&[Action::New], "New"),
+                    (&[Action::Rename], "Rename"),
+                    (&[Action::Export], "Export"),
+                    (&[Action::Import], "Import"),
+                    (&[Action::Delete], "Delete"),
+                    (&[Action::Undo], "Undo"),
+                    (&[Action::Close], "Quit"),
+                ],
+            );
+            &hints
+        }
+        AppState::KeyDetail => {
+            hints = key_hints(
+                app,
+                &[
+                    (&[Action::Back], "Back"),
+                    (
+                        &[Action::Down, Action::Up, Action::PageUp, Action::PageDown],
+                        "Scroll",
+                    ),
+                    (&[Action::CopyAuthorizedLine], "Copy authorized_keys Line"),
+                    (&[Action::EditComment], "Edit Comment"),
+                    (&[Action::ChangePassphrase], "Change Passphrase"),
+                    (&[Action::CheckDeployments], "Check Deployments"),
+                    (&[Action::RegeneratePublicKey], "Regenerate .pub"),
+                    (&[Action::Undo], "Undo"),
+                    (&[Action::Palette], "Actions"),
+                ],
+            );
+            &hints
+        }
+        AppState::HostList => {
+            hints = key_hints(
+                app,
+                &[
+                    (&[Action::Down, Action::Up], "Navigate"),
+                    (&[Action::Open], "Key Details"),
+                    (&[Action::SwitchTab], "Keys (on this host's key)"),
+                    (&[Action::Refresh], "Reload"),
+                    (&[Action::Back], "Back"),
+                    (&[Action::Close], "Quit"),
+                ],
+            );
+            &hints
+        }
+        AppState::Dashboard => {
+            hints = key_hints(
+                app,
+                &[
+                    (&[Action::Refresh], "Refresh"),
+                    (&[Action::Back, Action::Dashboard], "Back to Keys"),
+                ],
+            );
+            &hints
+        }
+        AppState::CreateWizard => "ESC: Back | Ctrl+C: Cancel | Enter: Continue",
+        AppState::ExportDialog if app.dialog_state == DialogState::SelectKeys => {
+            hints = with_movement(
+                app,
+                "Navigate",
+                "Space: Toggle | a: Toggle All | p: Private Keys On/Off | Enter: Continue | ESC: Cancel",
+            );
+            &hints
         }
-        AppState::KeyDetail => "ESC: Back | c: Edit Comment",
-        AppState::CreateWizard => "ESC: Cancel | Enter: Continue",
         AppState::ExportDialog => "Enter: Continue | ESC: Cancel",
+        AppState::ImportDialog if app.dialog_state == DialogState::SelectKeys => {
+            hints = with_movement(
+                app,
+                "Navigate",
+                "Space: Toggle | a: Toggle All | Enter: Import Checked | ESC: Cancel",
+            );
+            &hints
+        }
+        AppState::ImportDialog if app.dialog_state == DialogState::ChooseStrategy => {
+            hints = with_movement(
+                app,
+                "Choose",
+                "s/o/r: Skip/Overwrite/Rename | Enter: Import | ESC: Back",
+            );
+            &hints
+        }
         AppState::ImportDialog => "Enter: Continue | ESC: Cancel",
         AppState::DeleteConfirm => "y: Yes | n: No",
+        AppState::ChangePassphrase => "Enter: Continue | ESC: Cancel",
+        AppState::RenameDialog => "Enter: Rename | ESC: Cancel",
+        AppState::EditComment => "Enter: Save | ESC: Cancel",
+        AppState::CommandPalette => "Type to filter | ↑/↓: Select | Enter: Run | ESC: Close",
+        AppState::FindKey => "Type to search | ↑/↓: Select | Enter: Jump | ESC: Cancel",
+        AppState::FixPermissions => "y: Fix | n: Leave as is",
+        AppState::InspectFile if app.inspection.is_some() => {
+            hints = with_movement(app, "Scroll", "o: Inspect Another | ESC: Back");
+            &hints
+        }
+        AppState::InspectFile => "Enter: Inspect | ESC: Cancel",
+        AppState::FilterInput => {
+            "Type to filter by name, comment, type or fingerprint | ↑/↓: Navigate | Enter: Keep | ESC: Clear"
+        }
+        AppState::Locked => {
+            hints = format!("Enter: Unlock | {}: Quit", quit_keys(app));
+            &hints
+        }
+        AppState::AgentAdd => "Enter: Continue | ESC: Cancel",
+        AppState::MigrateWizard => match app.migrate.as_ref().map(|w| w.step) {
+            Some(MigrateStep::ChooseAgent) => "y: Yes | n: No | ESC: Back | Ctrl+C: Cancel",
+            Some(MigrateStep::Confirm) => "Enter: Migrate | ESC: Back | Ctrl+C: Cancel",
+            _ => "Enter: Continue | ESC: Back | Ctrl+C: Cancel",
+        },
+        AppState::Working => {
+            hints = if app.quit_after_job {
+                format!("Finishing... | {}: Quit now (abandons it)", quit_keys(app))
+            } else {
+                format!("Please wait | {}: Quit when done", quit_keys(app))
+            };
+            &hints
+        }
         AppState::MessageDialog => "Enter/ESC: OK",
         AppState::Quit => "",
     };
+    text.to_string()
+}
 
-    let footer = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::Gray))
-        .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::TOP));
+/// `key: label` pairs for the footer, with the first key of each action;
+/// hints whose actions have no key are left out
+fn key_hints(app: &App, hints: &[(&[Action], &str)]) -> String {
+    hints
+        .iter()
+        .filter_map(|(actions, label)| {
+            let keys: Vec<String> = actions.iter().filter_map(|&a| app.keymap.key(a)).collect();
+            (!keys.is_empty()).then(|| format!("{}: {}", keys.join("/"), label))
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
 
-    f.render_widget(footer, area);
+/// `rest` after the key list's up and down keys, which the checklists and
+/// scrolled results move with too
+fn with_movement(app: &App, label: &str, rest: &str) -> String {
+    let movement = key_hints(app, &[(&[Action::Down, Action::Up], label)]);
+    if movement.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{} | {}", movement, rest)
+    }
 }
 
-fn draw_help_popup(f: &mut Frame) {
-    let text = "SSH Key Manager Help\n\n\
-                  Global Shortcuts:\n\
-                  Ctrl+H - Toggle this help\n\
-                  Ctrl+Q - Quit application\n\n\
-                  Navigation:\n\
-                  j or ↓ - Move down\n\
-                  k or ↑ - Move up\n\
-                  Enter - Select/Confirm\n\
-                  ESC - Cancel/Back\n\n\
-                  Key List:\n\
-                  y - Copy public key (without comment)\n\
-                  c - Copy full public key (with comment)\n\
-                  n - Create new key\n\
-                  e - Export keys\n\
-                  i - Import keys\n\
-                  d - Delete selected key\n\
-                  r - Refresh list";
-
-    let paragraph = Paragraph::new(text).block(
-        Block::default()
-            .title("Help")
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan)),
-    );
+/// The configured quit keys; F10 quits whatever they are
+fn quit_keys(app: &App) -> String {
+    let mut keys = app.keymap.describe(Action::Quit);
+    if !keys.split('/').any(|key| key == "F10") {
+        if !keys.is_empty() {
+            keys.push('/');
+        }
+        keys.push_str("F10");
+    }
+    keys
+}
+
+/// Help for what is on screen: the actions of the key list or detail from the
+/// keymap, or a dialog's keys from its footer, then the global ones
+fn help_text(app: &App) -> String {
+    let mut sections: Vec<(&str, Vec<(String, String)>)> = Vec::new();
+
+    let view = match app.state {
+        AppState::KeyList => Some(("Key List", Context::List)),
+        AppState::KeyDetail => Some(("Key Detail", Context::Detail)),
+        _ => None,
+    };
+    match view {
+        Some((title, context)) => {
+            let keys = Action::in_context(context)
+                .filter(|&action| !app.keymap.keys(action).is_empty())
+                .map(|action| {
+                    (
+                        app.keymap.describe(action),
+                        action.description(context).to_string(),
+                    )
+                })
+                .collect();
+            sections.push((title, keys));
+            if context == Context::List {
+                let motions = VIM_MOTIONS
+                    .iter()
+                    .map(|(key, label)| (key.to_string(), label.to_string()))
+                    .collect();
+                sections.push(("Vim Motions", motions));
+            }
+        }
+        None => {
+            let keys = footer_text(app)
+                .split(" | ")
+                .filter_map(|hint| hint.split_once(": "))
+                .map(|(key, label)| (key.to_string(), label.to_string()))
+                .collect();
+            sections.push(("This Dialog", keys));
+        }
+    }
+
+    let global = Action::in_context(Context::Global)
+        .map(|action| {
+            let keys = match action {
+                Action::Quit => quit_keys(app),
+                _ => app.keymap.describe(action),
+            };
+            (keys, action.description(Context::Global).to_string())
+        })
+        .filter(|(keys, _)| !keys.is_empty())
+        .collect();
+    sections.push(("Global", global));
+
+    let mut text = String::from("SSH Key Manager Help");
+    for (title, keys) in sections {
+        let width = keys
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        text.push_str(&format!("\n\n{}:", title));
+        for (key, description) in keys {
+            let pad = width - key.chars().count();
+            text.push_str(&format!("\n  {}{}  {}", key, " ".repeat(pad), description));
+        }
+    }
+    text
+}
+
+fn draw_help_popup(f: &mut Frame, app: &mut App) {
+    let block = Block::default()
+        .title("Help")
+        .title_bottom(format!(
+            " {} ",
+            key_hints(
+                app,
+                &[
+                    (&[Action::Down, Action::Up], "Scroll"),
+                    (&[Action::Back], "Close"),
+                ],
+            )
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
 
     let area = centered_rect(60, 70, f.area());
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let lines = wrap_text(&help_text(app), inner.width as usize);
+    app.help_height = inner.height;
+    let max_scroll = lines.len().saturating_sub(inner.height as usize) as u16;
+    app.help_scroll = app.help_scroll.min(max_scroll);
+
+    let paragraph = Paragraph::new(lines.join("\n")).scroll((app.help_scroll, 0));
+    f.render_widget(paragraph, inner);
+
+    if max_scroll > 0 {
+        let mut state = ScrollbarState::new(max_scroll as usize)
+            .position(app.help_scroll as usize)
+            .viewport_content_length(inner.height as usize);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut state,
+        );
+    }
+}
+
+fn draw_command_palette(f: &mut Frame, app: &App) {
+    let Some(ref palette) = app.palette else {
+        return;
+    };
+
+    let area = centered_rect(60, 60, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title("Command Palette")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+
+    let input = Paragraph::new(format!("> {}", palette.query))
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::BOTTOM));
+    f.render_widget(input, chunks[0]);
+
+    let matches = palette.matches();
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|action| {
+            let text = match action.binding().and_then(|binding| app.keymap.key(binding)) {
+                Some(key) => format!("{:<40} {}", action.label(), key),
+                None => action.label().to_string(),
+            };
+            ListItem::new(text)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ")
+        .highlight_spacing(HighlightSpacing::Always);
+
+    let mut state = ratatui::widgets::ListState::default();
+    if !matches.is_empty() {
+        state.select(Some(palette.selected));
+    }
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn draw_key_finder(f: &mut Frame, app: &App) {
+    let Some(ref finder) = app.finder else {
+        return;
+    };
+
+    let area = centered_rect(60, 60, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title("Find Key")
+        .title_bottom(
+            Line::from(format!(" {}/{} ", finder.matches.len(), app.keys.len()))
+                .alignment(Alignment::Right),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+
+    let input = Paragraph::new(format!("> {}", finder.query))
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::BOTTOM));
+    f.render_widget(input, chunks[0]);
+
+    let items: Vec<ListItem> = finder
+        .matches
+        .iter()
+        .filter_map(|found| {
+            let key = app.keys.get(found.key)?;
+            let mut spans = highlight_matches(&key.name, &found.name_indices, Style::default());
+            spans.push(Span::styled(
+                format!("  {}", key.key_type),
+                Style::default().fg(Color::DarkGray),
+            ));
+            if let Some(ref comment) = key.comment {
+                spans.push(Span::raw("  "));
+                spans.extend(highlight_matches(
+                    comment,
+                    &found.comment_indices,
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            Some(ListItem::new(Line::from(spans)))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ")
+        .highlight_spacing(HighlightSpacing::Always);
+
+    let mut state = ratatui::widgets::ListState::default();
+    if !finder.matches.is_empty() {
+        state.select(Some(finder.selected));
+    }
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+/// `text` in `style`, with the chars at `indices` picked out in yellow
+fn highlight_matches(text: &str, indices: &[usize], style: Style) -> Vec<Span<'static>> {
+    let matched = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let style = if indices.contains(&i) { matched } else { style };
+            Span::styled(c.to_string(), style)
+        })
+        .collect()
+}
+
+fn draw_debug_log(f: &mut Frame, app: &App) {
+    let area = Rect {
+        x: f.area().x,
+        y: f.area().y + f.area().height / 2,
+        width: f.area().width,
+        height: f.area().height - f.area().height / 2,
+    };
+
+    // Show the most recent lines that fit inside the borders
+    let lines = app.log_buffer.lines();
+    let visible = area.height.saturating_sub(2) as usize;
+    let start = lines.len().saturating_sub(visible);
+    let text = lines[start..].join("\n");
+
+    let paragraph = Paragraph::new(text)
+        .style(Style::default().fg(Color::Gray))
+        .block(
+            Block::default()
+                .title("Debug Log (F12 to close)")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+
     f.render_widget(Clear, area);
     f.render_widget(paragraph, area);
 }
@@ -363,6 +2074,86 @@ fn draw_message(f: &mut Frame, msg: &str, msg_type: MessageType) {
     f.render_widget(paragraph, area);
 }
 
+/// A success or info message in the bottom right corner of `area`, over
+/// whatever is there; it goes away by itself
+fn draw_toast(f: &mut Frame, msg: &str, msg_type: MessageType, area: Rect) {
+    // Inside the view's border
+    let area = area.inner(Margin::new(1, 1));
+    let color = match msg_type {
+        MessageType::Success => Color::Green,
+        MessageType::Error => Color::Red,
+        MessageType::Info => Color::Blue,
+    };
+    let width = msg
+        .lines()
+        .map(|line| Line::from(line).width())
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+    let width = width.min(area.width);
+    let height = (msg.lines().count() as u16 + 2).min(area.height);
+    let toast = Rect {
+        x: area.right().saturating_sub(width),
+        y: area.bottom().saturating_sub(height),
+        width,
+        height,
+    };
+
+    let paragraph = Paragraph::new(msg).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(color))
+            .padding(Padding::horizontal(1)),
+    );
+    f.render_widget(Clear, toast);
+    f.render_widget(paragraph, toast);
+}
+
+/// Spinner with the time taken so far, plus a progress bar once the job
+/// reports one
+fn draw_job(f: &mut Frame, job: &Job, quitting: bool) {
+    let block = Block::default()
+        .title(job.action.as_str())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    // Room for the status, the detail line and the progress bar
+    let area = centered_rect(50, 20, f.area());
+    let height = area.height.max(5).min(f.area().height);
+    let area = Rect {
+        y: f.area().height.saturating_sub(height) / 2,
+        height,
+        ..area
+    };
+    f.render_widget(Clear, area);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner);
+
+    let mut text = format!(
+        "{} Working... {}s",
+        job.spinner(),
+        job.started.elapsed().as_secs()
+    );
+    if quitting {
+        text.push_str(" - quitting when done");
+    }
+    if let Some(ref progress) = job.progress {
+        text.push_str(&format!("\n{}", progress.detail));
+        let ratio = progress.done as f64 / progress.total.max(1) as f64;
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(Color::Yellow))
+            .ratio(ratio.min(1.0))
+            .label(format!("{}/{}", progress.done, progress.total));
+        f.render_widget(gauge, chunks[1]);
+    }
+    let paragraph = Paragraph::new(text).alignment(Alignment::Center);
+    f.render_widget(paragraph, chunks[0]);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
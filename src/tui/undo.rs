@@ -0,0 +1,286 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::ssh::SshKey;
+use crate::ssh::config::rewrite_identity_files;
+use crate::ssh::trash::Trash;
+
+/// Edits kept per session; older ones fall off the bottom
+const UNDO_LIMIT: usize = 50;
+
+/// An edit or deletion made from the TUI that can be reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Comment {
+        key_path: PathBuf,
+        old: String,
+        new: String,
+    },
+    /// Key rename, including the IdentityFile lines rewritten in `config_path`
+    /// if the rename rewrote them
+    Rename {
+        old_path: PathBuf,
+        new_path: PathBuf,
+        config_path: Option<PathBuf>,
+    },
+    /// Keys moved to the trash under `data_dir`, by path and trash id. Redoing
+    /// it trashes them again under new ids.
+    Delete {
+        data_dir: PathBuf,
+        keys: Vec<(PathBuf, String)>,
+        shred: bool,
+    },
+}
+
+impl Edit {
+    /// Redo (`forward`) or undo the edit. Returns the name of the affected key afterwards.
+    fn apply(&mut self, forward: bool) -> Result<String> {
+        match self {
+            Edit::Comment { key_path, old, new } => {
+                let mut key = SshKey::from_path(key_path)?;
+                key.update_comment(if forward { new } else { old })?;
+                Ok(key.name)
+            }
+            Edit::Rename {
+                old_path,
+                new_path,
+                config_path,
+            } => {
+                let (from, to) = if forward {
+                    (&*old_path, &*new_path)
+                } else {
+                    (&*new_path, &*old_path)
+                };
+                let renamed = SshKey::from_path(from)?.rename(&file_name(to))?;
+                if let Some(config_path) = config_path {
+                    rewrite_identity_files(config_path, from, to)?;
+                }
+                Ok(renamed.name)
+            }
+            Edit::Delete {
+                data_dir,
+                keys,
+                shred,
+            } => {
+                let trash = Trash::new(data_dir).shredding(*shred);
+                let mut names = Vec::new();
+                for (key_path, trash_id) in keys.iter_mut() {
+                    let entry = if forward {
+                        let entry = trash.put(&SshKey::from_path(&*key_path)?)?;
+                        *trash_id = entry.id.clone();
+                        entry
+                    } else {
+                        trash.restore(trash_id)?
+                    };
+                    names.push(entry.key_name);
+                }
+                Ok(names.into_iter().next().unwrap_or_default())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Comment { key_path, .. } => {
+                write!(f, "comment change on '{}'", file_name(key_path))
+            }
+            Edit::Rename {
+                old_path, new_path, ..
+            } => write!(
+                f,
+                "rename '{}' -> '{}'",
+                file_name(old_path),
+                file_name(new_path)
+            ),
+            Edit::Delete { keys, .. } => match keys.as_slice() {
+                [(key_path, _)] => write!(f, "delete of '{}'", file_name(key_path)),
+                keys => write!(f, "delete of {} keys", keys.len()),
+            },
+        }
+    }
+}
+
+/// Result of an undo or redo: what happened and which key to select
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub description: String,
+    pub key_name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UndoStack {
+    done: Vec<Edit>,
+    undone: Vec<Edit>,
+}
+
+impl UndoStack {
+    /// Remember an edit that has just been applied; clears the redo history
+    pub fn record(&mut self, edit: Edit) {
+        if self.done.len() == UNDO_LIMIT {
+            self.done.remove(0);
+        }
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    /// `None` when there is nothing to undo. An edit that can no longer be
+    /// reverted (e.g. the file was changed outside skm) is dropped.
+    pub fn undo(&mut self) -> Option<Result<Applied>> {
+        let mut edit = self.done.pop()?;
+        Some(edit.apply(false).map(|key_name| {
+            let applied = Applied {
+                description: format!("Undid {}", edit),
+                key_name,
+            };
+            self.undone.push(edit);
+            applied
+        }))
+    }
+
+    pub fn redo(&mut self) -> Option<Result<Applied>> {
+        let mut edit = self.undone.pop()?;
+        Some(edit.apply(true).map(|key_name| {
+            let applied = Applied {
+                description: format!("Redid {}", edit),
+                key_name,
+            };
+            self.done.push(edit);
+            applied
+        }))
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_key(dir: &Path, name: &str, comment: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, "private").unwrap();
+        std::fs::write(
+            path.with_extension("pub"),
+            format!("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ2X {}", comment),
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_undo_redo_comment() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_path = write_key(temp_dir.path(), "work", "old@host");
+
+        let mut key = SshKey::from_path(&key_path).unwrap();
+        key.update_comment("new@host").unwrap();
+
+        let mut stack = UndoStack::default();
+        stack.record(Edit::Comment {
+            key_path: key_path.clone(),
+            old: "old@host".to_string(),
+            new: "new@host".to_string(),
+        });
+
+        let applied = stack.undo().unwrap().unwrap();
+        assert_eq!(applied.key_name, "work");
+        let comment = || SshKey::from_path(&key_path).unwrap().comment;
+        assert_eq!(comment().as_deref(), Some("old@host"));
+
+        stack.redo().unwrap().unwrap();
+        assert_eq!(comment().as_deref(), Some("new@host"));
+
+        assert!(stack.redo().is_none());
+    }
+
+    #[test]
+    fn test_undo_rename_restores_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = write_key(temp_dir.path(), "old_key", "me@host");
+        let config_path = temp_dir.path().join("config");
+
+        let renamed = SshKey::from_path(&old_path)
+            .unwrap()
+            .rename("new_key")
+            .unwrap();
+        std::fs::write(
+            &config_path,
+            format!("Host x\n    IdentityFile {}\n", renamed.path.display()),
+        )
+        .unwrap();
+
+        let mut stack = UndoStack::default();
+        stack.record(Edit::Rename {
+            old_path: old_path.clone(),
+            new_path: renamed.path.clone(),
+            config_path: Some(config_path.clone()),
+        });
+
+        let applied = stack.undo().unwrap().unwrap();
+        assert_eq!(applied.key_name, "old_key");
+        assert!(old_path.exists() && !renamed.path.exists());
+        let config = std::fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains(&old_path.display().to_string()));
+    }
+
+    #[test]
+    fn test_undo_redo_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_path = write_key(temp_dir.path(), "work", "me@host");
+        let other_path = write_key(temp_dir.path(), "home", "me@host");
+        let data_dir = temp_dir.path().join("data");
+
+        let trash = Trash::new(&data_dir);
+        let keys = [&key_path, &other_path]
+            .into_iter()
+            .map(|path| {
+                let entry = trash.put(&SshKey::from_path(path).unwrap()).unwrap();
+                (path.clone(), entry.id)
+            })
+            .collect();
+        let mut stack = UndoStack::default();
+        stack.record(Edit::Delete {
+            data_dir: data_dir.clone(),
+            keys,
+            shred: false,
+        });
+
+        let applied = stack.undo().unwrap().unwrap();
+        assert_eq!(applied.description, "Undid delete of 2 keys");
+        assert_eq!(applied.key_name, "work");
+        assert!(key_path.exists() && other_path.exists());
+
+        stack.redo().unwrap().unwrap();
+        assert!(!key_path.exists() && !other_path.exists());
+        // The redone delete undoes from its new place in the trash
+        stack.undo().unwrap().unwrap();
+        assert!(key_path.exists() && key_path.with_extension("pub").exists());
+        assert!(trash.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut stack = UndoStack::default();
+        let edit = Edit::Comment {
+            key_path: PathBuf::from("/nonexistent/key"),
+            old: String::new(),
+            new: String::new(),
+        };
+
+        stack.record(edit.clone());
+        // Reverting fails because the key is gone; the edit is dropped
+        assert!(stack.undo().unwrap().is_err());
+        assert!(stack.undo().is_none());
+
+        stack.record(edit);
+        assert!(stack.redo().is_none());
+    }
+}
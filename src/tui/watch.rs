@@ -0,0 +1,126 @@
+//! Notices keys added, removed or changed in the ssh dir behind skm's back,
+//! e.g. by ssh-keygen, so the key list can refresh without `r`
+//!
+//! notify reports on its own thread over a channel that the event loop drains
+//! once per frame. A change is only reported once the directory has been
+//! quiet for a moment, so a key written in two halves is rescanned once.
+
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::{Result, SkmError};
+use crate::ssh::scan::KeyScanner;
+
+/// How long the directory has to be quiet before a change is reported
+const SETTLE: Duration = Duration::from_millis(300);
+
+#[derive(Debug)]
+pub struct DirWatcher {
+    _watcher: RecommendedWatcher, // stops watching when dropped
+    receiver: Receiver<notify::Result<Event>>,
+    last_change: Option<Instant>, // latest relevant event not yet reported
+}
+
+impl DirWatcher {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The app may have quit and dropped the receiver
+            let _ = sender.send(event);
+        })
+        .map_err(|e| SkmError::Tui(format!("can't watch {}: {}", dir.display(), e)))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| SkmError::Tui(format!("can't watch {}: {}", dir.display(), e)))?;
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+            last_change: None,
+        })
+    }
+
+    /// Take the pending events; true once something that may be a key has
+    /// changed and the directory has settled since
+    pub fn poll(&mut self) -> bool {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Ok(event) if is_relevant(&event) => self.last_change = Some(Instant::now()),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Watching the ssh dir: {}", e),
+            }
+        }
+
+        match self.last_change {
+            Some(at) if at.elapsed() >= SETTLE => {
+                self.last_change = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Files being created, written, renamed or removed, other than the ones
+/// the scan never lists (known_hosts, which ssh writes on every new host,
+/// config and the like)
+fn is_relevant(event: &Event) -> bool {
+    if matches!(event.kind, EventKind::Access(_) | EventKind::Other) {
+        return false;
+    }
+    event.paths.iter().any(|path| !is_ignored(path))
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(KeyScanner::is_non_key_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn wait(watcher: &mut DirWatcher) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if watcher.poll() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_reports_new_key() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = DirWatcher::new(dir.path()).unwrap();
+        assert!(!watcher.poll());
+
+        std::fs::write(dir.path().join("id_new"), "test").unwrap();
+        std::fs::write(dir.path().join("id_new.pub"), "test").unwrap();
+        assert!(wait(&mut watcher));
+        // Both halves settle into one report
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn test_is_relevant() {
+        let event = |kind, name: &str| Event::new(kind).add_path(PathBuf::from("/x").join(name));
+        let create = EventKind::Create(CreateKind::File);
+
+        assert!(is_relevant(&event(create, "id_ed25519")));
+        assert!(!is_relevant(&event(create, "known_hosts")));
+        assert!(!is_relevant(&event(
+            EventKind::Access(AccessKind::Any),
+            "id_ed25519"
+        )));
+    }
+}
@@ -0,0 +1,168 @@
+//! Long operations run on a worker thread, so the TUI keeps drawing (and
+//! shows a spinner) while a key is generated or a backup is encrypted
+//!
+//! The worker reports back over a channel that the event loop polls once per
+//! frame; nothing it touches is shared with the render thread.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::crypto::backup::{EntrySummary, ExportReport, ImportReport};
+use crate::error::{Result, SkmError};
+use crate::ssh::SshKey;
+use crate::tui::app::AppState;
+use crate::tui::components::migrate::MigrationReport;
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// What a finished job hands back to the app
+#[derive(Debug)]
+pub enum Outcome {
+    Generated(SshKey),
+    AddedToAgent {
+        name: String,
+        lifetime: Option<u32>,
+    },
+    Exported {
+        report: ExportReport,
+        path: PathBuf,
+    },
+    /// A backup decrypted for the import checklist
+    ImportListed(Vec<EntrySummary>),
+    Imported {
+        report: ImportReport,
+        path: PathBuf,
+    },
+    Migrated(Box<MigrationReport>),
+}
+
+/// How far along a job is, when it can tell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+enum Update {
+    Progress(Progress),
+    Finished(Result<Outcome>),
+}
+
+/// Handed to the work so it can report progress
+pub struct Reporter(Sender<Update>);
+
+impl Reporter {
+    pub fn progress(&self, done: usize, total: usize, detail: impl Into<String>) {
+        // The app may have quit and dropped the receiver; nothing to report to
+        let _ = self.0.send(Update::Progress(Progress {
+            done,
+            total,
+            detail: detail.into(),
+        }));
+    }
+}
+
+/// A job running on its own thread
+#[derive(Debug)]
+pub struct Job {
+    pub action: String,         // e.g. "Export", shown on the dialog and in errors
+    pub return_state: AppState, // where a failure is reported
+    pub started: Instant,
+    pub progress: Option<Progress>, // latest report from the worker
+    receiver: Receiver<Update>,
+}
+
+impl Job {
+    pub fn spawn<F>(action: impl Into<String>, return_state: AppState, work: F) -> Self
+    where
+        F: FnOnce(&Reporter) -> Result<Outcome> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let reporter = Reporter(sender);
+            let outcome = work(&reporter);
+            let _ = reporter.0.send(Update::Finished(outcome));
+        });
+
+        Self {
+            action: action.into(),
+            return_state,
+            started: Instant::now(),
+            progress: None,
+            receiver,
+        }
+    }
+
+    /// Take the worker's progress reports; `Some` once it has finished
+    pub fn poll(&mut self) -> Option<Result<Outcome>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Update::Progress(progress)) => self.progress = Some(progress),
+                Ok(Update::Finished(outcome)) => return Some(outcome),
+                Err(TryRecvError::Empty) => return None,
+                // The thread went away without finishing, i.e. it panicked
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(SkmError::Tui(
+                        "the worker thread stopped unexpectedly".to_string(),
+                    )));
+                }
+            }
+        }
+    }
+
+    pub fn spinner(&self) -> char {
+        let frame = self.started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+        SPINNER[frame as usize % SPINNER.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait(job: &mut Job) -> Result<Outcome> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(outcome) = job.poll() {
+                return outcome;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("job did not finish");
+    }
+
+    #[test]
+    fn test_job() {
+        let mut job = Job::spawn("Import", AppState::KeyList, |reporter| {
+            reporter.progress(1, 2, "id_a");
+            Ok(Outcome::ImportListed(Vec::new()))
+        });
+        assert!(matches!(wait(&mut job), Ok(Outcome::ImportListed(_))));
+        assert_eq!(
+            job.progress,
+            Some(Progress {
+                done: 1,
+                total: 2,
+                detail: "id_a".to_string()
+            })
+        );
+
+        let mut job = Job::spawn("Export", AppState::KeyList, |_| {
+            Err(SkmError::Encryption("no space left".to_string()))
+        });
+        assert!(wait(&mut job).is_err());
+
+        let mut job = Job::spawn("Export", AppState::KeyList, |_| panic!("boom"));
+        assert!(
+            wait(&mut job)
+                .unwrap_err()
+                .to_string()
+                .contains("stopped unexpectedly")
+        );
+    }
+}
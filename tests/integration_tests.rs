@@ -66,6 +66,7 @@ fn test_export_import_roundtrip() {
         description: Some("Test backup".to_string()),
         include_public_only: false,
         selected_keys: None,
+        armor: false,
     };
 
     manager
@@ -82,6 +83,7 @@ fn test_export_import_roundtrip() {
     let import_opts = ImportOptions {
         merge_strategy: MergeStrategy::SkipExisting,
         dry_run: false,
+        selected_keys: None,
     };
 
     let report = import_manager